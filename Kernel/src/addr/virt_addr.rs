@@ -63,6 +63,13 @@ impl VirtAddr /* Constructors */ {
         Self { m_hw_virt_addr: HwVirtAddr::from_2mib_indexes(l4_index, l3_index,
                                                              l2_index) }
     }
+
+    /**
+     * Constructs the `VirtAddr` from the given `PageTableIndex`
+     */
+    pub fn from_1gib_indexes(l4_index: PageTableIndex, l3_index: PageTableIndex) -> Self {
+        Self { m_hw_virt_addr: HwVirtAddr::from_1gib_indexes(l4_index, l3_index) }
+    }
 }
 
 impl VirtAddr /* Getters */ {
@@ -195,5 +202,7 @@ pub trait THwVirtAddr: THwAddr {
                          l2_index: PageTableIndex)
                          -> Self;
 
+    fn from_1gib_indexes(l4_index: PageTableIndex, l3_index: PageTableIndex) -> Self;
+
     fn raw_table_index_for_level(&self, page_table_level: PageTableLevel) -> u16;
 }
@@ -0,0 +1,293 @@
+/*! ACPI Fixed ACPI Description Table */
+
+use core::{
+    hint,
+    ptr::write_volatile,
+    slice
+};
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        TAddress
+    },
+    arch::x86_64::{
+        acpi::{
+            GenericAddress,
+            RootSysDescTable
+        },
+        x64_port::X64Port
+    },
+    dbg_print::DbgLevel,
+    dbg_println,
+    vm::mem_manager::MemManager
+};
+
+/* offsets of the fields added by the ACPI 2.0 revision of the table */
+const C_RESET_REG_OFFSET: usize = 116;
+const C_RESET_VALUE_OFFSET: usize = 128;
+const C_X_DSDT_OFFSET: usize = 140;
+
+/* <m_flags> bit which tells that the reset register is supported */
+const C_FLAG_RESET_REG_SUP: usize = 10;
+
+/* PM1 control register bits */
+const C_PM1_CNT_SCI_EN: usize = 0;
+const C_PM1_CNT_SLP_EN: u16 = 1 << 13;
+
+/* AML opcodes needed to decode the \_S5 package */
+const C_AML_NAME_OP: u8 = 0x08;
+const C_AML_PACKAGE_OP: u8 = 0x12;
+const C_AML_BYTE_PREFIX: u8 = 0x0a;
+
+/**
+ * Parsed FADT, which describes the fixed power management registers
+ */
+#[derive(Copy, Clone)]
+pub struct Fadt {
+    m_smi_cmd_port: u16,
+    m_acpi_enable: u8,
+    m_pm1a_cnt_port: u16,
+    m_pm1b_cnt_port: u16,
+    m_reset_reg: Option<(GenericAddress, u8)>,
+    m_s5_sleep_types: Option<(u16, u16)>,
+    m_century_cmos_reg: u8
+}
+
+impl Fadt /* Constants */ {
+    pub const SIGNATURE: &'static [u8; 4] = b"FACP";
+}
+
+impl Fadt /* Constructors */ {
+    /**
+     * Parses the given checksum validated table and the \_S5 sleeping
+     * state object of its DSDT
+     */
+    pub fn parse(fadt: &'static RootSysDescTable) -> Option<Self> {
+        let fadt_fields = fadt.read_at::<FixedAcpiDescTable>(0)?;
+
+        /* the reset register exists only from the ACPI 2.0 */
+        let reset_reg = if fadt_fields.m_flags.bit_at(C_FLAG_RESET_REG_SUP) {
+            fadt.read_at::<GenericAddress>(C_RESET_REG_OFFSET)
+                .zip(fadt.read_at::<u8>(C_RESET_VALUE_OFFSET))
+                .filter(|(reset_reg, _)| !reset_reg.is_null())
+        } else {
+            None
+        };
+
+        /* prefer the 64bit address of the DSDT when available */
+        let dsdt_phys_addr = fadt.read_at::<u64>(C_X_DSDT_OFFSET)
+                                 .filter(|x_dsdt| *x_dsdt != 0)
+                                 .unwrap_or(fadt_fields.m_dsdt as u64);
+        let s5_sleep_types = if dsdt_phys_addr != 0 {
+            Self::find_s5_sleep_types(RootSysDescTable::at_phys_addr(PhysAddr::from(
+                dsdt_phys_addr as usize
+            )))
+        } else {
+            None
+        };
+
+        Some(Self { m_smi_cmd_port: fadt_fields.m_smi_cmd as u16,
+                    m_acpi_enable: fadt_fields.m_acpi_enable,
+                    m_pm1a_cnt_port: fadt_fields.m_pm1a_cnt_blk as u16,
+                    m_pm1b_cnt_port: fadt_fields.m_pm1b_cnt_blk as u16,
+                    m_reset_reg: reset_reg,
+                    m_s5_sleep_types: s5_sleep_types,
+                    m_century_cmos_reg: fadt_fields.m_century })
+    }
+}
+
+impl Fadt /* Methods */ {
+    /**
+     * Writes the reset value into the reset register.
+     *
+     * Returns only if the reset register is not supported
+     */
+    pub fn reset(&self) {
+        let (reset_reg, reset_value) = if let Some(reset_reg) = self.m_reset_reg {
+            reset_reg
+        } else {
+            return;
+        };
+
+        match reset_reg.addr_space() {
+            GenericAddress::ADDR_SPACE_SYSTEM_IO => unsafe {
+                X64Port::<u8>::new(reset_reg.address() as u16).write(reset_value);
+            },
+            GenericAddress::ADDR_SPACE_SYSTEM_MEMORY => {
+                let reset_reg_phys_addr = PhysAddr::from(reset_reg.address() as usize);
+                let mmio_range = MemManager::instance()
+                    .map_mmio_region(reset_reg_phys_addr..reset_reg_phys_addr.offset(1));
+                if let Some(mmio_range) = mmio_range {
+                    unsafe {
+                        write_volatile(mmio_range.start.as_ptr_mut::<u8>(), reset_value);
+                    }
+                }
+            },
+            addr_space => {
+                dbg_println!(DbgLevel::Warn,
+                             "Unsupported ACPI reset register address space {}",
+                             addr_space)
+            }
+        }
+    }
+
+    /**
+     * Enters the S5 (soft-off) sleeping state, enabling the ACPI mode
+     * first when needed.
+     *
+     * Returns only if the S5 sleeping state is not supported
+     */
+    pub fn power_off(&self) {
+        let (slp_typ_a, slp_typ_b) = match self.m_s5_sleep_types {
+            Some(s5_sleep_types) if self.m_pm1a_cnt_port != 0 => s5_sleep_types,
+            _ => return
+        };
+
+        unsafe {
+            /* the firmware gives the control of the registers only in ACPI mode */
+            let pm1a_cnt = X64Port::<u16>::new(self.m_pm1a_cnt_port);
+            if !pm1a_cnt.read().bit_at(C_PM1_CNT_SCI_EN)
+               && self.m_smi_cmd_port != 0
+               && self.m_acpi_enable != 0
+            {
+                X64Port::<u8>::new(self.m_smi_cmd_port).write(self.m_acpi_enable);
+                while !pm1a_cnt.read().bit_at(C_PM1_CNT_SCI_EN) {
+                    hint::spin_loop();
+                }
+            }
+
+            pm1a_cnt.write(slp_typ_a << 10 | C_PM1_CNT_SLP_EN);
+            if self.m_pm1b_cnt_port != 0 {
+                X64Port::<u16>::new(self.m_pm1b_cnt_port).write(slp_typ_b << 10
+                                                                | C_PM1_CNT_SLP_EN);
+            }
+        }
+    }
+}
+
+impl Fadt /* Getters */ {
+    /**
+     * Returns whether the machine can be reset through the FADT
+     */
+    pub fn supports_reset(&self) -> bool {
+        self.m_reset_reg.is_some()
+    }
+
+    /**
+     * Returns whether the machine can be powered off through the FADT
+     */
+    pub fn supports_power_off(&self) -> bool {
+        self.m_s5_sleep_types.is_some() && self.m_pm1a_cnt_port != 0
+    }
+
+    /**
+     * Returns the index of the CMOS register which stores the century of
+     * the real time clock, if any
+     */
+    pub fn century_cmos_reg(&self) -> Option<u8> {
+        if self.m_century_cmos_reg != 0 {
+            Some(self.m_century_cmos_reg)
+        } else {
+            None
+        }
+    }
+}
+
+impl Fadt /* Privates */ {
+    /**
+     * Searches into the AML of the DSDT the \_S5 package, which stores the
+     * values to write into the PM1 control registers to power off.
+     *
+     * Only the simple encoding `Name(_S5, Package() { a, b, .. })` emitted
+     * by all the known firmwares is decoded
+     */
+    fn find_s5_sleep_types(dsdt: &'static RootSysDescTable) -> Option<(u16, u16)> {
+        if &dsdt.m_signature != b"DSDT" || !dsdt.is_valid() {
+            dbg_println!(DbgLevel::Warn, "Invalid DSDT, ACPI power off unavailable");
+            return None;
+        }
+
+        let aml =
+            unsafe { slice::from_raw_parts(dsdt.virt_addr().as_ptr::<u8>(), dsdt.len()) };
+        let s5_name_offset = aml.windows(4).position(|name| name == b"_S5_")?;
+
+        /* must be a named object (optionally from the root) defined as package */
+        let name_op_offset = if s5_name_offset > 0 && aml[s5_name_offset - 1] == b'\\' {
+            s5_name_offset.checked_sub(2)
+        } else {
+            s5_name_offset.checked_sub(1)
+        };
+        let is_name_op =
+            name_op_offset.and_then(|offset| aml.get(offset)) == Some(&C_AML_NAME_OP);
+        if !is_name_op || aml.get(s5_name_offset + 4) != Some(&C_AML_PACKAGE_OP) {
+            return None;
+        }
+
+        /* skip the package length, whose bits 6..8 tell the additional bytes */
+        let pkg_len_offset = s5_name_offset + 5;
+        let pkg_len_bytes = aml.get(pkg_len_offset)?.bits_at(6..8) as usize + 1;
+
+        /* skip the number of elements, then decode the first two integers */
+        let mut element_offset = pkg_len_offset + pkg_len_bytes + 1;
+        let mut next_integer = || {
+            if *aml.get(element_offset)? == C_AML_BYTE_PREFIX {
+                element_offset += 1;
+            }
+            let value = *aml.get(element_offset)? as u16;
+            element_offset += 1;
+            Some(value)
+        };
+
+        let slp_typ_a = next_integer()?;
+        let slp_typ_b = next_integer()?;
+        Some((slp_typ_a, slp_typ_b))
+    }
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct FixedAcpiDescTable {
+    m_header: RootSysDescTable,
+    m_firmware_ctrl: u32,
+    m_dsdt: u32,
+    _reserved: u8,
+    m_preferred_pm_profile: u8,
+    m_sci_intr: u16,
+    m_smi_cmd: u32,
+    m_acpi_enable: u8,
+    m_acpi_disable: u8,
+    m_s4bios_req: u8,
+    m_pstate_cnt: u8,
+    m_pm1a_evt_blk: u32,
+    m_pm1b_evt_blk: u32,
+    m_pm1a_cnt_blk: u32,
+    m_pm1b_cnt_blk: u32,
+    m_pm2_cnt_blk: u32,
+    m_pm_tmr_blk: u32,
+    m_gpe0_blk: u32,
+    m_gpe1_blk: u32,
+    m_pm1_evt_len: u8,
+    m_pm1_cnt_len: u8,
+    m_pm2_cnt_len: u8,
+    m_pm_tmr_len: u8,
+    m_gpe0_blk_len: u8,
+    m_gpe1_blk_len: u8,
+    m_gpe1_base: u8,
+    m_cst_cnt: u8,
+    m_p_lvl2_lat: u16,
+    m_p_lvl3_lat: u16,
+    m_flush_size: u16,
+    m_flush_stride: u16,
+    m_duty_offset: u8,
+    m_duty_width: u8,
+    m_day_alarm: u8,
+    m_mon_alarm: u8,
+    m_century: u8,
+    m_iapc_boot_arch: u16,
+    _reserved2: u8,
+    m_flags: u32
+}
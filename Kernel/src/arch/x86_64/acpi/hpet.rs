@@ -0,0 +1,114 @@
+/*! ACPI High Precision Event Timer table */
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    addr::phys_addr::PhysAddr,
+    arch::x86_64::acpi::{
+        GenericAddress,
+        RootSysDescTable
+    },
+    dbg_print::DbgLevel,
+    dbg_println
+};
+
+/**
+ * Parsed HPET table, which describes the event timer block
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct Hpet {
+    m_base_phys_addr: PhysAddr,
+    m_hpet_number: u8,
+    m_comparators_count: u8,
+    m_is_64bit_counter: bool,
+    m_supports_legacy_replacement: bool,
+    m_min_tick: u16
+}
+
+impl Hpet /* Constants */ {
+    pub const SIGNATURE: &'static [u8; 4] = b"HPET";
+}
+
+impl Hpet /* Constructors */ {
+    /**
+     * Parses the given checksum validated table
+     */
+    pub fn parse(hpet: &'static RootSysDescTable) -> Option<Self> {
+        let hpet_fields = hpet.read_at::<HpetDescTable>(0)?;
+
+        /* the event timer block is always memory mapped */
+        let base_addr = hpet_fields.m_base_addr;
+        if base_addr.addr_space() != GenericAddress::ADDR_SPACE_SYSTEM_MEMORY
+           || base_addr.is_null()
+        {
+            dbg_println!(DbgLevel::Warn, "HPET registers not memory mapped");
+            return None;
+        }
+
+        let event_timer_block_id = hpet_fields.m_event_timer_block_id;
+        Some(Self { m_base_phys_addr: PhysAddr::from(base_addr.address() as usize),
+                    m_hpet_number: hpet_fields.m_hpet_number,
+                    m_comparators_count: event_timer_block_id.bits_at(8..13) as u8 + 1,
+                    m_is_64bit_counter: event_timer_block_id.bit_at(13),
+                    m_supports_legacy_replacement: event_timer_block_id.bit_at(15),
+                    m_min_tick: hpet_fields.m_min_tick })
+    }
+}
+
+impl Hpet /* Getters */ {
+    /**
+     * Returns the physical address of the registers of the timer block
+     */
+    pub fn base_phys_addr(&self) -> PhysAddr {
+        self.m_base_phys_addr
+    }
+
+    /**
+     * Returns the sequence number of this timer block
+     */
+    pub fn hpet_number(&self) -> u8 {
+        self.m_hpet_number
+    }
+
+    /**
+     * Returns the number of comparators of the timer block
+     */
+    pub fn comparators_count(&self) -> u8 {
+        self.m_comparators_count
+    }
+
+    /**
+     * Returns whether the main counter is 64bit wide
+     */
+    pub fn is_64bit_counter(&self) -> bool {
+        self.m_is_64bit_counter
+    }
+
+    /**
+     * Returns whether the timer block can replace the PIT and the RTC
+     */
+    pub fn supports_legacy_replacement(&self) -> bool {
+        self.m_supports_legacy_replacement
+    }
+
+    /**
+     * Returns the minimum number of ticks for the periodic mode without
+     * lost interrupts
+     */
+    pub fn min_tick(&self) -> u16 {
+        self.m_min_tick
+    }
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct HpetDescTable {
+    m_header: RootSysDescTable,
+    m_event_timer_block_id: u32,
+    m_base_addr: GenericAddress,
+    m_hpet_number: u8,
+    m_min_tick: u16,
+    m_page_protection: u8
+}
@@ -0,0 +1,316 @@
+/*! ACPI Multiple APIC Description Table */
+
+use alloc::vec::Vec;
+use core::mem;
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    addr::phys_addr::PhysAddr,
+    arch::x86_64::acpi::RootSysDescTable,
+    dbg_print::DbgLevel,
+    dbg_println
+};
+
+/* types of the MADT entries */
+const C_MADT_LOCAL_APIC: u8 = 0;
+const C_MADT_IO_APIC: u8 = 1;
+const C_MADT_INTR_SOURCE_OVERRIDE: u8 = 2;
+const C_MADT_LOCAL_APIC_ADDR_OVERRIDE: u8 = 5;
+
+/**
+ * Parsed MADT, which enumerates the CPUs and the interrupt controllers
+ */
+#[derive(Debug)]
+pub struct Madt {
+    m_local_apic_phys_addr: PhysAddr,
+    m_has_legacy_pics: bool,
+    m_local_apics: Vec<LocalApicInfo>,
+    m_io_apics: Vec<IoApicInfo>,
+    m_intr_source_overrides: Vec<IntrSourceOverride>
+}
+
+impl Madt /* Constants */ {
+    pub const SIGNATURE: &'static [u8; 4] = b"APIC";
+}
+
+impl Madt /* Constructors */ {
+    /**
+     * Parses the given checksum validated table
+     */
+    pub fn parse(madt: &'static RootSysDescTable) -> Option<Self> {
+        let madt_fields = madt.read_at::<MultipleApicDescTable>(0)?;
+
+        let mut madt_info = Self { m_local_apic_phys_addr:
+                                       PhysAddr::from(madt_fields.m_apic_addr as usize),
+                                   m_has_legacy_pics: madt_fields.m_flags.bit_at(0),
+                                   m_local_apics: Vec::new(),
+                                   m_io_apics: Vec::new(),
+                                   m_intr_source_overrides: Vec::new() };
+
+        /* walk the variable length entries which follow the fixed part */
+        let mut entry_offset = mem::size_of::<MultipleApicDescTable>();
+        while let Some(apic_header) = madt.read_at::<ApicHeader>(entry_offset) {
+            if (apic_header.m_len as usize) < mem::size_of::<ApicHeader>() {
+                dbg_println!(DbgLevel::Warn, "Malformed MADT entry, stop parsing");
+                break;
+            }
+
+            match apic_header.m_type {
+                C_MADT_LOCAL_APIC => {
+                    if let Some(entry) = madt.read_at::<LocalApicEntry>(entry_offset) {
+                        madt_info.m_local_apics
+                                 .push(LocalApicInfo { m_acpi_cpu_id: entry.m_cpu,
+                                                       m_apic_id: entry.m_id,
+                                                       m_flags: entry.m_flags });
+                    }
+                },
+                C_MADT_IO_APIC => {
+                    if let Some(entry) = madt.read_at::<IoApicEntry>(entry_offset) {
+                        madt_info.m_io_apics
+                                 .push(IoApicInfo { m_id: entry.m_id,
+                                                    m_phys_addr:
+                                                        PhysAddr::from(entry.m_address
+                                                                       as usize),
+                                                    m_base_gsi: entry.m_base_gsi });
+                    }
+                },
+                C_MADT_INTR_SOURCE_OVERRIDE => {
+                    if let Some(entry) =
+                        madt.read_at::<ApicInterruptSourceOverrideEntry>(entry_offset)
+                    {
+                        madt_info.m_intr_source_overrides
+                                 .push(IntrSourceOverride { m_source: entry.m_source,
+                                                            m_gsi: entry.m_gsi,
+                                                            m_flags: entry.m_flags });
+                    }
+                },
+                C_MADT_LOCAL_APIC_ADDR_OVERRIDE => {
+                    if let Some(entry) =
+                        madt.read_at::<LocalApicAddrOverrideEntry>(entry_offset)
+                    {
+                        madt_info.m_local_apic_phys_addr =
+                            PhysAddr::from(entry.m_address as usize);
+                    }
+                },
+                _ => { /* not needed yet */ }
+            }
+            entry_offset += apic_header.m_len as usize;
+        }
+        Some(madt_info)
+    }
+}
+
+impl Madt /* Getters */ {
+    /**
+     * Returns the physical address of the local APICs registers
+     */
+    pub fn local_apic_phys_addr(&self) -> PhysAddr {
+        self.m_local_apic_phys_addr
+    }
+
+    /**
+     * Returns whether the legacy 8259 PICs are installed too
+     */
+    pub fn has_legacy_pics(&self) -> bool {
+        self.m_has_legacy_pics
+    }
+
+    /**
+     * Returns the local APICs, one for each CPU
+     */
+    pub fn local_apics(&self) -> &[LocalApicInfo] {
+        self.m_local_apics.as_slice()
+    }
+
+    /**
+     * Returns the I/O APICs
+     */
+    pub fn io_apics(&self) -> &[IoApicInfo] {
+        self.m_io_apics.as_slice()
+    }
+
+    /**
+     * Returns the interrupt source overrides
+     */
+    pub fn intr_source_overrides(&self) -> &[IntrSourceOverride] {
+        self.m_intr_source_overrides.as_slice()
+    }
+}
+
+/**
+ * Local APIC of a CPU described by the MADT
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct LocalApicInfo {
+    m_acpi_cpu_id: u8,
+    m_apic_id: u8,
+    m_flags: u32
+}
+
+impl LocalApicInfo /* Getters */ {
+    /**
+     * Returns the identifier of the CPU into the ACPI namespace
+     */
+    pub fn acpi_cpu_id(&self) -> u8 {
+        self.m_acpi_cpu_id
+    }
+
+    /**
+     * Returns the identifier of the local APIC of the CPU
+     */
+    pub fn apic_id(&self) -> u8 {
+        self.m_apic_id
+    }
+
+    /**
+     * Returns whether the CPU is ready to be started
+     */
+    pub fn is_enabled(&self) -> bool {
+        self.m_flags.bit_at(0)
+    }
+
+    /**
+     * Returns whether the CPU is disabled but could be enabled at runtime
+     */
+    pub fn is_online_capable(&self) -> bool {
+        self.m_flags.bit_at(1)
+    }
+}
+
+/**
+ * I/O APIC described by the MADT
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct IoApicInfo {
+    m_id: u8,
+    m_phys_addr: PhysAddr,
+    m_base_gsi: u32
+}
+
+impl IoApicInfo /* Getters */ {
+    /**
+     * Returns the hardware identifier of the I/O APIC
+     */
+    pub fn id(&self) -> u8 {
+        self.m_id
+    }
+
+    /**
+     * Returns the physical address of the registers of the I/O APIC
+     */
+    pub fn phys_addr(&self) -> PhysAddr {
+        self.m_phys_addr
+    }
+
+    /**
+     * Returns the first global system interrupt handled by the I/O APIC
+     */
+    pub fn base_gsi(&self) -> u32 {
+        self.m_base_gsi
+    }
+}
+
+/**
+ * Remapping of an ISA IRQ to a global system interrupt described by the
+ * MADT
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct IntrSourceOverride {
+    m_source: u8,
+    m_gsi: u32,
+    m_flags: u16
+}
+
+impl IntrSourceOverride /* Getters */ {
+    /**
+     * Returns the overridden ISA IRQ
+     */
+    pub fn source(&self) -> u8 {
+        self.m_source
+    }
+
+    /**
+     * Returns the global system interrupt to which the ISA IRQ is wired
+     */
+    pub fn gsi(&self) -> u32 {
+        self.m_gsi
+    }
+
+    /**
+     * Returns whether the interrupt is active low, the ISA default is
+     * active high
+     */
+    pub fn is_active_low(&self) -> bool {
+        self.m_flags.bits_at(0..2) == 0b11
+    }
+
+    /**
+     * Returns whether the interrupt is level triggered, the ISA default is
+     * edge triggered
+     */
+    pub fn is_level_triggered(&self) -> bool {
+        self.m_flags.bits_at(2..4) == 0b11
+    }
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct MultipleApicDescTable {
+    m_header: RootSysDescTable,
+    m_apic_addr: u32,
+    m_flags: u32
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct ApicHeader {
+    m_type: u8,
+    m_len: u8
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct LocalApicEntry {
+    m_header: ApicHeader,
+    m_cpu: u8,
+    m_id: u8,
+    m_flags: u32
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct IoApicEntry {
+    m_header: ApicHeader,
+    m_id: u8,
+    _reserved: u8,
+    m_address: u32,
+    m_base_gsi: u32
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct ApicInterruptSourceOverrideEntry {
+    m_header: ApicHeader,
+    m_bus: u8,
+    m_source: u8,
+    m_gsi: u32,
+    m_flags: u16
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct LocalApicAddrOverrideEntry {
+    m_header: ApicHeader,
+    _reserved: u16,
+    m_address: u64
+}
@@ -0,0 +1,123 @@
+/*! ACPI PCI Express memory mapped configuration table */
+
+use alloc::vec::Vec;
+use core::{
+    mem,
+    ops::RangeInclusive
+};
+
+use crate::{
+    addr::phys_addr::PhysAddr,
+    arch::x86_64::acpi::RootSysDescTable
+};
+
+/**
+ * Parsed MCFG table, which lists the enhanced configuration access
+ * mechanism (ECAM) regions of the PCI Express segments
+ */
+#[derive(Debug)]
+pub struct Mcfg {
+    m_ecam_regions: Vec<EcamRegion>
+}
+
+impl Mcfg /* Constants */ {
+    pub const SIGNATURE: &'static [u8; 4] = b"MCFG";
+}
+
+impl Mcfg /* Constructors */ {
+    /**
+     * Parses the given checksum validated table
+     */
+    pub fn parse(mcfg: &'static RootSysDescTable) -> Option<Self> {
+        let mut ecam_regions = Vec::new();
+
+        /* the allocation entries follow the header and 8 reserved bytes */
+        let mut entry_offset = mem::size_of::<RootSysDescTable>() + 8;
+        while let Some(entry) = mcfg.read_at::<McfgAllocationEntry>(entry_offset) {
+            let base_phys_addr = PhysAddr::from(entry.m_base_addr as usize);
+            ecam_regions.push(EcamRegion { m_base_phys_addr: base_phys_addr,
+                                           m_segment_group: entry.m_segment_group,
+                                           m_bus_range: entry.m_start_bus
+                                                        ..=entry.m_end_bus });
+            entry_offset += mem::size_of::<McfgAllocationEntry>();
+        }
+        Some(Self { m_ecam_regions: ecam_regions })
+    }
+}
+
+impl Mcfg /* Getters */ {
+    /**
+     * Returns all the `EcamRegion`s
+     */
+    pub fn ecam_regions(&self) -> &[EcamRegion] {
+        self.m_ecam_regions.as_slice()
+    }
+
+    /**
+     * Returns the `EcamRegion` which contains the configuration space of
+     * the given bus of the given segment group
+     */
+    pub fn ecam_region_of(&self, segment_group: u16, bus: u8) -> Option<&EcamRegion> {
+        self.m_ecam_regions.iter().find(|ecam_region| {
+                                      ecam_region.m_segment_group == segment_group
+                                      && ecam_region.m_bus_range.contains(&bus)
+                                  })
+    }
+}
+
+/**
+ * Memory mapped configuration space of a range of PCI Express buses
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct EcamRegion {
+    m_base_phys_addr: PhysAddr,
+    m_segment_group: u16,
+    m_bus_range: RangeInclusive<u8>
+}
+
+impl EcamRegion /* Getters */ {
+    /**
+     * Returns the physical address of the configuration space of the
+     * bus 0, even when it is not part of the region
+     */
+    pub fn base_phys_addr(&self) -> PhysAddr {
+        self.m_base_phys_addr
+    }
+
+    /**
+     * Returns the PCI segment group of the region
+     */
+    pub fn segment_group(&self) -> u16 {
+        self.m_segment_group
+    }
+
+    /**
+     * Returns the buses covered by the region
+     */
+    pub fn bus_range(&self) -> &RangeInclusive<u8> {
+        &self.m_bus_range
+    }
+
+    /**
+     * Returns the physical address of the configuration space of the
+     * given function
+     */
+    pub fn function_phys_addr(&self, bus: u8, device: u8, function: u8) -> PhysAddr {
+        PhysAddr::from(*self.m_base_phys_addr
+                       + ((bus as usize) << 20
+                          | (device as usize) << 15
+                          | (function as usize) << 12))
+    }
+}
+
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+struct McfgAllocationEntry {
+    m_base_addr: u64,
+    m_segment_group: u16,
+    m_start_bus: u8,
+    m_end_bus: u8,
+    _reserved: u32
+}
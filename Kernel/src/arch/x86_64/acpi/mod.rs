@@ -0,0 +1,422 @@
+/*! x86_64 basic ACPI implementation */
+
+use alloc::vec::Vec;
+use core::{
+    mem,
+    ops::Range,
+    ptr,
+    str
+};
+
+use helps::dbg::{
+    C_KIB,
+    C_MIB
+};
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::acpi::{
+        fadt::Fadt,
+        hpet::Hpet,
+        madt::Madt,
+        mcfg::Mcfg
+    },
+    dbg_println,
+    vm::mem_manager::MemManager,
+    DbgLevel
+};
+
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+pub mod mcfg;
+
+static mut SM_ACPI_MANAGER: Option<AcpiManager> = None;
+
+/* size of the RSDP of the revision 0, which has no length field */
+const C_RSDP_V1_SIZE: usize = 20;
+
+/* upper bound of the length of a table, to not sum a corrupted length of GiBs */
+const C_TABLE_MAX_SIZE: usize = 4 * C_MIB;
+
+/**
+ * ACPI tables manager.
+ *
+ * Finds the root table into the BIOS areas and parses, validating their
+ * checksums, the tables needed by the kernel
+ */
+pub struct AcpiManager {
+    m_rsdp: &'static RootSysDescPtr,
+    m_rsdt_tables: Vec<&'static RootSysDescTable>,
+    m_madt: Option<Madt>,
+    m_hpet: Option<Hpet>,
+    m_fadt: Option<Fadt>,
+    m_mcfg: Option<Mcfg>,
+    m_enabled: bool
+}
+
+impl AcpiManager /* Constructors */ {
+    pub fn init_instance() {
+        let rsdp_ptr = Self::find_root_table();
+        if let Some(rsdp_ptr_ref) = unsafe { rsdp_ptr.as_ref() } {
+            /* constructs the ACPI manager then parse the RSDT tables */
+            let mut acpi_manager = Self { m_rsdp: rsdp_ptr_ref,
+                                          m_rsdt_tables: Vec::new(),
+                                          m_madt: None,
+                                          m_hpet: None,
+                                          m_fadt: None,
+                                          m_mcfg: None,
+                                          m_enabled: true };
+            acpi_manager.parse_tables();
+
+            /* store the global ACPI manager */
+            unsafe {
+                SM_ACPI_MANAGER = Some(acpi_manager);
+            }
+        } else {
+            dbg_println!(DbgLevel::Debug, "No ACPI support found!");
+        }
+    }
+}
+
+impl AcpiManager /* Methods */ {
+    /**
+     * Resets the machine through the FADT reset register.
+     *
+     * Returns only if the reset register is not supported
+     */
+    pub fn reset(&self) {
+        if let Some(fadt) = self.m_fadt.as_ref() {
+            fadt.reset();
+        }
+    }
+
+    /**
+     * Enters the S5 (soft-off) sleeping state through the FADT power
+     * management control blocks.
+     *
+     * Returns only if the S5 sleeping state is not supported
+     */
+    pub fn power_off(&self) {
+        if let Some(fadt) = self.m_fadt.as_ref() {
+            fadt.power_off();
+        }
+    }
+}
+
+impl AcpiManager /* Getters */ {
+    /**
+     * Returns the global `AcpiManager` instance
+     */
+    pub fn instance() -> &'static Self {
+        Self::try_instance().expect("Tried to obtain AcpiManager instance without ACPI \
+                                     support")
+    }
+
+    /**
+     * Returns the global `AcpiManager` instance, if ACPI is supported
+     */
+    pub fn try_instance() -> Option<&'static Self> {
+        unsafe { SM_ACPI_MANAGER.as_ref() }
+    }
+
+    /**
+     * Returns the parsed Multiple APIC Description Table
+     */
+    pub fn madt(&self) -> Option<&Madt> {
+        self.m_madt.as_ref()
+    }
+
+    /**
+     * Returns the parsed High Precision Event Timer table
+     */
+    pub fn hpet(&self) -> Option<&Hpet> {
+        self.m_hpet.as_ref()
+    }
+
+    /**
+     * Returns the parsed Fixed ACPI Description Table
+     */
+    pub fn fadt(&self) -> Option<&Fadt> {
+        self.m_fadt.as_ref()
+    }
+
+    /**
+     * Returns the parsed PCI Express memory mapped configuration table
+     */
+    pub fn mcfg(&self) -> Option<&Mcfg> {
+        self.m_mcfg.as_ref()
+    }
+}
+
+impl AcpiManager /* Privates */ {
+    fn parse_tables(&mut self) {
+        dbg_println!(DbgLevel::Debug,
+                     "Parsing ACPI tables from root {}",
+                     VirtAddr::from(self.m_rsdp as *const _ as *const _));
+
+        /* perform the table parsing according to the version */
+        if self.m_rsdp.m_revision == 0 {
+            self.do_parse_tables((self.m_rsdp.m_rsdt_addr as usize).into(),
+                                 mem::size_of::<u32>());
+        } else {
+            self.do_parse_tables((self.m_rsdp.m_xsdt_addr as usize).into(),
+                                 mem::size_of::<u64>());
+        }
+
+        /* interpret the tables needed by the kernel */
+        self.m_madt = self.find_table(Madt::SIGNATURE).and_then(Madt::parse);
+        self.m_hpet = self.find_table(Hpet::SIGNATURE).and_then(Hpet::parse);
+        self.m_fadt = self.find_table(Fadt::SIGNATURE).and_then(Fadt::parse);
+        self.m_mcfg = self.find_table(Mcfg::SIGNATURE).and_then(Mcfg::parse);
+    }
+
+    fn do_parse_tables(&mut self, rsdt_phys_addr: PhysAddr, entry_ptr_size: usize) {
+        let rsdt = RootSysDescTable::at_phys_addr(rsdt_phys_addr);
+        if !rsdt.is_valid() {
+            dbg_println!(DbgLevel::Warn, "Invalid ACPI root table checksum");
+            return;
+        }
+
+        /* the entries are the physical addresses which follow the header, which
+         * <is_valid()> ensures to fit into the table
+         */
+        let header_size = mem::size_of::<RootSysDescTable>();
+        let entries_count = (rsdt.len() - header_size) / entry_ptr_size;
+        for entry_index in 0..entries_count {
+            let entry_offset = header_size + entry_index * entry_ptr_size;
+            let table_phys_addr = if entry_ptr_size == mem::size_of::<u64>() {
+                rsdt.read_at::<u64>(entry_offset).map(|phys_addr| phys_addr as usize)
+            } else {
+                rsdt.read_at::<u32>(entry_offset).map(|phys_addr| phys_addr as usize)
+            };
+
+            let table = RootSysDescTable::at_phys_addr(table_phys_addr.unwrap().into());
+            if table.is_valid() {
+                dbg_println!(DbgLevel::Trace,
+                             "Found ACPI table '{}' ({} bytes)",
+                             table.signature_str(),
+                             table.len());
+                self.m_rsdt_tables.push(table);
+            } else {
+                dbg_println!(DbgLevel::Warn,
+                             "Discarded ACPI table '{}' with invalid checksum",
+                             table.signature_str());
+            }
+        }
+    }
+
+    fn find_table(&self, signature: &[u8; 4]) -> Option<&'static RootSysDescTable> {
+        self.m_rsdt_tables.iter().find(|table| &table.m_signature == signature).copied()
+    }
+
+    fn find_root_table() -> *const RootSysDescPtr {
+        let ext_bios_data_area_range =
+            MemManager::instance().layout_manager()
+                                  .phys_addr_to_virt_addr(0x40e_usize.into())
+                                  .to_range(C_KIB);
+        let bios_area_range =
+            MemManager::instance().layout_manager()
+                                  .phys_addr_to_virt_addr(0xe0000_usize.into())
+                                  .to_range(C_MIB);
+
+        /* first try to find the root-table in the first KiB of the EBDA, then, if
+         * fail, try into the bios data area
+         */
+        if let Some(rsdp_ptr) = Self::find_root_table_in(ext_bios_data_area_range) {
+            rsdp_ptr
+        } else if let Some(rsdp_ptr) = Self::find_root_table_in(bios_area_range) {
+            rsdp_ptr
+        } else {
+            ptr::null()
+        }
+    }
+
+    fn find_root_table_in(virt_addr_range: Range<VirtAddr>)
+                          -> Option<*const RootSysDescPtr> {
+        /* step each 16 bytes to find the RSDP */
+        for virt_addr in virt_addr_range.step_by(16) {
+            let rsdp = unsafe { virt_addr.as_ref::<RootSysDescPtr>() };
+            if &rsdp.m_signature != b"RSD PTR " {
+                continue;
+            }
+
+            /* the first part is always validated, the extended one only from rev 2 */
+            let rsdp_ptr = rsdp as *const RootSysDescPtr as *const u8;
+            let rsdp_len = rsdp.m_len as usize;
+            if is_valid_checksum(rsdp_ptr, C_RSDP_V1_SIZE)
+               && (rsdp.m_revision == 0
+                   || rsdp_len >= mem::size_of::<RootSysDescPtr>()
+                      && is_valid_checksum(rsdp_ptr, rsdp_len))
+            {
+                return Some(virt_addr.as_ptr());
+            }
+        }
+        None
+    }
+}
+
+/**
+ * Returns whether the bytes of the given area sum to zero.
+ *
+ * The areas longer than `C_TABLE_MAX_SIZE` are never valid
+ */
+fn is_valid_checksum(ptr: *const u8, len: usize) -> bool {
+    if len > C_TABLE_MAX_SIZE {
+        return false;
+    }
+
+    let mut sum = 0u8;
+    for i in 0..len {
+        sum = sum.wrapping_add(unsafe { *ptr.add(i) });
+    }
+    sum == 0
+}
+
+#[repr(C)]
+#[repr(packed)]
+struct RootSysDescPtr {
+    m_signature: [u8; 8],
+    m_checksum: u8,
+    m_oem_id: [u8; 6],
+    m_revision: u8,
+    m_rsdt_addr: u32,
+    m_len: u32,
+    m_xsdt_addr: u64,
+    m_xchecksum: u8
+}
+
+/**
+ * Header shared by all the ACPI system description tables
+ */
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+pub struct RootSysDescTable {
+    m_signature: [u8; 4],
+    m_len: u32,
+    m_revision: u8,
+    m_checksum: u8,
+    m_oem_id: [u8; 6],
+    m_oem_table_id: [u8; 8],
+    m_oem_revision: u32,
+    m_creator_id: [u8; 4],
+    m_creator_revision: u32
+}
+
+impl RootSysDescTable /* Constructors */ {
+    /**
+     * Returns the table at the given physical address through the
+     * physical memory mapping
+     */
+    fn at_phys_addr(table_phys_addr: PhysAddr) -> &'static Self {
+        unsafe {
+            MemManager::instance().layout_manager()
+                                  .phys_addr_to_virt_addr(table_phys_addr)
+                                  .as_ref::<Self>()
+        }
+    }
+}
+
+impl RootSysDescTable /* Methods */ {
+    /**
+     * Reads a `T` at the given byte offset from the begin of the table.
+     *
+     * Returns `None` if the `T` exceeds the length of the table
+     */
+    pub fn read_at<T>(&self, offset: usize) -> Option<T>
+        where T: Copy {
+        if offset + mem::size_of::<T>() <= self.len() {
+            let value_ptr = self.virt_addr().offset(offset).as_ptr::<T>();
+            Some(unsafe { ptr::read_unaligned(value_ptr) })
+        } else {
+            None
+        }
+    }
+}
+
+impl RootSysDescTable /* Getters */ {
+    /**
+     * Returns the signature of the table as string
+     */
+    pub fn signature_str(&self) -> &str {
+        str::from_utf8(&self.m_signature).unwrap_or("????")
+    }
+
+    /**
+     * Returns the length in bytes of the table, header included
+     */
+    pub fn len(&self) -> usize {
+        self.m_len as usize
+    }
+
+    /**
+     * Returns the revision of the table
+     */
+    pub fn revision(&self) -> u8 {
+        self.m_revision
+    }
+
+    /**
+     * Returns the `VirtAddr` of the begin of the table
+     */
+    pub fn virt_addr(&self) -> VirtAddr {
+        VirtAddr::from(self as *const Self)
+    }
+
+    /**
+     * Returns whether the table is at least as long as its header and all
+     * its bytes sum to zero
+     */
+    pub fn is_valid(&self) -> bool {
+        self.len() >= mem::size_of::<Self>()
+        && is_valid_checksum(self.virt_addr().as_ptr(), self.len())
+    }
+}
+
+/**
+ * ACPI generic address structure, which describes the position of a
+ * register
+ */
+#[repr(C)]
+#[repr(packed)]
+#[derive(Copy, Clone)]
+pub struct GenericAddress {
+    m_addr_space: u8,
+    m_bit_width: u8,
+    m_bit_offset: u8,
+    m_access_size: u8,
+    m_address: u64
+}
+
+impl GenericAddress /* Constants */ {
+    pub const ADDR_SPACE_SYSTEM_MEMORY: u8 = 0;
+    pub const ADDR_SPACE_SYSTEM_IO: u8 = 1;
+}
+
+impl GenericAddress /* Getters */ {
+    /**
+     * Returns the address space of the register
+     */
+    pub fn addr_space(&self) -> u8 {
+        self.m_addr_space
+    }
+
+    /**
+     * Returns the address of the register into its address space
+     */
+    pub fn address(&self) -> u64 {
+        self.m_address
+    }
+
+    /**
+     * Returns whether the register is not present
+     */
+    pub fn is_null(&self) -> bool {
+        self.m_address == 0
+    }
+}
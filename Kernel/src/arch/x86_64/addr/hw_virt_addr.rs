@@ -59,6 +59,14 @@ impl THwVirtAddr for HwVirtAddr {
         Self::from(raw_virt_addr)
     }
 
+    fn from_1gib_indexes(l4_index: PageTableIndex, l3_index: PageTableIndex) -> Self {
+        let mut raw_virt_addr = 0;
+        raw_virt_addr.set_bits(39..48, l4_index.into());
+        raw_virt_addr.set_bits(30..39, l3_index.into());
+
+        Self::from(raw_virt_addr)
+    }
+
     fn raw_table_index_for_level(&self, page_table_level: PageTableLevel) -> u16 {
         match page_table_level {
             PageTableLevel::Root => self.m_raw_virt_addr.bits_at(39..48) as u16,
@@ -0,0 +1,204 @@
+/*! APIC interrupt controller */
+
+use alloc::vec::Vec;
+
+use crate::{
+    arch::x86_64::{
+        acpi::{
+            madt::IntrSourceOverride,
+            AcpiManager
+        },
+        intr::{
+            C_FIRST_IRQ_INTR_VECTOR,
+            C_FIRST_SYSTEM_INTR_VECTOR
+        },
+        io_apic::{
+            IoApic,
+            RedirectionEntry
+        },
+        local_apic::LocalApic
+    },
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    dbg_print::DbgLevel,
+    dbg_println,
+    intr::{
+        IntrVector,
+        Irq,
+        TInterruptController
+    }
+};
+
+/* <None> until <ApicController::init_instance()> is called */
+static mut SM_APIC_CONTROLLER: Option<ApicController> = None;
+
+/* number of the legacy ISA IRQs */
+const C_ISA_IRQS_COUNT: Irq = 16;
+
+/**
+ * `TInterruptController` which routes the device interrupts through the
+ * I/O APICs to the local APICs of the CPUs.
+ *
+ * Each `Irq` is delivered on the vector `C_FIRST_IRQ_INTR_VECTOR + irq`,
+ * the ISA IRQs are translated to global system interrupts (GSI) with the
+ * interrupt source overrides of the ACPI MADT
+ */
+pub struct ApicController {
+    m_local_apic: LocalApic,
+    m_io_apics: Vec<IoApic>,
+    m_intr_source_overrides: Vec<IntrSourceOverride>
+}
+
+impl ApicController /* Constructors */ {
+    /**
+     * Initializes the global `ApicController` instance with the I/O APICs
+     * described by the ACPI MADT.
+     *
+     * Returns `false` if the local APIC is not initialized or no I/O APIC
+     * is available
+     */
+    pub fn init_instance() -> bool {
+        unsafe {
+            assert!(SM_APIC_CONTROLLER.is_none(),
+                    "Called ApicController::init_instance() twice");
+        }
+
+        if !LocalApic::is_initialized() {
+            return false;
+        }
+        let madt =
+            AcpiManager::try_instance().and_then(|acpi_manager| acpi_manager.madt());
+        let madt = if let Some(madt) = madt {
+            madt
+        } else {
+            return false;
+        };
+
+        /* map the I/O APICs, each one with all the entries masked */
+        let io_apics: Vec<_> = madt.io_apics().iter().filter_map(IoApic::new).collect();
+        if io_apics.is_empty() {
+            dbg_println!(DbgLevel::Warn, "No usable I/O APIC found");
+            return false;
+        }
+        for io_apic in io_apics.iter() {
+            dbg_println!(DbgLevel::Debug,
+                         "I/O APIC {} routes GSIs {:?}",
+                         io_apic.id(),
+                         io_apic.gsi_range());
+        }
+
+        /* the end-of-interrupt register is the same for all the local APICs */
+        let mut local_apic = LocalApic::new();
+        local_apic.enable();
+
+        unsafe {
+            SM_APIC_CONTROLLER = Some(Self { m_local_apic: local_apic,
+                                             m_io_apics: io_apics,
+                                             m_intr_source_overrides:
+                                                 madt.intr_source_overrides().to_vec() });
+        }
+        true
+    }
+}
+
+impl ApicController /* Getters */ {
+    /**
+     * Returns the global `ApicController` instance
+     */
+    pub fn instance() -> &'static Self {
+        unsafe {
+            SM_APIC_CONTROLLER.as_ref().expect("Tried to obtain ApicController instance \
+                                                before initialization")
+        }
+    }
+}
+
+impl ApicController /* Privates */ {
+    /**
+     * Returns the GSI to which the given `Irq` is wired, with its
+     * polarity and trigger mode
+     */
+    fn resolve_irq(&self, irq: Irq) -> (u32, bool, bool) {
+        let intr_source_override =
+            self.m_intr_source_overrides
+                .iter()
+                .find(|intr_source_override| intr_source_override.source() as Irq == irq);
+
+        if let Some(intr_source_override) = intr_source_override {
+            (intr_source_override.gsi(),
+             intr_source_override.is_active_low(),
+             intr_source_override.is_level_triggered())
+        } else if irq < C_ISA_IRQS_COUNT {
+            /* identity mapped, active high and edge triggered */
+            (irq, false, false)
+        } else {
+            /* the PCI interrupts are active low and level triggered */
+            (irq, true, true)
+        }
+    }
+
+    /**
+     * Returns the `IoApic` which routes the given GSI
+     */
+    fn io_apic_of(&self, gsi: u32) -> Option<&IoApic> {
+        self.m_io_apics.iter().find(|io_apic| io_apic.handles_gsi(gsi))
+    }
+}
+
+impl TInterruptController for ApicController {
+    fn name(&self) -> &'static str {
+        "I/O APIC"
+    }
+
+    fn intr_vector_of(&self, irq: Irq) -> Option<IntrVector> {
+        let intr_vector = C_FIRST_IRQ_INTR_VECTOR as Irq + irq;
+
+        /* the highest vectors are reserved to the local APIC */
+        if intr_vector < C_FIRST_SYSTEM_INTR_VECTOR as Irq {
+            Some(intr_vector as IntrVector)
+        } else {
+            None
+        }
+    }
+
+    fn route(&self, irq: Irq, cpu_id: CpuId) -> bool {
+        let intr_vector = if let Some(intr_vector) = self.intr_vector_of(irq) {
+            intr_vector
+        } else {
+            return false;
+        };
+
+        let (gsi, is_active_low, is_level_triggered) = self.resolve_irq(irq);
+        if let Some(io_apic) = self.io_apic_of(gsi) {
+            let apic_id = Cpu::by_id(cpu_id).hw_cpu().apic_id();
+            io_apic.write_redirection(gsi,
+                                      RedirectionEntry::new(intr_vector,
+                                                            apic_id,
+                                                            is_active_low,
+                                                            is_level_triggered));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn mask(&self, irq: Irq) {
+        let (gsi, ..) = self.resolve_irq(irq);
+        if let Some(io_apic) = self.io_apic_of(gsi) {
+            io_apic.set_masked(gsi, true);
+        }
+    }
+
+    fn unmask(&self, irq: Irq) {
+        let (gsi, ..) = self.resolve_irq(irq);
+        if let Some(io_apic) = self.io_apic_of(gsi) {
+            io_apic.set_masked(gsi, false);
+        }
+    }
+
+    fn end_of_interrupt(&self, _intr_vector: IntrVector) {
+        self.m_local_apic.end_of_interrupt();
+    }
+}
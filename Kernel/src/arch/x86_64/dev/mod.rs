@@ -8,7 +8,11 @@ use crate::{
         },
         hw_uart::X64Serial16550Uart
     },
-    dev::DevManager
+    dev::{
+        random::TRandomDevice,
+        DevManager,
+        TDevice
+    }
 };
 
 pub mod hw_random;
@@ -45,3 +49,19 @@ impl DevManager /* Methods */ {
                 "Failed to register Serial COM4 driver");
     }
 }
+
+impl DevManager /* Static Functions */ {
+    /**
+     * Returns a random `u64` from the same source of the registered random
+     * device, without registering it, for the boot code which runs before
+     * the heap exists
+     */
+    pub fn early_random_u64() -> u64 {
+        let rdrand_random = X64RdRandRandom::new(0);
+        if rdrand_random.init_hw() {
+            rdrand_random.random_u64()
+        } else {
+            X64RdTscRandom::new(0).random_u64()
+        }
+    }
+}
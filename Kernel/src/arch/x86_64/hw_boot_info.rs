@@ -18,7 +18,10 @@ use crate::{
         phys_addr::PhysAddr,
         TAddress
     },
-    boot_info::THwBootInfo
+    boot_info::{
+        BootModule,
+        THwBootInfo
+    }
 };
 
 /**
@@ -73,6 +76,18 @@ impl THwBootInfo for HwBootInfo {
             })
             .expect("Bootloader doesn't provide memory areas")
     }
+
+    fn boot_modules(&self) -> Vec<BootModule> {
+        self.m_multiboot_ptr
+            .module_tags()
+            .map(|module_tag| {
+                let phys_range = PhysAddr::from(module_tag.start_address() as usize)
+                                 ..PhysAddr::from(module_tag.end_address() as usize);
+
+                BootModule::new(phys_range, module_tag.name())
+            })
+            .collect()
+    }
 }
 
 impl From<*const u8> for HwBootInfo {
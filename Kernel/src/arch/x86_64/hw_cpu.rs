@@ -1,8 +1,20 @@
 /*! x86_64 CPU management implementation */
 
-use core::arch::x86_64::{
-    CpuidResult,
-    __cpuid
+use alloc::vec::Vec;
+use core::{
+    arch::x86_64::{
+        __cpuid,
+        CpuidResult
+    },
+    cell::{
+        Cell,
+        UnsafeCell
+    },
+    ptr,
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
 };
 
 use bits::bit_fields::TBitFields;
@@ -14,79 +26,205 @@ use crate::{
     },
     arch::x86_64::{
         acpi::AcpiManager,
+        apic_controller::ApicController,
         gdt::{
             GlobalDescTable,
             Segment
         },
         idt::IntrDescTable,
+        intr::{
+            hw_intr_stub_virt_addr,
+            C_DOUBLE_FAULT_INTR_VECTOR,
+            C_MACHINE_CHECK_INTR_VECTOR,
+            C_NMI_INTR_VECTOR,
+            C_PAGE_FAULT_INTR_VECTOR
+        },
         local_apic::LocalApic,
-        tss::TaskStateSegment
+        ms_register::{
+            FsBaseRegister,
+            GsBaseRegister
+        },
+        pic::PicManager,
+        smp::{
+            hw_halt_other_cpus,
+            hw_send_resched_ipi,
+            hw_start_ap,
+            C_MAX_CPUS
+        },
+        syscall::hw_syscall_init,
+        tss::TaskStateSegment,
+        x64_port::X64Port
     },
     cpu::{
         CpuId,
         THwCpu
-    }
+    },
+    intr::IntrManager
 };
 
-const C_DOUBLE_FAULT_STACK: usize = 4096;
+/* the exceptions which could arrive with a corrupted stack run on their own.
+ * The page faults too, otherwise a fault on a guard page or on a not yet
+ * backed page of a kernel stack could not push its frame and would become a
+ * double fault
+ */
+const C_INTR_STACK_SIZE: usize = 8192;
+const C_INTR_STACKS_COUNT: usize = 4;
 const C_DOUBLE_FAULT_STACK_INDEX: usize = 0;
+const C_NMI_STACK_INDEX: usize = 1;
+const C_MACHINE_CHECK_STACK_INDEX: usize = 2;
+const C_PAGE_FAULT_STACK_INDEX: usize = 3;
+
+/* command port of the legacy keyboard controller and its CPU reset command */
+const C_KBD_CONTROLLER_PORT: u16 = 0x64;
+const C_KBD_CONTROLLER_RESET: u8 = 0xfe;
 
 /**
  * x86_64 `HwCpuBase` implementation
  */
 pub struct HwCpu {
+    m_cpu_id: CpuId,
+    m_apic_id: u8,
     m_is_ap: bool,
+    m_is_online: AtomicBool,
+    m_per_cpu_data: HwPerCpuData,
     m_gdt: GlobalDescTable,
-    m_tss: TaskStateSegment,
+    m_tss: UnsafeCell<TaskStateSegment>,
     m_idt: IntrDescTable,
     m_local_apic: LocalApic,
-    m_double_fault_stack: [u8; C_DOUBLE_FAULT_STACK]
+    m_intr_stacks: [[u8; C_INTR_STACK_SIZE]; C_INTR_STACKS_COUNT]
+}
+
+impl HwCpu /* Getters */ {
+    /**
+     * Returns the identifier of the local APIC of this CPU
+     */
+    pub fn apic_id(&self) -> u8 {
+        self.m_apic_id
+    }
+
+    /**
+     * Returns the `LocalApic` of this CPU
+     */
+    pub fn local_apic(&self) -> &LocalApic {
+        &self.m_local_apic
+    }
 }
 
 impl HwCpu /* Privates */ {
     fn cpu_frequency_info(&self) -> CpuidResult {
         unsafe { __cpuid(0x16) }
     }
+
+    /**
+     * Constructs an `HwCpu` with the given identifiers
+     */
+    fn new(cpu_id: CpuId, apic_id: u8, is_ap: bool) -> Self {
+        Self { m_cpu_id: cpu_id,
+               m_apic_id: apic_id,
+               m_is_ap: is_ap,
+               m_is_online: AtomicBool::new(false),
+               m_per_cpu_data: HwPerCpuData { m_self_ptr: ptr::null(),
+                                              m_kern_stack_top: Cell::new(0),
+                                              m_user_stack_ptr: 0,
+                                              m_cpu_id: cpu_id },
+               m_gdt: GlobalDescTable::new(),
+               m_tss: UnsafeCell::new(TaskStateSegment::new()),
+               m_idt: IntrDescTable::new(),
+               m_local_apic: LocalApic::new(),
+               m_intr_stacks: [[0; C_INTR_STACK_SIZE]; C_INTR_STACKS_COUNT] }
+    }
+
+    /**
+     * Returns the local APIC identifiers of the enabled APs listed by the
+     * MADT, in the order in which they are numbered
+     */
+    fn aps_apic_ids() -> Vec<u8> {
+        /* without the APIC the other CPUs cannot be waken up */
+        let madt =
+            AcpiManager::try_instance().and_then(|acpi_manager| acpi_manager.madt());
+        let madt = match madt {
+            Some(madt) if LocalApic::is_initialized() => madt,
+            _ => return Vec::new()
+        };
+
+        let bsp_apic_id = Self::this_apic_id();
+        madt.local_apics()
+            .iter()
+            .filter(|local_apic| {
+                local_apic.is_enabled() && local_apic.apic_id() != bsp_apic_id
+            })
+            .map(|local_apic| local_apic.apic_id())
+            .take(C_MAX_CPUS - 1)
+            .collect()
+    }
+
+    /**
+     * Returns the initial local APIC identifier of the executing CPU, which
+     * is available before the mapping of the local APIC
+     */
+    fn this_apic_id() -> u8 {
+        /* CPUID.01H:EBX.InitialApicId[bits 24..32] */
+        unsafe { __cpuid(0x01) }.ebx.bits_at(24..32) as u8
+    }
+
+    /**
+     * Selects the APIC as interrupt controller, falling back to the legacy
+     * PICs when the local APIC or the I/O APICs are not available
+     */
+    fn init_intr_controller() {
+        /* the PICs are remapped anyway, their spurious vectors would be exceptions */
+        PicManager::init_instance();
+
+        if LocalApic::init_apic() && ApicController::init_instance() {
+            unsafe {
+                PicManager::instance().disable();
+            }
+            IntrManager::set_controller(ApicController::instance());
+        } else {
+            IntrManager::set_controller(PicManager::instance());
+        }
+    }
 }
 
 impl THwCpu for HwCpu {
     fn new_bsp() -> Self {
-        Self { m_is_ap: false,
-               m_gdt: GlobalDescTable::new(),
-               m_tss: TaskStateSegment::new(),
-               m_idt: IntrDescTable {},
-               m_local_apic: LocalApic::new(),
-               m_double_fault_stack: [0; C_DOUBLE_FAULT_STACK] }
+        Self::new(0, Self::this_apic_id(), false)
     }
 
-    fn new_ap() -> Self {
-        Self { m_is_ap: true,
-               m_gdt: GlobalDescTable::new(),
-               m_tss: TaskStateSegment::new(),
-               m_idt: IntrDescTable {},
-               m_local_apic: LocalApic::new(),
-               m_double_fault_stack: [0; C_DOUBLE_FAULT_STACK] }
+    fn new_ap(cpu_id: CpuId) -> Self {
+        /* the APs are numbered after the BSP */
+        let ap_apic_id = Self::aps_apic_ids()[cpu_id as usize - 1];
+
+        Self::new(cpu_id, ap_apic_id, true)
+    }
+
+    fn aps_count() -> usize {
+        Self::aps_apic_ids().len()
     }
 
     fn init(&'static mut self) {
-        /* set the double fault stack pointer into the TSS */
-        self.m_tss.m_full_intr_stack_table[C_DOUBLE_FAULT_STACK_INDEX] = {
-            /* obtain the <VirtAddr> of the static buffer */
-            let double_fault_stack_virt_addr =
-                VirtAddr::from(self.m_double_fault_stack.as_mut_ptr());
-
-            /* return the bottom of the area */
-            double_fault_stack_virt_addr.offset(C_DOUBLE_FAULT_STACK)
-        };
+        /* make the per-CPU data reachable through the GS segment */
+        self.m_per_cpu_data.m_self_ptr = &self.m_per_cpu_data;
+        unsafe {
+            GsBaseRegister::write(VirtAddr::from(self.m_per_cpu_data.m_self_ptr));
+        }
 
-        /* setup the GDT segments */
+        /* set the interrupt stacks pointers into the TSS */
+        for (intr_stack_index, intr_stack) in self.m_intr_stacks.iter_mut().enumerate() {
+            /* the stacks grow down, store the bottom of the area */
+            self.m_tss.get_mut().m_full_intr_stack_table[intr_stack_index] =
+                VirtAddr::from(intr_stack.as_mut_ptr()).offset(C_INTR_STACK_SIZE);
+        }
+
+        /* setup the GDT segments, <sysret> wants the user data before the code */
         let kern_code_segment_selector =
             self.m_gdt.add_entry(Segment::kernel_code_segment());
         self.m_gdt.add_entry(Segment::kernel_data_segment());
+        let user_data_segment_selector =
+            self.m_gdt.add_entry(Segment::user_data_segment());
         self.m_gdt.add_entry(Segment::user_code_segment());
-        self.m_gdt.add_entry(Segment::user_data_segment());
         let tss_segment_selector =
-            self.m_gdt.add_entry(Segment::tss_segment(&self.m_tss));
+            self.m_gdt.add_entry(Segment::tss_segment(unsafe { &*self.m_tss.get() }));
 
         /* load the GDT, reload the code-segment register (CS) and load the TSS */
         self.m_gdt.load();
@@ -109,19 +247,49 @@ impl THwCpu for HwCpu {
                  in(reg) tss_segment_selector.as_raw(),
                  options(nomem, nostack, preserves_flags));
         }
+
+        /* install the interrupt stubs and load the IDT */
+        for intr_vector in 0..=u8::MAX {
+            let intr_stack_index = match intr_vector {
+                C_DOUBLE_FAULT_INTR_VECTOR => Some(C_DOUBLE_FAULT_STACK_INDEX),
+                C_NMI_INTR_VECTOR => Some(C_NMI_STACK_INDEX),
+                C_MACHINE_CHECK_INTR_VECTOR => Some(C_MACHINE_CHECK_STACK_INDEX),
+                C_PAGE_FAULT_INTR_VECTOR => Some(C_PAGE_FAULT_STACK_INDEX),
+                _ => None
+            };
+            self.m_idt.set_handler(intr_vector,
+                                   hw_intr_stub_virt_addr(intr_vector),
+                                   kern_code_segment_selector,
+                                   intr_stack_index);
+        }
+        self.m_idt.load();
+
+        /* enable the <syscall> entry into the Kernel */
+        hw_syscall_init(kern_code_segment_selector, user_data_segment_selector);
     }
 
     fn init_interrupts(&'static mut self) {
-        LocalApic::init_apic();
-        AcpiManager::init_instance();
+        /* the interrupt controllers are shared, initialize them once */
+        if !self.m_is_ap {
+            AcpiManager::init_instance();
+            Self::init_intr_controller();
+        }
 
-        // if !self.m_is_ap {
-        //     PicManager::init_instance();
-        //
-        //     /* initialize the APIC */
-        //     LocalApic::init_apic();
-        // }
-        // self.m_local_apic.enable();
+        /* each CPU enables its own local APIC */
+        if LocalApic::is_initialized() {
+            self.m_local_apic.enable();
+        }
+
+        /* from now the CPU can serve the inter-processor interrupts */
+        self.m_is_online.store(true, Ordering::SeqCst);
+    }
+
+    fn start(&'static self) -> bool {
+        hw_start_ap(self.m_apic_id, self.m_cpu_id, &self.m_is_online)
+    }
+
+    fn halt_others() {
+        hw_halt_other_cpus();
     }
 
     fn do_halt(&self) {
@@ -130,6 +298,44 @@ impl THwCpu for HwCpu {
         }
     }
 
+    fn do_reboot() {
+        if let Some(acpi_manager) = AcpiManager::try_instance() {
+            acpi_manager.reset();
+        }
+
+        /* fall back to the reset line of the legacy keyboard controller */
+        unsafe {
+            X64Port::<u8>::new(C_KBD_CONTROLLER_PORT).write(C_KBD_CONTROLLER_RESET);
+        }
+    }
+
+    fn do_power_off() {
+        if let Some(acpi_manager) = AcpiManager::try_instance() {
+            acpi_manager.power_off();
+        }
+    }
+
+    fn notify_reschedule(&self) {
+        if self.m_cpu_id != Self::current_id() && self.is_online() {
+            hw_send_resched_ipi(self.m_apic_id);
+        }
+    }
+
+    fn set_kern_stack_top(&self, stack_top: VirtAddr) {
+        self.m_per_cpu_data.m_kern_stack_top.set(*stack_top);
+
+        /* the hardware reads the TSS only at the privilege changes */
+        unsafe {
+            (*self.m_tss.get()).m_stacks_per_privilege[0] = stack_top;
+        }
+    }
+
+    fn set_user_thread_ptr(&self, thread_ptr: VirtAddr) {
+        unsafe {
+            FsBaseRegister::write(thread_ptr);
+        }
+    }
+
     fn do_enable_interrupts(&self) {
         unsafe {
             asm!("sti", options(nostack));
@@ -142,16 +348,27 @@ impl THwCpu for HwCpu {
         }
     }
 
+    fn do_wait_interrupt(&self) {
+        unsafe {
+            asm!("sti; hlt", options(nomem, nostack));
+        }
+    }
+
     fn current_id() -> CpuId {
-        0 /* TODO APIC */
+        HwPerCpuData::current().m_cpu_id
+    }
+
+    fn supports_1gib_pages() -> bool {
+        /* CPUID.80000001H:EDX.Page1GB[bit 26] */
+        unsafe { __cpuid(0x8000_0001) }.edx.bit_at(26)
     }
 
     fn id(&self) -> CpuId {
-        if self.m_local_apic.is_enabled() {
-            self.m_local_apic.cpu_id()
-        } else {
-            0
-        }
+        self.m_cpu_id
+    }
+
+    fn is_online(&self) -> bool {
+        self.m_is_online.load(Ordering::SeqCst)
     }
 
     fn base_frequency(&self) -> u64 {
@@ -174,3 +391,33 @@ impl THwCpu for HwCpu {
         rflags.bit_at(9)
     }
 }
+
+/**
+ * Per-CPU data block, pointed by the `GsBaseRegister` of each CPU.
+ *
+ * The first field points to the block itself, to obtain its address with
+ * a single `gs` relative load, the next two are used by the `syscall`
+ * entry, keep their offsets in sync with `syscall_entry.S`
+ */
+#[repr(C)]
+struct HwPerCpuData {
+    m_self_ptr: *const HwPerCpuData,
+    m_kern_stack_top: Cell<usize>,
+    m_user_stack_ptr: usize,
+    m_cpu_id: CpuId
+}
+
+impl HwPerCpuData /* Static Functions */ {
+    /**
+     * Returns the `HwPerCpuData` of the executing CPU
+     */
+    fn current() -> &'static Self {
+        let self_ptr: *const Self;
+        unsafe {
+            asm!("mov {}, gs:[0]",
+                 out(reg) self_ptr,
+                 options(nostack, readonly, preserves_flags));
+            &*self_ptr
+        }
+    }
+}
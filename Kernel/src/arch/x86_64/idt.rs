@@ -1,7 +1,136 @@
 /*! x86_64 Interrupt descriptor table */
 
+use core::mem::size_of;
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    arch::x86_64::{
+        desc_table::{
+            CpuRingMode,
+            DescTablePtr
+        },
+        gdt::SegmentSelector
+    }
+};
+
+/**
+ * Number of the interrupt vectors supported by the x86_64 CPUs
+ */
+pub const C_INTR_VECTORS_COUNT: usize = 256;
+
+/**
+ * x86_64 IDT
+ */
 #[repr(C)]
 #[repr(align(16))]
 #[derive(Debug)]
 #[derive(Clone)]
-pub struct IntrDescTable {/* TODO fields */}
+pub struct IntrDescTable {
+    m_gates: [IntrGate; C_INTR_VECTORS_COUNT]
+}
+
+impl IntrDescTable /* Constructors */ {
+    /**
+     * Constructs an `IntrDescTable` with all the gates non-present
+     */
+    pub const fn new() -> Self {
+        Self { m_gates: [IntrGate::new_missing(); C_INTR_VECTORS_COUNT] }
+    }
+}
+
+impl IntrDescTable /* Methods */ {
+    /**
+     * Installs the given `handler_virt_addr` as interrupt gate for the
+     * given `intr_vector`.
+     *
+     * When `intr_stack_index` is given the CPU switches to the stack stored
+     * at that index of the `TaskStateSegment`'s interrupt stack table
+     */
+    pub fn set_handler(&mut self,
+                       intr_vector: u8,
+                       handler_virt_addr: VirtAddr,
+                       code_segment_selector: SegmentSelector,
+                       intr_stack_index: Option<usize>) {
+        self.m_gates[intr_vector as usize] = IntrGate::new(handler_virt_addr,
+                                                           code_segment_selector,
+                                                           intr_stack_index,
+                                                           CpuRingMode::Ring0);
+    }
+
+    /**
+     * Loads into the current CPU this IDT
+     */
+    pub fn load(&'static self) {
+        unsafe {
+            asm!("lidt [{}]",
+            in(reg) &self.table_ptr(),
+            options(readonly, nostack, preserves_flags));
+        }
+    }
+
+    /**
+     * Returns the `DescTablePtr` for this IDT
+     */
+    pub fn table_ptr(&self) -> DescTablePtr {
+        DescTablePtr::new((C_INTR_VECTORS_COUNT * size_of::<IntrGate>() - 1) as u16,
+                          self.m_gates.as_ptr().into())
+    }
+}
+
+/**
+ * x86_64 64bit interrupt gate descriptor
+ */
+#[repr(C)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+struct IntrGate {
+    m_handler_low: u16,
+    m_code_selector: u16,
+    m_options: u16,
+    m_handler_mid: u16,
+    m_handler_high: u32,
+    m_reserved: u32
+}
+
+impl IntrGate /* Constructors */ {
+    /**
+     * Constructs a present `IntrGate` which jumps to `handler_virt_addr`
+     */
+    fn new(handler_virt_addr: VirtAddr,
+           code_segment_selector: SegmentSelector,
+           intr_stack_index: Option<usize>,
+           cpu_ring_mode: CpuRingMode)
+           -> Self {
+        let raw_handler = *handler_virt_addr;
+
+        /* the interrupt stack table indexes start from 1 into the gates */
+        let mut gate_options = 0u16;
+        if let Some(intr_stack_index) = intr_stack_index {
+            gate_options.set_bits(0..3, intr_stack_index as u16 + 1);
+        }
+        gate_options.set_bits(8..12, 0b1110); /* 64bit interrupt gate */
+        gate_options.set_bits(13..15, cpu_ring_mode as u16);
+        gate_options.set_bit(15, true); /* present */
+
+        Self { m_handler_low: raw_handler as u16,
+               m_code_selector: code_segment_selector.as_raw() as u16,
+               m_options: gate_options,
+               m_handler_mid: (raw_handler >> 16) as u16,
+               m_handler_high: (raw_handler >> 32) as u32,
+               m_reserved: 0 }
+    }
+
+    /**
+     * Constructs a non-present `IntrGate`
+     */
+    const fn new_missing() -> Self {
+        Self { m_handler_low: 0,
+               m_code_selector: 0,
+               m_options: 0,
+               m_handler_mid: 0,
+               m_handler_high: 0,
+               m_reserved: 0 }
+    }
+}
@@ -0,0 +1,311 @@
+/*! x86_64 interrupts dispatching */
+
+use bits::bit_fields::TBitFields;
+use symbols::code_symbols::CodeSymbols;
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::smp::hw_is_halting,
+    dbg_print::DbgLevel,
+    dbg_println,
+    intr::{
+        IntrFrame,
+        IntrManager,
+        IntrVector,
+        THwIntrFrame
+    },
+    task::sched::Scheduler,
+    vm::page_fault::PageFault
+};
+
+/**
+ * Number of the vectors reserved to the CPU exceptions
+ */
+pub const C_EXCEPTIONS_COUNT: usize = 32;
+
+/**
+ * First vector available for the device interrupts
+ */
+pub const C_FIRST_IRQ_INTR_VECTOR: u8 = C_EXCEPTIONS_COUNT as u8;
+
+/**
+ * Vector of the non-maskable interrupt
+ */
+pub const C_NMI_INTR_VECTOR: u8 = 2;
+
+/**
+ * Vector of the breakpoint exception
+ */
+pub const C_BREAKPOINT_INTR_VECTOR: u8 = 3;
+
+/**
+ * Vector of the double fault exception
+ */
+pub const C_DOUBLE_FAULT_INTR_VECTOR: u8 = 8;
+
+/**
+ * Vector of the page fault exception
+ */
+pub const C_PAGE_FAULT_INTR_VECTOR: u8 = 14;
+
+/**
+ * Vector of the machine check exception
+ */
+pub const C_MACHINE_CHECK_INTR_VECTOR: u8 = 18;
+
+/**
+ * First vector reserved to the local APIC, which delivers here its timer,
+ * the inter-processor and the spurious interrupts
+ */
+pub const C_FIRST_SYSTEM_INTR_VECTOR: u8 = 0xf0;
+
+/**
+ * Vector of the one-shot interrupt of the local APIC timer
+ */
+pub const C_APIC_TIMER_INTR_VECTOR: u8 = 0xf0;
+
+/**
+ * Vector of the inter-processor interrupt which asks to check whether the
+ * running thread must be preempted
+ */
+pub const C_RESCHED_INTR_VECTOR: u8 = 0xfd;
+
+/**
+ * Vector of the inter-processor interrupt which asks to invalidate the
+ * TLB entries of a range
+ */
+pub const C_TLB_SHOOTDOWN_INTR_VECTOR: u8 = 0xfe;
+
+/**
+ * Vector of the spurious interrupts of the local APIC, which must not be
+ * acknowledged
+ */
+pub const C_SPURIOUS_INTR_VECTOR: u8 = 0xff;
+
+/* size of each stub of <hw_intr_stubs_table>, keep in sync with intr_stubs.S */
+const C_INTR_STUB_SIZE: usize = 16;
+
+/* names of the CPU exceptions, indexed by vector */
+const C_EXCEPTION_NAMES: [&str; C_EXCEPTIONS_COUNT] = ["Divide Error",
+                                                       "Debug",
+                                                       "Non-Maskable Interrupt",
+                                                       "Breakpoint",
+                                                       "Overflow",
+                                                       "Bound Range Exceeded",
+                                                       "Invalid Opcode",
+                                                       "Device Not Available",
+                                                       "Double Fault",
+                                                       "Coprocessor Segment Overrun",
+                                                       "Invalid TSS",
+                                                       "Segment Not Present",
+                                                       "Stack-Segment Fault",
+                                                       "General Protection Fault",
+                                                       "Page Fault",
+                                                       "Reserved",
+                                                       "x87 Floating-Point Exception",
+                                                       "Alignment Check",
+                                                       "Machine Check",
+                                                       "SIMD Floating-Point Exception",
+                                                       "Virtualization Exception",
+                                                       "Control Protection Exception",
+                                                       "Reserved",
+                                                       "Reserved",
+                                                       "Reserved",
+                                                       "Reserved",
+                                                       "Reserved",
+                                                       "Reserved",
+                                                       "Hypervisor Injection Exception",
+                                                       "VMM Communication Exception",
+                                                       "Security Exception",
+                                                       "Reserved"];
+
+extern "C" {
+    static hw_intr_stubs_table: u8;
+    static hw_syscall_iretq: u8;
+}
+
+/**
+ * CPU state saved by `Kernel/arch/x86_64/intr_stubs.S` on interrupt entry.
+ *
+ * The layout must match the order of the pushes of the stubs
+ */
+#[repr(C)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct HwIntrFrame {
+    pub m_r15: usize,
+    pub m_r14: usize,
+    pub m_r13: usize,
+    pub m_r12: usize,
+    pub m_r11: usize,
+    pub m_r10: usize,
+    pub m_r9: usize,
+    pub m_r8: usize,
+    pub m_rbp: usize,
+    pub m_rdi: usize,
+    pub m_rsi: usize,
+    pub m_rdx: usize,
+    pub m_rcx: usize,
+    pub m_rbx: usize,
+    pub m_rax: usize,
+    pub m_intr_vector: usize,
+    pub m_error_code: usize,
+    pub m_rip: usize,
+    pub m_cs: usize,
+    pub m_rflags: usize,
+    pub m_rsp: usize,
+    pub m_ss: usize
+}
+
+impl THwIntrFrame for HwIntrFrame {
+    fn intr_vector(&self) -> IntrVector {
+        self.m_intr_vector as IntrVector
+    }
+
+    fn error_code(&self) -> usize {
+        self.m_error_code
+    }
+
+    fn instr_virt_addr(&self) -> VirtAddr {
+        VirtAddr::from(self.m_rip)
+    }
+
+    fn stack_virt_addr(&self) -> VirtAddr {
+        VirtAddr::from(self.m_rsp)
+    }
+
+    fn is_user(&self) -> bool {
+        /* the requested privilege level of the saved code selector, but the
+         * fault of the system call <iretq> is raised by the userspace state
+         */
+        let syscall_iretq_addr = unsafe { &hw_syscall_iretq as *const u8 as usize };
+        self.m_cs.bits_at(0..2) == 3 || self.m_rip == syscall_iretq_addr
+    }
+}
+
+/**
+ * Returns the `VirtAddr` of the interrupt entry stub for the given vector
+ */
+pub fn hw_intr_stub_virt_addr(intr_vector: u8) -> VirtAddr {
+    let stubs_table_virt_addr =
+        unsafe { VirtAddr::from(&hw_intr_stubs_table as *const u8) };
+    stubs_table_virt_addr.offset(intr_vector as usize * C_INTR_STUB_SIZE)
+}
+
+/**
+ * Rust entry-point of all the interrupt stubs
+ */
+#[no_mangle]
+extern "C" fn hw_intr_dispatch(hw_intr_frame: &mut HwIntrFrame) {
+    if (hw_intr_frame.m_intr_vector as usize) < C_EXCEPTIONS_COUNT {
+        hw_exception_dispatch(hw_intr_frame);
+    } else if hw_intr_frame.m_intr_vector as u8 != C_SPURIOUS_INTR_VECTOR {
+        IntrManager::dispatch(IntrFrame::from_hw_mut(hw_intr_frame));
+
+        /* the interrupt is already acknowledged, so the switch doesn't hold it */
+        Scheduler::preempt_if_needed();
+    }
+}
+
+/**
+ * Handles the recoverable CPU exceptions, reports the others and
+ * terminates the faulting userspace thread or panics for the kernel ones
+ */
+fn hw_exception_dispatch(hw_intr_frame: &mut HwIntrFrame) {
+    match hw_intr_frame.m_intr_vector as u8 {
+        C_PAGE_FAULT_INTR_VECTOR => hw_page_fault_handler(hw_intr_frame),
+        C_NMI_INTR_VECTOR if hw_is_halting() => {
+            /* another CPU panicked, the NMIs stay blocked until an <iretq> */
+            loop {
+                unsafe {
+                    asm!("cli; hlt");
+                }
+            }
+        },
+        C_BREAKPOINT_INTR_VECTOR => {
+            dbg_println!(DbgLevel::Debug, "Breakpoint at {:#018x}", hw_intr_frame.m_rip)
+        },
+        intr_vector => {
+            let exception_name = C_EXCEPTION_NAMES[intr_vector as usize];
+
+            hw_exception_report(exception_name, hw_intr_frame);
+
+            /* the userspace can't take down the kernel, only its own thread, but
+             * the NMIs and the aborts are not caused by the interrupted code
+             */
+            let is_thread_fault = !matches!(intr_vector,
+                                            C_NMI_INTR_VECTOR
+                                            | C_DOUBLE_FAULT_INTR_VECTOR
+                                            | C_MACHINE_CHECK_INTR_VECTOR);
+            if is_thread_fault && hw_intr_frame.is_user() {
+                Scheduler::exit_current_by_fault(exception_name);
+            }
+            panic!("Unrecoverable CPU exception {}: {}", intr_vector, exception_name);
+        }
+    }
+}
+
+/**
+ * Prints the saved registers of the unrecoverable exception with the
+ * symbol of the faulting instruction when available
+ */
+fn hw_exception_report(exception_name: &str, hw_intr_frame: &HwIntrFrame) {
+    dbg_println!(DbgLevel::Err, "<< {} >>", exception_name);
+    dbg_println!(DbgLevel::Err,
+                 ">> Error code {:#x} in {} code",
+                 hw_intr_frame.m_error_code,
+                 if hw_intr_frame.is_user() {
+                     "user"
+                 } else {
+                     "kernel"
+                 });
+
+    /* resolve the symbol of the faulting instruction */
+    let code_symbol = if CodeSymbols::are_available() {
+        CodeSymbols::instance().symbol_at(hw_intr_frame.m_rip)
+    } else {
+        None
+    };
+    if let Some(code_symbol) = code_symbol {
+        dbg_println!(DbgLevel::Err,
+                     ">> Instruction at {:#018x} in {}",
+                     hw_intr_frame.m_rip,
+                     code_symbol);
+    } else {
+        dbg_println!(DbgLevel::Err,
+                     ">> Instruction at {:#018x} (unknown symbol)",
+                     hw_intr_frame.m_rip);
+    }
+    dbg_println!(DbgLevel::Err, ">> {:#x?}", hw_intr_frame);
+}
+
+/**
+ * Decodes the page fault error code and the faulting address stored into CR2.
+ *
+ * Runs on the page fault interrupt stack of the CPU, which a nested page
+ * fault would overwrite, so the handling must never fault itself
+ */
+fn hw_page_fault_handler(hw_intr_frame: &mut HwIntrFrame) {
+    let fault_virt_addr: usize;
+    unsafe {
+        asm!("mov {}, cr2", out(reg) fault_virt_addr, options(nomem, nostack, preserves_flags));
+    }
+
+    let error_code = hw_intr_frame.m_error_code;
+    let page_fault = PageFault::new(VirtAddr::from(fault_virt_addr),
+                                    VirtAddr::from(hw_intr_frame.m_rip),
+                                    error_code.bit_at(0),
+                                    error_code.bit_at(1),
+                                    error_code.bit_at(2),
+                                    error_code.bit_at(4));
+
+    /* the failed user memory copies return to their caller */
+    if let Some(fixup_virt_addr) = page_fault.handle() {
+        hw_intr_frame.m_rip = *fixup_virt_addr;
+    }
+}
+
+global_asm!(include_str!("intr_stubs.S"), options(att_syntax));
@@ -0,0 +1,193 @@
+/*! I/O Advanced Programmable Interrupt Controller */
+
+use core::{
+    ops::Range,
+    ptr::{
+        read_volatile,
+        write_volatile
+    }
+};
+
+use bits::bit_fields::TBitFields;
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::acpi::madt::IoApicInfo,
+    vm::mem_manager::MemManager
+};
+
+/* offsets of the indirect access registers */
+const C_REG_SELECT_OFFSET: usize = 0x00;
+const C_REG_WINDOW_OFFSET: usize = 0x10;
+
+/* size of the memory mapped registers */
+const C_MMIO_SIZE: usize = 0x20;
+
+/* indirectly accessed registers */
+const C_REG_VERSION: u32 = 0x01;
+const C_REG_REDIRECTION_TABLE: u32 = 0x10;
+
+/**
+ * I/O APIC chip, which routes a contiguous range of global system
+ * interrupts (GSI) to the local APICs of the CPUs.
+ *
+ * The registers are accessed selecting them first, so the accesses are
+ * serialized by a lock
+ */
+pub struct IoApic {
+    m_id: u8,
+    m_mmio_virt_addr: SpinMutex<VirtAddr>,
+    m_gsi_range: Range<u32>
+}
+
+impl IoApic /* Constructors */ {
+    /**
+     * Maps the registers of the I/O APIC described by the given
+     * `IoApicInfo` and masks all its redirection entries
+     */
+    pub fn new(io_apic_info: &IoApicInfo) -> Option<Self> {
+        let mmio_range =
+            MemManager::instance().map_mmio_region(io_apic_info.phys_addr()
+                                                   ..io_apic_info.phys_addr()
+                                                                 .offset(C_MMIO_SIZE))?;
+
+        let mut io_apic = Self { m_id: io_apic_info.id(),
+                                 m_mmio_virt_addr:
+                                     SpinMutex::const_new(mmio_range.start),
+                                 m_gsi_range: 0..0 };
+
+        /* VERSION[bits 16..24] stores the index of the last redirection entry */
+        let redirection_entries = io_apic.read(C_REG_VERSION).bits_at(16..24) + 1;
+        let base_gsi = io_apic_info.base_gsi();
+        io_apic.m_gsi_range = base_gsi..base_gsi + redirection_entries;
+
+        /* no interrupt is delivered until a driver unmasks it */
+        for gsi in io_apic.m_gsi_range.clone() {
+            io_apic.write_redirection(gsi, RedirectionEntry::new_masked());
+        }
+        Some(io_apic)
+    }
+}
+
+impl IoApic /* Methods */ {
+    /**
+     * Returns whether this `IoApic` routes the given GSI
+     */
+    pub fn handles_gsi(&self, gsi: u32) -> bool {
+        self.m_gsi_range.contains(&gsi)
+    }
+
+    /**
+     * Overwrites the redirection entry of the given GSI
+     */
+    pub fn write_redirection(&self, gsi: u32, redirection_entry: RedirectionEntry) {
+        let reg_index = C_REG_REDIRECTION_TABLE + (gsi - self.m_gsi_range.start) * 2;
+
+        /* write the high half first, the entry becomes valid with the low half */
+        self.write(reg_index + 1, (redirection_entry.m_raw >> 32) as u32);
+        self.write(reg_index, redirection_entry.m_raw as u32);
+    }
+
+    /**
+     * Reads the redirection entry of the given GSI
+     */
+    pub fn read_redirection(&self, gsi: u32) -> RedirectionEntry {
+        let reg_index = C_REG_REDIRECTION_TABLE + (gsi - self.m_gsi_range.start) * 2;
+
+        let low_half = self.read(reg_index) as u64;
+        let high_half = self.read(reg_index + 1) as u64;
+        RedirectionEntry { m_raw: high_half << 32 | low_half }
+    }
+
+    /**
+     * Masks or unmasks the given GSI
+     */
+    pub fn set_masked(&self, gsi: u32, masked: bool) {
+        let mut redirection_entry = self.read_redirection(gsi);
+
+        redirection_entry.m_raw.set_bit(16, masked);
+        self.write_redirection(gsi, redirection_entry);
+    }
+}
+
+impl IoApic /* Getters */ {
+    /**
+     * Returns the hardware identifier of this `IoApic`
+     */
+    pub fn id(&self) -> u8 {
+        self.m_id
+    }
+
+    /**
+     * Returns the `Range` of GSIs routed by this `IoApic`
+     */
+    pub fn gsi_range(&self) -> &Range<u32> {
+        &self.m_gsi_range
+    }
+}
+
+impl IoApic /* Privates */ {
+    fn read(&self, reg_index: u32) -> u32 {
+        let mmio_virt_addr = self.m_mmio_virt_addr.lock();
+        unsafe {
+            write_volatile(mmio_virt_addr.offset(C_REG_SELECT_OFFSET)
+                                         .as_ptr_mut::<u32>(),
+                           reg_index);
+            read_volatile(mmio_virt_addr.offset(C_REG_WINDOW_OFFSET).as_ptr::<u32>())
+        }
+    }
+
+    fn write(&self, reg_index: u32, value: u32) {
+        let mmio_virt_addr = self.m_mmio_virt_addr.lock();
+        unsafe {
+            write_volatile(mmio_virt_addr.offset(C_REG_SELECT_OFFSET)
+                                         .as_ptr_mut::<u32>(),
+                           reg_index);
+            write_volatile(mmio_virt_addr.offset(C_REG_WINDOW_OFFSET)
+                                         .as_ptr_mut::<u32>(),
+                           value);
+        }
+    }
+}
+
+/**
+ * 64bit I/O APIC redirection table entry
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct RedirectionEntry {
+    m_raw: u64
+}
+
+impl RedirectionEntry /* Constructors */ {
+    /**
+     * Constructs a masked `RedirectionEntry` which delivers the interrupt
+     * with the given vector to the local APIC with the given id
+     */
+    pub fn new(intr_vector: u8,
+               dest_apic_id: u8,
+               is_active_low: bool,
+               is_level_triggered: bool)
+               -> Self {
+        let mut raw_entry = 0u64;
+
+        /* fixed delivery mode, physical destination mode */
+        raw_entry.set_bits(0..8, intr_vector as u64)
+                 .set_bit(13, is_active_low)
+                 .set_bit(15, is_level_triggered)
+                 .set_bit(16, true)
+                 .set_bits(56..64, dest_apic_id as u64);
+        Self { m_raw: raw_entry }
+    }
+
+    /**
+     * Constructs a masked `RedirectionEntry` without destination
+     */
+    pub fn new_masked() -> Self {
+        Self { m_raw: 1 << 16 }
+    }
+}
@@ -2,6 +2,7 @@
 
 use core::{
     arch::x86_64::__cpuid,
+    hint,
     ptr::{
         read_volatile,
         write_volatile
@@ -16,13 +17,19 @@ use crate::{
         virt_addr::VirtAddr,
         TAddress
     },
-    arch::x86_64::ms_register::MsRegister,
-    cpu::CpuId,
+    arch::x86_64::{
+        intr::C_SPURIOUS_INTR_VECTOR,
+        ms_register::MsRegister
+    },
+    intr::IntrVector,
     vm::mem_manager::MemManager
 };
 
 static mut SM_APIC_BASE_VIRT_ADDR: Option<VirtAddr> = None;
 
+/* size of the memory mapped registers */
+const C_MMIO_SIZE: usize = 0x400;
+
 pub struct LocalApic {
     m_virt_addr: VirtAddr,
     m_enabled: bool
@@ -44,11 +51,17 @@ impl LocalApic /* Constructors */ {
             return false;
         }
 
-        /* obtain the APIC physical address and convert it to virtual */
+        /* obtain the APIC physical address and map its registers */
         let apic_base_phys_addr: PhysAddr = (apic_base & 0xffff_f000).into();
-        let apic_base_virt_addr =
-            MemManager::instance().layout_manager()
-                                  .phys_addr_to_virt_addr(apic_base_phys_addr);
+        let apic_mmio_phys_range =
+            apic_base_phys_addr..apic_base_phys_addr.offset(C_MMIO_SIZE);
+        let apic_base_virt_addr = if let Some(apic_mmio_range) =
+            MemManager::instance().map_mmio_region(apic_mmio_phys_range)
+        {
+            apic_mmio_range.start
+        } else {
+            return false;
+        };
         unsafe {
             /* store the APIC base VirtAddr for use of the other cores */
             SM_APIC_BASE_VIRT_ADDR = Some(apic_base_virt_addr);
@@ -74,10 +87,9 @@ impl LocalApic /* Methods */ {
             /* copy the LAPIC virtual address from the global one */
             self.m_virt_addr = SM_APIC_BASE_VIRT_ADDR.unwrap();
 
-            /* enable spurious interrupts */
-            if self.read(Register::SpuriousInterrupt) & SPURIOUS_INTERRUPT_DISABLE != 0 {
-                self.write(Register::SpuriousInterrupt, SPURIOUS_INTERRUPT_ENABLE);
-            }
+            /* software enable the APIC, delivering the spurious interrupts apart */
+            self.write(Register::SpuriousInterrupt,
+                       SPURIOUS_INTERRUPT_ENABLE | C_SPURIOUS_INTR_VECTOR as u32);
 
             /* set task priority and 16 as timer counter divider */
             self.write(Register::TaskPrio, 0x10);
@@ -87,12 +99,61 @@ impl LocalApic /* Methods */ {
         /* mask as enabled for this core */
         self.m_enabled = true;
     }
+
+    pub fn send_init_ipi(&self, apic_id: u8) {
+        self.write_intr_command(apic_id, DELIVERY_MODE_INIT | LEVEL_ASSERT);
+    }
+
+    pub fn send_startup_ipi(&self, apic_id: u8, start_page: u8) {
+        /* the AP starts in real mode at <start_page> * 4KiB */
+        self.write_intr_command(apic_id,
+                                DELIVERY_MODE_STARTUP | LEVEL_ASSERT | start_page as u32);
+    }
+
+    pub fn send_ipi(&self, apic_id: u8, intr_vector: IntrVector) {
+        self.write_intr_command(apic_id,
+                                DELIVERY_MODE_NORMAL | LEVEL_ASSERT | intr_vector as u32);
+    }
+
+    pub fn send_nmi_to_others(&self) {
+        self.write_intr_command(0,
+                                DELIVERY_MODE_NON_MASKABLE
+                                | LEVEL_ASSERT
+                                | DESTINATION_ALL_BUT_THIS);
+    }
+
+    pub fn start_timer(&self, intr_vector: IntrVector, init_count: u32) {
+        unsafe {
+            /* the counter starts with the write of the initial count */
+            self.write(Register::LocalVecTableTimer, MODE_ONE_SHOT | intr_vector as u32);
+            self.write(Register::TimerInitCounter, init_count);
+        }
+    }
+
+    pub fn start_timer_masked(&self, init_count: u32) {
+        unsafe {
+            /* counts down without interrupts, used to measure the timer frequency */
+            self.write(Register::LocalVecTableTimer, MODE_ONE_SHOT | LVT_MASKED);
+            self.write(Register::TimerInitCounter, init_count);
+        }
+    }
+
+    pub fn stop_timer(&self) {
+        unsafe {
+            self.write(Register::LocalVecTableTimer, LVT_MASKED);
+            self.write(Register::TimerInitCounter, 0);
+        }
+    }
 }
 
 impl LocalApic /* Static Functions */ {
     pub fn is_supported() -> bool {
         (unsafe { __cpuid(0x01) }.edx & (1 << 9)) != 0
     }
+
+    pub fn is_initialized() -> bool {
+        unsafe { SM_APIC_BASE_VIRT_ADDR.is_some() }
+    }
 }
 
 impl LocalApic /* Getters */ {
@@ -100,8 +161,13 @@ impl LocalApic /* Getters */ {
         self.m_enabled
     }
 
-    pub fn cpu_id(&self) -> CpuId {
-        unsafe { self.read(Register::CoreId) as CpuId }
+    pub fn apic_id(&self) -> u8 {
+        /* the APIC id is stored into the highest byte */
+        unsafe { self.read(Register::CoreId).bits_at(24..32) as u8 }
+    }
+
+    pub fn timer_current_count(&self) -> u32 {
+        unsafe { self.read(Register::TimerCurrentCounter) }
     }
 
     pub fn end_of_interrupt(&self) {
@@ -117,6 +183,18 @@ impl LocalApic /* Privates */ {
     unsafe fn write(&self, register: Register, value: u32) {
         write_volatile((*self.m_virt_addr + register as usize) as *mut u32, value);
     }
+
+    fn write_intr_command(&self, apic_id: u8, command: u32) {
+        unsafe {
+            /* the low register write sends the IPI, so the destination goes first */
+            self.write(Register::IntrCommandHigh, (apic_id as u32) << 24);
+            self.write(Register::IntrCommandLow, command);
+
+            while self.read(Register::IntrCommandLow) & DELIVERY_STATUS_PENDING != 0 {
+                hint::spin_loop();
+            }
+        }
+    }
 }
 
 #[repr(usize)]
@@ -124,21 +202,21 @@ impl LocalApic /* Privates */ {
 #[derive(Copy, Clone)]
 #[derive(Eq, PartialEq)]
 enum Register {
-    CoreId                   = 0x20,
-    TaskPrio                 = 0x80,
-    EndOfInterrupt           = 0xB0,
-    SpuriousInterrupt        = 0xF0,
-    IntrCommandLow           = 0x300,
-    IntrCommandHigh          = 0x310,
-    LocalVecTableTimer       = 0x320,
-    LocalVecTableThermal     = 0x330,
+    CoreId               = 0x20,
+    TaskPrio             = 0x80,
+    EndOfInterrupt       = 0xB0,
+    SpuriousInterrupt    = 0xF0,
+    IntrCommandLow       = 0x300,
+    IntrCommandHigh      = 0x310,
+    LocalVecTableTimer   = 0x320,
+    LocalVecTableThermal = 0x330,
     LocalVecTablePerfCounter = 0x340,
-    LocalVecTableLint0       = 0x350,
-    LocalVecTableLint1       = 0x360,
-    LocalVecTableError       = 0x370,
-    TimerInitCounter         = 0x380,
-    TimerCurrentCounter      = 0x390,
-    TimerDivideConfig        = 0x3E0
+    LocalVecTableLint0   = 0x350,
+    LocalVecTableLint1   = 0x360,
+    LocalVecTableError   = 0x370,
+    TimerInitCounter     = 0x380,
+    TimerCurrentCounter  = 0x390,
+    TimerDivideConfig    = 0x3E0
 }
 
 const SPURIOUS_INTERRUPT_DISABLE: u32 = 0 << 8;
@@ -149,7 +227,7 @@ const DELIVERY_MODE_LOW_PRIO: u32 = 1 << 8;
 const DELIVERY_MODE_SYSTEM_MANAGEMENT: u32 = 2 << 8;
 const DELIVERY_MODE_NON_MASKABLE: u32 = 4 << 8;
 const DELIVERY_MODE_INIT: u32 = 5 << 8;
-const DELIVERY_MODE_INTER_PROCESSOR: u32 = 6 << 8;
+const DELIVERY_MODE_STARTUP: u32 = 6 << 8;
 const DELIVERY_MODE_EXTERNAL: u32 = 7 << 8;
 
 const DESTINATION_MODE_PHYSICAL: u32 = 0 << 11;
@@ -158,12 +236,14 @@ const DESTINATION_MODE_LOGICAL: u32 = 1 << 11;
 const DELIVERY_STATUS_IDLE: u32 = 0 << 12;
 const DELIVERY_STATUS_PENDING: u32 = 1 << 12;
 
-const LEVEL_ASSERT: u32 = 0 << 14;
-const LEVEL_DE_ASSERT: u32 = 1 << 14;
+const LEVEL_DE_ASSERT: u32 = 0 << 14;
+const LEVEL_ASSERT: u32 = 1 << 14;
 
 const TRIGGER_MODE_EDGE: u32 = 0 << 15;
 const TRIGGER_MODE_LEVEL: u32 = 1 << 15;
 
+const LVT_MASKED: u32 = 1 << 16;
+
 const MODE_ONE_SHOT: u32 = 0 << 17;
 const MODE_PERIODIC: u32 = 1 << 17;
 const MODE_DEADLINE: u32 = 2 << 17;
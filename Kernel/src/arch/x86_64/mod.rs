@@ -2,15 +2,22 @@
 
 pub mod acpi;
 pub mod addr;
+pub mod apic_controller;
 pub mod desc_table;
 pub mod dev;
 pub mod gdt;
 pub mod hw_boot_info;
 pub mod hw_cpu;
 pub mod idt;
+pub mod intr;
+pub mod io_apic;
 pub mod local_apic;
 pub mod ms_register;
 pub mod pic;
+pub mod smp;
+pub mod syscall;
+pub mod task;
+pub mod time;
 pub mod tss;
 pub mod vm;
 pub mod x64_port;
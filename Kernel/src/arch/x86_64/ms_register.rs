@@ -1,13 +1,106 @@
 /*! Model Specific Register */
 
+use bits::bit_fields::TBitFields;
+
+use crate::addr::virt_addr::VirtAddr;
+
 pub struct EfeRegister;
 
+impl EfeRegister /* Static Functions */ {
+    /**
+     * Enables the `syscall`/`sysret` instructions
+     */
+    pub unsafe fn enable_syscall_ext() {
+        let ms_register = MsRegister::new(0xc000_0080);
+
+        let mut efe_value = ms_register.read();
+        ms_register.write(*efe_value.set_bit(0, true));
+    }
+}
+
+pub struct StarRegister;
+
+impl StarRegister /* Static Functions */ {
+    /**
+     * Overwrites the segment selectors loaded by `syscall` and `sysret`.
+     *
+     * `syscall` loads `kern_code_selector` into CS and the next selector
+     * into SS, while the 64bit `sysret` loads the selector after
+     * `user_base_selector` into SS and the next one into CS
+     */
+    pub unsafe fn write(kern_code_selector: u16, user_base_selector: u16) {
+        let star_value =
+            ((user_base_selector as u64) << 48) | ((kern_code_selector as u64) << 32);
+
+        MsRegister::new(0xc000_0081).write(star_value);
+    }
+}
+
+pub struct LStarRegister;
+
+impl LStarRegister /* Static Functions */ {
+    /**
+     * Overwrites the entry point of the 64bit `syscall`
+     */
+    pub unsafe fn write(virt_addr: VirtAddr) {
+        MsRegister::new(0xc000_0082).write(*virt_addr as u64);
+    }
+}
+
+pub struct FMaskRegister;
+
+impl FMaskRegister /* Static Functions */ {
+    /**
+     * Overwrites the mask of the RFLAGS bits cleared by `syscall`
+     */
+    pub unsafe fn write(rflags_mask: u64) {
+        MsRegister::new(0xc000_0084).write(rflags_mask);
+    }
+}
+
 pub struct FsBaseRegister;
 
+impl FsBaseRegister /* Static Functions */ {
+    /**
+     * Overwrites the base address of the FS segment, which the userspace
+     * uses as thread pointer
+     */
+    pub unsafe fn write(virt_addr: VirtAddr) {
+        MsRegister::new(0xc000_0100).write(*virt_addr as u64);
+    }
+}
+
 pub struct GsBaseRegister;
 
+impl GsBaseRegister /* Static Functions */ {
+    /**
+     * Returns the base address of the GS segment
+     */
+    pub fn read() -> VirtAddr {
+        VirtAddr::from(unsafe { MsRegister::new(0xc000_0101).read() } as usize)
+    }
+
+    /**
+     * Overwrites the base address of the GS segment, which is used to reach
+     * the per-CPU data
+     */
+    pub unsafe fn write(virt_addr: VirtAddr) {
+        MsRegister::new(0xc000_0101).write(*virt_addr as u64);
+    }
+}
+
 pub struct KernGsBaseRegister;
 
+impl KernGsBaseRegister /* Static Functions */ {
+    /**
+     * Overwrites the base address which `swapgs` exchanges with the one of
+     * the GS segment
+     */
+    pub unsafe fn write(virt_addr: VirtAddr) {
+        MsRegister::new(0xc000_0102).write(*virt_addr as u64);
+    }
+}
+
 /**
  * Generic x86_64 Model Specific Register
  */
@@ -1,6 +1,17 @@
 /*! Programmable interrupt controller */
 
-use crate::arch::x86_64::x64_port::X64Port;
+use crate::{
+    arch::x86_64::x64_port::X64Port,
+    cpu::CpuId,
+    intr::{
+        IntrVector,
+        Irq,
+        TInterruptController
+    }
+};
+
+/* IRQ of the master PIC to which the slave one is chained */
+const C_CASCADE_IRQ: Irq = 2;
 
 /* <None> until <PicManager::init_instance()> is called */
 static mut SM_PIC_MANAGER: Option<PicManager> = None;
@@ -69,8 +80,8 @@ impl PicManager /* Constructors */ {
             pic_manager.m_slave_pic.m_data.write(0x01);
             write_wait();
 
-            /* enable now all the interrupts */
-            pic_manager.set_interrupts_masks(0, 0);
+            /* the interrupts are enabled one by one by the drivers, keep the cascade */
+            pic_manager.set_interrupts_masks(!(1 << C_CASCADE_IRQ), u8::MAX);
 
             /* initialize the global instance */
             SM_PIC_MANAGER = Some(pic_manager);
@@ -95,11 +106,11 @@ impl PicManager /* Methods */ {
      */
     pub unsafe fn end_of_interrupt(&self, interrupt_num: u8) {
         if self.can_handle_interrupt(interrupt_num) {
-            if self.m_master_pic.can_handle_interrupt(interrupt_num) {
-                self.m_master_pic.end_of_interrupt()
-            } else {
+            /* the slave interrupts pass through the master too */
+            if self.m_slave_pic.can_handle_interrupt(interrupt_num) {
                 self.m_slave_pic.end_of_interrupt()
             }
+            self.m_master_pic.end_of_interrupt()
         }
     }
 
@@ -137,6 +148,57 @@ impl PicManager /* Setters */ {
     }
 }
 
+impl PicManager /* Privates */ {
+    /**
+     * Returns the `Pic` which handles the given `Irq` and the line of the
+     * `Irq` on it
+     */
+    fn pic_of(&self, irq: Irq) -> Option<(&Pic, u8)> {
+        match irq {
+            0..=7 => Some((&self.m_master_pic, irq as u8)),
+            8..=15 => Some((&self.m_slave_pic, irq as u8 - 8)),
+            _ => None
+        }
+    }
+}
+
+impl TInterruptController for PicManager {
+    fn name(&self) -> &'static str {
+        "8259 PIC"
+    }
+
+    fn intr_vector_of(&self, irq: Irq) -> Option<IntrVector> {
+        self.pic_of(irq).map(|(pic, pic_line)| pic.m_offset + pic_line)
+    }
+
+    fn route(&self, irq: Irq, cpu_id: CpuId) -> bool {
+        /* the PICs are wired only to the bootstrap CPU */
+        self.pic_of(irq).is_some() && cpu_id == 0
+    }
+
+    fn mask(&self, irq: Irq) {
+        if let Some((pic, pic_line)) = self.pic_of(irq) {
+            unsafe {
+                pic.set_interrupt_mask(pic.interrupt_mask() | 1 << pic_line);
+            }
+        }
+    }
+
+    fn unmask(&self, irq: Irq) {
+        if let Some((pic, pic_line)) = self.pic_of(irq) {
+            unsafe {
+                pic.set_interrupt_mask(pic.interrupt_mask() & !(1 << pic_line));
+            }
+        }
+    }
+
+    fn end_of_interrupt(&self, intr_vector: IntrVector) {
+        unsafe {
+            PicManager::end_of_interrupt(self, intr_vector);
+        }
+    }
+}
+
 /**
  * Programmable Interrupt Controller chip
  */
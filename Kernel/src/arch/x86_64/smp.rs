@@ -0,0 +1,314 @@
+/*! x86_64 multiprocessor startup and inter-processor interrupts */
+
+use core::{
+    hint,
+    ops::Range,
+    ptr,
+    sync::atomic::{
+        AtomicBool,
+        AtomicUsize,
+        Ordering
+    },
+    time::Duration
+};
+
+use helps::dbg::C_KIB;
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::intr::{
+        C_RESCHED_INTR_VECTOR,
+        C_TLB_SHOOTDOWN_INTR_VECTOR
+    },
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    dbg_print::DbgLevel,
+    dbg_println,
+    intr::{
+        IntrFrame,
+        IntrManager
+    },
+    time::TimeManager,
+    vm::{
+        mem_manager::MemManager,
+        tlb::Tlb,
+        MapFlags,
+        MapFlagsBits,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+extern "C" {
+    static hw_ap_trampoline_begin: u8;
+    static hw_ap_trampoline_args: u8;
+    static hw_ap_trampoline_end: u8;
+}
+
+/**
+ * Maximum number of CPUs, the xAPIC identifiers are 8bit wide
+ */
+pub const C_MAX_CPUS: usize = 256;
+
+/* physical address of the trampoline, keep in sync with ap_trampoline.S */
+const C_AP_TRAMPOLINE_PHYS_ADDR: usize = 0x8000;
+
+/* size of the stack with which each AP enters the kernel */
+const C_AP_STACK_SIZE: usize = 64 * C_KIB;
+
+/* set once a CPU has started to halt all the others */
+static SM_HALTING: AtomicBool = AtomicBool::new(false);
+
+/* placeholder of the CPUs without a pending TLB shootdown */
+#[allow(clippy::declare_interior_mutable_const)]
+const C_NO_TLB_SHOOTDOWN: AtomicBool = AtomicBool::new(false);
+
+/* the range to invalidate, written by the holder of <SM_TLB_SHOOTDOWN_LOCK> */
+static SM_TLB_SHOOTDOWN_LOCK: SpinMutex<()> = SpinMutex::const_new(());
+static SM_TLB_SHOOTDOWN_BEGIN: AtomicUsize = AtomicUsize::new(0);
+static SM_TLB_SHOOTDOWN_END: AtomicUsize = AtomicUsize::new(0);
+
+/* the CPUs which still have to serve the current TLB shootdown, indexed by <CpuId> */
+static SM_TLB_SHOOTDOWN_PENDING: [AtomicBool; C_MAX_CPUS] =
+    [C_NO_TLB_SHOOTDOWN; C_MAX_CPUS];
+
+/**
+ * Wakes up the AP with the given local APIC identifier through the
+ * INIT-SIPI-SIPI sequence, making it enter `kernel_ap_rust_start()` with
+ * the given `CpuId`.
+ *
+ * Returns whether the AP has set `is_online` within the timeout
+ */
+pub fn hw_start_ap(apic_id: u8, cpu_id: CpuId, is_online: &AtomicBool) -> bool {
+    let local_apic = Cpu::current().hw_cpu().local_apic();
+
+    /* the TLB shootdown and the reschedule are needed as soon as another CPU runs */
+    if !IntrManager::is_registered(C_TLB_SHOOTDOWN_INTR_VECTOR) {
+        IntrManager::register_handler(C_TLB_SHOOTDOWN_INTR_VECTOR,
+                                      hw_tlb_shootdown_intr_handler);
+        IntrManager::register_handler(C_RESCHED_INTR_VECTOR, hw_resched_intr_handler);
+        unsafe {
+            Tlb::set_shootdown_hook(hw_tlb_shootdown);
+        }
+    }
+
+    /* the stack is touched before the AP loads its IDT, so it cannot be lazy */
+    let ap_stack = if let Some(ap_stack) =
+        MemManager::instance().allocate_kernel_stack(C_AP_STACK_SIZE)
+    {
+        ap_stack
+    } else {
+        dbg_println!(DbgLevel::Warn, "Failed to allocate the stack for CPU {}", cpu_id);
+        return false;
+    };
+    for virt_addr in ap_stack.clone().step_by(Page4KiB::SIZE) {
+        unsafe {
+            ptr::write_volatile(virt_addr.as_ptr_mut::<u8>(), 0);
+        }
+    }
+
+    if !install_trampoline(ap_stack.end, cpu_id) {
+        MemManager::instance().free_kernel_stack(ap_stack);
+        return false;
+    }
+
+    /* INIT-SIPI-SIPI, the second startup IPI is needed only by some CPUs */
+    local_apic.send_init_ipi(apic_id);
+    TimeManager::delay(Duration::from_millis(10));
+    for _ in 0..2 {
+        local_apic.send_startup_ipi(apic_id, (C_AP_TRAMPOLINE_PHYS_ADDR >> 12) as u8);
+        TimeManager::delay(Duration::from_micros(200));
+        if is_online.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    /* give to the AP up to 100ms to initialize itself */
+    for _ in 0..100 {
+        if is_online.load(Ordering::SeqCst) {
+            break;
+        }
+        TimeManager::delay(Duration::from_millis(1));
+    }
+    uninstall_trampoline();
+
+    /* a late AP would run the unmapped trampoline, so its stack is leaked */
+    is_online.load(Ordering::SeqCst)
+}
+
+/**
+ * Sends a non-maskable interrupt to all the other CPUs, which halt
+ * themselves until the next reset
+ */
+pub fn hw_halt_other_cpus() {
+    /* the CPU which arrives first halts the others */
+    if SM_HALTING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let local_apic = Cpu::current().hw_cpu().local_apic();
+    if local_apic.is_enabled() {
+        local_apic.send_nmi_to_others();
+    }
+}
+
+/**
+ * Sends the reschedule inter-processor interrupt to the CPU with the given
+ * local APIC identifier
+ */
+pub fn hw_send_resched_ipi(apic_id: u8) {
+    Cpu::current().hw_cpu().local_apic().send_ipi(apic_id, C_RESCHED_INTR_VECTOR);
+}
+
+/**
+ * Returns whether the non-maskable interrupts are halt requests
+ */
+pub fn hw_is_halting() -> bool {
+    SM_HALTING.load(Ordering::SeqCst)
+}
+
+/**
+ * Asks to all the other online CPUs to invalidate the given `Range` and
+ * waits until all of them have done.
+ *
+ * Registered as `Tlb` shootdown hook once the first AP is started
+ */
+fn hw_tlb_shootdown(virt_range: &Range<VirtAddr>) {
+    let this_cpu = Cpu::current();
+
+    /* the other CPUs could wait for us while holding the lock */
+    let shootdown_guard = loop {
+        if let Some(shootdown_guard) = SM_TLB_SHOOTDOWN_LOCK.try_lock() {
+            break shootdown_guard;
+        }
+        hw_serve_tlb_shootdown();
+        hint::spin_loop();
+    };
+
+    SM_TLB_SHOOTDOWN_BEGIN.store(*virt_range.start, Ordering::SeqCst);
+    SM_TLB_SHOOTDOWN_END.store(*virt_range.end, Ordering::SeqCst);
+
+    /* interrupt each other online CPU, then wait for all of them */
+    let local_apic = this_cpu.hw_cpu().local_apic();
+    for cpu_id in 0..Cpu::cpus_count() as CpuId {
+        let cpu = Cpu::by_id(cpu_id);
+        if cpu_id != this_cpu.id() && cpu.is_online() {
+            SM_TLB_SHOOTDOWN_PENDING[cpu_id as usize].store(true, Ordering::SeqCst);
+            local_apic.send_ipi(cpu.hw_cpu().apic_id(), C_TLB_SHOOTDOWN_INTR_VECTOR);
+        }
+    }
+    for pending_shootdown in SM_TLB_SHOOTDOWN_PENDING.iter() {
+        while pending_shootdown.load(Ordering::SeqCst) {
+            hint::spin_loop();
+        }
+    }
+
+    drop(shootdown_guard);
+}
+
+/**
+ * Invalidates the range of the TLB shootdown when requested to this CPU
+ */
+fn hw_serve_tlb_shootdown() {
+    let this_cpu_id = Cpu::current().id() as usize;
+
+    if SM_TLB_SHOOTDOWN_PENDING[this_cpu_id].load(Ordering::SeqCst) {
+        let virt_range = VirtAddr::from(SM_TLB_SHOOTDOWN_BEGIN.load(Ordering::SeqCst))
+                         ..VirtAddr::from(SM_TLB_SHOOTDOWN_END.load(Ordering::SeqCst));
+
+        Tlb::invalidate_range(&virt_range);
+        SM_TLB_SHOOTDOWN_PENDING[this_cpu_id].store(false, Ordering::SeqCst);
+    }
+}
+
+fn hw_tlb_shootdown_intr_handler(_intr_frame: &mut IntrFrame) {
+    hw_serve_tlb_shootdown();
+}
+
+fn hw_resched_intr_handler(_intr_frame: &mut IntrFrame) {
+    /* the preemption is checked by <hw_intr_dispatch()> after the acknowledge */
+}
+
+/**
+ * Copies the trampoline below the first MiB, identity maps it into the
+ * kernel page directory and writes the `ApTrampolineArgs` for the AP
+ */
+fn install_trampoline(ap_stack_top: VirtAddr, cpu_id: CpuId) -> bool {
+    let mem_manager = MemManager::instance();
+
+    let (trampoline_begin, trampoline_args, trampoline_end) = unsafe {
+        (VirtAddr::from(&hw_ap_trampoline_begin as *const u8),
+         VirtAddr::from(&hw_ap_trampoline_args as *const u8),
+         VirtAddr::from(&hw_ap_trampoline_end as *const u8))
+    };
+    let trampoline_size = *trampoline_end - *trampoline_begin;
+    assert!(trampoline_size <= Page4KiB::SIZE, "AP trampoline exceeds one page");
+
+    /* the protected mode code can load only a 32bit page directory address */
+    let page_dir_phys_addr = mem_manager.kernel_page_dir().root_phys_frame();
+    assert!(*page_dir_phys_addr < u32::MAX as usize,
+            "Kernel page directory unreachable from the AP trampoline");
+
+    /* the trampoline enables the paging while running from its physical address */
+    let trampoline_phys_addr = PhysAddr::from(C_AP_TRAMPOLINE_PHYS_ADDR);
+    let trampoline_identity_range =
+        VirtAddr::from(C_AP_TRAMPOLINE_PHYS_ADDR).to_range(Page4KiB::SIZE);
+    if !mem_manager.kernel_page_dir()
+                   .map::<Page4KiB>(trampoline_identity_range,
+                                    trampoline_phys_addr,
+                                    MapFlags::new_zero() | MapFlagsBits::Executable)
+    {
+        dbg_println!(DbgLevel::Warn, "Failed to identity map the AP trampoline");
+        return false;
+    }
+
+    /* copy the code then the arguments through the physical memory mapping */
+    let trampoline_virt_addr =
+        mem_manager.layout_manager().phys_addr_to_virt_addr(trampoline_phys_addr);
+    let ap_trampoline_args = ApTrampolineArgs { m_page_dir_phys_addr: *page_dir_phys_addr
+                                                                      as u64,
+                                                m_stack_top: *ap_stack_top as u64,
+                                                m_cpu_id: cpu_id as u64 };
+    unsafe {
+        ptr::copy_nonoverlapping(trampoline_begin.as_ptr::<u8>(),
+                                 trampoline_virt_addr.as_ptr_mut::<u8>(),
+                                 trampoline_size);
+
+        let args_offset = *trampoline_args - *trampoline_begin;
+        ptr::write_volatile(trampoline_virt_addr.offset(args_offset)
+                                                .as_ptr_mut::<ApTrampolineArgs>(),
+                            ap_trampoline_args);
+    }
+    true
+}
+
+/**
+ * Removes the identity mapping of the trampoline
+ */
+fn uninstall_trampoline() {
+    let trampoline_identity_range =
+        VirtAddr::from(C_AP_TRAMPOLINE_PHYS_ADDR).to_range(Page4KiB::SIZE);
+
+    MemManager::instance().kernel_page_dir()
+                          .unmap::<Page4KiB>(trampoline_identity_range, false);
+}
+
+/**
+ * Arguments read by the trampoline, keep in sync with ap_trampoline.S
+ */
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ApTrampolineArgs {
+    m_page_dir_phys_addr: u64,
+    m_stack_top: u64,
+    m_cpu_id: u64
+}
+
+global_asm!(include_str!("ap_trampoline.S"), options(att_syntax));
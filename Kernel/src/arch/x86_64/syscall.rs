@@ -0,0 +1,95 @@
+/*! x86_64 system calls entry */
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::{
+        gdt::SegmentSelector,
+        ms_register::{
+            EfeRegister,
+            FMaskRegister,
+            KernGsBaseRegister,
+            LStarRegister,
+            StarRegister
+        }
+    },
+    cpu::Cpu,
+    syscall::SysCallManager
+};
+
+/* RFLAGS cleared at the entry: trap, interrupts, direction and alignment check */
+const C_SYSCALL_RFLAGS_MASK: u64 = (1 << 8) | (1 << 9) | (1 << 10) | (1 << 18);
+
+extern "C" {
+    fn hw_syscall_entry();
+}
+
+/**
+ * Userspace state saved by `Kernel/arch/x86_64/syscall_entry.S` on system
+ * call entry.
+ *
+ * The layout must match the order of the pushes of the entry
+ */
+#[repr(C)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct HwSysCallFrame {
+    pub m_r15: usize,
+    pub m_r14: usize,
+    pub m_r13: usize,
+    pub m_r12: usize,
+    pub m_r10: usize,
+    pub m_r9: usize,
+    pub m_r8: usize,
+    pub m_rbp: usize,
+    pub m_rdi: usize,
+    pub m_rsi: usize,
+    pub m_rdx: usize,
+    pub m_rbx: usize,
+    pub m_rax: usize,
+    pub m_rip: usize,
+    pub m_rflags: usize,
+    pub m_rsp: usize
+}
+
+/**
+ * Enables the `syscall`/`sysret` instructions for the executing CPU.
+ *
+ * `sysret` expects the user code segment just after the user data one
+ */
+pub fn hw_syscall_init(kern_code_selector: SegmentSelector,
+                       user_data_selector: SegmentSelector) {
+    let user_base_selector = SegmentSelector::new(user_data_selector.array_index() - 1,
+                                                  user_data_selector.cpu_ring_mode());
+
+    unsafe {
+        StarRegister::write(kern_code_selector.as_raw() as u16,
+                            user_base_selector.as_raw() as u16);
+        LStarRegister::write(VirtAddr::from(hw_syscall_entry as usize));
+        FMaskRegister::write(C_SYSCALL_RFLAGS_MASK);
+
+        /* the userspace starts without a GS base, swapped in at each entry */
+        KernGsBaseRegister::write(VirtAddr::null());
+        EfeRegister::enable_syscall_ext();
+    }
+}
+
+/**
+ * Rust entry-point of the system calls.
+ *
+ * `rax` carries the userspace pointer to the `SysCallPayload`, which is
+ * served with the interrupts enabled
+ */
+#[no_mangle]
+extern "C" fn hw_syscall_dispatch(hw_syscall_frame: &HwSysCallFrame) {
+    Cpu::current().enable_interrupts();
+
+    SysCallManager::dispatch(VirtAddr::from(hw_syscall_frame.m_rax));
+
+    /* the thread could have been migrated meanwhile */
+    Cpu::current().disable_interrupts();
+}
+
+global_asm!(include_str!("syscall_entry.S"), options(att_syntax));
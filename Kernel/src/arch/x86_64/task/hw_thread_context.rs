@@ -0,0 +1,71 @@
+/*! x86_64 thread context */
+
+use core::{
+    mem,
+    ptr
+};
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    task::thread::{
+        THwThreadContext,
+        ThreadStartFn
+    }
+};
+
+/* the System V ABI requires 16 bytes aligned stacks at each call */
+const C_STACK_ALIGN: usize = 16;
+
+extern "C" {
+    fn hw_switch_context(prev_stack_ptr: *mut usize, next_stack_ptr: usize);
+    fn hw_thread_start();
+    fn hw_enter_user(entry_point: usize, stack_ptr: usize, arg0: usize, arg1: usize)
+                     -> !;
+}
+
+/**
+ * x86_64 `THwThreadContext` implementation.
+ *
+ * The registers are saved onto the stack of the thread, so only its stack
+ * pointer is stored
+ */
+#[derive(Debug)]
+pub struct HwThreadContext {
+    m_stack_ptr: usize
+}
+
+impl THwThreadContext for HwThreadContext {
+    fn new_empty() -> Self {
+        Self { m_stack_ptr: 0 }
+    }
+
+    fn new(stack_top: VirtAddr, start_fn: ThreadStartFn, start_arg: usize) -> Self {
+        /* the frame popped by <hw_switch_context>: r15, r14, r13, r12, rbx, rbp, rip */
+        let initial_frame: [usize; 7] =
+            [0, 0, start_arg, start_fn as usize, 0, 0, hw_thread_start as usize];
+
+        let stack_ptr = VirtAddr::from(*stack_top.align_down(C_STACK_ALIGN)
+                                       - mem::size_of_val(&initial_frame));
+        unsafe {
+            ptr::write(stack_ptr.as_ptr_mut::<[usize; 7]>(), initial_frame);
+        }
+        Self { m_stack_ptr: *stack_ptr }
+    }
+
+    unsafe fn switch(prev_context: *mut Self, next_context: *const Self) {
+        hw_switch_context(&mut (*prev_context).m_stack_ptr, (*next_context).m_stack_ptr);
+    }
+
+    unsafe fn enter_user(entry_point: VirtAddr,
+                         stack_ptr: VirtAddr,
+                         arg0: usize,
+                         arg1: usize)
+                         -> ! {
+        hw_enter_user(*entry_point, *stack_ptr, arg0, arg1)
+    }
+}
+
+global_asm!(include_str!("context_switch.S"), options(att_syntax));
@@ -0,0 +1,8 @@
+/*! x86_64 tasks implementation code */
+
+pub mod hw_thread_context;
+
+/**
+ * ELF `e_machine` of the executables which run on x86_64
+ */
+pub const C_HW_ELF_MACHINE: u16 = 62;
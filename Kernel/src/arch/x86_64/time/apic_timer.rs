@@ -0,0 +1,54 @@
+/*! Local APIC timer clock event */
+
+use core::time::Duration;
+
+use crate::{
+    arch::x86_64::intr::C_APIC_TIMER_INTR_VECTOR,
+    cpu::Cpu,
+    time::TClockEvent
+};
+
+/**
+ * `TClockEvent` on the timer of the local APIC of each CPU, which share
+ * the same calibrated frequency
+ */
+pub struct ApicTimer {
+    m_frequency: u64
+}
+
+impl ApicTimer /* Constructors */ {
+    /**
+     * Constructs an `ApicTimer` with the calibrated frequency in Hz of the
+     * divided bus clock
+     */
+    pub fn new(frequency: u64) -> Self {
+        assert_ne!(frequency, 0, "Tried to use an uncalibrated local APIC timer");
+
+        Self { m_frequency: frequency }
+    }
+}
+
+impl TClockEvent for ApicTimer {
+    fn name(&self) -> &'static str {
+        "Local APIC Timer"
+    }
+
+    fn max_delay(&self) -> Duration {
+        let max_nanos = u32::MAX as u128 * 1_000_000_000 / self.m_frequency as u128;
+        Duration::from_nanos(max_nanos as u64)
+    }
+
+    fn arm(&self, delay: Duration) {
+        /* a zero initial count would stop the timer instead */
+        let ticks = delay.as_nanos() * self.m_frequency as u128 / 1_000_000_000;
+        let ticks = ticks.clamp(1, u32::MAX as u128);
+
+        Cpu::current().hw_cpu()
+                      .local_apic()
+                      .start_timer(C_APIC_TIMER_INTR_VECTOR, ticks as u32);
+    }
+
+    fn disarm(&self) {
+        Cpu::current().hw_cpu().local_apic().stop_timer();
+    }
+}
@@ -0,0 +1,162 @@
+/*! High Precision Event Timer */
+
+use core::{
+    hint,
+    ptr::{
+        read_volatile,
+        write_volatile
+    }
+};
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::x86_64::acpi::AcpiManager,
+    dbg_print::DbgLevel,
+    dbg_println,
+    time::TClockSource,
+    vm::mem_manager::MemManager
+};
+
+/* size of the memory mapped registers of the timer block */
+const C_MMIO_SIZE: usize = 0x400;
+
+/* the counter period cannot exceed 100ns by specification */
+const C_MAX_PERIOD_FEMTOS: u64 = 100_000_000;
+const C_FEMTOS_PER_SEC: u64 = 1_000_000_000_000_000;
+
+/* <Register::GeneralConfig> bits */
+const C_CONFIG_ENABLE: usize = 0;
+const C_CONFIG_LEGACY_REPLACEMENT: usize = 1;
+
+/**
+ * Main counter of the HPET timer block described by the ACPI tables
+ */
+pub struct HpetTimer {
+    m_virt_addr: VirtAddr,
+    m_period_femtos: u64,
+    m_is_64bit_counter: bool
+}
+
+impl HpetTimer /* Constructors */ {
+    /**
+     * Maps the registers of the timer block and starts its main counter.
+     *
+     * Returns `None` without ACPI HPET table or with an invalid one
+     */
+    pub fn init() -> Option<Self> {
+        let hpet = AcpiManager::try_instance()?.hpet()?;
+
+        let mmio_phys_range =
+            hpet.base_phys_addr()..hpet.base_phys_addr().offset(C_MMIO_SIZE);
+        let mmio_range = MemManager::instance().map_mmio_region(mmio_phys_range)?;
+        let mut hpet_timer = Self { m_virt_addr: mmio_range.start,
+                                    m_period_femtos: 0,
+                                    m_is_64bit_counter: hpet.is_64bit_counter() };
+
+        let period_femtos =
+            unsafe { hpet_timer.read_reg(Register::Capabilities) }.bits_at(32..64);
+        if period_femtos == 0 || period_femtos > C_MAX_PERIOD_FEMTOS {
+            dbg_println!(DbgLevel::Warn,
+                         "Invalid HPET counter period: {}fs",
+                         period_femtos);
+            MemManager::instance().unmap_mmio_region(mmio_range);
+            return None;
+        }
+        hpet_timer.m_period_femtos = period_femtos;
+
+        /* the comparators are unused, keep the PIT and the RTC interrupts */
+        unsafe {
+            let mut general_config = hpet_timer.read_reg(Register::GeneralConfig);
+            general_config.set_bit(C_CONFIG_LEGACY_REPLACEMENT, false)
+                          .set_bit(C_CONFIG_ENABLE, true);
+            hpet_timer.write_reg(Register::GeneralConfig, general_config);
+        }
+        Some(hpet_timer)
+    }
+}
+
+impl HpetTimer /* Methods */ {
+    /**
+     * Busy waits for the given amount of microseconds.
+     *
+     * Returns the nanoseconds effectively elapsed
+     */
+    pub fn busy_wait(&self, micros: u64) -> u64 {
+        let wait_ticks = micros * 1_000_000_000 / self.m_period_femtos;
+
+        let begin_counter = self.read_counter();
+        let elapsed_ticks = loop {
+            let elapsed_ticks =
+                self.read_counter().wrapping_sub(begin_counter) & self.counter_mask();
+            if elapsed_ticks >= wait_ticks {
+                break elapsed_ticks;
+            }
+            hint::spin_loop();
+        };
+        elapsed_ticks * self.m_period_femtos / 1_000_000
+    }
+}
+
+impl HpetTimer /* Getters */ {
+    /**
+     * Returns the current value of the main counter
+     */
+    pub fn read_counter(&self) -> u64 {
+        unsafe { self.read_reg(Register::MainCounter) & self.counter_mask() }
+    }
+
+    /**
+     * Returns whether the main counter is 64bit wide, otherwise it wraps
+     * in few minutes
+     */
+    pub fn is_64bit_counter(&self) -> bool {
+        self.m_is_64bit_counter
+    }
+}
+
+impl HpetTimer /* Privates */ {
+    fn counter_mask(&self) -> u64 {
+        if self.m_is_64bit_counter {
+            u64::MAX
+        } else {
+            u32::MAX as u64
+        }
+    }
+
+    unsafe fn read_reg(&self, register: Register) -> u64 {
+        read_volatile((*self.m_virt_addr + register as usize) as *const u64)
+    }
+
+    unsafe fn write_reg(&self, register: Register, value: u64) {
+        write_volatile((*self.m_virt_addr + register as usize) as *mut u64, value);
+    }
+}
+
+impl TClockSource for HpetTimer {
+    fn name(&self) -> &'static str {
+        "HPET"
+    }
+
+    fn frequency(&self) -> u64 {
+        C_FEMTOS_PER_SEC / self.m_period_femtos
+    }
+
+    fn read(&self) -> u64 {
+        self.read_counter()
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+enum Register {
+    Capabilities  = 0x000,
+    GeneralConfig = 0x010,
+    MainCounter   = 0x0F0
+}
@@ -0,0 +1,150 @@
+/*! x86_64 clocks calibration and selection */
+
+use crate::{
+    arch::x86_64::{
+        intr::C_APIC_TIMER_INTR_VECTOR,
+        time::{
+            apic_timer::ApicTimer,
+            hpet_timer::HpetTimer,
+            pit::{
+                Pit,
+                PitClockEvent,
+                C_PIT_IRQ
+            },
+            rtc::Rtc,
+            tsc::Tsc
+        }
+    },
+    cpu::Cpu,
+    dbg_print::DbgLevel,
+    dbg_println,
+    intr::{
+        IntrFrame,
+        IntrManager
+    },
+    time::{
+        TClockEvent,
+        TClockSource,
+        THwClocks,
+        TimeManager
+    }
+};
+
+/* length of the window in which the TSC and the local APIC timer are measured */
+const C_CALIBRATION_MICROS: u64 = 10_000;
+
+/* <None> until <HwClocks::init_clocks()> selects them */
+static mut SM_HPET_TIMER: Option<HpetTimer> = None;
+static mut SM_TSC: Option<Tsc> = None;
+static mut SM_APIC_TIMER: Option<ApicTimer> = None;
+static SM_PIT_CLOCK_EVENT: PitClockEvent = PitClockEvent;
+
+/**
+ * x86_64 `THwClocks` implementation
+ */
+pub struct HwClocks;
+
+impl HwClocks /* Privates */ {
+    /**
+     * Measures the frequencies in Hz of the TSC and of the local APIC timer
+     * of the executing CPU against the HPET, or the PIT without it.
+     *
+     * The local APIC timer frequency is zero when its local APIC is not
+     * enabled
+     */
+    fn calibrate(hpet_timer: Option<&HpetTimer>) -> (u64, u64) {
+        let this_cpu = Cpu::current();
+        let local_apic = this_cpu.hw_cpu().local_apic();
+
+        let mut frequencies = (0, 0);
+        this_cpu.without_interrupts(|| {
+                    if local_apic.is_enabled() {
+                        local_apic.start_timer_masked(u32::MAX);
+                    }
+                    let tsc_begin = Tsc::read_counter();
+
+                    let elapsed_nanos = if let Some(hpet_timer) = hpet_timer {
+                        hpet_timer.busy_wait(C_CALIBRATION_MICROS)
+                    } else {
+                        Pit::busy_wait(C_CALIBRATION_MICROS);
+                        C_CALIBRATION_MICROS * 1_000
+                    };
+
+                    let tsc_ticks = Tsc::read_counter() - tsc_begin;
+                    let apic_timer_ticks = if local_apic.is_enabled() {
+                        let apic_timer_ticks =
+                            u32::MAX - local_apic.timer_current_count();
+                        local_apic.stop_timer();
+                        apic_timer_ticks as u64
+                    } else {
+                        0
+                    };
+
+                    frequencies = (ticks_to_frequency(tsc_ticks, elapsed_nanos),
+                                   ticks_to_frequency(apic_timer_ticks, elapsed_nanos));
+                });
+        frequencies
+    }
+}
+
+impl THwClocks for HwClocks {
+    fn init_clocks() -> (&'static dyn TClockSource, &'static dyn TClockEvent) {
+        let hpet_timer = unsafe {
+            SM_HPET_TIMER = HpetTimer::init();
+            SM_HPET_TIMER.as_ref()
+        };
+
+        let (tsc_frequency, apic_timer_frequency) = Self::calibrate(hpet_timer);
+        dbg_println!(DbgLevel::Debug,
+                     "TSC frequency: {}Hz, Local APIC Timer frequency: {}Hz",
+                     tsc_frequency,
+                     apic_timer_frequency);
+
+        /* the TSC is the cheapest to read, but it is reliable only if invariant */
+        let clock_source: &'static dyn TClockSource = match hpet_timer {
+            Some(hpet_timer) if !Tsc::is_invariant() && hpet_timer.is_64bit_counter() => {
+                hpet_timer
+            },
+            _ => unsafe {
+                SM_TSC = Some(Tsc::new(tsc_frequency));
+                SM_TSC.as_ref().unwrap()
+            }
+        };
+
+        /* without the local APIC there are no other CPUs, so the PIT is enough */
+        let clock_event: &'static dyn TClockEvent = if apic_timer_frequency != 0 {
+            IntrManager::register_handler(C_APIC_TIMER_INTR_VECTOR,
+                                          hw_clock_event_intr_handler);
+            unsafe {
+                SM_APIC_TIMER = Some(ApicTimer::new(apic_timer_frequency));
+                SM_APIC_TIMER.as_ref().unwrap()
+            }
+        } else {
+            SM_PIT_CLOCK_EVENT.disarm();
+            if IntrManager::register_irq_handler(C_PIT_IRQ, hw_clock_event_intr_handler)
+                .is_none()
+            {
+                dbg_println!(DbgLevel::Warn, "Failed to register the PIT interrupt");
+            }
+            &SM_PIT_CLOCK_EVENT
+        };
+
+        (clock_source, clock_event)
+    }
+
+    fn read_wall_clock_secs() -> u64 {
+        Rtc::read_unix_secs()
+    }
+}
+
+fn hw_clock_event_intr_handler(_intr_frame: &mut IntrFrame) {
+    TimeManager::handle_clock_event();
+}
+
+/**
+ * Returns the frequency in Hz of the given ticks counted in the given
+ * nanoseconds
+ */
+fn ticks_to_frequency(ticks: u64, elapsed_nanos: u64) -> u64 {
+    (ticks as u128 * 1_000_000_000 / elapsed_nanos as u128) as u64
+}
@@ -0,0 +1,8 @@
+/*! x86_64 clocks and timers */
+
+pub mod apic_timer;
+pub mod hpet_timer;
+pub mod hw_clocks;
+pub mod pit;
+pub mod rtc;
+pub mod tsc;
@@ -0,0 +1,110 @@
+/*! Programmable Interval Timer */
+
+use core::{
+    hint,
+    time::Duration
+};
+
+use bits::bit_fields::TBitFields;
+
+use crate::{
+    arch::x86_64::x64_port::X64Port,
+    intr::Irq,
+    time::TClockEvent
+};
+
+/**
+ * Legacy ISA interrupt line of the channel 0 of the PIT
+ */
+pub const C_PIT_IRQ: Irq = 0;
+
+/* frequency of the PIT input clock */
+const C_PIT_FREQUENCY: u64 = 1_193_182;
+
+/* I/O ports of the PIT and of the gate of the channel 2 */
+const C_CHANNEL_0_PORT: u16 = 0x40;
+const C_CHANNEL_2_PORT: u16 = 0x42;
+const C_COMMAND_PORT: u16 = 0x43;
+const C_CHANNEL_2_GATE_PORT: u16 = 0x61;
+
+/* channel 0/2, low & high byte access, mode 0 (interrupt on terminal count) */
+const C_CHANNEL_0_ONE_SHOT_COMMAND: u8 = 0b0011_0000;
+const C_CHANNEL_2_ONE_SHOT_COMMAND: u8 = 0b1011_0000;
+
+/**
+ * Legacy PIT, used as calibration reference when the HPET is not
+ * available
+ */
+pub struct Pit;
+
+impl Pit /* Static Functions */ {
+    /**
+     * Busy waits for the given amount of microseconds using the channel 2,
+     * which is not connected to any interrupt line
+     */
+    pub fn busy_wait(micros: u64) {
+        let gate_port = X64Port::<u8>::new(C_CHANNEL_2_GATE_PORT);
+        let command_port = X64Port::<u8>::new(C_COMMAND_PORT);
+        let channel_2_port = X64Port::<u8>::new(C_CHANNEL_2_PORT);
+
+        /* the counter is 16bit wide, longer delays are split */
+        let mut remaining_ticks = C_PIT_FREQUENCY * micros / 1_000_000;
+        while remaining_ticks > 0 {
+            let ticks = remaining_ticks.min(u16::MAX as u64);
+
+            unsafe {
+                /* keep the gate low and the speaker disconnected while programming */
+                let gate_value = gate_port.read() & !0b11;
+                gate_port.write(gate_value);
+
+                command_port.write(C_CHANNEL_2_ONE_SHOT_COMMAND);
+                channel_2_port.write(ticks as u8);
+                channel_2_port.write((ticks >> 8) as u8);
+
+                /* the count starts at the gate raise, the output goes high at its end */
+                gate_port.write(gate_value | 1);
+                while !gate_port.read().bit_at(5) {
+                    hint::spin_loop();
+                }
+            }
+            remaining_ticks -= ticks;
+        }
+    }
+}
+
+/**
+ * `TClockEvent` on the channel 0 of the PIT, used only when the local
+ * APIC is not available, so by the primary CPU alone
+ */
+pub struct PitClockEvent;
+
+impl TClockEvent for PitClockEvent {
+    fn name(&self) -> &'static str {
+        "PIT"
+    }
+
+    fn max_delay(&self) -> Duration {
+        Duration::from_nanos(u16::MAX as u64 * 1_000_000_000 / C_PIT_FREQUENCY)
+    }
+
+    fn arm(&self, delay: Duration) {
+        let ticks = delay.as_nanos() * C_PIT_FREQUENCY as u128 / 1_000_000_000;
+        let ticks = ticks.clamp(1, u16::MAX as u128);
+
+        /* the IRQ is raised once, when the output goes high at the terminal count */
+        unsafe {
+            let channel_0_port = X64Port::<u8>::new(C_CHANNEL_0_PORT);
+
+            X64Port::<u8>::new(C_COMMAND_PORT).write(C_CHANNEL_0_ONE_SHOT_COMMAND);
+            channel_0_port.write(ticks as u8);
+            channel_0_port.write((ticks >> 8) as u8);
+        }
+    }
+
+    fn disarm(&self) {
+        /* the command alone holds the output low until the next count */
+        unsafe {
+            X64Port::<u8>::new(C_COMMAND_PORT).write(C_CHANNEL_0_ONE_SHOT_COMMAND);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+/*! CMOS Real Time Clock */
+
+use core::hint;
+
+use bits::bit_fields::TBitFields;
+
+use crate::arch::x86_64::{
+    acpi::AcpiManager,
+    x64_port::X64Port
+};
+
+/* I/O ports to select and access the CMOS registers */
+const C_CMOS_SELECT_PORT: u16 = 0x70;
+const C_CMOS_DATA_PORT: u16 = 0x71;
+
+/* CMOS registers of the real time clock */
+const C_SECS_REG: u8 = 0x00;
+const C_MINS_REG: u8 = 0x02;
+const C_HOURS_REG: u8 = 0x04;
+const C_DAY_REG: u8 = 0x07;
+const C_MONTH_REG: u8 = 0x08;
+const C_YEAR_REG: u8 = 0x09;
+const C_STATUS_A_REG: u8 = 0x0a;
+const C_STATUS_B_REG: u8 = 0x0b;
+
+/* status registers bits */
+const C_STATUS_A_UPDATE_IN_PROGRESS: usize = 7;
+const C_STATUS_B_24_HOURS: usize = 1;
+const C_STATUS_B_BINARY: usize = 2;
+
+/* the hours register stores the PM flag into the highest bit */
+const C_HOURS_PM: usize = 7;
+
+/* century assumed when the FADT doesn't tell the CMOS register which stores it */
+const C_DEFAULT_CENTURY: u64 = 20;
+
+/* days between 0000-03-01 and 1970-01-01 of the proleptic Gregorian calendar */
+const C_UNIX_EPOCH_DAYS: u64 = 719_468;
+
+/**
+ * Battery backed clock, read once to obtain the wall clock at boot
+ */
+pub struct Rtc;
+
+impl Rtc /* Static Functions */ {
+    /**
+     * Returns the seconds elapsed from the Unix epoch
+     */
+    pub fn read_unix_secs() -> u64 {
+        /* read until two consecutive readings match to not mix two updates */
+        let mut date_time = RtcDateTime::read();
+        loop {
+            let next_date_time = RtcDateTime::read();
+            if next_date_time == date_time {
+                break;
+            }
+            date_time = next_date_time;
+        }
+        date_time.to_unix_secs()
+    }
+}
+
+/**
+ * Decoded date and time registers
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+struct RtcDateTime {
+    m_secs: u64,
+    m_mins: u64,
+    m_hours: u64,
+    m_day: u64,
+    m_month: u64,
+    m_year: u64
+}
+
+impl RtcDateTime /* Constructors */ {
+    /**
+     * Reads the registers once no update is in progress, converting them
+     * from BCD and 12 hours format when needed
+     */
+    fn read() -> Self {
+        while read_cmos_reg(C_STATUS_A_REG).bit_at(C_STATUS_A_UPDATE_IN_PROGRESS) {
+            hint::spin_loop();
+        }
+
+        let status_b = read_cmos_reg(C_STATUS_B_REG);
+        let decode = |value: u8| {
+            if status_b.bit_at(C_STATUS_B_BINARY) {
+                value as u64
+            } else {
+                (value >> 4) as u64 * 10 + (value & 0x0f) as u64
+            }
+        };
+
+        let raw_hours = read_cmos_reg(C_HOURS_REG);
+        let mut hours = decode(raw_hours & 0x7f);
+        if !status_b.bit_at(C_STATUS_B_24_HOURS) {
+            /* 12AM is midnight and 12PM is noon */
+            hours %= 12;
+            if raw_hours.bit_at(C_HOURS_PM) {
+                hours += 12;
+            }
+        }
+
+        let century =
+            AcpiManager::try_instance().and_then(|acpi_manager| acpi_manager.fadt())
+                                       .and_then(|fadt| fadt.century_cmos_reg())
+                                       .map_or(C_DEFAULT_CENTURY, |century_reg| {
+                                           decode(read_cmos_reg(century_reg))
+                                       });
+
+        Self { m_secs: decode(read_cmos_reg(C_SECS_REG)),
+               m_mins: decode(read_cmos_reg(C_MINS_REG)),
+               m_hours: hours,
+               m_day: decode(read_cmos_reg(C_DAY_REG)),
+               m_month: decode(read_cmos_reg(C_MONTH_REG)),
+               m_year: century * 100 + decode(read_cmos_reg(C_YEAR_REG)) }
+    }
+}
+
+impl RtcDateTime /* Methods */ {
+    /**
+     * Converts the date and time to seconds from the Unix epoch
+     */
+    fn to_unix_secs(&self) -> u64 {
+        /* count the years from March, so the leap day is the last of the year */
+        let year = if self.m_month <= 2 {
+            self.m_year - 1
+        } else {
+            self.m_year
+        };
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let month_from_march = (self.m_month + 9) % 12;
+        let day_of_year = (153 * month_from_march + 2) / 5 + self.m_day - 1;
+        let day_of_era =
+            year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = (era * 146_097 + day_of_era).saturating_sub(C_UNIX_EPOCH_DAYS);
+
+        days * 86_400 + self.m_hours * 3_600 + self.m_mins * 60 + self.m_secs
+    }
+}
+
+/**
+ * Reads the given CMOS register
+ */
+fn read_cmos_reg(cmos_reg: u8) -> u8 {
+    unsafe {
+        X64Port::<u8>::new(C_CMOS_SELECT_PORT).write(cmos_reg);
+        X64Port::<u8>::new(C_CMOS_DATA_PORT).read()
+    }
+}
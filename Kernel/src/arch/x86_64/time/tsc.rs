@@ -0,0 +1,64 @@
+/*! Time Stamp Counter */
+
+use core::arch::x86_64::{
+    __cpuid,
+    _rdtsc
+};
+
+use bits::bit_fields::TBitFields;
+
+use crate::time::TClockSource;
+
+/**
+ * Per-CPU cycles counter, assumed synchronized among the CPUs
+ */
+pub struct Tsc {
+    m_frequency: u64
+}
+
+impl Tsc /* Constructors */ {
+    /**
+     * Constructs a `Tsc` with the calibrated frequency in Hz
+     */
+    pub fn new(frequency: u64) -> Self {
+        assert_ne!(frequency, 0, "Tried to use an uncalibrated TSC");
+
+        Self { m_frequency: frequency }
+    }
+}
+
+impl Tsc /* Static Functions */ {
+    /**
+     * Returns the counter of the executing CPU
+     */
+    pub fn read_counter() -> u64 {
+        unsafe { _rdtsc() }
+    }
+
+    /**
+     * Returns whether the counter runs at constant rate in all the power
+     * states of the CPU
+     */
+    pub fn is_invariant() -> bool {
+        if unsafe { __cpuid(0x8000_0000) }.eax < 0x8000_0007 {
+            return false;
+        }
+
+        /* CPUID.80000007H:EDX.InvariantTsc[bit 8] */
+        unsafe { __cpuid(0x8000_0007) }.edx.bit_at(8)
+    }
+}
+
+impl TClockSource for Tsc {
+    fn name(&self) -> &'static str {
+        "TSC"
+    }
+
+    fn frequency(&self) -> u64 {
+        self.m_frequency
+    }
+
+    fn read(&self) -> u64 {
+        Self::read_counter()
+    }
+}
@@ -27,11 +27,6 @@ impl THwPageTableEntry for HwPageTableEntry {
         Self { m_entry_value: 0 }
     }
 
-    #[inline]
-    unsafe fn invalidate_in_tlb(&self) {
-        asm!("invlpg [{}]", in(reg) self.raw_phys_frame())
-    }
-
     #[inline]
     fn raw_phys_frame(&self) -> usize {
         self.m_entry_value & 0x000f_ffff_ffff_f000
@@ -87,6 +82,24 @@ impl THwPageTableEntry for HwPageTableEntry {
         self.m_entry_value.bit_at(2)
     }
 
+    #[inline]
+    fn is_lazy(&self) -> bool {
+        /* first bit available for the software */
+        self.m_entry_value.bit_at(9)
+    }
+
+    #[inline]
+    fn is_guard(&self) -> bool {
+        /* second bit available for the software */
+        self.m_entry_value.bit_at(10)
+    }
+
+    #[inline]
+    fn is_copy_on_write(&self) -> bool {
+        /* third bit available for the software */
+        self.m_entry_value.bit_at(11)
+    }
+
     #[inline]
     fn is_unused(&self) -> bool {
         self.m_entry_value == 0
@@ -148,6 +161,21 @@ impl THwPageTableEntry for HwPageTableEntry {
         self.m_entry_value.set_bit(2, is_user);
     }
 
+    #[inline]
+    fn set_lazy(&mut self, is_lazy: bool) {
+        self.m_entry_value.set_bit(9, is_lazy);
+    }
+
+    #[inline]
+    fn set_guard(&mut self, is_guard: bool) {
+        self.m_entry_value.set_bit(10, is_guard);
+    }
+
+    #[inline]
+    fn set_copy_on_write(&mut self, is_copy_on_write: bool) {
+        self.m_entry_value.set_bit(11, is_copy_on_write);
+    }
+
     #[inline]
     fn set_unused(&mut self) {
         self.m_entry_value = 0;
@@ -170,7 +198,10 @@ impl Debug for HwPageTableEntry {
                                    (self.is_accessed(), "is_accessed"),
                                    (self.is_dirty(), "is_dirty"),
                                    (self.is_no_execute(), "is_no_execute"),
-                                   (self.is_user(), "is_user")];
+                                   (self.is_user(), "is_user"),
+                                   (self.is_lazy(), "is_lazy"),
+                                   (self.is_guard(), "is_guard"),
+                                   (self.is_copy_on_write(), "is_copy_on_write")];
         let mut is_first = true;
         for (bit_value, str_name) in all_printable_flags {
             if bit_value {
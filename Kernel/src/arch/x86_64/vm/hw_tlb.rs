@@ -0,0 +1,31 @@
+/*! x86_64 TLB management */
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    vm::tlb::THwTlb
+};
+
+/* CR4.PGE, when toggled flushes the global entries too */
+const C_CR4_PAGE_GLOBAL_ENABLE: usize = 1 << 7;
+
+/**
+ * x86_64 `THwTlb` implementation
+ */
+pub struct HwTlb;
+
+impl THwTlb for HwTlb {
+    #[inline]
+    unsafe fn invalidate(virt_addr: VirtAddr) {
+        asm!("invlpg [{}]", in(reg) *virt_addr, options(nostack, preserves_flags));
+    }
+
+    unsafe fn flush_all() {
+        let cr4_value: usize;
+        asm!("mov {}, cr4", out(reg) cr4_value, options(nomem, nostack, preserves_flags));
+
+        asm!("mov cr4, {}",
+             in(reg) cr4_value ^ C_CR4_PAGE_GLOBAL_ENABLE,
+             options(nostack, preserves_flags));
+        asm!("mov cr4, {}", in(reg) cr4_value, options(nostack, preserves_flags));
+    }
+}
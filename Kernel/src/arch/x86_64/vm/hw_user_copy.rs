@@ -0,0 +1,37 @@
+/*! x86_64 userspace memory copy */
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    vm::user_copy::THwUserCopy
+};
+
+extern "C" {
+    fn hw_user_copy(dst_ptr: *mut u8, src_ptr: *const u8, size: usize) -> bool;
+
+    static hw_user_copy_instr: u8;
+    static hw_user_copy_fixup: u8;
+}
+
+/**
+ * x86_64 `THwUserCopy` implementation
+ */
+pub struct HwUserCopy;
+
+impl THwUserCopy for HwUserCopy {
+    unsafe fn copy(dst_ptr: *mut u8, src_ptr: *const u8, size: usize) -> bool {
+        hw_user_copy(dst_ptr, src_ptr, size)
+    }
+
+    fn fixup_of(instr_virt_addr: VirtAddr) -> Option<VirtAddr> {
+        /* the faulting <rep movsb> is restarted, so the RIP points to it */
+        unsafe {
+            if instr_virt_addr == VirtAddr::from(&hw_user_copy_instr as *const u8) {
+                Some(VirtAddr::from(&hw_user_copy_fixup as *const u8))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+global_asm!(include_str!("user_copy.S"), options(att_syntax));
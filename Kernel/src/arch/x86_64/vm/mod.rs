@@ -2,3 +2,5 @@
 
 pub mod hw_page_dir;
 pub mod hw_page_table_entry;
+pub mod hw_tlb;
+pub mod hw_user_copy;
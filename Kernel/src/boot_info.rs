@@ -24,7 +24,8 @@ static mut SM_BOOT_INFORMATION: Option<BootInfo> = None;
 pub struct BootInfo {
     m_boot_loader_name: String,
     m_cmd_line_args_buf: String,
-    m_boot_mem_areas: Vec<Range<PhysAddr>>
+    m_boot_mem_areas: Vec<Range<PhysAddr>>,
+    m_boot_modules: Vec<BootModule>
 }
 
 impl BootInfo /* Constructors */ {
@@ -49,7 +50,8 @@ impl BootInfo /* Constructors */ {
                                 String::from(hw_boot_info.boot_loader_name()),
                             m_cmd_line_args_buf:
                                 String::from(hw_boot_info.cmd_line_args()),
-                            m_boot_mem_areas: hw_boot_info.phys_mem_ranges() });
+                            m_boot_mem_areas: hw_boot_info.phys_mem_ranges(),
+                            m_boot_modules: hw_boot_info.boot_modules() });
         }
     }
 }
@@ -59,10 +61,7 @@ impl BootInfo /* Methods */ {
      * Returns whether the given key exists into the command line
      */
     pub fn cmd_line_arg_exists(&self, key_to_search: &str) -> bool {
-        self.cmd_line_args()
-            .split_whitespace()
-            .find(|arg_str| arg_str.contains(key_to_search))
-            .is_some()
+        Self::cmd_line_find_arg_in(self.cmd_line_args(), key_to_search).is_some()
     }
 
     /**
@@ -72,16 +71,7 @@ impl BootInfo /* Methods */ {
      * the `=`
      */
     pub fn cmd_line_find_arg(&self, key_to_search: &str) -> Option<(&str, Option<&str>)> {
-        self.cmd_line_args()
-            .split_whitespace()
-            .find(|arg_str| arg_str.contains(key_to_search))
-            .map(|arg_str| {
-                if let Some(eq_sign_pos) = arg_str.find("=") {
-                    (&arg_str[..eq_sign_pos], Some(&arg_str[eq_sign_pos + 1..]))
-                } else {
-                    (arg_str, None)
-                }
-            })
+        Self::cmd_line_find_arg_in(self.cmd_line_args(), key_to_search)
     }
 
     /**
@@ -93,23 +83,7 @@ impl BootInfo /* Methods */ {
     pub fn cmd_line_find_arg_int(&self,
                                  key_to_search: &str)
                                  -> Option<(&str, Option<usize>)> {
-        self.cmd_line_find_arg(key_to_search).map(|(key, value_opt)| {
-                                                 let int_value =
-                                                     value_opt.map(|str_value| {
-                                                                  if let Ok(int_value) =
-                                                             usize::from_str(str_value)
-                                                         {
-                                                             int_value
-                                                         } else {
-                                                             panic!("invalid integer \
-                                                                     for `{}`: {}",
-                                                                    key_to_search,
-                                                                    str_value)
-                                                         }
-                                                              });
-
-                                                 (key, int_value)
-                                             })
+        Self::cmd_line_find_arg_int_in(self.cmd_line_args(), key_to_search)
     }
 }
 
@@ -123,6 +97,66 @@ impl BootInfo /* Static Functions */ {
                                                  BootInfo::init_instance()")
         }
     }
+
+    /**
+     * Reads the command line arguments directly from the given raw boot
+     * information pointer, without allocating, and gives them to the given
+     * closure.
+     *
+     * Used only by the boot code which runs before the kernel heap exists
+     */
+    pub fn with_raw_cmd_line_args<F, R>(raw_boot_info_ptr: *const u8,
+                                        cmd_line_fn: F)
+                                        -> R
+        where F: FnOnce(&str) -> R {
+        cmd_line_fn(HwBootInfo::from(raw_boot_info_ptr).cmd_line_args())
+    }
+
+    /**
+     * Searches into the given command line arguments for an option with the
+     * given `key_to_search`.
+     *
+     * If it founds the key returns the key and, if any, the option after
+     * the `=`
+     */
+    pub fn cmd_line_find_arg_in<'a>(cmd_line_args: &'a str,
+                                    key_to_search: &str)
+                                    -> Option<(&'a str, Option<&'a str>)> {
+        cmd_line_args.split_whitespace()
+                     .find(|arg_str| arg_str.contains(key_to_search))
+                     .map(|arg_str| {
+                         if let Some(eq_sign_pos) = arg_str.find("=") {
+                             (&arg_str[..eq_sign_pos], Some(&arg_str[eq_sign_pos + 1..]))
+                         } else {
+                             (arg_str, None)
+                         }
+                     })
+    }
+
+    /**
+     * Searches into the given command line arguments for an option with the
+     * given `key_to_search`.
+     *
+     * If it founds the key returns the key and, if any, the option after
+     * the `=` as `usize` value
+     */
+    pub fn cmd_line_find_arg_int_in<'a>(cmd_line_args: &'a str,
+                                        key_to_search: &str)
+                                        -> Option<(&'a str, Option<usize>)> {
+        Self::cmd_line_find_arg_in(cmd_line_args, key_to_search).map(|(key, value_opt)| {
+            let int_value = value_opt.map(|str_value| {
+                                         if let Ok(int_value) = usize::from_str(str_value) {
+                                             int_value
+                                         } else {
+                                             panic!("invalid integer for `{}`: {}",
+                                                    key_to_search,
+                                                    str_value)
+                                         }
+                                     });
+
+            (key, int_value)
+        })
+    }
 }
 
 impl BootInfo /* Getters */ {
@@ -146,6 +180,52 @@ impl BootInfo /* Getters */ {
     pub fn boot_mem_areas(&self) -> &Vec<Range<PhysAddr>> {
         &self.m_boot_mem_areas
     }
+
+    /**
+     * Returns the `Vec` of the `BootModule`s loaded by the bootloader
+     */
+    pub fn boot_modules(&self) -> &Vec<BootModule> {
+        &self.m_boot_modules
+    }
+}
+
+/**
+ * File loaded into the physical memory by the bootloader together with the
+ * kernel.
+ *
+ * Its frames are never given to the physical frames allocators
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct BootModule {
+    m_phys_range: Range<PhysAddr>,
+    m_cmd_line: String
+}
+
+impl BootModule /* Constructors */ {
+    /**
+     * Constructs a `BootModule` which occupies the given physical `Range`
+     */
+    pub fn new(phys_range: Range<PhysAddr>, cmd_line: &str) -> Self {
+        Self { m_phys_range: phys_range,
+               m_cmd_line: String::from(cmd_line) }
+    }
+}
+
+impl BootModule /* Getters */ {
+    /**
+     * Returns the physical `Range` which contains the module
+     */
+    pub fn phys_range(&self) -> &Range<PhysAddr> {
+        &self.m_phys_range
+    }
+
+    /**
+     * Returns the command-line given by the bootloader to the module
+     */
+    pub fn cmd_line(&self) -> &str {
+        self.m_cmd_line.as_str()
+    }
 }
 
 /**
@@ -167,4 +247,9 @@ pub trait THwBootInfo: From<*const u8> {
      * Returns a filled `BootMemAreas`
      */
     fn phys_mem_ranges(&self) -> Vec<Range<PhysAddr>>;
+
+    /**
+     * Returns the `BootModule`s loaded by the bootloader
+     */
+    fn boot_modules(&self) -> Vec<BootModule>;
 }
@@ -1,11 +1,21 @@
 /*! Kernel CPU management */
 
-use alloc::vec::Vec;
-
-use crate::arch::hw_cpu::HwCpu;
-
-/* All the CPUs descriptors currently active */
-static mut SM_ALL_CPUS: Vec<Cpu> = Vec::new();
+use alloc::{
+    boxed::Box,
+    vec::Vec
+};
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    arch::hw_cpu::HwCpu,
+    dbg_print::DbgLevel,
+    dbg_println,
+    task::sched::RunQueue,
+    time::TimerQueue
+};
+
+/* All the CPUs descriptors, boxed since the hardware keeps their addresses */
+static mut SM_ALL_CPUS: Vec<Box<Cpu>> = Vec::new();
 
 /**
  * The index of the `Cpu` inside the global vector
@@ -16,7 +26,9 @@ pub type CpuId = u16;
  * High-level CPU management
  */
 pub struct Cpu {
-    m_hw_cpu: HwCpu
+    m_hw_cpu: HwCpu,
+    m_timer_queue: TimerQueue,
+    m_run_queue: RunQueue
 }
 
 impl Cpu /* Constructors */ {
@@ -26,14 +38,25 @@ impl Cpu /* Constructors */ {
      * Called once by `kernel_rust_start()`
      */
     pub fn early_init() {
-        Self::add_cpu(Self { m_hw_cpu: HwCpu::new_bsp() }).m_hw_cpu.init();
+        Self::add_cpu(Self::new(HwCpu::new_bsp())).m_hw_cpu.init();
     }
 
     /**
-     * Initializes the current secondary CPU.
+     * Initializes the current secondary CPU, registered by
+     * `Cpu::start_aps()` with the given `CpuId`.
+     *
+     * Called once by `kernel_ap_rust_start()`
      */
-    pub fn init_ap() {
-        Self::add_cpu(Self { m_hw_cpu: HwCpu::new_ap() }).m_hw_cpu.init();
+    pub fn init_ap(cpu_id: CpuId) {
+        let this_cpu = unsafe {
+            SM_ALL_CPUS.get_mut(cpu_id as usize)
+                       .expect(format!("Started an unregistered Cpu with id: {}", cpu_id)
+                               .as_str())
+        };
+        this_cpu.m_hw_cpu.init();
+
+        /* the shared interrupt controllers are already initialized by the BSP */
+        Self::init_interrupts_for_this();
     }
 }
 
@@ -70,14 +93,83 @@ impl Cpu /* Methods */ {
     }
 
     /**
-     * Halts this CPU
+     * Enables the hardware interrupts for this `Cpu` and waits for the
+     * next one
+     */
+    pub fn wait_interrupt(&self) {
+        self.m_hw_cpu.do_wait_interrupt();
+    }
+
+    /**
+     * Asks to this `Cpu` to check whether its running `Thread` must be
+     * preempted
+     */
+    pub fn notify_reschedule(&self) {
+        self.m_hw_cpu.notify_reschedule();
+    }
+
+    /**
+     * Sets the top of the kernel stack on which this `Cpu` enters from the
+     * userspace.
+     *
+     * Must be called by this `Cpu` with the interrupts disabled
+     */
+    pub fn set_kern_stack_top(&self, stack_top: VirtAddr) {
+        self.m_hw_cpu.set_kern_stack_top(stack_top);
+    }
+
+    /**
+     * Sets the thread pointer of the userspace `Thread` which this `Cpu`
+     * is going to execute.
+     *
+     * Must be called by this `Cpu` with the interrupts disabled
+     */
+    pub fn set_user_thread_ptr(&self, thread_ptr: VirtAddr) {
+        self.m_hw_cpu.set_user_thread_ptr(thread_ptr);
+    }
+
+    /**
+     * Halts all the other CPUs then resets the machine, or halts this CPU
+     * too if the reset is not supported
+     */
+    pub fn reboot(&self) -> ! {
+        HwCpu::halt_others();
+        HwCpu::do_reboot();
+        loop {
+            self.m_hw_cpu.do_halt();
+        }
+    }
+
+    /**
+     * Halts all the other CPUs then powers off the machine, or halts this
+     * CPU too if the power off is not supported
+     */
+    pub fn power_off(&self) -> ! {
+        HwCpu::halt_others();
+        HwCpu::do_power_off();
+        loop {
+            self.m_hw_cpu.do_halt();
+        }
+    }
+
+    /**
+     * Halts this CPU and all the others
      */
     pub fn halt(&self) -> ! {
-        /* TODO halt other CPUs */
+        HwCpu::halt_others();
         loop {
             self.m_hw_cpu.do_halt();
         }
     }
+
+    /**
+     * Waits forever for the interrupts, which are enabled
+     */
+    pub fn idle(&self) -> ! {
+        loop {
+            self.m_hw_cpu.do_wait_interrupt();
+        }
+    }
 }
 
 impl Cpu /* Static Functions */ {
@@ -96,6 +188,44 @@ impl Cpu /* Static Functions */ {
         /* initialize the interrupts management */
         this_cpu.m_hw_cpu.init_interrupts();
     }
+
+    /**
+     * Registers and starts, one at time, all the secondary CPUs, which
+     * are numbered after the primary one.
+     *
+     * Called once by `kernel_rust_start()` after the interrupts management
+     * of the primary CPU
+     */
+    pub fn start_aps() {
+        let aps_count = HwCpu::aps_count();
+        unsafe {
+            SM_ALL_CPUS.reserve_exact(aps_count);
+        }
+
+        for ap_index in 0..aps_count {
+            let cpu_id = (ap_index + 1) as CpuId;
+
+            let ap_cpu = Self::add_cpu(Self::new(HwCpu::new_ap(cpu_id)));
+            if !ap_cpu.m_hw_cpu.start() {
+                dbg_println!(DbgLevel::Warn, "Cpu {} did not start", cpu_id);
+            }
+        }
+    }
+
+    /**
+     * Returns the number of the registered `Cpu`s, which includes the
+     * ones which did not start
+     */
+    pub fn cpus_count() -> usize {
+        unsafe { SM_ALL_CPUS.len() }
+    }
+
+    /**
+     * Returns whether the CPUs support the 1GiB huge pages
+     */
+    pub fn supports_1gib_pages() -> bool {
+        HwCpu::supports_1gib_pages()
+    }
 }
 
 impl Cpu /* Getters */ {
@@ -111,12 +241,36 @@ impl Cpu /* Getters */ {
      */
     pub fn by_id(cpu_id: CpuId) -> &'static Self {
         unsafe {
-            SM_ALL_CPUS.get(cpu_id as usize)
-                       .expect(format!("Requested an unregistered Cpu with id: {}",
-                                       cpu_id).as_str())
+            /* called into the interrupt handlers too, so it must not allocate */
+            SM_ALL_CPUS.get(cpu_id as usize).unwrap_or_else(|| {
+                                                panic!("Requested an unregistered Cpu \
+                                                        with id: {}",
+                                                       cpu_id)
+                                            })
         }
     }
 
+    /**
+     * Returns the architecture dependent part of this `Cpu`
+     */
+    pub fn hw_cpu(&self) -> &HwCpu {
+        &self.m_hw_cpu
+    }
+
+    /**
+     * Returns the `TimerQueue` of the timers run by this `Cpu`
+     */
+    pub fn timer_queue(&self) -> &TimerQueue {
+        &self.m_timer_queue
+    }
+
+    /**
+     * Returns the `RunQueue` of the `Thread`s run by this `Cpu`
+     */
+    pub fn run_queue(&self) -> &RunQueue {
+        &self.m_run_queue
+    }
+
     /**
      * Returns the `CpuId` of this `Cpu`
      */
@@ -151,9 +305,25 @@ impl Cpu /* Getters */ {
     pub fn are_interrupts_enabled(&self) -> bool {
         self.m_hw_cpu.are_interrupts_enabled()
     }
+
+    /**
+     * Returns whether this `Cpu` has completed its initialization
+     */
+    pub fn is_online(&self) -> bool {
+        self.m_hw_cpu.is_online()
+    }
 }
 
 impl Cpu /* Privates */ {
+    /**
+     * Constructs a `Cpu` around the given `HwCpu`
+     */
+    fn new(hw_cpu: HwCpu) -> Self {
+        Self { m_hw_cpu: hw_cpu,
+               m_timer_queue: TimerQueue::new(),
+               m_run_queue: RunQueue::new() }
+    }
+
     /**
      * Stores the given `Cpu` into the `SM_ALL_CPUS` array
      */
@@ -161,9 +331,9 @@ impl Cpu /* Privates */ {
         let cpu_id = cpu.id();
 
         unsafe {
-            assert!(SM_ALL_CPUS.get(cpu_id as usize).is_none());
+            assert_eq!(SM_ALL_CPUS.len(), cpu_id as usize);
 
-            SM_ALL_CPUS.push(cpu);
+            SM_ALL_CPUS.push(Box::new(cpu));
             &mut SM_ALL_CPUS[cpu_id as usize]
         }
     }
@@ -180,9 +350,15 @@ pub trait THwCpu {
     fn new_bsp() -> Self;
 
     /**
-     * Constructs an `HwCpu` which identifies an AP CPU
+     * Constructs an `HwCpu` which identifies the AP CPU with the given
+     * `CpuId`, which is between 1 and `THwCpu::aps_count()`
      */
-    fn new_ap() -> Self;
+    fn new_ap(cpu_id: CpuId) -> Self;
+
+    /**
+     * Returns the number of the AP CPUs which can be started
+     */
+    fn aps_count() -> usize;
 
     /**
      * Once the `HwCpu` is stored into the static `SM_ALL_CPUS` array this
@@ -198,11 +374,56 @@ pub trait THwCpu {
      */
     fn init_interrupts(&'static mut self);
 
+    /**
+     * Wakes up this AP `HwCpu`, which must call `Cpu::init_ap()` with its
+     * `CpuId`.
+     *
+     * Returns whether the AP came online
+     */
+    fn start(&'static self) -> bool;
+
+    /**
+     * Asks to all the other CPUs to halt themselves
+     */
+    fn halt_others();
+
     /**
      * Halts this `HwCpu`
      */
     fn do_halt(&self);
 
+    /**
+     * Resets the machine, returns only if it is not supported
+     */
+    fn do_reboot();
+
+    /**
+     * Powers off the machine, returns only if it is not supported
+     */
+    fn do_power_off();
+
+    /**
+     * Enables the hardware interrupts and waits for the next one
+     */
+    fn do_wait_interrupt(&self);
+
+    /**
+     * Sends to this `HwCpu` the inter-processor interrupt after which the
+     * scheduler checks the preemption
+     */
+    fn notify_reschedule(&self);
+
+    /**
+     * Sets the top of the stack loaded by the system calls and by the
+     * interrupts which arrive from the userspace
+     */
+    fn set_kern_stack_top(&self, stack_top: VirtAddr);
+
+    /**
+     * Sets the thread pointer register of the userspace
+     */
+    fn set_user_thread_ptr(&self, thread_ptr: VirtAddr);
+
     /**
      * Enable hardware interrupts for this `Cpu`
      */
@@ -218,6 +439,11 @@ pub trait THwCpu {
      */
     fn current_id() -> CpuId;
 
+    /**
+     * Returns whether the CPU supports the 1GiB huge pages
+     */
+    fn supports_1gib_pages() -> bool;
+
     /**
      * Returns the hardware `CpuId` of this `HwCpu`
      */
@@ -242,4 +468,9 @@ pub trait THwCpu {
      * Returns whether this `Cpu` have hardware interrupts enabled
      */
     fn are_interrupts_enabled(&self) -> bool;
+
+    /**
+     * Returns whether this `HwCpu` has completed `THwCpu::init_interrupts()`
+     */
+    fn is_online(&self) -> bool;
 }
@@ -0,0 +1,143 @@
+/*! Kernel block devices support */
+
+use api_data::error::class::OsErrorClass;
+
+use crate::dev::TDevice;
+
+/**
+ * Block device driver interface.
+ *
+ * The device is addressed by blocks of `block_size()` bytes, which is a
+ * power of two. The byte granular methods are provided on top of the
+ * block ones, and read-modify-write the partially covered blocks
+ */
+pub trait TBlockDevice: TDevice + Send + Sync {
+    /**
+     * Returns the size in bytes of the blocks
+     */
+    fn block_size(&self) -> usize;
+
+    /**
+     * Returns the amount of blocks of the device
+     */
+    fn blocks_count(&self) -> u64;
+
+    /**
+     * Reads the blocks from the given one into the given buffer, which
+     * length must be a multiple of the `block_size()`
+     */
+    fn read_blocks(&self,
+                   first_block: u64,
+                   buffer: &mut [u8])
+                   -> Result<(), OsErrorClass>;
+
+    /**
+     * Writes the given buffer to the blocks from the given one, the buffer
+     * length must be a multiple of the `block_size()`
+     */
+    fn write_blocks(&self, first_block: u64, buffer: &[u8]) -> Result<(), OsErrorClass>;
+
+    /**
+     * Writes back the data cached by the driver
+     */
+    fn sync(&self) -> Result<(), OsErrorClass> {
+        Ok(())
+    }
+
+    /**
+     * Returns whether the device refuses the writes
+     */
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /**
+     * Returns the size in bytes of the device
+     */
+    fn size(&self) -> u64 {
+        self.blocks_count() * self.block_size() as u64
+    }
+
+    /**
+     * Reads the bytes from the given offset into the given buffer
+     */
+    fn read_bytes(&self, offset: u64, buffer: &mut [u8]) -> Result<(), OsErrorClass> {
+        self.ensure_in_bounds(offset, buffer.len())?;
+
+        let block_size = self.block_size();
+        let mut block_buffer = vec![0; block_size];
+        let mut done_bytes = 0;
+        while done_bytes < buffer.len() {
+            let position = offset + done_bytes as u64;
+            let block_index = position / block_size as u64;
+            let block_offset = (position % block_size as u64) as usize;
+            let remaining_bytes = buffer.len() - done_bytes;
+
+            if block_offset == 0 && remaining_bytes >= block_size {
+                /* the whole blocks are read directly into the buffer */
+                let whole_bytes = remaining_bytes - remaining_bytes % block_size;
+                self.read_blocks(block_index,
+                                 &mut buffer[done_bytes..done_bytes + whole_bytes])?;
+                done_bytes += whole_bytes;
+            } else {
+                let chunk_bytes = (block_size - block_offset).min(remaining_bytes);
+
+                self.read_blocks(block_index, &mut block_buffer)?;
+                let block_chunk = &block_buffer[block_offset..block_offset + chunk_bytes];
+                buffer[done_bytes..done_bytes + chunk_bytes].copy_from_slice(block_chunk);
+                done_bytes += chunk_bytes;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes the given buffer at the given offset
+     */
+    fn write_bytes(&self, offset: u64, buffer: &[u8]) -> Result<(), OsErrorClass> {
+        self.ensure_in_bounds(offset, buffer.len())?;
+
+        let block_size = self.block_size();
+        let mut block_buffer = vec![0; block_size];
+        let mut done_bytes = 0;
+        while done_bytes < buffer.len() {
+            let position = offset + done_bytes as u64;
+            let block_index = position / block_size as u64;
+            let block_offset = (position % block_size as u64) as usize;
+            let remaining_bytes = buffer.len() - done_bytes;
+
+            if block_offset == 0 && remaining_bytes >= block_size {
+                /* the whole blocks are written directly from the buffer */
+                let whole_bytes = remaining_bytes - remaining_bytes % block_size;
+                self.write_blocks(block_index,
+                                  &buffer[done_bytes..done_bytes + whole_bytes])?;
+                done_bytes += whole_bytes;
+            } else {
+                let chunk_bytes = (block_size - block_offset).min(remaining_bytes);
+
+                /* the untouched part of the block must be preserved */
+                self.read_blocks(block_index, &mut block_buffer)?;
+                block_buffer[block_offset..block_offset + chunk_bytes]
+                    .copy_from_slice(&buffer[done_bytes..done_bytes + chunk_bytes]);
+                self.write_blocks(block_index, &block_buffer)?;
+                done_bytes += chunk_bytes;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns `OsErrorClass::EndOfDataReached` when the given byte range
+     * exceeds the device
+     */
+    fn ensure_in_bounds(&self, offset: u64, len: usize) -> Result<(), OsErrorClass> {
+        let end_offset =
+            offset.checked_add(len as u64).ok_or(OsErrorClass::LimitOverflow)?;
+
+        if end_offset > self.size() {
+            Err(OsErrorClass::EndOfDataReached)
+        } else {
+            Ok(())
+        }
+    }
+}
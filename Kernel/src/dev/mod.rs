@@ -14,12 +14,14 @@ use api_data::object::device::{
 use sync::SpinRwLock;
 
 use crate::dev::{
+    block::TBlockDevice,
     random::TRandomDevice,
     uart::TUartDevice
 };
 
+pub mod block;
+pub mod ram_disk;
 pub mod random;
-pub mod adapters;
 pub mod uart;
 
 /* <None> until <DevManager::early_init()> is called */
@@ -47,7 +49,8 @@ impl DevManager /* Constructors */ {
              * Kernel/arch/<arch_name>/dev/mod.rs.
              *
              * Each architecture must implement it and here must be registered at least
-             * the random device and the serial device
+             * the random device and the serial device. The same file implements
+             * <DevManager::early_random_u64()> too
              */
             SM_DEV_MANAGER.register_early_devices();
         }
@@ -177,6 +180,13 @@ pub trait TDevice {
     fn as_uart(&self) -> Option<&dyn TUartDevice> {
         None
     }
+
+    /**
+     * Downcast this `TDevice` to a `TBlockDevice`
+     */
+    fn as_block(&self) -> Option<&dyn TBlockDevice> {
+        None
+    }
 }
 
 impl TDevice for Arc<dyn TDevice> {
@@ -199,4 +209,8 @@ impl TDevice for Arc<dyn TDevice> {
     fn as_uart(&self) -> Option<&dyn TUartDevice> {
         (**self).as_uart()
     }
+
+    fn as_block(&self) -> Option<&dyn TBlockDevice> {
+        (**self).as_block()
+    }
 }
@@ -0,0 +1,219 @@
+/*! Memory backed block device */
+
+use alloc::{
+    string::String,
+    sync::Arc
+};
+use core::{
+    slice,
+    sync::atomic::{
+        AtomicU32,
+        Ordering
+    }
+};
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::{
+        device::{
+            DeviceId,
+            DeviceIdClass,
+            DeviceIdType
+        },
+        types::ObjType
+    }
+};
+use sync::SpinRwLock;
+
+use crate::{
+    addr::TAddress,
+    boot_info::{
+        BootInfo,
+        BootModule
+    },
+    dbg_print::DbgLevel,
+    dev::{
+        block::TBlockDevice,
+        TDevice
+    },
+    filesystem::Vfs,
+    vm::mem_manager::MemManager
+};
+
+/**
+ * Size of the blocks exposed by the `RamDisk`s
+ */
+pub const C_RAM_DISK_BLOCK_SIZE: usize = 512;
+
+/* first word of the command line of the bootloader modules used as disks */
+const C_DISK_MODULE_CMD_LINE: &str = "disk";
+
+/* serial of the <DeviceId> of the next <RamDisk> */
+static SM_NEXT_RAM_DISK_SERIAL: AtomicU32 = AtomicU32::new(0);
+
+/**
+ * `TBlockDevice` which exposes a memory area as disk.
+ *
+ * It allows to mount the filesystem images loaded by the bootloader as
+ * modules, which are given back to the tools with their modifications
+ */
+pub struct RamDisk {
+    m_device_id: DeviceId,
+    m_data: SpinRwLock<&'static mut [u8]>
+}
+
+impl RamDisk /* Constructors */ {
+    /**
+     * Constructs a `RamDisk` over the given memory, which tail is ignored
+     * when it doesn't fill a whole block
+     */
+    pub fn new(data: &'static mut [u8]) -> Self {
+        let serial = SM_NEXT_RAM_DISK_SERIAL.fetch_add(1, Ordering::SeqCst);
+
+        Self { m_device_id: DeviceId::new(DeviceIdType::Block,
+                                          DeviceIdClass::Storage,
+                                          serial),
+               m_data: SpinRwLock::const_new(data) }
+    }
+}
+
+impl RamDisk /* Static Functions */ {
+    /**
+     * Mounts the filesystems of the `BootModule`s with `disk <path>` as
+     * command line at the given paths, creating the missing directories
+     */
+    pub fn mount_boot_modules() {
+        for boot_module in BootInfo::instance().boot_modules().iter() {
+            let mount_path = match Self::disk_module_mount_path(boot_module) {
+                Some(Some(mount_path)) => mount_path,
+                Some(None) => {
+                    dbg_println!(DbgLevel::Warn,
+                                 "RamDisk: missing mount path for '{}'",
+                                 boot_module.cmd_line());
+                    continue;
+                },
+                None => continue
+            };
+
+            let phys_range = boot_module.phys_range();
+            let virt_addr =
+                MemManager::instance().layout_manager()
+                                      .phys_addr_to_virt_addr(phys_range.start);
+
+            /* the frames of the modules are reserved for the whole kernel lifetime and
+             * each module is given to a single <RamDisk>
+             */
+            let data = unsafe {
+                slice::from_raw_parts_mut(virt_addr.as_ptr_mut::<u8>(),
+                                          *phys_range.end - *phys_range.start)
+            };
+
+            let vfs = Vfs::instance();
+            let mount_result = match vfs.create(mount_path, ObjType::Dir) {
+                Ok(_) | Err(OsErrorClass::IdentifierNotAvailable) => {
+                    vfs.mount_device(mount_path, Arc::new(Self::new(data)))
+                },
+                Err(os_error_class) => Err(os_error_class)
+            };
+            if let Err(os_error_class) = mount_result {
+                dbg_println!(DbgLevel::Err,
+                             "RamDisk: failed to mount at {}: {}",
+                             mount_path,
+                             os_error_class);
+            }
+        }
+    }
+
+    /**
+     * Returns whether the given `BootModule` must be used as disk instead
+     * of initrd
+     */
+    pub fn is_disk_module(boot_module: &BootModule) -> bool {
+        Self::disk_module_mount_path(boot_module).is_some()
+    }
+}
+
+impl RamDisk /* Privates */ {
+    /**
+     * Returns the byte offset of the given block, checking that the given
+     * amount of bytes is made of whole blocks and fits the disk
+     */
+    fn block_offset(&self,
+                    first_block: u64,
+                    len: usize,
+                    disk_size: usize)
+                    -> Result<usize, OsErrorClass> {
+        if len % C_RAM_DISK_BLOCK_SIZE != 0 {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        (first_block as usize).checked_mul(C_RAM_DISK_BLOCK_SIZE)
+                              .filter(|offset| {
+                                  offset.checked_add(len).map_or(false, |end_offset| {
+                                                             end_offset <= disk_size
+                                                         })
+                              })
+                              .ok_or(OsErrorClass::EndOfDataReached)
+    }
+
+    /**
+     * Returns the mount path given into the command line of the disk
+     * `BootModule`s, `None` for the other modules
+     */
+    fn disk_module_mount_path(boot_module: &BootModule) -> Option<Option<&str>> {
+        let mut cmd_line_args = boot_module.cmd_line().split_whitespace();
+
+        if cmd_line_args.next() == Some(C_DISK_MODULE_CMD_LINE) {
+            Some(cmd_line_args.next())
+        } else {
+            None
+        }
+    }
+}
+
+impl TDevice for RamDisk {
+    fn device_id(&self) -> DeviceId {
+        self.m_device_id
+    }
+
+    fn device_name(&self) -> String {
+        String::from("ramdisk")
+    }
+
+    fn init_hw(&self) -> bool {
+        true
+    }
+
+    fn as_block(&self) -> Option<&dyn TBlockDevice> {
+        Some(self)
+    }
+}
+
+impl TBlockDevice for RamDisk {
+    fn block_size(&self) -> usize {
+        C_RAM_DISK_BLOCK_SIZE
+    }
+
+    fn blocks_count(&self) -> u64 {
+        (self.m_data.read().len() / C_RAM_DISK_BLOCK_SIZE) as u64
+    }
+
+    fn read_blocks(&self,
+                   first_block: u64,
+                   buffer: &mut [u8])
+                   -> Result<(), OsErrorClass> {
+        let data = self.m_data.read();
+
+        let offset = self.block_offset(first_block, buffer.len(), data.len())?;
+        buffer.copy_from_slice(&data[offset..offset + buffer.len()]);
+        Ok(())
+    }
+
+    fn write_blocks(&self, first_block: u64, buffer: &[u8]) -> Result<(), OsErrorClass> {
+        let mut data = self.m_data.write();
+
+        let offset = self.block_offset(first_block, buffer.len(), data.len())?;
+        data[offset..offset + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+}
@@ -1,6 +1,203 @@
-mod extent;
+/*! MeetiX FileSystem */
 
-#[repr(u8)]
-enum Compression {
-    None = 0,
+use alloc::sync::Arc;
+use core::{
+    ops::Deref,
+    time::Duration
+};
+
+use api_data::{
+    error::class::OsErrorClass,
+    instant::RawInstant
+};
+use mxfs::{
+    device::TMxfsDevice,
+    super_block::SuperBlock,
+    volume::Volume,
+    MxfsError,
+    MxfsResult
+};
+use sync::SpinMutex;
+
+use crate::{
+    dbg_print::DbgLevel,
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::mxfs::node::MxfsNode,
+        node::TFsNode,
+        Filesystems,
+        FsResult,
+        TFilesystem,
+        TFilesystemProvider
+    },
+    time::TimeManager
+};
+
+pub mod node;
+
+/**
+ * Mounted MXFS volume shared by the `MxfsNode`s, which lock serializes
+ * all the accesses to the volume
+ */
+pub type MxfsVolume = SpinMutex<Volume<MxfsDevice<Arc<dyn TBlockDevice>>>>;
+
+/**
+ * `TFilesystemProvider` of the MeetiX FileSystem
+ */
+pub struct MxfsProvider;
+
+impl TFilesystemProvider for MxfsProvider {
+    fn verify_superblock(&self,
+                         block_device: &dyn TBlockDevice)
+                         -> FsResult<Filesystems> {
+        let device = MxfsDevice::new(block_device);
+        let super_block = SuperBlock::read(&device).map_err(os_error_of)?;
+
+        super_block.verify(device.size()).map_err(os_error_of)?;
+        Ok(Filesystems::Mxfs)
+    }
+
+    fn open(&self,
+            block_device: Arc<dyn TBlockDevice>)
+            -> FsResult<Arc<dyn TFilesystem>> {
+        let volume =
+            Volume::mount(MxfsDevice::new(block_device), now).map_err(os_error_of)?;
+        let mode = if volume.is_read_only() {
+            "read-only"
+        } else {
+            "read-write"
+        };
+        dbg_println!(DbgLevel::Info,
+                     "Mxfs: opened volume '{}' {}, {} blocks free",
+                     volume.super_block().label(),
+                     mode,
+                     volume.free_blocks());
+
+        let root_id = volume.root();
+        let volume = Arc::new(SpinMutex::new(volume));
+        Ok(Arc::new(Mxfs { m_root: MxfsNode::new_root(volume.clone(), root_id),
+                           m_volume: volume }))
+    }
+}
+
+/**
+ * `TFilesystem` native of MeetiX.
+ *
+ * The inodes describe their data with extent trees, where each extent is
+ * compressed on its own, and the directories with B-trees of their
+ * entries. The metadata is modified through a journal, so the volume is
+ * consistent after each crash
+ */
+pub struct Mxfs {
+    m_root: Arc<MxfsNode>,
+    m_volume: Arc<MxfsVolume>
+}
+
+impl TFilesystem for Mxfs {
+    fn name(&self) -> &str {
+        "mxfs"
+    }
+
+    fn root(&self) -> Arc<dyn TFsNode> {
+        self.m_root.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.m_volume.lock().is_read_only()
+    }
+
+    fn sync(&self) -> FsResult<()> {
+        self.m_volume.lock().sync().map_err(os_error_of)
+    }
+}
+
+/**
+ * `TMxfsDevice` over a kernel `TBlockDevice`, which is either borrowed
+ * or shared
+ */
+pub struct MxfsDevice<B> {
+    m_block_device: B
+}
+
+impl<B> MxfsDevice<B> /* Constructors */ {
+    /**
+     * Constructs a `MxfsDevice` which accesses the given `TBlockDevice`
+     */
+    pub fn new(block_device: B) -> Self {
+        Self { m_block_device: block_device }
+    }
+}
+
+impl<B> MxfsDevice<B> /* Getters */ {
+    /**
+     * Returns the reference to the wrapped `TBlockDevice`
+     */
+    pub fn block_device(&self) -> &B {
+        &self.m_block_device
+    }
+}
+
+impl<'a, B: Deref<Target = dyn TBlockDevice + 'a>> TMxfsDevice for MxfsDevice<B> {
+    fn size(&self) -> u64 {
+        self.m_block_device.size()
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> MxfsResult<()> {
+        self.m_block_device.read_bytes(offset, buffer).map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> MxfsResult<()> {
+        self.m_block_device
+            .write_bytes(offset, buffer)
+            .map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn flush(&self) -> MxfsResult<()> {
+        self.m_block_device.sync().map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.m_block_device.is_read_only()
+    }
+}
+
+/**
+ * Converts the given `MxfsError` into the `OsErrorClass` returned to the
+ * `Vfs`
+ */
+pub fn os_error_of(mxfs_error: MxfsError) -> OsErrorClass {
+    match mxfs_error {
+        MxfsError::DeviceError => OsErrorClass::InterruptedOperation,
+        MxfsError::BadMagic
+        | MxfsError::UnsupportedVersion
+        | MxfsError::TypesNotMatch => OsErrorClass::TypesNotMatch,
+        MxfsError::Corrupted | MxfsError::InvalidArgument => {
+            OsErrorClass::InvalidArgument
+        },
+        MxfsError::NotFound => OsErrorClass::ReferenceNotFound,
+        MxfsError::AlreadyExists => OsErrorClass::IdentifierNotAvailable,
+        MxfsError::NameTooLong => OsErrorClass::LimitOverflow,
+        MxfsError::NoSpace | MxfsError::NoInodes | MxfsError::TransactionTooBig => {
+            OsErrorClass::LimitReached
+        },
+        MxfsError::DirectoryNotEmpty | MxfsError::ReadOnly => {
+            OsErrorClass::OperationNotEnabled
+        },
+    }
+}
+
+/**
+ * Converts the given on-disk time, in nanoseconds since the Unix epoch,
+ * into a `RawInstant`
+ */
+pub fn time_to_instant(time: u64) -> RawInstant {
+    Duration::from_nanos(time)
+}
+
+/**
+ * Clock of the `Volume`, which returns the nanoseconds since the Unix
+ * epoch
+ */
+fn now() -> u64 {
+    TimeManager::now_instant().as_nanos() as u64
 }
@@ -0,0 +1,240 @@
+/*! MeetiX FileSystem nodes */
+
+use alloc::{
+    string::String,
+    sync::Arc
+};
+use core::any::Any;
+
+use api_data::{
+    entity::OsEntityId,
+    error::class::OsErrorClass,
+    object::{
+        dir::DirEntry,
+        grants::RawObjGrants,
+        info::RawObjInfo,
+        types::ObjType
+    }
+};
+use mxfs::inode::{
+    InodeId,
+    InodeType
+};
+
+use crate::{
+    dev::TDevice,
+    filesystem::{
+        implementation::mxfs::{
+            os_error_of,
+            time_to_instant,
+            MxfsVolume
+        },
+        node::{
+            default_prot_grants,
+            FsNodeId,
+            TFsNode
+        },
+        FsResult
+    }
+};
+
+/**
+ * `TFsNode` of the `Mxfs`.
+ *
+ * The node only stores its inode number, the inode is kept by the
+ * `MxfsVolume`, which caches the metadata blocks
+ */
+pub struct MxfsNode {
+    m_inode_id: InodeId,
+    m_obj_type: ObjType,
+    m_volume: Arc<MxfsVolume>
+}
+
+impl MxfsNode /* Constructors */ {
+    /**
+     * Constructs the root directory of the given `MxfsVolume`
+     */
+    pub fn new_root(volume: Arc<MxfsVolume>, root_id: InodeId) -> Arc<Self> {
+        Self::new(volume, root_id, ObjType::Dir)
+    }
+
+    /**
+     * Constructs the `MxfsNode` of the given inode
+     */
+    fn new(volume: Arc<MxfsVolume>, inode_id: InodeId, obj_type: ObjType) -> Arc<Self> {
+        Arc::new(Self { m_inode_id: inode_id,
+                        m_obj_type: obj_type,
+                        m_volume: volume })
+    }
+}
+
+impl TFsNode for MxfsNode {
+    fn id(&self) -> FsNodeId {
+        self.m_inode_id
+    }
+
+    fn obj_type(&self) -> ObjType {
+        self.m_obj_type
+    }
+
+    fn size(&self) -> usize {
+        self.m_volume
+            .lock()
+            .inode(self.m_inode_id)
+            .map_or(0, |inode| inode.size() as usize)
+    }
+
+    fn info(&self) -> FsResult<RawObjInfo> {
+        let mut volume = self.m_volume.lock();
+        let inode = volume.inode(self.m_inode_id).map_err(os_error_of)?;
+        let name = volume.name_of(self.m_inode_id).map_err(os_error_of)?;
+
+        /* the root directory reports the data blocks of the whole volume */
+        let super_block = volume.super_block();
+        let data_blocks_used = if self.m_inode_id == volume.root() {
+            super_block.blocks_count() - volume.free_blocks()
+        } else {
+            inode.blocks_used()
+        };
+        let prot_grants =
+            inode.prot_grants()
+                 .map(|raw_grants| RawObjGrants::from_raw_truncate(raw_grants as usize))
+                 .unwrap_or_else(|| default_prot_grants(self.m_obj_type));
+        let device_id = volume.device().block_device().device_id();
+
+        /* the open handles are counted by the kernel objects, not by the nodes */
+        Ok(RawObjInfo::new(self.m_obj_type,
+                           0,
+                           device_id,
+                           self.m_inode_id,
+                           name.as_deref(),
+                           inode.links(),
+                           super_block.block_size() as usize,
+                           data_blocks_used as usize,
+                           inode.size() as usize,
+                           inode.os_user_id(),
+                           inode.os_group_id(),
+                           prot_grants,
+                           time_to_instant(inode.creat_time()),
+                           time_to_instant(inode.access_time()),
+                           time_to_instant(inode.modify_time()),
+                           time_to_instant(inode.access_time()),
+                           time_to_instant(inode.change_time())))
+    }
+
+    fn set_owner(&self, os_user_id: OsEntityId, os_group_id: OsEntityId) -> FsResult<()> {
+        self.m_volume
+            .lock()
+            .set_owner(self.m_inode_id, os_user_id, os_group_id)
+            .map_err(os_error_of)
+    }
+
+    fn set_prot_grants(&self, prot_grants: RawObjGrants) -> FsResult<()> {
+        self.m_volume
+            .lock()
+            .set_prot_grants(self.m_inode_id, prot_grants.raw_bits() as u64)
+            .map_err(os_error_of)
+    }
+
+    fn read_at(&self, offset: usize, buffer: &mut [u8]) -> FsResult<usize> {
+        self.m_volume
+            .lock()
+            .read_at(self.m_inode_id, offset as u64, buffer)
+            .map_err(os_error_of)
+    }
+
+    fn write_at(&self, offset: usize, buffer: &[u8]) -> FsResult<usize> {
+        self.m_volume
+            .lock()
+            .write_at(self.m_inode_id, offset as u64, buffer)
+            .map_err(os_error_of)
+    }
+
+    fn truncate(&self, size: usize) -> FsResult<()> {
+        self.m_volume.lock().truncate(self.m_inode_id, size as u64).map_err(os_error_of)
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn TFsNode>> {
+        let dir_record =
+            self.m_volume.lock().lookup(self.m_inode_id, name).map_err(os_error_of)?;
+
+        Ok(Self::new(self.m_volume.clone(),
+                     dir_record.inode(),
+                     obj_type_of(dir_record.inode_type())))
+    }
+
+    fn read_dir(&self, index: usize) -> FsResult<Option<DirEntry>> {
+        let dir_record =
+            self.m_volume.lock().read_dir(self.m_inode_id, index).map_err(os_error_of)?;
+
+        Ok(dir_record.map(|dir_record| {
+                         DirEntry::new(dir_record.name(),
+                                       obj_type_of(dir_record.inode_type()))
+                     }))
+    }
+
+    fn create(&self, name: &str, obj_type: ObjType) -> FsResult<Arc<dyn TFsNode>> {
+        let inode_type = match obj_type {
+            ObjType::File => InodeType::File,
+            ObjType::Dir => InodeType::Dir,
+            _ => return Err(OsErrorClass::InvalidArgument)
+        };
+
+        let child_id = self.m_volume
+                           .lock()
+                           .create(self.m_inode_id, name, inode_type)
+                           .map_err(os_error_of)?;
+        Ok(Self::new(self.m_volume.clone(), child_id, obj_type))
+    }
+
+    fn create_link(&self, name: &str, target: &str) -> FsResult<Arc<dyn TFsNode>> {
+        let child_id = self.m_volume
+                           .lock()
+                           .create_link(self.m_inode_id, name, target)
+                           .map_err(os_error_of)?;
+        Ok(Self::new(self.m_volume.clone(), child_id, ObjType::Link))
+    }
+
+    fn unlink(&self, name: &str) -> FsResult<()> {
+        self.m_volume.lock().unlink(self.m_inode_id, name).map_err(os_error_of)
+    }
+
+    fn rename(&self,
+              name: &str,
+              new_parent: &dyn TFsNode,
+              new_name: &str)
+              -> FsResult<()> {
+        let new_parent =
+            new_parent.as_any()
+                      .downcast_ref::<MxfsNode>()
+                      .filter(|new_parent| {
+                          Arc::ptr_eq(&new_parent.m_volume, &self.m_volume)
+                      })
+                      .ok_or(OsErrorClass::InvalidArgument)?;
+
+        self.m_volume
+            .lock()
+            .rename(self.m_inode_id, name, new_parent.m_inode_id, new_name)
+            .map_err(os_error_of)
+    }
+
+    fn link_target(&self) -> FsResult<String> {
+        self.m_volume.lock().link_target(self.m_inode_id).map_err(os_error_of)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/**
+ * Returns the `ObjType` of the inodes of the given `InodeType`
+ */
+fn obj_type_of(inode_type: InodeType) -> ObjType {
+    match inode_type {
+        InodeType::File => ObjType::File,
+        InodeType::Dir => ObjType::Dir,
+        InodeType::Link => ObjType::Link,
+        InodeType::Free => ObjType::Unknown
+    }
+}
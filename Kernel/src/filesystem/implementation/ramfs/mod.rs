@@ -1,23 +1,212 @@
-mod node;
+/*! Memory backed filesystem */
 
-use crate::filesystem::implementation::{
-    DirectoryNode,
-    INode
+use alloc::sync::Arc;
+use core::sync::atomic::{
+    AtomicU32,
+    AtomicU64,
+    AtomicUsize,
+    Ordering
 };
-use alloc::collections::{
-    HashMap,
-    Vec
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::{
+        device::{
+            DeviceId,
+            DeviceIdClass,
+            DeviceIdType
+        },
+        types::ObjType
+    }
+};
+
+use crate::{
+    boot_info::BootInfo,
+    dbg_print::DbgLevel,
+    filesystem::{
+        implementation::ramfs::node::RamNode,
+        node::{
+            FsNodeId,
+            TFsNode
+        },
+        FsResult,
+        TFilesystem,
+        Vfs
+    }
 };
 
-struct RamFS<'a> {
-    file_map: HashMap<&'a str, RamFileNode>
+pub mod node;
+
+/**
+ * Size of the data blocks in which the `RamFs` allocates the files
+ */
+pub const C_RAMFS_BLOCK_SIZE: usize = 4096;
+
+/* serial of the <DeviceId> of the next <RamFs> */
+static SM_NEXT_RAMFS_SERIAL: AtomicU32 = AtomicU32::new(0);
+
+/* directory at which <RamFs::mount_tmp()> mounts */
+const C_TMP_PATH: &str = "/Tmp";
+
+/* command line option which selects the size limit in bytes of the /Tmp <RamFs> */
+const C_TMP_SIZE_LIMIT_ARG: &str = "-tmp-size-limit";
+
+/* size limit of the /Tmp <RamFs> when the command line doesn't select one */
+const C_DEFAULT_TMP_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+
+/**
+ * `TFilesystem` which keeps its whole tree into the kernel heap.
+ *
+ * The files are sparse, only the written `C_RAMFS_BLOCK_SIZE` blocks are
+ * allocated, and their total amount can be limited. The data is lost at
+ * the unmount
+ */
+pub struct RamFs {
+    m_root: Arc<RamNode>,
+    m_fs_state: Arc<RamFsState>
 }
 
-impl Filesystem for RamFS {
+impl RamFs /* Constructors */ {
+    /**
+     * Constructs an empty `RamFs` which can allocate up to the given
+     * amount of bytes, unlimited when `None`
+     */
+    pub fn new(size_limit: Option<usize>) -> Self {
+        let serial = SM_NEXT_RAMFS_SERIAL.fetch_add(1, Ordering::SeqCst);
+        let blocks_limit = size_limit.map_or(usize::MAX, |size_limit| {
+                                         (size_limit + C_RAMFS_BLOCK_SIZE - 1)
+                                         / C_RAMFS_BLOCK_SIZE
+                                     });
+
+        let fs_state = Arc::new(RamFsState { m_device_id:
+                                                 DeviceId::new(DeviceIdType::Block,
+                                                               DeviceIdClass::Memory,
+                                                               serial),
+                                             m_blocks_used: AtomicUsize::new(0),
+                                             m_blocks_limit: blocks_limit,
+                                             m_next_node_id: AtomicU64::new(0) });
+        Self { m_root: RamNode::new_root(fs_state.clone()),
+               m_fs_state: fs_state }
+    }
+}
+
+impl RamFs /* Getters */ {
+    /**
+     * Returns the amount of the allocated data blocks
+     */
+    pub fn blocks_used(&self) -> usize {
+        self.m_fs_state.blocks_used()
+    }
+
+    /**
+     * Returns the maximum amount of data blocks
+     */
+    pub fn blocks_limit(&self) -> usize {
+        self.m_fs_state.m_blocks_limit
+    }
 }
 
-impl DirectoryNode for RamFS {
-    fn get_content(&self) -> Result<&dyn Iterator<Item = &dyn INode>, &str> {
-        todo!()
+impl RamFs /* Static Functions */ {
+    /**
+     * Mounts a new `RamFs` at `/Tmp`, limited by the `-tmp-size-limit=`
+     * command line option, creating the directory when missing
+     */
+    pub fn mount_tmp() {
+        let size_limit = BootInfo::instance().cmd_line_find_arg_int(C_TMP_SIZE_LIMIT_ARG)
+                                             .and_then(|(_, size_limit)| size_limit)
+                                             .unwrap_or(C_DEFAULT_TMP_SIZE_LIMIT);
+
+        let vfs = Vfs::instance();
+        let mount_result = match vfs.create(C_TMP_PATH, ObjType::Dir) {
+            Ok(_) | Err(OsErrorClass::IdentifierNotAvailable) => {
+                vfs.mount(C_TMP_PATH, Arc::new(Self::new(Some(size_limit))))
+            },
+            Err(os_error_class) => Err(os_error_class)
+        };
+        if let Err(os_error_class) = mount_result {
+            dbg_println!(DbgLevel::Err,
+                         "Failed to mount the RamFs at {}: {}",
+                         C_TMP_PATH,
+                         os_error_class);
+        }
+    }
+}
+
+impl TFilesystem for RamFs {
+    fn name(&self) -> &str {
+        "ramfs"
+    }
+
+    fn root(&self) -> Arc<dyn TFsNode> {
+        self.m_root.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/**
+ * State of a `RamFs` shared with all its `RamNode`s
+ */
+pub struct RamFsState {
+    m_device_id: DeviceId,
+    m_blocks_used: AtomicUsize,
+    m_blocks_limit: usize,
+    m_next_node_id: AtomicU64
+}
+
+impl RamFsState /* Methods */ {
+    /**
+     * Accounts the given amount of new data blocks, failing with
+     * `OsErrorClass::LimitReached` when they exceed the limit
+     */
+    pub fn reserve_blocks(&self, blocks_count: usize) -> FsResult<()> {
+        let mut blocks_used = self.m_blocks_used.load(Ordering::SeqCst);
+        loop {
+            let new_blocks_used =
+                blocks_used.checked_add(blocks_count)
+                           .filter(|&new_used| new_used <= self.m_blocks_limit)
+                           .ok_or(OsErrorClass::LimitReached)?;
+
+            match self.m_blocks_used.compare_exchange(blocks_used,
+                                                      new_blocks_used,
+                                                      Ordering::SeqCst,
+                                                      Ordering::SeqCst)
+            {
+                Ok(_) => return Ok(()),
+                Err(current_blocks_used) => blocks_used = current_blocks_used
+            }
+        }
+    }
+
+    /**
+     * Gives back the given amount of data blocks
+     */
+    pub fn release_blocks(&self, blocks_count: usize) {
+        self.m_blocks_used.fetch_sub(blocks_count, Ordering::SeqCst);
+    }
+
+    /**
+     * Returns a new `FsNodeId`
+     */
+    pub fn next_node_id(&self) -> FsNodeId {
+        self.m_next_node_id.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+impl RamFsState /* Getters */ {
+    /**
+     * Returns the `DeviceId` which identifies the `RamFs`
+     */
+    pub fn device_id(&self) -> DeviceId {
+        self.m_device_id
+    }
+
+    /**
+     * Returns the amount of the allocated data blocks
+     */
+    pub fn blocks_used(&self) -> usize {
+        self.m_blocks_used.load(Ordering::SeqCst)
     }
 }
@@ -1,39 +1,559 @@
-use alloc::collections::Vec;
+/*! Memory backed filesystem nodes */
 
-use crate::filesystem::implementation::{
-    FileNode,
-    INode
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc
 };
+use core::{
+    any::Any,
+    ptr
+};
+
+use api_data::{
+    entity::OsEntityId,
+    error::class::OsErrorClass,
+    instant::RawInstant,
+    object::{
+        dir::DirEntry,
+        grants::RawObjGrants,
+        info::RawObjInfo,
+        types::ObjType
+    }
+};
+use sync::SpinMutex;
+
+use crate::{
+    filesystem::{
+        implementation::ramfs::{
+            RamFsState,
+            C_RAMFS_BLOCK_SIZE
+        },
+        node::{
+            default_prot_grants,
+            FsNodeId,
+            TFsNode
+        },
+        FsResult
+    },
+    time::TimeManager
+};
+
+/**
+ * `TFsNode` of the `RamFs`.
+ *
+ * The `Vfs` serializes the accesses through the `VfsNode` locks, the
+ * inner lock only protects the node from the concurrent renames of its
+ * parent
+ */
+pub struct RamNode {
+    m_id: FsNodeId,
+    m_fs_state: Arc<RamFsState>,
+    m_inner: SpinMutex<RamNodeInner>
+}
+
+impl RamNode /* Constructors */ {
+    /**
+     * Constructs the root directory of a `RamFs`
+     */
+    pub fn new_root(fs_state: Arc<RamFsState>) -> Arc<Self> {
+        Self::new(fs_state, String::new(), RamNodeContent::Dir(BTreeMap::new()), 0, 0)
+    }
+
+    /**
+     * Constructs a new `RamNode` owned by the given user and group
+     */
+    fn new(fs_state: Arc<RamFsState>,
+           name: String,
+           content: RamNodeContent,
+           os_user_id: OsEntityId,
+           os_group_id: OsEntityId)
+           -> Arc<Self> {
+        let inner = RamNodeInner::new(name, content, os_user_id, os_group_id);
+
+        Arc::new(Self { m_id: fs_state.next_node_id(),
+                        m_fs_state: fs_state,
+                        m_inner: SpinMutex::const_new(inner) })
+    }
+}
+
+impl RamNode /* Privates */ {
+    /**
+     * Updates the name of this node after a rename
+     */
+    fn set_name(&self, name: &str) {
+        let mut inner = self.m_inner.lock();
+
+        inner.m_name = name.to_string();
+        inner.m_last_info_modify_inst = TimeManager::now_instant();
+    }
+
+    /**
+     * Returns whether this node is a directory with children
+     */
+    fn is_non_empty_dir(&self) -> bool {
+        matches!(&self.m_inner.lock().m_content,
+                 RamNodeContent::Dir(children) if !children.is_empty())
+    }
+
+    /**
+     * Inserts a new child with the given name and content into this
+     * directory, owned by the same user and group
+     */
+    fn add_child(&self,
+                 name: &str,
+                 content: RamNodeContent)
+                 -> FsResult<Arc<dyn TFsNode>> {
+        let mut inner = self.m_inner.lock();
+
+        let child_node = Self::new(self.m_fs_state.clone(),
+                                   name.to_string(),
+                                   content,
+                                   inner.m_os_user_id,
+                                   inner.m_os_group_id);
+
+        inner.dir_children_mut()?.insert(name.to_string(), child_node.clone());
+        inner.touch_data_modify();
+        Ok(child_node)
+    }
+}
+
+impl TFsNode for RamNode {
+    fn id(&self) -> FsNodeId {
+        self.m_id
+    }
+
+    fn obj_type(&self) -> ObjType {
+        self.m_inner.lock().m_content.obj_type()
+    }
+
+    fn size(&self) -> usize {
+        match &self.m_inner.lock().m_content {
+            RamNodeContent::Dir(_) => 0,
+            RamNodeContent::File(file_data) => file_data.m_size,
+            RamNodeContent::Link(target) => target.len()
+        }
+    }
+
+    fn info(&self) -> FsResult<RawObjInfo> {
+        let mut inner = self.m_inner.lock();
+        inner.m_last_info_access_inst = TimeManager::now_instant();
+
+        /* the root directory reports the data blocks of the whole filesystem */
+        let (data_blocks_used, data_bytes_used) = match &inner.m_content {
+            RamNodeContent::Dir(_) if inner.m_name.is_empty() => {
+                (self.m_fs_state.blocks_used(), 0)
+            },
+            RamNodeContent::Dir(_) => (0, 0),
+            RamNodeContent::File(file_data) => {
+                (file_data.m_blocks.len(), file_data.m_size)
+            },
+            RamNodeContent::Link(target) => (0, target.len())
+        };
+        let name = Some(inner.m_name.as_str()).filter(|name| !name.is_empty());
+
+        /* the open handles are counted by the kernel objects, not by the nodes */
+        Ok(RawObjInfo::new(inner.m_content.obj_type(),
+                           0,
+                           self.m_fs_state.device_id(),
+                           self.m_id,
+                           name,
+                           1,
+                           C_RAMFS_BLOCK_SIZE,
+                           data_blocks_used,
+                           data_bytes_used,
+                           inner.m_os_user_id,
+                           inner.m_os_group_id,
+                           inner.m_prot_grants,
+                           inner.m_creat_inst,
+                           inner.m_last_data_access_inst,
+                           inner.m_last_data_modify_inst,
+                           inner.m_last_info_access_inst,
+                           inner.m_last_info_modify_inst))
+    }
+
+    fn set_owner(&self, os_user_id: OsEntityId, os_group_id: OsEntityId) -> FsResult<()> {
+        let mut inner = self.m_inner.lock();
+
+        inner.m_os_user_id = os_user_id;
+        inner.m_os_group_id = os_group_id;
+        inner.m_last_info_modify_inst = TimeManager::now_instant();
+        Ok(())
+    }
+
+    fn set_prot_grants(&self, prot_grants: RawObjGrants) -> FsResult<()> {
+        let mut inner = self.m_inner.lock();
+
+        inner.m_prot_grants = prot_grants;
+        inner.m_last_info_modify_inst = TimeManager::now_instant();
+        Ok(())
+    }
+
+    fn read_at(&self, offset: usize, buffer: &mut [u8]) -> FsResult<usize> {
+        let mut inner = self.m_inner.lock();
+
+        let read_bytes = inner.file_data_mut()?.read_at(offset, buffer);
+        inner.m_last_data_access_inst = TimeManager::now_instant();
+        Ok(read_bytes)
+    }
+
+    fn write_at(&self, offset: usize, buffer: &[u8]) -> FsResult<usize> {
+        let mut inner = self.m_inner.lock();
+
+        inner.file_data_mut()?.write_at(&self.m_fs_state, offset, buffer)?;
+        inner.touch_data_modify();
+        Ok(buffer.len())
+    }
+
+    fn truncate(&self, size: usize) -> FsResult<()> {
+        let mut inner = self.m_inner.lock();
+
+        inner.file_data_mut()?.truncate(&self.m_fs_state, size);
+        inner.touch_data_modify();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn TFsNode>> {
+        let mut inner = self.m_inner.lock();
+
+        let child_node = inner.dir_children_mut()?
+                              .get(name)
+                              .cloned()
+                              .ok_or(OsErrorClass::ReferenceNotFound)?;
+        Ok(child_node)
+    }
+
+    fn read_dir(&self, index: usize) -> FsResult<Option<DirEntry>> {
+        let mut inner = self.m_inner.lock();
+
+        let dir_entry =
+            inner.dir_children_mut()?
+                 .iter()
+                 .nth(index)
+                 .map(|(name, child_node)| DirEntry::new(name, child_node.obj_type()));
+        inner.m_last_data_access_inst = TimeManager::now_instant();
+        Ok(dir_entry)
+    }
+
+    fn create(&self, name: &str, obj_type: ObjType) -> FsResult<Arc<dyn TFsNode>> {
+        let content = match obj_type {
+            ObjType::Dir => RamNodeContent::Dir(BTreeMap::new()),
+            ObjType::File => RamNodeContent::File(RamFileData::new()),
+            _ => return Err(OsErrorClass::InvalidArgument)
+        };
+        self.add_child(name, content)
+    }
+
+    fn create_link(&self, name: &str, target: &str) -> FsResult<Arc<dyn TFsNode>> {
+        self.add_child(name, RamNodeContent::Link(target.to_string()))
+    }
+
+    fn unlink(&self, name: &str) -> FsResult<()> {
+        let mut inner = self.m_inner.lock();
+        let children = inner.dir_children_mut()?;
+
+        let child_node = children.get(name).ok_or(OsErrorClass::ReferenceNotFound)?;
+        if child_node.is_non_empty_dir() {
+            return Err(OsErrorClass::OperationNotEnabled);
+        }
+
+        /* the data blocks are released with the last reference to the node */
+        children.remove(name);
+        inner.touch_data_modify();
+        Ok(())
+    }
+
+    fn rename(&self,
+              name: &str,
+              new_parent: &dyn TFsNode,
+              new_name: &str)
+              -> FsResult<()> {
+        let new_parent = new_parent.as_any()
+                                   .downcast_ref::<RamNode>()
+                                   .filter(|new_parent| {
+                                       Arc::ptr_eq(&new_parent.m_fs_state,
+                                                   &self.m_fs_state)
+                                   })
+                                   .ok_or(OsErrorClass::InvalidArgument)?;
 
-struct RamFileNode<'a> {
-    name: &'a str,
-    file_data: Vec<u8>
+        if ptr::eq(self, new_parent) {
+            let mut inner = self.m_inner.lock();
+            let children = inner.dir_children_mut()?;
+
+            let child_node =
+                children.remove(name).ok_or(OsErrorClass::ReferenceNotFound)?;
+            child_node.set_name(new_name);
+            children.insert(new_name.to_string(), child_node);
+            inner.touch_data_modify();
+        } else {
+            /* always locked in the same order, so crossed renames never deadlock */
+            let self_first = (self as *const Self) < (new_parent as *const Self);
+            let (mut inner, mut new_parent_inner) = if self_first {
+                let inner = self.m_inner.lock();
+                (inner, new_parent.m_inner.lock())
+            } else {
+                let new_parent_inner = new_parent.m_inner.lock();
+                (self.m_inner.lock(), new_parent_inner)
+            };
+
+            /* the destination is checked before removing the child */
+            new_parent_inner.dir_children_mut()?;
+            let child_node = inner.dir_children_mut()?
+                                  .remove(name)
+                                  .ok_or(OsErrorClass::ReferenceNotFound)?;
+            child_node.set_name(new_name);
+            new_parent_inner.dir_children_mut()?.insert(new_name.to_string(), child_node);
+
+            inner.touch_data_modify();
+            new_parent_inner.touch_data_modify();
+        }
+        Ok(())
+    }
+
+    fn link_target(&self) -> FsResult<String> {
+        match &self.m_inner.lock().m_content {
+            RamNodeContent::Link(target) => Ok(target.clone()),
+            _ => Err(OsErrorClass::TypesNotMatch)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
-impl RamFileNode {
-    pub fn new(name: &str) -> Self {
-        Self { name,
-               file_data: Vec::new() }
+impl Drop for RamNode {
+    fn drop(&mut self) {
+        if let RamNodeContent::File(file_data) = &self.m_inner.lock().m_content {
+            self.m_fs_state.release_blocks(file_data.m_blocks.len());
+        }
     }
 }
 
-impl FileNode for RamFileNode {
-    fn get_name(&self) -> &str {
-        self.name
+/**
+ * Fields of the `RamNode` protected by its lock
+ */
+struct RamNodeInner {
+    m_name: String,
+    m_content: RamNodeContent,
+    m_os_user_id: OsEntityId,
+    m_os_group_id: OsEntityId,
+    m_prot_grants: RawObjGrants,
+    m_creat_inst: RawInstant,
+    m_last_data_access_inst: RawInstant,
+    m_last_data_modify_inst: RawInstant,
+    m_last_info_access_inst: RawInstant,
+    m_last_info_modify_inst: RawInstant
+}
+
+impl RamNodeInner /* Constructors */ {
+    /**
+     * Constructs the `RamNodeInner` of a new node, with the default
+     * `RawObjGrants` for its `ObjType` and all the timestamps at now
+     */
+    fn new(name: String,
+           content: RamNodeContent,
+           os_user_id: OsEntityId,
+           os_group_id: OsEntityId)
+           -> Self {
+        let prot_grants = default_prot_grants(content.obj_type());
+
+        let now = TimeManager::now_instant();
+        Self { m_name: name,
+               m_content: content,
+               m_os_user_id: os_user_id,
+               m_os_group_id: os_group_id,
+               m_prot_grants: prot_grants,
+               m_creat_inst: now,
+               m_last_data_access_inst: now,
+               m_last_data_modify_inst: now,
+               m_last_info_access_inst: now,
+               m_last_info_modify_inst: now }
     }
+}
+
+impl RamNodeInner /* Methods */ {
+    /**
+     * Updates the timestamps after a modification of the data
+     */
+    fn touch_data_modify(&mut self) {
+        let now = TimeManager::now_instant();
 
-    fn get_data_stream(&self) -> Result<&dyn Iterator<Item = u8>, &str> {
-        Ok(self.file_data.iter())
+        self.m_last_data_modify_inst = now;
+        self.m_last_info_modify_inst = now;
+    }
+}
+
+impl RamNodeInner /* Getters */ {
+    /**
+     * Returns the children of this directory
+     */
+    fn dir_children_mut(&mut self) -> FsResult<&mut BTreeMap<String, Arc<RamNode>>> {
+        match &mut self.m_content {
+            RamNodeContent::Dir(children) => Ok(children),
+            _ => Err(OsErrorClass::TypesNotMatch)
+        }
     }
 
-    fn use_external_cache_hint(&self) -> bool {
-        false
+    /**
+     * Returns the `RamFileData` of this file
+     */
+    fn file_data_mut(&mut self) -> FsResult<&mut RamFileData> {
+        match &mut self.m_content {
+            RamNodeContent::File(file_data) => Ok(file_data),
+            _ => Err(OsErrorClass::TypesNotMatch)
+        }
     }
+}
+
+/**
+ * Lists the contents of the `RamNode`s
+ */
+enum RamNodeContent {
+    /**
+     * Directory with its children sorted by name
+     */
+    Dir(BTreeMap<String, Arc<RamNode>>),
+
+    /**
+     * Regular file with its sparse data
+     */
+    File(RamFileData),
+
+    /**
+     * Symbolic link with its target path
+     */
+    Link(String)
+}
+
+impl RamNodeContent /* Getters */ {
+    /**
+     * Returns the `ObjType` of the content
+     */
+    fn obj_type(&self) -> ObjType {
+        match self {
+            Self::Dir(_) => ObjType::Dir,
+            Self::File(_) => ObjType::File,
+            Self::Link(_) => ObjType::Link
+        }
+    }
+}
+
+/**
+ * Sparse data of a `RamNode` file.
+ *
+ * Only the written `C_RAMFS_BLOCK_SIZE` blocks are allocated, the holes
+ * are read as zeroes
+ */
+struct RamFileData {
+    m_blocks: BTreeMap<usize, Box<[u8]>>,
+    m_size: usize
+}
 
-    fn size_hint(&self) -> Option<usize> {
-        Some(self.file_data.len())
+impl RamFileData /* Constructors */ {
+    /**
+     * Constructs an empty `RamFileData`
+     */
+    fn new() -> Self {
+        Self { m_blocks: BTreeMap::new(),
+               m_size: 0 }
     }
 }
 
-impl INode for RamFileNode {
+impl RamFileData /* Methods */ {
+    /**
+     * Reads the data from the given offset into the given buffer,
+     * returning the amount of bytes read
+     */
+    fn read_at(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        let read_bytes = self.m_size.saturating_sub(offset).min(buffer.len());
+
+        let mut done_bytes = 0;
+        while done_bytes < read_bytes {
+            let position = offset + done_bytes;
+            let block_offset = position % C_RAMFS_BLOCK_SIZE;
+            let chunk_bytes =
+                (C_RAMFS_BLOCK_SIZE - block_offset).min(read_bytes - done_bytes);
+
+            let chunk_buffer = &mut buffer[done_bytes..done_bytes + chunk_bytes];
+            match self.m_blocks.get(&(position / C_RAMFS_BLOCK_SIZE)) {
+                Some(block) => chunk_buffer.copy_from_slice(&block[block_offset
+                                                                   ..block_offset
+                                                                     + chunk_bytes]),
+                None => chunk_buffer.fill(0)
+            }
+            done_bytes += chunk_bytes;
+        }
+        read_bytes
+    }
+
+    /**
+     * Writes the given buffer at the given offset, allocating the missing
+     * blocks from the given `RamFsState`.
+     *
+     * Nothing is written when the blocks can't be allocated
+     */
+    fn write_at(&mut self,
+                fs_state: &RamFsState,
+                offset: usize,
+                buffer: &[u8])
+                -> FsResult<()> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let end_offset =
+            offset.checked_add(buffer.len()).ok_or(OsErrorClass::LimitOverflow)?;
+
+        let blocks_range =
+            offset / C_RAMFS_BLOCK_SIZE..=(end_offset - 1) / C_RAMFS_BLOCK_SIZE;
+        let missing_blocks =
+            blocks_range.clone()
+                        .filter(|block_index| !self.m_blocks.contains_key(block_index))
+                        .count();
+        fs_state.reserve_blocks(missing_blocks)?;
+
+        for block_index in blocks_range {
+            let block_start = block_index * C_RAMFS_BLOCK_SIZE;
+            let copy_start = offset.max(block_start);
+            let copy_end = end_offset.min(block_start + C_RAMFS_BLOCK_SIZE);
+
+            let block =
+                self.m_blocks
+                    .entry(block_index)
+                    .or_insert_with(|| vec![0; C_RAMFS_BLOCK_SIZE].into_boxed_slice());
+            block[copy_start - block_start..copy_end - block_start]
+                .copy_from_slice(&buffer[copy_start - offset..copy_end - offset]);
+        }
+
+        self.m_size = self.m_size.max(end_offset);
+        Ok(())
+    }
+
+    /**
+     * Shrinks or grows the data to the given size, releasing the blocks
+     * over it to the given `RamFsState`.
+     *
+     * The grown part is a hole
+     */
+    fn truncate(&mut self, fs_state: &RamFsState, size: usize) {
+        if size < self.m_size {
+            let first_unused_block = (size + C_RAMFS_BLOCK_SIZE - 1) / C_RAMFS_BLOCK_SIZE;
+            let unused_blocks = self.m_blocks.split_off(&first_unused_block);
+            fs_state.release_blocks(unused_blocks.len());
+
+            /* the tail of the last block must read as zeroes if grown again */
+            let block_offset = size % C_RAMFS_BLOCK_SIZE;
+            if let Some(last_block) = self.m_blocks.get_mut(&(size / C_RAMFS_BLOCK_SIZE))
+            {
+                if block_offset != 0 {
+                    last_block[block_offset..].fill(0);
+                }
+            }
+        }
+        self.m_size = size;
+    }
 }
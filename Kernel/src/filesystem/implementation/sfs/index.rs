@@ -0,0 +1,676 @@
+/*! Simple FileSystem index area */
+
+use alloc::{
+    collections::{
+        BTreeMap,
+        BTreeSet
+    },
+    string::{
+        String,
+        ToString
+    },
+    vec::Vec
+};
+use core::{
+    convert::TryInto,
+    iter,
+    ops::Range,
+    str
+};
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::types::ObjType
+};
+
+use crate::{
+    dbg_print::DbgLevel,
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::sfs::{
+            super_block::SuperBlock,
+            SfsVariant
+        },
+        FsResult
+    }
+};
+
+/**
+ * Size in bytes of each slot of the index area
+ */
+pub const C_INDEX_ENTRY_SIZE: usize = 64;
+
+/* types of the index entries */
+const C_VOLUME_ID_ENTRY: u8 = 0x01;
+const C_START_MARKER_ENTRY: u8 = 0x02;
+const C_UNUSED_ENTRY: u8 = 0x10;
+const C_DIR_ENTRY: u8 = 0x11;
+const C_FILE_ENTRY: u8 = 0x12;
+const C_UNUSABLE_ENTRY: u8 = 0x18;
+const C_DELETED_DIR_ENTRY: u8 = 0x19;
+const C_DELETED_FILE_ENTRY: u8 = 0x1A;
+
+/* offsets of the fields of the directory and file entries of <SfsVariant::Sfs>, the
+ * <SfsVariant::Sfse> layout inserts its CRC byte before them
+ */
+const C_CONTINUATIONS_OFFSET: usize = 1;
+const C_TIME_STAMP_OFFSET: usize = 2;
+const C_DIR_NAME_OFFSET: usize = 10;
+const C_FILE_START_BLOCK_OFFSET: usize = 10;
+const C_FILE_END_BLOCK_OFFSET: usize = 18;
+const C_FILE_LENGTH_OFFSET: usize = 26;
+const C_FILE_NAME_OFFSET: usize = 34;
+
+/* offset of the CRC byte of the <SfsVariant::Sfse> entries */
+const C_SFSE_CRC_OFFSET: usize = 1;
+
+/* offsets of the fields of the unusable entries, equal for both the layouts */
+const C_UNUSABLE_START_BLOCK_OFFSET: usize = 10;
+const C_UNUSABLE_END_BLOCK_OFFSET: usize = 18;
+
+/* offset and size of the name of the volume id entry */
+const C_VOLUME_NAME_OFFSET: usize = 12;
+const C_VOLUME_NAME_LEN_MAX: usize = 52;
+
+/* the amount of continuations is stored into a byte */
+const C_CONTINUATIONS_MAX: usize = u8::MAX as usize;
+
+/**
+ * In-memory copy of the index area of a Simple FileSystem volume.
+ *
+ * The area is an array of `C_INDEX_ENTRY_SIZE` slots, which starts with
+ * the start marker and ends with the volume id. Each directory or file
+ * entry occupies a run of slots, made by the entry itself followed by the
+ * continuations which store the tail of its name. The modified slots are
+ * written back by `IndexArea::flush()`
+ */
+pub struct IndexArea {
+    m_variant: SfsVariant,
+    m_bytes: Vec<u8>,
+    m_used_slots: Vec<bool>,
+    m_deleted_runs: BTreeMap<usize, usize>,
+    m_dirty_slots: BTreeSet<usize>
+}
+
+impl IndexArea /* Constructors */ {
+    /**
+     * Reads the index area described by the given `SuperBlock` and returns
+     * it with the `IndexRecord`s it contains.
+     *
+     * The damaged entries are reported and kept untouched
+     */
+    pub fn load(block_device: &dyn TBlockDevice,
+                super_block: &SuperBlock)
+                -> FsResult<(Self, Vec<IndexRecord>)> {
+        let mut bytes = vec![0; super_block.index_size() as usize];
+        block_device.read_bytes(super_block.index_offset(), &mut bytes)?;
+
+        let slots_count = bytes.len() / C_INDEX_ENTRY_SIZE;
+        let mut index_area = Self { m_variant: super_block.variant(),
+                                    m_bytes: bytes,
+                                    m_used_slots: vec![false; slots_count],
+                                    m_deleted_runs: BTreeMap::new(),
+                                    m_dirty_slots: BTreeSet::new() };
+
+        let volume_id_slot = slots_count - 1;
+        if index_area.entry_type_at(volume_id_slot) != C_VOLUME_ID_ENTRY {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+        index_area.m_used_slots[volume_id_slot] = true;
+
+        let mut index_records = Vec::new();
+        let mut slot = 0;
+        while slot < volume_id_slot {
+            match index_area.entry_type_at(slot) {
+                C_START_MARKER_ENTRY => {
+                    index_area.m_used_slots[slot] = true;
+                    slot += 1;
+                },
+                C_UNUSABLE_ENTRY => {
+                    let raw_entry = index_area.raw_run(slot..slot + 1);
+                    let start_block = read_u64(raw_entry, C_UNUSABLE_START_BLOCK_OFFSET);
+                    let end_block = read_u64(raw_entry, C_UNUSABLE_END_BLOCK_OFFSET);
+
+                    match index_area.decode_blocks(start_block, end_block) {
+                        Some(blocks) => index_records.push(IndexRecord::Unusable(blocks)),
+                        None => dbg_println!(DbgLevel::Warn,
+                                             "Sfs: invalid unusable entry {}",
+                                             slot)
+                    }
+                    index_area.m_used_slots[slot] = true;
+                    slot += 1;
+                },
+                entry_type @ (C_DIR_ENTRY | C_FILE_ENTRY | C_DELETED_DIR_ENTRY
+                | C_DELETED_FILE_ENTRY) => {
+                    let continuations_offset =
+                        index_area.field_offset(C_CONTINUATIONS_OFFSET);
+                    let continuations =
+                        index_area.raw_run(slot..slot + 1)[continuations_offset] as usize;
+                    let run = slot..slot + 1 + continuations;
+
+                    /* the continuations can't overwrite the volume id */
+                    if run.end > volume_id_slot {
+                        dbg_println!(DbgLevel::Warn,
+                                     "Sfs: truncated index entry {}",
+                                     slot);
+                        for used_slot in slot..volume_id_slot {
+                            index_area.m_used_slots[used_slot] = true;
+                        }
+                        break;
+                    }
+
+                    if entry_type == C_DELETED_DIR_ENTRY
+                       || entry_type == C_DELETED_FILE_ENTRY
+                    {
+                        index_area.m_deleted_runs.insert(run.start, run.end);
+                    } else {
+                        if !index_area.is_crc_valid(run.clone()) {
+                            dbg_println!(DbgLevel::Warn,
+                                         "Sfs: CRC mismatch of index entry {}",
+                                         slot);
+                        }
+
+                        match index_area.parse_entry(run.clone()) {
+                            Some(index_entry) => {
+                                index_records.push(IndexRecord::Node(run.clone(),
+                                                                     index_entry))
+                            },
+                            None => dbg_println!(DbgLevel::Warn,
+                                                 "Sfs: invalid index entry {}",
+                                                 slot)
+                        }
+                        for used_slot in run.clone() {
+                            index_area.m_used_slots[used_slot] = true;
+                        }
+                    }
+                    slot = run.end;
+                },
+                /* the unused and the unknown entries are reusable */
+                _ => slot += 1
+            }
+        }
+        Ok((index_area, index_records))
+    }
+}
+
+impl IndexArea /* Methods */ {
+    /**
+     * Returns the amount of slots needed by the entry of the given
+     * `ObjType` with a name of the given length, `None` when the name is
+     * too long to be stored
+     */
+    pub fn slots_needed(&self, obj_type: ObjType, name_len: usize) -> Option<usize> {
+        let first_slot_name_len = C_INDEX_ENTRY_SIZE - self.name_offset(obj_type);
+        let tail_name_len = name_len.saturating_sub(first_slot_name_len);
+        let continuations = (tail_name_len + C_INDEX_ENTRY_SIZE - 1) / C_INDEX_ENTRY_SIZE;
+
+        if continuations <= C_CONTINUATIONS_MAX {
+            Some(continuations + 1)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Returns the highest run of the given amount of free slots, which
+     * keeps the new entries near the volume id
+     */
+    pub fn find_free_run(&self, slots_count: usize) -> Option<Range<usize>> {
+        let mut run_end = self.slots_count() - 1;
+
+        for slot in (0..self.slots_count() - 1).rev() {
+            if self.m_used_slots[slot] {
+                run_end = slot;
+            } else if run_end - slot == slots_count {
+                return Some(slot..run_end);
+            }
+        }
+        None
+    }
+
+    /**
+     * Writes the given `IndexEntry` into the given run of free slots, which
+     * must be long as `IndexArea::slots_needed()`.
+     *
+     * The deleted entries which share slots with the run are cleared, so
+     * their leftovers are never read as entries
+     */
+    pub fn write_entry(&mut self, run: Range<usize>, index_entry: &IndexEntry) {
+        let overlapping_runs: Vec<_> =
+            self.m_deleted_runs
+                .range(..run.end)
+                .filter(|(_, deleted_run_end)| **deleted_run_end > run.start)
+                .map(|(&deleted_run_start, &deleted_run_end)| {
+                    deleted_run_start..deleted_run_end
+                })
+                .collect();
+        for deleted_run in overlapping_runs {
+            self.m_deleted_runs.remove(&deleted_run.start);
+            for slot in deleted_run.filter(|slot| !run.contains(slot)) {
+                self.write_unused(slot);
+            }
+        }
+
+        let field_shift = self.field_offset(0);
+        let name_offset = self.name_offset(index_entry.obj_type());
+        let (start_block, end_block) = self.encode_blocks(&index_entry.m_blocks);
+
+        let raw_run = self.raw_run_mut(run.clone());
+        raw_run.fill(0);
+        raw_run[field_shift + C_CONTINUATIONS_OFFSET] = (run.len() - 1) as u8;
+        raw_run[field_shift + C_TIME_STAMP_OFFSET..field_shift + C_TIME_STAMP_OFFSET + 8]
+            .copy_from_slice(&index_entry.m_time_stamp.to_le_bytes());
+        if index_entry.obj_type() == ObjType::Dir {
+            raw_run[0] = C_DIR_ENTRY;
+        } else {
+            raw_run[0] = C_FILE_ENTRY;
+            write_u64(raw_run, field_shift + C_FILE_START_BLOCK_OFFSET, start_block);
+            write_u64(raw_run, field_shift + C_FILE_END_BLOCK_OFFSET, end_block);
+            write_u64(raw_run, field_shift + C_FILE_LENGTH_OFFSET, index_entry.m_length);
+        }
+
+        /* the name is terminated only when it doesn't fill the run */
+        let name_bytes = index_entry.m_name.as_bytes();
+        raw_run[name_offset..name_offset + name_bytes.len()].copy_from_slice(name_bytes);
+
+        self.update_crc(run.clone());
+        self.mark_run(run, true);
+    }
+
+    /**
+     * Marks as deleted the entry stored into the given run, which slots
+     * become free.
+     *
+     * The deleted entries are kept as they are until their slots are
+     * reused, so they can be recovered by the tools
+     */
+    pub fn delete_run(&mut self, run: Range<usize>) {
+        let raw_run = self.raw_run_mut(run.clone());
+        raw_run[0] = match raw_run[0] {
+            C_DIR_ENTRY => C_DELETED_DIR_ENTRY,
+            _ => C_DELETED_FILE_ENTRY
+        };
+
+        self.update_crc(run.clone());
+        self.m_deleted_runs.insert(run.start, run.end);
+        self.mark_run(run, false);
+    }
+
+    /**
+     * Marks the slots of the given run as unused
+     */
+    pub fn clear_run(&mut self, run: Range<usize>) {
+        for slot in run.clone() {
+            self.write_unused(slot);
+        }
+        self.mark_run(run, false);
+    }
+
+    /**
+     * Prepends the given amount of free slots to the index area, moving
+     * the start marker at its beginning.
+     *
+     * The slots of the entries are shifted up by the same amount
+     */
+    pub fn grow(&mut self, slots_count: usize) {
+        self.m_bytes.splice(0..0, iter::repeat(0).take(slots_count * C_INDEX_ENTRY_SIZE));
+        self.m_used_slots.splice(0..0, iter::repeat(false).take(slots_count));
+        self.m_deleted_runs = self.m_deleted_runs
+                                  .iter()
+                                  .map(|(&run_start, &run_end)| {
+                                      (run_start + slots_count, run_end + slots_count)
+                                  })
+                                  .collect();
+        self.m_dirty_slots =
+            self.m_dirty_slots.iter().map(|&slot| slot + slots_count).collect();
+
+        for slot in 1..slots_count {
+            self.write_unused(slot);
+        }
+        if self.entry_type_at(slots_count) == C_START_MARKER_ENTRY {
+            self.write_unused(slots_count);
+            self.m_used_slots[slots_count] = false;
+        }
+
+        self.raw_run_mut(0..1)[0] = C_START_MARKER_ENTRY;
+        self.update_crc(0..1);
+        self.mark_run(0..1, true);
+    }
+
+    /**
+     * Writes back the modified slots into the given `TBlockDevice` at the
+     * given offset of the index area
+     */
+    pub fn flush(&mut self,
+                 block_device: &dyn TBlockDevice,
+                 index_offset: u64)
+                 -> FsResult<()> {
+        let mut dirty_slots = self.m_dirty_slots.iter().copied().peekable();
+
+        /* the contiguous slots are written together */
+        while let Some(run_start) = dirty_slots.next() {
+            let mut run_end = run_start + 1;
+            while dirty_slots.peek() == Some(&run_end) {
+                dirty_slots.next();
+                run_end += 1;
+            }
+
+            block_device.write_bytes(index_offset
+                                     + (run_start * C_INDEX_ENTRY_SIZE) as u64,
+                                     self.raw_run(run_start..run_end))?;
+        }
+
+        self.m_dirty_slots.clear();
+        Ok(())
+    }
+}
+
+impl IndexArea /* Getters */ {
+    /**
+     * Returns the amount of slots of the index area
+     */
+    pub fn slots_count(&self) -> usize {
+        self.m_used_slots.len()
+    }
+
+    /**
+     * Returns the size in bytes of the index area
+     */
+    pub fn size(&self) -> u64 {
+        self.m_bytes.len() as u64
+    }
+
+    /**
+     * Returns the name stored into the volume id
+     */
+    pub fn volume_name(&self) -> String {
+        let raw_volume_id = self.raw_run(self.slots_count() - 1..self.slots_count());
+        let raw_name = &raw_volume_id
+            [C_VOLUME_NAME_OFFSET..C_VOLUME_NAME_OFFSET + C_VOLUME_NAME_LEN_MAX];
+
+        let name_len =
+            raw_name.iter().position(|byte| *byte == 0).unwrap_or(raw_name.len());
+        str::from_utf8(&raw_name[..name_len]).unwrap_or_default().to_string()
+    }
+}
+
+impl IndexArea /* Privates */ {
+    /**
+     * Parses the directory or file entry stored into the given run
+     */
+    fn parse_entry(&self, run: Range<usize>) -> Option<IndexEntry> {
+        let raw_run = self.raw_run(run);
+        let obj_type = if raw_run[0] == C_DIR_ENTRY {
+            ObjType::Dir
+        } else {
+            ObjType::File
+        };
+
+        let raw_name = &raw_run[self.name_offset(obj_type)..];
+        let name_len =
+            raw_name.iter().position(|byte| *byte == 0).unwrap_or(raw_name.len());
+        let name = str::from_utf8(&raw_name[..name_len]).ok()?;
+
+        let time_stamp = read_u64(raw_run, self.field_offset(C_TIME_STAMP_OFFSET)) as i64;
+        if obj_type == ObjType::Dir {
+            Some(IndexEntry::new(ObjType::Dir, name.to_string(), time_stamp))
+        } else {
+            let start_block =
+                read_u64(raw_run, self.field_offset(C_FILE_START_BLOCK_OFFSET));
+            let end_block = read_u64(raw_run, self.field_offset(C_FILE_END_BLOCK_OFFSET));
+            let blocks = self.decode_blocks(start_block, end_block)?;
+
+            let mut index_entry =
+                IndexEntry::new(ObjType::File, name.to_string(), time_stamp);
+            index_entry.m_blocks = blocks;
+            index_entry.m_length =
+                read_u64(raw_run, self.field_offset(C_FILE_LENGTH_OFFSET));
+            Some(index_entry)
+        }
+    }
+
+    /**
+     * Converts the on-disk start and end blocks into a `Range`.
+     *
+     * The `SfsVariant::Sfs` end block is exclusive, while the
+     * `SfsVariant::Sfse` one is inclusive and both zero for no blocks
+     */
+    fn decode_blocks(&self, start_block: u64, end_block: u64) -> Option<Range<u64>> {
+        match self.m_variant {
+            SfsVariant::Sfs if start_block <= end_block => Some(start_block..end_block),
+            SfsVariant::Sfse if start_block == 0 && end_block == 0 => Some(0..0),
+            SfsVariant::Sfse if start_block <= end_block => {
+                Some(start_block..end_block.checked_add(1)?)
+            },
+            _ => None
+        }
+    }
+
+    /**
+     * Converts the given `Range` of blocks into the on-disk start and end
+     * blocks
+     */
+    fn encode_blocks(&self, blocks: &Range<u64>) -> (u64, u64) {
+        if blocks.is_empty() {
+            (0, 0)
+        } else if self.m_variant == SfsVariant::Sfse {
+            (blocks.start, blocks.end - 1)
+        } else {
+            (blocks.start, blocks.end)
+        }
+    }
+
+    /**
+     * Returns whether the bytes of the given run sum to zero, always true
+     * for the `SfsVariant::Sfs` entries which have no CRC
+     */
+    fn is_crc_valid(&self, run: Range<usize>) -> bool {
+        self.m_variant != SfsVariant::Sfse
+        || self.raw_run(run).iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+    }
+
+    /**
+     * Updates the CRC of the `SfsVariant::Sfse` entry stored into the
+     * given run
+     */
+    fn update_crc(&mut self, run: Range<usize>) {
+        if self.m_variant == SfsVariant::Sfse {
+            let raw_run = self.raw_run_mut(run);
+
+            raw_run[C_SFSE_CRC_OFFSET] = 0;
+            let sum = raw_run.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            raw_run[C_SFSE_CRC_OFFSET] = 0u8.wrapping_sub(sum);
+        }
+    }
+
+    /**
+     * Overwrites the given slot with an unused entry
+     */
+    fn write_unused(&mut self, slot: usize) {
+        let raw_entry = self.raw_run_mut(slot..slot + 1);
+
+        raw_entry.fill(0);
+        raw_entry[0] = C_UNUSED_ENTRY;
+        self.update_crc(slot..slot + 1);
+        self.m_dirty_slots.insert(slot);
+    }
+
+    /**
+     * Marks the slots of the given run as used or free and as dirty
+     */
+    fn mark_run(&mut self, run: Range<usize>, is_used: bool) {
+        for slot in run {
+            self.m_used_slots[slot] = is_used;
+            self.m_dirty_slots.insert(slot);
+        }
+    }
+
+    /**
+     * Returns the type of the entry stored into the given slot
+     */
+    fn entry_type_at(&self, slot: usize) -> u8 {
+        self.m_bytes[slot * C_INDEX_ENTRY_SIZE]
+    }
+
+    /**
+     * Returns the offset of the given `SfsVariant::Sfs` field of the
+     * directory and file entries for the layout of this index area
+     */
+    fn field_offset(&self, sfs_field_offset: usize) -> usize {
+        match self.m_variant {
+            SfsVariant::Sfs => sfs_field_offset,
+            SfsVariant::Sfse => sfs_field_offset + 1
+        }
+    }
+
+    /**
+     * Returns the offset of the name into the entries of the given
+     * `ObjType`
+     */
+    fn name_offset(&self, obj_type: ObjType) -> usize {
+        if obj_type == ObjType::Dir {
+            self.field_offset(C_DIR_NAME_OFFSET)
+        } else {
+            self.field_offset(C_FILE_NAME_OFFSET)
+        }
+    }
+
+    /**
+     * Returns the bytes of the given run of slots
+     */
+    fn raw_run(&self, run: Range<usize>) -> &[u8] {
+        &self.m_bytes[run.start * C_INDEX_ENTRY_SIZE..run.end * C_INDEX_ENTRY_SIZE]
+    }
+
+    /**
+     * Returns the mutable bytes of the given run of slots
+     */
+    fn raw_run_mut(&mut self, run: Range<usize>) -> &mut [u8] {
+        &mut self.m_bytes[run.start * C_INDEX_ENTRY_SIZE..run.end * C_INDEX_ENTRY_SIZE]
+    }
+}
+
+/**
+ * Directory or file entry of the `IndexArea`.
+ *
+ * The name is the full path of the object from the root, without the
+ * leading separator
+ */
+#[derive(Clone)]
+pub struct IndexEntry {
+    m_obj_type: ObjType,
+    m_name: String,
+    m_time_stamp: i64,
+    m_blocks: Range<u64>,
+    m_length: u64
+}
+
+impl IndexEntry /* Constructors */ {
+    /**
+     * Constructs an `IndexEntry` with no data blocks
+     */
+    pub fn new(obj_type: ObjType, name: String, time_stamp: i64) -> Self {
+        Self { m_obj_type: obj_type,
+               m_name: name,
+               m_time_stamp: time_stamp,
+               m_blocks: 0..0,
+               m_length: 0 }
+    }
+}
+
+impl IndexEntry /* Getters */ {
+    /**
+     * Returns the `ObjType`, `ObjType::Dir` or `ObjType::File`
+     */
+    pub fn obj_type(&self) -> ObjType {
+        self.m_obj_type
+    }
+
+    /**
+     * Returns the full path without the leading separator
+     */
+    pub fn name(&self) -> &str {
+        &self.m_name
+    }
+
+    /**
+     * Returns the time stamp of the last modification
+     */
+    pub fn time_stamp(&self) -> i64 {
+        self.m_time_stamp
+    }
+
+    /**
+     * Returns the contiguous data blocks of the file
+     */
+    pub fn blocks(&self) -> Range<u64> {
+        self.m_blocks.clone()
+    }
+
+    /**
+     * Returns the length in bytes of the data of the file
+     */
+    pub fn length(&self) -> u64 {
+        self.m_length
+    }
+}
+
+impl IndexEntry /* Setters */ {
+    /**
+     * Updates the full path
+     */
+    pub fn set_name(&mut self, name: String) {
+        self.m_name = name;
+    }
+
+    /**
+     * Updates the time stamp of the last modification
+     */
+    pub fn set_time_stamp(&mut self, time_stamp: i64) {
+        self.m_time_stamp = time_stamp;
+    }
+
+    /**
+     * Updates the contiguous data blocks of the file
+     */
+    pub fn set_blocks(&mut self, blocks: Range<u64>) {
+        self.m_blocks = blocks;
+    }
+
+    /**
+     * Updates the length in bytes of the data of the file
+     */
+    pub fn set_length(&mut self, length: u64) {
+        self.m_length = length;
+    }
+}
+
+/**
+ * Lists the records found into the `IndexArea` which the volume must
+ * account
+ */
+pub enum IndexRecord {
+    /**
+     * Directory or file entry with its run of slots
+     */
+    Node(Range<usize>, IndexEntry),
+
+    /**
+     * Range of blocks marked as unusable, which must never be allocated
+     */
+    Unusable(Range<u64>)
+}
+
+/**
+ * Reads the little endian `u64` at the given offset
+ */
+fn read_u64(raw_run: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(raw_run[offset..offset + 8].try_into().unwrap())
+}
+
+/**
+ * Writes the given value as little endian `u64` at the given offset
+ */
+fn write_u64(raw_run: &mut [u8], offset: usize, value: u64) {
+    raw_run[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
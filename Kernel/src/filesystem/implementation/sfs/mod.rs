@@ -1,32 +1,176 @@
-use alloc::rc::Rc;
-
-use crate::filesystem::Filesystem;
-
-struct SFS {}
-
-fn is_invalid_value(val: u8) -> bool {
-    // Test for value ranges first.
-    val < 0x20u8 && (val > 0x20u8 && val < 0x32u8)
-    || (val >= 0x80u8 && val <= 0x9Fu8)
-    // Test for characters.
-    || val == b'"' || val == b'*' || val == b':' || val == b'<' || val == b'>' || val == b'?' || val == b'\\'
-    // DEL character
-    || val == 0x7Fu8
-    // NBSP (No Space Break Character) is willingly left out, since it's swapped
-    // with a space character instead
-}
-
-impl Filesystem for SFS {
-    fn validate_path_in_namespace(&self, path: Rc<&str>) -> Result<&str, &str> {
-        if !path.into_iter().any(is_invalid_value) {
-            // Convert NBSP (No Space Break Character) to a normal character, if
-            // any.
-            Ok(path.swap(0x00A0, 0x0020))
+/*! Simple FileSystem */
+
+use alloc::{
+    string::String,
+    sync::Arc
+};
+use core::time::Duration;
+
+use api_data::instant::RawInstant;
+
+use crate::{
+    dbg_print::DbgLevel,
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::sfs::{
+            node::SfsNode,
+            super_block::SuperBlock,
+            volume::SfsVolume
+        },
+        node::TFsNode,
+        Filesystems,
+        FsResult,
+        TFilesystem,
+        TFilesystemProvider
+    }
+};
+
+pub mod index;
+pub mod node;
+pub mod super_block;
+pub mod volume;
+
+/* the time stamps count the 1/65536 fractions of second since the Unix epoch */
+const C_TIME_STAMP_FRACTIONS: u128 = 65536;
+
+/* characters which the names can't contain, other than the control ones */
+const C_INVALID_NAME_CHARS: &str = "\"*:<>?\\/\u{7F}";
+
+/* NBSP (No-Break Space), stored as a normal space */
+const C_NO_BREAK_SPACE: char = '\u{A0}';
+
+/**
+ * Lists the on-disk layouts of the Simple FileSystem
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum SfsVariant {
+    /**
+     * The original 2006 layout
+     */
+    Sfs,
+
+    /**
+     * The layout extended by Forever Young Software in 2018
+     */
+    Sfse
+}
+
+impl SfsVariant /* Getters */ {
+    /**
+     * Returns the name of the layout
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sfs => "sfs",
+            Self::Sfse => "sfse"
+        }
+    }
+}
+
+impl From<SfsVariant> for Filesystems {
+    fn from(sfs_variant: SfsVariant) -> Self {
+        match sfs_variant {
+            SfsVariant::Sfs => Self::Sfs,
+            SfsVariant::Sfse => Self::Sfse
         }
-        Err("Invalid path")
+    }
+}
+
+/**
+ * `TFilesystemProvider` of the Simple FileSystem, which recognizes both
+ * the original layout and the Forever Young extended one
+ */
+pub struct SfsProvider;
+
+impl TFilesystemProvider for SfsProvider {
+    fn verify_superblock(&self,
+                         block_device: &dyn TBlockDevice)
+                         -> FsResult<Filesystems> {
+        let super_block = SuperBlock::read(block_device)?;
+
+        super_block.verify(block_device)?;
+        Ok(super_block.variant().into())
     }
 
-    fn get_root_inode(&self) -> _ {
-        todo!()
+    fn open(&self,
+            block_device: Arc<dyn TBlockDevice>)
+            -> FsResult<Arc<dyn TFilesystem>> {
+        let volume = SfsVolume::load(block_device)?;
+        dbg_println!(DbgLevel::Info, "Sfs: opened volume '{}'", volume.volume_name());
+
+        Ok(Arc::new(Sfs { m_root: SfsNode::new_root(volume.clone()),
+                          m_volume: volume }))
+    }
+}
+
+/**
+ * `TFilesystem` which exchanges files with the tools which speak the
+ * Simple FileSystem.
+ *
+ * The whole tree is described by the index area at the end of the
+ * volume, where each entry stores the full path of a directory or of a
+ * file, which data is a contiguous range of blocks
+ */
+pub struct Sfs {
+    m_root: Arc<SfsNode>,
+    m_volume: Arc<SfsVolume>
+}
+
+impl TFilesystem for Sfs {
+    fn name(&self) -> &str {
+        self.m_volume.variant().name()
     }
+
+    fn root(&self) -> Arc<dyn TFsNode> {
+        self.m_root.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.m_volume.is_read_only()
+    }
+
+    fn sync(&self) -> FsResult<()> {
+        self.m_volume.sync()
+    }
+}
+
+/**
+ * Returns whether the given name can be a component of the paths stored
+ * into the index area
+ */
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+    && !name.chars().any(|c| {
+                        c < ' '
+                        || ('\u{80}'..='\u{9F}').contains(&c)
+                        || C_INVALID_NAME_CHARS.contains(c)
+                    })
+}
+
+/**
+ * Returns the given name with the NBSPs swapped with normal spaces
+ */
+pub fn normalize_name(name: &str) -> String {
+    name.replace(C_NO_BREAK_SPACE, " ")
+}
+
+/**
+ * Converts the given on-disk time stamp into a `RawInstant`, the times
+ * before the Unix epoch are clamped to it
+ */
+pub fn time_stamp_to_instant(time_stamp: i64) -> RawInstant {
+    let fractions = time_stamp.max(0) as u128;
+    let nanos =
+        fractions % C_TIME_STAMP_FRACTIONS * 1_000_000_000 / C_TIME_STAMP_FRACTIONS;
+
+    Duration::new((fractions / C_TIME_STAMP_FRACTIONS) as u64, nanos as u32)
+}
+
+/**
+ * Converts the given `RawInstant` into an on-disk time stamp
+ */
+pub fn instant_to_time_stamp(instant: RawInstant) -> i64 {
+    (instant.as_nanos() * C_TIME_STAMP_FRACTIONS / 1_000_000_000) as i64
 }
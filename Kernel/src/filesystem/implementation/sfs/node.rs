@@ -0,0 +1,133 @@
+/*! Simple FileSystem nodes */
+
+use alloc::sync::Arc;
+use core::any::Any;
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::{
+        dir::DirEntry,
+        info::RawObjInfo,
+        types::ObjType
+    }
+};
+
+use crate::filesystem::{
+    implementation::sfs::volume::{
+        SfsVolume,
+        C_ROOT_NODE_ID
+    },
+    node::{
+        FsNodeId,
+        TFsNode
+    },
+    FsResult
+};
+
+/**
+ * `TFsNode` of the `Sfs`.
+ *
+ * The node only stores its `FsNodeId`, the entry is kept by the
+ * `SfsVolume`, which also serializes the accesses of the nodes which share
+ * it
+ */
+pub struct SfsNode {
+    m_id: FsNodeId,
+    m_obj_type: ObjType,
+    m_volume: Arc<SfsVolume>
+}
+
+impl SfsNode /* Constructors */ {
+    /**
+     * Constructs the root directory of the given `SfsVolume`
+     */
+    pub fn new_root(volume: Arc<SfsVolume>) -> Arc<Self> {
+        Self::new(volume, C_ROOT_NODE_ID, ObjType::Dir)
+    }
+
+    /**
+     * Constructs the `SfsNode` of the entry with the given `FsNodeId`
+     */
+    fn new(volume: Arc<SfsVolume>, node_id: FsNodeId, obj_type: ObjType) -> Arc<Self> {
+        Arc::new(Self { m_id: node_id,
+                        m_obj_type: obj_type,
+                        m_volume: volume })
+    }
+}
+
+impl TFsNode for SfsNode {
+    fn id(&self) -> FsNodeId {
+        self.m_id
+    }
+
+    fn obj_type(&self) -> ObjType {
+        self.m_obj_type
+    }
+
+    fn size(&self) -> usize {
+        self.m_volume.size(self.m_id)
+    }
+
+    fn info(&self) -> FsResult<RawObjInfo> {
+        self.m_volume.info(self.m_id)
+    }
+
+    fn read_at(&self, offset: usize, buffer: &mut [u8]) -> FsResult<usize> {
+        self.m_volume.read_at(self.m_id, offset, buffer)
+    }
+
+    fn write_at(&self, offset: usize, buffer: &[u8]) -> FsResult<usize> {
+        self.m_volume.write_at(self.m_id, offset, buffer)
+    }
+
+    fn truncate(&self, size: usize) -> FsResult<()> {
+        self.m_volume.truncate(self.m_id, size)
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn TFsNode>> {
+        let (child_id, obj_type) = self.m_volume.lookup(self.m_id, name)?;
+        Ok(Self::new(self.m_volume.clone(), child_id, obj_type))
+    }
+
+    fn read_dir(&self, index: usize) -> FsResult<Option<DirEntry>> {
+        self.m_volume.read_dir(self.m_id, index)
+    }
+
+    fn create(&self, name: &str, obj_type: ObjType) -> FsResult<Arc<dyn TFsNode>> {
+        if !matches!(obj_type, ObjType::File | ObjType::Dir) {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let child_id = self.m_volume.create(self.m_id, name, obj_type)?;
+        Ok(Self::new(self.m_volume.clone(), child_id, obj_type))
+    }
+
+    fn create_link(&self, _name: &str, _target: &str) -> FsResult<Arc<dyn TFsNode>> {
+        /* the layout has no entry for the links */
+        Err(OsErrorClass::OperationNotEnabled)
+    }
+
+    fn unlink(&self, name: &str) -> FsResult<()> {
+        self.m_volume.unlink(self.m_id, name)
+    }
+
+    fn rename(&self,
+              name: &str,
+              new_parent: &dyn TFsNode,
+              new_name: &str)
+              -> FsResult<()> {
+        let new_parent =
+            new_parent.as_any()
+                      .downcast_ref::<SfsNode>()
+                      .filter(|new_parent| {
+                          Arc::ptr_eq(&new_parent.m_volume, &self.m_volume)
+                      })
+                      .ok_or(OsErrorClass::InvalidArgument)?;
+
+        self.m_volume.rename(self.m_id, name, new_parent.m_id, new_name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
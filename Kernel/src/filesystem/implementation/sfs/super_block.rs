@@ -0,0 +1,288 @@
+/*! Simple FileSystem super block */
+
+use core::{
+    convert::TryInto,
+    ops::Range
+};
+
+use api_data::error::class::OsErrorClass;
+
+use crate::{
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::sfs::{
+            index::C_INDEX_ENTRY_SIZE,
+            instant_to_time_stamp,
+            SfsVariant
+        },
+        FsResult
+    },
+    time::TimeManager
+};
+
+/**
+ * Size in bytes of the `SuperBlock` on disk
+ */
+pub const C_SUPER_BLOCK_SIZE: usize = 42;
+
+/* offset of the <SuperBlock> into the boot block for each layout */
+const C_SFS_SUPER_BLOCK_OFFSET: usize = 0x194;
+const C_SFSE_SUPER_BLOCK_OFFSET: usize = 0x18E;
+
+/* version written by each layout, the higher ones are mounted read-only */
+const C_SFS_VERSION: u8 = 0x10;
+const C_SFSE_VERSION: u8 = 0x1A;
+
+/* signature which precedes the version */
+const C_SFS_MAGIC: &[u8] = b"SFS";
+
+/* size of the boot block which contains the <SuperBlock> */
+const C_BOOT_BLOCK_SIZE: usize = 512;
+
+/* limit of the block size shift, which selects blocks of at most 512KiB */
+const C_BLOCK_SIZE_SHIFT_MAX: u8 = 12;
+
+/* the index area contains at least the start marker and the volume id */
+const C_INDEX_SIZE_MIN: u64 = 2 * C_INDEX_ENTRY_SIZE as u64;
+
+/**
+ * Super block of a Simple FileSystem volume.
+ *
+ * It lives into the boot block, at an offset which depends on the
+ * `SfsVariant`, and describes the layout of the volume: the
+ * reserved blocks, the data area which follows them and the index area at
+ * the end of the volume
+ */
+pub struct SuperBlock {
+    m_variant: SfsVariant,
+    m_time_stamp: i64,
+    m_data_blocks: u64,
+    m_index_size: u64,
+    m_version: u8,
+    m_total_blocks: u64,
+    m_reserved_blocks: u32,
+    m_block_size_shift: u8
+}
+
+impl SuperBlock /* Constructors */ {
+    /**
+     * Reads the `SuperBlock` from the boot block of the given
+     * `TBlockDevice`, checking its signature and its checksum.
+     *
+     * Returns `OsErrorClass::TypesNotMatch` when none of the `SfsVariant`s
+     * matches
+     */
+    pub fn read(block_device: &dyn TBlockDevice) -> FsResult<Self> {
+        let mut boot_block = [0; C_BOOT_BLOCK_SIZE];
+        block_device.read_bytes(0, &mut boot_block)?;
+
+        for &variant in [SfsVariant::Sfs, SfsVariant::Sfse].iter() {
+            let super_block_offset = Self::offset_of(variant);
+            let raw_super_block =
+                &boot_block[super_block_offset..super_block_offset + C_SUPER_BLOCK_SIZE];
+
+            if let Some(super_block) = Self::parse(variant, raw_super_block) {
+                return Ok(super_block);
+            }
+        }
+        Err(OsErrorClass::TypesNotMatch)
+    }
+}
+
+impl SuperBlock /* Methods */ {
+    /**
+     * Checks the consistency of the fields against the given
+     * `TBlockDevice`
+     */
+    pub fn verify(&self, block_device: &dyn TBlockDevice) -> FsResult<()> {
+        if self.m_block_size_shift < 1 || self.m_block_size_shift > C_BLOCK_SIZE_SHIFT_MAX
+        {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        /* the reserved blocks include the boot block */
+        let reserved_bytes = self.m_reserved_blocks as u64 * self.block_size();
+        if reserved_bytes < C_BOOT_BLOCK_SIZE as u64 {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        if self.m_index_size < C_INDEX_SIZE_MIN
+           || self.m_index_size % C_INDEX_ENTRY_SIZE as u64 != 0
+        {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let total_size = self.m_total_blocks
+                             .checked_mul(self.block_size())
+                             .ok_or(OsErrorClass::LimitOverflow)?;
+        if total_size > block_device.size() || self.m_index_size > total_size {
+            return Err(OsErrorClass::LimitOverflow);
+        }
+
+        /* the data area must end before the index area */
+        let data_end =
+            (self.m_reserved_blocks as u64).checked_add(self.m_data_blocks)
+                                           .ok_or(OsErrorClass::LimitOverflow)?;
+        if data_end > self.free_blocks_end() {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes back this `SuperBlock` into the boot block of the given
+     * `TBlockDevice`
+     */
+    pub fn write(&self, block_device: &dyn TBlockDevice) -> FsResult<()> {
+        let mut raw_super_block = [0; C_SUPER_BLOCK_SIZE];
+
+        raw_super_block[0..8].copy_from_slice(&self.m_time_stamp.to_le_bytes());
+        raw_super_block[8..16].copy_from_slice(&self.m_data_blocks.to_le_bytes());
+        raw_super_block[16..24].copy_from_slice(&self.m_index_size.to_le_bytes());
+        raw_super_block[24..27].copy_from_slice(C_SFS_MAGIC);
+        raw_super_block[27] = self.m_version;
+        raw_super_block[28..36].copy_from_slice(&self.m_total_blocks.to_le_bytes());
+        raw_super_block[36..40].copy_from_slice(&self.m_reserved_blocks.to_le_bytes());
+        raw_super_block[40] = self.m_block_size_shift;
+
+        /* the checksummed bytes, from the signature to the checksum, sum to zero */
+        let checksum =
+            raw_super_block[24..41].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        raw_super_block[41] = 0u8.wrapping_sub(checksum);
+
+        block_device.write_bytes(Self::offset_of(self.m_variant) as u64, &raw_super_block)
+    }
+}
+
+impl SuperBlock /* Getters */ {
+    /**
+     * Returns the `SfsVariant` of the layout
+     */
+    pub fn variant(&self) -> SfsVariant {
+        self.m_variant
+    }
+
+    /**
+     * Returns whether the volume was written by a newer version of the
+     * layout, which must not be modified
+     */
+    pub fn is_newer_version(&self) -> bool {
+        self.m_version > Self::version_of(self.m_variant)
+    }
+
+    /**
+     * Returns the time stamp of the last modification of the `SuperBlock`
+     */
+    pub fn time_stamp(&self) -> i64 {
+        self.m_time_stamp
+    }
+
+    /**
+     * Returns the size in bytes of the blocks
+     */
+    pub fn block_size(&self) -> u64 {
+        1 << (self.m_block_size_shift as u64 + 7)
+    }
+
+    /**
+     * Returns the range of blocks of the data area
+     */
+    pub fn data_area(&self) -> Range<u64> {
+        let data_start = self.m_reserved_blocks as u64;
+        data_start..data_start + self.m_data_blocks
+    }
+
+    /**
+     * Returns the size in bytes of the index area
+     */
+    pub fn index_size(&self) -> u64 {
+        self.m_index_size
+    }
+
+    /**
+     * Returns the byte offset of the index area, which ends with the volume
+     */
+    pub fn index_offset(&self) -> u64 {
+        self.m_total_blocks * self.block_size() - self.m_index_size
+    }
+
+    /**
+     * Returns the first block which is not entirely free because it is
+     * covered by the index area
+     */
+    pub fn free_blocks_end(&self) -> u64 {
+        self.index_offset() / self.block_size()
+    }
+}
+
+impl SuperBlock /* Setters */ {
+    /**
+     * Updates the amount of blocks of the data area
+     */
+    pub fn set_data_blocks(&mut self, data_blocks: u64) {
+        self.m_data_blocks = data_blocks;
+        self.m_time_stamp = instant_to_time_stamp(TimeManager::now_instant());
+    }
+
+    /**
+     * Updates the size in bytes of the index area
+     */
+    pub fn set_index_size(&mut self, index_size: u64) {
+        self.m_index_size = index_size;
+        self.m_time_stamp = instant_to_time_stamp(TimeManager::now_instant());
+    }
+}
+
+impl SuperBlock /* Privates */ {
+    /**
+     * Parses the given raw `SuperBlock` with the layout of the given
+     * `SfsVariant`, `None` when the signature, the version or the
+     * checksum doesn't match
+     */
+    fn parse(variant: SfsVariant, raw_super_block: &[u8]) -> Option<Self> {
+        let version = raw_super_block[27];
+        let checksum =
+            raw_super_block[24..42].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        if &raw_super_block[24..27] != C_SFS_MAGIC
+           || version < Self::version_of(variant)
+           || checksum != 0
+        {
+            return None;
+        }
+
+        let read_u64 = |offset: usize| {
+            u64::from_le_bytes(raw_super_block[offset..offset + 8].try_into().unwrap())
+        };
+        Some(Self { m_variant: variant,
+                    m_time_stamp: read_u64(0) as i64,
+                    m_data_blocks: read_u64(8),
+                    m_index_size: read_u64(16),
+                    m_version: version,
+                    m_total_blocks: read_u64(28),
+                    m_reserved_blocks:
+                        u32::from_le_bytes(raw_super_block[36..40].try_into().unwrap()),
+                    m_block_size_shift: raw_super_block[40] })
+    }
+
+    /**
+     * Returns the offset of the `SuperBlock` into the boot block for the
+     * given `SfsVariant`
+     */
+    fn offset_of(variant: SfsVariant) -> usize {
+        match variant {
+            SfsVariant::Sfs => C_SFS_SUPER_BLOCK_OFFSET,
+            SfsVariant::Sfse => C_SFSE_SUPER_BLOCK_OFFSET
+        }
+    }
+
+    /**
+     * Returns the version written by the given `SfsVariant`
+     */
+    fn version_of(variant: SfsVariant) -> u8 {
+        match variant {
+            SfsVariant::Sfs => C_SFS_VERSION,
+            SfsVariant::Sfse => C_SFSE_VERSION
+        }
+    }
+}
@@ -0,0 +1,940 @@
+/*! Simple FileSystem volume */
+
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+use core::ops::Range;
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::{
+        device::DeviceId,
+        dir::DirEntry,
+        info::RawObjInfo,
+        types::ObjType
+    }
+};
+use sync::SpinMutex;
+
+use crate::{
+    dbg_print::DbgLevel,
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::sfs::{
+            index::{
+                IndexArea,
+                IndexEntry,
+                IndexRecord,
+                C_INDEX_ENTRY_SIZE
+            },
+            instant_to_time_stamp,
+            is_valid_name,
+            normalize_name,
+            super_block::SuperBlock,
+            time_stamp_to_instant,
+            SfsVariant
+        },
+        node::{
+            default_prot_grants,
+            FsNodeId
+        },
+        FsResult
+    },
+    time::TimeManager
+};
+
+/**
+ * `FsNodeId` of the root directory, which has no index entry
+ */
+pub const C_ROOT_NODE_ID: FsNodeId = 0;
+
+/**
+ * Mounted Simple FileSystem volume shared by the `SfsNode`s.
+ *
+ * The whole index area is kept in memory and each modification is
+ * written back before returning. The volume is opened read-only when it
+ * was written by a newer version of the layout, or when its data extents
+ * are inconsistent
+ */
+pub struct SfsVolume {
+    m_variant: SfsVariant,
+    m_device_id: DeviceId,
+    m_is_read_only: bool,
+    m_state: SpinMutex<SfsState>
+}
+
+impl SfsVolume /* Constructors */ {
+    /**
+     * Loads the volume stored into the given `TBlockDevice`.
+     *
+     * The missing parent directories of the entries are made implicit,
+     * while the invalid and the duplicated entries are hidden
+     */
+    pub fn load(block_device: Arc<dyn TBlockDevice>) -> FsResult<Arc<Self>> {
+        let super_block = SuperBlock::read(block_device.as_ref())?;
+        super_block.verify(block_device.as_ref())?;
+
+        let (index_area, index_records) =
+            IndexArea::load(block_device.as_ref(), &super_block)?;
+
+        let variant = super_block.variant();
+        let device_id = block_device.device_id();
+        let mut is_read_only =
+            block_device.is_read_only() || super_block.is_newer_version();
+
+        let root_entry =
+            IndexEntry::new(ObjType::Dir, String::new(), super_block.time_stamp());
+        let mut state = SfsState { m_block_device: block_device,
+                                   m_super_block: super_block,
+                                   m_is_super_block_dirty: false,
+                                   m_index_area: index_area,
+                                   m_records: BTreeMap::new(),
+                                   m_paths: BTreeMap::new(),
+                                   m_used_blocks: BTreeMap::new(),
+                                   m_next_node_id: C_ROOT_NODE_ID };
+        state.insert_record(root_entry, None);
+
+        if !state.load_records(index_records) {
+            dbg_println!(DbgLevel::Warn,
+                         "Sfs: inconsistent data extents, mounting read-only");
+            is_read_only = true;
+        }
+
+        Ok(Arc::new(Self { m_variant: variant,
+                           m_device_id: device_id,
+                           m_is_read_only: is_read_only,
+                           m_state: SpinMutex::const_new(state) }))
+    }
+}
+
+impl SfsVolume /* Methods */ {
+    /**
+     * Reads the data of the given file from the given offset into the
+     * given buffer, returning the amount of bytes read
+     */
+    pub fn read_at(&self,
+                   node_id: FsNodeId,
+                   offset: usize,
+                   buffer: &mut [u8])
+                   -> FsResult<usize> {
+        let state = self.m_state.lock();
+        let index_entry = state.file_entry(node_id)?;
+
+        let read_bytes =
+            (index_entry.length() as usize).saturating_sub(offset).min(buffer.len());
+        if read_bytes > 0 {
+            let data_offset =
+                state.block_offset(index_entry.blocks().start) + offset as u64;
+            state.m_block_device.read_bytes(data_offset, &mut buffer[..read_bytes])?;
+        }
+        Ok(read_bytes)
+    }
+
+    /**
+     * Writes the given buffer at the given offset of the given file,
+     * moving its data when the following blocks are not free
+     */
+    pub fn write_at(&self,
+                    node_id: FsNodeId,
+                    offset: usize,
+                    buffer: &[u8])
+                    -> FsResult<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let end_offset =
+            offset.checked_add(buffer.len()).ok_or(OsErrorClass::LimitOverflow)? as u64;
+
+        let mut state = self.m_state.lock();
+        let index_entry = state.file_entry(node_id)?;
+        let length = index_entry.length();
+
+        /* the extent is only grown, the blocks after the written data are kept */
+        let blocks_count = state.blocks_for(end_offset);
+        if blocks_count > index_entry.blocks().end - index_entry.blocks().start {
+            state.resize_extent(node_id, blocks_count)?;
+        }
+
+        /* the gap between the old end and the written data is read as zeroes */
+        let data_start = state.block_offset(state.file_entry(node_id)?.blocks().start);
+        if offset as u64 > length {
+            state.zero_fill(data_start + length, offset as u64 - length)?;
+        }
+        state.m_block_device.write_bytes(data_start + offset as u64, buffer)?;
+
+        state.update_file_entry(node_id, length.max(end_offset))?;
+        state.flush()?;
+        Ok(buffer.len())
+    }
+
+    /**
+     * Shrinks or grows the data of the given file to the given size,
+     * releasing or allocating its blocks
+     */
+    pub fn truncate(&self, node_id: FsNodeId, size: usize) -> FsResult<()> {
+        let mut state = self.m_state.lock();
+        let length = state.file_entry(node_id)?.length();
+        let blocks_count = state.blocks_for(size as u64);
+
+        state.resize_extent(node_id, blocks_count)?;
+        if size as u64 > length {
+            let data_start =
+                state.block_offset(state.file_entry(node_id)?.blocks().start);
+            state.zero_fill(data_start + length, size as u64 - length)?;
+        }
+
+        state.update_file_entry(node_id, size as u64)?;
+        state.flush()
+    }
+
+    /**
+     * Returns the `FsNodeId` and the `ObjType` of the child with the given
+     * name of the given directory
+     */
+    pub fn lookup(&self, dir_id: FsNodeId, name: &str) -> FsResult<(FsNodeId, ObjType)> {
+        let state = self.m_state.lock();
+        let child_path = state.child_path(dir_id, &normalize_name(name))?;
+
+        let child_id =
+            *state.m_paths.get(&child_path).ok_or(OsErrorClass::ReferenceNotFound)?;
+        Ok((child_id, state.record(child_id)?.m_entry.obj_type()))
+    }
+
+    /**
+     * Returns the `DirEntry` of the child at the given index of the given
+     * directory, sorted by name
+     */
+    pub fn read_dir(&self, dir_id: FsNodeId, index: usize) -> FsResult<Option<DirEntry>> {
+        let state = self.m_state.lock();
+        let children_prefix = state.child_path(dir_id, "")?;
+
+        let dir_entry =
+            state.m_paths
+                 .range(children_prefix.clone()..)
+                 .take_while(|(path, _)| path.starts_with(&children_prefix))
+                 .map(|(path, child_id)| (&path[children_prefix.len()..], child_id))
+                 .filter(|(name, _)| !name.is_empty() && !name.contains('/'))
+                 .nth(index)
+                 .map(|(name, child_id)| {
+                     DirEntry::new(name, state.m_records[child_id].m_entry.obj_type())
+                 });
+        Ok(dir_entry)
+    }
+
+    /**
+     * Creates a new empty `ObjType::File` or `ObjType::Dir` with the given
+     * name into the given directory, returning its `FsNodeId`.
+     *
+     * The implicit parent directories are written into the index area
+     * before the new entry
+     */
+    pub fn create(&self,
+                  dir_id: FsNodeId,
+                  name: &str,
+                  obj_type: ObjType)
+                  -> FsResult<FsNodeId> {
+        let name = normalize_name(name);
+        if !is_valid_name(&name) {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let mut state = self.m_state.lock();
+        let child_path = state.child_path(dir_id, &name)?;
+        if state.m_paths.contains_key(&child_path) {
+            return Err(OsErrorClass::IdentifierNotAvailable);
+        }
+        state.materialize_dir(dir_id)?;
+
+        let time_stamp = instant_to_time_stamp(TimeManager::now_instant());
+        let index_entry = IndexEntry::new(obj_type, child_path, time_stamp);
+        let slots = state.place_entry(&index_entry)?;
+
+        let child_id = state.insert_record(index_entry, Some(slots));
+        state.flush()?;
+        Ok(child_id)
+    }
+
+    /**
+     * Removes the child with the given name from the given directory,
+     * which entry is marked as deleted and its blocks released
+     */
+    pub fn unlink(&self, dir_id: FsNodeId, name: &str) -> FsResult<()> {
+        let mut state = self.m_state.lock();
+        let child_path = state.child_path(dir_id, &normalize_name(name))?;
+
+        let child_id =
+            *state.m_paths.get(&child_path).ok_or(OsErrorClass::ReferenceNotFound)?;
+        if state.has_children(&child_path) {
+            return Err(OsErrorClass::OperationNotEnabled);
+        }
+
+        let record =
+            state.m_records.remove(&child_id).ok_or(OsErrorClass::ReferenceNotFound)?;
+        state.m_paths.remove(&child_path);
+        if let Some(slots) = record.m_slots {
+            state.m_index_area.delete_run(slots);
+        }
+        state.release_blocks(record.m_entry.blocks());
+        state.flush()
+    }
+
+    /**
+     * Moves the child with the given name of the given directory into the
+     * given new directory with the given new name.
+     *
+     * Since the entries store the full paths, the entries of all the
+     * descendants are rewritten too, moving them into new slots when the
+     * new path needs more continuations. When the index area is full the
+     * rename stops at the first entry which can't be moved
+     */
+    pub fn rename(&self,
+                  dir_id: FsNodeId,
+                  name: &str,
+                  new_dir_id: FsNodeId,
+                  new_name: &str)
+                  -> FsResult<()> {
+        let new_name = normalize_name(new_name);
+        if !is_valid_name(&new_name) {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let mut state = self.m_state.lock();
+        let old_path = state.child_path(dir_id, &normalize_name(name))?;
+        let new_path = state.child_path(new_dir_id, &new_name)?;
+
+        let node_id =
+            *state.m_paths.get(&old_path).ok_or(OsErrorClass::ReferenceNotFound)?;
+        if state.m_paths.contains_key(&new_path) {
+            return Err(OsErrorClass::IdentifierNotAvailable);
+        }
+        state.materialize_dir(new_dir_id)?;
+
+        /* the node is moved with all its descendants */
+        let descendants_prefix = format!("{}/", old_path);
+        let mut moved_nodes = vec![(old_path.clone(), node_id)];
+        moved_nodes.extend(state.m_paths
+                                .range(descendants_prefix.clone()..)
+                                .take_while(|(path, _)| {
+                                    path.starts_with(&descendants_prefix)
+                                })
+                                .map(|(path, &child_id)| (path.clone(), child_id)));
+
+        let mut rename_result = Ok(());
+        for (path, moved_id) in moved_nodes {
+            let moved_path = format!("{}{}", new_path, &path[old_path.len()..]);
+
+            rename_result = state.rename_record(moved_id, &path, moved_path);
+            if rename_result.is_err() {
+                break;
+            }
+        }
+
+        state.flush()?;
+        rename_result
+    }
+
+    /**
+     * Returns the metadata of the given node.
+     *
+     * The owner and the protection grants are not stored by the layout,
+     * so the defaults are returned
+     */
+    pub fn info(&self, node_id: FsNodeId) -> FsResult<RawObjInfo> {
+        let state = self.m_state.lock();
+        let index_entry = &state.record(node_id)?.m_entry;
+
+        /* the root directory reports the data blocks of the whole volume */
+        let (data_blocks_used, data_bytes_used) = if node_id == C_ROOT_NODE_ID {
+            (state.m_used_blocks.iter().map(|(start, end)| end - start).sum::<u64>(), 0)
+        } else {
+            (index_entry.blocks().end - index_entry.blocks().start, index_entry.length())
+        };
+        let name = index_entry.name().rsplit('/').next().filter(|name| !name.is_empty());
+        let time_stamp = time_stamp_to_instant(index_entry.time_stamp());
+
+        Ok(RawObjInfo::new(index_entry.obj_type(),
+                           0,
+                           self.m_device_id,
+                           node_id,
+                           name,
+                           1,
+                           state.m_super_block.block_size() as usize,
+                           data_blocks_used as usize,
+                           data_bytes_used as usize,
+                           0,
+                           0,
+                           default_prot_grants(index_entry.obj_type()),
+                           time_stamp,
+                           time_stamp,
+                           time_stamp,
+                           time_stamp,
+                           time_stamp))
+    }
+
+    /**
+     * Writes back the pending modifications and the device cache
+     */
+    pub fn sync(&self) -> FsResult<()> {
+        let mut state = self.m_state.lock();
+
+        state.flush()?;
+        state.m_block_device.sync()
+    }
+}
+
+impl SfsVolume /* Getters */ {
+    /**
+     * Returns the `SfsVariant` of the layout
+     */
+    pub fn variant(&self) -> SfsVariant {
+        self.m_variant
+    }
+
+    /**
+     * Returns whether the volume refuses any modification
+     */
+    pub fn is_read_only(&self) -> bool {
+        self.m_is_read_only
+    }
+
+    /**
+     * Returns the size in bytes of the data of the given node
+     */
+    pub fn size(&self, node_id: FsNodeId) -> usize {
+        self.m_state
+            .lock()
+            .file_entry(node_id)
+            .map_or(0, |index_entry| index_entry.length() as usize)
+    }
+
+    /**
+     * Returns the name stored into the volume id
+     */
+    pub fn volume_name(&self) -> String {
+        self.m_state.lock().m_index_area.volume_name()
+    }
+}
+
+/**
+ * Mutable state of a `SfsVolume`, protected by its lock
+ */
+struct SfsState {
+    m_block_device: Arc<dyn TBlockDevice>,
+    m_super_block: SuperBlock,
+    m_is_super_block_dirty: bool,
+    m_index_area: IndexArea,
+    m_records: BTreeMap<FsNodeId, SfsRecord>,
+    m_paths: BTreeMap<String, FsNodeId>,
+    m_used_blocks: BTreeMap<u64, u64>,
+    m_next_node_id: FsNodeId
+}
+
+impl SfsState /* Methods */ {
+    /**
+     * Accounts the given `IndexRecord`s, returning whether their data
+     * extents are consistent
+     */
+    fn load_records(&mut self, index_records: Vec<IndexRecord>) -> bool {
+        let data_area = self.m_super_block.data_area();
+        let block_size = self.m_super_block.block_size();
+
+        let mut is_consistent = true;
+        for index_record in index_records {
+            let (slots, mut index_entry) = match index_record {
+                IndexRecord::Node(slots, index_entry) => (slots, index_entry),
+                IndexRecord::Unusable(blocks) => {
+                    is_consistent &= self.reserve_blocks(blocks);
+                    continue;
+                }
+            };
+
+            let path = normalize_name(index_entry.name());
+            if !path.split('/').all(is_valid_name) {
+                dbg_println!(DbgLevel::Warn, "Sfs: hiding invalid path '{}'", path);
+                continue;
+            } else if self.m_paths.contains_key(&path) {
+                dbg_println!(DbgLevel::Warn, "Sfs: hiding duplicated path '{}'", path);
+                continue;
+            }
+
+            let blocks = index_entry.blocks();
+            let is_extent_valid =
+                (blocks.is_empty()
+                 || data_area.start <= blocks.start && blocks.end <= data_area.end)
+                && index_entry.length() <= (blocks.end - blocks.start) * block_size;
+            if index_entry.obj_type() == ObjType::File
+               && !(is_extent_valid && self.reserve_blocks(blocks))
+            {
+                dbg_println!(DbgLevel::Warn, "Sfs: invalid data extent of '{}'", path);
+                is_consistent = false;
+            }
+
+            index_entry.set_name(path);
+            self.insert_record(index_entry, Some(slots));
+        }
+
+        /* the parents not listed into the index area are implicit directories */
+        let paths: Vec<String> = self.m_paths.keys().cloned().collect();
+        for path in paths {
+            self.insert_implicit_parents(&path);
+        }
+        is_consistent
+    }
+
+    /**
+     * Inserts a new `SfsRecord` with the given `IndexEntry`, stored into
+     * the given slots, and returns its `FsNodeId`
+     */
+    fn insert_record(&mut self,
+                     index_entry: IndexEntry,
+                     slots: Option<Range<usize>>)
+                     -> FsNodeId {
+        let node_id = self.m_next_node_id;
+        self.m_next_node_id += 1;
+
+        self.m_paths.insert(index_entry.name().to_string(), node_id);
+        self.m_records.insert(node_id,
+                              SfsRecord { m_entry: index_entry,
+                                          m_slots: slots });
+        node_id
+    }
+
+    /**
+     * Inserts the implicit directories for the missing parents of the
+     * given path.
+     *
+     * The path is hidden when one of its parents is a file
+     */
+    fn insert_implicit_parents(&mut self, path: &str) {
+        let parent_paths: Vec<&str> =
+            path.match_indices('/')
+                .map(|(separator_index, _)| &path[..separator_index])
+                .collect();
+
+        let parent_types: Vec<_> =
+            parent_paths.iter()
+                        .map(|parent_path| self.obj_type_at(parent_path))
+                        .collect();
+        if parent_types.contains(&Some(ObjType::File)) {
+            dbg_println!(DbgLevel::Warn, "Sfs: hiding '{}' under a file", path);
+            if let Some(node_id) = self.m_paths.remove(path) {
+                self.m_records.remove(&node_id);
+            }
+            return;
+        }
+
+        for (parent_path, parent_type) in parent_paths.iter().zip(parent_types) {
+            if parent_type.is_none() {
+                let time_stamp = self.m_super_block.time_stamp();
+                let index_entry =
+                    IndexEntry::new(ObjType::Dir, parent_path.to_string(), time_stamp);
+
+                self.insert_record(index_entry, None);
+            }
+        }
+    }
+
+    /**
+     * Writes into the index area the given directory and its parents
+     * which are implicit
+     */
+    fn materialize_dir(&mut self, dir_id: FsNodeId) -> FsResult<()> {
+        let mut implicit_dir_ids = Vec::new();
+
+        let mut dir_path = self.record(dir_id)?.m_entry.name().to_string();
+        while !dir_path.is_empty() {
+            let ancestor_id = self.m_paths[&dir_path];
+            if self.m_records[&ancestor_id].m_slots.is_none() {
+                implicit_dir_ids.push(ancestor_id);
+            }
+
+            let parent_path_len = dir_path.rfind('/').unwrap_or(0);
+            dir_path.truncate(parent_path_len);
+        }
+
+        /* the outer directories are written first */
+        for implicit_dir_id in implicit_dir_ids.into_iter().rev() {
+            let index_entry = self.m_records[&implicit_dir_id].m_entry.clone();
+            let slots = self.place_entry(&index_entry)?;
+
+            self.record_mut(implicit_dir_id)?.m_slots = Some(slots);
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes the given `IndexEntry` into a new run of slots, growing the
+     * index area when there isn't a free one
+     */
+    fn place_entry(&mut self, index_entry: &IndexEntry) -> FsResult<Range<usize>> {
+        let slots_count =
+            self.m_index_area
+                .slots_needed(index_entry.obj_type(), index_entry.name().len())
+                .ok_or(OsErrorClass::LimitOverflow)?;
+
+        let slots = match self.m_index_area.find_free_run(slots_count) {
+            Some(slots) => slots,
+            None => {
+                self.grow_index_area(slots_count)?;
+                self.m_index_area
+                    .find_free_run(slots_count)
+                    .ok_or(OsErrorClass::LimitReached)?
+            }
+        };
+        self.m_index_area.write_entry(slots.clone(), index_entry);
+        Ok(slots)
+    }
+
+    /**
+     * Grows the index area towards the data area of at least the given
+     * amount of slots
+     */
+    fn grow_index_area(&mut self, slots_count: usize) -> FsResult<()> {
+        let block_size = self.m_super_block.block_size();
+
+        /* the index area grows by whole blocks when possible */
+        let slots_per_block = block_size as usize / C_INDEX_ENTRY_SIZE;
+        let grow_slots_count = slots_count.max(slots_per_block);
+        let grow_size = (grow_slots_count * C_INDEX_ENTRY_SIZE) as u64;
+
+        let index_offset = self.m_super_block.index_offset();
+        if index_offset < grow_size
+           || index_offset - grow_size < self.blocks_end() * block_size
+        {
+            return Err(OsErrorClass::LimitReached);
+        }
+
+        self.m_index_area.grow(grow_slots_count);
+        self.m_super_block.set_index_size(self.m_index_area.size());
+        self.m_is_super_block_dirty = true;
+
+        for record in self.m_records.values_mut() {
+            if let Some(slots) = &mut record.m_slots {
+                *slots = slots.start + grow_slots_count..slots.end + grow_slots_count;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Moves the node with the given `FsNodeId` from the given path to the
+     * given new path, rewriting its entry
+     */
+    fn rename_record(&mut self,
+                     node_id: FsNodeId,
+                     path: &str,
+                     new_path: String)
+                     -> FsResult<()> {
+        let mut index_entry = self.record(node_id)?.m_entry.clone();
+        index_entry.set_name(new_path.clone());
+
+        if let Some(slots) = self.record(node_id)?.m_slots.clone() {
+            let slots_count = self.m_index_area
+                                  .slots_needed(index_entry.obj_type(), new_path.len())
+                                  .ok_or(OsErrorClass::LimitOverflow)?;
+
+            let new_slots = if slots_count <= slots.len() {
+                let new_slots = slots.start..slots.start + slots_count;
+
+                self.m_index_area.write_entry(new_slots.clone(), &index_entry);
+                self.m_index_area.clear_run(new_slots.end..slots.end);
+                new_slots
+            } else {
+                let new_slots = self.place_entry(&index_entry)?;
+
+                /* the old slots are shifted when the index area grows */
+                if let Some(old_slots) = self.record(node_id)?.m_slots.clone() {
+                    self.m_index_area.delete_run(old_slots);
+                }
+                new_slots
+            };
+            self.record_mut(node_id)?.m_slots = Some(new_slots);
+        }
+
+        self.record_mut(node_id)?.m_entry = index_entry;
+        self.m_paths.remove(path);
+        self.m_paths.insert(new_path, node_id);
+        Ok(())
+    }
+
+    /**
+     * Moves or resizes the data extent of the given file to the given
+     * amount of blocks, copying the data when it moves
+     */
+    fn resize_extent(&mut self, node_id: FsNodeId, blocks_count: u64) -> FsResult<()> {
+        let index_entry = self.file_entry(node_id)?;
+        let blocks = index_entry.blocks();
+        let length = index_entry.length();
+
+        let new_blocks = if blocks_count <= blocks.end - blocks.start {
+            /* the unneeded tail is released */
+            let new_blocks = blocks.start..blocks.start + blocks_count;
+            self.release_blocks(blocks);
+            self.reserve_blocks(new_blocks.clone());
+            new_blocks
+        } else if !blocks.is_empty()
+                  && blocks.start + blocks_count <= self.m_super_block.free_blocks_end()
+                  && self.is_blocks_free(blocks.end..blocks.start + blocks_count)
+        {
+            let new_blocks = blocks.start..blocks.start + blocks_count;
+            self.m_used_blocks.insert(new_blocks.start, new_blocks.end);
+            self.extend_data_area(new_blocks.end);
+            new_blocks
+        } else {
+            let new_blocks = self.allocate_blocks(blocks_count)?;
+
+            /* only the valid data is copied into the new extent */
+            self.copy_data(self.block_offset(blocks.start),
+                           self.block_offset(new_blocks.start),
+                           length)?;
+            self.release_blocks(blocks);
+            new_blocks
+        };
+
+        self.record_mut(node_id)?.m_entry.set_blocks(new_blocks);
+        Ok(())
+    }
+
+    /**
+     * Reserves the first free range of the given amount of blocks, growing
+     * the data area towards the index area when needed
+     */
+    fn allocate_blocks(&mut self, blocks_count: u64) -> FsResult<Range<u64>> {
+        let free_blocks_end = self.m_super_block.free_blocks_end();
+
+        let mut free_start = self.m_super_block.data_area().start;
+        for (&used_start, &used_end) in self.m_used_blocks.iter() {
+            if used_start >= free_start + blocks_count {
+                break;
+            }
+            free_start = free_start.max(used_end);
+        }
+
+        let new_blocks = free_start..free_start + blocks_count;
+        if new_blocks.end > free_blocks_end {
+            return Err(OsErrorClass::LimitReached);
+        }
+
+        self.m_used_blocks.insert(new_blocks.start, new_blocks.end);
+        self.extend_data_area(new_blocks.end);
+        Ok(new_blocks)
+    }
+
+    /**
+     * Marks the given range of blocks as used, returning whether it was
+     * free
+     */
+    fn reserve_blocks(&mut self, blocks: Range<u64>) -> bool {
+        if blocks.is_empty() {
+            true
+        } else if self.is_blocks_free(blocks.clone()) {
+            self.m_used_blocks.insert(blocks.start, blocks.end);
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Marks the given used range of blocks as free
+     */
+    fn release_blocks(&mut self, blocks: Range<u64>) {
+        if !blocks.is_empty() {
+            self.m_used_blocks.remove(&blocks.start);
+        }
+    }
+
+    /**
+     * Grows the data area to include the blocks before the given one
+     */
+    fn extend_data_area(&mut self, blocks_end: u64) {
+        let data_area = self.m_super_block.data_area();
+
+        if blocks_end > data_area.end {
+            self.m_super_block.set_data_blocks(blocks_end - data_area.start);
+            self.m_is_super_block_dirty = true;
+        }
+    }
+
+    /**
+     * Updates the length and the time stamp of the given file and writes
+     * back its entry
+     */
+    fn update_file_entry(&mut self, node_id: FsNodeId, length: u64) -> FsResult<()> {
+        let record = self.record_mut(node_id)?;
+
+        record.m_entry.set_length(length);
+        record.m_entry.set_time_stamp(instant_to_time_stamp(TimeManager::now_instant()));
+        if let Some(slots) = record.m_slots.clone() {
+            let index_entry = record.m_entry.clone();
+            self.m_index_area.write_entry(slots, &index_entry);
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes zeroes into the given amount of bytes from the given offset
+     */
+    fn zero_fill(&self, offset: u64, len: u64) -> FsResult<()> {
+        let zero_buffer = vec![0; self.m_super_block.block_size() as usize];
+
+        let mut done_bytes = 0;
+        while done_bytes < len {
+            let chunk_bytes = (len - done_bytes).min(zero_buffer.len() as u64);
+
+            self.m_block_device
+                .write_bytes(offset + done_bytes, &zero_buffer[..chunk_bytes as usize])?;
+            done_bytes += chunk_bytes;
+        }
+        Ok(())
+    }
+
+    /**
+     * Copies the given amount of bytes between the given non overlapping
+     * offsets
+     */
+    fn copy_data(&self, from_offset: u64, to_offset: u64, len: u64) -> FsResult<()> {
+        let mut copy_buffer = vec![0; self.m_super_block.block_size() as usize];
+
+        let mut done_bytes = 0;
+        while done_bytes < len {
+            let chunk_bytes = (len - done_bytes).min(copy_buffer.len() as u64);
+            let chunk_buffer = &mut copy_buffer[..chunk_bytes as usize];
+
+            self.m_block_device.read_bytes(from_offset + done_bytes, chunk_buffer)?;
+            self.m_block_device.write_bytes(to_offset + done_bytes, chunk_buffer)?;
+            done_bytes += chunk_bytes;
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes back the modified slots of the index area and then the
+     * `SuperBlock`, so it never describes an index area not yet written
+     */
+    fn flush(&mut self) -> FsResult<()> {
+        let index_offset = self.m_super_block.index_offset();
+        self.m_index_area.flush(self.m_block_device.as_ref(), index_offset)?;
+
+        if self.m_is_super_block_dirty {
+            self.m_super_block.write(self.m_block_device.as_ref())?;
+            self.m_is_super_block_dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl SfsState /* Getters */ {
+    /**
+     * Returns the `SfsRecord` of the given node
+     */
+    fn record(&self, node_id: FsNodeId) -> FsResult<&SfsRecord> {
+        self.m_records.get(&node_id).ok_or(OsErrorClass::ReferenceNotFound)
+    }
+
+    /**
+     * Returns the mutable `SfsRecord` of the given node
+     */
+    fn record_mut(&mut self, node_id: FsNodeId) -> FsResult<&mut SfsRecord> {
+        self.m_records.get_mut(&node_id).ok_or(OsErrorClass::ReferenceNotFound)
+    }
+
+    /**
+     * Returns the `IndexEntry` of the given file
+     */
+    fn file_entry(&self, node_id: FsNodeId) -> FsResult<&IndexEntry> {
+        let index_entry = &self.record(node_id)?.m_entry;
+
+        if index_entry.obj_type() == ObjType::File {
+            Ok(index_entry)
+        } else {
+            Err(OsErrorClass::TypesNotMatch)
+        }
+    }
+
+    /**
+     * Returns the full path of the child with the given name of the given
+     * directory
+     */
+    fn child_path(&self, dir_id: FsNodeId, name: &str) -> FsResult<String> {
+        let index_entry = &self.record(dir_id)?.m_entry;
+
+        if index_entry.obj_type() != ObjType::Dir {
+            Err(OsErrorClass::TypesNotMatch)
+        } else if dir_id == C_ROOT_NODE_ID {
+            Ok(name.to_string())
+        } else {
+            Ok(format!("{}/{}", index_entry.name(), name))
+        }
+    }
+
+    /**
+     * Returns the `ObjType` of the node at the given path
+     */
+    fn obj_type_at(&self, path: &str) -> Option<ObjType> {
+        self.m_paths.get(path).map(|node_id| self.m_records[node_id].m_entry.obj_type())
+    }
+
+    /**
+     * Returns whether the directory at the given path has children
+     */
+    fn has_children(&self, dir_path: &str) -> bool {
+        let children_prefix = format!("{}/", dir_path);
+
+        self.m_paths
+            .range(children_prefix.clone()..)
+            .next()
+            .map_or(false, |(path, _)| path.starts_with(&children_prefix))
+    }
+
+    /**
+     * Returns whether the given range of blocks is not used
+     */
+    fn is_blocks_free(&self, blocks: Range<u64>) -> bool {
+        let previous_used_end = self.m_used_blocks
+                                    .range(..blocks.end)
+                                    .next_back()
+                                    .map(|(_, &used_end)| used_end);
+
+        previous_used_end.map_or(true, |used_end| used_end <= blocks.start)
+    }
+
+    /**
+     * Returns the block after the last one used by the data area or by the
+     * unusable entries
+     */
+    fn blocks_end(&self) -> u64 {
+        let used_end = self.m_used_blocks.values().copied().max().unwrap_or(0);
+        used_end.max(self.m_super_block.data_area().end)
+    }
+
+    /**
+     * Returns the amount of blocks needed to store the given amount of
+     * bytes
+     */
+    fn blocks_for(&self, size: u64) -> u64 {
+        let block_size = self.m_super_block.block_size();
+        (size + block_size - 1) / block_size
+    }
+
+    /**
+     * Returns the byte offset of the given block
+     */
+    fn block_offset(&self, block: u64) -> u64 {
+        block * self.m_super_block.block_size()
+    }
+}
+
+/**
+ * Directory or file known by a `SfsVolume`
+ */
+struct SfsRecord {
+    m_entry: IndexEntry,
+    m_slots: Option<Range<usize>>
+}
@@ -1,272 +1,266 @@
-use alloc::sync::Arc;
-use alloc::vec::Vec;
-use core::cmp;
-use core::mem;
-use core::lazy::Lazy;
-use core::ops::Range;
+/*! Loaded nodes table and path resolution */
 
-use api_data::path::{PathComponent, PathExistsState};
-use sync::SpinRwLock;
+use alloc::{
+    collections::{
+        BTreeMap,
+        VecDeque
+    },
+    sync::{
+        Arc,
+        Weak
+    },
+    vec::Vec
+};
 
-use sync::rw_lock::data_guard::RwLockDataReadGuard;
-use sync::rw_lock::spin_rw_lock::RawSpinRwLock;
+use api_data::{
+    error::class::OsErrorClass,
+    object::types::ObjType,
+    path::PathComponent
+};
+use sync::SpinMutex;
 
-use crate::filesystem::{Filesystem, FsError};
-use crate::filesystem::r#virtual::{INode, DirectoryNode};
-use crate::filesystem::node_structs::{NodeTable, NodeTreeMap, PartialNodeResult};
+use crate::filesystem::{
+    mount::{
+        Mount,
+        MountId,
+        MountTable
+    },
+    node::{
+        FsNodeId,
+        TFsNode,
+        VfsNode
+    },
+    node_structs::{
+        NodeCache,
+        NodeCacheStats,
+        C_DEFAULT_CACHE_CAPACITY
+    },
+    parse_path,
+    FsResult
+};
 
-type INodeLockMap<'a> = SpinRwLock<HashMap<&'a [PathComponent], dyn INode>>;
+/* maximum amount of links followed while resolving a single path */
+const C_MAX_FOLLOWED_LINKS: usize = 16;
 
-pub type INodeResult = Result<Arc<dyn INode>, ()>;
+/**
+ * `VfsNode` resolved from a path, with the canonical path which reaches it
+ * without any `PathComponent::SelfLink`, `PathComponent::ParentLink` or
+ * link
+ */
+pub type ResolvedNode = (Arc<VfsNode>, Vec<PathComponent>);
 
-enum PartialSearchResult<T> {
-    None,
-    Found(T),
-    NewBestScore(usize, T),
+/**
+ * Table of the `VfsNode`s in use, which ensures that each `TFsNode` is
+ * wrapped by one `VfsNode` at time.
+ *
+ * The table doesn't keep the nodes alive, each `VfsNode` removes itself
+ * when its last reference is dropped, but the `NodeCache` keeps the most
+ * recently resolved ones
+ */
+pub struct LoadedNodes {
+    m_nodes: SpinMutex<BTreeMap<(MountId, FsNodeId), Weak<VfsNode>>>,
+    m_cache: SpinMutex<NodeCache>
 }
 
-pub static LOADED_NODES: Lazy<LoadedNodes> = Lazy::new(|| {
-    LoadedNodes {
-        _opened_nodes: SpinRwLock::new(NodeTable::new()).unwrap(),
-        _cached_nodes: INodeLockMap::new(HashMap::new()).unwrap(),
-        _filesystem_roots: SpinRwLock::new(FsRoots::new()).unwrap(),
+impl LoadedNodes /* Constructors */ {
+    /**
+     * Constructs an empty `LoadedNodes`
+     */
+    pub const fn new() -> Self {
+        Self { m_nodes: SpinMutex::const_new(BTreeMap::new()),
+               m_cache: SpinMutex::const_new(NodeCache::new(C_DEFAULT_CACHE_CAPACITY)) }
     }
-});
-
-pub struct LoadedNodes<'a> {
-    _opened_nodes: SpinRwLock<NodeTable<'a>>,
-    _cached_nodes: INodeLockMap<'a>,
-    _filesystem_roots: SpinRwLock<FsRoots<'a>>,
 }
 
-struct FsRoots<'a> {
-    _filesystem_mountpoints: HashMap<&'a [PathComponent], dyn Filesystem>,
-}
-
-/**
- * Returns the minimum common ancestor between a Path and an INode, and how many Path components
- * are necessary to go from the common ancestor to the path.
- * // Find and fix this formatting:
- *      param max_inverse_depth: optimization, after the given depth, consider the search failed
- *                               and pass to the next. If more than the path length, defaults to
- *                               it.
- */
-fn get_minimum_common_ancestor(path: &[PathComponent], mut node: Arc<&dyn INode>, max_inverse_depth: usize) -> Result<(&Arc<&dyn INode>, usize), ()> {
-    let mut minimum_ancestor = None;
-    let mut minimum_inverse_depth = cmp::min(max_inverse_depth, path.len()); // Start from the max
-
-    /*
-     * Iterate the nodes backwards to the system root.
+impl LoadedNodes /* Methods */ {
+    /**
+     * Resolves the given absolute path walking the directories from the
+     * nearest ancestor into the `NodeCache`, or from the root `Mount`.
+     *
+     * The `PathComponent::SelfLink`s are skipped, the
+     * `PathComponent::ParentLink`s return to the previous directory (never
+     * over the root) and the mountpoints are crossed into the root of the
+     * mounted `TFilesystem`. The links are followed, the last one only
+     * when `follow_last_link` is `true`, up to `C_MAX_FOLLOWED_LINKS`
      */
-    loop {
-        // Try to match the current node with the path component nearest to the target path.
-        for path_inverse_depth in (0..minimum_inverse_depth).rev() {
-            // They match, meaning the current node is part of out path's ancestry
-            if *node.get_name() == path[path_inverse_depth] {
-                // If we already have an ancestor, then it IS nearer to the path's target, since
-                // we iterate backwards.
-                if minimum_ancestor == None {
-                    minimum_ancestor = Some(&node);
-                    minimum_inverse_depth = path_inverse_depth;
-                }
-
-                // The node won't change inside this for loop, while keeping to iterate will
-                // reset our ancestor, so break and iterate to the next node.
-                break;
-            }
-            // Otherwise, reset the "false" ancestor, since it's own ancestors aren't in part of
-            // our path's ancestry.
-            // This should only be executed before we match a path with the current node,
-            // otherwise we might reset a "true" ancestor.
-            else {
-                /*
-                 * This will remove:
-                 * - If our path is a super-path that includes the node, the path components
-                 *   after the node.
-                 * - If the path and the node have nothing in common but a few middle path
-                 *   components, (eg /bar/FOO/file and /faz/fuz/FOO/something), this removes the
-                 *   false matches.
-                 */
-                minimum_ancestor = None;
-            }
+    pub fn find_node(&self,
+                     mount_table: &MountTable,
+                     path: &[PathComponent],
+                     follow_last_link: bool)
+                     -> FsResult<ResolvedNode> {
+        if path.first() != Some(&PathComponent::Root) {
+            return Err(OsErrorClass::InvalidArgument);
         }
+        let root_node = mount_table.root_mount()
+                                   .ok_or(OsErrorClass::ReferenceNotFound)?
+                                   .root()
+                                   .clone();
 
-        match node.get_parent() {
-            Some(n) => node = n, // Iterate to the next ancestor.
-            None => {            // Node is the system root, return.
-                if let Some(result) = minimum_ancestor {
-                    Ok((result, minimum_inverse_depth))
-                }
-                // Something went wrong, since the path and the inode should _at least_ share
-                // the system root.
-                else {
-                    Err(())
-                }
-            }
-        }
-    }
-}
+        let (cache_generation, best_match) = {
+            let mut cache = self.m_cache.lock();
+            (cache.generation(), cache.get_best_match(path))
+        };
 
-fn find_child_from_ancestor(mut ancestor: &Arc<&dyn INode>, path_to_child: &[PathComponent]) -> INodeResult {
-    // Walk to the node iteratively, to prevent stack overflows.
-    for depth_index in 0..path_to_child.len() - 1 {
-        match ancestor.get_type() {
-            NodeType::File => Err(()),
-            NodeType::Directory => {
-                // Search for the next node
-                let as_directory: &dyn DirectoryNode = ancestor.as_type().unwrap();
-                match as_directory.get_nodes() {
-                    Ok(nodes) => {
-                        match nodes.iter().find(|n| *n.get_name() == ancestor[depth_index]) {
-                            Some(n) => ancestor = n, // Our new ancestor.
-                            None => Err(())
+        /* the walked nodes of the last components of the canonical path, the
+         * last one is the current and none means the root
+         */
+        let (mut canonical_path, mut walked_nodes, walked_len) = match best_match {
+            Some((cached_node, prefix_len)) => {
+                (path[..prefix_len].to_vec(), vec![cached_node], prefix_len)
+            },
+            None => (vec![PathComponent::Root], Vec::new(), 1)
+        };
+
+        let mut followed_links = 0;
+        let mut pending_components: VecDeque<PathComponent> =
+            path.iter().skip(walked_len).cloned().collect();
+        while let Some(path_component) = pending_components.pop_front() {
+            let name = match path_component {
+                PathComponent::Root => {
+                    walked_nodes.clear();
+                    canonical_path.truncate(1);
+                    continue;
+                },
+                PathComponent::SelfLink => continue,
+                PathComponent::ParentLink => {
+                    if walked_nodes.pop().is_some() {
+                        canonical_path.pop();
+
+                        /* the walk started from a cached node, resolve its parent */
+                        if walked_nodes.is_empty() && canonical_path.len() > 1 {
+                            let (parent_node, _) =
+                                self.find_node(mount_table, &canonical_path, false)?;
+                            walked_nodes.push(parent_node);
                         }
                     }
-                    Err(_) => Err(())
+                    continue;
+                },
+                PathComponent::ObjectName(name) => name
+            };
+
+            let current_node = walked_nodes.last().unwrap_or(&root_node);
+            let child_fs_node = current_node.lookup_child(&name)?;
+
+            canonical_path.push(PathComponent::ObjectName(name));
+            let child_node = match mount_table.mount_at(&canonical_path) {
+                Some(mount) => mount.root().clone(),
+                None => {
+                    let mount = mount_table.mount_of(&canonical_path)
+                                           .ok_or(OsErrorClass::ReferenceNotFound)?;
+                    self.load(mount, child_fs_node)
                 }
-            }
-        }
-    }
-    // We finally found it!
-    if *ancestor.get_name() == path_to_child.last()? {
-        Ok(ancestor)
-    }
-    Err(())
-}
+            };
 
-/**
- * Assumes the node indicated by the path isn't in the map.
- * Never returns the node targeted by the path, only the a nearer ancestor it can find from the map
- * values.
- */
-fn search_ancestor_node(nodes: &impl Iterator<Item=Arc<&dyn INode>>, path: &[PathComponent], mut best_score: usize) -> PartialINodeSearchResult {
-    let mut nearest_node: Optional<&Arc<&dyn INode>> = None;
+            let must_follow = !pending_components.is_empty() || follow_last_link;
+            if child_node.obj_type() == ObjType::Link && must_follow {
+                if followed_links == C_MAX_FOLLOWED_LINKS {
+                    return Err(OsErrorClass::LimitReached);
+                }
+                followed_links += 1;
 
-    for node in nodes.iter() {
-        if let Ok((ancestor, reverse_depth)) = get_minimum_common_ancestor(path, node, best_score) {
-            if reverse_depth < best_score {
-                best_score = reverse_depth;
-                nearest_node = Some(ancestor);
-            }
-            // Can't get any better from searching, since we assume that we can't reach 0.
-            if best_score == 1 {
-                break;
+                /* the relative targets continue from the directory of the link */
+                let target_components = parse_path(&child_node.link_target()?)?;
+                canonical_path.pop();
+                for target_component in target_components.into_iter().rev() {
+                    pending_components.push_front(target_component);
+                }
+            } else {
+                walked_nodes.push(child_node);
             }
         }
-    }
-
-    match nearest_node {
-        Some(n) => PartialINodeSearchResult::NewBestScore(best_score, n),
-        None => PartialINodeSearchResult::None
-    }
-}
 
-impl LoadedNodes {
-    fn search_node_in_map(map: &impl NodeTreeMap, path: &[PathComponent], best_score: usize) -> PartialNodeResult {
-        map.get_best_match(path, best_score)
+        let node = walked_nodes.pop().unwrap_or(root_node);
+        self.cache_resolved(cache_generation,
+                            &canonical_path,
+                            &node,
+                            walked_nodes.last());
+        Ok((node, canonical_path))
     }
 
     /**
-     * Find an INode by path by checking the cache, opened nodes, roots and ultimately, walking the
-     * VFS tree.
-     * Only handles absolute paths.
-     * This is thread safe.
+     * Returns the `VfsNode` of the given `TFsNode` of the given `Mount`,
+     * wrapping it if not already loaded
      */
-    pub fn find_node(&self, path: &Vec<PathComponent>) -> INodeResult {
-        // Empty paths, non-absolute paths and maths with multiple root paths(?) are not supported.
-        if path.len() == 0 || path[0] != PathComponent::Root || path[1..].contains(&PathComponent::Root) {
-            Err(())
-        }
-
-        // Lock the filesystem roots table and then get the filesystem root inode since it must not
-        // change or be unmounted while we're searching.
-        let node_filesystem: Arc<&dyn INode> = {
-            let roots = self._filesystem_roots.read();
-            let filesystem = roots.get_filesystem_of(path)?;
-            // Ensure the node's filesystem is locked before dropping the Filesystem table guard.
-            let node = filesystem.get_root_node();
-            mem::drop(roots);
-            node
-        };
-
-
-        // The number of backwards iterations the current best match had.
-        let mut best_score = usize::MAX;
-        // The nearest node found to the target path. Starts at the node's filesystem implementation
-        // root.
-        let mut nearest_node = node_filesystem;
+    pub fn load(&self, mount: &Mount, fs_node: Arc<dyn TFsNode>) -> Arc<VfsNode> {
+        let node_key = (mount.id(), fs_node.id());
+        let mut nodes = self.m_nodes.lock();
 
-        // First, check if the path is the root of it's filesystem.
-        if *path.last().unwrap() == PathComponent::Root {
-            Ok(nearest_node)
+        if let Some(loaded_node) = nodes.get(&node_key).and_then(Weak::upgrade) {
+            return loaded_node;
         }
 
-        // Second, search the opened nodes
-        {
-            let opened_nodes  = self._opened_nodes.read();
-            match Self::search_node_in_map(&opened_nodes, path, best_score) {
-                PartialINodeSearchResult::None => {}
-                PartialINodeSearchResult::Found(node) => Ok(node.clone()),
-                PartialINodeSearchResult::NewBestScore(new_best, new_nearest) => {
-                    best_score = new_best;
-                    nearest_node = new_nearest.clone();
-                }
-            }
-        }
+        let vfs_node = Arc::new(VfsNode::new(fs_node,
+                                             mount.id(),
+                                             mount.filesystem().is_read_only()));
+        nodes.insert(node_key, Arc::downgrade(&vfs_node));
+        vfs_node
+    }
 
-        // Third, search the cache
-        {
-            let cached_nodes = self._cached_nodes.read();
-            match Self::search_node_in_map(&cached_nodes, path, best_score) {
-                PartialINodeSearchResult::None => {}
-                PartialINodeSearchResult::Found(node) => Ok(node.clone()),
-                PartialINodeSearchResult::NewBestScore(new_best, new_nearest) => {
-                    best_score = new_best;
-                    nearest_node = new_nearest.clone();
-                }
-            }
-        }
+    /**
+     * Removes from the `NodeCache` the given canonical path and all its
+     * descendants.
+     *
+     * Called after any modification which changes the node reached by a
+     * path
+     */
+    pub fn invalidate_cached(&self, path: &[PathComponent]) {
+        self.m_cache.lock().invalidate(path);
+    }
 
-        // Fourth, walk from the node's filesystem root.
-        let result = find_child_from_ancestor(&nearest_node, path)?;
+    /**
+     * Removes the entry of a dropped `VfsNode`, unless it was already
+     * replaced by a new one
+     */
+    pub fn forget(&self, mount_id: MountId, node_id: FsNodeId) {
+        let mut nodes = self.m_nodes.lock();
 
-        // Add the parent of the inode to the cache.
-        // TODO: when supported, set a maximum timer to keep things speedy
-        {
-            let cached_nodes = self._cached_nodes.write();
-            cached_nodes.insert(path, result.get_parent()?);
+        let is_dead = nodes.get(&(mount_id, node_id))
+                           .map_or(false, |loaded_node| loaded_node.strong_count() == 0);
+        if is_dead {
+            nodes.remove(&(mount_id, node_id));
         }
-        INodeResult::Ok(result)
     }
 }
 
-impl FsRoots {
-    pub fn new() -> Self {
-        Self {
-            _filesystem_mountpoints: HashMap::new()
-        }
+impl LoadedNodes /* Getters */ {
+    /**
+     * Returns a snapshot of the `NodeCacheStats`
+     */
+    pub fn cache_stats(&self) -> NodeCacheStats {
+        self.m_cache.lock().stats()
     }
+}
 
-    pub fn get_vfs_tree_root(&self) -> Result<Arc<&dyn INode>, ()> {
-        Ok(self.get_filesystem_of(&[PathComponent::Root])?.get_root_node())
+impl LoadedNodes /* Setters */ {
+    /**
+     * Sets the maximum amount of entries of the `NodeCache`
+     */
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        self.m_cache.lock().set_capacity(capacity);
     }
+}
 
-    pub fn as_path_inode_map(&self) -> impl Iterator<Item=Arc<&dyn INode>> {
-        self._filesystem_mountpoints.iter().map(|(k, v)| {
-            (k, v.get_root_node())
-        })
-    }
+impl LoadedNodes /* Privates */ {
+    /**
+     * Caches the resolved `VfsNode` and its parent directory. The links
+     * are never cached, since their paths reach their targets when followed
+     */
+    fn cache_resolved(&self,
+                      cache_generation: u64,
+                      canonical_path: &[PathComponent],
+                      node: &Arc<VfsNode>,
+                      parent_node: Option<&Arc<VfsNode>>) {
+        if canonical_path.len() < 2 {
+            return;
+        }
 
-    pub fn get_filesystem_of(self, path: &[PathComponent]) -> Result<Arc<dyn Filesystem>, ()> {
-        match self._filesystem_mountpoints.get(path) {
-            Some(fs) => Ok(fs),
-            None => {
-                let nodes = self._filesystem_mountpoints.iter().values().map(|fs| fs.get_root_node());
-                match search_ancestor_node(nodes, path, usize::MAX) {
-                    PartialSearchResult::Found(n) => Ok(n.get_filesystem()),
-                    _ => Err(())
-                }
-            }
+        let mut cache = self.m_cache.lock();
+        if let Some(parent_node) = parent_node {
+            let parent_path = canonical_path[..canonical_path.len() - 1].to_vec();
+            cache.insert(cache_generation, parent_path, parent_node.clone());
+        }
+        if node.obj_type() != ObjType::Link {
+            cache.insert(cache_generation, canonical_path.to_vec(), node.clone());
         }
     }
-}
\ No newline at end of file
+}
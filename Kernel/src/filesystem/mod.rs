@@ -1,48 +1,442 @@
-pub mod implementation;
-pub mod r#virtual;
-mod loaded_nodes;
-mod node_structs;
+/*! Virtual filesystem */
 
-use alloc::rc::Rc;
-use alloc::sync::Arc;
+use alloc::{
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
 
+use api_data::{
+    error::class::OsErrorClass,
+    limit::{
+        VFS_NAME_LEN_MAX,
+        VFS_PATH_LEN_MAX
+    },
+    object::types::ObjType,
+    path::PathComponent
+};
 use sync::SpinRwLock;
 
-pub use crate::filesystem::r#virtual::INode;
+use crate::{
+    boot_info::BootInfo,
+    dbg_print::DbgLevel,
+    dev::block::TBlockDevice,
+    filesystem::{
+        implementation::{
+            mxfs::MxfsProvider,
+            sfs::SfsProvider
+        },
+        loaded_nodes::LoadedNodes,
+        mount::MountTable,
+        node::{
+            TFsNode,
+            VfsNode
+        },
+        node_structs::NodeCacheStats
+    }
+};
 
-pub type FsResult<T> = Result<T, FsError>;
+pub mod implementation;
+pub mod loaded_nodes;
+pub mod mount;
+pub mod node;
+pub mod node_structs;
 
-pub enum FsError {
-    NotSupported,
-    InvalidLink
-}
+static mut SM_VFS: Vfs = Vfs { m_mount_table: SpinRwLock::const_new(MountTable::new()),
+                               m_loaded_nodes: LoadedNodes::new() };
+
+/* command line option which selects the capacity of the <NodeCache> */
+const C_CACHE_CAPACITY_ARG: &str = "-vfs-cache-size";
+
+/* <TFilesystemProvider>s probed by <Vfs::mount_device()>, in order */
+const C_FS_PROVIDERS: [&dyn TFilesystemProvider; 2] = [&MxfsProvider, &SfsProvider];
 
 /**
- * All supported filesystems.
+ * Result of the filesystem operations
  */
+pub type FsResult<T> = Result<T, OsErrorClass>;
+
+/**
+ * Lists the on-disk filesystems which the kernel can mount
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
 pub enum Filesystems {
-    // No known filesystem.
-    None,
-    // The Simple FileSystem, per it's original 2006 specification.
-    SFS,
-    // The Simple FileSystem Extended by Forever Young Software in 2018.
-    SFSE,
-    // An original, feature-rich, inode-based filesystem.
-    MXFS
+    /**
+     * The Simple FileSystem, per its original 2006 specification
+     */
+    Sfs,
+
+    /**
+     * The Simple FileSystem extended by Forever Young Software in 2018
+     */
+    Sfse,
+
+    /**
+     * The MeetiX FileSystem, the native one
+     */
+    Mxfs
+}
+
+/**
+ * Interface implemented by the filesystem drivers which can be mounted
+ * into the `Vfs`
+ */
+pub trait TFilesystem: Send + Sync {
+    /**
+     * Returns the name of the filesystem driver
+     */
+    fn name(&self) -> &str;
+
+    /**
+     * Returns the root directory `TFsNode`
+     */
+    fn root(&self) -> Arc<dyn TFsNode>;
+
+    /**
+     * Returns whether the filesystem refuses any modification
+     */
+    fn is_read_only(&self) -> bool;
+
+    /**
+     * Writes back the pending modifications, called before the unmount
+     */
+    fn sync(&self) -> FsResult<()> {
+        Ok(())
+    }
 }
 
-trait FilesystemProvider {
-    fn as_enum(&self) -> Filesystems;
+/**
+ * Interface implemented by the on-disk filesystem drivers, which
+ * recognize and open the `TFilesystem`s stored into the `TBlockDevice`s
+ */
+pub trait TFilesystemProvider {
+    /**
+     * Checks the super block of the given `TBlockDevice`, returning the
+     * `Filesystems` variant it contains
+     */
+    fn verify_superblock(&self, block_device: &dyn TBlockDevice)
+                         -> FsResult<Filesystems>;
 
-    fn verify_superblock(&self) -> Result<(), &str>;
+    /**
+     * Opens the `TFilesystem` stored into the given `TBlockDevice`, which
+     * super block was already verified
+     */
+    fn open(&self, block_device: Arc<dyn TBlockDevice>)
+            -> FsResult<Arc<dyn TFilesystem>>;
+}
 
-    //fn mount_filesystem() -> Result<&dyn Filesystem, str>;
+/**
+ * Kernel centralized filesystem tree, which joins the mounted
+ * `TFilesystem`s under a single root.
+ *
+ * The paths given to the `Vfs` must be absolute
+ */
+pub struct Vfs {
+    m_mount_table: SpinRwLock<MountTable>,
+    m_loaded_nodes: LoadedNodes
 }
 
-trait Filesystem {
-    fn validate_path_in_namespace(&self, path: Rc<&str>) -> Result<&str, &str>;
+impl Vfs /* Constructors */ {
+    /**
+     * Applies the `Vfs` options given by the command line
+     */
+    pub fn early_init() {
+        let cache_capacity =
+            BootInfo::instance().cmd_line_find_arg_int(C_CACHE_CAPACITY_ARG)
+                                .and_then(|(_, cache_capacity)| cache_capacity);
+
+        if let Some(cache_capacity) = cache_capacity {
+            dbg_println!(DbgLevel::Info, "Vfs: node cache capacity {}", cache_capacity);
+            Self::instance().m_loaded_nodes.set_cache_capacity(cache_capacity);
+        }
+    }
+}
+
+impl Vfs /* Methods */ {
+    /**
+     * Mounts the given `TFilesystem` at the given directory, which must be
+     * `/` when nothing is mounted yet
+     */
+    pub fn mount(&self, path: &str, filesystem: Arc<dyn TFilesystem>) -> FsResult<()> {
+        let path = parse_path(path)?;
+        let mut mount_table = self.m_mount_table.write();
+
+        let canonical_path = if mount_table.is_empty() {
+            if path.as_slice() != [PathComponent::Root] {
+                return Err(OsErrorClass::ReferenceNotFound);
+            }
+            path
+        } else {
+            let (mountpoint_node, canonical_path) =
+                self.m_loaded_nodes.find_node(&mount_table, &path, true)?;
+
+            if mountpoint_node.obj_type() != ObjType::Dir {
+                return Err(OsErrorClass::TypesNotMatch);
+            } else if mount_table.mount_at(&canonical_path).is_some() {
+                return Err(OsErrorClass::IdentifierNotAvailable);
+            }
+            canonical_path
+        };
+
+        /* the mount hides the previous content of the directory */
+        self.m_loaded_nodes.invalidate_cached(&canonical_path);
+
+        let mount = mount_table.insert(canonical_path, filesystem);
+        dbg_println!(DbgLevel::Info,
+                     "Vfs: mounted {} at {}",
+                     mount.filesystem().name(),
+                     path_to_string(mount.path()));
+        Ok(())
+    }
+
+    /**
+     * Mounts the `TFilesystem` stored into the given `TBlockDevice` at the
+     * given directory, probing the registered `TFilesystemProvider`s.
+     *
+     * Returns `OsErrorClass::TypesNotMatch` when none of them recognizes
+     * the device
+     */
+    pub fn mount_device(&self,
+                        path: &str,
+                        block_device: Arc<dyn TBlockDevice>)
+                        -> FsResult<Filesystems> {
+        for fs_provider in C_FS_PROVIDERS.iter() {
+            if let Ok(fs_type) = fs_provider.verify_superblock(block_device.as_ref()) {
+                let filesystem = fs_provider.open(block_device)?;
+
+                self.mount(path, filesystem)?;
+                return Ok(fs_type);
+            }
+        }
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Unmounts the `TFilesystem` mounted at the given directory, after
+     * its `TFilesystem::sync()`, and returns it.
+     *
+     * The mounts nested under it must be unmounted before
+     */
+    pub fn unmount(&self, path: &str) -> FsResult<Arc<dyn TFilesystem>> {
+        let path = parse_path(path)?;
+        let mut mount_table = self.m_mount_table.write();
+
+        let (_, canonical_path) =
+            self.m_loaded_nodes.find_node(&mount_table, &path, true)?;
+        let mount = mount_table.mount_at(&canonical_path)
+                               .ok_or(OsErrorClass::ReferenceNotFound)?
+                               .clone();
+        if mount_table.has_nested_mounts(&canonical_path) {
+            return Err(OsErrorClass::OperationNotEnabled);
+        }
+
+        mount.filesystem().sync()?;
+        self.m_loaded_nodes.invalidate_cached(&canonical_path);
+        mount_table.remove(&canonical_path);
 
-    fn get_root_node(&self) -> Arc<&dyn INode>;
+        dbg_println!(DbgLevel::Info,
+                     "Vfs: unmounted {} from {}",
+                     mount.filesystem().name(),
+                     path_to_string(mount.path()));
+        Ok(mount.filesystem().clone())
+    }
 
-    fn get_filesystem_state(&self) -> SpinRwLock<()>;
+    /**
+     * Returns the `VfsNode` at the given path, following the links
+     */
+    pub fn lookup(&self, path: &str) -> FsResult<Arc<VfsNode>> {
+        let path = parse_path(path)?;
+        let mount_table = self.m_mount_table.read();
+
+        self.m_loaded_nodes.find_node(&mount_table, &path, true).map(|(node, _)| node)
+    }
+
+    /**
+     * Returns the `VfsNode` at the given path, without following the last
+     * component when it is a link
+     */
+    pub fn lookup_no_follow(&self, path: &str) -> FsResult<Arc<VfsNode>> {
+        let path = parse_path(path)?;
+        let mount_table = self.m_mount_table.read();
+
+        self.m_loaded_nodes.find_node(&mount_table, &path, false).map(|(node, _)| node)
+    }
+
+    /**
+     * Creates a new empty `ObjType::File` or `ObjType::Dir` at the given
+     * path
+     */
+    pub fn create(&self, path: &str, obj_type: ObjType) -> FsResult<Arc<VfsNode>> {
+        if !matches!(obj_type, ObjType::File | ObjType::Dir) {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let mount_table = self.m_mount_table.read();
+        let (parent_node, name, canonical_path) = self.find_parent(&mount_table, path)?;
+
+        let fs_node = parent_node.create_child(&name, obj_type)?;
+        let mount = mount_table.mount_of(&canonical_path)
+                               .ok_or(OsErrorClass::ReferenceNotFound)?;
+        Ok(self.m_loaded_nodes.load(mount, fs_node))
+    }
+
+    /**
+     * Creates a new `ObjType::Link` at the given path which points to the
+     * given target path, which is not required to exist
+     */
+    pub fn create_link(&self, path: &str, target: &str) -> FsResult<Arc<VfsNode>> {
+        parse_path(target)?;
+
+        let mount_table = self.m_mount_table.read();
+        let (parent_node, name, canonical_path) = self.find_parent(&mount_table, path)?;
+
+        let fs_node = parent_node.create_link_child(&name, target)?;
+        let mount = mount_table.mount_of(&canonical_path)
+                               .ok_or(OsErrorClass::ReferenceNotFound)?;
+        Ok(self.m_loaded_nodes.load(mount, fs_node))
+    }
+
+    /**
+     * Removes the object at the given path, which must not be a
+     * mountpoint. The links are removed, not their targets
+     */
+    pub fn unlink(&self, path: &str) -> FsResult<()> {
+        let mount_table = self.m_mount_table.read();
+        let (parent_node, name, canonical_path) = self.find_parent(&mount_table, path)?;
+
+        if mount_table.mount_at(&canonical_path).is_some() {
+            return Err(OsErrorClass::OperationNotEnabled);
+        }
+
+        parent_node.unlink_child(&name)?;
+        self.m_loaded_nodes.invalidate_cached(&canonical_path);
+        Ok(())
+    }
+
+    /**
+     * Moves the object at the given path to the given new path, which must
+     * not exist and must be into the same `TFilesystem`.
+     *
+     * The mountpoints can't be moved, neither the directories which contain
+     * them nor a directory into itself
+     */
+    pub fn rename(&self, path: &str, new_path: &str) -> FsResult<()> {
+        let mount_table = self.m_mount_table.read();
+        let (parent_node, name, canonical_path) = self.find_parent(&mount_table, path)?;
+        let (new_parent_node, new_name, new_canonical_path) =
+            self.find_parent(&mount_table, new_path)?;
+
+        if mount_table.mount_at(&canonical_path).is_some()
+           || mount_table.has_nested_mounts(&canonical_path)
+           || parent_node.mount_id() != new_parent_node.mount_id()
+        {
+            return Err(OsErrorClass::OperationNotEnabled);
+        } else if new_canonical_path.starts_with(&canonical_path) {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        parent_node.rename_child(&name, &new_parent_node, &new_name)?;
+        self.m_loaded_nodes.invalidate_cached(&canonical_path);
+        Ok(())
+    }
+
+    /**
+     * Returns the target path of the `ObjType::Link` at the given path
+     */
+    pub fn read_link(&self, path: &str) -> FsResult<String> {
+        self.lookup_no_follow(path)?.link_target()
+    }
+}
+
+impl Vfs /* Getters */ {
+    /**
+     * Returns the global `Vfs` instance
+     */
+    pub fn instance() -> &'static Self {
+        unsafe { &SM_VFS }
+    }
+
+    /**
+     * Returns a snapshot of the statistics of the `NodeCache`
+     */
+    pub fn cache_stats(&self) -> NodeCacheStats {
+        self.m_loaded_nodes.cache_stats()
+    }
+
+    /**
+     * Returns the `LoadedNodes` table
+     */
+    pub fn loaded_nodes(&self) -> &LoadedNodes {
+        &self.m_loaded_nodes
+    }
+}
+
+impl Vfs /* Privates */ {
+    /**
+     * Returns the parent directory `VfsNode` of the given path, the name of
+     * its last component and its canonical path.
+     *
+     * The last component must be an object name
+     */
+    fn find_parent(&self,
+                   mount_table: &MountTable,
+                   path: &str)
+                   -> FsResult<(Arc<VfsNode>, String, Vec<PathComponent>)> {
+        let mut path = parse_path(path)?;
+        let name = match path.pop() {
+            Some(PathComponent::ObjectName(name)) => name,
+            _ => return Err(OsErrorClass::InvalidArgument)
+        };
+
+        let (parent_node, mut canonical_path) =
+            self.m_loaded_nodes.find_node(mount_table, &path, true)?;
+        if parent_node.obj_type() != ObjType::Dir {
+            return Err(OsErrorClass::TypesNotMatch);
+        }
+
+        canonical_path.push(PathComponent::ObjectName(name.clone()));
+        Ok((parent_node, name, canonical_path))
+    }
+}
+
+/**
+ * Splits the given absolute or relative path into `PathComponent`s,
+ * dropping the empty ones.
+ *
+ * Only the absolute paths start with `PathComponent::Root`
+ */
+pub fn parse_path(path: &str) -> FsResult<Vec<PathComponent>> {
+    if path.is_empty() {
+        return Err(OsErrorClass::InvalidArgument);
+    } else if path.len() > VFS_PATH_LEN_MAX {
+        return Err(OsErrorClass::LimitOverflow);
+    }
+
+    let mut path_components = Vec::new();
+    if path.starts_with(PathComponent::SEPARATOR) {
+        path_components.push(PathComponent::Root);
+    }
+    for name in path.split(PathComponent::SEPARATOR).filter(|name| !name.is_empty()) {
+        if name.len() > VFS_NAME_LEN_MAX {
+            return Err(OsErrorClass::LimitOverflow);
+        }
+        path_components.push(PathComponent::from(name));
+    }
+    Ok(path_components)
+}
+
+/**
+ * Joins the given canonical `PathComponent`s into a path string
+ */
+pub fn path_to_string(path: &[PathComponent]) -> String {
+    let mut path_string = String::new();
+    for path_component in path.iter() {
+        if path_component.need_separator_before() && path_string.len() > 1 {
+            path_string.push_str(PathComponent::SEPARATOR);
+        }
+        path_string.push_str(&path_component.as_string());
+    }
+    path_string
 }
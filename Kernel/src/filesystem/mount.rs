@@ -0,0 +1,167 @@
+/*! Filesystems mount table */
+
+use alloc::{
+    collections::BTreeMap,
+    sync::Arc,
+    vec::Vec
+};
+
+use api_data::path::PathComponent;
+
+use crate::filesystem::{
+    node::VfsNode,
+    TFilesystem
+};
+
+/**
+ * Identifier of a `Mount`, never reused while the kernel runs
+ */
+pub type MountId = usize;
+
+/**
+ * `TFilesystem` attached to a directory of the `Vfs` tree
+ */
+pub struct Mount {
+    m_id: MountId,
+    m_path: Vec<PathComponent>,
+    m_filesystem: Arc<dyn TFilesystem>,
+    m_root: Arc<VfsNode>
+}
+
+impl Mount /* Constructors */ {
+    /**
+     * Constructs a `Mount` of the given `TFilesystem` at the given
+     * canonical path
+     */
+    pub fn new(id: MountId,
+               path: Vec<PathComponent>,
+               filesystem: Arc<dyn TFilesystem>,
+               root: Arc<VfsNode>)
+               -> Self {
+        Self { m_id: id,
+               m_path: path,
+               m_filesystem: filesystem,
+               m_root: root }
+    }
+}
+
+impl Mount /* Getters */ {
+    /**
+     * Returns the `MountId` of this mount
+     */
+    pub fn id(&self) -> MountId {
+        self.m_id
+    }
+
+    /**
+     * Returns the canonical path of the mountpoint
+     */
+    pub fn path(&self) -> &[PathComponent] {
+        self.m_path.as_slice()
+    }
+
+    /**
+     * Returns the mounted `TFilesystem`
+     */
+    pub fn filesystem(&self) -> &Arc<dyn TFilesystem> {
+        &self.m_filesystem
+    }
+
+    /**
+     * Returns the root directory `VfsNode` of the mounted `TFilesystem`,
+     * which is kept loaded as long as it is mounted
+     */
+    pub fn root(&self) -> &Arc<VfsNode> {
+        &self.m_root
+    }
+}
+
+/**
+ * Table of the `Mount`s keyed by the canonical `PathComponent`s of their
+ * mountpoints
+ */
+pub struct MountTable {
+    m_mounts: BTreeMap<Vec<PathComponent>, Arc<Mount>>,
+    m_next_mount_id: MountId
+}
+
+impl MountTable /* Constructors */ {
+    /**
+     * Constructs an empty `MountTable`
+     */
+    pub const fn new() -> Self {
+        Self { m_mounts: BTreeMap::new(),
+               m_next_mount_id: 0 }
+    }
+}
+
+impl MountTable /* Methods */ {
+    /**
+     * Inserts a new `Mount` at the given canonical path, which must not
+     * be already used by another one
+     */
+    pub fn insert(&mut self,
+                  path: Vec<PathComponent>,
+                  filesystem: Arc<dyn TFilesystem>)
+                  -> Arc<Mount> {
+        let mount_id = self.m_next_mount_id;
+        self.m_next_mount_id += 1;
+
+        let root = Arc::new(VfsNode::new(filesystem.root(),
+                                         mount_id,
+                                         filesystem.is_read_only()));
+        let mount = Arc::new(Mount::new(mount_id, path.clone(), filesystem, root));
+        self.m_mounts.insert(path, mount.clone());
+        mount
+    }
+
+    /**
+     * Removes the `Mount` at the given canonical path
+     */
+    pub fn remove(&mut self, path: &[PathComponent]) -> Option<Arc<Mount>> {
+        self.m_mounts.remove(path)
+    }
+}
+
+impl MountTable /* Getters */ {
+    /**
+     * Returns the `Mount` exactly at the given canonical path
+     */
+    pub fn mount_at(&self, path: &[PathComponent]) -> Option<&Arc<Mount>> {
+        self.m_mounts.get(path)
+    }
+
+    /**
+     * Returns the `Mount` with the longest mountpoint which prefixes the
+     * given canonical path, so the one which owns the node at the path
+     */
+    pub fn mount_of(&self, path: &[PathComponent]) -> Option<&Arc<Mount>> {
+        (1..=path.len()).rev()
+                        .find_map(|prefix_len| self.m_mounts.get(&path[..prefix_len]))
+    }
+
+    /**
+     * Returns the `Mount` at `/`
+     */
+    pub fn root_mount(&self) -> Option<&Arc<Mount>> {
+        self.m_mounts.get([PathComponent::Root].as_ref())
+    }
+
+    /**
+     * Returns whether any `Mount` is nested under the given canonical path
+     */
+    pub fn has_nested_mounts(&self, path: &[PathComponent]) -> bool {
+        self.m_mounts
+            .keys()
+            .any(|mount_path| {
+                mount_path.len() > path.len() && mount_path.starts_with(path)
+            })
+    }
+
+    /**
+     * Returns whether no `TFilesystem` is mounted
+     */
+    pub fn is_empty(&self) -> bool {
+        self.m_mounts.is_empty()
+    }
+}
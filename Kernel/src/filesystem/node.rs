@@ -0,0 +1,503 @@
+/*! Filesystem nodes */
+
+use alloc::{
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
+use core::{
+    any::Any,
+    ptr
+};
+
+use api_data::{
+    entity::OsEntityId,
+    error::class::OsErrorClass,
+    object::{
+        dir::DirEntry,
+        grants::{
+            ObjGrantsBits,
+            RawObjGrants
+        },
+        info::RawObjInfo,
+        types::ObjType
+    }
+};
+use sync::SpinRwLock;
+
+use crate::filesystem::{
+    mount::MountId,
+    FsResult,
+    Vfs
+};
+
+/* <ObjGrantsBits> enabled for the new directories */
+const C_DIR_DEFAULT_GRANTS: [ObjGrantsBits; 19] =
+    [ObjGrantsBits::UserCanOpenIt,
+     ObjGrantsBits::UserCanReadData,
+     ObjGrantsBits::UserCanWriteData,
+     ObjGrantsBits::UserCanExecTraversData,
+     ObjGrantsBits::UserCanReadInfo,
+     ObjGrantsBits::UserCanWriteInfo,
+     ObjGrantsBits::UserCanSeeIt,
+     ObjGrantsBits::GroupCanOpenIt,
+     ObjGrantsBits::GroupCanReadData,
+     ObjGrantsBits::GroupCanExecTraversData,
+     ObjGrantsBits::GroupCanReadInfo,
+     ObjGrantsBits::GroupCanWriteInfo,
+     ObjGrantsBits::GroupCanSeeIt,
+     ObjGrantsBits::OtherCanOpenIt,
+     ObjGrantsBits::OtherCanReadData,
+     ObjGrantsBits::OtherCanExecTraversData,
+     ObjGrantsBits::OtherCanReadInfo,
+     ObjGrantsBits::OtherCanWriteInfo,
+     ObjGrantsBits::OtherCanSeeIt];
+
+/* <ObjGrantsBits> enabled for the new files and links */
+const C_FILE_DEFAULT_GRANTS: [ObjGrantsBits; 15] =
+    [ObjGrantsBits::UserCanOpenIt,
+     ObjGrantsBits::UserCanReadData,
+     ObjGrantsBits::UserCanWriteData,
+     ObjGrantsBits::UserCanExecTraversData,
+     ObjGrantsBits::UserCanReadInfo,
+     ObjGrantsBits::UserCanWriteInfo,
+     ObjGrantsBits::UserCanSeeIt,
+     ObjGrantsBits::GroupCanOpenIt,
+     ObjGrantsBits::GroupCanReadData,
+     ObjGrantsBits::GroupCanWriteData,
+     ObjGrantsBits::GroupCanReadInfo,
+     ObjGrantsBits::GroupCanSeeIt,
+     ObjGrantsBits::OtherCanOpenIt,
+     ObjGrantsBits::OtherCanReadData,
+     ObjGrantsBits::OtherCanSeeIt];
+
+/**
+ * Identifier of a `TFsNode`, unique into its filesystem
+ */
+pub type FsNodeId = u64;
+
+/**
+ * Interface implemented by the nodes of the filesystem drivers.
+ *
+ * The methods which don't apply to the `ObjType` of the node are kept with
+ * their default implementation, which returns `OsErrorClass::TypesNotMatch`.
+ * The drivers don't need to care about concurrency, since the `Vfs` calls
+ * them under the lock of the `VfsNode`
+ */
+pub trait TFsNode: Send + Sync {
+    /**
+     * Returns the `FsNodeId` of this node
+     */
+    fn id(&self) -> FsNodeId;
+
+    /**
+     * Returns the `ObjType` of this node
+     */
+    fn obj_type(&self) -> ObjType;
+
+    /**
+     * Returns the size in bytes of the data of this node
+     */
+    fn size(&self) -> usize {
+        0
+    }
+
+    /**
+     * Returns the metadata of this node
+     */
+    fn info(&self) -> FsResult<RawObjInfo> {
+        Err(OsErrorClass::OperationNotEnabled)
+    }
+
+    /**
+     * Changes the owner user and group of this node
+     */
+    fn set_owner(&self,
+                 _os_user_id: OsEntityId,
+                 _os_group_id: OsEntityId)
+                 -> FsResult<()> {
+        Err(OsErrorClass::OperationNotEnabled)
+    }
+
+    /**
+     * Changes the protection grants of this node
+     */
+    fn set_prot_grants(&self, _prot_grants: RawObjGrants) -> FsResult<()> {
+        Err(OsErrorClass::OperationNotEnabled)
+    }
+
+    /**
+     * Reads the data from the given offset into the given buffer.
+     *
+     * Returns the amount of bytes read, which is `0` at the end of data
+     */
+    fn read_at(&self, _offset: usize, _buffer: &mut [u8]) -> FsResult<usize> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Writes the given buffer at the given offset, growing the data when
+     * needed.
+     *
+     * Returns the amount of bytes written
+     */
+    fn write_at(&self, _offset: usize, _buffer: &[u8]) -> FsResult<usize> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Shrinks or grows the data to the given size
+     */
+    fn truncate(&self, _size: usize) -> FsResult<()> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Returns the child with the given name of this directory
+     */
+    fn lookup(&self, _name: &str) -> FsResult<Arc<dyn TFsNode>> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Returns the `DirEntry` of the child at the given index of this
+     * directory, `None` when the index is over the last child
+     */
+    fn read_dir(&self, _index: usize) -> FsResult<Option<DirEntry>> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Creates a new empty child of the given `ObjType` into this directory
+     */
+    fn create(&self, _name: &str, _obj_type: ObjType) -> FsResult<Arc<dyn TFsNode>> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Creates a new link child which points to the given path into this
+     * directory
+     */
+    fn create_link(&self, _name: &str, _target: &str) -> FsResult<Arc<dyn TFsNode>> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Removes the child with the given name from this directory
+     */
+    fn unlink(&self, _name: &str) -> FsResult<()> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Moves the child with the given name of this directory into the given
+     * directory of the same filesystem with the given new name, which is
+     * not used by any other child
+     */
+    fn rename(&self,
+              _name: &str,
+              _new_parent: &dyn TFsNode,
+              _new_name: &str)
+              -> FsResult<()> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Returns the path pointed by this link
+     */
+    fn link_target(&self) -> FsResult<String> {
+        Err(OsErrorClass::TypesNotMatch)
+    }
+
+    /**
+     * Returns this node as `Any`, so the drivers can downcast the nodes
+     * given back by the `Vfs` to their own type
+     */
+    fn as_any(&self) -> &dyn Any;
+}
+
+/**
+ * Loaded `TFsNode` shared by all the users of the `Vfs`.
+ *
+ * Each `TFsNode` has at most one `VfsNode` at time, so its lock serializes
+ * all the accesses to the node. The directories are write-locked only to
+ * modify their children
+ */
+pub struct VfsNode {
+    m_fs_node: Arc<dyn TFsNode>,
+    m_mount_id: MountId,
+    m_is_read_only: bool,
+    m_lock: SpinRwLock<()>
+}
+
+impl VfsNode /* Constructors */ {
+    /**
+     * Constructs a `VfsNode` which wraps the given `TFsNode`
+     */
+    pub fn new(fs_node: Arc<dyn TFsNode>, mount_id: MountId, is_read_only: bool) -> Self {
+        Self { m_fs_node: fs_node,
+               m_mount_id: mount_id,
+               m_is_read_only: is_read_only,
+               m_lock: SpinRwLock::const_new(()) }
+    }
+}
+
+impl VfsNode /* Methods */ {
+    /**
+     * Reads the data from the given offset into the given buffer
+     */
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> FsResult<usize> {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.read_at(offset, buffer)
+    }
+
+    /**
+     * Reads the whole data into a new `Vec`
+     */
+    pub fn read_all(&self) -> FsResult<Vec<u8>> {
+        let _read_guard = self.m_lock.read();
+
+        let mut data = vec![0; self.m_fs_node.size()];
+        let mut read_bytes = 0;
+        while read_bytes < data.len() {
+            match self.m_fs_node.read_at(read_bytes, &mut data[read_bytes..])? {
+                0 => return Err(OsErrorClass::EndOfDataReached),
+                chunk_bytes => read_bytes += chunk_bytes
+            }
+        }
+        Ok(data)
+    }
+
+    /**
+     * Writes the given buffer at the given offset
+     */
+    pub fn write_at(&self, offset: usize, buffer: &[u8]) -> FsResult<usize> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.m_fs_node.write_at(offset, buffer)
+    }
+
+    /**
+     * Shrinks or grows the data to the given size
+     */
+    pub fn truncate(&self, size: usize) -> FsResult<()> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.m_fs_node.truncate(size)
+    }
+
+    /**
+     * Returns the `DirEntry` of the child at the given index
+     */
+    pub fn read_dir(&self, index: usize) -> FsResult<Option<DirEntry>> {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.read_dir(index)
+    }
+
+    /**
+     * Returns the path pointed by this link
+     */
+    pub fn link_target(&self) -> FsResult<String> {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.link_target()
+    }
+
+    /**
+     * Returns the metadata
+     */
+    pub fn info(&self) -> FsResult<RawObjInfo> {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.info()
+    }
+
+    /**
+     * Changes the owner user and group
+     */
+    pub fn set_owner(&self,
+                     os_user_id: OsEntityId,
+                     os_group_id: OsEntityId)
+                     -> FsResult<()> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.m_fs_node.set_owner(os_user_id, os_group_id)
+    }
+
+    /**
+     * Changes the protection grants
+     */
+    pub fn set_prot_grants(&self, prot_grants: RawObjGrants) -> FsResult<()> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.m_fs_node.set_prot_grants(prot_grants)
+    }
+}
+
+impl VfsNode /* Getters */ {
+    /**
+     * Returns the `FsNodeId` of the wrapped `TFsNode`
+     */
+    pub fn id(&self) -> FsNodeId {
+        self.m_fs_node.id()
+    }
+
+    /**
+     * Returns the `ObjType` of the wrapped `TFsNode`
+     */
+    pub fn obj_type(&self) -> ObjType {
+        self.m_fs_node.obj_type()
+    }
+
+    /**
+     * Returns the size in bytes of the data
+     */
+    pub fn size(&self) -> usize {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.size()
+    }
+
+    /**
+     * Returns the `MountId` of the filesystem which owns this node
+     */
+    pub fn mount_id(&self) -> MountId {
+        self.m_mount_id
+    }
+
+    /**
+     * Returns whether the filesystem which owns this node is read-only
+     */
+    pub fn is_read_only(&self) -> bool {
+        self.m_is_read_only
+    }
+}
+
+impl VfsNode /* Privates */ {
+    /**
+     * Returns the `TFsNode` child with the given name
+     */
+    pub(super) fn lookup_child(&self, name: &str) -> FsResult<Arc<dyn TFsNode>> {
+        let _read_guard = self.m_lock.read();
+        self.m_fs_node.lookup(name)
+    }
+
+    /**
+     * Creates a new child of the given `ObjType`
+     */
+    pub(super) fn create_child(&self,
+                               name: &str,
+                               obj_type: ObjType)
+                               -> FsResult<Arc<dyn TFsNode>> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.ensure_child_not_exists(name)?;
+        self.m_fs_node.create(name, obj_type)
+    }
+
+    /**
+     * Creates a new link child which points to the given path
+     */
+    pub(super) fn create_link_child(&self,
+                                    name: &str,
+                                    target: &str)
+                                    -> FsResult<Arc<dyn TFsNode>> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.ensure_child_not_exists(name)?;
+        self.m_fs_node.create_link(name, target)
+    }
+
+    /**
+     * Removes the child with the given name
+     */
+    pub(super) fn unlink_child(&self, name: &str) -> FsResult<()> {
+        self.ensure_writable()?;
+
+        let _write_guard = self.m_lock.write();
+        self.m_fs_node.unlink(name)
+    }
+
+    /**
+     * Moves the child with the given name into the given directory of the
+     * same filesystem with the given new name
+     */
+    pub(super) fn rename_child(&self,
+                               name: &str,
+                               new_parent: &VfsNode,
+                               new_name: &str)
+                               -> FsResult<()> {
+        self.ensure_writable()?;
+
+        if ptr::eq(self, new_parent) {
+            let _write_guard = self.m_lock.write();
+            self.ensure_child_not_exists(new_name)?;
+            self.m_fs_node.rename(name, self.m_fs_node.as_ref(), new_name)
+        } else {
+            /* always locked in the same order, so crossed renames never deadlock */
+            let (first_node, second_node) =
+                if (self as *const Self) < (new_parent as *const Self) {
+                    (self, new_parent)
+                } else {
+                    (new_parent, self)
+                };
+            let _first_write_guard = first_node.m_lock.write();
+            let _second_write_guard = second_node.m_lock.write();
+
+            new_parent.ensure_child_not_exists(new_name)?;
+            self.m_fs_node.rename(name, new_parent.m_fs_node.as_ref(), new_name)
+        }
+    }
+
+    /**
+     * Returns `OsErrorClass::NotEnoughGrants` when the filesystem is
+     * read-only
+     */
+    fn ensure_writable(&self) -> FsResult<()> {
+        if self.m_is_read_only {
+            Err(OsErrorClass::NotEnoughGrants)
+        } else {
+            Ok(())
+        }
+    }
+
+    /**
+     * Returns `OsErrorClass::IdentifierNotAvailable` when a child with the
+     * given name already exists.
+     *
+     * Must be called with the lock held
+     */
+    fn ensure_child_not_exists(&self, name: &str) -> FsResult<()> {
+        match self.m_fs_node.lookup(name) {
+            Ok(_) => Err(OsErrorClass::IdentifierNotAvailable),
+            Err(OsErrorClass::ReferenceNotFound) => Ok(()),
+            Err(os_error_class) => Err(os_error_class)
+        }
+    }
+}
+
+impl Drop for VfsNode {
+    fn drop(&mut self) {
+        Vfs::instance().loaded_nodes().forget(self.m_mount_id, self.id());
+    }
+}
+
+/**
+ * Returns the `RawObjGrants` given to the new nodes of the given `ObjType`
+ * by the filesystems which don't store them
+ */
+pub fn default_prot_grants(obj_type: ObjType) -> RawObjGrants {
+    let default_grants = match obj_type {
+        ObjType::Dir => C_DIR_DEFAULT_GRANTS.as_ref(),
+        _ => C_FILE_DEFAULT_GRANTS.as_ref()
+    };
+
+    let mut prot_grants = RawObjGrants::new_zero();
+    for grant_bit in default_grants.iter() {
+        prot_grants.set_enabled(*grant_bit);
+    }
+    prot_grants
+}
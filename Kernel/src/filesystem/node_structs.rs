@@ -1,303 +1,308 @@
-use alloc::string::String;
-use alloc::sync::{Arc, Weak};
-use alloc::vec::Vec;
-use alloc::collections::BTreeMap;
-use core::cmp::Ordering::{self, Equal, Greater, Less};
-use core::ops::{Add, Bound, Deref};
-use core::ptr::NonNull;
+/*! Resolved nodes cache */
 
-use api_data::path::PathComponent;
-
-use crate::filesystem::r#virtual::INode;
-use alloc::boxed::Box;
-use alloc::alloc::Global;
+use alloc::{
+    collections::BTreeMap,
+    sync::Arc,
+    vec::Vec
+};
 
-type Link<T> = Option<NonNull<T>>;
+use api_data::path::PathComponent;
 
-pub enum PartialNodeResult {
-    None,
-    Found(Arc<dyn INode>),
-    Ancestor(Arc<dyn INode>, usize),
-}
+use crate::filesystem::node::VfsNode;
 
 /**
- * Support struct to be used as key.
- * Inverts the string ordering, such that ancestors come after child paths.
+ * Capacity of the `NodeCache` when the command line doesn't select one
  */
-struct PathWrapper {
-    _string: String,    // The path as a string.
-    _separators: usize  // The number of Path::SEPARATOR in the string.
-}
+pub const C_DEFAULT_CACHE_CAPACITY: usize = 512;
 
 /**
- * A node cache entry that is also a node of a doubly-linked list of entries that don't own each
- * other.
+ * Bounded LRU cache of the `VfsNode`s resolved from their canonical paths.
+ *
+ * It keeps alive the most recently used nodes and allows the path
+ * resolution to start from the nearest cached ancestor instead of the
+ * root. The entries are ordered by their last use stamp, so the least
+ * recently used one is the first evicted when the cache is full
  */
-struct CacheEntry {
-    next: Link<CacheEntry>,
-    prev: Link<CacheEntry>,
-    value: Arc<dyn INode>
+pub struct NodeCache {
+    m_entries: BTreeMap<Vec<PathComponent>, CacheEntry>,
+    m_lru_order: BTreeMap<u64, Vec<PathComponent>>,
+    m_next_use_stamp: u64,
+    m_generation: u64,
+    m_capacity: usize,
+    m_stats: NodeCacheStats
 }
 
-pub trait NodeTreeMap {
-    fn set(&mut self, path: &[PathComponent], node: &Arc<dyn INode>);
-
-    fn get_exact(&self, path: &[PathComponent]) -> PartialNodeResult;
-
+impl NodeCache /* Constructors */ {
     /**
-     * Returns the nearest ancestor to the given path and it's distance from it, if any.
-     * Does not return the node from with the given path.
+     * Constructs an empty `NodeCache` with the given capacity
      */
-    fn get_nearest_ancestor(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult;
+    pub const fn new(capacity: usize) -> Self {
+        Self { m_entries: BTreeMap::new(),
+               m_lru_order: BTreeMap::new(),
+               m_next_use_stamp: 0,
+               m_generation: 0,
+               m_capacity: capacity,
+               m_stats: NodeCacheStats::new() }
+    }
+}
 
+impl NodeCache /* Methods */ {
     /**
-     * Returns the best match it can find from the given path and it's distance from it. IE: either
-     * the node associated with the path, or the node with the nearest ancestor path.
+     * Returns the `VfsNode` of the longest cached prefix of the given
+     * path, with the amount of its `PathComponent`s.
+     *
+     * Only the prefix before the first `PathComponent::SelfLink` or
+     * `PathComponent::ParentLink` is considered, since the cached paths
+     * are canonical
      */
-    fn get_best_match(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult;
-
-    fn remove(&mut self, path: &[PathComponent]) -> bool;
-}
-
-/**
- * WARNING: doesn't support parent or self path links and does not check for them: it will produce
- * wacky results if fed any!
- */
-pub struct NodeTable {
-    _map: BTreeMap<PathWrapper, Arc<dyn INode>>
-}
-
-/**
- * WARNING: doesn't support parent or self path links and does not check for them: it will produce
- * wacky results if fed any!
- */
-pub struct NodeCache<'a> {
-    _map: BTreeMap<PathWrapper, Box<CacheEntry>>,
-    _list_head: Link<CacheEntry>,
-    _list_back: Link<CacheEntry>,
-    _capacity: usize
-}
-
-impl PathWrapper {
-    pub fn new(path: &[PathComponent]) -> Self {
-        let mut accumulator = String::new();
-        path.iter().map(|p_comp| accumulator.add(&p_comp.as_string())).collect();
-        accumulator.shrink_to_fit();
-
-        Self {
-            _string: accumulator,
-            _separators: path.len(),
+    pub fn get_best_match(&mut self,
+                          path: &[PathComponent])
+                          -> Option<(Arc<VfsNode>, usize)> {
+        if path.first() != Some(&PathComponent::Root) {
+            return None;
         }
-    }
-}
 
-impl Ord for PathWrapper {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self._string.cmp(&other._string).reverse()
-    }
-}
-
-impl PartialOrd for PathWrapper {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self._string.partial_cmp(&other._string)?.reverse())
-    }
-}
-
-impl Eq for PathWrapper {}
-
-impl PartialEq for PathWrapper {
-    fn eq(&self, other: &Self) -> bool {
-        self._string.eq(&self._string)
-    }
-}
-
-impl CacheEntry {
-    pub fn new(value: &Arc<dyn INode>) -> Self {
-        Self {
-            next: None,
-            prev: None,
-            value: value.clone()
+        let canonical_prefix_len =
+            1 + path.iter()
+                    .skip(1)
+                    .take_while(|path_component| path_component.is_object_name())
+                    .count();
+        for prefix_len in (2..=canonical_prefix_len).rev() {
+            if let Some(node) = self.touch(&path[..prefix_len]) {
+                if prefix_len == path.len() {
+                    self.m_stats.m_hits += 1;
+                } else {
+                    self.m_stats.m_ancestor_hits += 1;
+                }
+                return Some((node, prefix_len));
+            }
         }
+
+        self.m_stats.m_misses += 1;
+        None
     }
-}
 
-impl NodeTable {
     /**
-     * Returns true if the child is a sub-path of the ancestor.
-     * Assumes the ancestor is smaller than the child. May panic if it's not.
+     * Inserts or refreshes the `VfsNode` of the given canonical path,
+     * evicting the least recently used entries when the cache is full.
+     *
+     * The insertion is discarded when any invalidation happened after the
+     * given generation, since the node could be already stale
      */
-    fn is_ancestor(ancestor: &PathWrapper, child: &PathWrapper) -> bool {
-        // Check that the ancestor string ends where the child has a path separator.
-        // This rules out `/foo/ba` and `/foo/baz/file` cases.
-        child._string[ancestor._string.len()] == PathComponent::SEPARATOR
-            // Checks that the child's path string starts with the full ancestor.
-            && child._string.starts_with(&ancestor._string)
-    }
+    pub fn insert(&mut self,
+                  generation: u64,
+                  path: Vec<PathComponent>,
+                  node: Arc<VfsNode>) {
+        if generation != self.m_generation || self.m_capacity == 0 {
+            return;
+        }
 
-    fn get_match_of<P>(&self, path: &PathWrapper, max_distance: usize, child_bound: Bound<PathWrapper>, predicate: P) -> PartialNodeResult
-    where
-        P: FnMut((&PathWrapper, &Arc<&dyn INode>)) -> bool
-    {
-        // The maximum number of separators in a path to be within the given maximum distance from
-        // the child.
-        let max_separators = path._separators - max_distance;
-
-        match self._map.range((Bound::Unbounded, child_bound))
-            .filter(|(path , _)| path._separators < max_separators)
-            .find(predicate)
-        {
-            None => PartialNodeResult::None,
-            Some((ancestor_path, ancestor)) => {
-                let distance = path._separators - ancestor_path._separators;
-                if distance == 0 {
-                    PartialNodeResult::Found(ancestor.clone())
-                } else {
-                    PartialNodeResult::NewBestScore(ancestor.clone(), distance)
-                }
-            }
+        if let Some(cache_entry) = self.m_entries.get_mut(&path) {
+            cache_entry.m_node = node;
+            self.touch(&path);
+            return;
         }
-    }
 
-    pub fn new() -> Self {
-        Self {
-            _map: BTreeMap::new()
+        while self.m_entries.len() >= self.m_capacity {
+            self.evict_lru();
         }
-    }
-}
 
-impl NodeTreeMap for NodeTable {
-    fn set(&mut self, path: &[PathComponent], node: &Arc<&dyn INode>) {
-        self._map.insert(path_to_str(path), node.clone());
+        let use_stamp = self.next_use_stamp();
+        self.m_lru_order.insert(use_stamp, path.clone());
+        self.m_entries.insert(path,
+                              CacheEntry { m_node: node,
+                                           m_use_stamp: use_stamp });
     }
 
-    fn get_exact(&self, path: &[PathComponent]) -> PartialNodeResult {
-        match self._map.get(&path_to_str(path)) {
-            None => PartialNodeResult::None,
-            Some(node) => PartialNodeResult::Found(node.clone())
+    /**
+     * Removes the entry of the given canonical path and the ones of all
+     * its descendants
+     */
+    pub fn invalidate(&mut self, path: &[PathComponent]) {
+        self.m_generation += 1;
+
+        /* the descendants follow their ancestor into the ordered map */
+        let invalid_paths: Vec<_> =
+            self.m_entries
+                .range(path.to_vec()..)
+                .map(|(cached_path, _)| cached_path)
+                .take_while(|cached_path| cached_path.starts_with(path))
+                .cloned()
+                .collect();
+        for invalid_path in invalid_paths {
+            self.remove(&invalid_path);
+            self.m_stats.m_invalidations += 1;
         }
     }
+}
 
+impl NodeCache /* Getters */ {
     /**
-     * Returns the nearest ancestor to the given path, if any.
+     * Returns the current generation, to be given back to
+     * `NodeCache::insert()`
      */
-    fn get_nearest_ancestor(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult {
-        let path = PathWrapper::new(path);
-        // The first path with less separators could be an ancestor (less characters alone could
-        // also include a sibling file/directory with shorter name).
-        // Check that the ancestor's string path is a sub-path ot the child.
-        self.get_match_of(&path, max_distance, Bound::Excluded(path), |(k, _)| {
-            k._separators < path._separators && Self::is_ancestor(k, &path)
-        })
+    pub fn generation(&self) -> u64 {
+        self.m_generation
     }
 
-    fn get_best_match(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult {
-        let path = PathWrapper::new(path);
-        // The first path with less or equal separators could be an ancestor or the path to the
-        // child (less characters alone could also include a sibling file/directory with shorter
-        // name).
-        // Check that the current string path is a sub-path ot the child or the child itself.
-        self.get_match_of(&path, max_distance, Bound::Included(path), |(k, _)| {
-            if k._separators == path._separators {
-                k._string == path._separators
-            } else if k._separators < path._separators {
-                Self::is_ancestor(k, &path)
-            }
-            false
-        })
+    /**
+     * Returns a snapshot of the `NodeCacheStats`
+     */
+    pub fn stats(&self) -> NodeCacheStats {
+        NodeCacheStats { m_entries: self.m_entries.len(),
+                         m_capacity: self.m_capacity,
+                         ..self.m_stats }
     }
+}
 
-    fn remove(&mut self, path: &[PathComponent]) -> bool {
-        self._map.remove(&path_to_str(path)) != None
+impl NodeCache /* Setters */ {
+    /**
+     * Sets the maximum amount of entries, evicting the exceeding ones.
+     *
+     * A capacity of `0` disables the cache
+     */
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.m_capacity = capacity;
+        while self.m_entries.len() > self.m_capacity {
+            self.evict_lru();
+        }
     }
 }
 
-impl NodeCache {
-    unsafe fn unlink(mut entry: NonNull<CacheEntry>) {
-        if let Some(mut prev) = entry.as_mut().prev {
-            prev.as_mut().next = entry.as_ref().next;
-            entry.as_mut().prev = None;
-        }
-        if let Some(mut next) = entry.as_mut().next {
-            next.as_mut().prev = entry.as_ref().prev;
-            entry.as_mut().next = None;
-        }
+impl NodeCache /* Privates */ {
+    /**
+     * Marks as most recently used the entry of the given path, returning
+     * its `VfsNode`
+     */
+    fn touch(&mut self, path: &[PathComponent]) -> Option<Arc<VfsNode>> {
+        let use_stamp = self.next_use_stamp();
+        let cache_entry = self.m_entries.get_mut(path)?;
+
+        let path = self.m_lru_order
+                       .remove(&cache_entry.m_use_stamp)
+                       .expect("NodeCache entry without LRU stamp");
+        cache_entry.m_use_stamp = use_stamp;
+        self.m_lru_order.insert(use_stamp, path);
+        Some(cache_entry.m_node.clone())
     }
 
     /**
-     * Pushes an entry to the top of the list.
+     * Removes the least recently used entry
      */
-    unsafe fn move_as_head(&mut self, mut entry: NonNull<CacheEntry>) {
-        Self::unlink(entry);
-        entry.as_mut().prev = None;
-        if let Some(mut old_head) = self._list_head {
-            old_head.as_mut().prev = Some(entry);
-            entry.as_mut().next = Some(old_head);
-            self._list_head = Some(entry);
-        } else {
-            entry.as_mut().next = None;
-            self._list_head = Some(entry);
-            self._list_back = Some(entry);
+    fn evict_lru(&mut self) {
+        let oldest_stamp = self.m_lru_order.keys().next().copied();
+        let lru_path = oldest_stamp.and_then(|stamp| self.m_lru_order.remove(&stamp));
+        if let Some(lru_path) = lru_path {
+            self.m_entries.remove(&lru_path);
+            self.m_stats.m_evictions += 1;
         }
     }
 
-    pub fn new(capacity: usize) -> Self {
-        Self {
-            _map: BTreeMap::new(),
-            _list_head: None,
-            _list_back: None,
-            _capacity: capacity
+    /**
+     * Removes the entry of the given path
+     */
+    fn remove(&mut self, path: &[PathComponent]) {
+        if let Some(cache_entry) = self.m_entries.remove(path) {
+            self.m_lru_order.remove(&cache_entry.m_use_stamp);
         }
     }
 
-    pub fn capacity(&self) -> usize {
-        self._capacity
+    /**
+     * Returns a new use stamp, greater than all the previous ones
+     */
+    fn next_use_stamp(&mut self) -> u64 {
+        let use_stamp = self.m_next_use_stamp;
+        self.m_next_use_stamp += 1;
+        use_stamp
     }
+}
 
-    pub fn set_capacity(&mut self) {
-        todo!()
-    }
+/**
+ * `NodeCache` entry
+ */
+struct CacheEntry {
+    m_node: Arc<VfsNode>,
+    m_use_stamp: u64
+}
+
+/**
+ * Usage statistics of the `NodeCache`
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct NodeCacheStats {
+    m_hits: usize,
+    m_ancestor_hits: usize,
+    m_misses: usize,
+    m_evictions: usize,
+    m_invalidations: usize,
+    m_entries: usize,
+    m_capacity: usize
+}
 
-    pub fn count(&self) -> usize {
-        self._map.len()
+impl NodeCacheStats /* Constructors */ {
+    /**
+     * Constructs zeroed `NodeCacheStats`
+     */
+    const fn new() -> Self {
+        Self { m_hits: 0,
+               m_ancestor_hits: 0,
+               m_misses: 0,
+               m_evictions: 0,
+               m_invalidations: 0,
+               m_entries: 0,
+               m_capacity: 0 }
     }
+}
 
-    pub fn clear(&mut self) {
-        self._map.clear()
+impl NodeCacheStats /* Getters */ {
+    /**
+     * Returns the amount of lookups which found the whole path
+     */
+    pub fn hits(&self) -> usize {
+        self.m_hits
     }
 
-    pub fn touch(&self, path: &[PathComponent]) {
-        todo!()
+    /**
+     * Returns the amount of lookups which found only an ancestor of the
+     * path
+     */
+    pub fn ancestor_hits(&self) -> usize {
+        self.m_ancestor_hits
     }
-}
 
-impl NodeTreeMap for NodeCache {
-    fn set(&mut self, path: &[PathComponent], node: &Arc<&dyn INode>) {
-        let path = PathWrapper::new(path);
-        let entry = Box::new(CacheEntry::new(node));
-        // Put the new entry at the top of the list.
-        self.push_list(entry.as_ref());
-
-        match self._map.insert(path, entry) {
-            None => { }
-            Some(old_entry) => {
-                // Unlink the old value from the rest of the list
-            }
-        }
+    /**
+     * Returns the amount of lookups which found nothing
+     */
+    pub fn misses(&self) -> usize {
+        self.m_misses
     }
 
-    fn get_exact(&self, path: &[PathComponent]) -> PartialNodeResult {
-        todo!()
+    /**
+     * Returns the amount of entries evicted to make room for new ones
+     */
+    pub fn evictions(&self) -> usize {
+        self.m_evictions
     }
 
-    fn get_nearest_ancestor(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult {
-        todo!()
+    /**
+     * Returns the amount of entries removed by the invalidations
+     */
+    pub fn invalidations(&self) -> usize {
+        self.m_invalidations
     }
 
-    fn get_best_match(&self, path: &[PathComponent], max_distance: usize) -> PartialNodeResult {
-        todo!()
+    /**
+     * Returns the amount of cached entries
+     */
+    pub fn entries(&self) -> usize {
+        self.m_entries
     }
 
-    fn remove(&mut self, path: &[PathComponent]) -> bool {
-        todo!()
+    /**
+     * Returns the maximum amount of entries
+     */
+    pub fn capacity(&self) -> usize {
+        self.m_capacity
     }
 }
@@ -1,4 +0,0 @@
-pub use inode::*;
-
-mod inode;
-
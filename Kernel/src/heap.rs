@@ -22,6 +22,9 @@ use crate::{
     dbg_print::DbgLevel,
     dbg_println,
     vm::{
+        mem_manager::MemManager,
+        MapFlags,
+        MapFlagsBits,
         Page4KiB,
         TPageSize
     }
@@ -70,8 +73,18 @@ fn kernel_heap_mem_supplier(requested_size: usize) -> Option<(NonNull<u8>, usize
         if C_ETERNAL_POOL_SIZE / Page4KiB::SIZE
            < SM_ETERNAL_POOL_USED_PAGES + requested_pages
         {
-            None /* TODO allocate a new kernel region
-                  *      MemManager::instance().allocate_kernel_region(...) */
+            /* the <MemManager> is not available during the early initialization */
+            let mem_manager = MemManager::try_instance()?;
+
+            /* allocate a new writeable kernel region for the heap */
+            let heap_region = mem_manager.allocate_kernel_region(requested_size,
+                                                                 MapFlags::new_zero()
+                                                                 | MapFlagsBits::Writeable
+                                                                 | MapFlagsBits::Global)?;
+            dbg_println!(DbgLevel::Trace, "New kernel heap region: {:?}", heap_region);
+
+            Some((NonNull::new_unchecked(heap_region.start.as_ptr_mut()),
+                  *heap_region.end - *heap_region.start))
         } else {
             let eternal_pool_used_size = SM_ETERNAL_POOL_USED_PAGES * Page4KiB::SIZE;
 
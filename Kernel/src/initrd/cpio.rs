@@ -0,0 +1,203 @@
+/*! `newc` cpio archives reader */
+
+use core::str;
+
+use api_data::error::class::OsErrorClass;
+use helps::align::align_up;
+
+/* magic of the <newc> format, without the CRC checksums */
+const C_CPIO_NEWC_MAGIC: &[u8] = b"070701";
+
+/* the header is the magic followed by 13 fields of 8 hex digits */
+const C_CPIO_HEADER_SIZE: usize = 110;
+const C_CPIO_FIELD_SIZE: usize = 8;
+
+/* indexes of the used header fields */
+const C_CPIO_MODE_FIELD: usize = 1;
+const C_CPIO_FILE_SIZE_FIELD: usize = 6;
+const C_CPIO_NAME_SIZE_FIELD: usize = 11;
+
+/* the names and the data are padded to this alignment */
+const C_CPIO_ALIGN: usize = 4;
+
+/* name of the last entry of the archive */
+const C_CPIO_TRAILER_NAME: &str = "TRAILER!!!";
+
+/* file type bits of the mode */
+const C_MODE_TYPE_MASK: u32 = 0o170_000;
+const C_MODE_TYPE_DIR: u32 = 0o040_000;
+const C_MODE_TYPE_FILE: u32 = 0o100_000;
+const C_MODE_TYPE_LINK: u32 = 0o120_000;
+
+/**
+ * Iterator over the `CpioEntry`s of a `newc` cpio archive.
+ *
+ * The iteration ends with the trailer entry or with the first error, which
+ * is `OsErrorClass::TypesNotMatch` for unknown headers,
+ * `OsErrorClass::EndOfDataReached` for truncated archives and
+ * `OsErrorClass::InvalidArgument` for malformed fields
+ */
+pub struct CpioArchive<'a> {
+    m_bytes: &'a [u8],
+    m_offset: usize,
+    m_is_ended: bool
+}
+
+impl<'a> CpioArchive<'a> /* Constructors */ {
+    /**
+     * Constructs a `CpioArchive` which reads the given bytes
+     */
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { m_bytes: bytes,
+               m_offset: 0,
+               m_is_ended: false }
+    }
+}
+
+impl<'a> CpioArchive<'a> /* Privates */ {
+    /**
+     * Decodes the entry at the current offset and moves after it.
+     *
+     * Returns `None` for the trailer entry
+     */
+    fn read_entry(&mut self) -> Result<Option<CpioEntry<'a>>, OsErrorClass> {
+        let header = self.bytes_at(self.m_offset, C_CPIO_HEADER_SIZE)?;
+        if &header[..C_CPIO_NEWC_MAGIC.len()] != C_CPIO_NEWC_MAGIC {
+            return Err(OsErrorClass::TypesNotMatch);
+        }
+
+        let mode = header_field(header, C_CPIO_MODE_FIELD)?;
+        let file_size = header_field(header, C_CPIO_FILE_SIZE_FIELD)? as usize;
+        let name_size = header_field(header, C_CPIO_NAME_SIZE_FIELD)? as usize;
+
+        /* the name is terminated by a null byte, included into its size */
+        let name_offset = self.m_offset + C_CPIO_HEADER_SIZE;
+        let raw_name = self.bytes_at(name_offset, name_size)?;
+        let name = match raw_name.split_last() {
+            Some((0, raw_name)) => {
+                str::from_utf8(raw_name).map_err(|_| OsErrorClass::InvalidArgument)?
+            },
+            _ => return Err(OsErrorClass::InvalidArgument)
+        };
+
+        let data_offset = align_up(name_offset + name_size, C_CPIO_ALIGN);
+        let data = self.bytes_at(data_offset, file_size)?;
+        self.m_offset = align_up(data_offset + file_size, C_CPIO_ALIGN);
+
+        if name == C_CPIO_TRAILER_NAME {
+            Ok(None)
+        } else {
+            Ok(Some(CpioEntry { m_path: name,
+                                m_mode: mode,
+                                m_data: data }))
+        }
+    }
+
+    /**
+     * Returns the given amount of bytes at the given offset
+     */
+    fn bytes_at(&self, offset: usize, size: usize) -> Result<&'a [u8], OsErrorClass> {
+        offset.checked_add(size)
+              .and_then(|end_offset| self.m_bytes.get(offset..end_offset))
+              .ok_or(OsErrorClass::EndOfDataReached)
+    }
+}
+
+impl<'a> Iterator for CpioArchive<'a> {
+    type Item = Result<CpioEntry<'a>, OsErrorClass>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.m_is_ended {
+            return None;
+        }
+
+        let entry = self.read_entry();
+        match entry {
+            Ok(Some(cpio_entry)) => Some(Ok(cpio_entry)),
+            Ok(None) => {
+                self.m_is_ended = true;
+                None
+            },
+            Err(os_error_class) => {
+                self.m_is_ended = true;
+                Some(Err(os_error_class))
+            }
+        }
+    }
+}
+
+/**
+ * File stored into a `CpioArchive`
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct CpioEntry<'a> {
+    m_path: &'a str,
+    m_mode: u32,
+    m_data: &'a [u8]
+}
+
+impl<'a> CpioEntry<'a> /* Getters */ {
+    /**
+     * Returns the path of the file, relative to the root of the archive
+     */
+    pub fn path(&self) -> &'a str {
+        self.m_path
+    }
+
+    /**
+     * Returns the `CpioEntryType` of the file
+     */
+    pub fn entry_type(&self) -> CpioEntryType {
+        match self.m_mode & C_MODE_TYPE_MASK {
+            C_MODE_TYPE_DIR => CpioEntryType::Dir,
+            C_MODE_TYPE_FILE => CpioEntryType::File,
+            C_MODE_TYPE_LINK => CpioEntryType::Link,
+            _ => CpioEntryType::Other
+        }
+    }
+
+    /**
+     * Returns the content of the file, which is the target path for the
+     * links
+     */
+    pub fn data(&self) -> &'a [u8] {
+        self.m_data
+    }
+
+    /**
+     * Returns the target path of the link
+     */
+    pub fn link_target(&self) -> Result<&'a str, OsErrorClass> {
+        str::from_utf8(self.m_data).map_err(|_| OsErrorClass::InvalidArgument)
+    }
+}
+
+/**
+ * Lists the types of `CpioEntry`
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum CpioEntryType {
+    Dir,
+    File,
+    Link,
+
+    /**
+     * Devices, pipes and sockets, which are not unpacked
+     */
+    Other
+}
+
+/**
+ * Decodes the hexadecimal header field with the given index
+ */
+fn header_field(header: &[u8], field_index: usize) -> Result<u32, OsErrorClass> {
+    let field_offset = C_CPIO_NEWC_MAGIC.len() + field_index * C_CPIO_FIELD_SIZE;
+
+    str::from_utf8(&header[field_offset..field_offset + C_CPIO_FIELD_SIZE])
+        .ok()
+        .and_then(|hex_field| u32::from_str_radix(hex_field, 16).ok())
+        .ok_or(OsErrorClass::InvalidArgument)
+}
@@ -0,0 +1,183 @@
+/*! Initial ramdisk */
+
+use alloc::{
+    string::String,
+    sync::Arc
+};
+use core::slice;
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::types::ObjType
+};
+
+use crate::{
+    addr::TAddress,
+    boot_info::{
+        BootInfo,
+        BootModule
+    },
+    dbg_print::DbgLevel,
+    dev::ram_disk::RamDisk,
+    filesystem::{
+        implementation::ramfs::RamFs,
+        FsResult,
+        Vfs
+    },
+    initrd::cpio::{
+        CpioArchive,
+        CpioEntryType
+    },
+    vm::mem_manager::MemManager
+};
+
+pub mod cpio;
+
+/* command line with which the bootloader module is marked as initrd */
+const C_INITRD_MODULE_CMD_LINE: &str = "initrd";
+
+/**
+ * Unpacker of the cpio archive which the bootloader loads as module.
+ *
+ * The archive is copied into a `RamFs` mounted at `/`, which allows the
+ * kernel to start the first userland process without any disk driver and
+ * gives to it a writable root
+ */
+pub struct Initrd {
+    m_files_count: usize,
+    m_dirs_count: usize,
+    m_links_count: usize
+}
+
+impl Initrd /* Static Functions */ {
+    /**
+     * Mounts a new `RamFs` at `/` and unpacks into it the `BootModule`
+     * marked as `initrd`, or the first one which is not a disk when no
+     * module is marked
+     */
+    pub fn mount_root() {
+        if let Err(os_error_class) =
+            Vfs::instance().mount("/", Arc::new(RamFs::new(None)))
+        {
+            dbg_println!(DbgLevel::Err,
+                         "Failed to mount the root RamFs: {}",
+                         os_error_class);
+            return;
+        }
+
+        let boot_modules = BootInfo::instance().boot_modules();
+        let is_initrd_module = |boot_module: &&BootModule| {
+            boot_module.cmd_line().trim() == C_INITRD_MODULE_CMD_LINE
+        };
+        let is_not_disk_module =
+            |boot_module: &&BootModule| !RamDisk::is_disk_module(boot_module);
+        let initrd_module =
+            boot_modules.iter()
+                        .find(is_initrd_module)
+                        .or_else(|| boot_modules.iter().find(is_not_disk_module));
+
+        if let Some(initrd_module) = initrd_module {
+            let mut initrd = Self { m_files_count: 0,
+                                    m_dirs_count: 0,
+                                    m_links_count: 0 };
+
+            match initrd.unpack(Self::module_bytes(initrd_module)) {
+                Ok(_) => {
+                    dbg_println!(DbgLevel::Info,
+                                 "Initrd: {} files, {} directories, {} links",
+                                 initrd.m_files_count,
+                                 initrd.m_dirs_count,
+                                 initrd.m_links_count)
+                },
+                Err(os_error_class) => {
+                    dbg_println!(DbgLevel::Err,
+                                 "Failed to unpack the initrd: {}",
+                                 os_error_class)
+                }
+            }
+        } else {
+            dbg_println!(DbgLevel::Warn, "No initrd module given by the bootloader");
+        }
+    }
+}
+
+impl Initrd /* Privates */ {
+    /**
+     * Copies the entries of the given cpio archive into the `Vfs`,
+     * creating the missing parent directories.
+     *
+     * The directories listed after their children are already existing
+     * and are kept as they are
+     */
+    fn unpack(&mut self, archive_bytes: &[u8]) -> FsResult<()> {
+        let vfs = Vfs::instance();
+
+        for cpio_entry in CpioArchive::new(archive_bytes) {
+            let cpio_entry = cpio_entry?;
+
+            /* the archives created from a directory use "./" as prefix */
+            let path = cpio_entry.path().trim_start_matches("./").trim_matches('/');
+            if path.is_empty() || path == "." {
+                continue;
+            }
+
+            let (parent_path, _) = path.rsplit_once('/').unwrap_or(("", path));
+            let abs_path = format!("/{}", path);
+            match cpio_entry.entry_type() {
+                CpioEntryType::Dir => self.create_dirs(path)?,
+                CpioEntryType::File => {
+                    self.create_dirs(parent_path)?;
+                    vfs.create(&abs_path, ObjType::File)?
+                       .write_at(0, cpio_entry.data())?;
+                    self.m_files_count += 1;
+                },
+                CpioEntryType::Link => {
+                    self.create_dirs(parent_path)?;
+                    vfs.create_link(&abs_path, cpio_entry.link_target()?)?;
+                    self.m_links_count += 1;
+                },
+                CpioEntryType::Other => {
+                    dbg_println!(DbgLevel::Debug,
+                                 "Initrd: skipping special file {}",
+                                 path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Creates the directories of the given relative path which don't exist
+     */
+    fn create_dirs(&mut self, path: &str) -> FsResult<()> {
+        let mut dir_path = String::new();
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            dir_path.push('/');
+            dir_path.push_str(name);
+
+            match Vfs::instance().create(&dir_path, ObjType::Dir) {
+                Ok(_) => self.m_dirs_count += 1,
+                Err(OsErrorClass::IdentifierNotAvailable) => {},
+                Err(os_error_class) => return Err(os_error_class)
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the content of the given `BootModule` through the physical
+     * memory mapping
+     */
+    fn module_bytes(boot_module: &BootModule) -> &'static [u8] {
+        let phys_range = boot_module.phys_range();
+        let virt_addr = MemManager::instance().layout_manager()
+                                              .phys_addr_to_virt_addr(phys_range.start);
+
+        /* the frames of the modules are reserved for the whole kernel lifetime */
+        unsafe {
+            slice::from_raw_parts(virt_addr.as_ptr::<u8>(),
+                                  *phys_range.end - *phys_range.start)
+        }
+    }
+}
@@ -0,0 +1,346 @@
+/*! Kernel interrupts management */
+
+use core::{
+    fmt,
+    fmt::Debug,
+    mem,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    arch::{
+        idt::C_INTR_VECTORS_COUNT,
+        intr::{
+            HwIntrFrame,
+            C_FIRST_IRQ_INTR_VECTOR
+        }
+    },
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    dbg_print::DbgLevel,
+    dbg_println
+};
+
+/* placeholder of the vectors without an <IntrHandler> */
+#[allow(clippy::declare_interior_mutable_const)]
+const C_NO_INTR_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/* <IntrHandler>s registered for each vector, stored as raw function pointers */
+static SM_INTR_HANDLERS: [AtomicUsize; C_INTR_VECTORS_COUNT] =
+    [C_NO_INTR_HANDLER; C_INTR_VECTORS_COUNT];
+
+/* <None> until <IntrManager::set_controller()> is called */
+static mut SM_INTR_CONTROLLER: Option<&'static dyn TInterruptController> = None;
+
+/**
+ * Index of an interrupt into the interrupts table of the CPU
+ */
+pub type IntrVector = u8;
+
+/**
+ * Number of a device interrupt line as seen by the `TInterruptController`.
+ *
+ * The lines below 16 are the legacy ISA IRQs
+ */
+pub type Irq = u32;
+
+/**
+ * Handler of a device interrupt, called with the interrupts disabled
+ */
+pub type IntrHandler = fn(intr_frame: &mut IntrFrame);
+
+/**
+ * Registry of the `IntrHandler`s for the device interrupt vectors.
+ *
+ * The CPU exceptions vectors are reserved to the architecture, which
+ * dispatches them by itself
+ */
+pub struct IntrManager;
+
+impl IntrManager /* Static Functions */ {
+    /**
+     * Registers the given `IntrHandler` for the given `IntrVector`.
+     *
+     * Returns `false` if the `IntrVector` is reserved to the CPU exceptions
+     * or it already has an `IntrHandler`
+     */
+    pub fn register_handler(intr_vector: IntrVector, intr_handler: IntrHandler) -> bool {
+        if intr_vector < C_FIRST_IRQ_INTR_VECTOR {
+            return false;
+        }
+
+        SM_INTR_HANDLERS[intr_vector as usize].compare_exchange(0,
+                                                                intr_handler as usize,
+                                                                Ordering::SeqCst,
+                                                                Ordering::SeqCst)
+                                              .is_ok()
+    }
+
+    /**
+     * Routes the given `Irq` to the executing `Cpu`, registers the given
+     * `IntrHandler` for its `IntrVector` and unmasks it.
+     *
+     * Returns the `IntrVector` on which the `Irq` is delivered
+     */
+    pub fn register_irq_handler(irq: Irq,
+                                intr_handler: IntrHandler)
+                                -> Option<IntrVector> {
+        let intr_controller = Self::controller()?;
+        let intr_vector = intr_controller.intr_vector_of(irq)?;
+
+        if !intr_controller.route(irq, Cpu::current().id()) {
+            return None;
+        }
+        if !Self::register_handler(intr_vector, intr_handler) {
+            return None;
+        }
+
+        intr_controller.unmask(irq);
+        Some(intr_vector)
+    }
+
+    /**
+     * Masks the given `Irq` and removes its `IntrHandler`
+     */
+    pub fn unregister_irq_handler(irq: Irq) {
+        if let Some(intr_controller) = Self::controller() {
+            intr_controller.mask(irq);
+            if let Some(intr_vector) = intr_controller.intr_vector_of(irq) {
+                Self::unregister_handler(intr_vector);
+            }
+        }
+    }
+
+    /**
+     * Removes the `IntrHandler` of the given `IntrVector`, the interrupts
+     * which arrive after are reported as unhandled
+     */
+    pub fn unregister_handler(intr_vector: IntrVector) {
+        SM_INTR_HANDLERS[intr_vector as usize].store(0, Ordering::SeqCst);
+    }
+
+    /**
+     * Returns whether the given `IntrVector` has an `IntrHandler`
+     */
+    pub fn is_registered(intr_vector: IntrVector) -> bool {
+        Self::handler_of(intr_vector).is_some()
+    }
+
+    /**
+     * Calls the `IntrHandler` registered for the vector of the given
+     * `IntrFrame`.
+     *
+     * Called by the architecture dependent interrupt code for the device
+     * interrupt vectors, which are then acknowledged to the
+     * `TInterruptController`. Returns `false` when no `IntrHandler` is
+     * registered
+     */
+    pub fn dispatch(intr_frame: &mut IntrFrame) -> bool {
+        let intr_vector = intr_frame.intr_vector();
+
+        let handled = if let Some(intr_handler) = Self::handler_of(intr_vector) {
+            intr_handler(intr_frame);
+            true
+        } else {
+            dbg_println!(DbgLevel::Warn, "Unhandled interrupt vector {}", intr_vector);
+            false
+        };
+
+        if let Some(intr_controller) = Self::controller() {
+            intr_controller.end_of_interrupt(intr_vector);
+        }
+        handled
+    }
+
+    /**
+     * Returns the active `TInterruptController`
+     */
+    pub fn controller() -> Option<&'static dyn TInterruptController> {
+        unsafe { SM_INTR_CONTROLLER }
+    }
+
+    /**
+     * Selects the `TInterruptController` which delivers the device
+     * interrupts.
+     *
+     * Called once by the architecture dependent code
+     */
+    pub fn set_controller(intr_controller: &'static dyn TInterruptController) {
+        unsafe {
+            assert!(SM_INTR_CONTROLLER.is_none(),
+                    "Called IntrManager::set_controller() twice");
+
+            dbg_println!(DbgLevel::Info,
+                         "Using {} as interrupt controller",
+                         intr_controller.name());
+            SM_INTR_CONTROLLER = Some(intr_controller);
+        }
+    }
+
+    /**
+     * Returns the `IntrHandler` registered for the given `IntrVector`
+     */
+    fn handler_of(intr_vector: IntrVector) -> Option<IntrHandler> {
+        let raw_intr_handler =
+            SM_INTR_HANDLERS[intr_vector as usize].load(Ordering::SeqCst);
+        if raw_intr_handler != 0 {
+            /* the non-zero values are stored only by <register_handler()> */
+            Some(unsafe { mem::transmute::<usize, IntrHandler>(raw_intr_handler) })
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * Architecture independent view of the CPU state saved on interrupt entry.
+ *
+ * The changes to the frame are restored when the interrupt returns
+ */
+#[repr(transparent)]
+pub struct IntrFrame {
+    m_hw_intr_frame: HwIntrFrame
+}
+
+impl IntrFrame /* Constructors */ {
+    /**
+     * Wraps the `HwIntrFrame` saved by the architecture dependent stubs
+     */
+    pub fn from_hw_mut(hw_intr_frame: &mut HwIntrFrame) -> &mut Self {
+        /* safe since <IntrFrame> is transparent over <HwIntrFrame> */
+        unsafe { &mut *(hw_intr_frame as *mut HwIntrFrame as *mut Self) }
+    }
+}
+
+impl IntrFrame /* Getters */ {
+    /**
+     * Returns the `IntrVector` of the interrupt
+     */
+    pub fn intr_vector(&self) -> IntrVector {
+        self.m_hw_intr_frame.intr_vector()
+    }
+
+    /**
+     * Returns the error code pushed by the CPU, zero when not provided
+     */
+    pub fn error_code(&self) -> usize {
+        self.m_hw_intr_frame.error_code()
+    }
+
+    /**
+     * Returns the `VirtAddr` of the interrupted instruction
+     */
+    pub fn instr_virt_addr(&self) -> VirtAddr {
+        self.m_hw_intr_frame.instr_virt_addr()
+    }
+
+    /**
+     * Returns the stack pointer of the interrupted code
+     */
+    pub fn stack_virt_addr(&self) -> VirtAddr {
+        self.m_hw_intr_frame.stack_virt_addr()
+    }
+
+    /**
+     * Returns whether the interrupted code was executing in userspace
+     */
+    pub fn is_user(&self) -> bool {
+        self.m_hw_intr_frame.is_user()
+    }
+
+    /**
+     * Returns the reference to the architecture dependent frame
+     */
+    pub fn hw_intr_frame(&self) -> &HwIntrFrame {
+        &self.m_hw_intr_frame
+    }
+
+    /**
+     * Returns the mutable reference to the architecture dependent frame
+     */
+    pub fn hw_intr_frame_mut(&mut self) -> &mut HwIntrFrame {
+        &mut self.m_hw_intr_frame
+    }
+}
+
+impl Debug for IntrFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x?}", self.m_hw_intr_frame)
+    }
+}
+
+/**
+ * Interface on which the `IntrFrame` relies to read the hardware saved
+ * CPU state
+ */
+pub trait THwIntrFrame {
+    /**
+     * Returns the `IntrVector` of the interrupt
+     */
+    fn intr_vector(&self) -> IntrVector;
+
+    /**
+     * Returns the error code pushed by the CPU, zero when not provided
+     */
+    fn error_code(&self) -> usize;
+
+    /**
+     * Returns the `VirtAddr` of the interrupted instruction
+     */
+    fn instr_virt_addr(&self) -> VirtAddr;
+
+    /**
+     * Returns the stack pointer of the interrupted code
+     */
+    fn stack_virt_addr(&self) -> VirtAddr;
+
+    /**
+     * Returns whether the interrupted code was executing in userspace
+     */
+    fn is_user(&self) -> bool;
+}
+
+/**
+ * Interface of the hardware which delivers the device interrupts to the
+ * CPUs
+ */
+pub trait TInterruptController {
+    /**
+     * Returns the name of the controller, for debug purposes
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Returns the `IntrVector` on which the given `Irq` is delivered,
+     * `None` if this controller can't deliver it
+     */
+    fn intr_vector_of(&self, irq: Irq) -> Option<IntrVector>;
+
+    /**
+     * Routes the given `Irq` to the `Cpu` with the given `CpuId`.
+     *
+     * The `Irq` is left masked, returns `false` when it can't be routed
+     */
+    fn route(&self, irq: Irq, cpu_id: CpuId) -> bool;
+
+    /**
+     * Stops the delivery of the given `Irq`
+     */
+    fn mask(&self, irq: Irq);
+
+    /**
+     * Enables the delivery of the given `Irq`
+     */
+    fn unmask(&self, irq: Irq);
+
+    /**
+     * Acknowledges the end of the handling of the given `IntrVector`
+     */
+    fn end_of_interrupt(&self, intr_vector: IntrVector);
+}
@@ -23,15 +23,34 @@ use symbols::code_symbols::CodeSymbols;
 
 use crate::{
     boot_info::BootInfo,
-    cpu::Cpu,
+    cpu::{
+        Cpu,
+        CpuId
+    },
     dbg_print::{
         dbg_print_init,
         DbgLevel
     },
-    dev::DevManager,
+    dev::{
+        ram_disk::RamDisk,
+        DevManager
+    },
+    filesystem::{
+        implementation::ramfs::RamFs,
+        Vfs
+    },
     heap::kernel_heap_init_eternal_pool,
+    initrd::Initrd,
+    task::{
+        init_launcher::InitLauncher,
+        sched::Scheduler
+    },
+    time::TimeManager,
     version::KERNEL_VERSION,
-    vm::mem_manager::MemManager
+    vm::{
+        kern_text_slide::KernTextSlide,
+        mem_manager::MemManager
+    }
 };
 
 mod addr;
@@ -41,13 +60,31 @@ mod cpu;
 mod dbg_print;
 mod dev;
 mod heap;
+mod initrd;
+mod intr;
 mod panic;
+mod syscall;
+mod task;
+mod time;
 mod version;
 mod vm;
 mod filesystem;
 mod info;
 mod mem;
 
+/**
+ * Rust entry-point of the kernel image slide.
+ *
+ * `Kernel/arch/<arch_name>/kernel_start.S` calls it while the kernel image
+ * is still executed at its link address, then maps the image at the
+ * returned slide, applies the relocations and calls the slid
+ * `kernel_rust_start()`
+ */
+#[no_mangle]
+pub extern "C" fn kernel_choose_text_slide(raw_boot_info_ptr: *const u8) -> usize {
+    KernTextSlide::choose(raw_boot_info_ptr)
+}
+
 /**
  * Rust entry-point.
  *
@@ -73,7 +110,7 @@ pub extern "C" fn kernel_rust_start(raw_boot_info_ptr: *const u8) -> ! {
 
     /* initialize the kernel symbols */
     dbg_println!(DbgLevel::Trace, "Initializing Kernel Symbols...");
-    CodeSymbols::init_instance();
+    CodeSymbols::init_instance(KernTextSlide::slide());
 
     /* initialize the CPU management for the bootstrap CPU */
     dbg_println!(DbgLevel::Trace, "Initializing CPU Management...");
@@ -87,6 +124,27 @@ pub extern "C" fn kernel_rust_start(raw_boot_info_ptr: *const u8) -> ! {
     dbg_println!(DbgLevel::Trace, "Initializing Interrupts Management...");
     Cpu::init_interrupts_for_this();
 
+    /* calibrate the clocks, the startup of the secondary CPUs needs the delays */
+    dbg_println!(DbgLevel::Trace, "Initializing Time Management...");
+    TimeManager::init();
+
+    /* continue as the first thread of this CPU, the scheduler ticks need the timers */
+    dbg_println!(DbgLevel::Trace, "Initializing Scheduler...");
+    Scheduler::init_for_this_cpu("kernel_init");
+
+    /* wake up the secondary CPUs, which enter <kernel_ap_rust_start()> */
+    dbg_println!(DbgLevel::Trace, "Starting Secondary CPUs...");
+    Cpu::start_aps();
+
+    /* unpack the initial ramdisk into the root filesystem, mount the /Tmp and the
+     * disk images given as modules
+     */
+    dbg_println!(DbgLevel::Trace, "Unpacking Initial Ramdisk...");
+    Vfs::early_init();
+    Initrd::mount_root();
+    RamFs::mount_tmp();
+    RamDisk::mount_boot_modules();
+
     /* FIXME debug printing to remove */
     {
         dbg_println!(DbgLevel::Debug,
@@ -116,5 +174,24 @@ pub extern "C" fn kernel_rust_start(raw_boot_info_ptr: *const u8) -> ! {
                      "Interrupts are enabled: {}",
                      Cpu::current().are_interrupts_enabled());
     }
-    panic!("TODO implement the remaining code");
+
+    /* run the first userland process, the system goes down when it exits */
+    InitLauncher::run_and_shutdown()
+}
+
+/**
+ * Rust entry-point of the secondary CPUs.
+ *
+ * Each CPU reaches this function from the startup trampoline
+ * `Kernel/arch/<arch_name>/ap_trampoline.S`, with its own stack
+ */
+#[no_mangle]
+pub extern "C" fn kernel_ap_rust_start(cpu_id: CpuId) -> ! {
+    /* initialize the CPU and the interrupts management for this CPU */
+    Cpu::init_ap(cpu_id);
+    dbg_println!(DbgLevel::Debug, "Cpu {} online", cpu_id);
+
+    /* leave this CPU to its idle thread until some other thread is ready */
+    Scheduler::init_for_this_cpu(format!("kernel_ap{}", cpu_id).as_str());
+    Scheduler::exit_current()
 }
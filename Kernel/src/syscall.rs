@@ -0,0 +1,142 @@
+/*! Kernel system calls dispatching */
+
+use api_data::{
+    error::{
+        class::OsErrorClass,
+        OsError
+    },
+    sys::{
+        fn_path::KernFnPath,
+        SysCallPayload
+    }
+};
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    dbg_print::DbgLevel,
+    dbg_println,
+    task::{
+        kern_fn::task_kern_fn,
+        sched::Scheduler
+    },
+    vm::user_copy::UserCopy
+};
+
+/* number of the <KernFnPath> classes, <KernFnPath::Invalid> excluded */
+const C_KERN_FN_CLASSES_COUNT: usize = KernFnPath::CLASSES_COUNT;
+
+/* kernel functions handlers indexed by <KernFnPath::raw_fn_class()> */
+static SM_KERN_FN_CLASS_HANDLERS: [Option<KernFnClassHandler>; C_KERN_FN_CLASSES_COUNT] =
+    [None,               /* KernHandle */
+     None,               /* ObjConfig */
+     None,               /* TaskConfig */
+     None,               /* OsEntConfig */
+     None,               /* Object */
+     Some(task_kern_fn), /* Task */
+     None,               /* Device */
+     None,               /* Dir */
+     None,               /* File */
+     None,               /* IpcChan */
+     None,               /* Link */
+     None,               /* MMap */
+     None,               /* Mutex */
+     None,               /* Instant */
+     None,               /* Path */
+     None,               /* OsEntity */
+     None,               /* OsUser */
+     None,               /* OsGroup */
+     None,               /* Proc */
+     None                /* Thread */];
+
+/**
+ * Kernel function class handler, which receives the validated copy of the
+ * `SysCallPayload` and returns the result or the `OsErrorClass` of the
+ * failure
+ */
+pub type KernFnClassHandler =
+    fn(sys_call_payload: &SysCallPayload) -> Result<usize, OsErrorClass>;
+
+/**
+ * Architecture independent system calls dispatcher
+ */
+pub struct SysCallManager;
+
+impl SysCallManager /* Static Functions */ {
+    /**
+     * Serves the system call which payload is at the given userspace
+     * address.
+     *
+     * The payload is copied into the Kernel memory before its dispatching
+     * and only its output fields are copied back, so the userspace can't
+     * change it while the kernel function runs. A payload which the
+     * userspace can't read is ignored
+     */
+    pub fn dispatch(raw_payload_addr: VirtAddr) {
+        let mut raw_payload = match UserCopy::read_raw::<SysCallPayload>(raw_payload_addr)
+        {
+            Some(raw_payload) => raw_payload,
+            None => {
+                dbg_println!(DbgLevel::Warn,
+                             "Ignored system call with invalid payload at {:#x}",
+                             *raw_payload_addr);
+                return;
+            }
+        };
+        let sys_call_payload =
+            unsafe { SysCallPayload::read_from_raw(raw_payload.as_ptr()) };
+
+        let result = match &sys_call_payload {
+            Some(sys_call_payload) => Self::call_kern_fn(sys_call_payload),
+            None => Err(OsErrorClass::InvalidArgument)
+        };
+        let mut sys_call_payload = sys_call_payload.unwrap_or_default();
+
+        match result {
+            Ok(value) => sys_call_payload.set_result(value),
+            Err(error_class) => {
+                let thread_id =
+                    Scheduler::current_thread().map_or(0, |thread| thread.id());
+
+                /* there are no processes yet, the threads belong to the Kernel */
+                *sys_call_payload.error_mut() =
+                    OsError::new(error_class,
+                                 sys_call_payload.kern_fn_path(),
+                                 sys_call_payload.raw_handle(),
+                                 0,
+                                 thread_id,
+                                 None);
+            }
+        }
+
+        /* the userspace could have unmapped the payload meanwhile */
+        unsafe {
+            sys_call_payload.write_output_to_raw(raw_payload.as_mut_ptr());
+        }
+        if !UserCopy::write_raw(raw_payload_addr,
+                                &raw_payload,
+                                SysCallPayload::output_bytes_range())
+        {
+            dbg_println!(DbgLevel::Warn,
+                         "Lost the result of the system call with payload at {:#x}",
+                         *raw_payload_addr);
+        }
+    }
+}
+
+impl SysCallManager /* Privates */ {
+    /**
+     * Calls the `KernFnClassHandler` of the `KernFnPath` of the given
+     * `SysCallPayload`
+     */
+    fn call_kern_fn(sys_call_payload: &SysCallPayload) -> Result<usize, OsErrorClass> {
+        let raw_fn_class = sys_call_payload.kern_fn_path().raw_fn_class() as usize;
+
+        match SM_KERN_FN_CLASS_HANDLERS.get(raw_fn_class).copied().flatten() {
+            Some(class_handler) => class_handler(sys_call_payload),
+            None => Err(OsErrorClass::OperationNotEnabled)
+        }
+    }
+}
@@ -0,0 +1,314 @@
+/*! ELF64 executables parser */
+
+use core::{
+    convert::TryInto,
+    ops::Range
+};
+
+use api_data::error::class::OsErrorClass;
+
+use crate::{
+    arch::task::C_HW_ELF_MACHINE,
+    vm::{
+        layout_manager::LayoutManager,
+        MapFlags,
+        MapFlagsBits
+    }
+};
+
+/* sizes of the ELF64 file header and of each program header */
+const C_ELF_HEADER_SIZE: usize = 64;
+const C_ELF_PROG_HEADER_SIZE: usize = 56;
+
+/* identification bytes: magic, 64bit class, little endian data, version 1 */
+const C_ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const C_ELF_CLASS_64: u8 = 2;
+const C_ELF_DATA_LSB: u8 = 1;
+const C_ELF_VERSION_CURRENT: u8 = 1;
+
+/* only the statically linked executables are supported */
+const C_ELF_TYPE_EXEC: u16 = 2;
+
+/* program header types */
+const C_PT_LOAD: u32 = 1;
+const C_PT_DYNAMIC: u32 = 2;
+const C_PT_INTERP: u32 = 3;
+const C_PT_TLS: u32 = 7;
+
+/* program header flags */
+const C_PF_X: u32 = 1 << 0;
+const C_PF_W: u32 = 1 << 1;
+
+/**
+ * Validated ELF64 executable, borrowed from the memory which contains it.
+ *
+ * The file header and all the program headers are checked at the
+ * construction, so the segments can be read without further bounds checks
+ */
+pub struct ElfFile<'a> {
+    m_bytes: &'a [u8],
+    m_entry_point: usize,
+    m_prog_headers_offset: usize,
+    m_prog_headers_count: usize
+}
+
+impl<'a> ElfFile<'a> /* Constructors */ {
+    /**
+     * Parses and validates the given ELF64 executable.
+     *
+     * Returns `OsErrorClass::TypesNotMatch` if it is not a static
+     * executable for this machine, `OsErrorClass::OperationNotEnabled` if
+     * it requires the dynamic linking, `OsErrorClass::EndOfDataReached` if
+     * it is truncated and `OsErrorClass::InvalidArgument` if its headers
+     * are inconsistent
+     */
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, OsErrorClass> {
+        if bytes.len() < C_ELF_HEADER_SIZE {
+            return Err(OsErrorClass::EndOfDataReached);
+        }
+
+        /* check the identification and the target of the executable */
+        if bytes[0..4] != C_ELF_MAGIC
+           || bytes[4] != C_ELF_CLASS_64
+           || bytes[5] != C_ELF_DATA_LSB
+           || bytes[6] != C_ELF_VERSION_CURRENT
+           || read_u16(bytes, 16) != C_ELF_TYPE_EXEC
+           || read_u16(bytes, 18) != C_HW_ELF_MACHINE
+        {
+            return Err(OsErrorClass::TypesNotMatch);
+        }
+
+        let elf_file = Self { m_bytes: bytes,
+                              m_entry_point: read_u64(bytes, 24) as usize,
+                              m_prog_headers_offset: read_u64(bytes, 32) as usize,
+                              m_prog_headers_count: read_u16(bytes, 56) as usize };
+
+        /* the program headers table must be entirely into the file */
+        let prog_headers_size = elf_file.m_prog_headers_count * C_ELF_PROG_HEADER_SIZE;
+        if read_u16(bytes, 54) as usize != C_ELF_PROG_HEADER_SIZE
+           || elf_file.m_prog_headers_count == 0
+        {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+        match elf_file.m_prog_headers_offset.checked_add(prog_headers_size) {
+            Some(prog_headers_end) if prog_headers_end <= bytes.len() => {},
+            _ => return Err(OsErrorClass::EndOfDataReached)
+        }
+
+        let mut tls_segments_count = 0;
+        for prog_header in elf_file.prog_headers() {
+            prog_header.validate(bytes.len())?;
+
+            if prog_header.is_tls() {
+                tls_segments_count += 1;
+            }
+        }
+        if tls_segments_count > 1 {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+        Ok(elf_file)
+    }
+}
+
+impl<'a> ElfFile<'a> /* Getters */ {
+    /**
+     * Returns the virtual address of the first instruction
+     */
+    pub fn entry_point(&self) -> usize {
+        self.m_entry_point
+    }
+
+    /**
+     * Returns an iterator over the `ElfProgHeader`s
+     */
+    pub fn prog_headers(&self) -> impl Iterator<Item = ElfProgHeader> + '_ {
+        (0..self.m_prog_headers_count).map(move |prog_header_index| {
+            let prog_header_offset =
+                self.m_prog_headers_offset + prog_header_index * C_ELF_PROG_HEADER_SIZE;
+
+            ElfProgHeader::from_bytes(&self.m_bytes[prog_header_offset..])
+        })
+    }
+
+    /**
+     * Returns the `ElfProgHeader` of the thread local storage template
+     */
+    pub fn tls_prog_header(&self) -> Option<ElfProgHeader> {
+        self.prog_headers().find(ElfProgHeader::is_tls)
+    }
+
+    /**
+     * Returns the bytes stored into the file for the given `ElfProgHeader`
+     */
+    pub fn segment_bytes(&self, prog_header: &ElfProgHeader) -> &'a [u8] {
+        &self.m_bytes
+            [prog_header.m_offset..prog_header.m_offset + prog_header.m_file_size]
+    }
+}
+
+/**
+ * ELF64 program header, which describes a segment of the executable
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct ElfProgHeader {
+    m_type: u32,
+    m_flags: u32,
+    m_offset: usize,
+    m_virt_addr: usize,
+    m_file_size: usize,
+    m_mem_size: usize,
+    m_align: usize
+}
+
+impl ElfProgHeader /* Constructors */ {
+    /**
+     * Decodes the `ElfProgHeader` at the beginning of the given bytes
+     */
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self { m_type: read_u32(bytes, 0),
+               m_flags: read_u32(bytes, 4),
+               m_offset: read_u64(bytes, 8) as usize,
+               m_virt_addr: read_u64(bytes, 16) as usize,
+               m_file_size: read_u64(bytes, 32) as usize,
+               m_mem_size: read_u64(bytes, 40) as usize,
+               m_align: read_u64(bytes, 48) as usize }
+    }
+}
+
+impl ElfProgHeader /* Getters */ {
+    /**
+     * Returns whether this segment must be loaded into memory
+     */
+    pub fn is_load(&self) -> bool {
+        self.m_type == C_PT_LOAD
+    }
+
+    /**
+     * Returns whether this segment is the thread local storage template
+     */
+    pub fn is_tls(&self) -> bool {
+        self.m_type == C_PT_TLS
+    }
+
+    /**
+     * Returns whether this segment contains instructions
+     */
+    pub fn is_executable(&self) -> bool {
+        self.m_flags & C_PF_X != 0
+    }
+
+    /**
+     * Returns the `MapFlags` which give to the memory of this segment the
+     * requested permissions
+     */
+    pub fn map_flags(&self) -> MapFlags {
+        let mut map_flags = MapFlags::new_zero();
+        if self.m_flags & C_PF_W != 0 {
+            map_flags.set_enabled(MapFlagsBits::Writeable);
+        }
+        if self.is_executable() {
+            map_flags.set_enabled(MapFlagsBits::Executable);
+        }
+        map_flags
+    }
+
+    /**
+     * Returns the virtual address of this segment
+     */
+    pub fn virt_addr(&self) -> usize {
+        self.m_virt_addr
+    }
+
+    /**
+     * Returns the virtual `Range` of the memory of this segment
+     */
+    pub fn virt_range(&self) -> Range<usize> {
+        self.m_virt_addr..self.m_virt_addr + self.m_mem_size
+    }
+
+    /**
+     * Returns the amount of bytes of this segment stored into the file
+     */
+    pub fn file_size(&self) -> usize {
+        self.m_file_size
+    }
+
+    /**
+     * Returns the amount of bytes of this segment in memory, the ones
+     * after `file_size()` are zeroed
+     */
+    pub fn mem_size(&self) -> usize {
+        self.m_mem_size
+    }
+
+    /**
+     * Returns the alignment of this segment, at least 1
+     */
+    pub fn align(&self) -> usize {
+        self.m_align.max(1)
+    }
+}
+
+impl ElfProgHeader /* Privates */ {
+    /**
+     * Checks this `ElfProgHeader` against the size of its file and the
+     * user space bounds
+     */
+    fn validate(&self, file_size: usize) -> Result<(), OsErrorClass> {
+        match self.m_type {
+            C_PT_DYNAMIC | C_PT_INTERP => return Err(OsErrorClass::OperationNotEnabled),
+            C_PT_LOAD | C_PT_TLS => {},
+            _ => return Ok(())
+        }
+
+        match self.m_offset.checked_add(self.m_file_size) {
+            Some(segment_end) if segment_end <= file_size => {},
+            _ => return Err(OsErrorClass::EndOfDataReached)
+        }
+
+        /* the segment must fit into the user space with its zeroed tail */
+        let fits_user_space = match self.m_virt_addr.checked_add(self.m_mem_size) {
+            Some(segment_end) => {
+                self.m_virt_addr >= LayoutManager::USER_SPACE_BEGIN
+                && segment_end <= LayoutManager::USER_SPACE_END
+            },
+            None => false
+        };
+        if !fits_user_space
+           || self.m_file_size > self.m_mem_size
+           || !self.align().is_power_of_two()
+        {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        /* the pages are mapped at the same offset which they have into the file */
+        if self.is_load()
+           && self.m_virt_addr % self.align() != self.m_offset % self.align()
+        {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Reads the little endian `u16` at the given offset
+ */
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+/**
+ * Reads the little endian `u32` at the given offset
+ */
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/**
+ * Reads the little endian `u64` at the given offset
+ */
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
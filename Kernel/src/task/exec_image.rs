@@ -0,0 +1,371 @@
+/*! Userland executables loader */
+
+use alloc::vec::Vec;
+use core::{
+    mem,
+    ops::Range
+};
+
+use api_data::error::class::OsErrorClass;
+use helps::{
+    align::{
+        align_down,
+        align_up
+    },
+    dbg::{
+        C_KIB,
+        C_MIB
+    }
+};
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    task::elf::{
+        ElfFile,
+        ElfProgHeader
+    },
+    vm::{
+        addr_space::AddressSpace,
+        layout_manager::LayoutManager,
+        MapFlags,
+        MapFlagsBits,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/* size of the lazily backed stack of the first thread */
+const C_USER_STACK_SIZE: usize = 8 * C_MIB;
+
+/* maximum size of the command line arguments copied onto the stack */
+const C_USER_STACK_ARGS_MAX: usize = 64 * C_KIB;
+
+/* the System V ABI wants the stack pointer 16 bytes aligned at the entry */
+const C_USER_STACK_ALIGN: usize = 16;
+
+/* the thread control block contains only the pointer to itself */
+const C_TLS_TCB_SIZE: usize = mem::size_of::<usize>();
+
+/**
+ * Userland executable loaded into a new `AddressSpace`, ready to be
+ * executed by its first thread.
+ *
+ * The initial stack follows the System V ABI: the stack pointer points to
+ * `argc`, followed by the `argv` pointers, terminated by a null one, an
+ * empty environment and an empty auxiliary vector, so the `_start` of the
+ * executable can give `argc` and `argv` to `lang_start()`
+ */
+pub struct ExecImage {
+    m_addr_space: AddressSpace,
+    m_entry_point: VirtAddr,
+    m_stack_ptr: VirtAddr,
+    m_args_count: usize,
+    m_tls_template: Option<TlsTemplate>,
+    m_thread_ptr: Option<VirtAddr>
+}
+
+impl ExecImage /* Constructors */ {
+    /**
+     * Loads the given ELF64 executable into a new `AddressSpace` and
+     * copies the given command line arguments onto the stack of its first
+     * thread.
+     *
+     * Returns the `OsErrorClass` of `ElfFile::parse()` for the malformed
+     * executables, `OsErrorClass::InvalidArgument` if its segments overlap
+     * or its entry point is not executable, `OsErrorClass::LimitOverflow`
+     * if the arguments are too big and `OsErrorClass::NotEnoughMemory` if
+     * the memory is exhausted
+     */
+    pub fn load(elf_bytes: &[u8], cmdline_args: &[&str]) -> Result<Self, OsErrorClass> {
+        let elf_file = ElfFile::parse(elf_bytes)?;
+
+        /* the entry point must be into an executable segment */
+        let entry_point = elf_file.entry_point();
+        let is_entry_point_valid =
+            elf_file.prog_headers()
+                    .filter(|prog_header| {
+                        prog_header.is_load() && prog_header.is_executable()
+                    })
+                    .any(|prog_header| prog_header.virt_range().contains(&entry_point));
+        if !is_entry_point_valid {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        let addr_space = AddressSpace::new().ok_or(OsErrorClass::NotEnoughMemory)?;
+        Self::load_segments(&addr_space, &elf_file)?;
+
+        /* the first thread receives its block now, the next ones at their creation */
+        let (tls_template, thread_ptr) = match elf_file.tls_prog_header() {
+            Some(tls_prog_header) => {
+                let tls_template = TlsTemplate::new(&tls_prog_header);
+                let thread_ptr =
+                    tls_template.create_block(&addr_space,
+                                              elf_file.segment_bytes(&tls_prog_header))?;
+                (Some(tls_template), Some(thread_ptr))
+            },
+            None => (None, None)
+        };
+
+        let stack_ptr = Self::build_stack(&addr_space, cmdline_args)?;
+        Ok(Self { m_addr_space: addr_space,
+                  m_entry_point: VirtAddr::from(entry_point),
+                  m_stack_ptr: stack_ptr,
+                  m_args_count: cmdline_args.len(),
+                  m_tls_template: tls_template,
+                  m_thread_ptr: thread_ptr })
+    }
+}
+
+impl ExecImage /* Methods */ {
+    /**
+     * Consumes this `ExecImage` returning its `AddressSpace`, which is
+     * given to its first thread
+     */
+    pub fn into_addr_space(self) -> AddressSpace {
+        self.m_addr_space
+    }
+}
+
+impl ExecImage /* Getters */ {
+    /**
+     * Returns the `AddressSpace` which contains the executable
+     */
+    pub fn addr_space(&self) -> &AddressSpace {
+        &self.m_addr_space
+    }
+
+    /**
+     * Returns the address of the first instruction to execute
+     */
+    pub fn entry_point(&self) -> VirtAddr {
+        self.m_entry_point
+    }
+
+    /**
+     * Returns the initial stack pointer of the first thread, which points
+     * to `argc`
+     */
+    pub fn stack_ptr(&self) -> VirtAddr {
+        self.m_stack_ptr
+    }
+
+    /**
+     * Returns the amount of command line arguments (`argc`)
+     */
+    pub fn args_count(&self) -> usize {
+        self.m_args_count
+    }
+
+    /**
+     * Returns the address of the command line arguments pointers (`argv`)
+     */
+    pub fn args_ptr(&self) -> VirtAddr {
+        self.m_stack_ptr.offset(mem::size_of::<usize>())
+    }
+
+    /**
+     * Returns the `TlsTemplate` from which the blocks of the next threads
+     * are created
+     */
+    pub fn tls_template(&self) -> Option<&TlsTemplate> {
+        self.m_tls_template.as_ref()
+    }
+
+    /**
+     * Returns the thread pointer of the first thread, which points to its
+     * thread control block
+     */
+    pub fn thread_ptr(&self) -> Option<VirtAddr> {
+        self.m_thread_ptr
+    }
+}
+
+impl ExecImage /* Privates */ {
+    /**
+     * Maps the `PT_LOAD` segments with their permissions and copies their
+     * content from the file, the rest of their memory is left zeroed
+     */
+    fn load_segments(addr_space: &AddressSpace,
+                     elf_file: &ElfFile)
+                     -> Result<(), OsErrorClass> {
+        let mut mapped_ranges: Vec<Range<usize>> = Vec::new();
+
+        for prog_header in elf_file.prog_headers().filter(ElfProgHeader::is_load) {
+            if prog_header.mem_size() == 0 {
+                continue;
+            }
+
+            /* the segments can't share a page, their permissions could differ */
+            let page_range = align_down(prog_header.virt_addr(), Page4KiB::SIZE)
+                             ..align_up(prog_header.virt_range().end, Page4KiB::SIZE);
+            if mapped_ranges.iter().any(|mapped_range| {
+                                       mapped_range.start < page_range.end
+                                       && page_range.start < mapped_range.end
+                                   })
+            {
+                return Err(OsErrorClass::InvalidArgument);
+            }
+
+            let virt_range =
+                VirtAddr::from(page_range.start)..VirtAddr::from(page_range.end);
+            if !addr_space.map_area_at(virt_range, prog_header.map_flags())
+               || !addr_space.write_bytes(VirtAddr::from(prog_header.virt_addr()),
+                                          elf_file.segment_bytes(&prog_header))
+            {
+                return Err(OsErrorClass::NotEnoughMemory);
+            }
+            mapped_ranges.push(page_range);
+        }
+        Ok(())
+    }
+
+    /**
+     * Maps the stack at the end of the user space and copies on its top
+     * the command line arguments, returning the initial stack pointer
+     */
+    fn build_stack(addr_space: &AddressSpace,
+                   cmdline_args: &[&str])
+                   -> Result<VirtAddr, OsErrorClass> {
+        let stack_top = LayoutManager::USER_SPACE_END;
+        let stack_range =
+            VirtAddr::from(stack_top - C_USER_STACK_SIZE)..VirtAddr::from(stack_top);
+
+        /* <argc>, <argv> with its null terminator, empty <envp> and auxiliary vector */
+        let strings_size = cmdline_args.iter().map(|arg| arg.len() + 1).sum::<usize>();
+        let words_size = (cmdline_args.len() + 5) * mem::size_of::<usize>();
+        if strings_size + words_size + 2 * C_USER_STACK_ALIGN > C_USER_STACK_ARGS_MAX {
+            return Err(OsErrorClass::LimitOverflow);
+        }
+
+        /* the strings are placed at the top, each terminated by a null byte */
+        let strings_begin = align_down(stack_top - strings_size, C_USER_STACK_ALIGN);
+
+        let mut args_strings = Vec::with_capacity(strings_size);
+        let mut stack_words = Vec::with_capacity(words_size / mem::size_of::<usize>());
+        stack_words.push(cmdline_args.len());
+        for arg in cmdline_args.iter() {
+            stack_words.push(strings_begin + args_strings.len());
+            args_strings.extend_from_slice(arg.as_bytes());
+            args_strings.push(0);
+        }
+
+        /* terminate <argv>, then the empty <envp> and the <AT_NULL> auxiliary entry */
+        stack_words.extend_from_slice(&[0, 0, 0, 0]);
+
+        let stack_ptr = align_down(strings_begin - words_size, C_USER_STACK_ALIGN);
+        let stack_bytes = stack_words.iter()
+                                     .flat_map(|stack_word| stack_word.to_ne_bytes())
+                                     .collect::<Vec<u8>>();
+
+        let mut stack_flags = MapFlags::new_zero();
+        stack_flags.set_enabled(MapFlagsBits::Writeable)
+                   .set_enabled(MapFlagsBits::LazyBacked);
+        if !addr_space.map_area_at(stack_range, stack_flags)
+           || !addr_space.write_bytes(VirtAddr::from(strings_begin), &args_strings)
+           || !addr_space.write_bytes(VirtAddr::from(stack_ptr), &stack_bytes)
+        {
+            return Err(OsErrorClass::NotEnoughMemory);
+        }
+        Ok(VirtAddr::from(stack_ptr))
+    }
+}
+
+/**
+ * Thread local storage template of an executable, the `PT_TLS` segment.
+ *
+ * Each thread owns a block which follows the variant II of the x86_64
+ * System V ABI: the copy of the template, aligned to end where the thread
+ * control block begins, with the thread pointer which points to the thread
+ * control block, which stores its own address
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct TlsTemplate {
+    m_image_virt_addr: VirtAddr,
+    m_file_size: usize,
+    m_mem_size: usize,
+    m_align: usize
+}
+
+impl TlsTemplate /* Constructors */ {
+    /**
+     * Constructs the `TlsTemplate` described by the given `PT_TLS`
+     * `ElfProgHeader`
+     */
+    fn new(prog_header: &ElfProgHeader) -> Self {
+        Self { m_image_virt_addr: VirtAddr::from(prog_header.virt_addr()),
+               m_file_size: prog_header.file_size(),
+               m_mem_size: prog_header.mem_size(),
+               m_align: prog_header.align().max(mem::align_of::<usize>()) }
+    }
+}
+
+impl TlsTemplate /* Methods */ {
+    /**
+     * Maps into the given `AddressSpace` a new block initialized with the
+     * given template bytes, returning the thread pointer.
+     *
+     * Returns `OsErrorClass::InvalidArgument` if the alignment of the
+     * template exceeds the page size
+     */
+    pub fn create_block(&self,
+                        addr_space: &AddressSpace,
+                        template_bytes: &[u8])
+                        -> Result<VirtAddr, OsErrorClass> {
+        if self.m_align > Page4KiB::SIZE {
+            return Err(OsErrorClass::InvalidArgument);
+        }
+
+        /* the block begins at a page boundary, so the template stays aligned */
+        let tcb_offset = align_up(self.m_mem_size, self.m_align);
+        let mut block_flags = MapFlags::new_zero();
+        block_flags.set_enabled(MapFlagsBits::Writeable);
+        let block_range = addr_space.map_area(tcb_offset + C_TLS_TCB_SIZE, block_flags)
+                                    .ok_or(OsErrorClass::NotEnoughMemory)?;
+
+        /* the tail of the template is already zeroed by the mapping */
+        let template_virt_addr = block_range.start.offset(tcb_offset - self.m_mem_size);
+        let thread_ptr = block_range.start.offset(tcb_offset);
+        if !addr_space.write_bytes(template_virt_addr, template_bytes)
+           || !addr_space.write_bytes(thread_ptr, &(*thread_ptr).to_ne_bytes())
+        {
+            return Err(OsErrorClass::NotEnoughMemory);
+        }
+        Ok(thread_ptr)
+    }
+}
+
+impl TlsTemplate /* Getters */ {
+    /**
+     * Returns the address of the initialized part of the template into
+     * the user space
+     */
+    pub fn image_virt_addr(&self) -> VirtAddr {
+        self.m_image_virt_addr
+    }
+
+    /**
+     * Returns the size of the initialized part of the template
+     */
+    pub fn file_size(&self) -> usize {
+        self.m_file_size
+    }
+
+    /**
+     * Returns the size of the whole template, the bytes after
+     * `file_size()` are zeroed
+     */
+    pub fn mem_size(&self) -> usize {
+        self.m_mem_size
+    }
+
+    /**
+     * Returns the alignment of the template
+     */
+    pub fn align(&self) -> usize {
+        self.m_align
+    }
+}
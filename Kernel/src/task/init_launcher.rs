@@ -0,0 +1,132 @@
+/*! First userland process launcher */
+
+use api_data::{
+    error::class::OsErrorClass,
+    object::types::ObjType,
+    task::{
+        config::TaskConfigFlags,
+        exit_status::TaskExitStatus,
+        modes::TaskExecCpu
+    }
+};
+
+use crate::{
+    boot_info::BootInfo,
+    cpu::Cpu,
+    dbg_print::DbgLevel,
+    filesystem::Vfs,
+    task::{
+        exec_image::ExecImage,
+        sched::Scheduler,
+        thread::Thread
+    }
+};
+
+/* command line option which selects the init executable */
+const C_INIT_PATH_ARG: &str = "-init=";
+
+/* command line option which reboots the machine instead of powering it off */
+const C_REBOOT_ON_EXIT_ARG: &str = "-reboot-on-init-exit";
+
+/* executable started when the command line doesn't select one */
+const C_DEFAULT_INIT_PATH: &str = "/Bins/example";
+
+/**
+ * Starter of the first userland process, which ends the boot.
+ *
+ * The init executable is loaded through the `Vfs` and the system goes down
+ * when it exits
+ */
+pub struct InitLauncher;
+
+impl InitLauncher /* Static Functions */ {
+    /**
+     * Runs the init executable selected by the `-init=` command line
+     * option, waits for its exit then powers off the machine, or reboots
+     * it with `-reboot-on-init-exit`.
+     *
+     * The shutdown is clean even when init is terminated by a CPU
+     * exception, which ends only its thread
+     */
+    pub fn run_and_shutdown() -> ! {
+        let init_path = Self::init_path();
+
+        dbg_println!(DbgLevel::Info, "Starting init: {}", init_path);
+        match Self::run(init_path) {
+            Ok(TaskExitStatus::Success) => {
+                dbg_println!(DbgLevel::Info, "Init exited successfully")
+            },
+            Ok(TaskExitStatus::WithValue(exit_value)) => {
+                dbg_println!(DbgLevel::Info, "Init exited with value {}", exit_value)
+            },
+            Ok(TaskExitStatus::WithError(os_error))
+                if os_error.error_class() == OsErrorClass::UnrecoverableFault =>
+            {
+                /* the fault is already reported, the kernel survives its process */
+                dbg_println!(DbgLevel::Err, "Init was terminated by a fault: {}", os_error)
+            },
+            Ok(TaskExitStatus::WithError(os_error)) => {
+                dbg_println!(DbgLevel::Err, "Init exited with error: {}", os_error)
+            },
+            Err(os_error_class) => {
+                dbg_println!(DbgLevel::Err,
+                             "Failed to start init {}: {}",
+                             init_path,
+                             os_error_class)
+            }
+        }
+
+        let cache_stats = Vfs::instance().cache_stats();
+        dbg_println!(DbgLevel::Debug,
+                     "Vfs cache: {} hits, {} ancestor hits, {} misses, {}/{} entries",
+                     cache_stats.hits(),
+                     cache_stats.ancestor_hits(),
+                     cache_stats.misses(),
+                     cache_stats.entries(),
+                     cache_stats.capacity());
+
+        if BootInfo::instance().cmd_line_arg_exists(C_REBOOT_ON_EXIT_ARG) {
+            dbg_println!(DbgLevel::Info, "Rebooting...");
+            Cpu::current().reboot()
+        } else {
+            dbg_println!(DbgLevel::Info, "Shutting down...");
+            Cpu::current().power_off()
+        }
+    }
+}
+
+impl InitLauncher /* Privates */ {
+    /**
+     * Loads the executable at the given path into a new userspace `Thread`
+     * and waits for its `TaskExitStatus`
+     */
+    fn run(init_path: &str) -> Result<TaskExitStatus, OsErrorClass> {
+        let init_node = Vfs::instance().lookup(init_path)?;
+        if init_node.obj_type() != ObjType::File {
+            return Err(OsErrorClass::TypesNotMatch);
+        }
+        let elf_bytes = init_node.read_all()?;
+
+        /* the path is given as first argument, as the shells do */
+        let exec_image = ExecImage::load(&elf_bytes, &[init_path])?;
+        let init_thread =
+            Thread::new_user("init",
+                             exec_image,
+                             TaskConfigFlags::new_zero(),
+                             TaskExecCpu::Any).ok_or(OsErrorClass::NotEnoughMemory)?;
+
+        Scheduler::spawn(&init_thread);
+        Ok(Scheduler::join(&init_thread))
+    }
+
+    /**
+     * Returns the path of the init executable given by the command line or
+     * the default one
+     */
+    fn init_path() -> &'static str {
+        BootInfo::instance().cmd_line_find_arg(C_INIT_PATH_ARG)
+                            .and_then(|(_, init_path)| init_path)
+                            .filter(|init_path| !init_path.is_empty())
+                            .unwrap_or(C_DEFAULT_INIT_PATH)
+    }
+}
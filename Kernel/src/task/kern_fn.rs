@@ -0,0 +1,69 @@
+/*! Task kernel functions */
+
+use api_data::{
+    error::{
+        class::OsErrorClass,
+        OsError
+    },
+    sys::{
+        codes::KernTaskFnId,
+        fn_path::KernFnPath,
+        SysCallPayload
+    },
+    task::exit_status::TaskExitStatus
+};
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    task::sched::Scheduler,
+    vm::user_copy::UserCopy
+};
+
+/**
+ * Executes the `KernFnPath::Task` function of the given `SysCallPayload`
+ * for the running `Thread`.
+ *
+ * Returns the value to give back to the caller or the `OsErrorClass` of
+ * the failure
+ */
+pub fn task_kern_fn(sys_call_payload: &SysCallPayload) -> Result<usize, OsErrorClass> {
+    match sys_call_payload.kern_fn_path() {
+        KernFnPath::Task(KernTaskFnId::Exit) => {
+            /* there are no processes yet, both the task types terminate the thread */
+            let exit_status = read_user_exit_status(sys_call_payload.raw_arg(1));
+            Scheduler::exit_current_with(exit_status)
+        },
+        KernFnPath::Task(KernTaskFnId::Yield) => {
+            Scheduler::yield_now();
+            Ok(0)
+        },
+        _ => Err(OsErrorClass::OperationNotEnabled)
+    }
+}
+
+/**
+ * Reads the `TaskExitStatus` at the given userspace address.
+ *
+ * The exit can't fail, so an unreadable or malformed one is replaced by an
+ * `OsErrorClass::InvalidArgument` error
+ */
+fn read_user_exit_status(raw_exit_status_addr: usize) -> TaskExitStatus {
+    /* validate the copy, the userspace could change or unmap the original */
+    let exit_status =
+        UserCopy::read_raw::<TaskExitStatus>(VirtAddr::from(raw_exit_status_addr))
+            .and_then(|raw_exit_status| unsafe {
+                TaskExitStatus::read_from_raw(raw_exit_status.as_ptr())
+            });
+
+    exit_status.unwrap_or_else(|| {
+                   let thread_id =
+                       Scheduler::current_thread().map_or(0, |thread| thread.id());
+                   let os_error = OsError::new(OsErrorClass::InvalidArgument,
+                                               KernFnPath::Task(KernTaskFnId::Exit),
+                                               None,
+                                               0,
+                                               thread_id,
+                                               None);
+                   TaskExitStatus::WithError(os_error)
+               })
+}
@@ -0,0 +1,8 @@
+/*! Kernel tasks management */
+
+pub mod elf;
+pub mod exec_image;
+pub mod init_launcher;
+pub mod kern_fn;
+pub mod sched;
+pub mod thread;
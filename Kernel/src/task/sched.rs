@@ -0,0 +1,619 @@
+/*! Kernel threads scheduler */
+
+use alloc::{
+    collections::VecDeque,
+    sync::Arc
+};
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicBool,
+        AtomicUsize,
+        Ordering
+    },
+    time::Duration
+};
+
+use api_data::{
+    error::{
+        class::OsErrorClass,
+        OsError
+    },
+    sys::fn_path::KernFnPath,
+    task::{
+        config::{
+            TaskConfigBits,
+            TaskConfigFlags
+        },
+        exit_status::TaskExitStatus
+    }
+};
+use sync::SpinMutex;
+
+use crate::{
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    task::thread::{
+        Thread,
+        ThreadState
+    },
+    time::{
+        TimeManager,
+        TimerId
+    }
+};
+
+/* period of the scheduler tick of each CPU */
+const C_SCHED_TICK_MILLIS: u64 = 10;
+
+/* ticks which a preemptible <Thread> runs before leaving the CPU to the others */
+const C_TIME_SLICE_TICKS: usize = 3;
+
+/* one ready queue for each <SchedPrio> except the idle one */
+const C_READY_QUEUES_COUNT: usize = 3;
+
+/**
+ * Lists the priority classes of the `Thread`s.
+ *
+ * The ready `Thread`s of an higher class always run before the lower ones,
+ * the ones of the same class share the CPU in round robin
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(PartialEq, Eq)]
+#[derive(PartialOrd, Ord)]
+pub enum SchedPrio {
+    /**
+     * Reserved to the idle `Thread` of each `Cpu`
+     */
+    Idle,
+
+    /**
+     * Selected by `TaskConfigBits::LowPrioTask`
+     */
+    Low,
+
+    /**
+     * Default priority class
+     */
+    Normal,
+
+    /**
+     * Selected by `TaskConfigBits::HighPrioTask`, which wins when both the
+     * bits are enabled
+     */
+    High
+}
+
+impl SchedPrio /* Constructors */ {
+    /**
+     * Maps the priority bits of the given `TaskConfigFlags`
+     */
+    pub fn from_config_flags(config_flags: &TaskConfigFlags) -> Self {
+        if config_flags.is_enabled(TaskConfigBits::HighPrioTask) {
+            Self::High
+        } else if config_flags.is_enabled(TaskConfigBits::LowPrioTask) {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+impl SchedPrio /* Privates */ {
+    /**
+     * Returns the index of the ready queue of this class
+     */
+    fn ready_queue_index(&self) -> usize {
+        match self {
+            Self::Idle => panic!("The idle Thread is never queued"),
+            Self::Low => 0,
+            Self::Normal => 1,
+            Self::High => 2
+        }
+    }
+}
+
+/**
+ * Per `Cpu` queues of the ready `Thread`s.
+ *
+ * Locked only with the interrupts disabled, since the scheduler uses it
+ * from the interrupts too
+ */
+pub struct RunQueue {
+    m_inner: SpinMutex<RunQueueInner>,
+    m_ready_count: AtomicUsize,
+    m_need_resched: AtomicBool,
+    m_slice_ticks: AtomicUsize
+}
+
+impl RunQueue /* Constructors */ {
+    /**
+     * Constructs an empty `RunQueue`, filled by
+     * `Scheduler::init_for_this_cpu()`
+     */
+    pub fn new() -> Self {
+        Self { m_inner: SpinMutex::const_new(RunQueueInner::new()),
+               m_ready_count: AtomicUsize::new(0),
+               m_need_resched: AtomicBool::new(false),
+               m_slice_ticks: AtomicUsize::new(0) }
+    }
+}
+
+impl RunQueue /* Getters */ {
+    /**
+     * Returns the amount of the ready `Thread`s waiting for this `Cpu`
+     */
+    pub fn ready_count(&self) -> usize {
+        self.m_ready_count.load(Ordering::SeqCst)
+    }
+}
+
+impl RunQueue /* Privates */ {
+    /**
+     * Appends the given ready `Thread`, which is not running on this `Cpu`.
+     *
+     * Returns whether the `Thread` must preempt the running one
+     */
+    fn push_ready(&self, thread: Arc<Thread>) -> bool {
+        let sched_prio = thread.sched_prio();
+        let mut inner = self.m_inner.lock();
+
+        /* keep room for the running thread, re-queued without allocations if preempted */
+        let ready_queue = &mut inner.m_ready_queues[sched_prio.ready_queue_index()];
+        ready_queue.reserve(2);
+        ready_queue.push_back(thread);
+        self.m_ready_count.fetch_add(1, Ordering::SeqCst);
+
+        /* the <RunQueue> of a <Cpu> which is still starting has no running thread */
+        inner.m_current_thread
+             .as_ref()
+             .map_or(false, |current_thread| sched_prio > current_thread.sched_prio())
+    }
+
+    /**
+     * Selects the next `Thread` to run, re-queueing the running one if it
+     * is still ready.
+     *
+     * Returns the running and the next `Thread` when they must be switched
+     */
+    fn select_next(&self) -> Option<(*const Thread, *const Thread)> {
+        let mut inner = self.m_inner.lock();
+
+        let prev_thread = inner.m_current_thread.take()?;
+        let idle_thread =
+            inner.m_idle_thread.clone().expect("RunQueue without idle Thread");
+
+        /* a paused or exited thread leaves the CPU without being re-queued */
+        if prev_thread.swap_state(ThreadState::Running, ThreadState::Ready)
+           && !Arc::ptr_eq(&prev_thread, &idle_thread)
+        {
+            let queue_index = prev_thread.sched_prio().ready_queue_index();
+            inner.m_ready_queues[queue_index].push_back(prev_thread.clone());
+            self.m_ready_count.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let next_thread = match inner.m_ready_queues
+                                     .iter_mut()
+                                     .rev()
+                                     .find_map(|ready_queue| ready_queue.pop_front())
+        {
+            Some(next_thread) => {
+                self.m_ready_count.fetch_sub(1, Ordering::SeqCst);
+                next_thread
+            },
+            None => idle_thread
+        };
+        next_thread.set_state(ThreadState::Running);
+        self.m_slice_ticks.store(0, Ordering::SeqCst);
+        self.m_need_resched.store(false, Ordering::SeqCst);
+
+        if Arc::ptr_eq(&next_thread, &prev_thread) {
+            inner.m_current_thread = Some(next_thread);
+            return None;
+        }
+
+        /* a resumed thread could still be saving its context on another CPU */
+        while next_thread.is_on_cpu() {
+            hint::spin_loop();
+        }
+        next_thread.set_on_cpu(true);
+
+        let switch_pair = (Arc::as_ptr(&prev_thread), Arc::as_ptr(&next_thread));
+        inner.m_prev_thread = Some(prev_thread);
+        inner.m_current_thread = Some(next_thread);
+        Some(switch_pair)
+    }
+}
+
+/**
+ * Fields of the `RunQueue` protected by its lock
+ */
+struct RunQueueInner {
+    m_ready_queues: [VecDeque<Arc<Thread>>; C_READY_QUEUES_COUNT],
+    m_current_thread: Option<Arc<Thread>>,
+    m_idle_thread: Option<Arc<Thread>>,
+    m_prev_thread: Option<Arc<Thread>>,
+    m_released_thread: Option<Arc<Thread>>
+}
+
+impl RunQueueInner /* Constructors */ {
+    /**
+     * Constructs empty `RunQueueInner`
+     */
+    fn new() -> Self {
+        Self { m_ready_queues: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+               m_current_thread: None,
+               m_idle_thread: None,
+               m_prev_thread: None,
+               m_released_thread: None }
+    }
+}
+
+/**
+ * Preemptive scheduler of the kernel `Thread`s.
+ *
+ * Each `Cpu` runs the `Thread`s of its own `RunQueue`, in which they are
+ * placed by their `TaskExecCpu` affinity when they become ready
+ */
+pub struct Scheduler;
+
+impl Scheduler /* Constructors */ {
+    /**
+     * Initializes the scheduling on the executing `Cpu`, which continues
+     * its execution as a `Thread` with the given name.
+     *
+     * Called once by each `Cpu` after `TimeManager::init()`
+     */
+    pub fn init_for_this_cpu(boot_thread_name: &str) {
+        let this_cpu = Cpu::current();
+
+        let idle_thread =
+            Thread::new_idle(idle_thread_entry).expect("Failed to create idle Thread");
+        let boot_thread = Thread::new_current(boot_thread_name, SchedPrio::Normal);
+
+        this_cpu.without_interrupts(|| {
+                    let mut inner = this_cpu.run_queue().m_inner.lock();
+
+                    let queue_index = boot_thread.sched_prio().ready_queue_index();
+                    inner.m_ready_queues[queue_index].reserve(1);
+                    inner.m_current_thread = Some(boot_thread);
+                    inner.m_idle_thread = Some(idle_thread);
+                });
+
+        TimeManager::add_periodic_timer(Duration::from_millis(C_SCHED_TICK_MILLIS),
+                                        sched_tick);
+    }
+}
+
+impl Scheduler /* Static Functions */ {
+    /**
+     * Makes runnable the given new `Thread`, unless it was constructed
+     * with `TaskConfigBits::StartPaused`, in which case it waits for
+     * `Scheduler::resume()`
+     */
+    pub fn spawn(thread: &Arc<Thread>) {
+        if thread.state() == ThreadState::Ready {
+            Self::enqueue(thread.clone());
+        }
+    }
+
+    /**
+     * Makes runnable again the given paused `Thread`.
+     *
+     * Returns `false` if the `Thread` was not paused. Must not be called
+     * by the interrupt handlers, since the `RunQueue` could grow
+     */
+    pub fn resume(thread: &Arc<Thread>) -> bool {
+        if thread.swap_state(ThreadState::Paused, ThreadState::Ready) {
+            Self::enqueue(thread.clone());
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Leaves the `Cpu` to the next ready `Thread`, if any.
+     *
+     * The cooperative `Thread`s use this to release the `Cpu`
+     */
+    pub fn yield_now() {
+        let this_cpu = Cpu::current();
+
+        this_cpu.without_interrupts(Self::schedule);
+    }
+
+    /**
+     * Pauses the running `Thread` until another one calls
+     * `Scheduler::resume()` with it
+     */
+    pub fn pause_current() {
+        let this_cpu = Cpu::current();
+        let were_enabled = this_cpu.are_interrupts_enabled();
+
+        Self::release_disabling_interrupts(this_cpu);
+        if let Some(current_thread) = Self::current_thread() {
+            current_thread.set_state(ThreadState::Paused);
+            drop(current_thread);
+            Self::schedule();
+        }
+
+        /* the <Thread> could be resumed by another <Cpu> */
+        if were_enabled {
+            Cpu::current().enable_interrupts();
+        }
+    }
+
+    /**
+     * Waits until the given `Thread` exits, returning its
+     * `TaskExitStatus`.
+     *
+     * Must not be called by the `Thread` itself
+     */
+    pub fn join(thread: &Arc<Thread>) -> TaskExitStatus {
+        let current_thread =
+            Self::current_thread().expect("Scheduler::join() without a running Thread");
+        assert!(!Arc::ptr_eq(&current_thread, thread), "A Thread can't join itself");
+
+        loop {
+            if let Some(exit_status) = thread.exit_status() {
+                return exit_status;
+            }
+
+            let this_cpu = Cpu::current();
+            let were_enabled = this_cpu.are_interrupts_enabled();
+
+            /* paused before the registration, so the resume at the exit is never lost */
+            Self::release_disabling_interrupts(this_cpu);
+            current_thread.set_state(ThreadState::Paused);
+            if thread.add_joiner(current_thread.clone()) {
+                Self::schedule();
+            } else {
+                current_thread.set_state(ThreadState::Running);
+            }
+
+            /* the <Thread> could be resumed by another <Cpu> */
+            if were_enabled {
+                Cpu::current().enable_interrupts();
+            }
+        }
+    }
+
+    /**
+     * Terminates the running `Thread` with `TaskExitStatus::Success`
+     */
+    pub fn exit_current() -> ! {
+        Self::exit_current_with(TaskExitStatus::Success)
+    }
+
+    /**
+     * Terminates the running `Thread` with the given `TaskExitStatus`,
+     * resuming the `Thread`s which wait for it.
+     *
+     * Its resources are released with its last reference
+     */
+    pub fn exit_current_with(exit_status: TaskExitStatus) -> ! {
+        let this_cpu = Cpu::current();
+
+        if let Some(current_thread) = Self::current_thread() {
+            for joiner_thread in current_thread.set_exit_status(exit_status) {
+                Self::resume(&joiner_thread);
+            }
+        }
+
+        Self::release_disabling_interrupts(this_cpu);
+        if let Some(current_thread) = Self::current_thread() {
+            current_thread.set_state(ThreadState::Exited);
+            drop(current_thread);
+            Self::schedule();
+        }
+        unreachable!("Scheduler::exit_current() without a running Thread");
+    }
+
+    /**
+     * Terminates the running userspace `Thread` which caused a CPU
+     * exception that can't be resolved, with an
+     * `OsErrorClass::UnrecoverableFault` error carrying the given message.
+     *
+     * Called by the exception handlers, even from an interrupt stack, which
+     * is never resumed
+     */
+    pub fn exit_current_by_fault(fault_message: &str) -> ! {
+        let thread_id = Self::current_thread().map_or(0, |thread| thread.id());
+        let os_error = OsError::new(OsErrorClass::UnrecoverableFault,
+                                    KernFnPath::Invalid,
+                                    None,
+                                    0,
+                                    thread_id,
+                                    Some(fault_message));
+
+        Self::exit_current_with(TaskExitStatus::WithError(os_error))
+    }
+
+    /**
+     * Returns the `Thread` running on this `Cpu`, `None` before
+     * `Scheduler::init_for_this_cpu()`
+     */
+    pub fn current_thread() -> Option<Arc<Thread>> {
+        let this_cpu = Cpu::current();
+
+        let mut current_thread = None;
+        this_cpu.without_interrupts(|| {
+                    current_thread =
+                        this_cpu.run_queue().m_inner.lock().m_current_thread.clone();
+                });
+        current_thread
+    }
+
+    /**
+     * Switches to the next ready `Thread` when the running one must be
+     * preempted and it is not cooperative.
+     *
+     * Called by the architecture at the end of each hardware interrupt,
+     * after its acknowledge
+     */
+    pub fn preempt_if_needed() {
+        let run_queue = Cpu::current().run_queue();
+        if !run_queue.m_need_resched.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let is_coop = run_queue.m_inner
+                               .lock()
+                               .m_current_thread
+                               .as_ref()
+                               .map_or(true, |current_thread| current_thread.is_coop());
+        if !is_coop {
+            Self::schedule();
+        }
+    }
+
+    /**
+     * Completes the switch to the executing `Thread`, releasing the
+     * `Thread` which has left the `Cpu`.
+     *
+     * Called with the interrupts disabled by each `Thread` when it starts
+     * or returns from a switch
+     */
+    pub fn finish_switch() {
+        let mut inner = Cpu::current().run_queue().m_inner.lock();
+
+        if let Some(prev_thread) = inner.m_prev_thread.take() {
+            prev_thread.set_on_cpu(false);
+
+            /* its last reference must not be dropped into an interrupt */
+            if matches!(prev_thread.state(), ThreadState::Paused | ThreadState::Exited) {
+                assert!(inner.m_released_thread.is_none());
+                inner.m_released_thread = Some(prev_thread);
+            }
+        }
+    }
+}
+
+impl Scheduler /* Privates */ {
+    /**
+     * Switches the running `Thread` with the next ready one.
+     *
+     * Must be called with the interrupts disabled
+     */
+    fn schedule() {
+        let this_cpu = Cpu::current();
+
+        if let Some((prev_thread, next_thread)) = this_cpu.run_queue().select_next() {
+            /* both are kept alive by the <RunQueue> until <finish_switch()> */
+            unsafe {
+                /* the entries from the userspace must land on the stack of <next> */
+                if let Some(kern_stack) = (*next_thread).kern_stack() {
+                    this_cpu.set_kern_stack_top(kern_stack.end);
+                }
+                if let Some(user_thread_ptr) = (*next_thread).user_thread_ptr() {
+                    this_cpu.set_user_thread_ptr(user_thread_ptr);
+                }
+                (*next_thread).activate_addr_space();
+                (*prev_thread).switch_to(&*next_thread);
+            }
+            Self::finish_switch();
+        }
+    }
+
+    /**
+     * Places the given ready `Thread` into the `RunQueue` of the least
+     * loaded `Cpu` allowed by its affinity, preferring the executing one.
+     *
+     * An affinity without online `Cpu`s is ignored
+     */
+    fn enqueue(thread: Arc<Thread>) {
+        let this_cpu = Cpu::current();
+
+        let mut target_cpu = this_cpu;
+        let mut target_load = usize::MAX;
+        for cpu_id in 0..Cpu::cpus_count() as CpuId {
+            let cpu = Cpu::by_id(cpu_id);
+            if !cpu.is_online() || !thread.can_run_on(cpu_id) {
+                continue;
+            }
+
+            let cpu_load = cpu.run_queue().ready_count();
+            if cpu_load < target_load
+               || (cpu_load == target_load && cpu_id == this_cpu.id())
+            {
+                target_cpu = cpu;
+                target_load = cpu_load;
+            }
+        }
+
+        let mut need_resched = false;
+        this_cpu.without_interrupts(|| {
+                    need_resched = target_cpu.run_queue().push_ready(thread);
+                });
+
+        if need_resched {
+            target_cpu.run_queue().m_need_resched.store(true, Ordering::SeqCst);
+            if target_cpu.id() == this_cpu.id() {
+                this_cpu.without_interrupts(Self::preempt_if_needed);
+            } else {
+                target_cpu.notify_reschedule();
+            }
+        }
+    }
+
+    /**
+     * Drops the `Thread` released by the last switch of this `Cpu`, with
+     * the interrupts enabled when they were at the call.
+     *
+     * Returns with the interrupts disabled and nothing to release, so the
+     * next switch can release the running `Thread`
+     */
+    fn release_disabling_interrupts(this_cpu: &Cpu) {
+        let were_enabled = this_cpu.are_interrupts_enabled();
+        loop {
+            this_cpu.disable_interrupts();
+
+            let released_thread =
+                this_cpu.run_queue().m_inner.lock().m_released_thread.take();
+            if let Some(released_thread) = released_thread {
+                if were_enabled {
+                    this_cpu.enable_interrupts();
+                }
+                drop(released_thread);
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/**
+ * Entry of the idle `Thread` of each `Cpu`, which releases the exited
+ * `Thread`s and waits for the interrupts when there is nothing to run
+ */
+fn idle_thread_entry(_arg: usize) {
+    let this_cpu = Cpu::current();
+    loop {
+        Scheduler::release_disabling_interrupts(this_cpu);
+
+        /* the interrupts are enabled atomically with the wait, so no wakeup is lost */
+        if this_cpu.run_queue().ready_count() > 0 {
+            Scheduler::schedule();
+            this_cpu.enable_interrupts();
+        } else {
+            this_cpu.wait_interrupt();
+        }
+    }
+}
+
+/**
+ * Periodic timer of each `Cpu`, which asks to preempt the running `Thread`
+ * once its time slice is over
+ */
+fn sched_tick(_timer_id: TimerId) {
+    let run_queue = Cpu::current().run_queue();
+
+    let slice_ticks = run_queue.m_slice_ticks.fetch_add(1, Ordering::SeqCst) + 1;
+    if slice_ticks >= C_TIME_SLICE_TICKS && run_queue.ready_count() > 0 {
+        run_queue.m_need_resched.store(true, Ordering::SeqCst);
+    }
+}
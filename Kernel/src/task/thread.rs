@@ -0,0 +1,575 @@
+/*! Kernel threads */
+
+use alloc::{
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+use core::{
+    cell::UnsafeCell,
+    convert::TryFrom,
+    mem,
+    ops::Range,
+    sync::atomic::{
+        AtomicBool,
+        AtomicU64,
+        AtomicU8,
+        Ordering
+    }
+};
+
+use num_enum::{
+    IntoPrimitive,
+    TryFromPrimitive
+};
+
+use api_data::task::{
+    config::{
+        TaskConfigBits,
+        TaskConfigFlags
+    },
+    exit_status::TaskExitStatus,
+    modes::TaskExecCpu,
+    TaskId
+};
+use bits::bit_fields::TBitFields;
+use helps::dbg::C_KIB;
+use sync::SpinMutex;
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    arch::task::hw_thread_context::HwThreadContext,
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    task::{
+        exec_image::ExecImage,
+        sched::{
+            SchedPrio,
+            Scheduler
+        }
+    },
+    vm::{
+        addr_space::AddressSpace,
+        mem_manager::MemManager,
+        page_dir::PageDir
+    }
+};
+
+/* identifier of the next <Thread>, the zero is never used */
+static SM_NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+/* size of the stack of each kernel <Thread>, eagerly backed */
+const C_KERN_THREAD_STACK_SIZE: usize = 64 * C_KIB;
+
+/**
+ * Entry point of the kernel `Thread`s, which exit when it returns
+ */
+pub type KernThreadEntry = fn(arg: usize);
+
+/**
+ * Function with which the `HwThreadContext` of a new `Thread` starts,
+ * receiving the raw pointer to its `Thread`
+ */
+pub type ThreadStartFn = extern "C" fn(start_arg: usize) -> !;
+
+/**
+ * Kernel schedulable execution flow.
+ *
+ * The userspace `Thread`s enter the kernel through the system calls and
+ * the interrupts, on their own kernel stack
+ */
+pub struct Thread {
+    m_id: TaskId,
+    m_name: String,
+    m_state: AtomicU8,
+    m_sched_prio: SchedPrio,
+    m_is_coop: bool,
+    m_exec_cpu: TaskExecCpu,
+    m_entry: Option<ThreadEntry>,
+    m_addr_space: Option<Arc<AddressSpace>>,
+    m_kern_stack: Option<Range<VirtAddr>>,
+    m_hw_context: UnsafeCell<HwThreadContext>,
+    m_is_on_cpu: AtomicBool,
+    m_exit: SpinMutex<ThreadExit>
+}
+
+/* the <HwThreadContext> is touched only by the <Scheduler>, with the interrupts
+ * disabled and after <Thread::is_on_cpu()> became <false>
+ */
+unsafe impl Sync for Thread {
+}
+
+impl Thread /* Constructors */ {
+    /**
+     * Constructs a kernel `Thread` which executes `entry(arg)` on its own
+     * stack.
+     *
+     * The `SchedPrio`, the cooperative scheduling and the paused start are
+     * selected by the given `TaskConfigFlags`, the CPUs which can execute
+     * it by the given `TaskExecCpu`. Returns `None` if the stack cannot be
+     * allocated
+     */
+    pub fn new_kernel(name: &str,
+                      entry: KernThreadEntry,
+                      arg: usize,
+                      config_flags: TaskConfigFlags,
+                      exec_cpu: TaskExecCpu)
+                      -> Option<Arc<Self>> {
+        Self::new_with_stack(name,
+                             ThreadEntry::Kernel(entry, arg),
+                             None,
+                             SchedPrio::from_config_flags(&config_flags),
+                             config_flags.is_enabled(TaskConfigBits::CoopSched),
+                             Self::initial_state(&config_flags),
+                             exec_cpu)
+    }
+
+    /**
+     * Constructs the first userspace `Thread` of the given `ExecImage`,
+     * which starts at its entry point with `argc` and `argv` as arguments.
+     *
+     * The `TaskConfigFlags` and the `TaskExecCpu` are used as for the
+     * kernel `Thread`s. Returns `None` if the kernel stack cannot be
+     * allocated
+     */
+    pub fn new_user(name: &str,
+                    exec_image: ExecImage,
+                    config_flags: TaskConfigFlags,
+                    exec_cpu: TaskExecCpu)
+                    -> Option<Arc<Self>> {
+        /* without TLS the thread pointer is cleared, to not inherit the previous one */
+        let thread_ptr = exec_image.thread_ptr().unwrap_or_else(VirtAddr::null);
+        let user_entry = UserEntry { m_entry_point: exec_image.entry_point(),
+                                     m_stack_ptr: exec_image.stack_ptr(),
+                                     m_args: (exec_image.args_count(),
+                                              *exec_image.args_ptr()),
+                                     m_thread_ptr: thread_ptr };
+
+        Self::new_with_stack(name,
+                             ThreadEntry::User(user_entry),
+                             Some(Arc::new(exec_image.into_addr_space())),
+                             SchedPrio::from_config_flags(&config_flags),
+                             config_flags.is_enabled(TaskConfigBits::CoopSched),
+                             Self::initial_state(&config_flags),
+                             exec_cpu)
+    }
+
+    /**
+     * Constructs the idle `Thread` of a `Cpu`, which is executed only
+     * when its run queue is empty
+     */
+    pub fn new_idle(entry: KernThreadEntry) -> Option<Arc<Self>> {
+        Self::new_with_stack("idle",
+                             ThreadEntry::Kernel(entry, 0),
+                             None,
+                             SchedPrio::Idle,
+                             false,
+                             ThreadState::Ready,
+                             TaskExecCpu::Any)
+    }
+
+    /**
+     * Constructs a `Thread` which represents the code which is executing,
+     * on the stack which it already uses.
+     *
+     * Its `HwThreadContext` is saved by its first switch away
+     */
+    pub fn new_current(name: &str, sched_prio: SchedPrio) -> Arc<Self> {
+        Arc::new(Self { m_id: SM_NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst),
+                        m_name: name.to_string(),
+                        m_state: AtomicU8::new(ThreadState::Running.into()),
+                        m_sched_prio: sched_prio,
+                        m_is_coop: false,
+                        m_exec_cpu: TaskExecCpu::Any,
+                        m_entry: None,
+                        m_addr_space: None,
+                        m_kern_stack: None,
+                        m_hw_context: UnsafeCell::new(HwThreadContext::new_empty()),
+                        m_is_on_cpu: AtomicBool::new(true),
+                        m_exit: SpinMutex::const_new(ThreadExit::new()) })
+    }
+}
+
+impl Thread /* Getters */ {
+    /**
+     * Returns the `TaskId` of this `Thread`
+     */
+    pub fn id(&self) -> TaskId {
+        self.m_id
+    }
+
+    /**
+     * Returns the name of this `Thread`
+     */
+    pub fn name(&self) -> &str {
+        self.m_name.as_str()
+    }
+
+    /**
+     * Returns the current `ThreadState`
+     */
+    pub fn state(&self) -> ThreadState {
+        ThreadState::try_from(self.m_state.load(Ordering::SeqCst))
+            .expect("Corrupted Thread state")
+    }
+
+    /**
+     * Returns the `SchedPrio` of this `Thread`
+     */
+    pub fn sched_prio(&self) -> SchedPrio {
+        self.m_sched_prio
+    }
+
+    /**
+     * Returns whether this `Thread` can't be preempted by the `Scheduler`
+     */
+    pub fn is_coop(&self) -> bool {
+        self.m_is_coop
+    }
+
+    /**
+     * Returns the `TaskExecCpu` affinity of this `Thread`
+     */
+    pub fn exec_cpu(&self) -> TaskExecCpu {
+        self.m_exec_cpu
+    }
+
+    /**
+     * Returns whether this `Thread` can be executed by the `Cpu` with the
+     * given `CpuId`
+     */
+    pub fn can_run_on(&self, cpu_id: CpuId) -> bool {
+        match self.m_exec_cpu {
+            TaskExecCpu::Any => true,
+            TaskExecCpu::Mask(cpu_mask) => cpu_id < 64 && cpu_mask.bit_at(cpu_id as usize)
+        }
+    }
+
+    /**
+     * Returns the `AddressSpace` of this `Thread`, `None` for the kernel
+     * `Thread`s
+     */
+    pub fn addr_space(&self) -> Option<&Arc<AddressSpace>> {
+        self.m_addr_space.as_ref()
+    }
+
+    /**
+     * Returns the range of the kernel stack of this `Thread`, `None` if it
+     * runs on the stack of its creator
+     */
+    pub fn kern_stack(&self) -> Option<&Range<VirtAddr>> {
+        self.m_kern_stack.as_ref()
+    }
+
+    /**
+     * Returns the thread pointer of the userspace `Thread`s, `None` for the
+     * kernel ones
+     */
+    pub fn user_thread_ptr(&self) -> Option<VirtAddr> {
+        match self.m_entry {
+            Some(ThreadEntry::User(ref user_entry)) => Some(user_entry.m_thread_ptr),
+            _ => None
+        }
+    }
+
+    /**
+     * Returns whether a `Cpu` still executes or is saving the context of
+     * this `Thread`
+     */
+    pub fn is_on_cpu(&self) -> bool {
+        self.m_is_on_cpu.load(Ordering::SeqCst)
+    }
+
+    /**
+     * Returns the `TaskExitStatus` of this `Thread`, `None` until it exits
+     */
+    pub fn exit_status(&self) -> Option<TaskExitStatus> {
+        self.m_exit.lock().m_exit_status
+    }
+}
+
+impl Thread /* Setters */ {
+    /**
+     * Replaces the `ThreadState` if it is the expected one.
+     *
+     * Returns whether the `ThreadState` was replaced
+     */
+    pub fn swap_state(&self,
+                      expected_state: ThreadState,
+                      new_state: ThreadState)
+                      -> bool {
+        self.m_state
+            .compare_exchange(expected_state.into(),
+                              new_state.into(),
+                              Ordering::SeqCst,
+                              Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /**
+     * Sets the given `ThreadState`
+     */
+    pub fn set_state(&self, new_state: ThreadState) {
+        self.m_state.store(new_state.into(), Ordering::SeqCst);
+    }
+
+    /**
+     * Marks whether a `Cpu` is executing this `Thread`
+     */
+    pub fn set_on_cpu(&self, is_on_cpu: bool) {
+        self.m_is_on_cpu.store(is_on_cpu, Ordering::SeqCst);
+    }
+
+    /**
+     * Stores the `TaskExitStatus` of this `Thread`.
+     *
+     * Returns the `Thread`s which were waiting for it
+     */
+    pub fn set_exit_status(&self, exit_status: TaskExitStatus) -> Vec<Arc<Thread>> {
+        let mut exit = self.m_exit.lock();
+
+        exit.m_exit_status = Some(exit_status);
+        mem::take(&mut exit.m_joiners)
+    }
+}
+
+impl Thread /* Methods */ {
+    /**
+     * Saves the context of this `Thread` and restores the one of `next`.
+     *
+     * Returns when another `Thread` switches back to this one
+     */
+    pub unsafe fn switch_to(&self, next: &Thread) {
+        HwThreadContext::switch(self.m_hw_context.get(), next.m_hw_context.get());
+    }
+
+    /**
+     * Activates the `AddressSpace` of this `Thread`, or the kernel one for
+     * the kernel `Thread`s, unless it is already active.
+     *
+     * Must be called with the interrupts disabled
+     */
+    pub unsafe fn activate_addr_space(&self) {
+        let kernel_page_dir = MemManager::instance().kernel_page_dir();
+        let page_dir = self.m_addr_space
+                           .as_ref()
+                           .map_or(kernel_page_dir, |addr_space| addr_space.page_dir());
+
+        if PageDir::current().root_phys_frame() != page_dir.root_phys_frame() {
+            page_dir.activate();
+        }
+    }
+
+    /**
+     * Registers the given `Thread` to be resumed when this one exits.
+     *
+     * Returns `false` if this `Thread` has already exited
+     */
+    pub fn add_joiner(&self, joiner: Arc<Thread>) -> bool {
+        let mut exit = self.m_exit.lock();
+
+        if exit.m_exit_status.is_none() {
+            exit.m_joiners.push(joiner);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Thread /* Privates */ {
+    /**
+     * Constructs a `Thread` with its own kernel stack, on which its
+     * `HwThreadContext` starts `thread_start()`
+     */
+    fn new_with_stack(name: &str,
+                      entry: ThreadEntry,
+                      addr_space: Option<Arc<AddressSpace>>,
+                      sched_prio: SchedPrio,
+                      is_coop: bool,
+                      initial_state: ThreadState,
+                      exec_cpu: TaskExecCpu)
+                      -> Option<Arc<Self>> {
+        let kern_stack =
+            MemManager::instance().allocate_kernel_stack(C_KERN_THREAD_STACK_SIZE)?;
+        let stack_top = kern_stack.end;
+
+        let thread =
+            Arc::new(Self { m_id: SM_NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst),
+                            m_name: name.to_string(),
+                            m_state: AtomicU8::new(initial_state.into()),
+                            m_sched_prio: sched_prio,
+                            m_is_coop: is_coop,
+                            m_exec_cpu: exec_cpu,
+                            m_entry: Some(entry),
+                            m_addr_space: addr_space,
+                            m_kern_stack: Some(kern_stack),
+                            m_hw_context: UnsafeCell::new(HwThreadContext::new_empty()),
+                            m_is_on_cpu: AtomicBool::new(false),
+                            m_exit: SpinMutex::const_new(ThreadExit::new()) });
+
+        /* the scheduler keeps the <Thread> alive while its context runs */
+        let hw_context =
+            HwThreadContext::new(stack_top, thread_start, Arc::as_ptr(&thread) as usize);
+        unsafe {
+            *thread.m_hw_context.get() = hw_context;
+        }
+        Some(thread)
+    }
+
+    /**
+     * Returns the `ThreadState` selected by `TaskConfigBits::StartPaused`
+     */
+    fn initial_state(config_flags: &TaskConfigFlags) -> ThreadState {
+        if config_flags.is_enabled(TaskConfigBits::StartPaused) {
+            ThreadState::Paused
+        } else {
+            ThreadState::Ready
+        }
+    }
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        if let Some(kern_stack) = self.m_kern_stack.take() {
+            MemManager::instance().free_kernel_stack(kern_stack);
+        }
+    }
+}
+
+/**
+ * Code which a `Thread` executes on its own stack
+ */
+enum ThreadEntry {
+    Kernel(KernThreadEntry, usize),
+    User(UserEntry)
+}
+
+/**
+ * Userspace state with which a userspace `Thread` leaves the kernel the
+ * first time
+ */
+struct UserEntry {
+    m_entry_point: VirtAddr,
+    m_stack_ptr: VirtAddr,
+    m_args: (usize, usize),
+    m_thread_ptr: VirtAddr
+}
+
+/**
+ * Exit state of a `Thread`, with the `Thread`s which wait for it
+ */
+struct ThreadExit {
+    m_exit_status: Option<TaskExitStatus>,
+    m_joiners: Vec<Arc<Thread>>
+}
+
+impl ThreadExit /* Constructors */ {
+    /**
+     * Constructs a `ThreadExit` of a running `Thread`
+     */
+    fn new() -> Self {
+        Self { m_exit_status: None,
+               m_joiners: Vec::new() }
+    }
+}
+
+/**
+ * Lists the scheduling states of a `Thread`
+ */
+#[repr(u8)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+pub enum ThreadState {
+    /**
+     * Waits into a run queue to be executed
+     */
+    Ready,
+
+    /**
+     * Is executing on a `Cpu`
+     */
+    Running,
+
+    /**
+     * Is not executed until resumed
+     */
+    Paused,
+
+    /**
+     * Has terminated, its resources are released with the last reference
+     */
+    Exited
+}
+
+/**
+ * Interface on which the `Thread` relies to save and restore the hardware
+ * context
+ */
+pub trait THwThreadContext {
+    /**
+     * Constructs an empty `HwThreadContext`, filled by the first switch
+     */
+    fn new_empty() -> Self;
+
+    /**
+     * Constructs the `HwThreadContext` of a new thread, which calls
+     * `start_fn(start_arg)` on the given stack with the interrupts
+     * disabled
+     */
+    fn new(stack_top: VirtAddr, start_fn: ThreadStartFn, start_arg: usize) -> Self;
+
+    /**
+     * Saves the executing context into `prev_context` and restores the
+     * `next_context`
+     */
+    unsafe fn switch(prev_context: *mut Self, next_context: *const Self);
+
+    /**
+     * Leaves the kernel to execute the userspace code at `entry_point` on
+     * the given stack, giving it `arg0` and `arg1` as the first two
+     * arguments of the calling convention.
+     *
+     * Must be called with the interrupts disabled and the `AddressSpace`
+     * of the userspace active
+     */
+    unsafe fn enter_user(entry_point: VirtAddr,
+                         stack_ptr: VirtAddr,
+                         arg0: usize,
+                         arg1: usize)
+                         -> !;
+}
+
+/**
+ * First function executed by the kernel `Thread`s
+ */
+extern "C" fn thread_start(raw_thread_ptr: usize) -> ! {
+    /* the <Scheduler> keeps a reference while the <Thread> runs */
+    let this_thread = unsafe { &*(raw_thread_ptr as *const Thread) };
+
+    Scheduler::finish_switch();
+    Cpu::current().enable_interrupts();
+
+    match this_thread.m_entry {
+        Some(ThreadEntry::Kernel(entry, arg)) => entry(arg),
+        Some(ThreadEntry::User(ref user_entry)) => {
+            /* the <Scheduler> has already activated the <AddressSpace> */
+            Cpu::current().disable_interrupts();
+            unsafe {
+                HwThreadContext::enter_user(user_entry.m_entry_point,
+                                            user_entry.m_stack_ptr,
+                                            user_entry.m_args.0,
+                                            user_entry.m_args.1)
+            }
+        },
+        None => {}
+    }
+    Scheduler::exit_current()
+}
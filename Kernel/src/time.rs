@@ -0,0 +1,470 @@
+/*! Kernel time management */
+
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    },
+    time::Duration
+};
+
+use api_data::{
+    instant::RawInstant,
+    object::info::RawObjInfo
+};
+use sync::SpinMutex;
+
+use crate::{
+    arch::time::hw_clocks::HwClocks,
+    cpu::{
+        Cpu,
+        CpuId
+    },
+    dbg_print::DbgLevel,
+    dbg_println
+};
+
+/* <None> until <TimeManager::init()> is called */
+static mut SM_CLOCK_SOURCE: Option<&'static dyn TClockSource> = None;
+static mut SM_CLOCK_EVENT: Option<&'static dyn TClockEvent> = None;
+
+/* <TClockSource> counter and wall clock seconds read by <TimeManager::init()> */
+static SM_BOOT_COUNTER: AtomicU64 = AtomicU64::new(0);
+static SM_BOOT_WALL_CLOCK_SECS: AtomicU64 = AtomicU64::new(0);
+
+/* sequence number of the next <TimerId>, which is stored above the <CpuId> */
+static SM_NEXT_TIMER_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/* bits of the <TimerId> which store the <CpuId> of the owner <TimerQueue> */
+const C_TIMER_ID_CPU_BITS: u64 = 16;
+
+/* maximum amount of pending timers of each <Cpu> */
+const C_MAX_TIMERS: usize = 64;
+
+const C_NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/**
+ * Unique identifier of a timer, which also tells the `Cpu` which runs it
+ */
+pub type TimerId = u64;
+
+/**
+ * Function called when a timer expires, with the interrupts disabled
+ */
+pub type TimerCallback = fn(timer_id: TimerId);
+
+/**
+ * Kernel clocks and timers management.
+ *
+ * Provides the monotonic clock, counted from the initialization with the
+ * `TClockSource`, and the one-shot/periodic timers, which are run by the
+ * `TClockEvent` of the `Cpu` which added them
+ */
+pub struct TimeManager;
+
+impl TimeManager /* Constructors */ {
+    /**
+     * Calibrates the hardware clocks and selects the `TClockSource` and
+     * the `TClockEvent`.
+     *
+     * Called once by `kernel_rust_start()` after the interrupts management
+     * of the primary CPU
+     */
+    pub fn init() {
+        unsafe {
+            assert!(SM_CLOCK_SOURCE.is_none(), "Called TimeManager::init() twice");
+        }
+
+        let (clock_source, clock_event) = HwClocks::init_clocks();
+        SM_BOOT_WALL_CLOCK_SECS.store(HwClocks::read_wall_clock_secs(), Ordering::SeqCst);
+        SM_BOOT_COUNTER.store(clock_source.read(), Ordering::SeqCst);
+        unsafe {
+            SM_CLOCK_SOURCE = Some(clock_source);
+            SM_CLOCK_EVENT = Some(clock_event);
+        }
+
+        dbg_println!(DbgLevel::Info,
+                     "Using {} ({}Hz) as clock source and {} as clock event",
+                     clock_source.name(),
+                     clock_source.frequency(),
+                     clock_event.name());
+    }
+}
+
+impl TimeManager /* Static Functions */ {
+    /**
+     * Returns the monotonic time elapsed from `TimeManager::init()`, zero
+     * before it
+     */
+    pub fn now() -> Duration {
+        let clock_source = if let Some(clock_source) = unsafe { SM_CLOCK_SOURCE } {
+            clock_source
+        } else {
+            return Duration::default();
+        };
+
+        let elapsed_ticks =
+            clock_source.read().saturating_sub(SM_BOOT_COUNTER.load(Ordering::SeqCst));
+        let elapsed_nanos =
+            elapsed_ticks as u128 * C_NANOS_PER_SEC / clock_source.frequency() as u128;
+        Duration::from_nanos(elapsed_nanos as u64)
+    }
+
+    /**
+     * Returns the wall clock time as `RawInstant` from the Unix epoch
+     */
+    pub fn now_instant() -> RawInstant {
+        Duration::from_secs(SM_BOOT_WALL_CLOCK_SECS.load(Ordering::SeqCst)) + Self::now()
+    }
+
+    /**
+     * Busy waits for the given `Duration`.
+     *
+     * Asserts on `TimeManager::init()` already called
+     */
+    pub fn delay(duration: Duration) {
+        unsafe {
+            assert!(SM_CLOCK_SOURCE.is_some(),
+                    "Called TimeManager::delay() before TimeManager::init()");
+        }
+
+        let deadline = Self::now() + duration;
+        while Self::now() < deadline {
+            hint::spin_loop();
+        }
+    }
+
+    /**
+     * Adds a timer which calls the given `TimerCallback` once, after the
+     * given delay, on the executing `Cpu`
+     */
+    pub fn add_one_shot_timer(delay: Duration, timer_callback: TimerCallback) -> TimerId {
+        Self::add_timer(delay, None, timer_callback)
+    }
+
+    /**
+     * Adds a timer which calls the given `TimerCallback` each given period
+     * on the executing `Cpu`, until cancelled
+     */
+    pub fn add_periodic_timer(period: Duration,
+                              timer_callback: TimerCallback)
+                              -> TimerId {
+        assert!(period.as_nanos() > 0, "Tried to add a periodic timer with zero period");
+
+        Self::add_timer(period, Some(period), timer_callback)
+    }
+
+    /**
+     * Removes the timer with the given `TimerId`.
+     *
+     * Returns `false` if the timer doesn't exist or it was a one-shot
+     * timer already expired
+     */
+    pub fn cancel_timer(timer_id: TimerId) -> bool {
+        let owner_cpu_id = (timer_id & ((1 << C_TIMER_ID_CPU_BITS) - 1)) as CpuId;
+
+        /* the owner CPU re-arms its clock event at the next expiration */
+        let this_cpu = Cpu::current();
+        let mut cancelled = false;
+        this_cpu.without_interrupts(|| {
+                    cancelled = Cpu::by_id(owner_cpu_id).timer_queue().remove(timer_id);
+                });
+        cancelled
+    }
+
+    /**
+     * Runs the expired timers of the executing `Cpu` and re-arms its
+     * `TClockEvent` for the next one.
+     *
+     * Called by the interrupt handler of the `TClockEvent`
+     */
+    pub fn handle_clock_event() {
+        let timer_queue = Cpu::current().timer_queue();
+
+        /* the callbacks are called without the lock, so they can add timers too */
+        while let Some((timer_id, callback)) = timer_queue.pop_expired(Self::now()) {
+            callback(timer_id);
+        }
+        Self::arm_clock_event();
+    }
+}
+
+impl TimeManager /* Privates */ {
+    /**
+     * Inserts a new timer into the `TimerQueue` of the executing `Cpu`
+     */
+    fn add_timer(delay: Duration,
+                 period: Option<Duration>,
+                 timer_callback: TimerCallback)
+                 -> TimerId {
+        let this_cpu = Cpu::current();
+        let timer_id = SM_NEXT_TIMER_SEQ.fetch_add(1, Ordering::SeqCst)
+                       << C_TIMER_ID_CPU_BITS
+                       | this_cpu.id() as TimerId;
+
+        let timer = Timer { m_timer_id: timer_id,
+                            m_deadline: Self::now() + delay,
+                            m_period: period,
+                            m_timer_callback: timer_callback };
+        this_cpu.without_interrupts(|| {
+                    this_cpu.timer_queue().insert(timer);
+                    Self::arm_clock_event();
+                });
+        timer_id
+    }
+
+    /**
+     * Programs the `TClockEvent` of the executing `Cpu` for the nearest
+     * deadline of its `TimerQueue`
+     */
+    fn arm_clock_event() {
+        let clock_event = unsafe {
+            SM_CLOCK_EVENT.expect("Tried to arm a timer before TimeManager::init()")
+        };
+
+        /* longer delays are reached re-arming at each expiration */
+        match Cpu::current().timer_queue().next_deadline() {
+            Some(deadline) => {
+                let delay = deadline.saturating_sub(Self::now());
+                clock_event.arm(delay.min(clock_event.max_delay()))
+            },
+            None => clock_event.disarm()
+        }
+    }
+}
+
+/**
+ * Per-`Cpu` fixed capacity collection of the pending timers.
+ *
+ * The timers are added and run into the interrupt handlers too, where the
+ * heap cannot be used, so the `TimerQueue` never allocates
+ */
+pub struct TimerQueue {
+    m_timers: SpinMutex<[Option<Timer>; C_MAX_TIMERS]>
+}
+
+impl TimerQueue /* Constructors */ {
+    /**
+     * Constructs an empty `TimerQueue`
+     */
+    pub const fn new() -> Self {
+        Self { m_timers: SpinMutex::const_new([None; C_MAX_TIMERS]) }
+    }
+}
+
+impl TimerQueue /* Privates */ {
+    /**
+     * Inserts the given `Timer`, must be called with the interrupts
+     * disabled.
+     *
+     * Panics when the `TimerQueue` is full
+     */
+    fn insert(&self, timer: Timer) {
+        let mut timers = self.m_timers.lock();
+
+        let free_slot = timers.iter_mut().find(|timer_slot| timer_slot.is_none());
+        if let Some(free_slot) = free_slot {
+            *free_slot = Some(timer);
+        } else {
+            panic!("Exceeded the limit of {} timers per Cpu", C_MAX_TIMERS);
+        }
+    }
+
+    /**
+     * Removes the `Timer` with the given `TimerId`, must be called with
+     * the interrupts disabled
+     */
+    fn remove(&self, timer_id: TimerId) -> bool {
+        let mut timers = self.m_timers.lock();
+
+        let timer_slot = timers.iter_mut().find(|timer_slot| {
+                                              timer_slot.map_or(false, |timer| {
+                                                            timer.m_timer_id == timer_id
+                                                        })
+                                          });
+        if let Some(timer_slot) = timer_slot {
+            *timer_slot = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Returns the nearest expired `Timer`, which is removed if one-shot
+     * or moved to its next period otherwise
+     */
+    fn pop_expired(&self, now: Duration) -> Option<(TimerId, TimerCallback)> {
+        let mut timers = self.m_timers.lock();
+
+        let timer_slot =
+            timers.iter_mut()
+                  .filter(|timer_slot| {
+                      timer_slot.map_or(false, |timer| timer.m_deadline <= now)
+                  })
+                  .min_by_key(|timer_slot| timer_slot.map(|timer| timer.m_deadline))?;
+
+        let timer = timer_slot.as_mut().unwrap();
+        let expired_timer = (timer.m_timer_id, timer.m_timer_callback);
+        if let Some(period) = timer.m_period {
+            /* the lost periods are skipped instead of being run in burst */
+            timer.m_deadline += period;
+            if timer.m_deadline <= now {
+                timer.m_deadline = now + period;
+            }
+        } else {
+            *timer_slot = None;
+        }
+        Some(expired_timer)
+    }
+
+    /**
+     * Returns the nearest deadline, `None` when there are no timers
+     */
+    fn next_deadline(&self) -> Option<Duration> {
+        self.m_timers.lock().iter().flatten().map(|timer| timer.m_deadline).min()
+    }
+}
+
+/**
+ * Interface of the hardware counters which measure the time
+ */
+pub trait TClockSource {
+    /**
+     * Returns the name of the counter, for debug purposes
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Returns the frequency in Hz of the counter
+     */
+    fn frequency(&self) -> u64;
+
+    /**
+     * Returns the current value of the counter, which must never go back
+     */
+    fn read(&self) -> u64;
+}
+
+/**
+ * Interface of the per-CPU hardware timers which raise an interrupt after
+ * a programmed delay
+ */
+pub trait TClockEvent {
+    /**
+     * Returns the name of the timer, for debug purposes
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Returns the longest delay accepted by `TClockEvent::arm()`
+     */
+    fn max_delay(&self) -> Duration;
+
+    /**
+     * Programs the timer of the executing CPU to raise an interrupt once,
+     * after the given delay, replacing the previous one.
+     *
+     * The interrupt handler must call `TimeManager::handle_clock_event()`
+     */
+    fn arm(&self, delay: Duration);
+
+    /**
+     * Cancels the pending interrupt of the timer of the executing CPU
+     */
+    fn disarm(&self);
+}
+
+/**
+ * Interface on which the `TimeManager` relies to initialize the hardware
+ * clocks
+ */
+pub trait THwClocks {
+    /**
+     * Calibrates the hardware clocks, registers the interrupt handler of
+     * the `TClockEvent` and returns the most accurate `TClockSource` and
+     * `TClockEvent`
+     */
+    fn init_clocks() -> (&'static dyn TClockSource, &'static dyn TClockEvent);
+
+    /**
+     * Returns the seconds elapsed from the Unix epoch according to the
+     * battery backed real time clock
+     */
+    fn read_wall_clock_secs() -> u64;
+}
+
+/**
+ * Updates the `RawInstant`s of the `RawObjInfo` with the wall clock of
+ * the `TimeManager`
+ */
+pub trait TObjInfoInstants {
+    /**
+     * Sets all the `RawInstant`s to now, for the just created `Object`s
+     */
+    fn init_instants(&mut self);
+
+    /**
+     * Updates the last data access `RawInstant`
+     */
+    fn update_data_access_inst(&mut self);
+
+    /**
+     * Updates the last data modification `RawInstant`, and the last info
+     * modification one since the used data size could change
+     */
+    fn update_data_modify_inst(&mut self);
+
+    /**
+     * Updates the last info access `RawInstant`
+     */
+    fn update_info_access_inst(&mut self);
+
+    /**
+     * Updates the last info modification `RawInstant`
+     */
+    fn update_info_modify_inst(&mut self);
+}
+
+impl TObjInfoInstants for RawObjInfo {
+    fn init_instants(&mut self) {
+        let now_instant = TimeManager::now_instant();
+
+        self.set_creat_inst(now_instant);
+        self.set_last_data_access_inst(now_instant);
+        self.set_last_data_modify_inst(now_instant);
+        self.set_last_info_access_inst(now_instant);
+        self.set_last_info_modify_inst(now_instant);
+    }
+
+    fn update_data_access_inst(&mut self) {
+        self.set_last_data_access_inst(TimeManager::now_instant());
+    }
+
+    fn update_data_modify_inst(&mut self) {
+        let now_instant = TimeManager::now_instant();
+
+        self.set_last_data_modify_inst(now_instant);
+        self.set_last_info_modify_inst(now_instant);
+    }
+
+    fn update_info_access_inst(&mut self) {
+        self.set_last_info_access_inst(TimeManager::now_instant());
+    }
+
+    fn update_info_modify_inst(&mut self) {
+        self.set_last_info_modify_inst(TimeManager::now_instant());
+    }
+}
+
+/**
+ * Pending timer into a `TimerQueue`
+ */
+#[derive(Copy, Clone)]
+struct Timer {
+    m_timer_id: TimerId,
+    m_deadline: Duration,
+    m_period: Option<Duration>,
+    m_timer_callback: TimerCallback
+}
@@ -0,0 +1,453 @@
+/*! User address space */
+
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+
+use helps::align::align_up;
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    vm::{
+        layout_manager::LayoutManager,
+        mem_manager::MemManager,
+        page_dir::PageDir,
+        page_table::{
+            PageTableIndex,
+            PageTableLevel
+        },
+        tlb::Tlb,
+        virt_alloc::VirtRangesAllocator,
+        MapFlags,
+        MapFlagsBits,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/**
+ * Per-process virtual address space.
+ *
+ * Owns a root page-table which shares the kernel space with the kernel
+ * `PageDir` and privately maps the `VmArea`s of the user space, which is
+ * `LayoutManager::USER_SPACE_BEGIN..LayoutManager::USER_SPACE_END`.
+ *
+ * When dropped all the `VmArea`s are unmapped and their frames, with the
+ * page-tables, are given back to the `MemManager`
+ */
+pub struct AddressSpace {
+    m_page_dir: PageDir,
+    m_vm_areas: SpinMutex<BTreeMap<VirtAddr, VmArea>>,
+    m_user_ranges_allocator: VirtRangesAllocator
+}
+
+impl AddressSpace /* Constructors */ {
+    /**
+     * Constructs an empty `AddressSpace` with a fresh root page-table
+     * which shares the kernel space entries of the kernel `PageDir`
+     */
+    pub fn new() -> Option<Self> {
+        let mem_manager = MemManager::instance();
+
+        /* allocate the new root page-table, clean it and copy the kernel entries */
+        let root_phys_frame = mem_manager.allocate_kernel_phys_frame()?;
+        let page_dir = PageDir::from_phys_frame(root_phys_frame);
+
+        let root_page_table = page_dir.root_page_table();
+        let kern_root_page_table = mem_manager.kernel_page_dir().root_page_table();
+        let kern_space_begin = VirtAddr::from(LayoutManager::KERN_SPACE_BEGIN);
+        let first_kern_root_index: usize =
+            kern_space_begin.page_table_index(PageTableLevel::Root).into();
+
+        root_page_table.clear();
+        for root_index in first_kern_root_index..512 {
+            let root_index = PageTableIndex::from(root_index);
+            root_page_table[root_index] = kern_root_page_table[root_index];
+        }
+
+        let user_space_range = VirtAddr::from(LayoutManager::USER_SPACE_BEGIN)
+                               ..VirtAddr::from(LayoutManager::USER_SPACE_END);
+        Some(Self { m_page_dir: page_dir,
+                    m_vm_areas: SpinMutex::const_new(BTreeMap::new()),
+                    m_user_ranges_allocator:
+                        VirtRangesAllocator::new(user_space_range) })
+    }
+}
+
+impl AddressSpace /* Methods */ {
+    /**
+     * Maps a new `VmArea` of at least `size` bytes, placed anywhere in the
+     * user space, with the given `MapFlags`.
+     *
+     * With `MapFlagsBits::LazyBacked` the frames are allocated by the page
+     * fault handler on the first access to each page
+     */
+    pub fn map_area(&self, size: usize, map_flags: MapFlags) -> Option<Range<VirtAddr>> {
+        let virt_range = self.m_user_ranges_allocator
+                             .allocate(align_up(size, Page4KiB::SIZE), Page4KiB::SIZE)?;
+
+        if self.back_area(virt_range.clone(), map_flags) {
+            Some(virt_range)
+        } else {
+            self.m_user_ranges_allocator.free(virt_range);
+            None
+        }
+    }
+
+    /**
+     * Maps a new `VmArea` exactly at the given page aligned `Range` with
+     * the given `MapFlags`.
+     *
+     * Returns `false` if the `Range` overlaps another `VmArea`, it is out
+     * of the user space or the memory is exhausted
+     */
+    pub fn map_area_at(&self, virt_range: Range<VirtAddr>, map_flags: MapFlags) -> bool {
+        assert!(virt_range.start.is_aligned(Page4KiB::SIZE)
+                && virt_range.end.is_aligned(Page4KiB::SIZE),
+                "Tried to map a non page aligned user area");
+
+        if !self.m_user_ranges_allocator.allocate_at(virt_range.clone()) {
+            return false;
+        }
+
+        if self.back_area(virt_range.clone(), map_flags) {
+            true
+        } else {
+            self.m_user_ranges_allocator.free(virt_range);
+            false
+        }
+    }
+
+    /**
+     * Unmaps the `VmArea` which begins at the given `VirtAddr` giving back
+     * its frames.
+     *
+     * Returns `false` if no `VmArea` begins at the given `VirtAddr`
+     */
+    pub fn unmap_area(&self, area_begin: VirtAddr) -> bool {
+        let vm_area = if let Some(vm_area) = self.m_vm_areas.lock().remove(&area_begin) {
+            vm_area
+        } else {
+            return false;
+        };
+
+        self.m_page_dir.unmap::<Page4KiB>(vm_area.virt_range().clone(), true);
+        self.m_user_ranges_allocator.free(vm_area.virt_range().clone());
+        true
+    }
+
+    /**
+     * Duplicates this `AddressSpace` sharing all the backed frames, which
+     * the writeable ones as copy-on-write, so each page is privatised by
+     * the first write of either of the two `AddressSpace`s.
+     *
+     * The lazy pages not yet backed stay lazy in both of them
+     */
+    pub fn fork(&self) -> Option<Self> {
+        let forked_addr_space = Self::new()?;
+
+        for vm_area in self.m_vm_areas.lock().values() {
+            let virt_range = vm_area.virt_range().clone();
+
+            /* register the area first, so the drop cleans up the failures */
+            if !forked_addr_space.m_user_ranges_allocator.allocate_at(virt_range.clone())
+            {
+                return None;
+            }
+            forked_addr_space.m_vm_areas.lock().insert(virt_range.start, vm_area.clone());
+
+            /* the other threads of this address space could privatise the same
+             * entries meanwhile, while the forked one is still private
+             */
+            let forked = self.m_page_dir.with_page_tables_locked(|| {
+                forked_addr_space.m_page_dir.with_page_tables_locked(|| {
+                    self.fork_area_locked(&forked_addr_space, &virt_range)
+                })
+            });
+
+            /* drop the writeable translations of this address space, even when
+             * failed, the already write-protected pages must stay so
+             */
+            Tlb::shootdown(&virt_range);
+            if !forked {
+                return None;
+            }
+        }
+        Some(forked_addr_space)
+    }
+
+    /**
+     * Copies the given bytes into the user pages which begin at the given
+     * `VirtAddr` through the physical memory mapping, so this
+     * `AddressSpace` could be inactive.
+     *
+     * The protection of the pages is ignored and the lazy ones are backed
+     * with zeroed frames. Returns `false` if some page is not mapped, is
+     * copy-on-write or the memory is exhausted
+     */
+    pub fn write_bytes(&self, virt_addr: VirtAddr, bytes: &[u8]) -> bool {
+        let layout_manager = MemManager::instance().layout_manager();
+
+        let mut written_bytes = 0;
+        while written_bytes < bytes.len() {
+            let chunk_virt_addr = virt_addr.offset(written_bytes);
+            let page_offset = *chunk_virt_addr % Page4KiB::SIZE;
+            let chunk_size =
+                (Page4KiB::SIZE - page_offset).min(bytes.len() - written_bytes);
+
+            let phys_frame =
+                match self.backed_phys_frame(chunk_virt_addr.align_down(Page4KiB::SIZE)) {
+                    Some(phys_frame) => phys_frame,
+                    None => return false
+                };
+            unsafe {
+                layout_manager.phys_addr_to_virt_addr(phys_frame)
+                              .offset(page_offset)
+                              .as_ptr_mut::<u8>()
+                              .copy_from_nonoverlapping(bytes[written_bytes..].as_ptr(),
+                                                        chunk_size);
+            }
+            written_bytes += chunk_size;
+        }
+        true
+    }
+
+    /**
+     * Switches the executing CPU to this `AddressSpace`
+     */
+    pub unsafe fn activate(&self) {
+        self.m_page_dir.activate();
+    }
+}
+
+impl AddressSpace /* Getters */ {
+    /**
+     * Returns the `VmArea` which contains the given `VirtAddr`
+     */
+    pub fn vm_area_at(&self, virt_addr: VirtAddr) -> Option<VmArea> {
+        self.m_vm_areas
+            .lock()
+            .range(..=virt_addr)
+            .next_back()
+            .map(|(_, vm_area)| vm_area)
+            .filter(|vm_area| vm_area.virt_range().contains(&virt_addr))
+            .cloned()
+    }
+
+    /**
+     * Returns whether this `AddressSpace` is the one active on the
+     * executing CPU
+     */
+    pub fn is_active(&self) -> bool {
+        PageDir::current().root_phys_frame() == self.m_page_dir.root_phys_frame()
+    }
+
+    /**
+     * Returns the reference to the `PageDir` of this `AddressSpace`
+     */
+    pub fn page_dir(&self) -> &PageDir {
+        &self.m_page_dir
+    }
+}
+
+impl AddressSpace /* Privates */ {
+    /**
+     * Maps the pages of the given `Range` for userspace and registers the
+     * new `VmArea`
+     */
+    fn back_area(&self, virt_range: Range<VirtAddr>, map_flags: MapFlags) -> bool {
+        /* the user mappings are never shared among the address spaces */
+        let mut map_flags = map_flags;
+        map_flags.set_enabled(MapFlagsBits::User).set_disabled(MapFlagsBits::Global);
+
+        let mapped = if map_flags.is_enabled(MapFlagsBits::LazyBacked) {
+            self.m_page_dir.map_lazy(virt_range.clone(), map_flags)
+        } else {
+            self.back_area_with_frames(virt_range.clone(), map_flags)
+        };
+
+        if mapped {
+            self.m_vm_areas.lock().insert(virt_range.start,
+                                          VmArea { m_virt_range: virt_range,
+                                                   m_map_flags: map_flags });
+        }
+        mapped
+    }
+
+    /**
+     * Returns the physical frame of the given user page, backing it when
+     * lazy, or `None` if it can't be written through the physical memory
+     * mapping
+     */
+    fn backed_phys_frame(&self, page_virt_addr: VirtAddr) -> Option<PhysAddr> {
+        /* the threads of this address space could fault on the same page */
+        self.m_page_dir
+            .with_page_tables_locked(|| self.do_backed_phys_frame(page_virt_addr))
+    }
+
+    /**
+     * Implements `AddressSpace::backed_phys_frame()` with the page-tables
+     * locked
+     */
+    fn do_backed_phys_frame(&self, page_virt_addr: VirtAddr) -> Option<PhysAddr> {
+        let page_table_entry = self.m_page_dir
+                                   .page_table_entry::<Page4KiB>(page_virt_addr)
+                                   .filter(|page_table_entry| {
+                                       page_table_entry.is_user()
+                                       && !page_table_entry.is_copy_on_write()
+                                   })?;
+
+        if page_table_entry.is_lazy() {
+            let phys_frame = Self::allocate_zeroed_phys_frame()?;
+
+            page_table_entry.set_phys_frame(phys_frame);
+            page_table_entry.set_lazy(false);
+            page_table_entry.set_present(true);
+
+            /* some CPUs cache the misses of the non-present entries */
+            if self.is_active() {
+                Tlb::invalidate(page_virt_addr);
+            }
+        }
+        page_table_entry.phys_frame()
+    }
+
+    /**
+     * Shares the pages of the given `Range` with the given forked
+     * `AddressSpace` like `AddressSpace::fork()`, with the page-tables of
+     * both locked.
+     *
+     * Returns `false` if a page-table of the forked `AddressSpace` cannot
+     * be allocated
+     */
+    fn fork_area_locked(&self,
+                        forked_addr_space: &AddressSpace,
+                        virt_range: &Range<VirtAddr>)
+                        -> bool {
+        let mem_manager = MemManager::instance();
+
+        for virt_addr in virt_range.clone().step_by(Page4KiB::SIZE) {
+            let page_table_entry =
+                match self.m_page_dir.page_table_entry::<Page4KiB>(virt_addr) {
+                    Some(page_table_entry) if !page_table_entry.is_unused() => {
+                        page_table_entry
+                    },
+                    _ => continue
+                };
+
+            let forked_page_table_entry =
+                match forked_addr_space.m_page_dir
+                                       .ensure_page_table_entry::<Page4KiB>(virt_addr)
+                {
+                    Some(forked_page_table_entry) => forked_page_table_entry,
+                    None => return false
+                };
+
+            /* share the backed frame, write-protecting it when writeable */
+            if let Some(phys_frame) = page_table_entry.phys_frame() {
+                if page_table_entry.is_writeable() {
+                    page_table_entry.set_copy_on_write(true);
+                }
+                mem_manager.ref_phys_frame(phys_frame);
+            }
+            *forked_page_table_entry = *page_table_entry;
+        }
+        true
+    }
+
+    /**
+     * Maps each page of the given `Range` with a zeroed frame
+     */
+    fn back_area_with_frames(&self,
+                             virt_range: Range<VirtAddr>,
+                             map_flags: MapFlags)
+                             -> bool {
+        let mem_manager = MemManager::instance();
+
+        for virt_addr in virt_range.clone().step_by(Page4KiB::SIZE) {
+            let phys_frame = Self::allocate_zeroed_phys_frame();
+            let mapped =
+                phys_frame.map_or(false, |phys_frame| {
+                              let page_range = virt_addr.to_range(Page4KiB::SIZE);
+                              if self.m_page_dir
+                                     .map::<Page4KiB>(page_range, phys_frame, map_flags)
+                              {
+                                  true
+                              } else {
+                                  mem_manager.free_phys_frame(phys_frame);
+                                  false
+                              }
+                          });
+
+            /* on failure give back the already mapped part of the area */
+            if !mapped {
+                self.m_page_dir.unmap::<Page4KiB>(virt_range.start..virt_addr, true);
+                return false;
+            }
+        }
+        true
+    }
+
+    /**
+     * Allocates a physical frame for the user space and zeroes it
+     */
+    fn allocate_zeroed_phys_frame() -> Option<PhysAddr> {
+        let mem_manager = MemManager::instance();
+        let phys_frame = mem_manager.allocate_kernel_phys_frame()?;
+
+        /* the address space could be inactive, zero through the phys mapping */
+        unsafe {
+            mem_manager.layout_manager()
+                       .phys_addr_to_virt_addr(phys_frame)
+                       .as_ptr_mut::<u8>()
+                       .write_bytes(0, Page4KiB::SIZE);
+        }
+        Some(phys_frame)
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        assert!(!self.is_active(), "Tried to destroy the active AddressSpace");
+
+        /* unmap all the areas, then release the remaining user page-tables */
+        for vm_area in self.m_vm_areas.lock().values() {
+            self.m_page_dir.unmap::<Page4KiB>(vm_area.virt_range().clone(), true);
+        }
+        self.m_page_dir
+            .release_page_tables(VirtAddr::from(LayoutManager::USER_SPACE_BEGIN)
+                                 ..VirtAddr::from(LayoutManager::USER_SPACE_END));
+
+        MemManager::instance().free_phys_frame(self.m_page_dir.root_phys_frame());
+    }
+}
+
+/**
+ * User virtual memory area of an `AddressSpace`
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct VmArea {
+    m_virt_range: Range<VirtAddr>,
+    m_map_flags: MapFlags
+}
+
+impl VmArea /* Getters */ {
+    /**
+     * Returns the virtual `Range` covered by this `VmArea`
+     */
+    pub fn virt_range(&self) -> &Range<VirtAddr> {
+        &self.m_virt_range
+    }
+
+    /**
+     * Returns the `MapFlags` used to map this `VmArea`
+     */
+    pub fn map_flags(&self) -> MapFlags {
+        self.m_map_flags
+    }
+}
@@ -0,0 +1,96 @@
+/*! Physical frames references counting */
+
+use core::{
+    ops::Range,
+    slice,
+    sync::atomic::{
+        AtomicU32,
+        Ordering
+    }
+};
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    vm::{
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/**
+ * Counts the additional owners of each 4KiB physical frame.
+ *
+ * A zero counter means that the frame has a single owner (or none), so
+ * only the shared frames, i.e. the copy-on-write ones, have a non-zero
+ * counter. The counters are stored into an eagerly backed kernel region,
+ * since the copy-on-write resolution accesses them from the page fault
+ * handler, which must never fault itself
+ */
+pub struct PhysFrameRefs {
+    m_shared_refs: &'static [AtomicU32]
+}
+
+impl PhysFrameRefs /* Constructors */ {
+    /**
+     * Constructs a `PhysFrameRefs` which stores the counters into the
+     * given zeroed kernel region
+     */
+    pub fn new(refs_region: Range<VirtAddr>) -> Self {
+        let refs_count = (*refs_region.end - *refs_region.start) / 4;
+
+        Self { m_shared_refs: unsafe {
+                   slice::from_raw_parts(refs_region.start.as_ptr_mut::<AtomicU32>(),
+                                         refs_count)
+               } }
+    }
+
+    /**
+     * Returns the size in bytes of the region needed to count the
+     * references of the frames until `last_phys_mem_addr`
+     */
+    pub fn region_size(last_phys_mem_addr: PhysAddr) -> usize {
+        last_phys_mem_addr.align_up(Page4KiB::SIZE).as_page_index::<Page4KiB>() * 4
+    }
+}
+
+impl PhysFrameRefs /* Methods */ {
+    /**
+     * Adds an owner to the given frame
+     */
+    pub fn acquire(&self, phys_frame: PhysAddr) {
+        self.refs_of(phys_frame).fetch_add(1, Ordering::SeqCst);
+    }
+
+    /**
+     * Removes an owner from the given frame.
+     *
+     * Returns `true` if it was the last owner, so the frame must be freed
+     */
+    pub fn release(&self, phys_frame: PhysAddr) -> bool {
+        self.refs_of(phys_frame)
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |refs| refs.checked_sub(1))
+            .is_err()
+    }
+}
+
+impl PhysFrameRefs /* Getters */ {
+    /**
+     * Returns whether the given frame has more than one owner
+     */
+    pub fn is_shared(&self, phys_frame: PhysAddr) -> bool {
+        self.refs_of(phys_frame).load(Ordering::SeqCst) > 0
+    }
+}
+
+impl PhysFrameRefs /* Privates */ {
+    /**
+     * Returns the counter of the given frame
+     */
+    fn refs_of(&self, phys_frame: PhysAddr) -> &AtomicU32 {
+        &self.m_shared_refs[phys_frame.as_page_index::<Page4KiB>()]
+    }
+}
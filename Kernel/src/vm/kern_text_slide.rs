@@ -0,0 +1,143 @@
+/*! Kernel text slide */
+
+use core::ptr;
+
+use helps::{
+    align::align_up,
+    dbg::C_KIB
+};
+
+use crate::{
+    boot_info::BootInfo,
+    dev::DevManager,
+    vm::{
+        layout_manager::LayoutManager,
+        Page2MiB,
+        TPageSize
+    }
+};
+
+extern "C" {
+    static __kernel_text_end: usize;
+    static __kernel_relocs_begin: u32;
+}
+
+/**
+ * The reserved size for the `S_KERN_RELOCS_STORAGE`
+ */
+const C_KERN_RELOCS_STORAGE_SIZE: usize = 256 * C_KIB;
+
+/**
+ * First word of the relocations written by the build, keep in sync with
+ * `Tools/KernRelocsTool`
+ */
+const C_KERN_RELOCS_MAGIC: u32 = 0x4c45_524b;
+
+/**
+ * Dedicated section inside the ELF where the build-process stores the
+ * sites of the absolute references to the kernel image, which
+ * `Kernel/arch/<arch_name>/kernel_start.S` patches to slide it
+ */
+#[used]
+#[link_section = ".kern_relocs"]
+static S_KERN_RELOCS_STORAGE: [u8; C_KERN_RELOCS_STORAGE_SIZE] =
+    [0; C_KERN_RELOCS_STORAGE_SIZE];
+
+/* <0> until <KernTextSlide::choose()> slides the kernel image */
+static mut SM_KERN_TEXT_SLIDE: usize = 0;
+
+/* <None> when the kernel layout is not randomized */
+static mut SM_KASLR_SEED: Option<u64> = None;
+
+/**
+ * Boot-time slide of the kernel image.
+ *
+ * The kernel is linked at `LayoutManager::KERN_VIRT_BASE`, the boot code
+ * maps it at the chosen slide too and patches its absolute references
+ * before continuing from the slid `kernel_rust_start()`
+ */
+pub struct KernTextSlide;
+
+impl KernTextSlide /* Static Functions */ {
+    /**
+     * Resolves the KASLR seed and returns the slide of the kernel image
+     * obtained from it, zero when the layout is plain or the image has no
+     * relocations.
+     *
+     * Called only once by the boot code, while the image is executed at
+     * its link address and before the heap exists, so nothing which keeps
+     * pointers or allocates can be used here
+     */
+    pub fn choose(raw_boot_info_ptr: *const u8) -> usize {
+        let kaslr_seed =
+            BootInfo::with_raw_cmd_line_args(raw_boot_info_ptr, Self::kaslr_seed_from);
+        unsafe {
+            SM_KASLR_SEED = kaslr_seed;
+        }
+
+        match kaslr_seed {
+            Some(kaslr_seed) if Self::is_relocatable() => {
+                let kern_text_slide =
+                    LayoutManager::kern_text_slide(kaslr_seed, Self::image_size());
+                unsafe {
+                    SM_KERN_TEXT_SLIDE = kern_text_slide;
+                }
+                kern_text_slide
+            },
+            _ => 0
+        }
+    }
+
+    /**
+     * Returns the amount of bytes which the kernel image is slid by from
+     * `LayoutManager::KERN_VIRT_BASE`
+     */
+    pub fn slide() -> usize {
+        unsafe { SM_KERN_TEXT_SLIDE }
+    }
+
+    /**
+     * Returns the seed used to randomize the kernel layout, `None` for the
+     * plain layout
+     */
+    pub fn kaslr_seed() -> Option<u64> {
+        unsafe { SM_KASLR_SEED }
+    }
+
+    /**
+     * Returns whether the build has written the relocations of the kernel
+     * image, without them it can't be slid
+     */
+    pub fn is_relocatable() -> bool {
+        /* the storage is opaque to the compiler, which would fold its zeroes */
+        unsafe { ptr::read_volatile(&__kernel_relocs_begin) == C_KERN_RELOCS_MAGIC }
+    }
+
+    /**
+     * Returns the size of the window which maps the kernel image with 2MiB
+     * pages, from the physical address zero to its end
+     */
+    pub fn image_size() -> usize {
+        let kern_image_end = unsafe { &__kernel_text_end as *const _ as usize };
+
+        align_up(kern_image_end - LayoutManager::KERN_VIRT_BASE - Self::slide(),
+                 Page2MiB::SIZE)
+    }
+}
+
+impl KernTextSlide /* Privates */ {
+    /**
+     * Returns the KASLR seed requested by the given command line arguments
+     */
+    fn kaslr_seed_from(cmd_line_args: &str) -> Option<u64> {
+        if BootInfo::cmd_line_find_arg_in(cmd_line_args, "-plain-vm-layout").is_some() {
+            return None;
+        }
+
+        /* a fixed seed reproduces the same layout among the boots */
+        match BootInfo::cmd_line_find_arg_int_in(cmd_line_args, "-kaslr-seed") {
+            Some((_, Some(kaslr_seed))) => Some(kaslr_seed as u64),
+            _ => Some(DevManager::early_random_u64())
+        }
+    }
+}
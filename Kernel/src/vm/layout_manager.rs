@@ -4,7 +4,10 @@ use alloc::vec::Vec;
 
 use core::{
     fmt,
-    fmt::Display,
+    fmt::{
+        Debug,
+        Display
+    },
     ops::Range
 };
 
@@ -24,23 +27,23 @@ use crate::{
     },
     dbg_print::DbgLevel,
     dbg_println,
-    dev::{
-        DevManager,
-        TDevice
-    },
     vm::{
+        kern_text_slide::KernTextSlide,
+        Page1GiB,
         Page2MiB,
         Page4KiB,
         TPageSize
     }
 };
-use api_data::object::device::DeviceIdClass;
 
 extern "C" {
     static __kernel_text_begin: usize;
     static __kernel_text_end: usize;
 }
 
+/* first non-canonical address after the lower half of the VM */
+const C_LOWER_HALF_END: usize = 0x0000_8000_0000_0000;
+
 /* <false> until <MemManager::init_instance()> is called */
 static mut SM_INSTANCE_INITIALIZED: bool = false;
 
@@ -55,38 +58,69 @@ pub struct LayoutManager {
     m_kern_regions_range: Range<VirtAddr>,
     m_fs_page_cache_range: Range<VirtAddr>,
     m_kern_text_range: Range<VirtAddr>,
-    m_kern_text_phys_range: Range<PhysAddr>
+    m_kern_text_phys_range: Range<PhysAddr>,
+    m_kaslr_seed: Option<u64>
 }
 
 impl LayoutManager /* Constants */ {
+    /**
+     * User space begins after the first page, which is never mapped to
+     * catch the null pointers
+     */
+    pub const USER_SPACE_BEGIN: usize = 0x0000_0000_0000_1000;
+
+    /**
+     * User space ends one page before the end of the lower canonical half,
+     * so the last canonical page is never mapped and a `syscall` at its
+     * end can never return to a non-canonical address
+     */
+    pub const USER_SPACE_END: usize = 0x0000_7fff_ffff_f000;
+
     /**
      * Kernel space begins at virtual offset of 192TiB
      */
-    const KERN_SPACE_BEGIN: usize = 0xffff_c000_0000_0000;
+    pub const KERN_SPACE_BEGIN: usize = 0xffff_c000_0000_0000;
 
     /**
      * Kernel base virtual address. Keep this in sync with various
      * `Kernel/linker.ld/KERNEL_VIRT_BASE`
      */
-    const KERN_VIRT_BASE: usize = 0xffff_ffff_c000_0000;
+    pub const KERN_VIRT_BASE: usize = 0xffff_ffff_c000_0000;
 }
 
+/* the user space must be canonical and must leave its last page unmapped */
+const _: () = assert!(LayoutManager::USER_SPACE_END
+                      <= C_LOWER_HALF_END - Page4KiB::SIZE,
+                      "USER_SPACE_END must be a canonical lower-half address");
+
 impl LayoutManager /* Constructor */ {
     /**
-     * Constructs a `LayoutManager` randomizing the `LayoutComponent`s as
-     * meltdown mitigation
+     * Constructs a `LayoutManager` randomizing the order of the
+     * `LayoutComponent`s and sliding each of them into its own window as
+     * meltdown mitigation.
+     *
+     * The same `kaslr_seed` always produces the same layout, together with
+     * the same slide of the kernel text, which is chosen from it at boot
      */
-    pub fn new_randomized(phys_mem_size: usize) -> Self {
-        /* obtain the ordered and sized <LayoutComponents> */
-        let sized_layout_components = Self::size_components(phys_mem_size);
+    pub fn new_randomized(phys_mem_size: usize, kaslr_seed: u64) -> Self {
+        let mut layout_randomizer = LayoutRandomizer::new(kaslr_seed);
+
+        /* the first number has slid the kernel text, see <kern_text_slide()> */
+        layout_randomizer.next_u64();
+
+        /* obtain the ordered and sized <LayoutComponents> with their slide window */
+        let (sized_layout_components, slide_window_size) =
+            Self::size_components(phys_mem_size, true);
 
         /* randomize the order of the LayoutComponent */
         let randomized_layout_components =
-            Self::randomize_components(&sized_layout_components);
+            Self::randomize_components(&sized_layout_components, &mut layout_randomizer);
 
-        /* assign them a virtual-range */
+        /* assign them a slid virtual-range */
         let unordered_vm_layout_ranges =
-            Self::place_components(&randomized_layout_components);
+            Self::place_components(&randomized_layout_components,
+                                   Some(&mut layout_randomizer),
+                                   slide_window_size);
 
         /* re-order back the ranges as expected by the constructor */
         let ordered_vm_layout_ranges =
@@ -94,7 +128,7 @@ impl LayoutManager /* Constructor */ {
                                        &unordered_vm_layout_ranges);
 
         /* construct the LayoutManager */
-        Self::new(&ordered_vm_layout_ranges)
+        Self::new(&ordered_vm_layout_ranges, Some(kaslr_seed))
     }
 
     /**
@@ -103,19 +137,19 @@ impl LayoutManager /* Constructor */ {
      */
     pub fn new_plain(phys_mem_size: usize) -> Self {
         /* obtain the ordered and sized <LayoutComponents> */
-        let sized_layout_components = Self::size_components(phys_mem_size);
+        let (sized_layout_components, _) = Self::size_components(phys_mem_size, false);
 
         /* place the components into the VM */
-        let vm_layout_ranges = Self::place_components(&sized_layout_components);
+        let vm_layout_ranges = Self::place_components(&sized_layout_components, None, 0);
 
         /* construct the LayoutManager */
-        Self::new(&vm_layout_ranges)
+        Self::new(&vm_layout_ranges, None)
     }
 
     /**
      * Internal constructor called by the previous two functions
      */
-    fn new(vm_layout_ranges: &Vec<Range<VirtAddr>>) -> Self {
+    fn new(vm_layout_ranges: &Vec<Range<VirtAddr>>, kaslr_seed: Option<u64>) -> Self {
         /* the LayoutManager is a singleton stored into the <MemManager> */
         unsafe {
             if SM_INSTANCE_INITIALIZED {
@@ -159,15 +193,19 @@ impl LayoutManager /* Constructor */ {
                     * the kernel text, so a convenient <PhysAddr> <Range> is stored
                     * into the <LayoutManager>
                     */
+                   let kern_image_base =
+                       LayoutManager::KERN_VIRT_BASE + KernTextSlide::slide();
+
                    Range { start: unsafe {
                                       &__kernel_text_begin as *const _ as usize
-                                      - LayoutManager::KERN_VIRT_BASE
+                                      - kern_image_base
                                   }.into(),
                            end: unsafe {
                                     &__kernel_text_end as *const _ as usize
-                                    - LayoutManager::KERN_VIRT_BASE
+                                    - kern_image_base
                                 }.into() }
-               } }
+               },
+               m_kaslr_seed: kaslr_seed }
     }
 }
 
@@ -181,6 +219,27 @@ impl LayoutManager /* Methods */ {
     }
 }
 
+impl LayoutManager /* Static Functions */ {
+    /**
+     * Returns the slide of the kernel image obtained from the given
+     * `kaslr_seed`, for an image which needs the given `image_size`
+     * (2MiB aligned) from the physical address zero.
+     *
+     * The image slides by 2MiB steps into the last GiB, where the boot
+     * page-tables map it with 2MiB pages. The slid image never overlaps
+     * the link one, still in use while the relocations are applied, and
+     * never reaches the last 2MiB, so its ranges don't overflow
+     */
+    pub fn kern_text_slide(kaslr_seed: u64, image_size: usize) -> usize {
+        let slide_slots =
+            (Page1GiB::SIZE - Page2MiB::SIZE - 2 * image_size) / Page2MiB::SIZE + 1;
+
+        let slide_slot =
+            LayoutRandomizer::new(kaslr_seed).next_below(slide_slots as u64) as usize;
+        image_size + slide_slot * Page2MiB::SIZE
+    }
+}
+
 impl LayoutManager /* Getters */ {
     /**
      * Returns the virtual `Range` where is mapped all the memory
@@ -224,13 +283,25 @@ impl LayoutManager /* Getters */ {
     pub fn kern_text_phys_range(&self) -> &Range<PhysAddr> {
         &self.m_kern_text_phys_range
     }
+
+    /**
+     * Returns the seed used to randomize this layout, `None` for the plain
+     * layout
+     */
+    pub fn kaslr_seed(&self) -> Option<u64> {
+        self.m_kaslr_seed
+    }
 }
 
 impl LayoutManager /* Privates */ {
     /**
-     * Returns all the `LayoutComponent`s with a size
+     * Returns all the `LayoutComponent`s with a size and the size of the
+     * window where each of them can slide, which is reserved only when
+     * `reserve_slides` is `true`
      */
-    fn size_components(phys_mem_size: usize) -> Vec<LayoutComponent> {
+    fn size_components(phys_mem_size: usize,
+                       reserve_slides: bool)
+                       -> (Vec<LayoutComponent>, usize) {
         /* reserve 2 MiB to be able to map up to 512 4KiB pages or one huge 2MiB page */
         let tmp_mapping_size = Page2MiB::SIZE;
 
@@ -238,11 +309,11 @@ impl LayoutManager /* Privates */ {
          * the physical memory.
          *
          * Since the kernel uses a memory-mapped-paging strategy for all the
-         * architectures this reservation is fundamental. The is size is 2MiB aligned
-         * because 2MiB huge-pages are used, to avoid too much waste in intermediate
-         * page-tables
+         * architectures this reservation is fundamental. The is size is 1GiB aligned
+         * because 1GiB (or 2MiB when not supported) huge-pages are used, to avoid too
+         * much waste in intermediate page-tables
          */
-        let phys_mem_mapping_size = align_up(phys_mem_size, Page2MiB::SIZE);
+        let phys_mem_mapping_size = align_up(phys_mem_size, Page1GiB::SIZE);
 
         /* obtain the remaining virtual space removing the kernel text */
         let rem_vm_kern_space_size = {
@@ -265,22 +336,45 @@ impl LayoutManager /* Privates */ {
         let rem_vm_kern_space_size =
             rem_vm_kern_space_size - phys_mem_mapping_size - tmp_mapping_size;
 
+        /* reserve an eighth of the remaining space to slide the components. Each
+         * window is 1GiB aligned to slide the physical memory mapping too
+         */
+        let slide_window_size = if reserve_slides {
+            align_down(rem_vm_kern_space_size / 8 / LayoutComponent::COUNT,
+                       Page1GiB::SIZE)
+        } else {
+            0
+        };
+        if reserve_slides && slide_window_size == 0 {
+            dbg_println!(DbgLevel::Warn,
+                         "No room for 1GiB slide windows, the layout components are \
+                          only shuffled");
+        }
+        let rem_vm_kern_space_size =
+            rem_vm_kern_space_size - slide_window_size * LayoutComponent::COUNT;
+
         /* remaining components receives an equal & shrinkable portion of the layout */
         let shrinkable_components_size = align_down(rem_vm_kern_space_size
                                                     / LayoutComponent::SHRINKABLES.len(),
                                                     Page4KiB::SIZE);
 
         /* return the components with the size */
-        vec![LayoutComponent::PhysMemMapping { m_size: phys_mem_mapping_size },
-             LayoutComponent::TmpMapping { m_size: tmp_mapping_size },
-             LayoutComponent::KernRegions { m_size: shrinkable_components_size },
-             LayoutComponent::FsPageCache { m_size: shrinkable_components_size }]
+        (vec![LayoutComponent::PhysMemMapping { m_size: phys_mem_mapping_size },
+              LayoutComponent::TmpMapping { m_size: tmp_mapping_size },
+              LayoutComponent::KernRegions { m_size: shrinkable_components_size },
+              LayoutComponent::FsPageCache { m_size: shrinkable_components_size }],
+         slide_window_size)
     }
 
     /**
-     * Places the given `LayoutComponent`s into VM
+     * Places the given `LayoutComponent`s into VM.
+     *
+     * When the `LayoutRandomizer` is given each component is slid by a
+     * random amount into its `slide_window_size` window
      */
-    fn place_components(layout_components: &Vec<LayoutComponent>)
+    fn place_components(layout_components: &Vec<LayoutComponent>,
+                        mut layout_randomizer: Option<&mut LayoutRandomizer>,
+                        slide_window_size: usize)
                         -> Vec<Range<VirtAddr>> {
         /* alignment mismatching and reset when encountered shrinkable components */
         let mut total_alignment_diff = 0;
@@ -291,8 +385,24 @@ impl LayoutManager /* Privates */ {
 
         /* place <LayoutComponent>s into virtual memory */
         for (i, &layout_component) in layout_components.iter().enumerate() {
+            /* extract an aligned slide inside the window of the component */
+            let slide =
+                layout_randomizer.as_mut().map_or(0, |layout_randomizer| {
+                                              let component_alignment =
+                                                  layout_component.alignment();
+                                              let slide_slots = slide_window_size
+                                                                / component_alignment
+                                                                + 1;
+
+                                              layout_randomizer.next_below(slide_slots
+                                                                           as u64)
+                                              as usize
+                                              * component_alignment
+                                          });
+
             /* obtain the aligned Range<VirtAddr> for the current component */
             layout_ranges[i] = Self::place_component(layout_component,
+                                                     slide,
                                                      &mut vm_range_addr,
                                                      &mut total_alignment_diff);
         }
@@ -301,13 +411,18 @@ impl LayoutManager /* Privates */ {
     }
 
     /**
-     * Places the given `LayoutComponent` at the given `VirtAddr` absorbing
-     * if possible the eventual alignment discard produced by the alignment
+     * Places the given `LayoutComponent` at the given `VirtAddr` plus the
+     * given `slide` absorbing if possible the eventual alignment discard
+     * produced by the alignment
      */
     fn place_component(layout_component: LayoutComponent,
+                       slide: usize,
                        vm_range_addr: &mut VirtAddr,
                        total_alignment_diff: &mut usize)
                        -> Range<VirtAddr> {
+        /* the slide is already reserved, skip it before aligning the component */
+        *vm_range_addr = vm_range_addr.offset(slide);
+
         /* obtain the aligned up VirtAddr for the current component */
         let aligned_up_addr = vm_range_addr.align_up(layout_component.alignment());
 
@@ -346,17 +461,9 @@ impl LayoutManager /* Privates */ {
      * Randomizes the `LayoutComponent`s order, in order to place them into
      * VM in a pseudo random order
      */
-    fn randomize_components(sized_layout_components: &Vec<LayoutComponent>)
+    fn randomize_components(sized_layout_components: &Vec<LayoutComponent>,
+                            layout_randomizer: &mut LayoutRandomizer)
                             -> Vec<LayoutComponent> {
-        /* obtain the random device */
-        let random_generic_device =
-            DevManager::instance().device_by_class(DeviceIdClass::Random)
-                                  .expect("No random driver registered");
-        let random_device =
-            random_generic_device.as_random()
-                                 .expect("DevManager returned a NON-random device for \
-                                          DeviceIdClass::Random device");
-
         /* keep a bitmap of the extracted components */
         let mut extracted_components = [false; LayoutComponent::COUNT];
 
@@ -367,7 +474,8 @@ impl LayoutManager /* Privates */ {
         let last_shrinkable_component = {
             /* extract the first available shrinkable component */
             let component_index =
-                random_device.random_u64() as usize % LayoutComponent::SHRINKABLES.len();
+                layout_randomizer.next_below(LayoutComponent::SHRINKABLES.len() as u64)
+                as usize;
 
             /* extract the LayoutComponent from the SHRINKABLES, then return the same
              * LayoutComponent but with the valid size
@@ -387,7 +495,8 @@ impl LayoutManager /* Privates */ {
             loop {
                 /* generate the next random number */
                 let next_index =
-                    random_device.random_u64() as usize % sized_layout_components.len();
+                    layout_randomizer.next_below(sized_layout_components.len() as u64)
+                    as usize;
 
                 /* mark as extracted */
                 if !extracted_components[next_index] {
@@ -436,6 +545,84 @@ impl LayoutManager /* Privates */ {
     }
 }
 
+impl Debug for LayoutManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(kaslr_seed) = self.m_kaslr_seed {
+            writeln!(f, "LayoutManager {{ m_kaslr_seed: {} }}", kaslr_seed)?;
+        } else {
+            writeln!(f, "LayoutManager {{ m_kaslr_seed: None (plain layout) }}")?;
+        }
+
+        let all_printable_ranges = [("PhysMemMapping", &self.m_phys_mem_mapping_range),
+                                    ("TmpMapping", &self.m_tmp_mem_mapping_range),
+                                    ("KernRegions", &self.m_kern_regions_range),
+                                    ("FsPageCache", &self.m_fs_page_cache_range),
+                                    ("KernText", &self.m_kern_text_range)];
+        for (range_name, virt_range) in all_printable_ranges {
+            writeln!(f,
+                     "\t{:<16}{}..{} ({})",
+                     range_name,
+                     virt_range.start,
+                     virt_range.end,
+                     (*virt_range.end - *virt_range.start).display_pretty())?;
+        }
+
+        /* the kernel text is slid by the boot code, without relocations it can't */
+        if self.m_kaslr_seed.is_some() {
+            if KernTextSlide::is_relocatable() {
+                writeln!(f,
+                         "\tKernText is slid by {:#x} from KERN_VIRT_BASE ({})",
+                         KernTextSlide::slide(),
+                         VirtAddr::from(Self::KERN_VIRT_BASE))?;
+            } else {
+                writeln!(f,
+                         "\tKernText has no relocations, it is not slid from \
+                          KERN_VIRT_BASE ({})",
+                         VirtAddr::from(Self::KERN_VIRT_BASE))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Deterministic pseudo random numbers generator (SplitMix64) used to
+ * randomize the layout, so the same seed reproduces the same layout
+ */
+struct LayoutRandomizer {
+    m_state: u64
+}
+
+impl LayoutRandomizer /* Constructors */ {
+    /**
+     * Constructs a `LayoutRandomizer` from the given seed
+     */
+    fn new(seed: u64) -> Self {
+        Self { m_state: seed }
+    }
+}
+
+impl LayoutRandomizer /* Methods */ {
+    /**
+     * Returns the next pseudo random `u64`
+     */
+    fn next_u64(&mut self) -> u64 {
+        self.m_state = self.m_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut value = self.m_state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    /**
+     * Returns the next pseudo random value in `0..upper_bound`
+     */
+    fn next_below(&mut self, upper_bound: u64) -> u64 {
+        self.next_u64() % upper_bound
+    }
+}
+
 /**
  * Identifier of the VM components
  */
@@ -494,7 +681,7 @@ impl LayoutComponent /* Getters */ {
             Self::TmpMapping { .. }
             | Self::KernRegions { .. }
             | Self::FsPageCache { .. } => Page4KiB::SIZE,
-            Self::PhysMemMapping { .. } => Page2MiB::SIZE,
+            Self::PhysMemMapping { .. } => Page1GiB::SIZE,
             _ => panic!("Tried to obtain alignment from a None LayoutComponent")
         }
     }
@@ -1,40 +1,70 @@
 /*! Kernel memory manager */
 
+use alloc::{
+    boxed::Box,
+    vec::Vec
+};
 use core::{
     fmt,
     fmt::Debug,
+    ops::Range,
     sync::atomic::{
         AtomicUsize,
         Ordering
     }
 };
 
-use bits::bit_fields::{
-    BitFindMode,
-    TBitArray,
-    TBitFields
+use helps::{
+    align::align_up,
+    dbg::{
+        TDisplaySizePretty,
+        C_MIB
+    }
 };
-use helps::dbg::TDisplaySizePretty;
-use sync::SpinMutex;
 
 use crate::{
     addr::{
         phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
         TAddress
     },
     boot_info::BootInfo,
+    cpu::Cpu,
     dbg_print::DbgLevel,
     dbg_println,
     vm::{
+        frame_refs::PhysFrameRefs,
+        kern_text_slide::KernTextSlide,
         layout_manager::LayoutManager,
         page_dir::PageDir,
-        page_table::PageTableIndex,
+        page_table::{
+            PageTableIndex,
+            PageTableLevel
+        },
+        phys_alloc::{
+            bitmap::BitmapPhysAllocator,
+            buddy::BuddyPhysAllocator,
+            TPhysAllocator
+        },
+        virt_alloc::VirtRangesAllocator,
+        MapFlags,
+        MapFlagsBits,
+        Page1GiB,
         Page2MiB,
         Page4KiB,
         TPageSize
     }
 };
 
+extern "C" {
+    static __kernel_rodata_begin: usize;
+    static __kernel_data_begin: usize;
+    static __kernel_symbols_begin: usize;
+}
+
+/* the first MiB is left to the firmware data and to the SMP startup trampoline */
+const C_LOW_MEM_END: usize = C_MIB;
+
 /* <None> until <MemManager::init_instance()> is called */
 static mut SM_MEM_MANAGER: Option<MemManager> = None;
 
@@ -47,7 +77,9 @@ static mut SM_MEM_MANAGER: Option<MemManager> = None;
  */
 pub struct MemManager {
     m_layout_manager: LayoutManager,
-    m_phys_frames_bitmap: SpinMutex<&'static mut [u8]>,
+    m_phys_allocator: Box<dyn TPhysAllocator>,
+    m_kern_regions_allocator: VirtRangesAllocator,
+    m_phys_frame_refs: Option<PhysFrameRefs>,
     m_mem_manager_stats: MemManagerStats,
     m_kernel_page_dir: PageDir
 }
@@ -67,36 +99,68 @@ impl MemManager /* Constructors */ {
             dbg_println!(DbgLevel::Warn, "Exceeded physical memory limit")
         }
 
-        /* construct the LayoutManager */
-        let layout_manager = if boot_info.cmd_line_arg_exists("-plain-vm-layout") {
+        /* construct the LayoutManager with the seed which has slid the kernel text */
+        let layout_manager = if let Some(kaslr_seed) = KernTextSlide::kaslr_seed() {
+            if let Some((_, None)) = boot_info.cmd_line_find_arg_int("-kaslr-seed") {
+                dbg_println!(DbgLevel::Warn, "Missing value for -kaslr-seed");
+            }
+            LayoutManager::new_randomized(*last_phys_mem_addr, kaslr_seed)
+        } else {
             dbg_println!(DbgLevel::Warn, "Disabled kernel layout randomization");
             LayoutManager::new_plain(*last_phys_mem_addr)
-        } else {
-            LayoutManager::new_randomized(*last_phys_mem_addr)
         };
-
-        /* allocate the physical frames bitmap filled with zeroes */
-        let phys_frames_bitmap_requested_pages =
-            ((*last_phys_mem_addr / Page4KiB::SIZE / u8::BIT_LEN) + Page4KiB::MASK) >> 12;
-        let mut phys_frames_bitmap =
-            vec![0; phys_frames_bitmap_requested_pages * Page4KiB::SIZE];
-
-        /* mark the available frames into the bitmap */
+        dbg_println!(DbgLevel::Info, "{:?}", layout_manager);
+
+        /* collect the available physical memory, without the kernel text and modules */
+        let mut reserved_phys_ranges =
+            vec![layout_manager.kern_text_phys_range().clone()];
+        for boot_module in boot_info.boot_modules().iter() {
+            dbg_println!(DbgLevel::Trace,
+                         "Reserved BootModule '{}' ({}..{})",
+                         boot_module.cmd_line(),
+                         boot_module.phys_range().start,
+                         boot_module.phys_range().end);
+            reserved_phys_ranges.push(boot_module.phys_range().clone());
+        }
+        let avail_phys_ranges =
+            Self::avail_phys_ranges(boot_info.boot_mem_areas(), &reserved_phys_ranges);
+
+        /* construct the physical frames allocator requested by the command line */
+        let phys_allocator: Box<dyn TPhysAllocator> =
+            match boot_info.cmd_line_find_arg("-phys-allocator") {
+                Some((_, Some("buddy"))) => {
+                    Box::new(BuddyPhysAllocator::new(last_phys_mem_addr,
+                                                     &avail_phys_ranges))
+                },
+                Some((_, Some("bitmap"))) | None => {
+                    Box::new(BitmapPhysAllocator::new(last_phys_mem_addr,
+                                                      &avail_phys_ranges))
+                },
+                Some((_, value)) => {
+                    dbg_println!(DbgLevel::Warn,
+                                 "Unsupported physical allocator given: {:?}",
+                                 value);
+                    Box::new(BitmapPhysAllocator::new(last_phys_mem_addr,
+                                                      &avail_phys_ranges))
+                }
+            };
+        dbg_println!(DbgLevel::Info,
+                     "Using {} physical frames allocator",
+                     phys_allocator.name());
+
+        /* account the available frames, the remaining are the reserved ones */
         let mem_manager_stats = MemManagerStats::new();
-        for phys_addr in
-            boot_info.boot_mem_areas()
-                     .iter()
-                     .flat_map(|phys_mem_range| {
-                         phys_mem_range.clone().step_by(Page4KiB::SIZE)
-                     })
-        {
-            /* mark as available only the frames which not store the kernel text */
-            if !layout_manager.kern_text_phys_range().contains(&phys_addr) {
-                phys_frames_bitmap.set_bit(phys_addr.as_page_index::<Page4KiB>(), true);
-                mem_manager_stats.m_free_phys_frames.fetch_add(1, Ordering::Relaxed);
-            } else {
-                mem_manager_stats.m_allocated_phys_frames.fetch_add(1, Ordering::Relaxed);
-            }
+        for phys_range in boot_info.boot_mem_areas().iter() {
+            let frames_count = (*phys_range.end - *phys_range.start) / Page4KiB::SIZE;
+            mem_manager_stats.m_allocated_phys_frames
+                             .fetch_add(frames_count, Ordering::Relaxed);
+        }
+        for phys_range in avail_phys_ranges.iter() {
+            let frames_count = (*phys_range.end - *phys_range.start) / Page4KiB::SIZE;
+            mem_manager_stats.m_allocated_phys_frames
+                             .fetch_sub(frames_count, Ordering::Relaxed);
+            mem_manager_stats.m_free_phys_frames
+                             .fetch_add(frames_count, Ordering::Relaxed);
         }
 
         dbg_println!(DbgLevel::Trace,
@@ -104,14 +168,19 @@ impl MemManager /* Constructors */ {
                      layout_manager.kern_text_phys_range());
         dbg_println!(DbgLevel::Trace, "{:?}", mem_manager_stats);
 
+        /* the kernel regions are carved from the reserved <LayoutManager> range */
+        let kern_regions_allocator =
+            VirtRangesAllocator::new(layout_manager.kern_regions_range().clone());
+
         /* initialize the global instance */
         let mm_inst = unsafe {
-            SM_MEM_MANAGER =
-                Some(Self { m_layout_manager: layout_manager,
-                            m_phys_frames_bitmap:
-                                SpinMutex::const_new(phys_frames_bitmap.leak()),
-                            m_mem_manager_stats: mem_manager_stats,
-                            m_kernel_page_dir: PageDir::pre_phys_mapping() });
+            SM_MEM_MANAGER = Some(Self { m_layout_manager: layout_manager,
+                                         m_phys_allocator: phys_allocator,
+                                         m_kern_regions_allocator:
+                                             kern_regions_allocator,
+                                         m_phys_frame_refs: None,
+                                         m_mem_manager_stats: mem_manager_stats,
+                                         m_kernel_page_dir: PageDir::pre_phys_mapping() });
             SM_MEM_MANAGER.as_mut().unwrap()
         };
 
@@ -120,6 +189,18 @@ impl MemManager /* Constructors */ {
         mm_inst.update_kernel_page_dir_after_phys_mapping();
         mm_inst.unmap_kernel_lower_half();
         mm_inst.protect_kernel_image();
+        mm_inst.preallocate_kernel_page_tables();
+
+        /* the frames references counters need the kernel regions, eagerly backed
+         * because the page fault handler reads them with its lock held
+         */
+        let refs_region =
+            mm_inst.allocate_kernel_region(PhysFrameRefs::region_size(last_phys_mem_addr),
+                                           MapFlags::new_zero()
+                                           | MapFlagsBits::Writeable
+                                           | MapFlagsBits::Global)
+                   .expect("Failed to allocate the physical frames references");
+        mm_inst.m_phys_frame_refs = Some(PhysFrameRefs::new(refs_region));
     }
 }
 
@@ -128,7 +209,244 @@ impl MemManager /* Methods */ {
      * Allocate a physical memory frame from the kernel pool
      */
     pub fn allocate_kernel_phys_frame(&self) -> Option<PhysAddr> {
-        self.allocate_phys_frame(BitFindMode::Regular)
+        self.allocate_phys_frames(1, 1).map(|phys_frames_range| phys_frames_range.start)
+    }
+
+    /**
+     * Allocates a physical frame of `S::SIZE` bytes which start address is
+     * aligned to `S::SIZE` too.
+     *
+     * Useful to obtain the backing memory for huge pages
+     */
+    pub fn allocate_sized_phys_frame<S>(&self) -> Option<PhysAddr>
+        where S: TPageSize {
+        let frames_count = S::SIZE / Page4KiB::SIZE;
+
+        self.allocate_phys_frames(frames_count, frames_count)
+            .map(|phys_frames_range| phys_frames_range.start)
+    }
+
+    /**
+     * Allocates `frames_count` physically contiguous 4KiB frames which first
+     * frame number is a multiple of `frames_align`.
+     *
+     * Returns the `Range` of the allocated physical memory
+     */
+    pub fn allocate_phys_frames(&self,
+                                frames_count: usize,
+                                frames_align: usize)
+                                -> Option<Range<PhysAddr>> {
+        assert_ne!(frames_count, 0, "Requested an empty physical frames run");
+        assert!(frames_align.is_power_of_two(),
+                "Physical frames alignment must be a power of two");
+
+        let phys_frames_range =
+            self.m_phys_allocator.allocate_phys_frames(frames_count, frames_align)?;
+        self.m_mem_manager_stats.on_allocated_phys_frames(frames_count);
+
+        Some(phys_frames_range)
+    }
+
+    /**
+     * Gives back to the physical pool the given 4KiB frame
+     */
+    pub fn free_phys_frame(&self, phys_frame: PhysAddr) {
+        self.free_phys_frames(phys_frame.to_range(Page4KiB::SIZE));
+    }
+
+    /**
+     * Gives back to the physical pool the given `S::SIZE` frame obtained
+     * with `MemManager::allocate_sized_phys_frame()`
+     */
+    pub fn free_sized_phys_frame<S>(&self, phys_frame: PhysAddr)
+        where S: TPageSize {
+        self.free_phys_frames(phys_frame.to_range(S::SIZE));
+    }
+
+    /**
+     * Gives back to the physical pool all the 4KiB frames which compose the
+     * given `Range`
+     */
+    pub fn free_phys_frames(&self, phys_frames_range: Range<PhysAddr>) {
+        assert!(phys_frames_range.start.is_aligned(Page4KiB::SIZE)
+                && phys_frames_range.end.is_aligned(Page4KiB::SIZE),
+                "Tried to free a non page-aligned physical range {:?}",
+                phys_frames_range);
+
+        let frames_count =
+            (*phys_frames_range.end - *phys_frames_range.start) / Page4KiB::SIZE;
+
+        self.m_phys_allocator.free_phys_frames(phys_frames_range);
+        self.m_mem_manager_stats.on_free_phys_frames(frames_count);
+    }
+
+    /**
+     * Adds an owner to the given 4KiB frame, which becomes shared
+     */
+    pub fn ref_phys_frame(&self, phys_frame: PhysAddr) {
+        self.phys_frame_refs().acquire(phys_frame);
+    }
+
+    /**
+     * Removes an owner from the given 4KiB frame, which is given back to
+     * the physical pool when it was the last
+     */
+    pub fn unref_phys_frame(&self, phys_frame: PhysAddr) {
+        self.unref_sized_phys_frame::<Page4KiB>(phys_frame);
+    }
+
+    /**
+     * Removes an owner from the given `S::SIZE` frame, which is given back
+     * to the physical pool when it was the last.
+     *
+     * The references are counted on the first 4KiB frame
+     */
+    pub fn unref_sized_phys_frame<S>(&self, phys_frame: PhysAddr)
+        where S: TPageSize {
+        /* before the counters initialization no frame can be shared */
+        let is_last_ref =
+            self.m_phys_frame_refs
+                .as_ref()
+                .map_or(true, |phys_frame_refs| phys_frame_refs.release(phys_frame));
+        if is_last_ref {
+            self.free_sized_phys_frame::<S>(phys_frame);
+        }
+    }
+
+    /**
+     * Allocates a zeroed kernel region of at least `size` bytes into the
+     * `LayoutManager::kern_regions_range()`, backed by 4KiB frames mapped
+     * with the given `MapFlags`.
+     *
+     * With `MapFlagsBits::LazyBacked` the frames are allocated and zeroed
+     * by the page fault handler on the first access to each page
+     */
+    pub fn allocate_kernel_region(&self,
+                                  size: usize,
+                                  map_flags: MapFlags)
+                                  -> Option<Range<VirtAddr>> {
+        let region_size = align_up(size, Page4KiB::SIZE);
+        let kern_region =
+            self.m_kern_regions_allocator.allocate(region_size, Page4KiB::SIZE)?;
+
+        /* lazily backed regions only reserve the page-table entries */
+        if map_flags.is_enabled(MapFlagsBits::LazyBacked) {
+            if !self.kernel_page_dir().map_lazy(kern_region.clone(), map_flags) {
+                self.m_kern_regions_allocator.free(kern_region);
+                return None;
+            }
+            return Some(kern_region);
+        }
+
+        /* on failure give back the already mapped part of the region */
+        if !self.back_kernel_range(kern_region.clone(), map_flags) {
+            self.free_kernel_region(kern_region);
+            return None;
+        }
+        Some(kern_region)
+    }
+
+    /**
+     * Unmaps the given kernel region, obtained with
+     * `MemManager::allocate_kernel_region()`, and gives back its frames
+     */
+    pub fn free_kernel_region(&self, kern_region: Range<VirtAddr>) {
+        assert!(self.layout_manager().kern_regions_range().start <= kern_region.start
+                && kern_region.end <= self.layout_manager().kern_regions_range().end,
+                "Tried to free a non kernel region {:?}",
+                kern_region);
+
+        self.kernel_page_dir().unmap::<Page4KiB>(kern_region.clone(), true);
+        self.m_kern_regions_allocator.free(kern_region);
+    }
+
+    /**
+     * Maps the given physical device registers `Range` into the
+     * `LayoutManager::kern_regions_range()` uncacheable.
+     *
+     * The returned `Range` keeps the page offset of the physical one
+     */
+    pub fn map_mmio_region(&self,
+                           phys_range: Range<PhysAddr>)
+                           -> Option<Range<VirtAddr>> {
+        let phys_frames_begin = phys_range.start.align_down(Page4KiB::SIZE);
+        let phys_frames_end = phys_range.end.align_up(Page4KiB::SIZE);
+        let region_size = *phys_frames_end - *phys_frames_begin;
+
+        let kern_region =
+            self.m_kern_regions_allocator.allocate(region_size, Page4KiB::SIZE)?;
+        if !self.kernel_page_dir().map::<Page4KiB>(kern_region.clone(),
+                                                   phys_frames_begin,
+                                                   MapFlags::new_zero()
+                                                   | MapFlagsBits::Writeable
+                                                   | MapFlagsBits::Uncacheable
+                                                   | MapFlagsBits::Global)
+        {
+            self.m_kern_regions_allocator.free(kern_region);
+            return None;
+        }
+
+        let page_offset = *phys_range.start - *phys_frames_begin;
+        Some(kern_region.start.offset(page_offset)
+             ..kern_region.start
+                          .offset(page_offset + (*phys_range.end - *phys_range.start)))
+    }
+
+    /**
+     * Unmaps the given device registers region, obtained with
+     * `MemManager::map_mmio_region()`
+     */
+    pub fn unmap_mmio_region(&self, mmio_region: Range<VirtAddr>) {
+        let kern_region = mmio_region.start.align_down(Page4KiB::SIZE)
+                          ..mmio_region.end.align_up(Page4KiB::SIZE);
+
+        /* the frames belong to the device, never give them to the allocator */
+        self.kernel_page_dir().unmap::<Page4KiB>(kern_region.clone(), false);
+        self.m_kern_regions_allocator.free(kern_region);
+    }
+
+    /**
+     * Allocates a kernel stack of at least `size` bytes preceded by a
+     * guard page, which catches the stack overflows.
+     *
+     * The stack is backed eagerly: a fault on it could happen with the
+     * physical allocator or the page-tables already locked.
+     *
+     * Returns the usable `Range` of the stack, without the guard page
+     */
+    pub fn allocate_kernel_stack(&self, size: usize) -> Option<Range<VirtAddr>> {
+        let stack_size = align_up(size, Page4KiB::SIZE);
+        let kern_region = self.m_kern_regions_allocator
+                              .allocate(stack_size + Page4KiB::SIZE, Page4KiB::SIZE)?;
+
+        /* the stacks grow downwards, so the guard page is the first one */
+        let guard_page_range =
+            kern_region.start..kern_region.start.offset(Page4KiB::SIZE);
+        let stack_range = guard_page_range.end..kern_region.end;
+
+        if !self.kernel_page_dir().map_guard(guard_page_range) {
+            self.m_kern_regions_allocator.free(kern_region);
+            return None;
+        }
+        if !self.back_kernel_range(stack_range.clone(),
+                                   MapFlags::new_zero()
+                                   | MapFlagsBits::Writeable
+                                   | MapFlagsBits::Global)
+        {
+            self.free_kernel_region(kern_region);
+            return None;
+        }
+        Some(stack_range)
+    }
+
+    /**
+     * Frees the given kernel stack, obtained with
+     * `MemManager::allocate_kernel_stack()`, with its guard page
+     */
+    pub fn free_kernel_stack(&self, stack_range: Range<VirtAddr>) {
+        let guard_page_begin = VirtAddr::from(*stack_range.start - Page4KiB::SIZE);
+
+        self.free_kernel_region(guard_page_begin..stack_range.end);
     }
 }
 
@@ -150,32 +468,121 @@ impl MemManager /* Getters */ {
         &self.m_layout_manager
     }
 
+    /**
+     * Returns the global `MemManager` instance if already initialized
+     */
+    pub fn try_instance() -> Option<&'static Self> {
+        unsafe { SM_MEM_MANAGER.as_ref() }
+    }
+
     /**
      * Returns the reference to the kernel `PageDir`
      */
     pub fn kernel_page_dir(&self) -> &PageDir {
         &self.m_kernel_page_dir
     }
+
+    /**
+     * Returns whether the given 4KiB frame has more than one owner
+     */
+    pub fn is_phys_frame_shared(&self, phys_frame: PhysAddr) -> bool {
+        self.m_phys_frame_refs
+            .as_ref()
+            .map_or(false, |phys_frame_refs| phys_frame_refs.is_shared(phys_frame))
+    }
+
+    /**
+     * Returns the reference to the `MemManagerStats`
+     */
+    pub fn stats(&self) -> &MemManagerStats {
+        &self.m_mem_manager_stats
+    }
 }
 
 impl MemManager /* Privates */ {
     /**
-     * Allocates the first available physical frame from the physical bitmap
+     * Backs each page of the given kernel `Range` with a zeroed 4KiB
+     * frame mapped with the given `MapFlags`.
+     *
+     * On failure the already mapped part is left to the caller
      */
-    fn allocate_phys_frame(&self, bit_find_mode: BitFindMode) -> Option<PhysAddr> {
-        let mut unlocked_bitmap = self.m_phys_frames_bitmap.lock();
+    fn back_kernel_range(&self,
+                         virt_range: Range<VirtAddr>,
+                         map_flags: MapFlags)
+                         -> bool {
+        for virt_addr in virt_range.clone().step_by(Page4KiB::SIZE) {
+            let mapped = self.allocate_kernel_phys_frame().map_or(false, |phys_frame| {
+                let page_range = virt_addr.to_range(Page4KiB::SIZE);
+                if self.kernel_page_dir().map::<Page4KiB>(page_range, phys_frame, map_flags)
+                {
+                    true
+                } else {
+                    self.free_phys_frame(phys_frame);
+                    false
+                }
+            });
+            if !mapped {
+                dbg_println!(DbgLevel::Warn,
+                             "Failed to back kernel range {:?} at {}",
+                             virt_range,
+                             virt_addr);
+                return false;
+            }
+        }
 
-        /* find the first bit as <true>, which means available bit */
-        if let Some(bit_index) = unlocked_bitmap.find_bit(true, bit_find_mode) {
-            /* mark the bit as no-more available and update the statistics */
-            unlocked_bitmap.set_bit(bit_index, false);
-            self.m_mem_manager_stats.on_allocated_phys_frame();
+        /* never expose the previous content of the frames */
+        unsafe {
+            virt_range.start
+                      .as_ptr_mut::<u8>()
+                      .write_bytes(0, *virt_range.end - *virt_range.start);
+        }
+        true
+    }
 
-            /* construct the <PhysAddr> from the frame number */
-            Some(PhysAddr::from(bit_index * Page4KiB::SIZE))
-        } else {
-            None
+    /**
+     * Returns the `PhysFrameRefs` instance
+     */
+    fn phys_frame_refs(&self) -> &PhysFrameRefs {
+        self.m_phys_frame_refs.as_ref().expect("Tried to share a physical frame before \
+                                                the references initialization")
+    }
+
+    /**
+     * Returns the page-aligned physical memory ranges which are available
+     * for the physical frames allocators, excluding the frames touched by
+     * the given reserved ranges
+     */
+    fn avail_phys_ranges(boot_mem_areas: &[Range<PhysAddr>],
+                         reserved_phys_ranges: &[Range<PhysAddr>])
+                         -> Vec<Range<PhysAddr>> {
+        let mut avail_phys_ranges =
+            Vec::with_capacity(boot_mem_areas.len() + reserved_phys_ranges.len());
+        for phys_mem_range in boot_mem_areas.iter() {
+            let range_begin = phys_mem_range.start
+                                            .max(PhysAddr::from(C_LOW_MEM_END))
+                                            .align_up(Page4KiB::SIZE);
+            let range_end = phys_mem_range.end.align_down(Page4KiB::SIZE);
+            if range_begin < range_end {
+                avail_phys_ranges.push(range_begin..range_end);
+            }
+        }
+
+        /* keep the parts before and after each reserved range */
+        for reserved_phys_range in reserved_phys_ranges.iter() {
+            let reserved_begin = reserved_phys_range.start.align_down(Page4KiB::SIZE);
+            let reserved_end = reserved_phys_range.end.align_up(Page4KiB::SIZE);
+
+            avail_phys_ranges =
+                avail_phys_ranges.into_iter()
+                                 .flat_map(|phys_range| {
+                                     [phys_range.start
+                                      ..phys_range.end.min(reserved_begin),
+                                      phys_range.start.max(reserved_end)..phys_range.end]
+                                 })
+                                 .filter(|phys_range| phys_range.start < phys_range.end)
+                                 .collect();
         }
+        avail_phys_ranges
     }
 
     /**
@@ -196,40 +603,76 @@ impl MemManager /* Privates */ {
         l4_page_table[index_zero].set_unused();
     }
 
+    /**
+     * Allocates all the page-tables referenced by the kernel space entries
+     * of the root page-table.
+     *
+     * The `AddressSpace`s copy these entries, so the kernel mappings
+     * created later are visible to all of them
+     */
+    fn preallocate_kernel_page_tables(&self) {
+        let kern_space_begin = VirtAddr::from(LayoutManager::KERN_SPACE_BEGIN);
+        let first_root_index: usize =
+            kern_space_begin.page_table_index(PageTableLevel::Root).into();
+
+        let kern_page_dir = self.kernel_page_dir();
+        for root_index in first_root_index..512 {
+            let virt_addr = VirtAddr::from_1gib_indexes(PageTableIndex::from(root_index),
+                                                        PageTableIndex::from(0usize));
+
+            let allocated =
+                kern_page_dir.with_page_tables_locked(|| {
+                    kern_page_dir.ensure_page_table_entry::<Page1GiB>(virt_addr).is_some()
+                });
+            assert!(allocated,
+                    "Failed to allocate the kernel page-table for {}",
+                    virt_addr);
+        }
+    }
+
     /**
      * Maps all the physical memory into the
      * `LayoutManager::phys_mem_mapping_range()`
      */
     fn map_physical_memory(&self, last_phys_mem_addr: PhysAddr) {
-        /* iterate all the available frames as 2MiB frames to reduce intermediate
-         * page-tables granularity and physical memory allocations.
+        /* map all the physical memory with the biggest huge pages supported to reduce
+         * intermediate page-tables granularity, physical memory allocations and TLB
+         * pressure.
          * In this stage, when this method is called, the <m_kernel_page_dir> doesn't
          * use the real mapped offset, because the memory is not mapped yet
          */
-        for phys_addr in (PhysAddr::null()..last_phys_mem_addr).step_by(Page2MiB::SIZE) {
-            let virt_addr = self.layout_manager().phys_addr_to_virt_addr(phys_addr);
-
-            /* obtain the page-table-entry for the current virtual-address */
-            let page_table_entry = self.kernel_page_dir()
-                                       .ensure_page_table_entry::<Page2MiB>(virt_addr)
-                                       .expect("Failed to map physical memory");
-
-            /* set the flags of the entry */
-            page_table_entry.set_phys_frame(phys_addr);
-            page_table_entry.set_huge_page(true);
-            page_table_entry.set_present(true);
-            page_table_entry.set_readable(true);
-            page_table_entry.set_writeable(true);
-            page_table_entry.set_global(true);
-
-            /* invalidate the TLB */
-            unsafe {
-                asm!("invlpg [{}]", in(reg) *virt_addr, options(nostack, preserves_flags));
-                /* TODO page_table_entry.invalidate_in_tlb(); */
-            }
+        if Cpu::supports_1gib_pages() {
+            self.map_physical_memory_with::<Page1GiB>(last_phys_mem_addr);
+        } else {
+            self.map_physical_memory_with::<Page2MiB>(last_phys_mem_addr);
         }
     }
 
+    /**
+     * Maps all the physical memory into the
+     * `LayoutManager::phys_mem_mapping_range()` using `S` pages
+     */
+    fn map_physical_memory_with<S>(&self, last_phys_mem_addr: PhysAddr)
+        where S: TPageSize {
+        let phys_mem_end = last_phys_mem_addr.align_up(S::SIZE);
+        let phys_mem_mapping_range =
+            self.layout_manager().phys_addr_to_virt_addr(PhysAddr::null())
+            ..self.layout_manager().phys_addr_to_virt_addr(phys_mem_end);
+
+        /* the direct mapping is shared by all the address spaces and never executed */
+        let mapped = self.kernel_page_dir().map::<S>(phys_mem_mapping_range,
+                                                     PhysAddr::null(),
+                                                     MapFlags::new_zero()
+                                                     | MapFlagsBits::Writeable
+                                                     | MapFlagsBits::Global);
+        assert!(mapped, "Failed to map physical memory");
+
+        dbg_println!(DbgLevel::Debug,
+                     "Mapped {} of physical memory with {:?} pages",
+                     (*phys_mem_end).display_pretty(),
+                     S::PAGE_TABLE_LEVEL);
+    }
+
     /**
      * Updates the kernel `PageDir` with same instance which resolves the
      * virtual -> physical addresses using the memory mapping
@@ -242,6 +685,69 @@ impl MemManager /* Privates */ {
      * Protects the kernel image with proper protection
      */
     fn protect_kernel_image(&self) {
+        let kern_page_dir = self.kernel_page_dir();
+        let kern_text_range = self.layout_manager().kern_text_range();
+        let kern_image_end = kern_text_range.end.align_up(Page4KiB::SIZE);
+
+        /* the boot code maps the kernel image with writeable & executable 2MiB pages
+         * starting from the slid <KERN_VIRT_BASE>, split them to protect each section
+         * with the 4KiB granularity given by the linker script alignment
+         */
+        let kern_boot_window =
+            VirtAddr::from(LayoutManager::KERN_VIRT_BASE + KernTextSlide::slide())
+            ..kern_image_end.align_up(Page2MiB::SIZE);
+        for virt_addr in kern_boot_window.clone().step_by(Page2MiB::SIZE) {
+            assert!(kern_page_dir.split_huge_page(virt_addr),
+                    "Failed to split the kernel image huge page at {}",
+                    virt_addr);
+        }
+
+        /* obtain the sections boundaries from the linker symbols */
+        let (kern_rodata_begin, kern_data_begin, kern_symbols_begin) = unsafe {
+            (VirtAddr::from(&__kernel_rodata_begin as *const _ as usize),
+             VirtAddr::from(&__kernel_data_begin as *const _ as usize),
+             VirtAddr::from(&__kernel_symbols_begin as *const _ as usize))
+        };
+        let kern_sections =
+            [(".text",
+              kern_text_range.start..kern_rodata_begin,
+              MapFlags::new_zero() | MapFlagsBits::Executable | MapFlagsBits::Global),
+             (".rodata",
+              kern_rodata_begin..kern_data_begin,
+              MapFlags::new_zero() | MapFlagsBits::Global),
+             (".data/.bss",
+              kern_data_begin..kern_symbols_begin,
+              MapFlags::new_zero() | MapFlagsBits::Writeable | MapFlagsBits::Global),
+             (".symbols",
+              kern_symbols_begin..kern_image_end,
+              MapFlags::new_zero() | MapFlagsBits::Global)];
+
+        /* apply the W^X protection to each section */
+        for (section_name, section_range, map_flags) in kern_sections {
+            dbg_println!(DbgLevel::Trace,
+                         "Protecting {} {:?} with {:?}",
+                         section_name,
+                         section_range,
+                         map_flags);
+
+            assert!(kern_page_dir.protect::<Page4KiB>(section_range, map_flags),
+                    "Failed to protect the kernel {} section",
+                    section_name);
+        }
+
+        /* the remaining pages of the boot window are not part of the kernel image */
+        kern_page_dir.unmap::<Page4KiB>(kern_boot_window.start..kern_text_range.start,
+                                        false);
+        kern_page_dir.unmap::<Page4KiB>(kern_image_end..kern_boot_window.end, false);
+
+        /* the link window of the slid image is no longer used after the relocations */
+        if KernTextSlide::slide() != 0 {
+            let kern_link_window = VirtAddr::from(LayoutManager::KERN_VIRT_BASE)
+                                       .to_range(KernTextSlide::image_size());
+
+            kern_page_dir.unmap::<Page2MiB>(kern_link_window, false);
+        }
+
         dbg_println!(DbgLevel::Debug, "PageDir:\n{:?}", self.kernel_page_dir());
     }
 }
@@ -282,19 +788,21 @@ impl MemManagerStats /* Getters */ {
 
 impl MemManagerStats /* Privates */ {
     /**
-     * Updates the counters when a physical frame is being allocated
+     * Updates the counters when `frames_count` physical frames are being
+     * allocated
      */
-    fn on_allocated_phys_frame(&self) {
-        self.m_free_phys_frames.fetch_sub(1, Ordering::SeqCst);
-        self.m_allocated_phys_frames.fetch_add(1, Ordering::SeqCst);
+    fn on_allocated_phys_frames(&self, frames_count: usize) {
+        self.m_free_phys_frames.fetch_sub(frames_count, Ordering::SeqCst);
+        self.m_allocated_phys_frames.fetch_add(frames_count, Ordering::SeqCst);
     }
 
     /**
-     * Updates the counters when a physical frame is being freed
+     * Updates the counters when `frames_count` physical frames are being
+     * freed
      */
-    fn on_free_phys_frame(&self) {
-        self.m_allocated_phys_frames.fetch_sub(1, Ordering::SeqCst);
-        self.m_free_phys_frames.fetch_add(1, Ordering::SeqCst);
+    fn on_free_phys_frames(&self, frames_count: usize) {
+        self.m_allocated_phys_frames.fetch_sub(frames_count, Ordering::SeqCst);
+        self.m_free_phys_frames.fetch_add(frames_count, Ordering::SeqCst);
     }
 }
 
@@ -5,18 +5,41 @@ use core::{
     hash::Hash
 };
 
+use num_enum::{
+    IntoPrimitive,
+    TryFromPrimitive
+};
+
+use bits::bit_flags::{
+    BitFlags,
+    TBitFlagsValues
+};
 use helps::dbg::{
+    C_GIB,
     C_KIB,
     C_MIB
 };
 
 use crate::vm::page_table::PageTableLevel;
 
+pub mod addr_space;
+pub mod frame_refs;
+pub mod kern_text_slide;
 pub mod layout_manager;
 pub mod mem_manager;
 pub mod page_dir;
+pub mod page_fault;
 pub mod page_table;
 pub mod page_table_entry;
+pub mod phys_alloc;
+pub mod tlb;
+pub mod user_copy;
+pub mod virt_alloc;
+
+/**
+ * Protection and caching flags of a virtual memory mapping
+ */
+pub type MapFlags = BitFlags<usize, MapFlagsBits>;
 
 /**
  * Default 4KiB `PageSize`
@@ -47,7 +70,26 @@ pub struct Page2MiB;
 impl TPageSize for Page2MiB {
     const SIZE: usize = 2 * C_MIB;
     const PAGE_TABLE_LEVEL: PageTableLevel = PageTableLevel::TwoMiB;
-    const IS_HUGE: bool = false;
+    const IS_HUGE: bool = true;
+}
+
+/**
+ * Huge 1GiB `PageSize`.
+ *
+ * Not all the CPUs support it, check `Cpu::supports_1gib_pages()` before
+ * mapping with it
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+#[derive(Ord, PartialOrd)]
+#[derive(Hash)]
+pub struct Page1GiB;
+
+impl TPageSize for Page1GiB {
+    const SIZE: usize = C_GIB;
+    const PAGE_TABLE_LEVEL: PageTableLevel = PageTableLevel::OneGiB;
+    const IS_HUGE: bool = true;
 }
 
 pub trait TPageSize:
@@ -72,3 +114,52 @@ pub trait TPageSize:
      */
     const MASK: usize = Self::SIZE - 1;
 }
+
+/**
+ * Lists the `MapFlags` bits.
+ *
+ * A mapping without bits is readable only, not executable, cacheable and
+ * accessible only by the kernel
+ */
+#[repr(usize)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+pub enum MapFlagsBits {
+    /**
+     * The mapped memory can be written
+     */
+    Writeable,
+
+    /**
+     * The mapped memory can be executed
+     */
+    Executable,
+
+    /**
+     * The mapped memory bypasses the CPU caches.
+     *
+     * Useful for memory mapped I/O
+     */
+    Uncacheable,
+
+    /**
+     * The mapping is shared among all the address spaces and survives to
+     * the TLB flushes caused by the address space switch
+     */
+    Global,
+
+    /**
+     * The mapped memory is accessible by the userspace
+     */
+    User,
+
+    /**
+     * The physical frames are not allocated with the mapping but by the
+     * page fault handler on the first access to each page
+     */
+    LazyBacked
+}
+
+impl TBitFlagsValues for MapFlagsBits {
+}
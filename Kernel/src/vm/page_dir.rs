@@ -2,9 +2,12 @@
 
 use core::{
     fmt,
-    fmt::Debug
+    fmt::Debug,
+    ops::Range
 };
 
+use sync::SpinMutex;
+
 use crate::{
     addr::{
         phys_addr::PhysAddr,
@@ -12,7 +15,9 @@ use crate::{
         TAddress
     },
     arch::vm::hw_page_dir::HwPageDir,
+    cpu::Cpu,
     vm::{
+        layout_manager::LayoutManager,
         mem_manager::MemManager,
         page_table::{
             PageTable,
@@ -20,14 +25,28 @@ use crate::{
             PageTableLevel
         },
         page_table_entry::PageTableEntry,
+        tlb::Tlb,
+        MapFlags,
+        Page2MiB,
         Page4KiB,
         TPageSize
     }
 };
 
+/* maximum amount of frames released by `PageDir::unmap()` with a single shootdown */
+const C_MAX_RELEASED_FRAMES: usize = 64;
+
+/**
+ * Page-tables hierarchy of an address space.
+ *
+ * The modifications of the page-tables are serialized by a per-`PageDir`
+ * lock, the kernel space ones only by the kernel `PageDir`, since the
+ * `AddressSpace`s share its page-tables without modifying them
+ */
 pub struct PageDir {
     m_hw_page_dir: HwPageDir,
-    m_phys_mem_offset: VirtAddr
+    m_phys_mem_offset: VirtAddr,
+    m_page_tables_lock: SpinMutex<()>
 }
 
 impl PageDir /* Constructors */ {
@@ -35,12 +54,26 @@ impl PageDir /* Constructors */ {
         Self { m_hw_page_dir: HwPageDir::current(),
                m_phys_mem_offset: MemManager::instance().layout_manager()
                                                         .phys_mem_mapping_range()
-                                                        .start }
+                                                        .start,
+               m_page_tables_lock: SpinMutex::const_new(()) }
+    }
+
+    /**
+     * Constructs a `PageDir` which root page-table is the given physical
+     * frame
+     */
+    pub fn from_phys_frame(phys_frame: PhysAddr) -> Self {
+        Self { m_hw_page_dir: HwPageDir::from_phys_frame(phys_frame),
+               m_phys_mem_offset: MemManager::instance().layout_manager()
+                                                        .phys_mem_mapping_range()
+                                                        .start,
+               m_page_tables_lock: SpinMutex::const_new(()) }
     }
 
     pub fn pre_phys_mapping() -> Self {
         Self { m_hw_page_dir: HwPageDir::current(),
-               m_phys_mem_offset: VirtAddr::null() }
+               m_phys_mem_offset: VirtAddr::null(),
+               m_page_tables_lock: SpinMutex::const_new(()) }
     }
 }
 
@@ -50,44 +83,256 @@ impl PageDir /* Methods */ {
     }
 
     /**
-     * Returns the mapping `PageTableEntry` for the given `VirtAddr`
+     * Executes `locked_fn` with the page-tables of this `PageDir` locked
+     * and the interrupts of the executing `Cpu` disabled, so a fault or a
+     * preemption can't try to lock them again.
+     *
+     * The `locked_fn` must not call the other locking methods of this
+     * `PageDir`, nor shootdown the TLBs: the CPUs which wait for the lock
+     * couldn't serve the shootdown
+     */
+    pub fn with_page_tables_locked<F, R>(&self, locked_fn: F) -> R
+        where F: FnOnce() -> R {
+        let this_cpu = Cpu::current();
+
+        let were_interrupts_enabled = this_cpu.are_interrupts_enabled();
+        if were_interrupts_enabled {
+            this_cpu.disable_interrupts();
+        }
+
+        let page_tables_guard = self.m_page_tables_lock.lock();
+        let result = locked_fn();
+        drop(page_tables_guard);
+
+        if were_interrupts_enabled {
+            this_cpu.enable_interrupts();
+        }
+        result
+    }
+
+    /**
+     * Returns the mapping `PageTableEntry` for the given `VirtAddr`.
+     *
+     * Must be called into `PageDir::with_page_tables_locked()`
      */
     pub fn ensure_page_table_entry<S>(&self,
                                       virt_addr: VirtAddr)
                                       -> Option<&mut PageTableEntry>
         where S: TPageSize {
         if virt_addr.is_aligned(S::SIZE) {
-            let l4_page_table = self.root_page_table();
-
-            /* obtain the Level3 page-table */
-            let l3_page_table =
-                self.ensure_next_page_table_from_level(virt_addr,
-                                                       l4_page_table,
-                                                       PageTableLevel::Root)?;
-
-            /* obtain the Level2 page-table */
-            let l2_page_table =
-                self.ensure_next_page_table_from_level(virt_addr,
-                                                       l3_page_table,
-                                                       PageTableLevel::OneGiB)?;
-
-            /* obtain the last mapping page-table level */
-            let map_page_table = if S::SIZE == Page4KiB::SIZE {
-                /* if a <Page4KiB> mapping is requested go a level deeper */
-                self.ensure_next_page_table_from_level(virt_addr,
-                                                       l2_page_table,
-                                                       PageTableLevel::TwoMiB)?
-            } else {
-                l2_page_table
-            };
+            /* walk the page-tables until the mapping level, allocating the missing */
+            let mut page_table = self.root_page_table();
+            for walk_level in
+                [PageTableLevel::Root, PageTableLevel::OneGiB, PageTableLevel::TwoMiB]
+            {
+                if walk_level == S::PAGE_TABLE_LEVEL {
+                    break;
+                }
+                page_table = self.ensure_next_page_table_from_level(virt_addr,
+                                                                    page_table,
+                                                                    walk_level)?;
+            }
 
             /* extract the <PageTableEntry> from the mapping level */
-            Some(&mut map_page_table[virt_addr.page_table_index(S::PAGE_TABLE_LEVEL)])
+            Some(&mut page_table[virt_addr.page_table_index(S::PAGE_TABLE_LEVEL)])
         } else {
             None
         }
     }
 
+    /**
+     * Returns the mapping `PageTableEntry` for the given `VirtAddr` if all
+     * the intermediate page-tables exist, without allocating anything.
+     *
+     * Must be called into `PageDir::with_page_tables_locked()`
+     */
+    pub fn page_table_entry<S>(&self,
+                               virt_addr: VirtAddr)
+                               -> Option<&mut PageTableEntry>
+        where S: TPageSize {
+        if virt_addr.is_aligned(S::SIZE) {
+            self.walk_to_level(virt_addr, S::PAGE_TABLE_LEVEL)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Splits the 2MiB huge page which contains the given `VirtAddr` into
+     * 512 4KiB pages which inherit the same protection.
+     *
+     * Returns `false` if the `VirtAddr` is not mapped by a huge page or the
+     * new page-table cannot be allocated
+     */
+    pub fn split_huge_page(&self, virt_addr: VirtAddr) -> bool {
+        let huge_virt_addr = virt_addr.align_down(Page2MiB::SIZE);
+
+        if self.with_page_tables_locked(|| self.split_huge_page_locked(virt_addr)) {
+            Tlb::shootdown(&huge_virt_addr.to_range(Page2MiB::SIZE));
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Maps the given `Range` to the physically contiguous memory which
+     * starts at `phys_addr` using `S` pages with the given `MapFlags`.
+     *
+     * Returns `false` and rolls back the partial mapping when a page-table
+     * cannot be allocated or a page of the `Range` is already mapped
+     */
+    pub fn map<S>(&self,
+                  virt_range: Range<VirtAddr>,
+                  phys_addr: PhysAddr,
+                  map_flags: MapFlags)
+                  -> bool
+        where S: TPageSize {
+        assert!(virt_range.start.is_aligned(S::SIZE)
+                && virt_range.end.is_aligned(S::SIZE)
+                && phys_addr.is_aligned(S::SIZE),
+                "Tried to map a non {:?} aligned range",
+                S::PAGE_TABLE_LEVEL);
+
+        let mapped_end =
+            self.with_page_tables_locked(|| {
+                for (page_index, virt_addr) in
+                    virt_range.clone().step_by(S::SIZE).enumerate()
+                {
+                    /* only unused entries can be mapped */
+                    match self.ensure_page_table_entry::<S>(virt_addr) {
+                        Some(page_table_entry) if page_table_entry.is_unused() => {
+                            page_table_entry.set_phys_frame(phys_addr.offset(page_index
+                                                                             * S::SIZE));
+                            page_table_entry.set_huge_page(S::IS_HUGE);
+                            page_table_entry.set_present(true);
+                            page_table_entry.set_map_flags(map_flags);
+                        },
+                        _ => return virt_addr
+                    }
+                }
+                virt_range.end
+            });
+
+        /* give back the mapped part of the range on failure */
+        if mapped_end != virt_range.end {
+            self.unmap::<S>(virt_range.start..mapped_end, false);
+            return false;
+        }
+        true
+    }
+
+    /**
+     * Reserves the 4KiB pages of the given `Range` with the given
+     * `MapFlags` without backing them; the physical frames are allocated
+     * by the page fault handler on the first access to each page.
+     *
+     * Returns `false` and rolls back the partial reservation like
+     * `PageDir::map()`
+     */
+    pub fn map_lazy(&self, virt_range: Range<VirtAddr>, map_flags: MapFlags) -> bool {
+        self.map_non_present(virt_range, |page_table_entry| {
+                page_table_entry.set_map_flags(map_flags);
+                page_table_entry.set_lazy(true);
+            })
+    }
+
+    /**
+     * Reserves the 4KiB pages of the given `Range` as guard pages, which
+     * access is always reported as an unrecoverable page fault.
+     *
+     * Returns `false` and rolls back the partial reservation like
+     * `PageDir::map()`
+     */
+    pub fn map_guard(&self, virt_range: Range<VirtAddr>) -> bool {
+        self.map_non_present(virt_range, |page_table_entry| {
+                page_table_entry.set_guard(true);
+            })
+    }
+
+    /**
+     * Unmaps the `S` pages of the given `Range`, releasing the page-tables
+     * which become empty, and invalidates them on all the CPUs.
+     *
+     * When `free_frames` is `true` the physical frames are given back to
+     * the `MemManager` unless they are still shared with other mappings.
+     *
+     * The frames and the page-tables are released only after the
+     * shootdown, because the other CPUs could still access them through
+     * their stale translations; they are collected in fixed capacity
+     * batches, since the heap could be the one which is unmapping.
+     *
+     * The 2MiB huge pages which contain the unmapped 4KiB pages are split,
+     * any other page of a different size than `S` is a bug of the caller
+     */
+    pub fn unmap<S>(&self, virt_range: Range<VirtAddr>, free_frames: bool)
+        where S: TPageSize {
+        let mut released_frames = ReleasedFrames::new();
+
+        let mut batch_begin = virt_range.start;
+        while batch_begin < virt_range.end {
+            /* invalidate and release the collected frames before overflowing, the
+             * CPUs which wait for the page-tables couldn't serve the shootdown
+             */
+            let batch_end = self.with_page_tables_locked(|| {
+                                    self.unmap_batch::<S>(batch_begin..virt_range.end,
+                                                          free_frames,
+                                                          &mut released_frames)
+                                });
+            Tlb::shootdown(&(batch_begin..batch_end));
+            released_frames.release::<S>();
+
+            batch_begin = batch_end;
+        }
+    }
+
+    /**
+     * Changes the protection of the already mapped (or lazily mapped) `S`
+     * pages of the given `Range` to the given `MapFlags`.
+     *
+     * The stale translations are invalidated on all the CPUs before
+     * returning, so the caller can rely on the downgraded protection.
+     *
+     * The 2MiB huge pages which contain the protected 4KiB pages are split.
+     *
+     * Returns `false` if some page of the `Range` is not mapped with a `S`
+     * page
+     */
+    pub fn protect<S>(&self, virt_range: Range<VirtAddr>, map_flags: MapFlags) -> bool
+        where S: TPageSize {
+        let all_mapped = self.with_page_tables_locked(|| {
+                                 self.protect_locked::<S>(&virt_range, map_flags)
+                             });
+
+        Tlb::shootdown(&virt_range);
+        all_mapped
+    }
+
+    /**
+     * Releases all the page-tables which cover the given `Range`, including
+     * the ones referenced by the root page-table.
+     *
+     * The pages must be already unmapped, only the page-tables are given
+     * back to the `MemManager`
+     */
+    pub fn release_page_tables(&self, virt_range: Range<VirtAddr>) {
+        self.with_page_tables_locked(|| {
+                let root_page_table = self.root_page_table();
+
+                let first_root_index: usize =
+                    virt_range.start.page_table_index(PageTableLevel::Root).into();
+                let last_root_index: usize =
+                    VirtAddr::from(*virt_range.end - 1).page_table_index(PageTableLevel::Root)
+                                                       .into();
+                for root_index in first_root_index..=last_root_index {
+                    let root_page_table_entry =
+                        &mut root_page_table[PageTableIndex::from(root_index)];
+                    self.release_page_table_tree(root_page_table_entry,
+                                                 PageTableLevel::OneGiB);
+                }
+            });
+    }
+
     pub unsafe fn next_page_table(&self,
                                   page_table_entry: &PageTableEntry)
                                   -> &mut PageTable {
@@ -99,6 +344,33 @@ impl PageDir /* Methods */ {
 }
 
 impl PageDir /* Getters */ {
+    /**
+     * Returns whether the userspace can access all the 4KiB pages which
+     * cover the given `Range`, even if they are still lazily mapped.
+     *
+     * With `is_write` the pages must also be writeable or copy-on-write
+     */
+    pub fn is_user_range_accessible(&self,
+                                    virt_range: &Range<VirtAddr>,
+                                    is_write: bool)
+                                    -> bool {
+        let first_page = virt_range.start.align_down(Page4KiB::SIZE);
+        self.with_page_tables_locked(|| {
+            (*first_page..*virt_range.end).step_by(Page4KiB::SIZE).all(|raw_virt_addr| {
+                match self.page_table_entry::<Page4KiB>(VirtAddr::from(raw_virt_addr)) {
+                    Some(page_table_entry) => {
+                        page_table_entry.is_user()
+                        && (page_table_entry.is_present() || page_table_entry.is_lazy())
+                        && (!is_write
+                            || page_table_entry.is_writeable()
+                            || page_table_entry.is_copy_on_write())
+                    },
+                    None => false
+                }
+            })
+        })
+    }
+
     pub fn root_phys_frame(&self) -> PhysAddr {
         self.m_hw_page_dir.root_phys_frame()
     }
@@ -109,6 +381,139 @@ impl PageDir /* Getters */ {
 }
 
 impl PageDir /* Privates */ {
+    /**
+     * Splits the 2MiB huge page which contains the given `VirtAddr` like
+     * `PageDir::split_huge_page()`, leaving the shootdown to the caller.
+     *
+     * The stale translations of the huge page are equal to the ones of its
+     * 4KiB pages, and the invalidation of any of them drops the huge one
+     */
+    fn split_huge_page_locked(&self, virt_addr: VirtAddr) -> bool {
+        let huge_virt_addr = virt_addr.align_down(Page2MiB::SIZE);
+
+        let huge_page_entry = match self.page_table_entry::<Page2MiB>(huge_virt_addr) {
+            Some(page_table_entry)
+                if page_table_entry.is_present() && page_table_entry.is_huge_page() =>
+            {
+                page_table_entry
+            },
+            _ => return false
+        };
+        let huge_phys_frame = huge_page_entry.phys_frame().unwrap();
+
+        /* fill the new page-table before linking it, the huge page could be in use */
+        let page_table_phys_frame =
+            match MemManager::instance().allocate_kernel_phys_frame() {
+                Some(phys_frame) => phys_frame,
+                None => return false
+            };
+        let page_table = unsafe { self.frame_to_next_page_table(page_table_phys_frame) };
+        for (page_index, virt_addr) in
+            huge_virt_addr.to_range(Page2MiB::SIZE).step_by(Page4KiB::SIZE).enumerate()
+        {
+            let mut page_table_entry = *huge_page_entry;
+            page_table_entry.set_huge_page(false);
+            page_table_entry.set_phys_frame(huge_phys_frame.offset(page_index
+                                                                   * Page4KiB::SIZE));
+
+            page_table[virt_addr.page_table_index(PageTableLevel::FourKiB)] =
+                page_table_entry;
+        }
+
+        /* replace the huge page with the new page-table with a single write */
+        let mut page_table_link_entry = PageTableEntry::new();
+        page_table_link_entry.set_phys_frame(page_table_phys_frame);
+        page_table_link_entry.set_present(true);
+        page_table_link_entry.set_readable(true);
+        page_table_link_entry.set_writeable(true);
+        *huge_page_entry = page_table_link_entry;
+        true
+    }
+
+    /**
+     * Unmaps the `S` pages of the given `Range` like `PageDir::unmap()`
+     * until the given `ReleasedFrames` are full, leaving the shootdown and
+     * their release to the caller.
+     *
+     * Returns the end of the unmapped part of the `Range`
+     */
+    fn unmap_batch<S>(&self,
+                      virt_range: Range<VirtAddr>,
+                      free_frames: bool,
+                      released_frames: &mut ReleasedFrames)
+                      -> VirtAddr
+        where S: TPageSize {
+        for virt_addr in virt_range.clone().step_by(S::SIZE) {
+            if released_frames.is_full() {
+                return virt_addr;
+            }
+
+            assert!(self.ensure_mapping_level::<S>(virt_addr),
+                    "Tried to unmap {} which is not mapped with a {:?} page",
+                    virt_addr,
+                    S::PAGE_TABLE_LEVEL);
+            if let Some(page_table_entry) = self.page_table_entry::<S>(virt_addr) {
+                if page_table_entry.is_unused() {
+                    continue;
+                }
+
+                /* clear the entry and collect the frame if requested */
+                let phys_frame = page_table_entry.phys_frame();
+                page_table_entry.set_unused();
+                if let Some(phys_frame) = phys_frame.filter(|_| free_frames) {
+                    released_frames.push_frame(phys_frame);
+                }
+
+                self.release_empty_page_tables(virt_addr,
+                                               S::PAGE_TABLE_LEVEL,
+                                               released_frames);
+            }
+        }
+        virt_range.end
+    }
+
+    /**
+     * Changes the protection of the `S` pages of the given `Range` like
+     * `PageDir::protect()`, leaving the shootdown to the caller
+     */
+    fn protect_locked<S>(&self,
+                         virt_range: &Range<VirtAddr>,
+                         map_flags: MapFlags)
+                         -> bool
+        where S: TPageSize {
+        let mut all_mapped = true;
+        for virt_addr in virt_range.clone().step_by(S::SIZE) {
+            if !self.ensure_mapping_level::<S>(virt_addr) {
+                all_mapped = false;
+                continue;
+            }
+
+            match self.page_table_entry::<S>(virt_addr) {
+                Some(page_table_entry)
+                    if page_table_entry.is_present() || page_table_entry.is_lazy() =>
+                {
+                    let mut new_page_table_entry = *page_table_entry;
+                    new_page_table_entry.set_map_flags(map_flags);
+
+                    /* shared frames must be privatised before being written */
+                    if let Some(phys_frame) = new_page_table_entry.phys_frame() {
+                        if new_page_table_entry.is_writeable()
+                           && MemManager::instance().is_phys_frame_shared(phys_frame)
+                        {
+                            new_page_table_entry.set_copy_on_write(true);
+                        }
+                    }
+
+                    /* a single write, a shared frame is never writeable meanwhile */
+                    *page_table_entry = new_page_table_entry;
+                },
+                _ => all_mapped = false
+            }
+        }
+
+        all_mapped
+    }
+
     /**
      * Ensures the next level `PageTable` for the given `VirtAddr` into the
      * given `PageDir`.
@@ -124,6 +529,11 @@ impl PageDir /* Privates */ {
         let page_table_entry =
             &mut prev_table[virt_addr.page_table_index(page_table_level)];
 
+        /* a huge page entry maps memory, not a page-table */
+        if page_table_entry.is_huge_page() {
+            return None;
+        }
+
         /* allocate the next page-table if missing */
         let new_table_created = if page_table_entry.is_unused() {
             let phys_frame = MemManager::instance().allocate_kernel_phys_frame()?;
@@ -133,6 +543,9 @@ impl PageDir /* Privates */ {
             page_table_entry.set_readable(true);
             page_table_entry.set_writeable(true);
 
+            /* the user pages are accessible only if all the levels allow it */
+            page_table_entry.set_user(*virt_addr < LayoutManager::USER_SPACE_END);
+
             true
         } else {
             false
@@ -148,6 +561,170 @@ impl PageDir /* Privates */ {
         Some(next_page_table)
     }
 
+    /**
+     * Marks the unused 4KiB entries of the given `Range` as non-present
+     * using the given `mark_entry` closure, rolling back on failure
+     */
+    fn map_non_present<F>(&self, virt_range: Range<VirtAddr>, mark_entry: F) -> bool
+        where F: Fn(&mut PageTableEntry) {
+        assert!(virt_range.start.is_aligned(Page4KiB::SIZE)
+                && virt_range.end.is_aligned(Page4KiB::SIZE),
+                "Tried to reserve a non page aligned range");
+
+        let marked_end =
+            self.with_page_tables_locked(|| {
+                    for virt_addr in virt_range.clone().step_by(Page4KiB::SIZE) {
+                        match self.ensure_page_table_entry::<Page4KiB>(virt_addr) {
+                            Some(page_table_entry) if page_table_entry.is_unused() => {
+                                mark_entry(page_table_entry)
+                            },
+                            _ => return virt_addr
+                        }
+                    }
+                    virt_range.end
+                });
+
+        if marked_end != virt_range.end {
+            self.unmap::<Page4KiB>(virt_range.start..marked_end, false);
+            return false;
+        }
+        true
+    }
+
+    /**
+     * Returns whether the entry of the `S` level for the given `VirtAddr`
+     * maps, or can map, a `S` page, splitting the 2MiB huge page which
+     * contains it when `S` is `Page4KiB`.
+     *
+     * Returns `false` if the `VirtAddr` belongs to a bigger huge page which
+     * can't be split, or if the entry of a huge level references a
+     * page-table. The stale translations of the split huge page are left
+     * to the shootdown of the caller
+     */
+    fn ensure_mapping_level<S>(&self, virt_addr: VirtAddr) -> bool
+        where S: TPageSize {
+        for walk_level in [PageTableLevel::OneGiB, PageTableLevel::TwoMiB] {
+            if walk_level >= S::PAGE_TABLE_LEVEL {
+                break;
+            }
+
+            match self.walk_to_level(virt_addr, walk_level) {
+                Some(page_table_entry)
+                    if page_table_entry.is_present() && page_table_entry.is_huge_page() =>
+                {
+                    /* only the 2MiB huge pages can be split into 4KiB pages */
+                    return walk_level == PageTableLevel::TwoMiB
+                           && self.split_huge_page_locked(virt_addr);
+                },
+                Some(_) => {},
+                None => return true
+            }
+        }
+
+        /* the entries of the huge levels map memory only with the huge page bit */
+        self.page_table_entry::<S>(virt_addr).map_or(true, |page_table_entry| {
+                                                  !S::IS_HUGE
+                                                  || page_table_entry.is_unused()
+                                                  || page_table_entry.is_huge_page()
+                                              })
+    }
+
+    /**
+     * Returns the `PageTableEntry` of the given `PageTableLevel` for the
+     * given `VirtAddr` walking only the existing page-tables
+     */
+    fn walk_to_level(&self,
+                     virt_addr: VirtAddr,
+                     page_table_level: PageTableLevel)
+                     -> Option<&mut PageTableEntry> {
+        let mut page_table = self.root_page_table();
+        for walk_level in
+            [PageTableLevel::Root, PageTableLevel::OneGiB, PageTableLevel::TwoMiB]
+        {
+            if walk_level == page_table_level {
+                break;
+            }
+
+            let page_table_entry = &page_table[virt_addr.page_table_index(walk_level)];
+            if !page_table_entry.is_present() || page_table_entry.is_huge_page() {
+                return None;
+            }
+            page_table = unsafe { self.next_page_table(page_table_entry) };
+        }
+
+        /* extract the <PageTableEntry> from the requested level */
+        Some(&mut page_table[virt_addr.page_table_index(page_table_level)])
+    }
+
+    /**
+     * Releases the intermediate page-tables which became empty after the
+     * unmapping of the given `VirtAddr` at the given `PageTableLevel`.
+     *
+     * The tables referenced by the root are never released because the
+     * kernel ones are shared among all the address spaces, the others are
+     * unlinked and collected into the given `ReleasedFrames`
+     */
+    fn release_empty_page_tables(&self,
+                                 virt_addr: VirtAddr,
+                                 unmapped_level: PageTableLevel,
+                                 released_frames: &mut ReleasedFrames) {
+        for parent_level in [PageTableLevel::TwoMiB, PageTableLevel::OneGiB] {
+            if parent_level >= unmapped_level {
+                continue;
+            }
+
+            /* obtain the entry which references the page-table to check */
+            let parent_entry = match self.walk_to_level(virt_addr, parent_level) {
+                Some(parent_entry) if parent_entry.is_present() => parent_entry,
+                _ => break
+            };
+            if parent_entry.is_huge_page()
+               || !unsafe { self.next_page_table(parent_entry) }.is_empty()
+            {
+                break;
+            }
+
+            /* unlink the empty page-table, its frame is released by the caller */
+            let page_table_phys_frame = parent_entry.phys_frame().unwrap();
+            parent_entry.set_unused();
+            released_frames.push_page_table(page_table_phys_frame);
+        }
+    }
+
+    /**
+     * Recursively releases the page-table referenced by the given
+     * `PageTableEntry`, which entries belong to `entries_level`, and all
+     * the page-tables referenced by it
+     */
+    fn release_page_table_tree(&self,
+                               page_table_entry: &mut PageTableEntry,
+                               entries_level: PageTableLevel) {
+        /* non-present and huge entries don't reference page-tables */
+        if !page_table_entry.is_present() || page_table_entry.is_huge_page() {
+            return;
+        }
+
+        /* the last level entries reference frames, not page-tables */
+        let page_table = unsafe { self.next_page_table(page_table_entry) };
+        let next_entries_level = match entries_level {
+            PageTableLevel::OneGiB => Some(PageTableLevel::TwoMiB),
+            PageTableLevel::TwoMiB => Some(PageTableLevel::FourKiB),
+            _ => None
+        };
+        if let Some(next_entries_level) = next_entries_level {
+            for index in 0..512usize {
+                self.release_page_table_tree(&mut page_table
+                                                 [PageTableIndex::from(index)],
+                                             next_entries_level);
+            }
+        }
+
+        /* unlink the page-table and give back its frame */
+        let page_table_phys_frame = page_table_entry.phys_frame().unwrap();
+        page_table_entry.set_unused();
+        MemManager::instance().free_phys_frame(page_table_phys_frame);
+    }
+
     unsafe fn frame_to_next_page_table(&self, phys_frame: PhysAddr) -> &mut PageTable {
         let page_table_virt_addr: VirtAddr =
             (*phys_frame + *self.m_phys_mem_offset).into();
@@ -179,7 +756,19 @@ impl Debug for PageDir {
                         continue;
                     }
 
-                    writeln!(f, "\t\t{:03} L3{:?}", l3_index, l3_page_table_entry)?;
+                    write!(f, "\t\t{:03} L3{:?}", l3_index, l3_page_table_entry)?;
+
+                    if l3_page_table_entry.is_huge_page() {
+                        let virt_frame =
+                            VirtAddr::from_1gib_indexes(PageTableIndex::from(l4_index),
+                                                        PageTableIndex::from(l3_index));
+                        writeln!(f,
+                                 " VirtFrame<1GiB>({}) (L4 {}, L3 {})",
+                                 virt_frame, l4_index, l3_index)?;
+                        continue;
+                    } else {
+                        writeln!(f)?;
+                    }
 
                     if l3_page_table_entry.is_present() {
                         for (l2_index,l2_page_table_entry) in
@@ -234,3 +823,74 @@ pub trait THwPageDir: Debug {
 
     fn root_phys_frame(&self) -> PhysAddr;
 }
+
+/**
+ * Fixed capacity collection of the physical frames and of the page-tables
+ * unlinked by `PageDir::unmap()`, which are given back to the `MemManager`
+ * after the TLB shootdown
+ */
+struct ReleasedFrames {
+    m_frames: [PhysAddr; C_MAX_RELEASED_FRAMES],
+    m_frames_count: usize,
+    m_page_tables: [PhysAddr; C_MAX_RELEASED_FRAMES],
+    m_page_tables_count: usize
+}
+
+impl ReleasedFrames /* Constructors */ {
+    /**
+     * Constructs an empty `ReleasedFrames`
+     */
+    fn new() -> Self {
+        Self { m_frames: [PhysAddr::null(); C_MAX_RELEASED_FRAMES],
+               m_frames_count: 0,
+               m_page_tables: [PhysAddr::null(); C_MAX_RELEASED_FRAMES],
+               m_page_tables_count: 0 }
+    }
+}
+
+impl ReleasedFrames /* Methods */ {
+    /**
+     * Collects the given mapped physical frame
+     */
+    fn push_frame(&mut self, phys_frame: PhysAddr) {
+        self.m_frames[self.m_frames_count] = phys_frame;
+        self.m_frames_count += 1;
+    }
+
+    /**
+     * Collects the given page-table physical frame
+     */
+    fn push_page_table(&mut self, page_table_phys_frame: PhysAddr) {
+        self.m_page_tables[self.m_page_tables_count] = page_table_phys_frame;
+        self.m_page_tables_count += 1;
+    }
+
+    /**
+     * Gives back the collected frames, which are `S` sized, and
+     * page-tables to the `MemManager`, the frames only when not shared
+     */
+    fn release<S>(&mut self)
+        where S: TPageSize {
+        let mem_manager = MemManager::instance();
+        for phys_frame in &self.m_frames[..self.m_frames_count] {
+            mem_manager.unref_sized_phys_frame::<S>(*phys_frame);
+        }
+        for page_table_phys_frame in &self.m_page_tables[..self.m_page_tables_count] {
+            mem_manager.free_phys_frame(*page_table_phys_frame);
+        }
+
+        self.m_frames_count = 0;
+        self.m_page_tables_count = 0;
+    }
+}
+
+impl ReleasedFrames /* Getters */ {
+    /**
+     * Returns whether the unmapping of another page could overflow this
+     * collection, which releases a frame and up to two page-tables
+     */
+    fn is_full(&self) -> bool {
+        self.m_frames_count == C_MAX_RELEASED_FRAMES
+        || self.m_page_tables_count + 2 > C_MAX_RELEASED_FRAMES
+    }
+}
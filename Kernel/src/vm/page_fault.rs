@@ -0,0 +1,408 @@
+/*! Page fault handling */
+
+use core::{
+    fmt,
+    fmt::Display
+};
+
+use symbols::code_symbols::CodeSymbols;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    dbg_print::DbgLevel,
+    dbg_println,
+    task::sched::Scheduler,
+    vm::{
+        layout_manager::LayoutManager,
+        mem_manager::MemManager,
+        page_dir::PageDir,
+        page_table_entry::PageTableEntry,
+        tlb::Tlb,
+        user_copy::UserCopy,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/**
+ * Decoded page fault, constructed by the architecture dependent interrupt
+ * code with the information given by the hardware
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct PageFault {
+    m_fault_virt_addr: VirtAddr,
+    m_instr_virt_addr: VirtAddr,
+    m_is_present: bool,
+    m_is_write: bool,
+    m_is_user: bool,
+    m_is_instr_fetch: bool
+}
+
+impl PageFault /* Constructors */ {
+    /**
+     * Constructs a `PageFault` caused by the instruction at
+     * `instr_virt_addr` accessing the given `fault_virt_addr`
+     */
+    pub fn new(fault_virt_addr: VirtAddr,
+               instr_virt_addr: VirtAddr,
+               is_present: bool,
+               is_write: bool,
+               is_user: bool,
+               is_instr_fetch: bool)
+               -> Self {
+        Self { m_fault_virt_addr: fault_virt_addr,
+               m_instr_virt_addr: instr_virt_addr,
+               m_is_present: is_present,
+               m_is_write: is_write,
+               m_is_user: is_user,
+               m_is_instr_fetch: is_instr_fetch }
+    }
+}
+
+impl PageFault /* Methods */ {
+    /**
+     * Resolves the fault backing the lazily mapped page which was
+     * accessed, both for kernel regions and userspace `MMap`s, or
+     * privatising the copy-on-write page which was written.
+     *
+     * Any other fault is unrecoverable, so it is reported and the faulting
+     * userspace `Thread` is terminated, while the kernel panics for its own
+     * faults, unless they were caused by a `UserCopy`, which reports them
+     * to its caller: the returned `VirtAddr` is where the execution must
+     * continue
+     */
+    pub fn handle(&self) -> Option<VirtAddr> {
+        if let Err(page_fault_error) = self.resolve() {
+            if !self.m_is_user {
+                if let Some(fixup_virt_addr) = UserCopy::fixup_of(self.m_instr_virt_addr)
+                {
+                    return Some(fixup_virt_addr);
+                }
+            }
+            self.report(page_fault_error);
+
+            if self.m_is_user {
+                Scheduler::exit_current_by_fault("Unrecoverable page fault");
+            }
+            panic!("Unrecoverable page fault at {} ({})",
+                   self.m_fault_virt_addr, page_fault_error);
+        }
+        None
+    }
+}
+
+impl PageFault /* Getters */ {
+    /**
+     * Returns the accessed `VirtAddr` which caused the fault
+     */
+    pub fn fault_virt_addr(&self) -> VirtAddr {
+        self.m_fault_virt_addr
+    }
+
+    /**
+     * Returns the `VirtAddr` of the instruction which caused the fault
+     */
+    pub fn instr_virt_addr(&self) -> VirtAddr {
+        self.m_instr_virt_addr
+    }
+
+    /**
+     * Returns whether the accessed page was present (protection violation)
+     */
+    pub fn is_present(&self) -> bool {
+        self.m_is_present
+    }
+
+    /**
+     * Returns whether the fault was caused by a write access
+     */
+    pub fn is_write(&self) -> bool {
+        self.m_is_write
+    }
+
+    /**
+     * Returns whether the fault was caused by userspace code
+     */
+    pub fn is_user(&self) -> bool {
+        self.m_is_user
+    }
+
+    /**
+     * Returns whether the fault was caused by an instruction fetch
+     */
+    pub fn is_instr_fetch(&self) -> bool {
+        self.m_is_instr_fetch
+    }
+}
+
+impl PageFault /* Privates */ {
+    /**
+     * Tries to resolve the fault allocating the physical frame for a lazy
+     * page of the `PageDir` which maps the accessed `VirtAddr`.
+     *
+     * The `PageTableEntry` is read with the page-tables locked, so a fault
+     * already resolved by another CPU is recognized by the access which
+     * is now allowed
+     */
+    fn resolve(&self) -> Result<(), PageFaultError> {
+        /* before the memory manager initialization nothing is lazily mapped */
+        let mem_manager = MemManager::try_instance().ok_or(PageFaultError::EarlyFault)?;
+
+        /* the kernel space is modified only through the kernel <PageDir>, the user
+         * space through the one of the <AddressSpace> of the running thread
+         */
+        let page_virt_addr = self.m_fault_virt_addr.align_down(Page4KiB::SIZE);
+        let current_thread = if *page_virt_addr < LayoutManager::KERN_SPACE_BEGIN {
+            Scheduler::current_thread()
+        } else {
+            None
+        };
+        let page_dir =
+            current_thread.as_ref()
+                          .and_then(|current_thread| current_thread.addr_space())
+                          .map_or(mem_manager.kernel_page_dir(), |addr_space| {
+                              addr_space.page_dir()
+                          });
+
+        /* another CPU could be resolving a fault on the same page or changing its
+         * mapping
+         */
+        let shared_phys_frame =
+            page_dir.with_page_tables_locked(|| {
+                        self.resolve_locked(mem_manager, page_dir, page_virt_addr)
+                    })?;
+
+        /* the CPUs which run threads of this <AddressSpace> still read the shared
+         * frame. The page-tables are unlocked first: the CPUs which wait for them
+         * with the interrupts disabled couldn't serve the shootdown
+         */
+        if let Some(shared_phys_frame) = shared_phys_frame {
+            Tlb::shootdown(&page_virt_addr.to_range(Page4KiB::SIZE));
+
+            /* only now nobody translates to it, so the other owner can free it */
+            mem_manager.unref_phys_frame(shared_phys_frame);
+        }
+        Ok(())
+    }
+
+    /**
+     * Resolves the fault on the given page of the given `PageDir`, which
+     * page-tables must be locked.
+     *
+     * Returns the shared frame copied by `PageFault::resolve_copy_on_write()`,
+     * which stale translations and reference are left to the caller
+     */
+    fn resolve_locked(&self,
+                      mem_manager: &MemManager,
+                      page_dir: &PageDir,
+                      page_virt_addr: VirtAddr)
+                      -> Result<Option<PhysAddr>, PageFaultError> {
+        /* obtain the entry of the accessed page */
+        let page_table_entry =
+            page_dir.page_table_entry::<Page4KiB>(page_virt_addr)
+                    .filter(|page_table_entry| !page_table_entry.is_unused())
+                    .ok_or(PageFaultError::NotMapped)?;
+
+        /* the present pages fault only for protection violations or to be copied */
+        if page_table_entry.is_present() {
+            if self.is_access_allowed(page_table_entry) {
+                /* resolved meanwhile, drop the stale translation of this CPU */
+                Tlb::invalidate(page_virt_addr);
+                return Ok(None);
+            } else if self.m_is_write
+                      && page_table_entry.is_copy_on_write()
+                      && (!self.m_is_user || page_table_entry.is_user())
+            {
+                return self.resolve_copy_on_write(mem_manager, page_table_entry);
+            }
+            return Err(PageFaultError::ProtectionViolation);
+        }
+
+        if page_table_entry.is_guard() {
+            return Err(PageFaultError::GuardPage);
+        } else if !page_table_entry.is_lazy() {
+            return Err(PageFaultError::NotMapped);
+        }
+
+        /* the access must respect the protection requested for the mapping */
+        if !self.is_access_allowed(page_table_entry) {
+            return Err(PageFaultError::ProtectionViolation);
+        }
+
+        /* back the page with a zeroed frame, never expose its previous content */
+        let phys_frame = mem_manager.allocate_kernel_phys_frame()
+                                    .ok_or(PageFaultError::OutOfMemory)?;
+        unsafe {
+            mem_manager.layout_manager()
+                       .phys_addr_to_virt_addr(phys_frame)
+                       .as_ptr_mut::<u8>()
+                       .write_bytes(0, Page4KiB::SIZE);
+        }
+
+        /* publish the backed entry with a single write */
+        let mut backed_page_table_entry = *page_table_entry;
+        backed_page_table_entry.set_phys_frame(phys_frame);
+        backed_page_table_entry.set_lazy(false);
+        backed_page_table_entry.set_present(true);
+        *page_table_entry = backed_page_table_entry;
+
+        /* the non-present entries are not cached, but some CPUs cache the misses */
+        Tlb::invalidate(page_virt_addr);
+        Ok(None)
+    }
+
+    /**
+     * Returns whether the protection of the given `PageTableEntry` allows
+     * the access which caused the fault
+     */
+    fn is_access_allowed(&self, page_table_entry: &PageTableEntry) -> bool {
+        !(self.m_is_write && !page_table_entry.is_writeable())
+        && !(self.m_is_instr_fetch && page_table_entry.is_no_execute())
+        && !(self.m_is_user && !page_table_entry.is_user())
+    }
+
+    /**
+     * Privatises the copy-on-write page written, copying the shared frame
+     * into a new one, or simply restoring the write permission when this
+     * is the last owner of the frame.
+     *
+     * Must be called with the page-tables locked, otherwise two CPUs
+     * could privatise the same entry and both give back its reference of
+     * the shared frame.
+     *
+     * Returns the shared frame when copied: its stale read-only
+     * translations must be invalidated on all the CPUs before giving back
+     * this reference, otherwise the other owner could free it while still
+     * read. The stale translations of a frame which stays private only
+     * cause a spurious fault
+     */
+    fn resolve_copy_on_write(&self,
+                             mem_manager: &MemManager,
+                             page_table_entry: &mut PageTableEntry)
+                             -> Result<Option<PhysAddr>, PageFaultError> {
+        let shared_phys_frame = page_table_entry.phys_frame().unwrap();
+
+        let mut private_page_table_entry = *page_table_entry;
+        if mem_manager.is_phys_frame_shared(shared_phys_frame) {
+            let phys_frame = mem_manager.allocate_kernel_phys_frame()
+                                        .ok_or(PageFaultError::OutOfMemory)?;
+
+            /* copy the content through the physical memory mapping */
+            let layout_manager = mem_manager.layout_manager();
+            let shared_frame_ptr =
+                layout_manager.phys_addr_to_virt_addr(shared_phys_frame).as_ptr::<u8>();
+            let frame_ptr =
+                layout_manager.phys_addr_to_virt_addr(phys_frame).as_ptr_mut::<u8>();
+            unsafe {
+                frame_ptr.copy_from_nonoverlapping(shared_frame_ptr, Page4KiB::SIZE);
+            }
+            private_page_table_entry.set_phys_frame(phys_frame);
+        }
+        private_page_table_entry.set_copy_on_write(false);
+        private_page_table_entry.set_writeable(true);
+
+        /* publish the private entry, the caller gives back the shared one */
+        *page_table_entry = private_page_table_entry;
+        if private_page_table_entry.phys_frame() != Some(shared_phys_frame) {
+            Ok(Some(shared_phys_frame))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /**
+     * Prints the details of the unrecoverable fault with the symbol of
+     * the faulting instruction when available
+     */
+    fn report(&self, page_fault_error: PageFaultError) {
+        dbg_println!(DbgLevel::Err, "<< PAGE FAULT >>");
+        dbg_println!(DbgLevel::Err,
+                     ">> {} {} at {} by {} code",
+                     if self.m_is_instr_fetch {
+                         "Instruction fetch"
+                     } else if self.m_is_write {
+                         "Write"
+                     } else {
+                         "Read"
+                     },
+                     if self.m_is_present {
+                         "of a present page"
+                     } else {
+                         "of a non-present page"
+                     },
+                     self.m_fault_virt_addr,
+                     if self.m_is_user {
+                         "user"
+                     } else {
+                         "kernel"
+                     });
+        dbg_println!(DbgLevel::Err, ">> Cause: {}", page_fault_error);
+
+        /* resolve the symbol of the faulting instruction */
+        let code_symbol = if CodeSymbols::are_available() {
+            CodeSymbols::instance().symbol_at(*self.m_instr_virt_addr)
+        } else {
+            None
+        };
+        if let Some(code_symbol) = code_symbol {
+            dbg_println!(DbgLevel::Err,
+                         ">> Instruction at {} in {}",
+                         self.m_instr_virt_addr,
+                         code_symbol);
+        } else {
+            dbg_println!(DbgLevel::Err,
+                         ">> Instruction at {} (unknown symbol)",
+                         self.m_instr_virt_addr);
+        }
+    }
+}
+
+/**
+ * Lists the causes of an unrecoverable `PageFault`
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum PageFaultError {
+    /**
+     * The fault happened before the `MemManager` initialization
+     */
+    EarlyFault,
+
+    /**
+     * The accessed page is not mapped at all
+     */
+    NotMapped,
+
+    /**
+     * The accessed page is a guard page, probably a stack overflow
+     */
+    GuardPage,
+
+    /**
+     * The access doesn't respect the protection of the mapping
+     */
+    ProtectionViolation,
+
+    /**
+     * No physical frames are available to back the lazy page
+     */
+    OutOfMemory
+}
+
+impl Display for PageFaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EarlyFault => write!(f, "Fault before memory manager initialization"),
+            Self::NotMapped => write!(f, "Access to unmapped memory"),
+            Self::GuardPage => write!(f, "Guard page hit, stack overflow"),
+            Self::ProtectionViolation => write!(f, "Protection violation"),
+            Self::OutOfMemory => write!(f, "Out of memory backing a lazy page")
+        }
+    }
+}
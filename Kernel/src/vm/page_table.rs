@@ -29,6 +29,13 @@ impl PageTable /* Getters */ {
     pub fn iter(&self) -> impl Iterator<Item = &PageTableEntry> {
         self.m_entries.iter()
     }
+
+    /**
+     * Returns whether all the entries are unused
+     */
+    pub fn is_empty(&self) -> bool {
+        self.iter().all(|page_table_entry| page_table_entry.is_unused())
+    }
 }
 
 impl Index<PageTableIndex> for PageTable {
@@ -4,7 +4,11 @@ use core::fmt::Debug;
 
 use crate::{
     addr::phys_addr::PhysAddr,
-    arch::vm::hw_page_table_entry::HwPageTableEntry
+    arch::vm::hw_page_table_entry::HwPageTableEntry,
+    vm::{
+        MapFlags,
+        MapFlagsBits
+    }
 };
 
 #[repr(transparent)]
@@ -20,12 +24,6 @@ impl PageTableEntry /* Constructors */ {
     }
 }
 
-impl PageTableEntry /* Methods */ {
-    pub unsafe fn invalidate_in_tlb(&self) {
-        self.m_hw_entry.invalidate_in_tlb();
-    }
-}
-
 impl PageTableEntry /* Getters */ {
     #[inline]
     pub fn phys_frame(&self) -> Option<PhysAddr> {
@@ -86,6 +84,33 @@ impl PageTableEntry /* Getters */ {
         self.m_hw_entry.is_user()
     }
 
+    /**
+     * Returns whether this non-present entry waits for a physical frame,
+     * which is allocated by the page fault handler on first access
+     */
+    #[inline]
+    pub fn is_lazy(&self) -> bool {
+        self.m_hw_entry.is_lazy()
+    }
+
+    /**
+     * Returns whether this non-present entry is a guard page, which must
+     * never be accessed
+     */
+    #[inline]
+    pub fn is_guard(&self) -> bool {
+        self.m_hw_entry.is_guard()
+    }
+
+    /**
+     * Returns whether this present entry maps a read-only frame which is
+     * privatised on the first write
+     */
+    #[inline]
+    pub fn is_copy_on_write(&self) -> bool {
+        self.m_hw_entry.is_copy_on_write()
+    }
+
     #[inline]
     pub fn is_unused(&self) -> bool {
         self.m_hw_entry.is_unused()
@@ -148,17 +173,61 @@ impl PageTableEntry /* Setters */ {
         self.m_hw_entry.set_user(is_user);
     }
 
+    #[inline]
+    pub fn set_lazy(&mut self, is_lazy: bool) {
+        self.m_hw_entry.set_lazy(is_lazy);
+    }
+
+    #[inline]
+    pub fn set_guard(&mut self, is_guard: bool) {
+        self.m_hw_entry.set_guard(is_guard);
+    }
+
+    /**
+     * Marks this writeable entry as copy-on-write, write-protecting it and
+     * clearing the dirty bit.
+     *
+     * Clearing the mark doesn't restore the write permission
+     */
+    #[inline]
+    pub fn set_copy_on_write(&mut self, is_copy_on_write: bool) {
+        self.m_hw_entry.set_copy_on_write(is_copy_on_write);
+        if is_copy_on_write {
+            self.m_hw_entry.set_writeable(false);
+            self.m_hw_entry.set_dirty(false);
+        }
+    }
+
     #[inline]
     pub fn set_unused(&mut self) {
         self.m_hw_entry.set_unused();
     }
+
+    /**
+     * Applies the protection and caching bits described by the given
+     * `MapFlags`, the other bits are left untouched.
+     *
+     * A copy-on-write entry stays write-protected until privatised, or
+     * loses the mark when it becomes read-only
+     */
+    pub fn set_map_flags(&mut self, map_flags: MapFlags) {
+        let is_writeable = map_flags.is_enabled(MapFlagsBits::Writeable);
+        if self.is_copy_on_write() && !is_writeable {
+            self.set_copy_on_write(false);
+        }
+
+        self.set_readable(true);
+        self.set_writeable(is_writeable && !self.is_copy_on_write());
+        self.set_no_execute(map_flags.is_disabled(MapFlagsBits::Executable));
+        self.set_cacheable(map_flags.is_disabled(MapFlagsBits::Uncacheable));
+        self.set_global(map_flags.is_enabled(MapFlagsBits::Global));
+        self.set_user(map_flags.is_enabled(MapFlagsBits::User));
+    }
 }
 
 pub trait THwPageTableEntry: Debug + Copy + Clone {
     fn new() -> Self;
 
-    unsafe fn invalidate_in_tlb(&self);
-
     fn raw_phys_frame(&self) -> usize;
     fn is_present(&self) -> bool;
     fn is_readable(&self) -> bool;
@@ -170,6 +239,9 @@ pub trait THwPageTableEntry: Debug + Copy + Clone {
     fn is_dirty(&self) -> bool;
     fn is_no_execute(&self) -> bool;
     fn is_user(&self) -> bool;
+    fn is_lazy(&self) -> bool;
+    fn is_guard(&self) -> bool;
+    fn is_copy_on_write(&self) -> bool;
     fn is_unused(&self) -> bool;
 
     fn set_raw_phys_frame(&mut self, raw_phys_frame: usize);
@@ -183,5 +255,8 @@ pub trait THwPageTableEntry: Debug + Copy + Clone {
     fn set_dirty(&mut self, is_dirty: bool);
     fn set_no_execute(&mut self, is_no_execute: bool);
     fn set_user(&mut self, is_user: bool);
+    fn set_lazy(&mut self, is_lazy: bool);
+    fn set_guard(&mut self, is_guard: bool);
+    fn set_copy_on_write(&mut self, is_copy_on_write: bool);
     fn set_unused(&mut self);
 }
@@ -0,0 +1,145 @@
+/*! Bitmap physical frames allocator */
+
+use core::ops::Range;
+
+use bits::bit_fields::{
+    BitFindMode,
+    TBitArray,
+    TBitFields
+};
+use helps::align::align_up;
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        TAddress
+    },
+    vm::{
+        phys_alloc::TPhysAllocator,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/**
+ * Flat bitmap physical frames allocator.
+ *
+ * Each bit of the bitmap represents a 4KiB frame, `true` means available.
+ * Allocations are linear in the installed memory, but the overhead is
+ * only one bit for each frame
+ */
+pub struct BitmapPhysAllocator {
+    m_phys_frames_bitmap: SpinMutex<&'static mut [u8]>
+}
+
+impl BitmapPhysAllocator /* Constructors */ {
+    /**
+     * Constructs a `BitmapPhysAllocator` able to cover the memory until
+     * `last_phys_mem_addr` which marks as available only the given ranges
+     */
+    pub fn new(last_phys_mem_addr: PhysAddr,
+               avail_phys_ranges: &[Range<PhysAddr>])
+               -> Self {
+        /* allocate the physical frames bitmap filled with zeroes */
+        let phys_frames_bitmap_requested_pages =
+            ((*last_phys_mem_addr / Page4KiB::SIZE / u8::BIT_LEN) + Page4KiB::MASK) >> 12;
+        let mut phys_frames_bitmap =
+            vec![0; phys_frames_bitmap_requested_pages * Page4KiB::SIZE];
+
+        /* mark the available frames into the bitmap */
+        for phys_addr in avail_phys_ranges.iter()
+                                          .flat_map(|phys_range| {
+                                              phys_range.clone().step_by(Page4KiB::SIZE)
+                                          })
+        {
+            phys_frames_bitmap.set_bit(phys_addr.as_page_index::<Page4KiB>(), true);
+        }
+
+        Self { m_phys_frames_bitmap: SpinMutex::const_new(phys_frames_bitmap.leak()) }
+    }
+}
+
+impl BitmapPhysAllocator /* Privates */ {
+    /**
+     * Returns the index of the first bit of a run of `frames_count`
+     * available bits which starts at a multiple of `frames_align`
+     */
+    fn find_phys_frames_run(phys_frames_bitmap: &[u8],
+                            frames_count: usize,
+                            frames_align: usize)
+                            -> Option<usize> {
+        let bitmap_bit_len = phys_frames_bitmap.bit_len();
+
+        let mut run_start = 0;
+        while run_start + frames_count <= bitmap_bit_len {
+            /* skip at once all the fully allocated bytes before the candidate */
+            let byte_index = run_start / u8::BIT_LEN;
+            let next_free_bit =
+                byte_index * u8::BIT_LEN
+                + phys_frames_bitmap[byte_index..].find_bit(true, BitFindMode::Regular)?;
+            if next_free_bit > run_start {
+                run_start = align_up(next_free_bit, frames_align);
+                continue;
+            }
+
+            /* check the whole candidate run, restart after the first busy bit */
+            let run_range = run_start..run_start + frames_count;
+            if let Some(busy_bit_index) =
+                run_range.into_iter().find(|&i| !phys_frames_bitmap.bit_at(i))
+            {
+                run_start = align_up(busy_bit_index + 1, frames_align);
+            } else {
+                return Some(run_start);
+            }
+        }
+        None
+    }
+}
+
+impl TPhysAllocator for BitmapPhysAllocator {
+    fn allocate_phys_frames(&self,
+                            frames_count: usize,
+                            frames_align: usize)
+                            -> Option<Range<PhysAddr>> {
+        let mut unlocked_bitmap = self.m_phys_frames_bitmap.lock();
+
+        /* find the first run of available bits which satisfies the alignment */
+        let first_bit_index = if frames_count == 1 {
+            unlocked_bitmap.find_bit(true, BitFindMode::Regular)?
+        } else {
+            Self::find_phys_frames_run(&unlocked_bitmap, frames_count, frames_align)?
+        };
+
+        /* mark the whole run as no-more available */
+        for bit_index in first_bit_index..first_bit_index + frames_count {
+            unlocked_bitmap.set_bit(bit_index, false);
+        }
+
+        /* construct the <PhysAddr> range from the first frame number */
+        let first_phys_frame = PhysAddr::from(first_bit_index * Page4KiB::SIZE);
+        Some(first_phys_frame.to_range(frames_count * Page4KiB::SIZE))
+    }
+
+    fn free_phys_frames(&self, phys_frames_range: Range<PhysAddr>) {
+        let mut unlocked_bitmap = self.m_phys_frames_bitmap.lock();
+
+        let first_bit_index = phys_frames_range.start.as_page_index::<Page4KiB>();
+        let last_bit_index = phys_frames_range.end.as_page_index::<Page4KiB>();
+        assert!(last_bit_index <= unlocked_bitmap.bit_len(),
+                "Tried to free physical frames outside the installed memory");
+
+        /* mark back the bits as available, a bit already available means double free */
+        for bit_index in first_bit_index..last_bit_index {
+            assert!(!unlocked_bitmap.bit_at(bit_index),
+                    "Double free of physical frame {}",
+                    PhysAddr::from(bit_index * Page4KiB::SIZE));
+
+            unlocked_bitmap.set_bit(bit_index, true);
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Bitmap"
+    }
+}
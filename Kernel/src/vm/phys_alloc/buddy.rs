@@ -0,0 +1,301 @@
+/*! Buddy system physical frames allocator */
+
+use alloc::vec::Vec;
+use core::{
+    cmp::min,
+    ops::Range
+};
+
+use bits::bit_fields::{
+    BitFindMode,
+    TBitArray,
+    TBitFields
+};
+use helps::dbg::C_GIB;
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        phys_addr::PhysAddr,
+        TAddress
+    },
+    vm::{
+        phys_alloc::TPhysAllocator,
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/* biggest order managed, <Page4KiB::SIZE> << <C_MAX_ORDER> = 1GiB */
+const C_MAX_ORDER: usize = (C_GIB / Page4KiB::SIZE).trailing_zeros() as usize;
+
+/**
+ * Buddy system physical frames allocator.
+ *
+ * Manages naturally aligned blocks of `4KiB << order` bytes, from 4KiB up
+ * to 1GiB. Each order keeps a bitmap of its free blocks, freed blocks are
+ * coalesced with their buddy as long as it is free too
+ */
+pub struct BuddyPhysAllocator {
+    m_orders: SpinMutex<Vec<BuddyOrder>>
+}
+
+impl BuddyPhysAllocator /* Constructors */ {
+    /**
+     * Constructs a `BuddyPhysAllocator` able to cover the memory until
+     * `last_phys_mem_addr` which marks as available only the given ranges
+     */
+    pub fn new(last_phys_mem_addr: PhysAddr,
+               avail_phys_ranges: &[Range<PhysAddr>])
+               -> Self {
+        let phys_frames_count = last_phys_mem_addr.as_page_index::<Page4KiB>();
+
+        /* allocate the free-blocks bitmap of each order */
+        let buddy_orders =
+            (0..=C_MAX_ORDER).map(|order| {
+                                 let blocks_count =
+                                     (phys_frames_count + (1 << order) - 1) >> order;
+                                 BuddyOrder::new(blocks_count)
+                             })
+                             .collect();
+        let buddy_allocator = Self { m_orders: SpinMutex::const_new(buddy_orders) };
+
+        /* release the available memory, the coalescing builds the biggest blocks */
+        {
+            let mut unlocked_orders = buddy_allocator.m_orders.lock();
+            for phys_range in avail_phys_ranges.iter() {
+                let first_frame = phys_range.start.as_page_index::<Page4KiB>();
+                let last_frame = phys_range.end.as_page_index::<Page4KiB>();
+
+                Self::free_frames_run(&mut unlocked_orders,
+                                      first_frame,
+                                      last_frame - first_frame);
+            }
+        }
+        buddy_allocator
+    }
+}
+
+impl BuddyPhysAllocator /* Privates */ {
+    /**
+     * Allocates a block of the given order and returns its first frame
+     * number
+     */
+    fn allocate_block(buddy_orders: &mut [BuddyOrder], order: usize) -> Option<usize> {
+        /* find the smallest order which have at least one free block */
+        let avail_order = (order..=C_MAX_ORDER).find(|&avail_order| {
+                              buddy_orders[avail_order].has_free_blocks()
+                          })?;
+        let mut block_index = buddy_orders[avail_order].take_free_block()?;
+
+        /* split the block until the requested order, freeing the upper halves */
+        for split_order in (order..avail_order).rev() {
+            block_index <<= 1;
+            buddy_orders[split_order].mark_free(block_index + 1);
+        }
+        Some(block_index << order)
+    }
+
+    /**
+     * Frees the given block coalescing it with its buddies
+     */
+    fn free_block(buddy_orders: &mut [BuddyOrder], block_index: usize, order: usize) {
+        /* a free block in the same order or in any of the upper orders which
+         * contains the given block means double free
+         */
+        for upper_order in order..=C_MAX_ORDER {
+            assert!(!buddy_orders[upper_order].is_free(block_index
+                                                       >> (upper_order - order)),
+                    "Double free of physical frame {}",
+                    PhysAddr::from((block_index << order) * Page4KiB::SIZE));
+        }
+
+        /* the same for a free block in any of the lower orders which is
+         * contained into the given block, scanned only by the debug builds
+         * because it costs O(2^order)
+         */
+        if cfg!(debug_assertions) {
+            for lower_order in 0..order {
+                let shift = order - lower_order;
+                let children = block_index << shift..(block_index + 1) << shift;
+
+                assert!(!buddy_orders[lower_order].has_free_in(children),
+                        "Double free of physical frames into {}",
+                        PhysAddr::from((block_index << order) * Page4KiB::SIZE));
+            }
+        }
+
+        /* coalesce the block with the buddy until it is free */
+        let mut block_index = block_index;
+        let mut order = order;
+        while order < C_MAX_ORDER && buddy_orders[order].is_free(block_index ^ 1) {
+            buddy_orders[order].mark_used(block_index ^ 1);
+            block_index >>= 1;
+            order += 1;
+        }
+        buddy_orders[order].mark_free(block_index);
+    }
+
+    /**
+     * Frees an arbitrary run of frames splitting it into the biggest naturally
+     * aligned blocks
+     */
+    fn free_frames_run(buddy_orders: &mut [BuddyOrder],
+                       first_frame: usize,
+                       frames_count: usize) {
+        let mut first_frame = first_frame;
+        let mut frames_count = frames_count;
+        while frames_count > 0 {
+            /* the block must be aligned to its size and must not exceed the run */
+            let order = min(min(first_frame.trailing_zeros() as usize,
+                                Self::floor_log2(frames_count)),
+                            C_MAX_ORDER);
+
+            Self::free_block(buddy_orders, first_frame >> order, order);
+            first_frame += 1 << order;
+            frames_count -= 1 << order;
+        }
+    }
+
+    /**
+     * Returns the order of the smallest block which contains `frames_count`
+     * frames
+     */
+    fn ceil_log2(frames_count: usize) -> usize {
+        frames_count.next_power_of_two().trailing_zeros() as usize
+    }
+
+    /**
+     * Returns the order of the biggest block which is contained into
+     * `frames_count` frames
+     */
+    fn floor_log2(frames_count: usize) -> usize {
+        (usize::BITS - 1 - frames_count.leading_zeros()) as usize
+    }
+}
+
+impl TPhysAllocator for BuddyPhysAllocator {
+    fn allocate_phys_frames(&self,
+                            frames_count: usize,
+                            frames_align: usize)
+                            -> Option<Range<PhysAddr>> {
+        /* the blocks are naturally aligned, so the alignment is an order too */
+        let order = Self::ceil_log2(frames_count).max(Self::ceil_log2(frames_align));
+        if order > C_MAX_ORDER {
+            return None;
+        }
+
+        let mut unlocked_orders = self.m_orders.lock();
+
+        /* allocate the block and give back the exceeding tail */
+        let first_frame = Self::allocate_block(&mut unlocked_orders, order)?;
+        Self::free_frames_run(&mut unlocked_orders,
+                              first_frame + frames_count,
+                              (1 << order) - frames_count);
+
+        /* construct the <PhysAddr> range from the first frame number */
+        let first_phys_frame = PhysAddr::from(first_frame * Page4KiB::SIZE);
+        Some(first_phys_frame.to_range(frames_count * Page4KiB::SIZE))
+    }
+
+    fn free_phys_frames(&self, phys_frames_range: Range<PhysAddr>) {
+        let first_frame = phys_frames_range.start.as_page_index::<Page4KiB>();
+        let last_frame = phys_frames_range.end.as_page_index::<Page4KiB>();
+
+        Self::free_frames_run(&mut self.m_orders.lock(),
+                              first_frame,
+                              last_frame - first_frame);
+    }
+
+    fn name(&self) -> &'static str {
+        "Buddy"
+    }
+}
+
+/**
+ * Free blocks of a single order of the `BuddyPhysAllocator`
+ */
+struct BuddyOrder {
+    m_free_blocks_bitmap: Vec<u8>,
+    m_free_blocks_count: usize,
+    m_first_free_byte_hint: usize
+}
+
+impl BuddyOrder /* Constructors */ {
+    /**
+     * Constructs a `BuddyOrder` with `blocks_count` blocks all marked as
+     * used
+     */
+    fn new(blocks_count: usize) -> Self {
+        Self { m_free_blocks_bitmap: vec![
+                   0;
+                   (blocks_count + u8::BIT_LEN - 1) / u8::BIT_LEN
+               ],
+               m_free_blocks_count: 0,
+               m_first_free_byte_hint: 0 }
+    }
+}
+
+impl BuddyOrder /* Methods */ {
+    /**
+     * Takes the first free block of this order and returns its index
+     */
+    fn take_free_block(&mut self) -> Option<usize> {
+        /* all the bytes before the hint are known to be without free blocks */
+        let hint_byte = self.m_first_free_byte_hint;
+        let block_index =
+            hint_byte * u8::BIT_LEN
+            + self.m_free_blocks_bitmap[hint_byte..].find_bit(true,
+                                                              BitFindMode::Regular)?;
+
+        self.m_first_free_byte_hint = block_index / u8::BIT_LEN;
+        self.mark_used(block_index);
+        Some(block_index)
+    }
+
+    /**
+     * Marks the given block as free
+     */
+    fn mark_free(&mut self, block_index: usize) {
+        self.m_free_blocks_bitmap.set_bit(block_index, true);
+        self.m_free_blocks_count += 1;
+        self.m_first_free_byte_hint =
+            min(self.m_first_free_byte_hint, block_index / u8::BIT_LEN);
+    }
+
+    /**
+     * Marks the given free block as used
+     */
+    fn mark_used(&mut self, block_index: usize) {
+        self.m_free_blocks_bitmap.set_bit(block_index, false);
+        self.m_free_blocks_count -= 1;
+    }
+}
+
+impl BuddyOrder /* Getters */ {
+    /**
+     * Returns whether the given block exists and it is free
+     */
+    fn is_free(&self, block_index: usize) -> bool {
+        block_index < self.m_free_blocks_bitmap.bit_len()
+        && self.m_free_blocks_bitmap.bit_at(block_index)
+    }
+
+    /**
+     * Returns whether at least one of the given blocks is free
+     */
+    fn has_free_in(&self, block_range: Range<usize>) -> bool {
+        self.has_free_blocks()
+        && block_range.take_while(|block_index| {
+                          *block_index < self.m_free_blocks_bitmap.bit_len()
+                      })
+                      .any(|block_index| self.m_free_blocks_bitmap.bit_at(block_index))
+    }
+
+    /**
+     * Returns whether this order have at least one free block
+     */
+    fn has_free_blocks(&self) -> bool {
+        self.m_free_blocks_count > 0
+    }
+}
@@ -0,0 +1,39 @@
+/*! Physical frames allocators */
+
+use core::ops::Range;
+
+use crate::addr::phys_addr::PhysAddr;
+
+pub mod bitmap;
+pub mod buddy;
+
+/**
+ * Common interface for the physical frames allocators used by the
+ * `MemManager`.
+ *
+ * The implementations work with 4KiB frames, are internally synchronized
+ * and don't update the `MemManagerStats`, which is a `MemManager` duty
+ */
+pub trait TPhysAllocator: Send + Sync {
+    /**
+     * Allocates `frames_count` physically contiguous 4KiB frames which first
+     * frame number is a multiple of `frames_align` (a power of two)
+     */
+    fn allocate_phys_frames(&self,
+                            frames_count: usize,
+                            frames_align: usize)
+                            -> Option<Range<PhysAddr>>;
+
+    /**
+     * Gives back to the allocator all the 4KiB frames which compose the
+     * given `Range`.
+     *
+     * Panics when one of the frames is already free
+     */
+    fn free_phys_frames(&self, phys_frames_range: Range<PhysAddr>);
+
+    /**
+     * Returns the name of the allocator for debugging purposes
+     */
+    fn name(&self) -> &'static str;
+}
@@ -0,0 +1,97 @@
+/*! Translation lookaside buffer management */
+
+use core::ops::Range;
+
+use crate::{
+    addr::virt_addr::VirtAddr,
+    arch::vm::hw_tlb::HwTlb,
+    vm::{
+        Page4KiB,
+        TPageSize
+    }
+};
+
+/* over this amount of pages a full flush is cheaper than a per-page invalidation */
+const C_MAX_INVALIDATE_PAGES: usize = 64;
+
+/* <None> until the secondary CPUs are started */
+static mut SM_SHOOTDOWN_HOOK: Option<fn(&Range<VirtAddr>)> = None;
+
+/**
+ * Architecture independent TLB invalidation interface
+ */
+pub struct Tlb;
+
+impl Tlb /* Static Functions */ {
+    /**
+     * Invalidates the TLB entry of the given `VirtAddr` on the executing
+     * CPU
+     */
+    pub fn invalidate(virt_addr: VirtAddr) {
+        unsafe {
+            HwTlb::invalidate(virt_addr);
+        }
+    }
+
+    /**
+     * Invalidates the TLB entries of the given `Range` on the executing CPU
+     */
+    pub fn invalidate_range(virt_range: &Range<VirtAddr>) {
+        let pages_count = (*virt_range.end - *virt_range.start) / Page4KiB::SIZE;
+        if pages_count > C_MAX_INVALIDATE_PAGES {
+            Self::flush_all();
+        } else {
+            for virt_addr in virt_range.clone().step_by(Page4KiB::SIZE) {
+                Self::invalidate(virt_addr);
+            }
+        }
+    }
+
+    /**
+     * Flushes all the TLB entries of the executing CPU, global ones too
+     */
+    pub fn flush_all() {
+        unsafe {
+            HwTlb::flush_all();
+        }
+    }
+
+    /**
+     * Invalidates the TLB entries of the given `Range` on all the CPUs.
+     *
+     * The other CPUs are reached through the hook registered with
+     * `Tlb::set_shootdown_hook()`
+     */
+    pub fn shootdown(virt_range: &Range<VirtAddr>) {
+        Self::invalidate_range(virt_range);
+
+        if let Some(shootdown_hook) = unsafe { SM_SHOOTDOWN_HOOK } {
+            shootdown_hook(virt_range);
+        }
+    }
+
+    /**
+     * Registers the function which asks to the other CPUs to invalidate
+     * the given `Range`.
+     *
+     * Called once the secondary CPUs are started
+     */
+    pub unsafe fn set_shootdown_hook(shootdown_hook: fn(&Range<VirtAddr>)) {
+        SM_SHOOTDOWN_HOOK = Some(shootdown_hook);
+    }
+}
+
+/**
+ * Interface on which the `Tlb` relies to invalidate the hardware TLB
+ */
+pub trait THwTlb {
+    /**
+     * Invalidates the TLB entry of the given `VirtAddr`
+     */
+    unsafe fn invalidate(virt_addr: VirtAddr);
+
+    /**
+     * Flushes all the TLB entries, global ones too
+     */
+    unsafe fn flush_all();
+}
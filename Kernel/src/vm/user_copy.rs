@@ -0,0 +1,120 @@
+/*! Userspace memory copy */
+
+use core::{
+    mem,
+    mem::MaybeUninit,
+    ops::Range
+};
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    arch::vm::hw_user_copy::HwUserCopy,
+    vm::layout_manager::LayoutManager
+};
+
+/**
+ * Fault tolerant copy between the userspace and the Kernel memory.
+ *
+ * The userspace could unmap its memory at any time, so the Kernel never
+ * dereferences its pointers; the unresolvable page faults of the copies
+ * are reported as failures to their callers
+ */
+pub struct UserCopy;
+
+impl UserCopy /* Static Functions */ {
+    /**
+     * Copies the raw bytes of the `T` at the given userspace address into
+     * the Kernel memory, without validating them.
+     *
+     * Returns `None` if the address is not aligned, it is out of the user
+     * space or some of its pages can't be read
+     */
+    pub fn read_raw<T>(raw_user_addr: VirtAddr) -> Option<MaybeUninit<T>> {
+        if !Self::is_user_ptr::<T>(raw_user_addr) {
+            return None;
+        }
+
+        let mut raw_value = MaybeUninit::<T>::uninit();
+        let copied = unsafe {
+            HwUserCopy::copy(raw_value.as_mut_ptr() as *mut u8,
+                             raw_user_addr.as_ptr::<u8>(),
+                             mem::size_of::<T>())
+        };
+        if copied {
+            Some(raw_value)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Copies the given `Range` of bytes of the given raw `T` into the `T`
+     * at the given userspace address, leaving untouched the other bytes.
+     *
+     * Returns `false` if the address is not aligned, it is out of the user
+     * space or some of its pages can't be written
+     */
+    pub fn write_raw<T>(raw_user_addr: VirtAddr,
+                        raw_value: &MaybeUninit<T>,
+                        bytes_range: Range<usize>)
+                        -> bool {
+        assert!(bytes_range.start <= bytes_range.end
+                && bytes_range.end <= mem::size_of::<T>(),
+                "Tried to write out of the bounds of the userspace value");
+
+        if !Self::is_user_ptr::<T>(raw_user_addr) {
+            return false;
+        }
+
+        unsafe {
+            HwUserCopy::copy(raw_user_addr.offset(bytes_range.start).as_ptr_mut::<u8>(),
+                             (raw_value.as_ptr() as *const u8).add(bytes_range.start),
+                             bytes_range.end - bytes_range.start)
+        }
+    }
+
+    /**
+     * Returns the `VirtAddr` where the execution must continue after the
+     * unresolvable page fault of the instruction at the given `VirtAddr`,
+     * when it belongs to a copy
+     */
+    pub fn fixup_of(instr_virt_addr: VirtAddr) -> Option<VirtAddr> {
+        HwUserCopy::fixup_of(instr_virt_addr)
+    }
+}
+
+impl UserCopy /* Privates */ {
+    /**
+     * Returns whether the given address points to an aligned `T` entirely
+     * into the user space
+     */
+    fn is_user_ptr<T>(raw_user_addr: VirtAddr) -> bool {
+        /* check the bounds without building the range, which could overflow */
+        raw_user_addr.is_aligned(mem::align_of::<T>())
+        && *raw_user_addr >= LayoutManager::USER_SPACE_BEGIN
+        && *raw_user_addr <= LayoutManager::USER_SPACE_END - mem::size_of::<T>()
+    }
+}
+
+/**
+ * Interface on which the `UserCopy` relies
+ */
+pub trait THwUserCopy {
+    /**
+     * Copies `size` bytes from `src_ptr` to `dst_ptr`, one of which is a
+     * userspace address.
+     *
+     * Returns `false` when an unresolvable page fault interrupts the copy
+     */
+    unsafe fn copy(dst_ptr: *mut u8, src_ptr: *const u8, size: usize) -> bool;
+
+    /**
+     * Returns the `VirtAddr` where the execution resumes when the given
+     * instruction, the faulting one of the copy, causes an unresolvable
+     * page fault
+     */
+    fn fixup_of(instr_virt_addr: VirtAddr) -> Option<VirtAddr>;
+}
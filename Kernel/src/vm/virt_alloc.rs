@@ -0,0 +1,253 @@
+/*! Virtual ranges allocator */
+
+use core::ops::Range;
+
+use sync::SpinMutex;
+
+use crate::{
+    addr::{
+        virt_addr::VirtAddr,
+        TAddress
+    },
+    dbg_print::DbgLevel,
+    dbg_println
+};
+
+/* maximum amount of non-contiguous free ranges tracked */
+const C_MAX_FREE_RANGES: usize = 512;
+
+/**
+ * First-fit allocator of virtual ranges.
+ *
+ * Keeps an address-ordered fixed capacity list of free ranges, coalesced
+ * when freed, so it never uses the heap and can serve the heap itself
+ */
+pub struct VirtRangesAllocator {
+    m_free_ranges: SpinMutex<FreeRanges>
+}
+
+impl VirtRangesAllocator /* Constructors */ {
+    /**
+     * Constructs a `VirtRangesAllocator` which manages the given `Range`
+     */
+    pub fn new(managed_range: Range<VirtAddr>) -> Self {
+        let mut free_ranges = FreeRanges::new();
+        free_ranges.insert_at(0, managed_range);
+
+        Self { m_free_ranges: SpinMutex::const_new(free_ranges) }
+    }
+}
+
+impl VirtRangesAllocator /* Methods */ {
+    /**
+     * Allocates a range of `size` bytes which start address is aligned to
+     * `align` bytes
+     */
+    pub fn allocate(&self, size: usize, align: usize) -> Option<Range<VirtAddr>> {
+        let mut free_ranges = self.m_free_ranges.lock();
+
+        for index in 0..free_ranges.m_count {
+            let free_range = free_ranges.range_at(index);
+
+            /* check whether the aligned request fits into the current free range */
+            let aligned_begin = free_range.start.align_up(align);
+            if *aligned_begin + size > *free_range.end {
+                continue;
+            }
+
+            /* split the free range in the head and the tail of the allocated range */
+            let allocated_range = aligned_begin.to_range(size);
+            if Self::carve_free_range(&mut free_ranges, index, &allocated_range) {
+                return Some(allocated_range);
+            }
+        }
+        None
+    }
+
+    /**
+     * Allocates exactly the given range, which must be entirely free.
+     *
+     * Returns `false` if some part of the range is already allocated or
+     * there is no more room for non-contiguous free ranges
+     */
+    pub fn allocate_at(&self, range_to_allocate: Range<VirtAddr>) -> bool {
+        let mut free_ranges = self.m_free_ranges.lock();
+
+        /* find the free range which contains the requested one */
+        let containing_index = (0..free_ranges.m_count).find(|&index| {
+                                   let free_range = free_ranges.range_at(index);
+
+                                   free_range.start <= range_to_allocate.start
+                                   && range_to_allocate.end <= free_range.end
+                               });
+        if let Some(index) = containing_index {
+            Self::carve_free_range(&mut free_ranges, index, &range_to_allocate)
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Gives back the given range, which must be obtained with
+     * `VirtRangesAllocator::allocate()` or
+     * `VirtRangesAllocator::allocate_at()`.
+     *
+     * Returns `false` if the range is leaked because there is no more room
+     * for non-contiguous free ranges
+     */
+    pub fn free(&self, range_to_free: Range<VirtAddr>) -> bool {
+        let mut free_ranges = self.m_free_ranges.lock();
+
+        /* find the first free range which follows the given one */
+        let next_index = (0..free_ranges.m_count).find(|&index| {
+                                                     free_ranges.range_at(index).start
+                                                     >= range_to_free.end
+                                                 })
+                                                 .unwrap_or(free_ranges.m_count);
+
+        /* an overlapping with the previous free range means double free */
+        let prev_range =
+            next_index.checked_sub(1).map(|index| free_ranges.range_at(index));
+        if let Some(prev_range) = &prev_range {
+            assert!(prev_range.end <= range_to_free.start,
+                    "Double free of virtual range {:?}",
+                    range_to_free);
+        }
+
+        /* coalesce with the neighbours when contiguous */
+        let merge_prev =
+            prev_range.map_or(false, |prev_range| prev_range.end == range_to_free.start);
+        let merge_next = next_index < free_ranges.m_count
+                         && free_ranges.range_at(next_index).start == range_to_free.end;
+        match (merge_prev, merge_next) {
+            (true, true) => {
+                let merged_range = free_ranges.range_at(next_index - 1).start
+                                   ..free_ranges.range_at(next_index).end;
+
+                free_ranges.set_range_at(next_index - 1, merged_range);
+                free_ranges.remove_at(next_index);
+            },
+            (true, false) => {
+                let merged_range =
+                    free_ranges.range_at(next_index - 1).start..range_to_free.end;
+
+                free_ranges.set_range_at(next_index - 1, merged_range);
+            },
+            (false, true) => {
+                let merged_range =
+                    range_to_free.start..free_ranges.range_at(next_index).end;
+
+                free_ranges.set_range_at(next_index, merged_range);
+            },
+            (false, false) => {
+                if free_ranges.is_full() {
+                    dbg_println!(DbgLevel::Warn,
+                                 "Leaked virtual range {:?}, too many free ranges",
+                                 range_to_free);
+                    return false;
+                }
+                free_ranges.insert_at(next_index, range_to_free);
+            }
+        }
+        true
+    }
+}
+
+impl VirtRangesAllocator /* Privates */ {
+    /**
+     * Removes the given `allocated_range` from the free range at the given
+     * index, keeping the head and the tail which remain free.
+     *
+     * Returns `false` if there is no room to remember the tail
+     */
+    fn carve_free_range(free_ranges: &mut FreeRanges,
+                        index: usize,
+                        allocated_range: &Range<VirtAddr>)
+                        -> bool {
+        let free_range = free_ranges.range_at(index);
+        let head_range = free_range.start..allocated_range.start;
+        let tail_range = allocated_range.end..free_range.end;
+        match (head_range.is_empty(), tail_range.is_empty()) {
+            (true, true) => free_ranges.remove_at(index),
+            (true, false) => free_ranges.set_range_at(index, tail_range),
+            (false, true) => free_ranges.set_range_at(index, head_range),
+            (false, false) => {
+                if free_ranges.is_full() {
+                    return false;
+                }
+                free_ranges.set_range_at(index, head_range);
+                free_ranges.insert_at(index + 1, tail_range);
+            }
+        }
+        true
+    }
+}
+
+/**
+ * Address ordered fixed capacity list of free `Range`s
+ */
+struct FreeRanges {
+    m_begins: [VirtAddr; C_MAX_FREE_RANGES],
+    m_ends: [VirtAddr; C_MAX_FREE_RANGES],
+    m_count: usize
+}
+
+impl FreeRanges /* Constructors */ {
+    /**
+     * Constructs an empty `FreeRanges`
+     */
+    fn new() -> Self {
+        Self { m_begins: [VirtAddr::null(); C_MAX_FREE_RANGES],
+               m_ends: [VirtAddr::null(); C_MAX_FREE_RANGES],
+               m_count: 0 }
+    }
+}
+
+impl FreeRanges /* Methods */ {
+    /**
+     * Inserts the given `Range` at the given index shifting the followings
+     */
+    fn insert_at(&mut self, index: usize, range: Range<VirtAddr>) {
+        assert!(!self.is_full());
+
+        self.m_begins.copy_within(index..self.m_count, index + 1);
+        self.m_ends.copy_within(index..self.m_count, index + 1);
+        self.m_count += 1;
+        self.set_range_at(index, range);
+    }
+
+    /**
+     * Removes the `Range` at the given index shifting the followings
+     */
+    fn remove_at(&mut self, index: usize) {
+        self.m_begins.copy_within(index + 1..self.m_count, index);
+        self.m_ends.copy_within(index + 1..self.m_count, index);
+        self.m_count -= 1;
+    }
+}
+
+impl FreeRanges /* Getters */ {
+    /**
+     * Returns the `Range` at the given index
+     */
+    fn range_at(&self, index: usize) -> Range<VirtAddr> {
+        self.m_begins[index]..self.m_ends[index]
+    }
+
+    /**
+     * Returns whether no more `Range`s can be inserted
+     */
+    fn is_full(&self) -> bool {
+        self.m_count == C_MAX_FREE_RANGES
+    }
+}
+
+impl FreeRanges /* Setters */ {
+    /**
+     * Overwrites the `Range` at the given index
+     */
+    fn set_range_at(&mut self, index: usize, range: Range<VirtAddr>) {
+        self.m_begins[index] = range.start;
+        self.m_ends[index] = range.end;
+    }
+}
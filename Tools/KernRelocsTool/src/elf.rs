@@ -0,0 +1,398 @@
+/*! Minimal ELF64 little-endian reader */
+
+use std::{
+    convert::TryInto,
+    ops::Range
+};
+
+/* section types */
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_RELA: u32 = 4;
+
+/* section flags */
+pub const SHF_ALLOC: u64 = 1 << 1;
+
+/* special section indexes */
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_LORESERVE: u16 = 0xff00;
+
+/* x86_64 relocation types */
+pub const R_X86_64_NONE: u32 = 0;
+pub const R_X86_64_64: u32 = 1;
+pub const R_X86_64_PC32: u32 = 2;
+pub const R_X86_64_PLT32: u32 = 4;
+pub const R_X86_64_32: u32 = 10;
+pub const R_X86_64_32S: u32 = 11;
+pub const R_X86_64_PC64: u32 = 24;
+pub const R_X86_64_SIZE32: u32 = 32;
+pub const R_X86_64_SIZE64: u32 = 33;
+
+/* sizes of the on-disk structures */
+const C_ELF_HEADER_SIZE: usize = 64;
+const C_SECTION_HEADER_SIZE: usize = 64;
+const C_SYMBOL_SIZE: usize = 24;
+const C_RELA_SIZE: usize = 24;
+
+/**
+ * ELF64 executable loaded in memory
+ */
+pub struct Elf64 {
+    m_data: Vec<u8>,
+    m_sections: Vec<Section>
+}
+
+impl Elf64 /* Constructors */ {
+    /**
+     * Parses the section headers of the given x86_64 ELF64 image
+     */
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < C_ELF_HEADER_SIZE || &data[..4] != b"\x7fELF" {
+            return Err(String::from("not an ELF file"));
+        }
+        if data[4] != 2 || data[5] != 1 {
+            return Err(String::from("not a little-endian ELF64 file"));
+        }
+
+        let section_headers_offset = read_u64(&data, 0x28)? as usize;
+        let section_header_size = read_u16(&data, 0x3a)? as usize;
+        let sections_count = read_u16(&data, 0x3c)? as usize;
+        let section_names_index = read_u16(&data, 0x3e)? as usize;
+        if section_header_size != C_SECTION_HEADER_SIZE {
+            return Err(format!("unexpected section header size {}",
+                               section_header_size));
+        }
+
+        /* read the raw headers, the names are resolved once all of them are
+         * known */
+        let mut sections = Vec::with_capacity(sections_count);
+        for section_index in 0..sections_count {
+            let header_offset =
+                section_headers_offset + section_index * C_SECTION_HEADER_SIZE;
+
+            sections.push(Section { m_name: String::new(),
+                                    m_name_offset: read_u32(&data, header_offset)?,
+                                    m_type: read_u32(&data, header_offset + 4)?,
+                                    m_flags: read_u64(&data, header_offset + 8)?,
+                                    m_addr: read_u64(&data, header_offset + 16)?,
+                                    m_offset: read_u64(&data, header_offset + 24)?,
+                                    m_size: read_u64(&data, header_offset + 32)?,
+                                    m_link: read_u32(&data, header_offset + 40)?,
+                                    m_info: read_u32(&data, header_offset + 44)? });
+        }
+
+        let section_names_range = sections.get(section_names_index)
+                                          .ok_or("missing section names table")?
+                                          .file_range();
+        for section in sections.iter_mut() {
+            section.m_name =
+                read_str(&data, &section_names_range, section.m_name_offset as usize)?;
+        }
+
+        Ok(Self { m_data: data,
+                  m_sections: sections })
+    }
+}
+
+impl Elf64 /* Methods */ {
+    /**
+     * Returns the `Section` with the given index
+     */
+    pub fn section(&self, section_index: usize) -> Result<&Section, String> {
+        self.m_sections
+            .get(section_index)
+            .ok_or_else(|| format!("invalid section index {}", section_index))
+    }
+
+    /**
+     * Returns the `Section` with the given name
+     */
+    pub fn section_by_name(&self, section_name: &str) -> Result<&Section, String> {
+        self.m_sections
+            .iter()
+            .find(|section| section.name() == section_name)
+            .ok_or_else(|| format!("missing {} section", section_name))
+    }
+
+    /**
+     * Returns the `Symbol`s of the static symbols table
+     */
+    pub fn symbols(&self) -> Result<Vec<Symbol>, String> {
+        let symtab =
+            self.m_sections
+                .iter()
+                .find(|section| section.m_type == SHT_SYMTAB)
+                .ok_or("missing symbols table, the kernel must not be stripped")?;
+        let strtab_range = self.section(symtab.m_link as usize)?.file_range();
+
+        self.entries_offsets(symtab, C_SYMBOL_SIZE)?
+            .map(|symbol_offset| {
+                let name_offset = read_u32(&self.m_data, symbol_offset)? as usize;
+
+                Ok(Symbol { m_name: read_str(&self.m_data,
+                                             &strtab_range,
+                                             name_offset)?,
+                            m_section_index: read_u16(&self.m_data,
+                                                      symbol_offset + 6)?,
+                            m_value: read_u64(&self.m_data, symbol_offset + 8)? })
+            })
+            .collect()
+    }
+
+    /**
+     * Returns the `Rela`s of the given `SHT_RELA` `Section`
+     */
+    pub fn relas(&self, rela_section: &Section) -> Result<Vec<Rela>, String> {
+        self.entries_offsets(rela_section, C_RELA_SIZE)?
+            .map(|rela_offset| {
+                let rela_info = read_u64(&self.m_data, rela_offset + 8)?;
+
+                Ok(Rela { m_offset: read_u64(&self.m_data, rela_offset)?,
+                          m_symbol_index: (rela_info >> 32) as u32,
+                          m_type: rela_info as u32 })
+            })
+            .collect()
+    }
+
+    /**
+     * Returns the `u64` stored at the given virtual address of the given
+     * `Section`
+     */
+    pub fn read_u64_at(&self, section: &Section, virt_addr: u64) -> Result<u64, String> {
+        read_u64(&self.m_data, self.file_offset_of(section, virt_addr, 8)?)
+    }
+
+    /**
+     * Returns the `u32` stored at the given virtual address of the given
+     * `Section`
+     */
+    pub fn read_u32_at(&self, section: &Section, virt_addr: u64) -> Result<u32, String> {
+        read_u32(&self.m_data, self.file_offset_of(section, virt_addr, 4)?)
+    }
+}
+
+impl Elf64 /* Getters */ {
+    /**
+     * Returns all the `Section`s
+     */
+    pub fn sections(&self) -> &[Section] {
+        &self.m_sections
+    }
+}
+
+impl Elf64 /* Privates */ {
+    /**
+     * Returns the file offsets of the fixed size entries of the given
+     * `Section`
+     */
+    fn entries_offsets(&self,
+                       section: &Section,
+                       entry_size: usize)
+                       -> Result<impl Iterator<Item = usize>, String> {
+        let file_range = section.file_range();
+        if file_range.end > self.m_data.len()
+           || !(section.m_size as usize).is_multiple_of(entry_size)
+        {
+            return Err(format!("malformed {} section", section.name()));
+        }
+        Ok(file_range.step_by(entry_size))
+    }
+
+    /**
+     * Returns the file offset of the `size` bytes at the given virtual
+     * address of the given `Section`
+     */
+    fn file_offset_of(&self,
+                      section: &Section,
+                      virt_addr: u64,
+                      size: u64)
+                      -> Result<usize, String> {
+        if virt_addr < section.m_addr
+           || virt_addr + size > section.m_addr + section.m_size
+        {
+            return Err(format!("{:#x} is out of the {} section",
+                               virt_addr,
+                               section.name()));
+        }
+        Ok((section.m_offset + (virt_addr - section.m_addr)) as usize)
+    }
+}
+
+/**
+ * ELF64 section header
+ */
+pub struct Section {
+    m_name: String,
+    m_name_offset: u32,
+    m_type: u32,
+    m_flags: u64,
+    m_addr: u64,
+    m_offset: u64,
+    m_size: u64,
+    m_link: u32,
+    m_info: u32
+}
+
+impl Section /* Getters */ {
+    /**
+     * Returns the name of the section
+     */
+    pub fn name(&self) -> &str {
+        &self.m_name
+    }
+
+    /**
+     * Returns the `SHT_*` type of the section
+     */
+    pub fn section_type(&self) -> u32 {
+        self.m_type
+    }
+
+    /**
+     * Returns whether the section occupies memory at runtime
+     */
+    pub fn is_alloc(&self) -> bool {
+        self.m_flags & SHF_ALLOC != 0
+    }
+
+    /**
+     * Returns the virtual address of the section
+     */
+    pub fn addr(&self) -> u64 {
+        self.m_addr
+    }
+
+    /**
+     * Returns the size of the section
+     */
+    pub fn size(&self) -> u64 {
+        self.m_size
+    }
+
+    /**
+     * Returns the index of the section which the relocations apply to, for
+     * the `SHT_RELA` sections
+     */
+    pub fn info(&self) -> u32 {
+        self.m_info
+    }
+}
+
+impl Section /* Privates */ {
+    /**
+     * Returns the range of the file occupied by the section
+     */
+    fn file_range(&self) -> Range<usize> {
+        self.m_offset as usize..(self.m_offset + self.m_size) as usize
+    }
+}
+
+/**
+ * ELF64 symbols table entry
+ */
+pub struct Symbol {
+    m_name: String,
+    m_section_index: u16,
+    m_value: u64
+}
+
+impl Symbol /* Getters */ {
+    /**
+     * Returns the name of the symbol
+     */
+    pub fn name(&self) -> &str {
+        &self.m_name
+    }
+
+    /**
+     * Returns the index of the section which defines the symbol, or one of
+     * the special `SHN_*` indexes
+     */
+    pub fn section_index(&self) -> u16 {
+        self.m_section_index
+    }
+
+    /**
+     * Returns the value of the symbol
+     */
+    pub fn value(&self) -> u64 {
+        self.m_value
+    }
+}
+
+/**
+ * ELF64 relocation entry with addend
+ */
+pub struct Rela {
+    m_offset: u64,
+    m_symbol_index: u32,
+    m_type: u32
+}
+
+impl Rela /* Getters */ {
+    /**
+     * Returns the virtual address patched by the relocation
+     */
+    pub fn offset(&self) -> u64 {
+        self.m_offset
+    }
+
+    /**
+     * Returns the index of the referenced symbol
+     */
+    pub fn symbol_index(&self) -> usize {
+        self.m_symbol_index as usize
+    }
+
+    /**
+     * Returns the `R_X86_64_*` type of the relocation
+     */
+    pub fn rela_type(&self) -> u32 {
+        self.m_type
+    }
+}
+
+/**
+ * Reads a little-endian `u16` at the given file offset
+ */
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    bytes_at(data, offset).map(u16::from_le_bytes)
+}
+
+/**
+ * Reads a little-endian `u32` at the given file offset
+ */
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    bytes_at(data, offset).map(u32::from_le_bytes)
+}
+
+/**
+ * Reads a little-endian `u64` at the given file offset
+ */
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, String> {
+    bytes_at(data, offset).map(u64::from_le_bytes)
+}
+
+/**
+ * Returns the `N` bytes at the given file offset
+ */
+fn bytes_at<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], String> {
+    data.get(offset..offset + N)
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or_else(|| format!("truncated file at offset {:#x}", offset))
+}
+
+/**
+ * Reads the null terminated string at the given offset of the given
+ * strings table
+ */
+fn read_str(data: &[u8],
+            strtab_range: &Range<usize>,
+            offset: usize)
+            -> Result<String, String> {
+    let strtab = data.get(strtab_range.clone()).ok_or("truncated strings table")?;
+    let bytes = strtab.get(offset..).ok_or("invalid string offset")?;
+    let str_len =
+        bytes.iter().position(|byte| *byte == 0).ok_or("unterminated string")?;
+
+    Ok(String::from_utf8_lossy(&bytes[..str_len]).into_owned())
+}
@@ -0,0 +1,224 @@
+/*! # Kernel Relocations Host Tool
+ *
+ * Extracts from the Kernel executable, linked with `--emit-relocs`, the
+ * table of the absolute references which the boot code patches to slide
+ * the Kernel image:
+ *
+ * ```text
+ * kern_relocs <kernel> <output>
+ * ```
+ *
+ * The output fills exactly the `.kern_relocs` section, which is then
+ * replaced with `objcopy --update-section`. It contains the magic, the
+ * offsets from `KERNEL_VIRT_BASE` of the 64bit sites terminated by a zero,
+ * then the offsets of the 32bit sign extended sites terminated by a zero
+ */
+
+use std::{
+    env,
+    fs,
+    process
+};
+
+use crate::elf::{
+    Elf64,
+    Section,
+    Symbol,
+    R_X86_64_32,
+    R_X86_64_32S,
+    R_X86_64_64,
+    R_X86_64_NONE,
+    R_X86_64_PC32,
+    R_X86_64_PC64,
+    R_X86_64_PLT32,
+    R_X86_64_SIZE32,
+    R_X86_64_SIZE64,
+    SHN_LORESERVE,
+    SHN_UNDEF,
+    SHT_RELA
+};
+
+mod elf;
+
+/* first word of the table, keep in sync with the Kernel's
+ * <C_KERN_RELOCS_MAGIC> */
+const C_KERN_RELOCS_MAGIC: u32 = 0x4c45_524b;
+
+/**
+ * Sites to patch, as offsets from `KERNEL_VIRT_BASE`
+ */
+struct KernRelocs {
+    m_relocs_64: Vec<u32>,
+    m_relocs_32s: Vec<u32>
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.as_slice() {
+        [kernel_path, output_path] => write_relocs(kernel_path, output_path),
+        _ => Err(String::from("usage: kern_relocs <kernel> <output>"))
+    };
+
+    if let Err(message) = result {
+        eprintln!("kern_relocs: {}", message);
+        process::exit(1);
+    }
+}
+
+/**
+ * Writes the relocations table of the given Kernel executable
+ */
+fn write_relocs(kernel_path: &str, output_path: &str) -> Result<(), String> {
+    let kernel_data =
+        fs::read(kernel_path).map_err(|error| format!("{}: {}", kernel_path, error))?;
+    let elf =
+        Elf64::parse(kernel_data).map_err(|error| format!("{}: {}", kernel_path, error))?;
+
+    let kern_relocs = collect_relocs(&elf)?;
+    let relocs_storage = elf.section_by_name(".kern_relocs")?;
+
+    /* the magic, the two lists and their terminators */
+    let relocs_size =
+        (kern_relocs.m_relocs_64.len() + kern_relocs.m_relocs_32s.len() + 3) * 4;
+    if relocs_size as u64 > relocs_storage.size() {
+        return Err(format!("the relocations need {} bytes but .kern_relocs reserves \
+                            {}, increase C_KERN_RELOCS_STORAGE_SIZE",
+                           relocs_size,
+                           relocs_storage.size()));
+    }
+
+    /* fill exactly the reserved section, so the sections after it don't move */
+    let mut output_data = Vec::with_capacity(relocs_storage.size() as usize);
+    output_data.extend_from_slice(&C_KERN_RELOCS_MAGIC.to_le_bytes());
+    for relocs in [&kern_relocs.m_relocs_64, &kern_relocs.m_relocs_32s] {
+        for reloc_offset in relocs.iter().chain([0].iter()) {
+            output_data.extend_from_slice(&reloc_offset.to_le_bytes());
+        }
+    }
+    output_data.resize(relocs_storage.size() as usize, 0);
+
+    fs::write(output_path, &output_data).map_err(|error| {
+                                            format!("{}: {}", output_path, error)
+                                        })?;
+    println!("{}: {} 64bit and {} 32bit relocations",
+             kernel_path,
+             kern_relocs.m_relocs_64.len(),
+             kern_relocs.m_relocs_32s.len());
+    Ok(())
+}
+
+/**
+ * Collects the absolute references of the slid sections to the Kernel
+ * image, failing on the references which the slide would break
+ */
+fn collect_relocs(elf: &Elf64) -> Result<KernRelocs, String> {
+    let symbols = elf.symbols()?;
+    let virt_base = symbol_value(&symbols, "KERNEL_VIRT_BASE")?;
+    let image_range = symbol_value(&symbols, "__kernel_text_begin")?
+                      ..=symbol_value(&symbols, "__kernel_text_end")?;
+
+    let mut kern_relocs = KernRelocs { m_relocs_64: Vec::new(),
+                                       m_relocs_32s: Vec::new() };
+    for rela_section in
+        elf.sections().iter().filter(|section| section.section_type() == SHT_RELA)
+    {
+        /* only the sections mapped into the higher half are slid */
+        let site_section = elf.section(rela_section.info() as usize)?;
+        if !is_slid(site_section, virt_base) {
+            continue;
+        }
+
+        for rela in elf.relas(rela_section)?.iter() {
+            let symbol = symbols.get(rela.symbol_index()).ok_or_else(|| {
+                                                              format!("invalid symbol \
+                                                                       index {} at {:#x}",
+                                                                      rela.symbol_index(),
+                                                                      rela.offset())
+                                                          })?;
+            let targets_slid = match symbol.section_index() {
+                SHN_UNDEF => false,
+                section_index if section_index >= SHN_LORESERVE => false,
+                section_index => is_slid(elf.section(section_index as usize)?, virt_base)
+            };
+
+            match rela.rela_type() {
+                /* the 64bit absolute addresses, like the vtables and the jump tables */
+                R_X86_64_64 => {
+                    let value = elf.read_u64_at(site_section, rela.offset())?;
+                    if targets_slid && image_range.contains(&value) {
+                        kern_relocs.m_relocs_64
+                                   .push(site_offset(rela.offset(), virt_base)?);
+                    }
+                },
+                /* the 32bit sign extended addresses of the kernel code model */
+                R_X86_64_32S => {
+                    let value =
+                        elf.read_u32_at(site_section, rela.offset())? as i32 as u64;
+                    if targets_slid && image_range.contains(&value) {
+                        kern_relocs.m_relocs_32s
+                                   .push(site_offset(rela.offset(), virt_base)?);
+                    }
+                },
+                /* the relative references move with the image, unless they leave it */
+                R_X86_64_PC32 | R_X86_64_PLT32 | R_X86_64_PC64 => {
+                    if !targets_slid {
+                        return Err(format!("relative reference to {} at {:#x} from \
+                                            the {} section, which is slid",
+                                           symbol.name(),
+                                           rela.offset(),
+                                           site_section.name()));
+                    }
+                },
+                /* the 32bit zero extended values are never addresses of the higher
+                 * half, but physical addresses which don't change
+                 */
+                R_X86_64_NONE | R_X86_64_32 | R_X86_64_SIZE32 | R_X86_64_SIZE64 => {},
+                rela_type => {
+                    return Err(format!("unsupported relocation type {} to {} at \
+                                        {:#x}, the kernel must use the static \
+                                        relocation model",
+                                       rela_type,
+                                       symbol.name(),
+                                       rela.offset()));
+                }
+            }
+        }
+    }
+
+    /* sorted offsets patch the image sequentially */
+    for relocs in [&mut kern_relocs.m_relocs_64, &mut kern_relocs.m_relocs_32s] {
+        relocs.sort_unstable();
+        relocs.dedup();
+    }
+    Ok(kern_relocs)
+}
+
+/**
+ * Returns whether the given `Section` is mapped into the slid higher half
+ */
+fn is_slid(section: &Section, virt_base: u64) -> bool {
+    section.is_alloc() && section.addr() >= virt_base
+}
+
+/**
+ * Returns the offset of the given site from `KERNEL_VIRT_BASE`
+ */
+fn site_offset(site_addr: u64, virt_base: u64) -> Result<u32, String> {
+    match site_addr.checked_sub(virt_base) {
+        Some(site_offset) if site_offset > 0 && site_offset <= u32::MAX as u64 => {
+            Ok(site_offset as u32)
+        },
+        _ => Err(format!("relocation site {:#x} out of the kernel window", site_addr))
+    }
+}
+
+/**
+ * Returns the value of the symbol with the given name
+ */
+fn symbol_value(symbols: &[Symbol], symbol_name: &str) -> Result<u64, String> {
+    symbols.iter()
+           .find(|symbol| symbol.name() == symbol_name)
+           .map(Symbol::value)
+           .ok_or_else(|| format!("missing {} symbol", symbol_name))
+}
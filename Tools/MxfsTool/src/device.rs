@@ -0,0 +1,85 @@
+/*! Image file device */
+
+use std::{
+    fs::{
+        File,
+        OpenOptions
+    },
+    io,
+    os::unix::fs::FileExt,
+    path::Path
+};
+
+use mxfs::{
+    device::TMxfsDevice,
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * `TMxfsDevice` backed by an image file
+ */
+pub struct FileDevice {
+    m_file: File,
+    m_size: u64,
+    m_is_read_only: bool
+}
+
+impl FileDevice /* Constructors */ {
+    /**
+     * Opens the given existing image
+     */
+    pub fn open(path: &Path, is_read_only: bool) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(!is_read_only).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self { m_file: file,
+                  m_size: size,
+                  m_is_read_only: is_read_only })
+    }
+
+    /**
+     * Creates the given image with the given size, replacing the existing
+     * one
+     */
+    pub fn create(path: &Path, size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true)
+                                     .write(true)
+                                     .create(true)
+                                     .truncate(true)
+                                     .open(path)?;
+        file.set_len(size)?;
+
+        Ok(Self { m_file: file,
+                  m_size: size,
+                  m_is_read_only: false })
+    }
+}
+
+impl TMxfsDevice for FileDevice {
+    fn size(&self) -> u64 {
+        self.m_size
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> MxfsResult<()> {
+        self.m_file.read_exact_at(buffer, offset).map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> MxfsResult<()> {
+        if self.m_is_read_only {
+            return Err(MxfsError::ReadOnly);
+        }
+        self.m_file.write_all_at(buffer, offset).map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn flush(&self) -> MxfsResult<()> {
+        if self.m_is_read_only {
+            return Ok(());
+        }
+        self.m_file.sync_data().map_err(|_| MxfsError::DeviceError)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.m_is_read_only
+    }
+}
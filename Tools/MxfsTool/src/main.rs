@@ -0,0 +1,383 @@
+/*! # MXFS Host Tool
+ *
+ * Builds, checks and inspects MXFS images from the host:
+ *
+ * ```text
+ * mxfs mkfs <image> [--size <N[K|M|G]>] [--block-size <N>] [--compression <none|lz4>]
+ *                   [--inodes <N>] [--journal-blocks <N>] [--label <L>] [--from <dir>]
+ * mxfs fsck <image>
+ * mxfs ls <image> [path]
+ * mxfs cat <image> <path>
+ * ```
+ */
+
+use std::{
+    env,
+    fs,
+    io::{
+        self,
+        Read,
+        Write
+    },
+    os::unix::fs::MetadataExt,
+    path::Path,
+    process,
+    time::{
+        SystemTime,
+        UNIX_EPOCH
+    }
+};
+
+use mxfs::{
+    check::check,
+    compress::Compression,
+    format::{
+        format,
+        FormatOptions
+    },
+    inode::{
+        InodeId,
+        InodeType
+    },
+    volume::Volume
+};
+
+use crate::device::FileDevice;
+
+mod device;
+
+/* size of the buffer used to copy the files */
+const C_COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("mkfs") => mkfs(&args[1..]),
+        Some("fsck") => fsck(&args[1..]),
+        Some("ls") => ls(&args[1..]),
+        Some("cat") => cat(&args[1..]),
+        _ => Err(String::from("usage: mxfs <mkfs|fsck|ls|cat> <image> [args...]"))
+    };
+
+    if let Err(message) = result {
+        eprintln!("mxfs: {}", message);
+        process::exit(1);
+    }
+}
+
+/**
+ * Formats the image, optionally filling it with the content of a host
+ * directory
+ */
+fn mkfs(args: &[String]) -> Result<(), String> {
+    let image_path = args.first().ok_or("mkfs: missing image path")?;
+
+    let mut options = FormatOptions::new();
+    options.with_creat_time(now());
+
+    let mut image_size = None;
+    let mut from_dir = None;
+    let mut arg_index = 1;
+    while arg_index < args.len() {
+        let value =
+            args.get(arg_index + 1)
+                .ok_or_else(|| format!("mkfs: missing value for {}", args[arg_index]))?;
+
+        match args[arg_index].as_str() {
+            "--size" => image_size = Some(parse_size(value)?),
+            "--block-size" => {
+                options.with_block_size(parse_size(value)?);
+            },
+            "--compression" => {
+                let compression = Compression::from_name(value).ok_or_else(|| {
+                                      format!("mkfs: unknown compression {}", value)
+                                  })?;
+                options.with_compression(compression);
+            },
+            "--inodes" => {
+                options.with_inodes(parse_size(value)?);
+            },
+            "--journal-blocks" => {
+                options.with_journal_blocks(parse_size(value)?);
+            },
+            "--label" => {
+                options.with_label(value);
+            },
+            "--from" => from_dir = Some(value.clone()),
+            option => return Err(format!("mkfs: unknown option {}", option))
+        }
+        arg_index += 2;
+    }
+
+    let image_path = Path::new(image_path);
+    let device = match image_size {
+                     Some(image_size) => FileDevice::create(image_path, image_size),
+                     None => FileDevice::open(image_path, false)
+                 }.map_err(|error| format!("{}: {}", image_path.display(), error))?;
+
+    let super_block =
+        format(&device, &options).map_err(|error| format!("mkfs: {}", error))?;
+    println!("formatted {}: {} blocks of {} bytes, {} inodes, {} compression",
+             image_path.display(),
+             super_block.blocks_count(),
+             super_block.block_size(),
+             super_block.inodes_count(),
+             super_block.compression().name());
+
+    if let Some(from_dir) = from_dir {
+        let mut volume =
+            Volume::mount(device, now).map_err(|error| format!("mount: {}", error))?;
+        let root = volume.root();
+
+        copy_dir(&mut volume, Path::new(&from_dir), root)?;
+        volume.sync().map_err(|error| format!("sync: {}", error))?;
+        println!("copied {}, {} blocks free", from_dir, volume.free_blocks());
+    }
+    Ok(())
+}
+
+/**
+ * Checks the image, failing when it is damaged
+ */
+fn fsck(args: &[String]) -> Result<(), String> {
+    let image_path = args.first().ok_or("fsck: missing image path")?;
+    let device = FileDevice::open(Path::new(image_path), true).map_err(|error| {
+                                                                  format!("{}: {}",
+                                                                          image_path,
+                                                                          error)
+                                                              })?;
+
+    let report = check(&device).map_err(|error| format!("fsck: {}", error))?;
+    for warning in report.warnings() {
+        println!("warning: {}", warning);
+    }
+    for error in report.errors() {
+        println!("error: {}", error);
+    }
+
+    let super_block = report.super_block();
+    println!("{}: version {}.{}, label {:?}",
+             image_path,
+             super_block.version() / 10,
+             super_block.version() % 10,
+             super_block.label());
+    println!("{} files, {} directories, {} links, {} blocks used, {} blocks free",
+             report.files_count(),
+             report.dirs_count(),
+             report.links_count(),
+             report.used_blocks(),
+             report.free_blocks());
+
+    if !report.is_clean() {
+        return Err(format!("{} errors found", report.errors().len()));
+    }
+    Ok(())
+}
+
+/**
+ * Lists the content of a directory of the image
+ */
+fn ls(args: &[String]) -> Result<(), String> {
+    let image_path = args.first().ok_or("ls: missing image path")?;
+    let path = args.get(1).map_or("/", String::as_str);
+
+    let mut volume = mount_read_only(image_path)?;
+    let dir_id = resolve(&mut volume, path)?;
+
+    let mut dir_records = Vec::new();
+    volume.for_each_entry(dir_id, &mut |dir_record| {
+              dir_records.push(dir_record);
+              Ok(true)
+          })
+          .map_err(|error| format!("{}: {}", path, error))?;
+    dir_records.sort_by(|record, other| record.name().cmp(other.name()));
+
+    for dir_record in dir_records.iter() {
+        let inode = volume.inode(dir_record.inode()).map_err(|error| {
+                                                         format!("{}: {}",
+                                                                 dir_record.name(),
+                                                                 error)
+                                                     })?;
+
+        match inode.inode_type() {
+            InodeType::Dir => println!("d {:>12} {}/", inode.size(), dir_record.name()),
+            InodeType::Link => {
+                let target =
+                    volume.link_target(dir_record.inode()).map_err(|error| {
+                                                               format!("{}: {}",
+                                                                       dir_record.name(),
+                                                                       error)
+                                                           })?;
+                println!("l {:>12} {} -> {}", inode.size(), dir_record.name(), target)
+            },
+            _ => println!("- {:>12} {}", inode.size(), dir_record.name())
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Writes the content of a file of the image to the standard output
+ */
+fn cat(args: &[String]) -> Result<(), String> {
+    let image_path = args.first().ok_or("cat: missing image path")?;
+    let path = args.get(1).ok_or("cat: missing file path")?;
+
+    let mut volume = mount_read_only(image_path)?;
+    let inode_id = resolve(&mut volume, path)?;
+
+    let mut buffer = vec![0; C_COPY_BUFFER_SIZE];
+    let mut offset = 0;
+    let mut stdout = io::stdout();
+    loop {
+        let read_len = volume.read_at(inode_id, offset, &mut buffer)
+                             .map_err(|error| format!("{}: {}", path, error))?;
+        if read_len == 0 {
+            return Ok(());
+        }
+
+        stdout.write_all(&buffer[..read_len]).map_err(|error| error.to_string())?;
+        offset += read_len as u64;
+    }
+}
+
+/**
+ * Copies the content of the given host directory into the given directory
+ * of the volume
+ */
+fn copy_dir(volume: &mut Volume<FileDevice>,
+            host_dir: &Path,
+            dir_id: InodeId)
+            -> Result<(), String> {
+    let read_dir = fs::read_dir(host_dir).map_err(|error| {
+                                             format!("{}: {}", host_dir.display(), error)
+                                         })?;
+
+    for dir_entry in read_dir {
+        let dir_entry =
+            dir_entry.map_err(|error| format!("{}: {}", host_dir.display(), error))?;
+        let host_path = dir_entry.path();
+        let name = dir_entry.file_name()
+                            .into_string()
+                            .map_err(|_| format!("{}: not UTF-8", host_path.display()))?;
+        let metadata = fs::symlink_metadata(&host_path).map_err(|error| {
+                                                           format!("{}: {}",
+                                                                   host_path.display(),
+                                                                   error)
+                                                       })?;
+        let to_volume_error = |error| format!("{}: {}", host_path.display(), error);
+
+        let inode_id = if metadata.file_type().is_symlink() {
+            let target =
+                fs::read_link(&host_path).map_err(|error| {
+                                             format!("{}: {}", host_path.display(), error)
+                                         })?;
+            let target =
+                target.to_str()
+                      .ok_or_else(|| format!("{}: not UTF-8", host_path.display()))?;
+
+            volume.create_link(dir_id, &name, target).map_err(to_volume_error)?
+        } else if metadata.is_dir() {
+            let inode_id =
+                volume.create(dir_id, &name, InodeType::Dir).map_err(to_volume_error)?;
+            copy_dir(volume, &host_path, inode_id)?;
+            inode_id
+        } else if metadata.is_file() {
+            let inode_id =
+                volume.create(dir_id, &name, InodeType::File).map_err(to_volume_error)?;
+            copy_file(volume, &host_path, inode_id)?;
+            inode_id
+        } else {
+            eprintln!("mxfs: {}: skipped special file", host_path.display());
+            continue;
+        };
+
+        volume.set_owner(inode_id, metadata.uid() as u64, metadata.gid() as u64)
+              .map_err(to_volume_error)?;
+        volume.set_prot_grants(inode_id, metadata.mode() as u64 & 0o7777)
+              .map_err(to_volume_error)?;
+    }
+    Ok(())
+}
+
+/**
+ * Copies the content of the given host file into the given file of the
+ * volume
+ */
+fn copy_file(volume: &mut Volume<FileDevice>,
+             host_path: &Path,
+             inode_id: InodeId)
+             -> Result<(), String> {
+    let mut host_file =
+        fs::File::open(host_path).map_err(|error| {
+                                     format!("{}: {}", host_path.display(), error)
+                                 })?;
+
+    let mut buffer = vec![0; C_COPY_BUFFER_SIZE];
+    let mut offset = 0;
+    loop {
+        let read_len =
+            host_file.read(&mut buffer)
+                     .map_err(|error| format!("{}: {}", host_path.display(), error))?;
+        if read_len == 0 {
+            return Ok(());
+        }
+
+        volume.write_at(inode_id, offset, &buffer[..read_len])
+              .map_err(|error| format!("{}: {}", host_path.display(), error))?;
+        offset += read_len as u64;
+    }
+}
+
+/**
+ * Mounts the given image without modifying it
+ */
+fn mount_read_only(image_path: &str) -> Result<Volume<FileDevice>, String> {
+    let device = FileDevice::open(Path::new(image_path), true).map_err(|error| {
+                                                                  format!("{}: {}",
+                                                                          image_path,
+                                                                          error)
+                                                              })?;
+
+    Volume::mount(device, now).map_err(|error| format!("{}: {}", image_path, error))
+}
+
+/**
+ * Returns the inode of the given absolute path, without following the
+ * symbolic links
+ */
+fn resolve(volume: &mut Volume<FileDevice>, path: &str) -> Result<InodeId, String> {
+    let mut inode_id = volume.root();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        inode_id = volume.lookup(inode_id, name)
+                         .map_err(|error| format!("{}: {}", path, error))?
+                         .inode();
+    }
+    Ok(inode_id)
+}
+
+/**
+ * Parses the given size, which accepts the K, M and G suffixes
+ */
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match value.chars().last() {
+        Some('K') | Some('k') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') | Some('m') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') | Some('g') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1)
+    };
+
+    digits.parse::<u64>()
+          .ok()
+          .and_then(|number| number.checked_mul(multiplier))
+          .ok_or_else(|| format!("invalid size {}", value))
+}
+
+/**
+ * Returns the current time in nanoseconds since the Unix epoch
+ */
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+                     .map_or(0, |duration| duration.as_nanos() as u64)
+}
@@ -100,7 +100,14 @@ pub enum OsErrorClass {
      * The previous system call was failed because the running transaction
      * was interrupted by something else
      */
-    InterruptedOperation
+    InterruptedOperation,
+
+    /**
+     * The `Thread` was terminated because it caused a CPU exception which
+     * the Kernel can't resolve (i.e an access to an unmapped address or an
+     * invalid opcode)
+     */
+    UnrecoverableFault
 }
 
 impl Default for OsErrorClass {
@@ -125,7 +132,8 @@ impl fmt::Display for OsErrorClass {
             Self::NoDataAvailable => write!(f, "Data not available"),
             Self::OperationNotEnabled => write!(f, "Operation not enabled"),
             Self::EndOfDataReached => write!(f, "End of data reached"),
-            Self::InterruptedOperation => write!(f, "Interrupted operation")
+            Self::InterruptedOperation => write!(f, "Interrupted operation"),
+            Self::UnrecoverableFault => write!(f, "Unrecoverable fault")
         }
     }
 }
@@ -1,6 +1,10 @@
 /*! Kernel call error management */
 
-use core::fmt;
+use core::{
+    convert::TryFrom,
+    fmt,
+    ptr
+};
 
 use helps::str::{
     copy_str_to_u8_buf,
@@ -23,6 +27,7 @@ pub mod class;
 /**
  * Operating system error in MeetiX
  */
+#[repr(C)]
 #[derive(Debug)]
 #[derive(Default)]
 #[derive(Copy, Clone)]
@@ -57,6 +62,26 @@ impl OsError /* Constructors */ {
                                      buffer
                                  }) }
     }
+
+    /**
+     * Reads the `OsErrorClass` and the `KernFnPath` of the `OsError` at
+     * the given pointer, validating them.
+     *
+     * The pointer must be aligned and its memory readable. The other
+     * fields are not read, since only the Kernel fills them, while an
+     * unknown `KernFnPath` becomes `KernFnPath::Invalid`. Returns `None` if
+     * the class is not valid
+     */
+    pub unsafe fn read_from_raw(raw_os_error_ptr: *const Self) -> Option<Self> {
+        let raw_class_ptr = ptr::addr_of!((*raw_os_error_ptr).m_class) as *const u8;
+        let raw_fn_path_ptr =
+            ptr::addr_of!((*raw_os_error_ptr).m_kern_fn_path) as *const u16;
+
+        let class = OsErrorClass::try_from(raw_class_ptr.read_volatile()).ok()?;
+        let kern_fn_path = KernFnPath::from_raw(raw_fn_path_ptr.read_volatile(),
+                                                raw_fn_path_ptr.add(1).read_volatile());
+        Some(Self::new(class, kern_fn_path.unwrap_or_default(), None, 0, 0, None))
+    }
 }
 
 impl OsError /* Getters */ {
@@ -58,6 +58,8 @@ impl TAsSysCallPtr for PathExistsState {
  */
 #[derive(Debug)]
 #[derive(Clone)]
+#[derive(PartialEq, Eq)]
+#[derive(PartialOrd, Ord)]
 pub enum PathComponent {
     Root,
     SelfLink,
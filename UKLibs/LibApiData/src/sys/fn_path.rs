@@ -1,6 +1,9 @@
 /*! Kernel function call paths */
 
-use core::fmt;
+use core::{
+    convert::TryFrom,
+    fmt
+};
 
 use crate::sys::codes::{
     KernDeviceFnId,
@@ -31,8 +34,13 @@ use crate::sys::codes::{
  * Each variant represent a Kernel call class, which is the primary key
  * of the Kernel's routines table, and each class contains the specific
  * codes for the call class, which is the secondary key of the Kernel's
- * routines table
+ * routines table.
+ *
+ * The layout is fixed to a `u16` class, which is the `raw_fn_class()`,
+ * followed by the `u16` function id, so the Kernel can validate the paths
+ * which it reads from the userspace memory
  */
+#[repr(C, u16)]
 #[derive(Debug)]
 #[derive(Copy, Clone)]
 pub enum KernFnPath {
@@ -59,11 +67,52 @@ pub enum KernFnPath {
     Invalid
 }
 
+impl KernFnPath /* Constructors */ {
+    /**
+     * Number of the raw classes accepted by `KernFnPath::from_raw()`,
+     * `KernFnPath::Invalid` excluded
+     */
+    pub const CLASSES_COUNT: usize =
+        Self::Thread(KernThreadFnId::Join).raw_fn_class() as usize + 1;
+
+    /**
+     * Constructs a `KernFnPath` from the given raw class and function id.
+     *
+     * Returns `None` if the class or the function id are not valid
+     */
+    pub fn from_raw(raw_fn_class: u16, raw_fn_id: u16) -> Option<Self> {
+        let kern_fn_path = match raw_fn_class {
+            0 => Self::KernHandle(KernHandleFnId::try_from(raw_fn_id).ok()?),
+            1 => Self::ObjConfig(KernObjConfigFnId::try_from(raw_fn_id).ok()?),
+            2 => Self::TaskConfig(KernTaskConfigFnId::try_from(raw_fn_id).ok()?),
+            3 => Self::OsEntConfig(KernOsEntConfigFnId::try_from(raw_fn_id).ok()?),
+            4 => Self::Object(KernObjectFnId::try_from(raw_fn_id).ok()?),
+            5 => Self::Task(KernTaskFnId::try_from(raw_fn_id).ok()?),
+            6 => Self::Device(KernDeviceFnId::try_from(raw_fn_id).ok()?),
+            7 => Self::Dir(KernDirFnId::try_from(raw_fn_id).ok()?),
+            8 => Self::File(KernFileFnId::try_from(raw_fn_id).ok()?),
+            9 => Self::IpcChan(KernIpcChanFnId::try_from(raw_fn_id).ok()?),
+            10 => Self::Link(KernLinkFnId::try_from(raw_fn_id).ok()?),
+            11 => Self::MMap(KernMMapFnId::try_from(raw_fn_id).ok()?),
+            12 => Self::Mutex(KernMutexFnId::try_from(raw_fn_id).ok()?),
+            13 => Self::Instant(KernInstantFnId::try_from(raw_fn_id).ok()?),
+            14 => Self::Path(KernPathFnId::try_from(raw_fn_id).ok()?),
+            15 => Self::OsEntity(KernOsEntFnId::try_from(raw_fn_id).ok()?),
+            16 => Self::OsUser(KernOsUserFnId::try_from(raw_fn_id).ok()?),
+            17 => Self::OsGroup(KernOsGroupFnId::try_from(raw_fn_id).ok()?),
+            18 => Self::Proc(KernProcFnId::try_from(raw_fn_id).ok()?),
+            19 => Self::Thread(KernThreadFnId::try_from(raw_fn_id).ok()?),
+            _ => return None
+        };
+        Some(kern_fn_path)
+    }
+}
+
 impl KernFnPath /* Methods */ {
     /**
      * Returns the current function class variant as `u16`
      */
-    pub fn raw_fn_class(&self) -> u16 {
+    pub const fn raw_fn_class(&self) -> u16 {
         match self {
             Self::KernHandle(_) => 0,
             Self::ObjConfig(_) => 1,
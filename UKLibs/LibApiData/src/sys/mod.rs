@@ -1,5 +1,12 @@
 /*! System call codes and classes */
 
+use core::{
+    mem,
+    mem::MaybeUninit,
+    ops::Range,
+    ptr
+};
+
 use crate::{
     error::OsError,
     limit::SYSCALL_ARGS_COUNT_MAX,
@@ -20,12 +27,16 @@ pub const INVALID_KERN_HANDLE: RawKernHandle = RawKernHandle::MAX;
 pub type RawKernHandle = u32;
 
 /**
- * Fixed collector of system call arguments
+ * Fixed collector of system call arguments.
+ *
+ * The layout is fixed since the Kernel reads and writes it directly into
+ * the userspace memory
  */
+#[repr(C)]
 #[derive(Debug)]
 pub struct SysCallPayload {
     m_kern_fn_path: KernFnPath,
-    m_raw_handle: Option<RawKernHandle>, /* TODO tables with inst_required = <> */
+    m_raw_handle: RawKernHandle, /* TODO tables with inst_required = <> */
     m_raw_args: [usize; SYSCALL_ARGS_COUNT_MAX],
     m_error_modified: bool,
     m_error: OsError,
@@ -47,12 +58,67 @@ impl SysCallPayload /* Constructors */ {
                arg5: usize)
                -> Self {
         Self { m_kern_fn_path: kern_fn_path,
-               m_raw_handle: raw_handle,
+               m_raw_handle: raw_handle.unwrap_or(INVALID_KERN_HANDLE),
                m_raw_args: [arg0, arg1, arg2, arg3, arg4, arg5],
                m_error_modified: false,
                m_error: OsError::default(),
                m_result: 0 }
     }
+
+    /**
+     * Reads the input fields of the `SysCallPayload` at the given pointer,
+     * validating its `KernFnPath`.
+     *
+     * The pointer must be aligned and its memory readable, while the output
+     * fields are not read, so they could contain anything. Returns `None`
+     * if the `KernFnPath` is not valid
+     */
+    pub unsafe fn read_from_raw(raw_payload_ptr: *const Self) -> Option<Self> {
+        let raw_fn_path_ptr =
+            ptr::addr_of!((*raw_payload_ptr).m_kern_fn_path) as *const u16;
+        let kern_fn_path = KernFnPath::from_raw(raw_fn_path_ptr.read_volatile(),
+                                                raw_fn_path_ptr.add(1).read_volatile())?;
+
+        Some(Self { m_kern_fn_path: kern_fn_path,
+                    m_raw_handle:
+                        ptr::addr_of!((*raw_payload_ptr).m_raw_handle).read_volatile(),
+                    m_raw_args:
+                        ptr::addr_of!((*raw_payload_ptr).m_raw_args).read_volatile(),
+                    m_error_modified: false,
+                    m_error: OsError::default(),
+                    m_result: 0 })
+    }
+}
+
+impl SysCallPayload /* Methods */ {
+    /**
+     * Writes the output fields of this `SysCallPayload` to the one at the
+     * given pointer, leaving untouched its input fields.
+     *
+     * The pointer must be aligned and its memory writeable
+     */
+    pub unsafe fn write_output_to_raw(&self, raw_payload_ptr: *mut Self) {
+        ptr::addr_of_mut!((*raw_payload_ptr).m_error_modified)
+            .write_volatile(self.m_error_modified);
+        ptr::addr_of_mut!((*raw_payload_ptr).m_error).write_volatile(self.m_error);
+        ptr::addr_of_mut!((*raw_payload_ptr).m_result).write_volatile(self.m_result);
+    }
+}
+
+impl SysCallPayload /* Static Functions */ {
+    /**
+     * Returns the `Range` of the bytes of the output fields, which are the
+     * last ones of the layout
+     */
+    pub fn output_bytes_range() -> Range<usize> {
+        let raw_payload = MaybeUninit::<Self>::uninit();
+        let raw_payload_ptr = raw_payload.as_ptr();
+
+        let output_begin = unsafe { ptr::addr_of!((*raw_payload_ptr).m_error_modified) }
+                           as usize
+                           - raw_payload_ptr as usize;
+        output_begin..mem::size_of::<Self>()
+    }
 }
 
 impl SysCallPayload /* Getters */ {
@@ -69,7 +135,11 @@ impl SysCallPayload /* Getters */ {
      */
     #[inline]
     pub fn raw_handle(&self) -> Option<RawKernHandle> {
-        self.m_raw_handle
+        if self.m_raw_handle != INVALID_KERN_HANDLE {
+            Some(self.m_raw_handle)
+        } else {
+            None
+        }
     }
 
     /**
@@ -127,6 +197,22 @@ impl SysCallPayload /* Getters */ {
     }
 }
 
+impl SysCallPayload /* Setters */ {
+    /**
+     * Sets the value returned by the kernel function
+     */
+    #[inline]
+    pub fn set_result(&mut self, result: usize) {
+        self.m_result = result;
+    }
+}
+
+impl Default for SysCallPayload {
+    fn default() -> Self {
+        Self::new(KernFnPath::Invalid, None, 0, 0, 0, 0, 0, 0)
+    }
+}
+
 impl TAsSysCallPtr for SysCallPayload {
     /* No methods to implement */
 }
@@ -1,13 +1,19 @@
 /*! `Task` exit value */
 
+use core::ptr;
+
 use crate::{
     error::OsError,
     sys::TAsSysCallPtr
 };
 
 /**
- * `Task` exist status
+ * `Task` exist status.
+ *
+ * The layout is fixed since the Kernel reads it directly from the
+ * userspace memory
  */
+#[repr(C, usize)]
 #[derive(Debug)]
 #[derive(Copy, Clone)]
 pub enum TaskExitStatus {
@@ -27,6 +33,29 @@ pub enum TaskExitStatus {
     WithError(OsError)
 }
 
+impl TaskExitStatus /* Constructors */ {
+    /**
+     * Reads the `TaskExitStatus` at the given pointer, validating its
+     * variant.
+     *
+     * The pointer must be aligned and its memory readable. Returns `None`
+     * if the variant or the carried `OsError` are not valid
+     */
+    pub unsafe fn read_from_raw(raw_exit_status_ptr: *const Self) -> Option<Self> {
+        /* the variant tag is followed by the fields, aligned to the <usize> */
+        let raw_tag_ptr = raw_exit_status_ptr as *const usize;
+        match ptr::read_volatile(raw_tag_ptr) {
+            0 => Some(Self::Success),
+            1 => Some(Self::WithValue(ptr::read_volatile(raw_tag_ptr.add(1)))),
+            2 => {
+                let raw_os_error_ptr = raw_tag_ptr.add(1) as *const OsError;
+                OsError::read_from_raw(raw_os_error_ptr).map(Self::WithError)
+            },
+            _ => None
+        }
+    }
+}
+
 impl TAsSysCallPtr for TaskExitStatus {
     /* No methods to implement */
 }
@@ -0,0 +1,671 @@
+/*! MXFS on-disk B+trees */
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::{
+    bytes::{
+        read_u16,
+        read_u32,
+        read_u64,
+        write_u16,
+        write_u32
+    },
+    crc::crc32_skipping,
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * Size in bytes of the header of each tree node block
+ */
+pub const C_NODE_HEADER_SIZE: usize = 16;
+
+/* signature of the tree node blocks, "MXBT" */
+const C_NODE_MAGIC: u32 = 0x5442_584D;
+
+/* offset of the checksum of the tree node blocks */
+const C_CRC_OFFSET: usize = 4;
+
+/* the trees can't be deeper than this */
+const C_LEVEL_MAX: u8 = 16;
+
+/**
+ * Reference to a child node stored into the branches: the lowest key which
+ * the child may contain and its block
+ */
+pub type ChildRef<K> = (K, u64);
+
+/* record replaced by an insertion and the new right sibling of the split node */
+type InsertOutcome<R> = (Option<R>, Option<ChildRef<<R as TTreeRecord>::Key>>);
+
+/**
+ * Interface implemented by the records stored into the leaves of a
+ * `BTree`.
+ *
+ * The records may have a variable encoded size, but each record and each
+ * key must take at most a third of the space of a node, so that any split
+ * produces two nodes which fit their blocks
+ */
+pub trait TTreeRecord: Sized {
+    /**
+     * Ordered key which identifies the record
+     */
+    type Key: Ord + Clone;
+
+    /**
+     * Value stored into the node headers to recognize the tree type
+     */
+    const C_TREE_KIND: u8;
+
+    /**
+     * Returns the key of this record
+     */
+    fn key(&self) -> Self::Key;
+
+    /**
+     * Returns the size in bytes of the encoded record
+     */
+    fn encoded_size(&self) -> usize;
+
+    /**
+     * Appends the encoded record to the given buffer
+     */
+    fn encode(&self, buffer: &mut Vec<u8>);
+
+    /**
+     * Decodes the record at the beginning of the given buffer, returning
+     * it with its encoded size
+     */
+    fn decode(buffer: &[u8]) -> MxfsResult<(Self, usize)>;
+
+    /**
+     * Returns the size in bytes of the encoded key
+     */
+    fn key_encoded_size(key: &Self::Key) -> usize;
+
+    /**
+     * Appends the encoded key to the given buffer
+     */
+    fn encode_key(key: &Self::Key, buffer: &mut Vec<u8>);
+
+    /**
+     * Decodes the key at the beginning of the given buffer, returning it
+     * with its encoded size
+     */
+    fn decode_key(buffer: &[u8]) -> MxfsResult<(Self::Key, usize)>;
+}
+
+/**
+ * Interface implemented by the storages of the tree nodes, which
+ * allocate, read and write whole blocks
+ */
+pub trait TTreeStore {
+    /**
+     * Returns the size in bytes of the node blocks
+     */
+    fn node_size(&self) -> usize;
+
+    /**
+     * Returns the content of the given node block
+     */
+    fn read_node(&mut self, block: u64) -> MxfsResult<Vec<u8>>;
+
+    /**
+     * Updates the content of the given node block
+     */
+    fn write_node(&mut self, block: u64, raw_node: Vec<u8>) -> MxfsResult<()>;
+
+    /**
+     * Allocates a new node block
+     */
+    fn alloc_node(&mut self) -> MxfsResult<u64>;
+
+    /**
+     * Releases the given node block
+     */
+    fn free_node(&mut self, block: u64) -> MxfsResult<()>;
+}
+
+/**
+ * Decoded content of a tree node block
+ */
+pub enum TreeNode<R: TTreeRecord> {
+    /**
+     * Node of level `0`, which stores the records sorted by key
+     */
+    Leaf(Vec<R>),
+
+    /**
+     * Node of the given level, which stores the children sorted by the
+     * lowest key they may contain
+     */
+    Branch(u8, Vec<ChildRef<R::Key>>)
+}
+
+impl<R: TTreeRecord> TreeNode<R> /* Constructors */ {
+    /**
+     * Decodes the given tree node block
+     */
+    pub fn decode(raw_node: &[u8]) -> MxfsResult<Self> {
+        if read_u32(raw_node, 0) != C_NODE_MAGIC
+           || crc32_skipping(raw_node, C_CRC_OFFSET) != read_u32(raw_node, C_CRC_OFFSET)
+           || raw_node[8] != R::C_TREE_KIND
+           || raw_node[9] > C_LEVEL_MAX
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let level = raw_node[9];
+        let entries_count = read_u16(raw_node, 10) as usize;
+        let mut offset = C_NODE_HEADER_SIZE;
+        if level == 0 {
+            let mut records = Vec::with_capacity(entries_count);
+            for _ in 0..entries_count {
+                let node_tail = raw_node.get(offset..).ok_or(MxfsError::Corrupted)?;
+                let (record, encoded_size) = R::decode(node_tail)?;
+
+                records.push(record);
+                offset += encoded_size;
+            }
+            Ok(Self::Leaf(records))
+        } else {
+            let mut children = Vec::with_capacity(entries_count);
+            for _ in 0..entries_count {
+                let node_tail = raw_node.get(offset..).ok_or(MxfsError::Corrupted)?;
+                let (key, encoded_size) = R::decode_key(node_tail)?;
+                offset += encoded_size;
+
+                if offset + 8 > raw_node.len() {
+                    return Err(MxfsError::Corrupted);
+                }
+                children.push((key, read_u64(raw_node, offset)));
+                offset += 8;
+            }
+            Ok(Self::Branch(level, children))
+        }
+    }
+}
+
+impl<R: TTreeRecord> TreeNode<R> /* Methods */ {
+    /**
+     * Encodes this node into a new block of the given size
+     */
+    pub fn encode(&self, node_size: usize) -> Vec<u8> {
+        let mut raw_node = Vec::with_capacity(node_size);
+        raw_node.resize(C_NODE_HEADER_SIZE, 0);
+
+        write_u32(&mut raw_node, 0, C_NODE_MAGIC);
+        raw_node[8] = R::C_TREE_KIND;
+        raw_node[9] = self.level();
+        write_u16(&mut raw_node, 10, self.len() as u16);
+        match self {
+            Self::Leaf(records) => {
+                for record in records.iter() {
+                    record.encode(&mut raw_node);
+                }
+            },
+            Self::Branch(_, children) => {
+                for (key, child) in children.iter() {
+                    R::encode_key(key, &mut raw_node);
+                    raw_node.extend_from_slice(&child.to_le_bytes());
+                }
+            },
+        }
+
+        debug_assert!(raw_node.len() <= node_size);
+        raw_node.resize(node_size, 0);
+
+        let checksum = crc32_skipping(&raw_node, C_CRC_OFFSET);
+        write_u32(&mut raw_node, C_CRC_OFFSET, checksum);
+        raw_node
+    }
+}
+
+impl<R: TTreeRecord> TreeNode<R> /* Getters */ {
+    /**
+     * Returns the level of this node, `0` for the leaves
+     */
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::Leaf(_) => 0,
+            Self::Branch(level, _) => *level
+        }
+    }
+
+    /**
+     * Returns the amount of records or children of this node
+     */
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Leaf(records) => records.len(),
+            Self::Branch(_, children) => children.len()
+        }
+    }
+
+    /**
+     * Returns whether this node has no records or no children
+     */
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Returns the lowest key of this node
+     */
+    pub fn first_key(&self) -> Option<R::Key> {
+        match self {
+            Self::Leaf(records) => records.first().map(|record| record.key()),
+            Self::Branch(_, children) => children.first().map(|(key, _)| key.clone())
+        }
+    }
+
+    /**
+     * Returns the size in bytes of the encoded node
+     */
+    pub fn encoded_size(&self) -> usize {
+        let entries_size: usize = match self {
+            Self::Leaf(records) => {
+                records.iter().map(|record| record.encoded_size()).sum()
+            },
+            Self::Branch(_, children) => {
+                children.iter().map(|(key, _)| R::key_encoded_size(key) + 8).sum()
+            },
+        };
+        C_NODE_HEADER_SIZE + entries_size
+    }
+}
+
+/**
+ * B+tree of `TTreeRecord`s stored into the blocks of a `TTreeStore`.
+ *
+ * Only the block of the root is kept, into the inode which owns the tree.
+ * The nodes are split when they overflow their block and released when
+ * they become empty, but they are never merged, so the trees which shrink
+ * may keep sparse nodes until they are emptied
+ */
+pub struct BTree<R: TTreeRecord> {
+    m_root: u64,
+    _unused: PhantomData<R>
+}
+
+impl<R: TTreeRecord> BTree<R> /* Constructors */ {
+    /**
+     * Constructs the `BTree` with the given root block, `0` when empty
+     */
+    pub fn new(root: u64) -> Self {
+        Self { m_root: root,
+               _unused: PhantomData }
+    }
+}
+
+impl<R: TTreeRecord> BTree<R> /* Methods */ {
+    /**
+     * Returns the record with the given key
+     */
+    pub fn find(&self,
+                store: &mut dyn TTreeStore,
+                key: &R::Key)
+                -> MxfsResult<Option<R>> {
+        let mut found_record = None;
+        self.for_each_from(store, Some(key), &mut |record| {
+                if record.key() == *key {
+                    found_record = Some(record);
+                }
+                Ok(false)
+            })?;
+        Ok(found_record)
+    }
+
+    /**
+     * Inserts the given record, returning the one which had the same key
+     */
+    pub fn insert(&mut self,
+                  store: &mut dyn TTreeStore,
+                  record: R)
+                  -> MxfsResult<Option<R>> {
+        if self.m_root == 0 {
+            let root = store.alloc_node()?;
+            store.write_node(root,
+                             TreeNode::Leaf(vec![record]).encode(store.node_size()))?;
+
+            self.m_root = root;
+            return Ok(None);
+        }
+
+        let (replaced_record, split) =
+            self.insert_into(store, self.m_root, None, record)?;
+        if let Some((split_key, split_block)) = split {
+            /* the root was split, so the tree grows by one level */
+            let old_root_node = self.load_node(store, self.m_root, None)?;
+            let old_root_key = old_root_node.first_key().ok_or(MxfsError::Corrupted)?;
+            let new_root_node = TreeNode::<R>::Branch(old_root_node.level() + 1,
+                                                      vec![(old_root_key, self.m_root),
+                                                           (split_key, split_block)]);
+            if new_root_node.level() > C_LEVEL_MAX {
+                return Err(MxfsError::NoSpace);
+            }
+
+            let new_root = store.alloc_node()?;
+            store.write_node(new_root, new_root_node.encode(store.node_size()))?;
+            self.m_root = new_root;
+        }
+        Ok(replaced_record)
+    }
+
+    /**
+     * Removes the record with the given key, returning it
+     */
+    pub fn remove(&mut self,
+                  store: &mut dyn TTreeStore,
+                  key: &R::Key)
+                  -> MxfsResult<Option<R>> {
+        if self.m_root == 0 {
+            return Ok(None);
+        }
+
+        let (removed_record, is_root_empty) =
+            self.remove_from(store, self.m_root, None, key)?;
+        if is_root_empty {
+            self.m_root = 0;
+            return Ok(removed_record);
+        }
+
+        /* the roots with a single child are replaced by it */
+        let mut root_level = None;
+        loop {
+            match self.load_node(store, self.m_root, root_level)? {
+                TreeNode::Branch(level, children) if children.len() == 1 => {
+                    store.free_node(self.m_root)?;
+                    self.m_root = children[0].1;
+                    root_level = Some(level - 1);
+                },
+                _ => break
+            }
+        }
+        Ok(removed_record)
+    }
+
+    /**
+     * Calls the given closure with the records in key order, starting from
+     * the given key when given, until it returns `false`.
+     *
+     * Returns `false` when the visit was interrupted
+     */
+    pub fn for_each_from(&self,
+                         store: &mut dyn TTreeStore,
+                         from_key: Option<&R::Key>,
+                         visitor: &mut dyn FnMut(R) -> MxfsResult<bool>)
+                         -> MxfsResult<bool> {
+        if self.m_root == 0 {
+            return Ok(true);
+        }
+        self.visit_from(store, self.m_root, None, from_key, visitor)
+    }
+
+    /**
+     * Calls the given closure with the block and the content of each node,
+     * parents before their children
+     */
+    pub fn for_each_node(&self,
+                         store: &mut dyn TTreeStore,
+                         visitor: &mut dyn FnMut(u64, &TreeNode<R>) -> MxfsResult<()>)
+                         -> MxfsResult<()> {
+        let mut pending_blocks = Vec::new();
+        if self.m_root != 0 {
+            pending_blocks.push((self.m_root, None));
+        }
+
+        while let Some((block, level)) = pending_blocks.pop() {
+            let node = self.load_node(store, block, level)?;
+            visitor(block, &node)?;
+
+            if let TreeNode::Branch(level, children) = node {
+                pending_blocks.extend(children.iter().rev().map(|(_, child)| {
+                                                               (*child, Some(level - 1))
+                                                           }));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: TTreeRecord> BTree<R> /* Getters */ {
+    /**
+     * Returns the root block, `0` when the tree is empty
+     */
+    pub fn root(&self) -> u64 {
+        self.m_root
+    }
+}
+
+impl<R: TTreeRecord> BTree<R> /* Privates */ {
+    /**
+     * Inserts the given record into the subtree of the given node.
+     *
+     * Returns the replaced record and, when the node was split, the lowest
+     * key and the block of its new right sibling
+     */
+    fn insert_into(&self,
+                   store: &mut dyn TTreeStore,
+                   block: u64,
+                   level: Option<u8>,
+                   record: R)
+                   -> MxfsResult<InsertOutcome<R>> {
+        let mut node = self.load_node(store, block, level)?;
+
+        let replaced_record = match &mut node {
+            TreeNode::Leaf(records) => {
+                match records.binary_search_by(|other| other.key().cmp(&record.key())) {
+                    Ok(index) => Some(core::mem::replace(&mut records[index], record)),
+                    Err(index) => {
+                        records.insert(index, record);
+                        None
+                    }
+                }
+            },
+            TreeNode::Branch(level, children) => {
+                let key = record.key();
+                let child_index = Self::child_index(children, &key);
+
+                /* the first child also receives the keys below its own */
+                let mut is_modified = false;
+                if key < children[child_index].0 {
+                    children[child_index].0 = key;
+                    is_modified = true;
+                }
+
+                let (replaced_record, split) = self.insert_into(store,
+                                                                children[child_index].1,
+                                                                Some(*level - 1),
+                                                                record)?;
+                if let Some(split_child) = split {
+                    children.insert(child_index + 1, split_child);
+                    is_modified = true;
+                }
+
+                if !is_modified {
+                    return Ok((replaced_record, None));
+                }
+                replaced_record
+            }
+        };
+
+        let node_size = store.node_size();
+        if node.encoded_size() <= node_size {
+            store.write_node(block, node.encode(node_size))?;
+            return Ok((replaced_record, None));
+        }
+
+        /* split the node at the half of its encoded size */
+        let right_node = self.split_node(&mut node);
+        let right_key = right_node.first_key().ok_or(MxfsError::Corrupted)?;
+        let right_block = store.alloc_node()?;
+
+        store.write_node(block, node.encode(node_size))?;
+        store.write_node(right_block, right_node.encode(node_size))?;
+        Ok((replaced_record, Some((right_key, right_block))))
+    }
+
+    /**
+     * Removes the record with the given key from the subtree of the given
+     * node.
+     *
+     * Returns the removed record and whether the node was released because
+     * it became empty
+     */
+    fn remove_from(&self,
+                   store: &mut dyn TTreeStore,
+                   block: u64,
+                   level: Option<u8>,
+                   key: &R::Key)
+                   -> MxfsResult<(Option<R>, bool)> {
+        let mut node = self.load_node(store, block, level)?;
+
+        let removed_record = match &mut node {
+            TreeNode::Leaf(records) => {
+                match records.binary_search_by(|other| other.key().cmp(key)) {
+                    Ok(index) => records.remove(index),
+                    Err(_) => return Ok((None, false))
+                }
+            },
+            TreeNode::Branch(level, children) => {
+                let child_index = Self::child_index(children, key);
+                let (removed_record, is_child_empty) =
+                    self.remove_from(store,
+                                     children[child_index].1,
+                                     Some(*level - 1),
+                                     key)?;
+                if !is_child_empty {
+                    return Ok((removed_record, false));
+                }
+
+                children.remove(child_index);
+                match removed_record {
+                    Some(removed_record) => removed_record,
+                    None => return Err(MxfsError::Corrupted)
+                }
+            }
+        };
+
+        if node.is_empty() {
+            store.free_node(block)?;
+            Ok((Some(removed_record), true))
+        } else {
+            store.write_node(block, node.encode(store.node_size()))?;
+            Ok((Some(removed_record), false))
+        }
+    }
+
+    /**
+     * Visits the records of the subtree of the given node
+     */
+    fn visit_from(&self,
+                  store: &mut dyn TTreeStore,
+                  block: u64,
+                  level: Option<u8>,
+                  from_key: Option<&R::Key>,
+                  visitor: &mut dyn FnMut(R) -> MxfsResult<bool>)
+                  -> MxfsResult<bool> {
+        match self.load_node(store, block, level)? {
+            TreeNode::Leaf(records) => {
+                for record in records.into_iter() {
+                    if from_key.is_none_or(|from_key| record.key() >= *from_key)
+                       && !visitor(record)?
+                    {
+                        return Ok(false);
+                    }
+                }
+            },
+            TreeNode::Branch(level, children) => {
+                let first_child =
+                    from_key.map_or(0, |from_key| Self::child_index(&children, from_key));
+                for (_, child) in children[first_child..].iter() {
+                    if !self.visit_from(store,
+                                        *child,
+                                        Some(level - 1),
+                                        from_key,
+                                        visitor)?
+                    {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /**
+     * Moves the second half of the entries of the given node into a new
+     * node, which is returned
+     */
+    fn split_node(&self, node: &mut TreeNode<R>) -> TreeNode<R> {
+        let half_size = (node.encoded_size() - C_NODE_HEADER_SIZE) / 2;
+
+        match node {
+            TreeNode::Leaf(records) => {
+                let split_index =
+                    Self::split_index(records.iter().map(|record| record.encoded_size()),
+                                      half_size);
+                TreeNode::Leaf(records.split_off(split_index))
+            },
+            TreeNode::Branch(level, children) => {
+                let split_index =
+                    Self::split_index(children.iter().map(|(key, _)| {
+                                                         R::key_encoded_size(key) + 8
+                                                     }),
+                                      half_size);
+                TreeNode::Branch(*level, children.split_off(split_index))
+            }
+        }
+    }
+
+    /**
+     * Returns the index of the first entry which doesn't fit the given
+     * size, keeping at least one entry for each side
+     */
+    fn split_index(entry_sizes: impl ExactSizeIterator<Item = usize>,
+                   size: usize)
+                   -> usize {
+        let entries_count = entry_sizes.len();
+
+        let mut prefix_size = 0;
+        let mut split_index = entries_count - 1;
+        for (i, entry_size) in entry_sizes.enumerate() {
+            prefix_size += entry_size;
+            if prefix_size > size {
+                split_index = i;
+                break;
+            }
+        }
+        split_index.max(1)
+    }
+
+    /**
+     * Returns the index of the child which may contain the given key
+     */
+    fn child_index(children: &[ChildRef<R::Key>], key: &R::Key) -> usize {
+        match children.binary_search_by(|(child_key, _)| child_key.cmp(key)) {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1)
+        }
+    }
+
+    /**
+     * Loads the node stored into the given block, which must be at the
+     * given level when known.
+     *
+     * The check of the level keeps a damaged tree from looping
+     */
+    fn load_node(&self,
+                 store: &mut dyn TTreeStore,
+                 block: u64,
+                 level: Option<u8>)
+                 -> MxfsResult<TreeNode<R>> {
+        let node = TreeNode::decode(&store.read_node(block)?)?;
+        if node.is_empty() || level.is_some_and(|level| node.level() != level) {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(node)
+    }
+}
@@ -0,0 +1,45 @@
+/*! Little endian fields of the on-disk structures */
+
+use core::convert::TryInto;
+
+/**
+ * Reads the `u16` at the given offset of the given buffer
+ */
+pub fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buffer[offset..offset + 2].try_into().unwrap())
+}
+
+/**
+ * Reads the `u32` at the given offset of the given buffer
+ */
+pub fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap())
+}
+
+/**
+ * Reads the `u64` at the given offset of the given buffer
+ */
+pub fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap())
+}
+
+/**
+ * Writes the given `u16` at the given offset of the given buffer
+ */
+pub fn write_u16(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/**
+ * Writes the given `u32` at the given offset of the given buffer
+ */
+pub fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/**
+ * Writes the given `u64` at the given offset of the given buffer
+ */
+pub fn write_u64(buffer: &mut [u8], offset: usize, value: u64) {
+    buffer[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,133 @@
+/*! MXFS metadata blocks cache */
+
+use alloc::{
+    collections::BTreeMap,
+    vec::Vec
+};
+
+use crate::{
+    device::TMxfsDevice,
+    MxfsResult
+};
+
+/* maximum amount of clean blocks kept in memory */
+const C_CLEAN_BLOCKS_MAX: usize = 1024;
+
+/**
+ * Cache of the metadata blocks.
+ *
+ * The modified blocks stay dirty into the cache until the running
+ * transaction is committed through the journal, or discarded when it
+ * fails, so the device always contains a consistent volume
+ */
+pub struct MetaCache {
+    m_block_size: u64,
+    m_clean: BTreeMap<u64, Vec<u8>>,
+    m_dirty: BTreeMap<u64, Vec<u8>>
+}
+
+impl MetaCache /* Constructors */ {
+    /**
+     * Constructs an empty `MetaCache` for blocks of the given size
+     */
+    pub fn new(block_size: u64) -> Self {
+        Self { m_block_size: block_size,
+               m_clean: BTreeMap::new(),
+               m_dirty: BTreeMap::new() }
+    }
+}
+
+impl MetaCache /* Methods */ {
+    /**
+     * Returns a copy of the given block, read from the device when not
+     * cached
+     */
+    pub fn read(&mut self, device: &dyn TMxfsDevice, block: u64) -> MxfsResult<Vec<u8>> {
+        if let Some(dirty_block) = self.m_dirty.get(&block) {
+            return Ok(dirty_block.clone());
+        }
+        if let Some(clean_block) = self.m_clean.get(&block) {
+            return Ok(clean_block.clone());
+        }
+
+        let mut raw_block = vec![0; self.m_block_size as usize];
+        device.read_at(block * self.m_block_size, &mut raw_block)?;
+
+        self.insert_clean(block, raw_block.clone());
+        Ok(raw_block)
+    }
+
+    /**
+     * Stores the given content of the given block into the running
+     * transaction
+     */
+    pub fn write(&mut self, block: u64, raw_block: Vec<u8>) {
+        debug_assert_eq!(raw_block.len() as u64, self.m_block_size);
+
+        self.m_clean.remove(&block);
+        self.m_dirty.insert(block, raw_block);
+    }
+
+    /**
+     * Takes the dirty blocks of the running transaction, which must be
+     * given back with `commit_done()` once written
+     */
+    pub fn take_dirty(&mut self) -> BTreeMap<u64, Vec<u8>> {
+        core::mem::take(&mut self.m_dirty)
+    }
+
+    /**
+     * Keeps the given blocks, already written to the device, as clean
+     */
+    pub fn commit_done(&mut self, written_blocks: BTreeMap<u64, Vec<u8>>) {
+        for (block, raw_block) in written_blocks.into_iter() {
+            self.insert_clean(block, raw_block);
+        }
+    }
+
+    /**
+     * Throws away the modifications of the running transaction
+     */
+    pub fn discard(&mut self) {
+        self.m_dirty.clear();
+    }
+
+    /**
+     * Forgets the cached content of the given block, which was written
+     * outside of the cache
+     */
+    pub fn invalidate(&mut self, block: u64) {
+        self.m_clean.remove(&block);
+    }
+}
+
+impl MetaCache /* Getters */ {
+    /**
+     * Returns the amount of dirty blocks of the running transaction
+     */
+    pub fn dirty_count(&self) -> usize {
+        self.m_dirty.len()
+    }
+
+    /**
+     * Returns whether the given block was modified by the running
+     * transaction
+     */
+    pub fn is_dirty(&self, block: u64) -> bool {
+        self.m_dirty.contains_key(&block)
+    }
+}
+
+impl MetaCache /* Privates */ {
+    /**
+     * Keeps the given clean block, evicting another one when full
+     */
+    fn insert_clean(&mut self, block: u64, raw_block: Vec<u8>) {
+        if self.m_clean.len() >= C_CLEAN_BLOCKS_MAX && !self.m_clean.contains_key(&block)
+        {
+            let evicted_block = *self.m_clean.keys().next().unwrap();
+            self.m_clean.remove(&evicted_block);
+        }
+        self.m_clean.insert(block, raw_block);
+    }
+}
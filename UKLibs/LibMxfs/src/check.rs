@@ -0,0 +1,906 @@
+/*! MXFS consistency check */
+
+use alloc::{
+    collections::{
+        BTreeMap,
+        BTreeSet
+    },
+    string::String,
+    vec::Vec
+};
+
+use crate::{
+    btree::{
+        BTree,
+        TTreeRecord,
+        TTreeStore,
+        TreeNode
+    },
+    crc::crc32,
+    device::TMxfsDevice,
+    dir::{
+        check_name,
+        name_hash,
+        DirRecord
+    },
+    extent::{
+        ChunkHeader,
+        ExtentRecord,
+        C_CHUNK_HEADER_SIZE
+    },
+    inode::{
+        Inode,
+        InodeId,
+        InodeType,
+        C_INODE_SIZE
+    },
+    journal::Journal,
+    pool::PoolHeader,
+    super_block::SuperBlock,
+    volume::count_zero_bits,
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * Outcome of the consistency check of a volume.
+ *
+ * The errors describe damages which make the volume unsafe to modify,
+ * while the warnings describe wasted space or interrupted operations which
+ * the driver tolerates
+ */
+#[derive(Debug)]
+pub struct CheckReport {
+    m_super_block: SuperBlock,
+    m_errors: Vec<String>,
+    m_warnings: Vec<String>,
+    m_files_count: u64,
+    m_dirs_count: u64,
+    m_links_count: u64,
+    m_used_blocks: u64,
+    m_free_blocks: u64
+}
+
+impl CheckReport /* Getters */ {
+    /**
+     * Returns the `SuperBlock` of the checked volume
+     */
+    pub fn super_block(&self) -> &SuperBlock {
+        &self.m_super_block
+    }
+
+    /**
+     * Returns the found damages
+     */
+    pub fn errors(&self) -> &[String] {
+        &self.m_errors
+    }
+
+    /**
+     * Returns the found anomalies which are not damages
+     */
+    pub fn warnings(&self) -> &[String] {
+        &self.m_warnings
+    }
+
+    /**
+     * Returns whether no damages were found
+     */
+    pub fn is_clean(&self) -> bool {
+        self.m_errors.is_empty()
+    }
+
+    /**
+     * Returns the amount of regular files
+     */
+    pub fn files_count(&self) -> u64 {
+        self.m_files_count
+    }
+
+    /**
+     * Returns the amount of directories, root included
+     */
+    pub fn dirs_count(&self) -> u64 {
+        self.m_dirs_count
+    }
+
+    /**
+     * Returns the amount of symbolic links
+     */
+    pub fn links_count(&self) -> u64 {
+        self.m_links_count
+    }
+
+    /**
+     * Returns the amount of blocks marked as used by the pools
+     */
+    pub fn used_blocks(&self) -> u64 {
+        self.m_used_blocks
+    }
+
+    /**
+     * Returns the amount of blocks marked as free by the pools
+     */
+    pub fn free_blocks(&self) -> u64 {
+        self.m_free_blocks
+    }
+}
+
+/**
+ * Checks the consistency of the MXFS volume stored into the given
+ * `TMxfsDevice` without modifying it.
+ *
+ * The committed transaction left into the journal, if any, is applied in
+ * memory, as the next mount would do.
+ *
+ * Fails only when the `SuperBlock` can't be read, the other damages are
+ * listed by the returned `CheckReport`
+ */
+pub fn check(device: &dyn TMxfsDevice) -> MxfsResult<CheckReport> {
+    let super_block = SuperBlock::read(device)?;
+    super_block.verify(device.size())?;
+
+    let mut checker =
+        Checker { m_store: CheckStore { m_device: device,
+                                        m_block_size: super_block.block_size(),
+                                        m_pools: Vec::new(),
+                                        m_overlay: BTreeMap::new() },
+                  m_report: CheckReport { m_super_block: super_block.clone(),
+                                          m_errors: Vec::new(),
+                                          m_warnings: Vec::new(),
+                                          m_files_count: 0,
+                                          m_dirs_count: 0,
+                                          m_links_count: 0,
+                                          m_used_blocks: 0,
+                                          m_free_blocks: 0 },
+                  m_super_block: super_block,
+                  m_used_blocks: BTreeSet::new(),
+                  m_extents: BTreeMap::new(),
+                  m_orphans: BTreeSet::new() };
+
+    checker.load_journal()?;
+    checker.load_pools()?;
+
+    let inodes = checker.load_inodes()?;
+    checker.load_orphans(&inodes)?;
+    let references = checker.check_inodes(&inodes)?;
+    checker.check_references(&inodes, &references);
+    checker.check_extents();
+    checker.check_bitmaps()?;
+    Ok(checker.m_report)
+}
+
+/**
+ * `TTreeStore` which reads the blocks of the volume through the journaled
+ * blocks and refuses any modification
+ */
+struct CheckStore<'a> {
+    m_device: &'a dyn TMxfsDevice,
+    m_block_size: u64,
+    m_pools: Vec<PoolHeader>,
+    m_overlay: BTreeMap<u64, Vec<u8>>
+}
+
+impl<'a> CheckStore<'a> /* Methods */ {
+    /**
+     * Reads the given block, preferring its journaled content
+     */
+    fn read_block(&self, block: u64) -> MxfsResult<Vec<u8>> {
+        if let Some(raw_block) = self.m_overlay.get(&block) {
+            return Ok(raw_block.clone());
+        }
+
+        let mut raw_block = vec![0; self.m_block_size as usize];
+        self.m_device.read_at(block * self.m_block_size, &mut raw_block)?;
+        Ok(raw_block)
+    }
+
+    /**
+     * Reads the given bytes starting from the given block
+     */
+    fn read_bytes(&self, block: u64, buffer: &mut [u8]) -> MxfsResult<()> {
+        let block_size = self.m_block_size as usize;
+
+        for (i, part) in buffer.chunks_mut(block_size).enumerate() {
+            let raw_block = self.read_block(block + i as u64)?;
+            part.copy_from_slice(&raw_block[..part.len()]);
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns whether the given blocks are into the data area of a pool
+     */
+    fn is_data_range(&self, first_block: u64, blocks_count: u64) -> bool {
+        self.m_pools.iter().any(|pool| {
+                               first_block >= pool.data_start()
+                               && first_block + blocks_count <= pool.blocks().end
+                           })
+    }
+}
+
+impl<'a> TTreeStore for CheckStore<'a> {
+    fn node_size(&self) -> usize {
+        self.m_block_size as usize
+    }
+
+    fn read_node(&mut self, block: u64) -> MxfsResult<Vec<u8>> {
+        if !self.is_data_range(block, 1) {
+            return Err(MxfsError::Corrupted);
+        }
+        self.read_block(block)
+    }
+
+    fn write_node(&mut self, _block: u64, _raw_node: Vec<u8>) -> MxfsResult<()> {
+        Err(MxfsError::ReadOnly)
+    }
+
+    fn alloc_node(&mut self) -> MxfsResult<u64> {
+        Err(MxfsError::ReadOnly)
+    }
+
+    fn free_node(&mut self, _block: u64) -> MxfsResult<()> {
+        Err(MxfsError::ReadOnly)
+    }
+}
+
+/**
+ * Collects the references to a data extent
+ */
+struct ExtentUsage {
+    m_ref_count: u16,
+    m_references: u64
+}
+
+/**
+ * Collects the references to an inode from the directories
+ */
+#[derive(Default)]
+struct InodeReferences {
+    m_count: u64,
+    m_dirs: BTreeSet<InodeId>
+}
+
+/**
+ * State of the consistency check
+ */
+struct Checker<'a> {
+    m_store: CheckStore<'a>,
+    m_report: CheckReport,
+    m_super_block: SuperBlock,
+    m_used_blocks: BTreeSet<u64>,
+    m_extents: BTreeMap<u64, ExtentUsage>,
+    m_orphans: BTreeSet<InodeId>
+}
+
+impl<'a> Checker<'a> /* Privates */ {
+    /**
+     * Loads the committed transaction left into the journal
+     */
+    fn load_journal(&mut self) -> MxfsResult<()> {
+        let journal =
+            Journal::new(self.m_super_block.journal(), self.m_super_block.block_size());
+        let journal_blocks = match journal.read_committed(self.m_store.m_device,
+                                                          self.m_super_block
+                                                              .journal_sequence())?
+        {
+            Some(journal_blocks) => journal_blocks,
+            None => return Ok(())
+        };
+
+        self.warning(format!("the journal contains a committed transaction of {} \
+                              blocks, replayed by the next mount",
+                             journal_blocks.len()));
+        for (block, raw_block) in journal_blocks.into_iter() {
+            if block == 0
+               || block >= self.m_super_block.blocks_count()
+               || self.m_super_block.journal().contains(&block)
+            {
+                self.error(format!("the journal targets the invalid block {}", block));
+            } else {
+                self.m_store.m_overlay.insert(block, raw_block);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Loads and checks the chain of the extent pools, which must cover
+     * the blocks after the first pool
+     */
+    fn load_pools(&mut self) -> MxfsResult<()> {
+        let first_pool = self.m_super_block.first_pool();
+        let pool_blocks = self.m_super_block.pool_blocks();
+
+        let mut pool_block = first_pool;
+        while pool_block != 0 {
+            let expected_block =
+                first_pool + self.m_store.m_pools.len() as u64 * pool_blocks;
+            if pool_block != expected_block
+               || pool_block >= self.m_super_block.blocks_count()
+            {
+                self.error(format!("the pool chain continues at the misplaced block {}",
+                                   pool_block));
+                break;
+            }
+
+            let raw_header = self.m_store.read_block(pool_block)?;
+            let pool = match PoolHeader::decode(&raw_header, pool_block) {
+                Ok(pool) => pool,
+                Err(error) => {
+                    self.error(format!("pool at block {}: {}", pool_block, error));
+                    break;
+                }
+            };
+            if pool.blocks().end > self.m_super_block.blocks_count()
+               || pool.blocks().end - pool.blocks().start > pool_blocks
+            {
+                self.error(format!("pool at block {}: exceeds its space", pool_block));
+                break;
+            }
+
+            for block in pool.blocks().start..pool.data_start() {
+                self.m_used_blocks.insert(block);
+            }
+            for bad_block in pool.bad_blocks() {
+                self.m_used_blocks.insert(*bad_block);
+            }
+
+            pool_block = pool.next_pool();
+            self.m_store.m_pools.push(pool);
+        }
+
+        let pools_end =
+            self.m_store.m_pools.last().map_or(first_pool, |pool| pool.blocks().end);
+        if pools_end < self.m_super_block.blocks_count() {
+            self.error(format!("the blocks from {} to {} are not covered by any pool",
+                               pools_end,
+                               self.m_super_block.blocks_count()));
+        }
+        Ok(())
+    }
+
+    /**
+     * Loads the inode table, the damaged inodes are reported and skipped
+     */
+    fn load_inodes(&mut self) -> MxfsResult<BTreeMap<InodeId, Inode>> {
+        let block_size = self.m_super_block.block_size();
+        let inodes_per_block = block_size / C_INODE_SIZE as u64;
+
+        let mut inodes = BTreeMap::new();
+        for (i, block) in self.m_super_block.inode_table().enumerate() {
+            let raw_block = self.m_store.read_block(block)?;
+
+            for (slot, raw_inode) in raw_block.chunks(C_INODE_SIZE).enumerate() {
+                let inode_id = i as u64 * inodes_per_block + slot as u64 + 1;
+                if inode_id > self.m_super_block.inodes_count() {
+                    break;
+                }
+
+                match Inode::decode(raw_inode) {
+                    Ok(inode) if inode.inode_type() == InodeType::Free => {},
+                    Ok(inode) => {
+                        inodes.insert(inode_id, inode);
+                    },
+                    Err(error) => self.error(format!("inode {}: {}", inode_id, error))
+                }
+            }
+        }
+
+        match inodes.get(&self.m_super_block.root_inode()) {
+            Some(root_inode) if root_inode.inode_type() == InodeType::Dir => {},
+            _ => self.error(format!("the root inode {} is not a directory",
+                                    self.m_super_block.root_inode()))
+        }
+        Ok(inodes)
+    }
+
+    /**
+     * Loads the inodes listed into the orphans directory, whose release is
+     * completed by the next mount
+     */
+    fn load_orphans(&mut self, inodes: &BTreeMap<InodeId, Inode>) -> MxfsResult<()> {
+        let orphans_id = self.m_super_block.orphans_inode();
+        let orphans_inode = match inodes.get(&orphans_id) {
+            Some(orphans_inode) if orphans_inode.inode_type() == InodeType::Dir => {
+                orphans_inode
+            },
+            _ => {
+                self.error(format!("the orphans inode {} is not a directory",
+                                   orphans_id));
+                return Ok(());
+            }
+        };
+
+        let mut dir_records = Vec::new();
+        let result = BTree::<DirRecord>::new(orphans_inode.tree_root())
+            .for_each_from(&mut self.m_store, None, &mut |record| {
+                dir_records.push(record);
+                Ok(true)
+            });
+        match result {
+            Ok(_) => {},
+            Err(MxfsError::DeviceError) => return Err(MxfsError::DeviceError),
+            /* reported by the check of the nodes */
+            Err(_) => return Ok(())
+        }
+
+        if dir_records.len() as u64 != orphans_inode.size() {
+            self.error(format!("the orphans directory has {} entries but counts {}",
+                               dir_records.len(),
+                               orphans_inode.size()));
+        }
+
+        for dir_record in dir_records.iter() {
+            let is_valid_entry =
+                dir_record.name() == format!("{}", dir_record.inode())
+                && dir_record.name_hash() == name_hash(dir_record.name())
+                && inodes.get(&dir_record.inode())
+                         .is_some_and(|inode| {
+                             inode.inode_type() == dir_record.inode_type()
+                         });
+            if !is_valid_entry {
+                self.error(format!("the orphans directory has the invalid entry {:?}",
+                                   dir_record.name()));
+                continue;
+            }
+
+            self.warning(format!("inode {}: release pending, completed by the next \
+                                  mount",
+                                 dir_record.inode()));
+            self.m_orphans.insert(dir_record.inode());
+        }
+        Ok(())
+    }
+
+    /**
+     * Checks the trees of the given inodes, returning the references
+     * found into the directories
+     */
+    fn check_inodes(&mut self,
+                    inodes: &BTreeMap<InodeId, Inode>)
+                    -> MxfsResult<BTreeMap<InodeId, InodeReferences>> {
+        let mut references: BTreeMap<InodeId, InodeReferences> = BTreeMap::new();
+
+        for (inode_id, inode) in inodes.iter() {
+            let tree_blocks = self.check_tree_nodes(*inode_id, inode)?;
+
+            let data_blocks = match inode.inode_type() {
+                /* the entries of the orphans directory are checked apart */
+                InodeType::Dir if *inode_id == self.m_super_block.orphans_inode() => 0,
+                InodeType::Dir => {
+                    self.m_report.m_dirs_count += 1;
+                    self.check_dir(*inode_id, inode, inodes, &mut references)?;
+                    0
+                },
+                InodeType::File => {
+                    self.m_report.m_files_count += 1;
+                    self.check_data(*inode_id, inode)?
+                },
+                InodeType::Link => {
+                    self.m_report.m_links_count += 1;
+                    if inode.size() == 0 || inode.size() > self.m_super_block.chunk_span()
+                    {
+                        self.error(format!("inode {}: invalid link target length {}",
+                                           inode_id,
+                                           inode.size()));
+                    }
+                    self.check_data(*inode_id, inode)?
+                },
+                InodeType::Free => 0
+            };
+
+            if tree_blocks + data_blocks != inode.blocks_used() {
+                self.error(format!("inode {}: uses {} blocks but counts {}",
+                                   inode_id,
+                                   tree_blocks + data_blocks,
+                                   inode.blocks_used()));
+            }
+        }
+        Ok(references)
+    }
+
+    /**
+     * Checks the nodes of the tree of the given inode, returning their
+     * amount
+     */
+    fn check_tree_nodes(&mut self, inode_id: InodeId, inode: &Inode) -> MxfsResult<u64> {
+        let result = match inode.inode_type() {
+            InodeType::Dir => self.collect_tree_nodes::<DirRecord>(inode.tree_root()),
+            _ => self.collect_tree_nodes::<ExtentRecord>(inode.tree_root())
+        };
+
+        match result {
+            Ok(node_blocks) => {
+                for block in node_blocks.iter() {
+                    self.mark_used(*block, inode_id, "tree node");
+                }
+                Ok(node_blocks.len() as u64)
+            },
+            Err(MxfsError::DeviceError) => Err(MxfsError::DeviceError),
+            Err(error) => {
+                self.error(format!("inode {}: damaged tree: {}", inode_id, error));
+                Ok(0)
+            }
+        }
+    }
+
+    /**
+     * Returns the blocks of the nodes of the given tree, checking that the
+     * keys are sorted
+     */
+    fn collect_tree_nodes<R: TTreeRecord>(&mut self,
+                                          tree_root: u64)
+                                          -> MxfsResult<Vec<u64>> {
+        let tree = BTree::<R>::new(tree_root);
+
+        let mut node_blocks = Vec::new();
+        tree.for_each_node(&mut self.m_store, &mut |block, node: &TreeNode<R>| {
+                let is_sorted = match node {
+                    TreeNode::Leaf(records) => {
+                        records.windows(2).all(|pair| pair[0].key() < pair[1].key())
+                    },
+                    TreeNode::Branch(_, children) => {
+                        children.windows(2).all(|pair| pair[0].0 < pair[1].0)
+                    },
+                };
+                if !is_sorted || node_blocks.contains(&block) {
+                    return Err(MxfsError::Corrupted);
+                }
+
+                node_blocks.push(block);
+                Ok(())
+            })?;
+
+        /* the keys must be sorted across the nodes too */
+        let mut last_key = None;
+        tree.for_each_from(&mut self.m_store, None, &mut |record| {
+                let key = record.key();
+                if last_key.as_ref().is_some_and(|last_key| *last_key >= key) {
+                    return Err(MxfsError::Corrupted);
+                }
+
+                last_key = Some(key);
+                Ok(true)
+            })?;
+        Ok(node_blocks)
+    }
+
+    /**
+     * Checks the entries of the given directory
+     */
+    fn check_dir(&mut self,
+                 dir_id: InodeId,
+                 dir_inode: &Inode,
+                 inodes: &BTreeMap<InodeId, Inode>,
+                 references: &mut BTreeMap<InodeId, InodeReferences>)
+                 -> MxfsResult<()> {
+        let mut dir_records = Vec::new();
+        let result = BTree::<DirRecord>::new(dir_inode.tree_root())
+            .for_each_from(&mut self.m_store, None, &mut |record| {
+                dir_records.push(record);
+                Ok(true)
+            });
+        match result {
+            Ok(_) => {},
+            Err(MxfsError::DeviceError) => return Err(MxfsError::DeviceError),
+            /* already reported by the check of the nodes */
+            Err(_) => return Ok(())
+        }
+
+        if dir_records.len() as u64 != dir_inode.size() {
+            self.error(format!("directory {}: has {} entries but counts {}",
+                               dir_id,
+                               dir_records.len(),
+                               dir_inode.size()));
+        }
+
+        for dir_record in dir_records.iter() {
+            let name = dir_record.name();
+            if check_name(name).is_err() || name_hash(name) != dir_record.name_hash() {
+                self.error(format!("directory {}: invalid entry {:?}", dir_id, name));
+            }
+
+            let inode = match inodes.get(&dir_record.inode()) {
+                Some(inode) => inode,
+                None => {
+                    self.error(format!("directory {}: entry {:?} refers to the unused \
+                                        inode {}",
+                                       dir_id,
+                                       name,
+                                       dir_record.inode()));
+                    continue;
+                }
+            };
+
+            if inode.inode_type() != dir_record.inode_type() {
+                self.error(format!("directory {}: entry {:?} has the wrong type",
+                                   dir_id, name));
+            }
+            if inode.inode_type() == InodeType::Dir && inode.parent() != dir_id {
+                self.error(format!("directory {}: entry {:?} has the parent {}",
+                                   dir_id,
+                                   name,
+                                   inode.parent()));
+            }
+
+            let inode_references = references.entry(dir_record.inode()).or_default();
+            inode_references.m_count += 1;
+            inode_references.m_dirs.insert(dir_id);
+        }
+        Ok(())
+    }
+
+    /**
+     * Checks the data extents of the given file or symbolic link,
+     * returning the amount of blocks they use
+     */
+    fn check_data(&mut self, inode_id: InodeId, inode: &Inode) -> MxfsResult<u64> {
+        let mut extent_records = Vec::new();
+        let result = BTree::<ExtentRecord>::new(inode.tree_root())
+            .for_each_from(&mut self.m_store, None, &mut |record| {
+                extent_records.push(record);
+                Ok(true)
+            });
+        match result {
+            Ok(_) => {},
+            Err(MxfsError::DeviceError) => return Err(MxfsError::DeviceError),
+            /* already reported by the check of the nodes */
+            Err(_) => return Ok(0)
+        }
+
+        /* the orphans may keep the extents after their end of data */
+        let chunk_span = self.m_super_block.chunk_span();
+        let is_orphan = self.m_orphans.contains(&inode_id);
+        let mut data_blocks = 0;
+        for extent_record in extent_records.iter() {
+            match self.check_chunk(extent_record.address()) {
+                Ok(chunk_header) => {
+                    let chunk_end = extent_record.chunk_index() * chunk_span
+                                    + chunk_header.raw_len() as u64;
+                    if chunk_end > inode.size() && !is_orphan {
+                        self.error(format!("inode {}: extent {} exceeds the end of \
+                                            data",
+                                           inode_id,
+                                           extent_record.chunk_index()));
+                    }
+                    data_blocks +=
+                        self.use_chunk(extent_record.address(), &chunk_header, inode_id);
+                },
+                Err(MxfsError::DeviceError) => return Err(MxfsError::DeviceError),
+                Err(error) => self.error(format!("inode {}: extent {} at block {}: {}",
+                                                 inode_id,
+                                                 extent_record.chunk_index(),
+                                                 extent_record.address(),
+                                                 error))
+            }
+        }
+        Ok(data_blocks)
+    }
+
+    /**
+     * Reads, verifies and decompresses the data extent at the given block
+     */
+    fn check_chunk(&mut self, address: u64) -> MxfsResult<ChunkHeader> {
+        if !self.m_store.is_data_range(address, 1) {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let first_block = self.m_store.read_block(address)?;
+        let chunk_header = ChunkHeader::decode(&first_block)?;
+
+        let block_size = self.m_super_block.block_size();
+        if chunk_header.blocks_exp() > self.m_super_block.chunk_exp()
+           || !self.m_store.is_data_range(address, chunk_header.blocks_count())
+           || (chunk_header.stored_len() + C_CHUNK_HEADER_SIZE) as u64
+              > chunk_header.blocks_count() * block_size
+           || chunk_header.raw_len() as u64 > self.m_super_block.chunk_span()
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let mut raw_chunk = vec![0; C_CHUNK_HEADER_SIZE + chunk_header.stored_len()];
+        self.m_store.read_bytes(address, &mut raw_chunk)?;
+
+        let stored_data = &raw_chunk[C_CHUNK_HEADER_SIZE..];
+        if chunk_header.crc().is_some_and(|crc| crc32(stored_data) != crc) {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let mut chunk_data = vec![0; chunk_header.raw_len()];
+        chunk_header.compression()
+                    .compressor()
+                    .decompress(stored_data, &mut chunk_data)?;
+        Ok(chunk_header)
+    }
+
+    /**
+     * Counts a reference to the given data extent, returning its blocks
+     */
+    fn use_chunk(&mut self,
+                 address: u64,
+                 chunk_header: &ChunkHeader,
+                 inode_id: InodeId)
+                 -> u64 {
+        let blocks_count = chunk_header.blocks_count();
+
+        match self.m_extents.get_mut(&address) {
+            Some(extent_usage) => extent_usage.m_references += 1,
+            None => {
+                self.m_extents
+                    .insert(address,
+                            ExtentUsage { m_ref_count: chunk_header.ref_count(),
+                                          m_references: 1 });
+                for block in address..address + blocks_count {
+                    self.mark_used(block, inode_id, "extent");
+                }
+            }
+        }
+        blocks_count
+    }
+
+    /**
+     * Compares the link counts of the inodes with the found references and
+     * checks that every referenced inode is reachable from the root
+     */
+    fn check_references(&mut self,
+                        inodes: &BTreeMap<InodeId, Inode>,
+                        references: &BTreeMap<InodeId, InodeReferences>) {
+        let root_id = self.m_super_block.root_inode();
+        let orphans_id = self.m_super_block.orphans_inode();
+
+        for (inode_id, inode) in inodes.iter() {
+            let count = references.get(inode_id)
+                                  .map_or(0, |inode_references| inode_references.m_count);
+
+            if *inode_id == root_id || *inode_id == orphans_id {
+                if count > 0 {
+                    self.error(format!("directory {}: linked {} times but reserved",
+                                       inode_id, count));
+                }
+                continue;
+            }
+
+            if count == 0 {
+                if inode.links() != 0 {
+                    self.error(format!("inode {}: not referenced by any directory",
+                                       inode_id));
+                } else if !self.m_orphans.contains(inode_id) {
+                    self.error(format!("inode {}: unlinked but not an orphan", inode_id));
+                }
+                continue;
+            }
+
+            if inode.inode_type() == InodeType::Dir && count > 1 {
+                self.error(format!("directory {}: linked {} times", inode_id, count));
+            }
+            if inode.links() as u64 != count {
+                self.error(format!("inode {}: has {} links but {} references",
+                                   inode_id,
+                                   inode.links(),
+                                   count));
+            }
+        }
+
+        /* the directories detached from the root form cycles */
+        let mut reachable = BTreeSet::new();
+        let mut pending_dirs = vec![root_id];
+        while let Some(dir_id) = pending_dirs.pop() {
+            if !reachable.insert(dir_id) {
+                continue;
+            }
+
+            for (inode_id, inode_references) in references.iter() {
+                if inode_references.m_dirs.contains(&dir_id)
+                   && !reachable.contains(inode_id)
+                {
+                    if inodes.get(inode_id)
+                             .is_some_and(|inode| inode.inode_type() == InodeType::Dir)
+                    {
+                        pending_dirs.push(*inode_id);
+                    } else {
+                        reachable.insert(*inode_id);
+                    }
+                }
+            }
+        }
+
+        for inode_id in references.keys() {
+            if inodes.contains_key(inode_id) && !reachable.contains(inode_id) {
+                self.error(format!("inode {}: not reachable from the root", inode_id));
+            }
+        }
+    }
+
+    /**
+     * Compares the reference counts of the data extents with the found
+     * references
+     */
+    fn check_extents(&mut self) {
+        let mut mismatches = Vec::new();
+        for (address, extent_usage) in self.m_extents.iter() {
+            if extent_usage.m_ref_count as u64 != extent_usage.m_references {
+                mismatches.push(format!("extent at block {}: counts {} references but \
+                                         has {}",
+                                        address,
+                                        extent_usage.m_ref_count,
+                                        extent_usage.m_references));
+            }
+        }
+
+        for mismatch in mismatches.into_iter() {
+            self.error(mismatch);
+        }
+    }
+
+    /**
+     * Compares the bitmaps of the pools with the blocks in use
+     */
+    fn check_bitmaps(&mut self) -> MxfsResult<()> {
+        let block_size = self.m_super_block.block_size();
+        let bits_per_block = block_size * 8;
+
+        let mut used_marked_free = Vec::new();
+        let mut leaked_blocks = 0;
+        for pool in self.m_store.m_pools.iter() {
+            let pool_blocks = pool.blocks();
+            for (i, bitmap_block) in pool.bitmap().enumerate() {
+                let raw_bitmap = self.m_store.read_block(bitmap_block)?;
+                let first_bit = i as u64 * bits_per_block;
+                let bits_count =
+                    (pool_blocks.end - pool_blocks.start - first_bit).min(bits_per_block);
+
+                self.m_report.m_free_blocks +=
+                    count_zero_bits(&raw_bitmap, bits_count as usize);
+                for bit_index in 0..bits_count as usize {
+                    let block = pool_blocks.start + first_bit + bit_index as u64;
+                    let is_marked_used =
+                        raw_bitmap[bit_index / 8] & (1 << (bit_index % 8)) != 0;
+
+                    match (is_marked_used, self.m_used_blocks.contains(&block)) {
+                        (false, true) => used_marked_free.push(block),
+                        (true, false) => leaked_blocks += 1,
+                        _ => {}
+                    }
+                }
+            }
+            self.m_report.m_used_blocks += pool_blocks.end - pool_blocks.start;
+        }
+        self.m_report.m_used_blocks -= self.m_report.m_free_blocks;
+
+        if let Some(first_block) = used_marked_free.first() {
+            self.error(format!("{} blocks in use are marked as free, the first is {}",
+                               used_marked_free.len(),
+                               first_block));
+        }
+        if leaked_blocks > 0 {
+            self.warning(format!("{} blocks are marked as used but not referenced",
+                                 leaked_blocks));
+        }
+        Ok(())
+    }
+
+    /**
+     * Marks the given block as used by the given inode
+     */
+    fn mark_used(&mut self, block: u64, inode_id: InodeId, usage: &str) {
+        if !self.m_used_blocks.insert(block) {
+            self.error(format!("inode {}: {} block {} is already in use",
+                               inode_id, usage, block));
+        }
+    }
+
+    /**
+     * Reports the given damage
+     */
+    fn error(&mut self, message: String) {
+        self.m_report.m_errors.push(message);
+    }
+
+    /**
+     * Reports the given anomaly
+     */
+    fn warning(&mut self, message: String) {
+        self.m_report.m_warnings.push(message);
+    }
+}
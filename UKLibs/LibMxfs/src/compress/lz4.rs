@@ -0,0 +1,208 @@
+/*! LZ4 block compression */
+
+use alloc::vec::Vec;
+
+use crate::{
+    bytes::{
+        read_u16,
+        read_u32
+    },
+    compress::TCompressor,
+    MxfsError,
+    MxfsResult
+};
+
+/* shortest match which can be encoded */
+const C_MATCH_LEN_MIN: usize = 4;
+
+/* the last bytes of the block are always literals */
+const C_LAST_LITERALS: usize = 5;
+
+/* the last match must start before this distance from the end */
+const C_MATCH_START_LIMIT: usize = 12;
+
+/* farthest match which can be encoded */
+const C_MATCH_OFFSET_MAX: usize = 0xFFFF;
+
+/* the hash table has 2^C_HASH_LOG entries */
+const C_HASH_LOG: u32 = 12;
+
+/* a length nibble with this value continues into the following bytes */
+const C_LEN_NIBBLE_MAX: usize = 15;
+
+/**
+ * `TCompressor` of `Compression::Lz4`.
+ *
+ * Produces raw LZ4 blocks (without the frame format) with a greedy
+ * single-probe match finder, which favours speed over the ratio
+ *
+ * [`Compression::Lz4`]: crate::compress::Compression::Lz4
+ */
+pub struct Lz4Compressor;
+
+impl TCompressor for Lz4Compressor {
+    fn compress(&self, data: &[u8], compressed: &mut Vec<u8>) -> bool {
+        let compressed_start = compressed.len();
+        let mut hash_table = vec![0u32; 1 << C_HASH_LOG];
+
+        let mut anchor = 0;
+        let mut position = 0;
+        if data.len() > C_MATCH_START_LIMIT {
+            let match_start_end = data.len() - C_MATCH_START_LIMIT;
+            let match_end_limit = data.len() - C_LAST_LITERALS;
+
+            while position < match_start_end {
+                let sequence = read_u32(data, position);
+                let hash_index = hash_of(sequence);
+                let candidate = hash_table[hash_index] as usize;
+                hash_table[hash_index] = position as u32;
+
+                if candidate >= position
+                   || position - candidate > C_MATCH_OFFSET_MAX
+                   || read_u32(data, candidate) != sequence
+                {
+                    position += 1;
+                    continue;
+                }
+
+                let mut match_len = C_MATCH_LEN_MIN;
+                while position + match_len < match_end_limit
+                      && data[candidate + match_len] == data[position + match_len]
+                {
+                    match_len += 1;
+                }
+
+                write_sequence(compressed,
+                               &data[anchor..position],
+                               Some((position - candidate, match_len)));
+                position += match_len;
+                anchor = position;
+
+                if compressed.len() - compressed_start >= data.len() {
+                    return false;
+                }
+            }
+        }
+
+        write_sequence(compressed, &data[anchor..], None);
+        compressed.len() - compressed_start < data.len()
+    }
+
+    fn decompress(&self, compressed: &[u8], data: &mut [u8]) -> MxfsResult<()> {
+        let mut input = 0;
+        let mut output = 0;
+        while input < compressed.len() {
+            let token = compressed[input] as usize;
+            input += 1;
+
+            /* copy the literals */
+            let literals_len = read_len(compressed, &mut input, token >> 4)?;
+            let literals = compressed.get(input..input + literals_len)
+                                     .ok_or(MxfsError::Corrupted)?;
+            data.get_mut(output..output + literals_len)
+                .ok_or(MxfsError::Corrupted)?
+                .copy_from_slice(literals);
+            input += literals_len;
+            output += literals_len;
+
+            /* the last sequence has no match */
+            if input == compressed.len() {
+                break;
+            }
+
+            if input + 2 > compressed.len() {
+                return Err(MxfsError::Corrupted);
+            }
+            let match_offset = read_u16(compressed, input) as usize;
+            input += 2;
+
+            let match_len =
+                read_len(compressed, &mut input, token & 0xF)? + C_MATCH_LEN_MIN;
+            if match_offset == 0
+               || match_offset > output
+               || output + match_len > data.len()
+            {
+                return Err(MxfsError::Corrupted);
+            }
+
+            /* the match may overlap the bytes it produces */
+            for _ in 0..match_len {
+                data[output] = data[output - match_offset];
+                output += 1;
+            }
+        }
+
+        if output != data.len() {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Returns the index of the hash table for the given 4 bytes sequence
+ */
+fn hash_of(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - C_HASH_LOG)) as usize
+}
+
+/**
+ * Appends a sequence made by the given literals and the given match,
+ * described by its offset and its length
+ */
+fn write_sequence(compressed: &mut Vec<u8>,
+                  literals: &[u8],
+                  match_info: Option<(usize, usize)>) {
+    let match_len_nibble =
+        match_info.map_or(0, |(_, match_len)| match_len - C_MATCH_LEN_MIN);
+
+    let token = (literals.len().min(C_LEN_NIBBLE_MAX) << 4)
+                | match_len_nibble.min(C_LEN_NIBBLE_MAX);
+    compressed.push(token as u8);
+
+    write_len(compressed, literals.len());
+    compressed.extend_from_slice(literals);
+
+    if let Some((match_offset, _)) = match_info {
+        compressed.extend_from_slice(&(match_offset as u16).to_le_bytes());
+        write_len(compressed, match_len_nibble);
+    }
+}
+
+/**
+ * Appends the bytes which continue the given length nibble
+ */
+fn write_len(compressed: &mut Vec<u8>, len: usize) {
+    if len < C_LEN_NIBBLE_MAX {
+        return;
+    }
+
+    let mut remaining_len = len - C_LEN_NIBBLE_MAX;
+    while remaining_len >= 0xFF {
+        compressed.push(0xFF);
+        remaining_len -= 0xFF;
+    }
+    compressed.push(remaining_len as u8);
+}
+
+/**
+ * Reads the bytes which continue the given length nibble
+ */
+fn read_len(compressed: &[u8],
+            input: &mut usize,
+            len_nibble: usize)
+            -> MxfsResult<usize> {
+    let mut len = len_nibble;
+    if len_nibble == C_LEN_NIBBLE_MAX {
+        loop {
+            let len_byte = *compressed.get(*input).ok_or(MxfsError::Corrupted)?;
+            *input += 1;
+
+            len += len_byte as usize;
+            if len_byte != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,116 @@
+/*! Per-extent compression */
+
+use alloc::vec::Vec;
+
+use crate::{
+    compress::lz4::Lz4Compressor,
+    MxfsError,
+    MxfsResult
+};
+
+pub mod lz4;
+
+/**
+ * Lists the compression algorithms of the data extents, the value is
+ * stored into the chunk header flags
+ */
+#[repr(u8)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum Compression {
+    /**
+     * The data is stored as is
+     */
+    None = 0,
+
+    /**
+     * The data is stored as an LZ4 block
+     */
+    Lz4  = 1
+}
+
+impl Compression /* Constructors */ {
+    /**
+     * Constructs the `Compression` stored with the given raw value
+     */
+    pub fn from_raw(raw_compression: u8) -> MxfsResult<Self> {
+        match raw_compression {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Lz4),
+            _ => Err(MxfsError::Corrupted)
+        }
+    }
+
+    /**
+     * Constructs the `Compression` with the given name
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "lz4" => Some(Self::Lz4),
+            _ => None
+        }
+    }
+}
+
+impl Compression /* Getters */ {
+    /**
+     * Returns the `TCompressor` which implements this algorithm
+     */
+    pub fn compressor(&self) -> &'static dyn TCompressor {
+        match self {
+            Self::None => &NoCompressor,
+            Self::Lz4 => &Lz4Compressor
+        }
+    }
+
+    /**
+     * Returns the name of the algorithm
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Lz4 => "lz4"
+        }
+    }
+}
+
+/**
+ * Interface implemented by the compression algorithms of the data
+ * extents
+ */
+pub trait TCompressor {
+    /**
+     * Appends the compressed form of the given data to the given buffer.
+     *
+     * Returns `false` when the compressed form would not be smaller than
+     * the data, in which case the content of the buffer is meaningless
+     */
+    fn compress(&self, data: &[u8], compressed: &mut Vec<u8>) -> bool;
+
+    /**
+     * Decompresses the given data, filling exactly the given buffer
+     */
+    fn decompress(&self, compressed: &[u8], data: &mut [u8]) -> MxfsResult<()>;
+}
+
+/**
+ * `TCompressor` of `Compression::None`
+ */
+pub struct NoCompressor;
+
+impl TCompressor for NoCompressor {
+    fn compress(&self, _data: &[u8], _compressed: &mut Vec<u8>) -> bool {
+        false
+    }
+
+    fn decompress(&self, compressed: &[u8], data: &mut [u8]) -> MxfsResult<()> {
+        if compressed.len() != data.len() {
+            return Err(MxfsError::Corrupted);
+        }
+
+        data.copy_from_slice(compressed);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+/*! CRC-32 checksums */
+
+/* reversed polynomial of the IEEE 802.3 CRC-32 */
+const C_CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/* CRC-32 of each byte value */
+const C_CRC32_TABLE: [u32; 256] = crc32_table();
+
+/**
+ * Incremental IEEE 802.3 CRC-32, the same used by zlib and Ethernet
+ */
+pub struct Crc32 {
+    m_state: u32
+}
+
+impl Crc32 /* Constructors */ {
+    /**
+     * Constructs an empty `Crc32`
+     */
+    pub fn new() -> Self {
+        Self { m_state: !0 }
+    }
+}
+
+impl Crc32 /* Methods */ {
+    /**
+     * Accumulates the given bytes
+     */
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            let table_index = (self.m_state ^ *byte as u32) & 0xFF;
+            self.m_state = C_CRC32_TABLE[table_index as usize] ^ (self.m_state >> 8);
+        }
+    }
+}
+
+impl Crc32 /* Getters */ {
+    /**
+     * Returns the checksum of the bytes accumulated until now
+     */
+    pub fn value(&self) -> u32 {
+        !self.m_state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Returns the CRC-32 of the given bytes
+ */
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.value()
+}
+
+/**
+ * Returns the CRC-32 of the given block, computed with the 4 bytes at the
+ * given offset, which store the checksum itself, as zeros
+ */
+pub fn crc32_skipping(data: &[u8], crc_offset: usize) -> u32 {
+    let mut crc = Crc32::new();
+
+    crc.update(&data[..crc_offset]);
+    crc.update(&[0; 4]);
+    crc.update(&data[crc_offset + 4..]);
+    crc.value()
+}
+
+/**
+ * Computes the `C_CRC32_TABLE` at compile time
+ */
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                C_CRC32_POLYNOMIAL ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
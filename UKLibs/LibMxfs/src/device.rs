@@ -0,0 +1,39 @@
+/*! MXFS storage interface */
+
+use crate::MxfsResult;
+
+/**
+ * Interface implemented by the storages which contain an MXFS volume.
+ *
+ * The `Kernel` implements it over its block devices, while the host tools
+ * implement it over the image files
+ */
+pub trait TMxfsDevice {
+    /**
+     * Returns the size in bytes of the storage
+     */
+    fn size(&self) -> u64;
+
+    /**
+     * Fills the given buffer with the bytes at the given offset
+     */
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> MxfsResult<()>;
+
+    /**
+     * Writes the given buffer at the given offset
+     */
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> MxfsResult<()>;
+
+    /**
+     * Waits until the written bytes are persistent, the journal relies on
+     * it to order the writes
+     */
+    fn flush(&self) -> MxfsResult<()>;
+
+    /**
+     * Returns whether the storage refuses the writes
+     */
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,166 @@
+/*! MXFS directory entries */
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+use core::str;
+
+use crate::{
+    btree::TTreeRecord,
+    bytes::read_u64,
+    inode::{
+        InodeId,
+        InodeType
+    },
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * Maximum length in bytes of the names of the directory entries
+ */
+pub const C_NAME_LEN_MAX: usize = 255;
+
+/* FNV-1a parameters */
+const C_FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const C_FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/**
+ * Key of the `DirRecord`s, which sorts the entries by the hash of their
+ * name and then by the name itself, so the colliding names coexist
+ */
+pub type DirKey = (u64, String);
+
+/**
+ * Record of the directory B-tree, which links a name to an inode
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct DirRecord {
+    m_name_hash: u64,
+    m_name: String,
+    m_inode: InodeId,
+    m_inode_type: InodeType
+}
+
+impl DirRecord /* Constructors */ {
+    /**
+     * Constructs a `DirRecord` which links the given name to the given
+     * inode
+     */
+    pub fn new(name: &str, inode: InodeId, inode_type: InodeType) -> Self {
+        Self { m_name_hash: name_hash(name),
+               m_name: String::from(name),
+               m_inode: inode,
+               m_inode_type: inode_type }
+    }
+}
+
+impl DirRecord /* Getters */ {
+    /**
+     * Returns the hash of the name, as stored on disk
+     */
+    pub fn name_hash(&self) -> u64 {
+        self.m_name_hash
+    }
+
+    /**
+     * Returns the name of the entry
+     */
+    pub fn name(&self) -> &str {
+        &self.m_name
+    }
+
+    /**
+     * Returns the linked inode
+     */
+    pub fn inode(&self) -> InodeId {
+        self.m_inode
+    }
+
+    /**
+     * Returns the `InodeType` of the linked inode
+     */
+    pub fn inode_type(&self) -> InodeType {
+        self.m_inode_type
+    }
+}
+
+impl TTreeRecord for DirRecord {
+    type Key = DirKey;
+
+    const C_TREE_KIND: u8 = 2;
+
+    fn key(&self) -> Self::Key {
+        (self.m_name_hash, self.m_name.clone())
+    }
+
+    fn encoded_size(&self) -> usize {
+        self.m_name.len() + 18
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.m_name_hash.to_le_bytes());
+        buffer.push(self.m_name.len() as u8);
+        buffer.extend_from_slice(self.m_name.as_bytes());
+        buffer.extend_from_slice(&self.m_inode.to_le_bytes());
+        buffer.push(self.m_inode_type as u8);
+    }
+
+    fn decode(buffer: &[u8]) -> MxfsResult<(Self, usize)> {
+        let ((name_hash, name), key_size) = Self::decode_key(buffer)?;
+        if buffer.len() < key_size + 9 {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let record = Self { m_name_hash: name_hash,
+                            m_name: name,
+                            m_inode: read_u64(buffer, key_size),
+                            m_inode_type: InodeType::from_raw(buffer[key_size + 8])? };
+        Ok((record, key_size + 9))
+    }
+
+    fn key_encoded_size(key: &Self::Key) -> usize {
+        9 + key.1.len()
+    }
+
+    fn encode_key(key: &Self::Key, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&key.0.to_le_bytes());
+        buffer.push(key.1.len() as u8);
+        buffer.extend_from_slice(key.1.as_bytes());
+    }
+
+    fn decode_key(buffer: &[u8]) -> MxfsResult<(Self::Key, usize)> {
+        if buffer.len() < 9 {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let name_len = buffer[8] as usize;
+        let raw_name = buffer.get(9..9 + name_len).ok_or(MxfsError::Corrupted)?;
+        let name = str::from_utf8(raw_name).map_err(|_| MxfsError::Corrupted)?;
+        Ok(((read_u64(buffer, 0), String::from(name)), 9 + name_len))
+    }
+}
+
+/**
+ * Checks whether the given name can be stored into a directory
+ */
+pub fn check_name(name: &str) -> MxfsResult<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(&['/', '\0'][..]) {
+        return Err(MxfsError::InvalidArgument);
+    }
+    if name.len() > C_NAME_LEN_MAX {
+        return Err(MxfsError::NameTooLong);
+    }
+    Ok(())
+}
+
+/**
+ * Returns the FNV-1a hash of the given name
+ */
+pub fn name_hash(name: &str) -> u64 {
+    name.bytes().fold(C_FNV_OFFSET_BASIS, |hash, byte| {
+                    (hash ^ byte as u64).wrapping_mul(C_FNV_PRIME)
+                })
+}
@@ -0,0 +1,277 @@
+/*! MXFS data extents */
+
+use alloc::vec::Vec;
+
+use crate::{
+    btree::TTreeRecord,
+    bytes::{
+        read_u32,
+        read_u64,
+        write_u32,
+        write_u64
+    },
+    compress::Compression,
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * Size in bytes of the `ChunkHeader` which opens each data extent
+ */
+pub const C_CHUNK_HEADER_SIZE: usize = 16;
+
+/**
+ * Maximum value of the BLK-EXP field of the `ChunkHeader`
+ */
+pub const C_CHUNK_EXP_MAX: u8 = 15;
+
+/* FLAGS bit which disables the data checksum */
+const C_FLAG_NO_CRC: u64 = 1 << 3;
+
+/* FLAGS bits which store the <Compression> */
+const C_FLAGS_COMPRESSION_MASK: u64 = 0x7;
+
+/**
+ * Header of a data extent (called chunk by the specification).
+ *
+ * The first 64 bits pack the reference count (bits 0..16), the BLK-EXP
+ * (bits 16..20), the FLAGS (bits 20..24) and the CRC-32 of the stored
+ * data (bits 32..64). The second 64 bits store the length of the data
+ * as stored on disk and its length once decompressed
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct ChunkHeader {
+    m_ref_count: u16,
+    m_blocks_exp: u8,
+    m_compression: Compression,
+    m_has_crc: bool,
+    m_crc: u32,
+    m_stored_len: u32,
+    m_raw_len: u32
+}
+
+impl ChunkHeader /* Constructors */ {
+    /**
+     * Constructs a `ChunkHeader` referenced once
+     */
+    pub fn new(blocks_exp: u8,
+               compression: Compression,
+               crc: Option<u32>,
+               stored_len: u32,
+               raw_len: u32)
+               -> Self {
+        Self { m_ref_count: 1,
+               m_blocks_exp: blocks_exp,
+               m_compression: compression,
+               m_has_crc: crc.is_some(),
+               m_crc: crc.unwrap_or(0),
+               m_stored_len: stored_len,
+               m_raw_len: raw_len }
+    }
+
+    /**
+     * Decodes the `ChunkHeader` at the beginning of the given buffer
+     */
+    pub fn decode(raw_header: &[u8]) -> MxfsResult<Self> {
+        let first_word = read_u64(raw_header, 0);
+        let flags = (first_word >> 20) & 0xF;
+
+        /* the bits between the FLAGS and the CRC are reserved */
+        if (first_word >> 24) & 0xFF != 0 {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let compression =
+            Compression::from_raw((flags & C_FLAGS_COMPRESSION_MASK) as u8)?;
+        let header = Self { m_ref_count: first_word as u16,
+                            m_blocks_exp: ((first_word >> 16) & 0xF) as u8,
+                            m_compression: compression,
+                            m_has_crc: flags & C_FLAG_NO_CRC == 0,
+                            m_crc: (first_word >> 32) as u32,
+                            m_stored_len: read_u32(raw_header, 8),
+                            m_raw_len: read_u32(raw_header, 12) };
+
+        /* an unreferenced extent is free space */
+        if header.m_ref_count == 0 {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(header)
+    }
+}
+
+impl ChunkHeader /* Methods */ {
+    /**
+     * Encodes this `ChunkHeader` at the beginning of the given buffer
+     */
+    pub fn encode(&self, raw_header: &mut [u8]) {
+        let mut flags = self.m_compression as u64;
+        if !self.m_has_crc {
+            flags |= C_FLAG_NO_CRC;
+        }
+
+        let first_word = self.m_ref_count as u64
+                         | (self.m_blocks_exp as u64) << 16
+                         | flags << 20
+                         | (self.m_crc as u64) << 32;
+        write_u64(raw_header, 0, first_word);
+        write_u32(raw_header, 8, self.m_stored_len);
+        write_u32(raw_header, 12, self.m_raw_len);
+    }
+}
+
+impl ChunkHeader /* Getters */ {
+    /**
+     * Returns the amount of extent tree entries which reference the extent
+     */
+    pub fn ref_count(&self) -> u16 {
+        self.m_ref_count
+    }
+
+    /**
+     * Returns the BLK-EXP field
+     */
+    pub fn blocks_exp(&self) -> u8 {
+        self.m_blocks_exp
+    }
+
+    /**
+     * Returns the amount of blocks of the extent, header included
+     */
+    pub fn blocks_count(&self) -> u64 {
+        1 << self.m_blocks_exp
+    }
+
+    /**
+     * Returns the `Compression` of the stored data
+     */
+    pub fn compression(&self) -> Compression {
+        self.m_compression
+    }
+
+    /**
+     * Returns the CRC-32 of the stored data, `None` when the extent was
+     * written with the check disabled
+     */
+    pub fn crc(&self) -> Option<u32> {
+        if self.m_has_crc {
+            Some(self.m_crc)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Returns the length in bytes of the data as stored on disk
+     */
+    pub fn stored_len(&self) -> usize {
+        self.m_stored_len as usize
+    }
+
+    /**
+     * Returns the length in bytes of the decompressed data
+     */
+    pub fn raw_len(&self) -> usize {
+        self.m_raw_len as usize
+    }
+}
+
+impl ChunkHeader /* Setters */ {
+    /**
+     * Updates the amount of extent tree entries which reference the extent
+     */
+    pub fn set_ref_count(&mut self, ref_count: u16) {
+        self.m_ref_count = ref_count;
+    }
+}
+
+/**
+ * Returns the smallest BLK-EXP of an extent which stores the given amount
+ * of bytes after its `ChunkHeader`
+ */
+pub fn blocks_exp_for(stored_len: usize, block_size: u64) -> u8 {
+    let needed_blocks =
+        (stored_len as u64 + C_CHUNK_HEADER_SIZE as u64).div_ceil(block_size);
+    needed_blocks.next_power_of_two().trailing_zeros() as u8
+}
+
+/**
+ * Record of the extent tree of a file, which maps the index of a span of
+ * `chunk_span()` bytes of the file to the data extent which stores it.
+ *
+ * The spans without record are holes, which read as zeros
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct ExtentRecord {
+    m_chunk_index: u64,
+    m_address: u64
+}
+
+impl ExtentRecord /* Constructors */ {
+    /**
+     * Constructs an `ExtentRecord` which maps the given span to the data
+     * extent at the given block
+     */
+    pub fn new(chunk_index: u64, address: u64) -> Self {
+        Self { m_chunk_index: chunk_index,
+               m_address: address }
+    }
+}
+
+impl ExtentRecord /* Getters */ {
+    /**
+     * Returns the index of the span of the file
+     */
+    pub fn chunk_index(&self) -> u64 {
+        self.m_chunk_index
+    }
+
+    /**
+     * Returns the first block of the data extent
+     */
+    pub fn address(&self) -> u64 {
+        self.m_address
+    }
+}
+
+impl TTreeRecord for ExtentRecord {
+    type Key = u64;
+
+    const C_TREE_KIND: u8 = 1;
+
+    fn key(&self) -> Self::Key {
+        self.m_chunk_index
+    }
+
+    fn encoded_size(&self) -> usize {
+        16
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.m_chunk_index.to_le_bytes());
+        buffer.extend_from_slice(&self.m_address.to_le_bytes());
+    }
+
+    fn decode(buffer: &[u8]) -> MxfsResult<(Self, usize)> {
+        if buffer.len() < 16 {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok((Self::new(read_u64(buffer, 0), read_u64(buffer, 8)), 16))
+    }
+
+    fn key_encoded_size(_key: &Self::Key) -> usize {
+        8
+    }
+
+    fn encode_key(key: &Self::Key, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&key.to_le_bytes());
+    }
+
+    fn decode_key(buffer: &[u8]) -> MxfsResult<(Self::Key, usize)> {
+        if buffer.len() < 8 {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok((read_u64(buffer, 0), 8))
+    }
+}
@@ -0,0 +1,325 @@
+/*! MXFS volume format */
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+use core::ops::Range;
+
+use crate::{
+    compress::Compression,
+    device::TMxfsDevice,
+    extent::C_CHUNK_EXP_MAX,
+    inode::{
+        Inode,
+        InodeType,
+        C_INODE_SIZE
+    },
+    journal::Journal,
+    pool::PoolHeader,
+    super_block::{
+        SuperBlock,
+        SuperBlockGeometry,
+        C_BLOCK_SIZE_SHIFT_MAX,
+        C_BLOCK_SIZE_SHIFT_MIN
+    },
+    MxfsError,
+    MxfsResult
+};
+
+/* minimum amount of blocks of the journal */
+const C_JOURNAL_BLOCKS_MIN: u64 = 32;
+
+/* maximum amount of blocks of the journal chosen by default */
+const C_JOURNAL_BLOCKS_DEFAULT_MAX: u64 = 4096;
+
+/* minimum amount of inodes of a volume */
+const C_INODES_MIN: u64 = 64;
+
+/* bytes of volume for each inode chosen by default */
+const C_BYTES_PER_INODE: u64 = 16 * 1024;
+
+/* amount of blocks zeroed by each write of the format */
+const C_ZERO_BLOCKS_BATCH: u64 = 64;
+
+/**
+ * Collects the parameters of a new MXFS volume.
+ *
+ * The missing sizes are chosen from the size of the `TMxfsDevice`
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct FormatOptions {
+    m_block_size: u64,
+    m_chunk_exp: u8,
+    m_compression: Compression,
+    m_inodes_count: Option<u64>,
+    m_journal_blocks: Option<u64>,
+    m_pool_blocks: Option<u64>,
+    m_label: String,
+    m_bad_blocks: Vec<u64>,
+    m_creat_time: u64
+}
+
+impl FormatOptions /* Constructors */ {
+    /**
+     * Constructs the `FormatOptions` with 4KiB blocks, 64KiB chunks and
+     * `Compression::Lz4`
+     */
+    pub fn new() -> Self {
+        Self { m_block_size: 4096,
+               m_chunk_exp: 4,
+               m_compression: Compression::Lz4,
+               m_inodes_count: None,
+               m_journal_blocks: None,
+               m_pool_blocks: None,
+               m_label: String::new(),
+               m_bad_blocks: Vec::new(),
+               m_creat_time: 0 }
+    }
+}
+
+impl FormatOptions /* Setters */ {
+    /**
+     * Sets the size of the blocks, a power of two from 1KiB to 64KiB
+     */
+    pub fn with_block_size(&mut self, block_size: u64) -> &mut Self {
+        self.m_block_size = block_size;
+        self
+    }
+
+    /**
+     * Sets the exponent of the maximum amount of blocks of a data extent
+     */
+    pub fn with_chunk_exp(&mut self, chunk_exp: u8) -> &mut Self {
+        self.m_chunk_exp = chunk_exp;
+        self
+    }
+
+    /**
+     * Sets the default `Compression` of the new inodes
+     */
+    pub fn with_compression(&mut self, compression: Compression) -> &mut Self {
+        self.m_compression = compression;
+        self
+    }
+
+    /**
+     * Sets the minimum amount of inodes of the volume
+     */
+    pub fn with_inodes(&mut self, inodes_count: u64) -> &mut Self {
+        self.m_inodes_count = Some(inodes_count);
+        self
+    }
+
+    /**
+     * Sets the amount of blocks of the journal
+     */
+    pub fn with_journal_blocks(&mut self, journal_blocks: u64) -> &mut Self {
+        self.m_journal_blocks = Some(journal_blocks);
+        self
+    }
+
+    /**
+     * Sets the amount of blocks of each extent pool
+     */
+    pub fn with_pool_blocks(&mut self, pool_blocks: u64) -> &mut Self {
+        self.m_pool_blocks = Some(pool_blocks);
+        self
+    }
+
+    /**
+     * Sets the label of the volume
+     */
+    pub fn with_label(&mut self, label: &str) -> &mut Self {
+        self.m_label = String::from(label);
+        self
+    }
+
+    /**
+     * Adds a block which must never be used
+     */
+    pub fn with_bad_block(&mut self, bad_block: u64) -> &mut Self {
+        self.m_bad_blocks.push(bad_block);
+        self
+    }
+
+    /**
+     * Sets the creation time, in nanoseconds since the Unix epoch, of the
+     * volume and of its root directory
+     */
+    pub fn with_creat_time(&mut self, creat_time: u64) -> &mut Self {
+        self.m_creat_time = creat_time;
+        self
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Writes a new empty MXFS volume, which covers the whole given
+ * `TMxfsDevice`, and returns its `SuperBlock`
+ */
+pub fn format(device: &dyn TMxfsDevice,
+              options: &FormatOptions)
+              -> MxfsResult<SuperBlock> {
+    if device.is_read_only() {
+        return Err(MxfsError::ReadOnly);
+    }
+
+    let block_size = options.m_block_size;
+    let block_size_shift = block_size.trailing_zeros() as u8;
+    if !block_size.is_power_of_two()
+       || !(C_BLOCK_SIZE_SHIFT_MIN..=C_BLOCK_SIZE_SHIFT_MAX).contains(&block_size_shift)
+       || options.m_chunk_exp > C_CHUNK_EXP_MAX
+    {
+        return Err(MxfsError::InvalidArgument);
+    }
+
+    let device_blocks = device.size() / block_size;
+    let journal_blocks = match options.m_journal_blocks {
+        Some(journal_blocks) => journal_blocks.max(C_JOURNAL_BLOCKS_MIN),
+        None => (device_blocks / 128).clamp(C_JOURNAL_BLOCKS_MIN,
+                                            C_JOURNAL_BLOCKS_DEFAULT_MAX)
+    };
+
+    /* the inode table is rounded to fill its last block */
+    let inodes_per_block = block_size / C_INODE_SIZE as u64;
+    let inodes_count = options.m_inodes_count
+                              .unwrap_or(device.size() / C_BYTES_PER_INODE)
+                              .max(C_INODES_MIN);
+    let inode_table_blocks = inodes_count.div_ceil(inodes_per_block);
+    let inodes_count = inode_table_blocks * inodes_per_block;
+
+    let inode_table_start = 1 + journal_blocks;
+    let first_pool = inode_table_start + inode_table_blocks;
+
+    /* each pool needs a header, a bitmap and room for a whole extent */
+    let pool_blocks = options.m_pool_blocks.unwrap_or(block_size * 8);
+    if pool_blocks < 3 + (1 << options.m_chunk_exp) {
+        return Err(MxfsError::InvalidArgument);
+    }
+
+    let mut pools = Vec::new();
+    let mut pool_start = first_pool;
+    while pool_start < device_blocks {
+        let pool_end = device_blocks.min(pool_start + pool_blocks);
+        let pool = PoolHeader::new(pool_start..pool_end, 0, block_size);
+        if pool.data_start() >= pool_end {
+            break;
+        }
+
+        pools.push(pool);
+        pool_start = pool_end;
+    }
+    if pools.is_empty() {
+        return Err(MxfsError::NoSpace);
+    }
+
+    /* link the pools and place the bad blocks */
+    let pools_count = pools.len();
+    for i in 0..pools_count - 1 {
+        let next_pool = pools[i + 1].blocks().start;
+        pools[i] = PoolHeader::new(pools[i].blocks(), next_pool, block_size);
+    }
+    for bad_block in options.m_bad_blocks.iter() {
+        let pool = pools.iter_mut()
+                        .find(|pool| pool.blocks().contains(bad_block))
+                        .ok_or(MxfsError::InvalidArgument)?;
+        pool.add_bad_block(*bad_block, block_size)?;
+    }
+
+    let blocks_count = pools[pools_count - 1].blocks().end;
+    let geometry = SuperBlockGeometry { m_block_size_shift: block_size_shift,
+                                        m_chunk_exp: options.m_chunk_exp,
+                                        m_compression: options.m_compression,
+                                        m_blocks_count: blocks_count,
+                                        m_first_pool: first_pool,
+                                        m_pool_blocks: pool_blocks,
+                                        m_root_inode: 1,
+                                        m_orphans_inode: 2,
+                                        m_inode_table_start: inode_table_start,
+                                        m_inodes_count: inodes_count,
+                                        m_journal_start: 1,
+                                        m_journal_blocks: journal_blocks };
+    let super_block = SuperBlock::new(&geometry, options.m_creat_time, &options.m_label)?;
+
+    Journal::new(super_block.journal(), block_size).clear(device)?;
+    zero_blocks(device, super_block.inode_table(), block_size)?;
+
+    /* the root and the orphans directories are their own parents */
+    let mut raw_inodes = vec![0; block_size as usize];
+    Inode::new(InodeType::Dir, options.m_compression, 1, options.m_creat_time)
+        .encode(&mut raw_inodes[..C_INODE_SIZE]);
+    Inode::new(InodeType::Dir, options.m_compression, 2, options.m_creat_time)
+        .encode(&mut raw_inodes[C_INODE_SIZE..2 * C_INODE_SIZE]);
+    device.write_at(inode_table_start * block_size, &raw_inodes)?;
+
+    for pool in pools.iter() {
+        write_pool(device, pool, block_size)?;
+    }
+
+    super_block.write(device)?;
+    device.flush()?;
+    Ok(super_block)
+}
+
+/**
+ * Writes the header and the bitmap of the given pool, where the blocks of
+ * the header, of the bitmap and the bad ones are used
+ */
+fn write_pool(device: &dyn TMxfsDevice,
+              pool: &PoolHeader,
+              block_size: u64)
+              -> MxfsResult<()> {
+    let pool_blocks = pool.blocks();
+    let bits_per_block = block_size * 8;
+
+    device.write_at(pool_blocks.start * block_size, &pool.encode(block_size))?;
+
+    let mut raw_bitmap =
+        vec![0u8; ((pool.data_start() - pool_blocks.start - 1) * block_size) as usize];
+    let mut set_used = |block: u64| {
+        let bit_index = (block - pool_blocks.start) as usize;
+        raw_bitmap[bit_index / 8] |= 1 << (bit_index % 8);
+    };
+
+    for block in pool_blocks.start..pool.data_start() {
+        set_used(block);
+    }
+    for bad_block in pool.bad_blocks() {
+        set_used(*bad_block);
+    }
+
+    /* the bits after the end of the pool are never free */
+    let bitmap_bits = (pool.data_start() - pool_blocks.start - 1) * bits_per_block;
+    for bit_index in pool_blocks.end - pool_blocks.start..bitmap_bits {
+        set_used(pool_blocks.start + bit_index);
+    }
+
+    device.write_at(pool.bitmap().start * block_size, &raw_bitmap)
+}
+
+/**
+ * Fills with zeros the given blocks
+ */
+fn zero_blocks(device: &dyn TMxfsDevice,
+               blocks: Range<u64>,
+               block_size: u64)
+               -> MxfsResult<()> {
+    let zeroes = vec![0; (C_ZERO_BLOCKS_BATCH * block_size) as usize];
+
+    let mut block = blocks.start;
+    while block < blocks.end {
+        let batch_blocks = (blocks.end - block).min(C_ZERO_BLOCKS_BATCH);
+
+        device.write_at(block * block_size,
+                        &zeroes[..(batch_blocks * block_size) as usize])?;
+        block += batch_blocks;
+    }
+    Ok(())
+}
@@ -0,0 +1,430 @@
+/*! MXFS inodes */
+
+use crate::{
+    bytes::{
+        read_u32,
+        read_u64,
+        write_u32,
+        write_u64
+    },
+    compress::Compression,
+    crc::crc32,
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * Size in bytes of each `Inode` of the inode table
+ */
+pub const C_INODE_SIZE: usize = 256;
+
+/**
+ * `Inode` flag which writes the data extents without checksum
+ */
+pub const C_INODE_FLAG_NO_CRC: u8 = 1 << 0;
+
+/**
+ * `Inode` flag which rewrites the data extents in place, when they are
+ * not shared and the new data fits them
+ */
+pub const C_INODE_FLAG_NO_COW: u8 = 1 << 1;
+
+/**
+ * `Inode` flag set when the protection grants were given explicitly,
+ * otherwise the system applies its defaults for the `InodeType`
+ */
+pub const C_INODE_FLAG_CUSTOM_GRANTS: u8 = 1 << 2;
+
+/* flags which can be changed by the users */
+const C_INODE_FLAGS_ALL: u8 =
+    C_INODE_FLAG_NO_CRC | C_INODE_FLAG_NO_COW | C_INODE_FLAG_CUSTOM_GRANTS;
+
+/* offset of the checksum, which covers all the bytes which precede it */
+const C_CRC_OFFSET: usize = C_INODE_SIZE - 4;
+
+/**
+ * Number of an `Inode`, which is its 1-based position into the inode
+ * table, `0` is never used
+ */
+pub type InodeId = u64;
+
+/**
+ * Lists the types of the `Inode`s
+ */
+#[repr(u8)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum InodeType {
+    /**
+     * The inode is not used
+     */
+    Free = 0,
+
+    /**
+     * Regular file, which data is described by an extent tree
+     */
+    File = 1,
+
+    /**
+     * Directory, which entries are kept by a directory B-tree
+     */
+    Dir  = 2,
+
+    /**
+     * Symbolic link, which data is the target path
+     */
+    Link = 3
+}
+
+impl InodeType /* Constructors */ {
+    /**
+     * Constructs the `InodeType` stored with the given raw value
+     */
+    pub fn from_raw(raw_inode_type: u8) -> MxfsResult<Self> {
+        match raw_inode_type {
+            0 => Ok(Self::Free),
+            1 => Ok(Self::File),
+            2 => Ok(Self::Dir),
+            3 => Ok(Self::Link),
+            _ => Err(MxfsError::Corrupted)
+        }
+    }
+}
+
+/**
+ * Entry of the inode table.
+ *
+ * The `tree_root` is the root block of the extent tree for the files and
+ * the links, and the root block of the directory B-tree for the
+ * directories, which use the `size` to count their entries. The times are
+ * in nanoseconds since the Unix epoch
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Inode {
+    m_inode_type: InodeType,
+    m_flags: u8,
+    m_compression: Compression,
+    m_links: u32,
+    m_size: u64,
+    m_parent: InodeId,
+    m_tree_root: u64,
+    m_blocks_used: u64,
+    m_os_user_id: u64,
+    m_os_group_id: u64,
+    m_prot_grants: u64,
+    m_creat_time: u64,
+    m_access_time: u64,
+    m_modify_time: u64,
+    m_change_time: u64
+}
+
+impl Inode /* Constructors */ {
+    /**
+     * Constructs a new `Inode` linked once into the given parent directory
+     */
+    pub fn new(inode_type: InodeType,
+               compression: Compression,
+               parent: InodeId,
+               time_stamp: u64)
+               -> Self {
+        Self { m_inode_type: inode_type,
+               m_flags: 0,
+               m_compression: compression,
+               m_links: 1,
+               m_size: 0,
+               m_parent: parent,
+               m_tree_root: 0,
+               m_blocks_used: 0,
+               m_os_user_id: 0,
+               m_os_group_id: 0,
+               m_prot_grants: 0,
+               m_creat_time: time_stamp,
+               m_access_time: time_stamp,
+               m_modify_time: time_stamp,
+               m_change_time: time_stamp }
+    }
+
+    /**
+     * Constructs an `InodeType::Free` `Inode`
+     */
+    pub fn new_free() -> Self {
+        Self::new(InodeType::Free, Compression::None, 0, 0)
+    }
+
+    /**
+     * Decodes the given raw `Inode`, the free ones are all zeros
+     */
+    pub fn decode(raw_inode: &[u8]) -> MxfsResult<Self> {
+        let inode_type = InodeType::from_raw(raw_inode[0])?;
+        if inode_type == InodeType::Free {
+            return if raw_inode.iter().all(|byte| *byte == 0) {
+                Ok(Self::new_free())
+            } else {
+                Err(MxfsError::Corrupted)
+            };
+        }
+
+        if crc32(&raw_inode[..C_CRC_OFFSET]) != read_u32(raw_inode, C_CRC_OFFSET) {
+            return Err(MxfsError::Corrupted);
+        }
+
+        Ok(Self { m_inode_type: inode_type,
+                  m_flags: raw_inode[1],
+                  m_compression: Compression::from_raw(raw_inode[2])?,
+                  m_links: read_u32(raw_inode, 4),
+                  m_size: read_u64(raw_inode, 8),
+                  m_parent: read_u64(raw_inode, 16),
+                  m_tree_root: read_u64(raw_inode, 24),
+                  m_blocks_used: read_u64(raw_inode, 32),
+                  m_os_user_id: read_u64(raw_inode, 40),
+                  m_os_group_id: read_u64(raw_inode, 48),
+                  m_prot_grants: read_u64(raw_inode, 56),
+                  m_creat_time: read_u64(raw_inode, 64),
+                  m_access_time: read_u64(raw_inode, 72),
+                  m_modify_time: read_u64(raw_inode, 80),
+                  m_change_time: read_u64(raw_inode, 88) })
+    }
+}
+
+impl Inode /* Methods */ {
+    /**
+     * Encodes this `Inode` into the given slot of the inode table
+     */
+    pub fn encode(&self, raw_inode: &mut [u8]) {
+        raw_inode[..C_INODE_SIZE].iter_mut().for_each(|byte| *byte = 0);
+        if self.m_inode_type == InodeType::Free {
+            return;
+        }
+
+        raw_inode[0] = self.m_inode_type as u8;
+        raw_inode[1] = self.m_flags;
+        raw_inode[2] = self.m_compression as u8;
+        write_u32(raw_inode, 4, self.m_links);
+        write_u64(raw_inode, 8, self.m_size);
+        write_u64(raw_inode, 16, self.m_parent);
+        write_u64(raw_inode, 24, self.m_tree_root);
+        write_u64(raw_inode, 32, self.m_blocks_used);
+        write_u64(raw_inode, 40, self.m_os_user_id);
+        write_u64(raw_inode, 48, self.m_os_group_id);
+        write_u64(raw_inode, 56, self.m_prot_grants);
+        write_u64(raw_inode, 64, self.m_creat_time);
+        write_u64(raw_inode, 72, self.m_access_time);
+        write_u64(raw_inode, 80, self.m_modify_time);
+        write_u64(raw_inode, 88, self.m_change_time);
+
+        let checksum = crc32(&raw_inode[..C_CRC_OFFSET]);
+        write_u32(raw_inode, C_CRC_OFFSET, checksum);
+    }
+
+    /**
+     * Updates the access and the modification times of the data
+     */
+    pub fn touch_data(&mut self, time_stamp: u64) {
+        self.m_access_time = time_stamp;
+        self.m_modify_time = time_stamp;
+        self.m_change_time = time_stamp;
+    }
+
+    /**
+     * Updates the modification time of the metadata
+     */
+    pub fn touch_info(&mut self, time_stamp: u64) {
+        self.m_change_time = time_stamp;
+    }
+}
+
+impl Inode /* Getters */ {
+    /**
+     * Returns the `InodeType`
+     */
+    pub fn inode_type(&self) -> InodeType {
+        self.m_inode_type
+    }
+
+    /**
+     * Returns the `C_INODE_FLAG_*` flags
+     */
+    pub fn flags(&self) -> u8 {
+        self.m_flags
+    }
+
+    /**
+     * Returns whether the given `C_INODE_FLAG_*` flag is set
+     */
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.m_flags & flag != 0
+    }
+
+    /**
+     * Returns the `Compression` of the new data extents
+     */
+    pub fn compression(&self) -> Compression {
+        self.m_compression
+    }
+
+    /**
+     * Returns the amount of directory entries which reference this inode
+     */
+    pub fn links(&self) -> u32 {
+        self.m_links
+    }
+
+    /**
+     * Returns the size in bytes of the data, or the amount of entries for
+     * the directories
+     */
+    pub fn size(&self) -> u64 {
+        self.m_size
+    }
+
+    /**
+     * Returns the parent directory of this inode.
+     *
+     * For the files and the symbolic links it is the directory which
+     * received the last link, which is only a hint once that link is removed
+     */
+    pub fn parent(&self) -> InodeId {
+        self.m_parent
+    }
+
+    /**
+     * Returns the root block of the tree of this inode, `0` when empty
+     */
+    pub fn tree_root(&self) -> u64 {
+        self.m_tree_root
+    }
+
+    /**
+     * Returns the amount of blocks used by the data extents and by the tree
+     */
+    pub fn blocks_used(&self) -> u64 {
+        self.m_blocks_used
+    }
+
+    /**
+     * Returns the owner user
+     */
+    pub fn os_user_id(&self) -> u64 {
+        self.m_os_user_id
+    }
+
+    /**
+     * Returns the owner group
+     */
+    pub fn os_group_id(&self) -> u64 {
+        self.m_os_group_id
+    }
+
+    /**
+     * Returns the raw protection grants, `None` when the defaults apply
+     */
+    pub fn prot_grants(&self) -> Option<u64> {
+        if self.has_flag(C_INODE_FLAG_CUSTOM_GRANTS) {
+            Some(self.m_prot_grants)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Returns the creation time
+     */
+    pub fn creat_time(&self) -> u64 {
+        self.m_creat_time
+    }
+
+    /**
+     * Returns the last access time, which is updated with the data
+     */
+    pub fn access_time(&self) -> u64 {
+        self.m_access_time
+    }
+
+    /**
+     * Returns the last data modification time
+     */
+    pub fn modify_time(&self) -> u64 {
+        self.m_modify_time
+    }
+
+    /**
+     * Returns the last metadata modification time
+     */
+    pub fn change_time(&self) -> u64 {
+        self.m_change_time
+    }
+}
+
+impl Inode /* Setters */ {
+    /**
+     * Updates the `C_INODE_FLAG_*` flags, the unknown ones are refused
+     */
+    pub fn set_flags(&mut self, flags: u8) -> MxfsResult<()> {
+        if flags & !C_INODE_FLAGS_ALL != 0 {
+            return Err(MxfsError::InvalidArgument);
+        }
+
+        self.m_flags = flags;
+        Ok(())
+    }
+
+    /**
+     * Updates the `Compression` of the new data extents
+     */
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.m_compression = compression;
+    }
+
+    /**
+     * Updates the amount of directory entries which reference this inode
+     */
+    pub fn set_links(&mut self, links: u32) {
+        self.m_links = links;
+    }
+
+    /**
+     * Updates the size of the data, or the amount of directory entries
+     */
+    pub fn set_size(&mut self, size: u64) {
+        self.m_size = size;
+    }
+
+    /**
+     * Updates the directory which contains the last link to this inode
+     */
+    pub fn set_parent(&mut self, parent: InodeId) {
+        self.m_parent = parent;
+    }
+
+    /**
+     * Updates the root block of the tree of this inode
+     */
+    pub fn set_tree_root(&mut self, tree_root: u64) {
+        self.m_tree_root = tree_root;
+    }
+
+    /**
+     * Updates the amount of blocks used by the data extents and by the tree
+     */
+    pub fn set_blocks_used(&mut self, blocks_used: u64) {
+        self.m_blocks_used = blocks_used;
+    }
+
+    /**
+     * Updates the owner user and group
+     */
+    pub fn set_owner(&mut self, os_user_id: u64, os_group_id: u64) {
+        self.m_os_user_id = os_user_id;
+        self.m_os_group_id = os_group_id;
+    }
+
+    /**
+     * Updates the raw protection grants, which replace the defaults
+     */
+    pub fn set_prot_grants(&mut self, prot_grants: u64) {
+        self.m_prot_grants = prot_grants;
+        self.m_flags |= C_INODE_FLAG_CUSTOM_GRANTS;
+    }
+}
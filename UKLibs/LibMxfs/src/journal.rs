@@ -0,0 +1,281 @@
+/*! MXFS metadata journal */
+
+use alloc::{
+    collections::BTreeMap,
+    vec::Vec
+};
+use core::ops::Range;
+
+use crate::{
+    bytes::{
+        read_u32,
+        read_u64,
+        write_u32,
+        write_u64
+    },
+    crc::{
+        crc32_skipping,
+        Crc32
+    },
+    device::TMxfsDevice,
+    MxfsError,
+    MxfsResult
+};
+
+/* signature of the descriptor blocks, "MXJD" */
+const C_DESCRIPTOR_MAGIC: u32 = 0x444A_584D;
+
+/* offset of the checksum of the descriptor blocks */
+const C_CRC_OFFSET: usize = 4;
+
+/* offset of the target blocks list into the descriptor blocks */
+const C_TARGETS_OFFSET: usize = 32;
+
+/**
+ * Home blocks of a committed transaction with their new content
+ */
+pub type CommittedBlocks = Vec<(u64, Vec<u8>)>;
+
+/**
+ * Metadata journal of the volume.
+ *
+ * It holds a single transaction at time: the descriptor blocks, which list
+ * the home blocks of the payload, followed by the new content of those
+ * blocks. The first descriptor is written last, after a flush, and its
+ * checksum of the payload acts as commit record. Once the payload is
+ * copied to its home the sequence into the super block is incremented,
+ * which makes the transaction stale
+ */
+pub struct Journal {
+    m_blocks: Range<u64>,
+    m_block_size: u64
+}
+
+impl Journal /* Constructors */ {
+    /**
+     * Constructs the `Journal` which lives into the given blocks
+     */
+    pub fn new(blocks: Range<u64>, block_size: u64) -> Self {
+        Self { m_blocks: blocks,
+               m_block_size: block_size }
+    }
+}
+
+impl Journal /* Methods */ {
+    /**
+     * Writes and commits the transaction with the given sequence, made by
+     * the given blocks indexed by their home block
+     */
+    pub fn write(&self,
+                 device: &dyn TMxfsDevice,
+                 sequence: u64,
+                 blocks: &BTreeMap<u64, Vec<u8>>)
+                 -> MxfsResult<()> {
+        let descriptors_count = self.descriptors_for(blocks.len());
+        if descriptors_count + blocks.len() > self.blocks_count() {
+            return Err(MxfsError::TransactionTooBig);
+        }
+
+        /* the payload follows the descriptors */
+        let mut payload_crc = Crc32::new();
+        let payload_start = self.m_blocks.start + descriptors_count as u64;
+        for (i, raw_block) in blocks.values().enumerate() {
+            payload_crc.update(raw_block);
+            self.write_block(device, payload_start + i as u64, raw_block)?;
+        }
+
+        let targets: Vec<u64> = blocks.keys().copied().collect();
+        for descriptor_index in (1..descriptors_count).rev() {
+            let raw_descriptor =
+                self.encode_descriptor(sequence, &targets, descriptor_index, 0);
+            self.write_block(device,
+                             self.m_blocks.start + descriptor_index as u64,
+                             &raw_descriptor)?;
+        }
+        device.flush()?;
+
+        /* the first descriptor commits the transaction */
+        let raw_descriptor =
+            self.encode_descriptor(sequence, &targets, 0, payload_crc.value());
+        self.write_block(device, self.m_blocks.start, &raw_descriptor)?;
+        device.flush()
+    }
+
+    /**
+     * Returns the blocks of the committed transaction with the given
+     * sequence, `None` when the journal contains a stale or a torn one
+     */
+    pub fn read_committed(&self,
+                          device: &dyn TMxfsDevice,
+                          sequence: u64)
+                          -> MxfsResult<Option<CommittedBlocks>> {
+        let first_descriptor = self.read_block(device, self.m_blocks.start)?;
+        let (blocks_count, payload_crc) =
+            match self.decode_descriptor(&first_descriptor, sequence, 0) {
+                Some(descriptor_info) => descriptor_info,
+                None => return Ok(None)
+            };
+
+        let descriptors_count = self.descriptors_for(blocks_count);
+        if descriptors_count + blocks_count > self.blocks_count() {
+            return Ok(None);
+        }
+
+        /* collect the home blocks from all the descriptors */
+        let targets_per_descriptor = self.targets_per_descriptor();
+        let mut targets = Vec::with_capacity(blocks_count);
+        for descriptor_index in 0..descriptors_count {
+            let raw_descriptor = if descriptor_index == 0 {
+                first_descriptor.clone()
+            } else {
+                let raw_descriptor = self.read_block(device,
+                                                     self.m_blocks.start
+                                                     + descriptor_index as u64)?;
+                match self.decode_descriptor(&raw_descriptor, sequence, descriptor_index)
+                {
+                    Some((count, _)) if count == blocks_count => raw_descriptor,
+                    _ => return Ok(None)
+                }
+            };
+
+            let first_target = descriptor_index * targets_per_descriptor;
+            let last_target = blocks_count.min(first_target + targets_per_descriptor);
+            for i in 0..last_target - first_target {
+                targets.push(read_u64(&raw_descriptor, C_TARGETS_OFFSET + i * 8));
+            }
+        }
+
+        /* a torn payload means that the transaction never committed */
+        let mut payload_crc_check = Crc32::new();
+        let mut blocks = Vec::with_capacity(blocks_count);
+        let payload_start = self.m_blocks.start + descriptors_count as u64;
+        for (i, target) in targets.into_iter().enumerate() {
+            let raw_block = self.read_block(device, payload_start + i as u64)?;
+
+            payload_crc_check.update(&raw_block);
+            blocks.push((target, raw_block));
+        }
+
+        if payload_crc_check.value() != payload_crc {
+            return Ok(None);
+        }
+        Ok(Some(blocks))
+    }
+
+    /**
+     * Invalidates any transaction into the journal, used by the format
+     */
+    pub fn clear(&self, device: &dyn TMxfsDevice) -> MxfsResult<()> {
+        self.write_block(device,
+                         self.m_blocks.start,
+                         &vec![0; self.m_block_size as usize])
+    }
+}
+
+impl Journal /* Getters */ {
+    /**
+     * Returns the maximum amount of blocks of a transaction
+     */
+    pub fn capacity(&self) -> usize {
+        let blocks_count = self.blocks_count();
+        blocks_count - self.descriptors_for(blocks_count)
+    }
+}
+
+impl Journal /* Privates */ {
+    /**
+     * Encodes the descriptor with the given index of a transaction
+     */
+    fn encode_descriptor(&self,
+                         sequence: u64,
+                         targets: &[u64],
+                         descriptor_index: usize,
+                         payload_crc: u32)
+                         -> Vec<u8> {
+        let mut raw_descriptor = vec![0; self.m_block_size as usize];
+
+        write_u32(&mut raw_descriptor, 0, C_DESCRIPTOR_MAGIC);
+        write_u64(&mut raw_descriptor, 8, sequence);
+        write_u32(&mut raw_descriptor, 16, targets.len() as u32);
+        write_u32(&mut raw_descriptor, 20, descriptor_index as u32);
+        write_u32(&mut raw_descriptor, 24, payload_crc);
+
+        let targets_per_descriptor = self.targets_per_descriptor();
+        let descriptor_targets = targets.iter()
+                                        .skip(descriptor_index * targets_per_descriptor)
+                                        .take(targets_per_descriptor);
+        for (i, target) in descriptor_targets.enumerate() {
+            write_u64(&mut raw_descriptor, C_TARGETS_OFFSET + i * 8, *target);
+        }
+
+        let checksum = crc32_skipping(&raw_descriptor, C_CRC_OFFSET);
+        write_u32(&mut raw_descriptor, C_CRC_OFFSET, checksum);
+        raw_descriptor
+    }
+
+    /**
+     * Decodes the given descriptor, returning the amount of blocks of the
+     * transaction and the checksum of the payload.
+     *
+     * Returns `None` when it doesn't belong to the given transaction
+     */
+    fn decode_descriptor(&self,
+                         raw_descriptor: &[u8],
+                         sequence: u64,
+                         descriptor_index: usize)
+                         -> Option<(usize, u32)> {
+        if read_u32(raw_descriptor, 0) != C_DESCRIPTOR_MAGIC
+           || crc32_skipping(raw_descriptor, C_CRC_OFFSET)
+              != read_u32(raw_descriptor, C_CRC_OFFSET)
+           || read_u64(raw_descriptor, 8) != sequence
+           || read_u32(raw_descriptor, 20) as usize != descriptor_index
+        {
+            return None;
+        }
+        Some((read_u32(raw_descriptor, 16) as usize, read_u32(raw_descriptor, 24)))
+    }
+
+    /**
+     * Returns the amount of descriptors needed by the given amount of
+     * blocks
+     */
+    fn descriptors_for(&self, blocks_count: usize) -> usize {
+        let targets_per_descriptor = self.targets_per_descriptor();
+        blocks_count.div_ceil(targets_per_descriptor).max(1)
+    }
+
+    /**
+     * Returns the amount of home blocks listed by each descriptor
+     */
+    fn targets_per_descriptor(&self) -> usize {
+        (self.m_block_size as usize - C_TARGETS_OFFSET) / 8
+    }
+
+    /**
+     * Returns the amount of blocks of the journal
+     */
+    fn blocks_count(&self) -> usize {
+        (self.m_blocks.end - self.m_blocks.start) as usize
+    }
+
+    /**
+     * Reads the given block of the volume
+     */
+    fn read_block(&self, device: &dyn TMxfsDevice, block: u64) -> MxfsResult<Vec<u8>> {
+        let mut raw_block = vec![0; self.m_block_size as usize];
+
+        device.read_at(block * self.m_block_size, &mut raw_block)?;
+        Ok(raw_block)
+    }
+
+    /**
+     * Writes the given block of the volume
+     */
+    fn write_block(&self,
+                   device: &dyn TMxfsDevice,
+                   block: u64,
+                   raw_block: &[u8])
+                   -> MxfsResult<()> {
+        device.write_at(block * self.m_block_size, raw_block)
+    }
+}
@@ -0,0 +1,138 @@
+/*! # MeetiX FileSystem Library
+ *
+ * Implements the MXFS on-disk format, described into
+ * `Docs/Filesystems/MXFS.md`, over any `TMxfsDevice`.
+ *
+ * The library is shared by the `Kernel` driver and by the host tools which
+ * build and check the images, so it depends only on `core` and `alloc`
+ */
+
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+
+use core::fmt;
+
+pub mod btree;
+mod bytes;
+pub mod cache;
+pub mod check;
+pub mod compress;
+pub mod crc;
+pub mod device;
+pub mod dir;
+pub mod extent;
+pub mod format;
+pub mod inode;
+pub mod journal;
+pub mod pool;
+pub mod super_block;
+pub mod volume;
+
+/**
+ * Result of the MXFS operations
+ */
+pub type MxfsResult<T> = Result<T, MxfsError>;
+
+/**
+ * Lists the failures of the MXFS operations
+ */
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq)]
+pub enum MxfsError {
+    /**
+     * The `TMxfsDevice` failed to transfer the data
+     */
+    DeviceError,
+
+    /**
+     * The device doesn't contain an MXFS volume
+     */
+    BadMagic,
+
+    /**
+     * The volume was written by an incompatible major version
+     */
+    UnsupportedVersion,
+
+    /**
+     * An on-disk structure has a bad checksum or inconsistent fields
+     */
+    Corrupted,
+
+    /**
+     * One of the given arguments is out of its valid range
+     */
+    InvalidArgument,
+
+    /**
+     * The requested entry doesn't exist
+     */
+    NotFound,
+
+    /**
+     * An entry with the given name already exists
+     */
+    AlreadyExists,
+
+    /**
+     * The inode type doesn't match the operation
+     */
+    TypesNotMatch,
+
+    /**
+     * The directory to remove still contains entries
+     */
+    DirectoryNotEmpty,
+
+    /**
+     * The given name is longer than `C_NAME_LEN_MAX`
+     *
+     * [`C_NAME_LEN_MAX`]: crate::dir::C_NAME_LEN_MAX
+     */
+    NameTooLong,
+
+    /**
+     * There are no more free blocks
+     */
+    NoSpace,
+
+    /**
+     * There are no more free inodes
+     */
+    NoInodes,
+
+    /**
+     * The modifications of the operation don't fit into the journal
+     */
+    TransactionTooBig,
+
+    /**
+     * The volume refuses any modification
+     */
+    ReadOnly
+}
+
+impl fmt::Display for MxfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::DeviceError => "device error",
+            Self::BadMagic => "not an MXFS volume",
+            Self::UnsupportedVersion => "unsupported MXFS version",
+            Self::Corrupted => "corrupted on-disk structure",
+            Self::InvalidArgument => "invalid argument",
+            Self::NotFound => "no such entry",
+            Self::AlreadyExists => "entry already exists",
+            Self::TypesNotMatch => "inode type doesn't match",
+            Self::DirectoryNotEmpty => "directory not empty",
+            Self::NameTooLong => "name too long",
+            Self::NoSpace => "no space left",
+            Self::NoInodes => "no free inodes left",
+            Self::TransactionTooBig => "transaction too big for the journal",
+            Self::ReadOnly => "read-only volume"
+        };
+        write!(f, "{}", message)
+    }
+}
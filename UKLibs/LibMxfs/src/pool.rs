@@ -0,0 +1,187 @@
+/*! MXFS extent pools */
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{
+    bytes::{
+        read_u32,
+        read_u64,
+        write_u32,
+        write_u64
+    },
+    crc::crc32_skipping,
+    MxfsError,
+    MxfsResult
+};
+
+/* signature of the pool header block, "MXEP" */
+const C_POOL_MAGIC: u32 = 0x5045_584D;
+
+/* offset of the checksum of the pool header block */
+const C_CRC_OFFSET: usize = 4;
+
+/* offset of the bad blocks list into the pool header block */
+const C_BAD_BLOCKS_OFFSET: usize = 40;
+
+/**
+ * Header of an extent pool, stored into its first block.
+ *
+ * The volume is split into pools to keep the extents of the same inode
+ * near each other. Each pool is made by the header block, by the free
+ * blocks bitmap, which covers all the blocks of the pool (header and
+ * bitmap included), and by the data blocks. The bad blocks listed by the
+ * header are marked as used into the bitmap
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct PoolHeader {
+    m_first_block: u64,
+    m_blocks_count: u64,
+    m_next_pool: u64,
+    m_bitmap_blocks: u32,
+    m_bad_blocks: Vec<u64>
+}
+
+impl PoolHeader /* Constructors */ {
+    /**
+     * Constructs the `PoolHeader` of the pool with the given blocks
+     */
+    pub fn new(blocks: Range<u64>, next_pool: u64, block_size: u64) -> Self {
+        let bits_per_block = block_size * 8;
+        let blocks_count = blocks.end - blocks.start;
+
+        Self { m_first_block: blocks.start,
+               m_blocks_count: blocks_count,
+               m_next_pool: next_pool,
+               m_bitmap_blocks: blocks_count.div_ceil(bits_per_block) as u32,
+               m_bad_blocks: Vec::new() }
+    }
+
+    /**
+     * Decodes the given pool header block, which is expected at the given
+     * block number
+     */
+    pub fn decode(raw_header: &[u8], block: u64) -> MxfsResult<Self> {
+        if read_u32(raw_header, 0) != C_POOL_MAGIC
+           || crc32_skipping(raw_header, C_CRC_OFFSET)
+              != read_u32(raw_header, C_CRC_OFFSET)
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let bad_blocks_count = read_u32(raw_header, 36) as usize;
+        if bad_blocks_count > Self::bad_blocks_max(raw_header.len() as u64) {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let pool_header = Self { m_first_block: read_u64(raw_header, 16),
+                                 m_blocks_count: read_u64(raw_header, 24),
+                                 m_next_pool: read_u64(raw_header, 8),
+                                 m_bitmap_blocks: read_u32(raw_header, 32),
+                                 m_bad_blocks: (0..bad_blocks_count).map(|i| {
+                                                   read_u64(raw_header,
+                                                            C_BAD_BLOCKS_OFFSET + i * 8)
+                                               })
+                                               .collect() };
+
+        /* the bitmap must cover the pool and leave at least one data block */
+        let bitmap_bits =
+            pool_header.m_bitmap_blocks as u64 * raw_header.len() as u64 * 8;
+        if pool_header.m_first_block != block
+           || bitmap_bits < pool_header.m_blocks_count
+           || pool_header.data_start() >= pool_header.blocks().end
+           || pool_header.m_bad_blocks
+                         .iter()
+                         .any(|bad_block| !pool_header.blocks().contains(bad_block))
+        {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(pool_header)
+    }
+}
+
+impl PoolHeader /* Methods */ {
+    /**
+     * Encodes this `PoolHeader` into a new block of the given size
+     */
+    pub fn encode(&self, block_size: u64) -> Vec<u8> {
+        let mut raw_header = vec![0; block_size as usize];
+
+        write_u32(&mut raw_header, 0, C_POOL_MAGIC);
+        write_u64(&mut raw_header, 8, self.m_next_pool);
+        write_u64(&mut raw_header, 16, self.m_first_block);
+        write_u64(&mut raw_header, 24, self.m_blocks_count);
+        write_u32(&mut raw_header, 32, self.m_bitmap_blocks);
+        write_u32(&mut raw_header, 36, self.m_bad_blocks.len() as u32);
+        for (i, bad_block) in self.m_bad_blocks.iter().enumerate() {
+            write_u64(&mut raw_header, C_BAD_BLOCKS_OFFSET + i * 8, *bad_block);
+        }
+
+        let checksum = crc32_skipping(&raw_header, C_CRC_OFFSET);
+        write_u32(&mut raw_header, C_CRC_OFFSET, checksum);
+        raw_header
+    }
+
+    /**
+     * Appends the given block to the bad blocks list
+     */
+    pub fn add_bad_block(&mut self, bad_block: u64, block_size: u64) -> MxfsResult<()> {
+        if !self.blocks().contains(&bad_block) || bad_block < self.data_start() {
+            return Err(MxfsError::InvalidArgument);
+        }
+        if self.m_bad_blocks.len() >= Self::bad_blocks_max(block_size) {
+            return Err(MxfsError::NoSpace);
+        }
+
+        self.m_bad_blocks.push(bad_block);
+        Ok(())
+    }
+}
+
+impl PoolHeader /* Getters */ {
+    /**
+     * Returns the range of blocks covered by the pool
+     */
+    pub fn blocks(&self) -> Range<u64> {
+        self.m_first_block..self.m_first_block + self.m_blocks_count
+    }
+
+    /**
+     * Returns the first block of the next pool, `0` for the last one
+     */
+    pub fn next_pool(&self) -> u64 {
+        self.m_next_pool
+    }
+
+    /**
+     * Returns the range of blocks of the free blocks bitmap
+     */
+    pub fn bitmap(&self) -> Range<u64> {
+        self.m_first_block + 1..self.data_start()
+    }
+
+    /**
+     * Returns the first block which follows the bitmap
+     */
+    pub fn data_start(&self) -> u64 {
+        self.m_first_block + 1 + self.m_bitmap_blocks as u64
+    }
+
+    /**
+     * Returns the blocks which must never be used
+     */
+    pub fn bad_blocks(&self) -> &[u64] {
+        &self.m_bad_blocks
+    }
+}
+
+impl PoolHeader /* Static Functions */ {
+    /**
+     * Returns the maximum amount of bad blocks listed by a pool header
+     * block of the given size
+     */
+    pub fn bad_blocks_max(block_size: u64) -> usize {
+        (block_size as usize - C_BAD_BLOCKS_OFFSET) / 8
+    }
+}
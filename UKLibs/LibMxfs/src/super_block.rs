@@ -0,0 +1,406 @@
+/*! MXFS super block */
+
+use core::{
+    ops::Range,
+    str
+};
+
+use crate::{
+    bytes::{
+        read_u32,
+        read_u64,
+        write_u32,
+        write_u64
+    },
+    compress::Compression,
+    crc::crc32,
+    device::TMxfsDevice,
+    extent::{
+        C_CHUNK_EXP_MAX,
+        C_CHUNK_HEADER_SIZE
+    },
+    inode::{
+        InodeId,
+        C_INODE_SIZE
+    },
+    MxfsError,
+    MxfsResult
+};
+
+/**
+ * GUID which opens the `SuperBlock`, stored in its textual byte order
+ * (69bfe672-67fd-4228-a54f-fccd3fb85998)
+ */
+pub const C_MXFS_GUID: [u8; 16] = [0x69, 0xBF, 0xE6, 0x72, 0x67, 0xFD, 0x42, 0x28, 0xA5,
+                                   0x4F, 0xFC, 0xCD, 0x3F, 0xB8, 0x59, 0x98];
+
+/**
+ * Version of the layout written by this implementation, where the decimal
+ * value divided by 10 gives the major and the minor versions
+ */
+pub const C_MXFS_VERSION: u8 = 10;
+
+/**
+ * Size in bytes of the `SuperBlock` on disk
+ */
+pub const C_SUPER_BLOCK_SIZE: usize = 148;
+
+/**
+ * Limits of the shift of the block size, which selects blocks from 1KiB to
+ * 64KiB
+ */
+pub const C_BLOCK_SIZE_SHIFT_MIN: u8 = 10;
+pub const C_BLOCK_SIZE_SHIFT_MAX: u8 = 16;
+
+/**
+ * Maximum length in bytes of the volume label
+ */
+pub const C_LABEL_LEN_MAX: usize = 32;
+
+/* offset of the checksum, which covers all the bytes which precede it */
+const C_CRC_OFFSET: usize = 144;
+
+/**
+ * Sizes and placement of the areas of a new MXFS volume, given to
+ * `SuperBlock::new()`
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct SuperBlockGeometry {
+    pub m_block_size_shift: u8,
+    pub m_chunk_exp: u8,
+    pub m_compression: Compression,
+    pub m_blocks_count: u64,
+    pub m_first_pool: u64,
+    pub m_pool_blocks: u64,
+    pub m_root_inode: InodeId,
+    pub m_orphans_inode: InodeId,
+    pub m_inode_table_start: u64,
+    pub m_inodes_count: u64,
+    pub m_journal_start: u64,
+    pub m_journal_blocks: u64
+}
+
+/**
+ * Super block of an MXFS volume.
+ *
+ * It lives at the beginning of the first block and describes where the
+ * journal, the inode table and the chain of the extent pools are placed
+ */
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct SuperBlock {
+    m_version: u8,
+    m_block_size_shift: u8,
+    m_chunk_exp: u8,
+    m_compression: Compression,
+    m_blocks_count: u64,
+    m_first_pool: u64,
+    m_pool_blocks: u64,
+    m_root_inode: InodeId,
+    m_orphans_inode: InodeId,
+    m_inode_table_start: u64,
+    m_inodes_count: u64,
+    m_journal_start: u64,
+    m_journal_blocks: u64,
+    m_journal_sequence: u64,
+    m_creat_time: u64,
+    m_label: [u8; C_LABEL_LEN_MAX]
+}
+
+impl SuperBlock /* Constructors */ {
+    /**
+     * Constructs a `SuperBlock` of the current version with the given
+     * `SuperBlockGeometry`, used by the format
+     */
+    pub fn new(geometry: &SuperBlockGeometry,
+               creat_time: u64,
+               label: &str)
+               -> MxfsResult<Self> {
+        if label.len() > C_LABEL_LEN_MAX {
+            return Err(MxfsError::NameTooLong);
+        }
+
+        let mut raw_label = [0; C_LABEL_LEN_MAX];
+        raw_label[..label.len()].copy_from_slice(label.as_bytes());
+
+        Ok(Self { m_version: C_MXFS_VERSION,
+                  m_block_size_shift: geometry.m_block_size_shift,
+                  m_chunk_exp: geometry.m_chunk_exp,
+                  m_compression: geometry.m_compression,
+                  m_blocks_count: geometry.m_blocks_count,
+                  m_first_pool: geometry.m_first_pool,
+                  m_pool_blocks: geometry.m_pool_blocks,
+                  m_root_inode: geometry.m_root_inode,
+                  m_orphans_inode: geometry.m_orphans_inode,
+                  m_inode_table_start: geometry.m_inode_table_start,
+                  m_inodes_count: geometry.m_inodes_count,
+                  m_journal_start: geometry.m_journal_start,
+                  m_journal_blocks: geometry.m_journal_blocks,
+                  m_journal_sequence: 1,
+                  m_creat_time: creat_time,
+                  m_label: raw_label })
+    }
+
+    /**
+     * Reads the `SuperBlock` from the beginning of the given `TMxfsDevice`,
+     * checking its GUID, its checksum and its major version
+     */
+    pub fn read(device: &dyn TMxfsDevice) -> MxfsResult<Self> {
+        if device.size() < C_SUPER_BLOCK_SIZE as u64 {
+            return Err(MxfsError::BadMagic);
+        }
+
+        let mut raw_super_block = [0; C_SUPER_BLOCK_SIZE];
+        device.read_at(0, &mut raw_super_block)?;
+
+        if raw_super_block[..16] != C_MXFS_GUID {
+            return Err(MxfsError::BadMagic);
+        }
+        if crc32(&raw_super_block[..C_CRC_OFFSET])
+           != read_u32(&raw_super_block, C_CRC_OFFSET)
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let version = raw_super_block[16];
+        if version / 10 != C_MXFS_VERSION / 10 {
+            return Err(MxfsError::UnsupportedVersion);
+        }
+
+        let mut label = [0; C_LABEL_LEN_MAX];
+        label.copy_from_slice(&raw_super_block[112..112 + C_LABEL_LEN_MAX]);
+
+        Ok(Self { m_version: version,
+                  m_block_size_shift: raw_super_block[17],
+                  m_chunk_exp: raw_super_block[18],
+                  m_compression: Compression::from_raw(raw_super_block[19])?,
+                  m_blocks_count: read_u64(&raw_super_block, 24),
+                  m_first_pool: read_u64(&raw_super_block, 32),
+                  m_pool_blocks: read_u64(&raw_super_block, 40),
+                  m_root_inode: read_u64(&raw_super_block, 48),
+                  m_orphans_inode: read_u64(&raw_super_block, 104),
+                  m_inode_table_start: read_u64(&raw_super_block, 56),
+                  m_inodes_count: read_u64(&raw_super_block, 64),
+                  m_journal_start: read_u64(&raw_super_block, 72),
+                  m_journal_blocks: read_u64(&raw_super_block, 80),
+                  m_journal_sequence: read_u64(&raw_super_block, 88),
+                  m_creat_time: read_u64(&raw_super_block, 96),
+                  m_label: label })
+    }
+}
+
+impl SuperBlock /* Methods */ {
+    /**
+     * Checks the consistency of the geometry against the size of the
+     * `TMxfsDevice`
+     */
+    pub fn verify(&self, device_size: u64) -> MxfsResult<()> {
+        if self.m_block_size_shift < C_BLOCK_SIZE_SHIFT_MIN
+           || self.m_block_size_shift > C_BLOCK_SIZE_SHIFT_MAX
+           || self.m_chunk_exp > C_CHUNK_EXP_MAX
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let volume_size = self.m_blocks_count
+                              .checked_mul(self.block_size())
+                              .ok_or(MxfsError::Corrupted)?;
+        if volume_size > device_size {
+            return Err(MxfsError::Corrupted);
+        }
+
+        /* the regions follow the super block in this order */
+        let journal = self.journal();
+        let inode_table = self.inode_table();
+        if journal.start < 1
+           || journal.end - journal.start < 2
+           || inode_table.start < journal.end
+           || inode_table.end > self.m_first_pool
+           || self.m_first_pool >= self.m_blocks_count
+           || self.m_pool_blocks < 3
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        let inodes = 1..=self.m_inodes_count;
+        if !inodes.contains(&self.m_root_inode)
+           || !inodes.contains(&self.m_orphans_inode)
+           || self.m_root_inode == self.m_orphans_inode
+        {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes back this `SuperBlock` at the beginning of the given
+     * `TMxfsDevice`
+     */
+    pub fn write(&self, device: &dyn TMxfsDevice) -> MxfsResult<()> {
+        let mut raw_super_block = [0; C_SUPER_BLOCK_SIZE];
+
+        raw_super_block[..16].copy_from_slice(&C_MXFS_GUID);
+        raw_super_block[16] = self.m_version;
+        raw_super_block[17] = self.m_block_size_shift;
+        raw_super_block[18] = self.m_chunk_exp;
+        raw_super_block[19] = self.m_compression as u8;
+        write_u64(&mut raw_super_block, 24, self.m_blocks_count);
+        write_u64(&mut raw_super_block, 32, self.m_first_pool);
+        write_u64(&mut raw_super_block, 40, self.m_pool_blocks);
+        write_u64(&mut raw_super_block, 48, self.m_root_inode);
+        write_u64(&mut raw_super_block, 56, self.m_inode_table_start);
+        write_u64(&mut raw_super_block, 64, self.m_inodes_count);
+        write_u64(&mut raw_super_block, 72, self.m_journal_start);
+        write_u64(&mut raw_super_block, 80, self.m_journal_blocks);
+        write_u64(&mut raw_super_block, 88, self.m_journal_sequence);
+        write_u64(&mut raw_super_block, 96, self.m_creat_time);
+        write_u64(&mut raw_super_block, 104, self.m_orphans_inode);
+        raw_super_block[112..112 + C_LABEL_LEN_MAX].copy_from_slice(&self.m_label);
+
+        let checksum = crc32(&raw_super_block[..C_CRC_OFFSET]);
+        write_u32(&mut raw_super_block, C_CRC_OFFSET, checksum);
+
+        device.write_at(0, &raw_super_block)
+    }
+}
+
+impl SuperBlock /* Getters */ {
+    /**
+     * Returns the version of the layout which wrote the volume
+     */
+    pub fn version(&self) -> u8 {
+        self.m_version
+    }
+
+    /**
+     * Returns whether the volume was written by a newer minor version,
+     * which must not be modified
+     */
+    pub fn is_newer_version(&self) -> bool {
+        self.m_version > C_MXFS_VERSION
+    }
+
+    /**
+     * Returns the size in bytes of the blocks
+     */
+    pub fn block_size(&self) -> u64 {
+        1 << self.m_block_size_shift
+    }
+
+    /**
+     * Returns the exponent of the blocks of the biggest data extent
+     */
+    pub fn chunk_exp(&self) -> u8 {
+        self.m_chunk_exp
+    }
+
+    /**
+     * Returns the amount of bytes of file data covered by each data extent
+     */
+    pub fn chunk_span(&self) -> u64 {
+        (self.block_size() << self.m_chunk_exp) - C_CHUNK_HEADER_SIZE as u64
+    }
+
+    /**
+     * Returns the `Compression` given to the new inodes
+     */
+    pub fn compression(&self) -> Compression {
+        self.m_compression
+    }
+
+    /**
+     * Returns the amount of blocks of the volume
+     */
+    pub fn blocks_count(&self) -> u64 {
+        self.m_blocks_count
+    }
+
+    /**
+     * Returns the first block of the first extent pool
+     */
+    pub fn first_pool(&self) -> u64 {
+        self.m_first_pool
+    }
+
+    /**
+     * Returns the amount of blocks of each extent pool, only the last one
+     * may be shorter
+     */
+    pub fn pool_blocks(&self) -> u64 {
+        self.m_pool_blocks
+    }
+
+    /**
+     * Returns the `InodeId` of the root directory
+     */
+    pub fn root_inode(&self) -> InodeId {
+        self.m_root_inode
+    }
+
+    /**
+     * Returns the `InodeId` of the hidden directory which lists the inodes
+     * with a pending release of their data extents
+     */
+    pub fn orphans_inode(&self) -> InodeId {
+        self.m_orphans_inode
+    }
+
+    /**
+     * Returns the amount of inodes of the inode table
+     */
+    pub fn inodes_count(&self) -> u64 {
+        self.m_inodes_count
+    }
+
+    /**
+     * Returns the range of blocks of the inode table
+     */
+    pub fn inode_table(&self) -> Range<u64> {
+        let inode_table_size = self.m_inodes_count.saturating_mul(C_INODE_SIZE as u64);
+        let inode_table_blocks =
+            (inode_table_size + self.block_size() - 1) >> self.m_block_size_shift;
+
+        self.m_inode_table_start
+        ..self.m_inode_table_start.saturating_add(inode_table_blocks)
+    }
+
+    /**
+     * Returns the range of blocks of the journal
+     */
+    pub fn journal(&self) -> Range<u64> {
+        self.m_journal_start..self.m_journal_start.saturating_add(self.m_journal_blocks)
+    }
+
+    /**
+     * Returns the sequence number of the next journal transaction
+     */
+    pub fn journal_sequence(&self) -> u64 {
+        self.m_journal_sequence
+    }
+
+    /**
+     * Returns the creation time in nanoseconds since the Unix epoch
+     */
+    pub fn creat_time(&self) -> u64 {
+        self.m_creat_time
+    }
+
+    /**
+     * Returns the label of the volume
+     */
+    pub fn label(&self) -> &str {
+        let label_len =
+            self.m_label.iter().position(|c| *c == 0).unwrap_or(C_LABEL_LEN_MAX);
+        str::from_utf8(&self.m_label[..label_len]).unwrap_or("")
+    }
+}
+
+impl SuperBlock /* Setters */ {
+    /**
+     * Updates the sequence number of the next journal transaction
+     */
+    pub fn set_journal_sequence(&mut self, journal_sequence: u64) {
+        self.m_journal_sequence = journal_sequence;
+    }
+}
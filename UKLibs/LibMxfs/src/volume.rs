@@ -0,0 +1,1545 @@
+/*! MXFS mounted volume */
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+use core::ops::Range;
+
+use crate::{
+    btree::{
+        BTree,
+        TTreeRecord,
+        TTreeStore
+    },
+    cache::MetaCache,
+    compress::Compression,
+    crc::crc32,
+    device::TMxfsDevice,
+    dir::{
+        check_name,
+        name_hash,
+        DirKey,
+        DirRecord
+    },
+    extent::{
+        blocks_exp_for,
+        ChunkHeader,
+        ExtentRecord,
+        C_CHUNK_HEADER_SIZE
+    },
+    inode::{
+        Inode,
+        InodeId,
+        InodeType,
+        C_INODE_FLAG_NO_COW,
+        C_INODE_FLAG_NO_CRC,
+        C_INODE_SIZE
+    },
+    journal::Journal,
+    pool::PoolHeader,
+    super_block::SuperBlock,
+    MxfsError,
+    MxfsResult
+};
+
+/* maximum amount of data extents released by each transaction */
+const C_RELEASE_BATCH: usize = 64;
+
+/**
+ * Mounted MXFS volume.
+ *
+ * Each modification runs into a transaction: the metadata blocks it
+ * changes stay into the `MetaCache` until the operation succeeds, then
+ * they are committed through the `Journal`. The data extents are instead
+ * written directly into newly allocated blocks (copy-on-write), before
+ * the commit of the metadata which references them, and the released
+ * blocks become free only with the commit, so a crash always leaves
+ * either the old or the new version of the data
+ */
+pub struct Volume<D: TMxfsDevice> {
+    m_device: D,
+    m_super_block: SuperBlock,
+    m_journal: Journal,
+    m_pools: Vec<PoolHeader>,
+    m_cache: MetaCache,
+    m_pending_frees: Vec<Range<u64>>,
+    m_free_blocks: u64,
+    m_committed_free_blocks: u64,
+    m_tree_blocks_delta: i64,
+    m_alloc_pool: usize,
+    m_inode_hint: InodeId,
+    m_is_read_only: bool,
+    m_clock: fn() -> u64
+}
+
+impl<D: TMxfsDevice> Volume<D> /* Constructors */ {
+    /**
+     * Mounts the volume stored into the given `TMxfsDevice`, completing
+     * the last committed transaction when it was interrupted.
+     *
+     * The given clock returns the nanoseconds since the Unix epoch
+     */
+    pub fn mount(device: D, clock: fn() -> u64) -> MxfsResult<Self> {
+        let mut super_block = SuperBlock::read(&device)?;
+        super_block.verify(device.size())?;
+
+        let is_read_only = device.is_read_only() || super_block.is_newer_version();
+        let journal = Journal::new(super_block.journal(), super_block.block_size());
+
+        /* copy to their home the blocks which the last transaction didn't */
+        if let Some(journal_blocks) =
+            journal.read_committed(&device, super_block.journal_sequence())?
+        {
+            if is_read_only {
+                return Err(MxfsError::ReadOnly);
+            }
+
+            let is_valid_target = |block: u64| {
+                block > 0
+                && block < super_block.blocks_count()
+                && !super_block.journal().contains(&block)
+            };
+            if !journal_blocks.iter().all(|(block, _)| is_valid_target(*block)) {
+                return Err(MxfsError::Corrupted);
+            }
+
+            Self::checkpoint(&device,
+                             &mut super_block,
+                             journal_blocks.iter().map(|(block, raw_block)| {
+                                                      (*block, raw_block.as_slice())
+                                                  }))?;
+        }
+
+        let pools = Self::load_pools(&device, &super_block)?;
+        let mut volume = Self { m_cache: MetaCache::new(super_block.block_size()),
+                                m_device: device,
+                                m_super_block: super_block,
+                                m_journal: journal,
+                                m_pools: pools,
+                                m_pending_frees: Vec::new(),
+                                m_free_blocks: 0,
+                                m_committed_free_blocks: 0,
+                                m_tree_blocks_delta: 0,
+                                m_alloc_pool: 0,
+                                m_inode_hint: 1,
+                                m_is_read_only: is_read_only,
+                                m_clock: clock };
+
+        volume.m_free_blocks = volume.count_free_blocks()?;
+        volume.m_committed_free_blocks = volume.m_free_blocks;
+
+        /* complete the releases interrupted before the last unmount */
+        if !volume.m_is_read_only {
+            volume.release_orphans()?;
+        }
+        Ok(volume)
+    }
+}
+
+impl<D: TMxfsDevice> Volume<D> /* Methods */ {
+    /**
+     * Returns the used `Inode` with the given `InodeId`
+     */
+    pub fn inode(&mut self, inode_id: InodeId) -> MxfsResult<Inode> {
+        let inode = self.load_inode(inode_id)?;
+        if inode.inode_type() == InodeType::Free {
+            return Err(MxfsError::NotFound);
+        }
+        Ok(inode)
+    }
+
+    /**
+     * Returns the `DirRecord` with the given name of the given directory
+     */
+    pub fn lookup(&mut self, dir_id: InodeId, name: &str) -> MxfsResult<DirRecord> {
+        check_name(name)?;
+
+        let dir_inode = self.load_typed(dir_id, InodeType::Dir)?;
+        self.find_entry(&dir_inode, name)?.ok_or(MxfsError::NotFound)
+    }
+
+    /**
+     * Returns the `DirRecord` at the given index of the given directory,
+     * `None` when the index is over the last entry
+     */
+    pub fn read_dir(&mut self,
+                    dir_id: InodeId,
+                    index: usize)
+                    -> MxfsResult<Option<DirRecord>> {
+        let mut dir_record = None;
+        let mut entry_index = 0;
+        self.for_each_entry(dir_id, &mut |record| {
+                if entry_index == index {
+                    dir_record = Some(record);
+                    return Ok(false);
+                }
+
+                entry_index += 1;
+                Ok(true)
+            })?;
+        Ok(dir_record)
+    }
+
+    /**
+     * Calls the given closure with the `DirRecord`s of the given directory,
+     * sorted by the hash of their name, until it returns `false`
+     */
+    pub fn for_each_entry(&mut self,
+                          dir_id: InodeId,
+                          visitor: &mut dyn FnMut(DirRecord) -> MxfsResult<bool>)
+                          -> MxfsResult<()> {
+        let dir_inode = self.load_typed(dir_id, InodeType::Dir)?;
+
+        BTree::<DirRecord>::new(dir_inode.tree_root()).for_each_from(self, None,
+                                                                     visitor)?;
+        Ok(())
+    }
+
+    /**
+     * Returns the name of the given inode into its parent directory,
+     * `None` for the root directory or when the parent no longer links it
+     */
+    pub fn name_of(&mut self, inode_id: InodeId) -> MxfsResult<Option<String>> {
+        let inode = self.inode(inode_id)?;
+        if inode_id == self.m_super_block.root_inode() {
+            return Ok(None);
+        }
+
+        let mut name = None;
+        self.for_each_entry(inode.parent(), &mut |record| {
+                if record.inode() == inode_id {
+                    name = Some(String::from(record.name()));
+                    return Ok(false);
+                }
+                Ok(true)
+            })?;
+        Ok(name)
+    }
+
+    /**
+     * Creates a new empty file or directory with the given name into the
+     * given directory
+     */
+    pub fn create(&mut self,
+                  dir_id: InodeId,
+                  name: &str,
+                  inode_type: InodeType)
+                  -> MxfsResult<InodeId> {
+        if !matches!(inode_type, InodeType::File | InodeType::Dir) {
+            return Err(MxfsError::InvalidArgument);
+        }
+
+        self.transaction(|volume| {
+                let (inode_id, _) = volume.create_inode(dir_id, name, inode_type)?;
+                Ok(inode_id)
+            })
+    }
+
+    /**
+     * Creates a new symbolic link with the given name into the given
+     * directory, which points to the given path
+     */
+    pub fn create_link(&mut self,
+                       dir_id: InodeId,
+                       name: &str,
+                       target: &str)
+                       -> MxfsResult<InodeId> {
+        if target.is_empty() || target.len() as u64 > self.m_super_block.chunk_span() {
+            return Err(MxfsError::InvalidArgument);
+        }
+
+        self.transaction(|volume| {
+                let (inode_id, mut inode) =
+                    volume.create_inode(dir_id, name, InodeType::Link)?;
+
+                volume.replace_chunk(inode_id, &mut inode, 0, None, target.as_bytes())?;
+                inode.set_size(target.len() as u64);
+                volume.store_inode(inode_id, &inode)?;
+                Ok(inode_id)
+            })
+    }
+
+    /**
+     * Adds a new link with the given name into the given directory to the
+     * given file or symbolic link
+     */
+    pub fn link(&mut self,
+                dir_id: InodeId,
+                name: &str,
+                inode_id: InodeId)
+                -> MxfsResult<()> {
+        check_name(name)?;
+
+        self.transaction(|volume| {
+                let mut inode = volume.inode(inode_id)?;
+                if inode.inode_type() == InodeType::Dir {
+                    return Err(MxfsError::TypesNotMatch);
+                }
+                if inode.links() == 0 {
+                    return Err(MxfsError::NotFound);
+                }
+
+                let mut dir_inode = volume.load_user_dir(dir_id)?;
+                if volume.find_entry(&dir_inode, name)?.is_some() {
+                    return Err(MxfsError::AlreadyExists);
+                }
+
+                let links = inode.links().checked_add(1).ok_or(MxfsError::NoSpace)?;
+                volume.insert_entry(dir_id,
+                                    &mut dir_inode,
+                                    DirRecord::new(name, inode_id, inode.inode_type()))?;
+
+                inode.set_links(links);
+                inode.set_parent(dir_id);
+                inode.touch_info(volume.now());
+                volume.store_inode(inode_id, &inode)
+            })
+    }
+
+    /**
+     * Removes the entry with the given name from the given directory,
+     * releasing its inode with the last link.
+     *
+     * The directories must be empty
+     */
+    pub fn unlink(&mut self, dir_id: InodeId, name: &str) -> MxfsResult<()> {
+        check_name(name)?;
+
+        let (inode_id, is_last_link) =
+            self.transaction(|volume| {
+                    let mut dir_inode = volume.load_user_dir(dir_id)?;
+                    let dir_record = volume.find_entry(&dir_inode, name)?
+                                           .ok_or(MxfsError::NotFound)?;
+
+                    let mut inode = volume.inode(dir_record.inode())?;
+                    if inode.inode_type() == InodeType::Dir && inode.size() > 0 {
+                        return Err(MxfsError::DirectoryNotEmpty);
+                    }
+
+                    volume.remove_entry(dir_id, &mut dir_inode, name)?;
+
+                    inode.set_links(inode.links().saturating_sub(1));
+                    inode.touch_info(volume.now());
+                    volume.store_inode(dir_record.inode(), &inode)?;
+
+                    /* the unreferenced inode is released by the next transactions */
+                    if inode.links() == 0 {
+                        volume.add_orphan(dir_record.inode(), inode.inode_type())?;
+                    }
+                    Ok((dir_record.inode(), inode.links() == 0))
+                })?;
+
+        if is_last_link {
+            self.release_orphan(inode_id)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Moves the entry with the given name of the given directory into the
+     * other given directory with the given new name, which must not exist
+     */
+    pub fn rename(&mut self,
+                  dir_id: InodeId,
+                  name: &str,
+                  new_dir_id: InodeId,
+                  new_name: &str)
+                  -> MxfsResult<()> {
+        check_name(name)?;
+        check_name(new_name)?;
+
+        self.transaction(|volume| {
+                let mut dir_inode = volume.load_user_dir(dir_id)?;
+                let dir_record =
+                    volume.find_entry(&dir_inode, name)?.ok_or(MxfsError::NotFound)?;
+                if dir_id == new_dir_id && name == new_name {
+                    return Ok(());
+                }
+
+                let new_dir_inode = volume.load_user_dir(new_dir_id)?;
+                if volume.find_entry(&new_dir_inode, new_name)?.is_some() {
+                    return Err(MxfsError::AlreadyExists);
+                }
+
+                /* a directory can't be moved into itself */
+                if dir_record.inode_type() == InodeType::Dir
+                   && volume.is_ancestor_of(dir_record.inode(), new_dir_id)?
+                {
+                    return Err(MxfsError::InvalidArgument);
+                }
+
+                volume.remove_entry(dir_id, &mut dir_inode, name)?;
+
+                /* reload the directory, which may be the same */
+                let mut new_dir_inode = volume.load_typed(new_dir_id, InodeType::Dir)?;
+                volume.insert_entry(new_dir_id,
+                                    &mut new_dir_inode,
+                                    DirRecord::new(new_name,
+                                                   dir_record.inode(),
+                                                   dir_record.inode_type()))?;
+
+                let mut inode = volume.inode(dir_record.inode())?;
+                inode.set_parent(new_dir_id);
+                inode.touch_info(volume.now());
+                volume.store_inode(dir_record.inode(), &inode)
+            })
+    }
+
+    /**
+     * Reads the data of the given file from the given offset into the
+     * given buffer.
+     *
+     * Returns the amount of bytes read, which is `0` at the end of data
+     */
+    pub fn read_at(&mut self,
+                   inode_id: InodeId,
+                   offset: u64,
+                   buffer: &mut [u8])
+                   -> MxfsResult<usize> {
+        let inode = self.load_typed(inode_id, InodeType::File)?;
+        self.read_data(&inode, offset, buffer)
+    }
+
+    /**
+     * Writes the given buffer at the given offset of the given file,
+     * growing it when needed.
+     *
+     * Each touched data extent is committed by its own transaction
+     */
+    pub fn write_at(&mut self,
+                    inode_id: InodeId,
+                    offset: u64,
+                    buffer: &[u8])
+                    -> MxfsResult<usize> {
+        offset.checked_add(buffer.len() as u64).ok_or(MxfsError::InvalidArgument)?;
+
+        let chunk_span = self.m_super_block.chunk_span();
+        let mut written = 0;
+        while written < buffer.len() {
+            let position = offset + written as u64;
+            let chunk_index = position / chunk_span;
+            let chunk_offset = (position % chunk_span) as usize;
+            let part_len =
+                (chunk_span as usize - chunk_offset).min(buffer.len() - written);
+            let part = &buffer[written..written + part_len];
+
+            self.transaction(|volume| {
+                let mut inode = volume.load_typed(inode_id, InodeType::File)?;
+                let extent_record =
+                    BTree::<ExtentRecord>::new(inode.tree_root()).find(volume,
+                                                                       &chunk_index)?;
+
+                let mut chunk_data = match extent_record {
+                    Some(extent_record) => volume.read_chunk(extent_record.address())?,
+                    None => Vec::new()
+                };
+                if chunk_data.len() < chunk_offset + part_len {
+                    chunk_data.resize(chunk_offset + part_len, 0);
+                }
+                chunk_data[chunk_offset..chunk_offset + part_len].copy_from_slice(part);
+
+                volume.replace_chunk(inode_id,
+                                     &mut inode,
+                                     chunk_index,
+                                     extent_record,
+                                     &chunk_data)?;
+
+                inode.set_size(inode.size().max(position + part_len as u64));
+                inode.touch_data(volume.now());
+                volume.store_inode(inode_id, &inode)
+            })?;
+            written += part_len;
+        }
+        Ok(written)
+    }
+
+    /**
+     * Shrinks or grows the given file to the given size, the grown part
+     * is a hole which reads as zeros
+     */
+    pub fn truncate(&mut self, inode_id: InodeId, size: u64) -> MxfsResult<()> {
+        let chunk_span = self.m_super_block.chunk_span();
+
+        let old_size =
+            self.transaction(|volume| {
+                let mut inode = volume.load_typed(inode_id, InodeType::File)?;
+                let old_size = inode.size();
+
+                /* cut the extent which contains the new end of data */
+                let chunk_index = size / chunk_span;
+                let chunk_len = (size % chunk_span) as usize;
+                if size < old_size && chunk_len > 0 {
+                    let extent_record =
+                        BTree::<ExtentRecord>::new(inode.tree_root()).find(volume,
+                                                                           &chunk_index)?;
+
+                    if let Some(extent_record) = extent_record {
+                        let mut chunk_data = volume.read_chunk(extent_record.address())?;
+                        if chunk_data.len() > chunk_len {
+                            chunk_data.truncate(chunk_len);
+                            volume.replace_chunk(inode_id,
+                                                 &mut inode,
+                                                 chunk_index,
+                                                 Some(extent_record),
+                                                 &chunk_data)?;
+                        }
+                    }
+                }
+
+                inode.set_size(size);
+                inode.touch_data(volume.now());
+                volume.store_inode(inode_id, &inode)?;
+
+                /* the extents after the new end are released by the next transactions */
+                if size < old_size {
+                    volume.add_orphan(inode_id, InodeType::File)?;
+                }
+                Ok(old_size)
+            })?;
+
+        if size < old_size {
+            self.release_orphan(inode_id)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the path pointed by the given symbolic link
+     */
+    pub fn link_target(&mut self, inode_id: InodeId) -> MxfsResult<String> {
+        let inode = self.load_typed(inode_id, InodeType::Link)?;
+
+        let mut raw_target = vec![0; inode.size() as usize];
+        let target_len = self.read_data(&inode, 0, &mut raw_target)?;
+        raw_target.truncate(target_len);
+
+        String::from_utf8(raw_target).map_err(|_| MxfsError::Corrupted)
+    }
+
+    /**
+     * Changes the owner user and group of the given inode
+     */
+    pub fn set_owner(&mut self,
+                     inode_id: InodeId,
+                     os_user_id: u64,
+                     os_group_id: u64)
+                     -> MxfsResult<()> {
+        self.update_inode(inode_id, |inode| {
+                inode.set_owner(os_user_id, os_group_id);
+                Ok(())
+            })
+    }
+
+    /**
+     * Changes the raw protection grants of the given inode
+     */
+    pub fn set_prot_grants(&mut self,
+                           inode_id: InodeId,
+                           prot_grants: u64)
+                           -> MxfsResult<()> {
+        self.update_inode(inode_id, |inode| {
+                inode.set_prot_grants(prot_grants);
+                Ok(())
+            })
+    }
+
+    /**
+     * Changes the `C_INODE_FLAG_*` flags of the given inode
+     */
+    pub fn set_flags(&mut self, inode_id: InodeId, flags: u8) -> MxfsResult<()> {
+        self.update_inode(inode_id, |inode| inode.set_flags(flags))
+    }
+
+    /**
+     * Changes the `Compression` of the new data extents of the given
+     * inode, the existing ones keep theirs until they are rewritten
+     */
+    pub fn set_compression(&mut self,
+                           inode_id: InodeId,
+                           compression: Compression)
+                           -> MxfsResult<()> {
+        self.update_inode(inode_id, |inode| {
+                inode.set_compression(compression);
+                Ok(())
+            })
+    }
+
+    /**
+     * Waits until the committed transactions are persistent
+     */
+    pub fn sync(&mut self) -> MxfsResult<()> {
+        self.m_device.flush()
+    }
+}
+
+impl<D: TMxfsDevice> Volume<D> /* Getters */ {
+    /**
+     * Returns the `InodeId` of the root directory
+     */
+    pub fn root(&self) -> InodeId {
+        self.m_super_block.root_inode()
+    }
+
+    /**
+     * Returns the `SuperBlock`
+     */
+    pub fn super_block(&self) -> &SuperBlock {
+        &self.m_super_block
+    }
+
+    /**
+     * Returns the amount of free blocks
+     */
+    pub fn free_blocks(&self) -> u64 {
+        self.m_free_blocks
+    }
+
+    /**
+     * Returns whether the volume refuses any modification
+     */
+    pub fn is_read_only(&self) -> bool {
+        self.m_is_read_only
+    }
+
+    /**
+     * Returns the `TMxfsDevice` which contains the volume
+     */
+    pub fn device(&self) -> &D {
+        &self.m_device
+    }
+}
+
+impl<D: TMxfsDevice> Volume<D> /* Privates */ {
+    /**
+     * Runs the given operation into a transaction, which is committed when
+     * it succeeds and discarded otherwise.
+     *
+     * A failed commit leaves the device in an unknown state, so the
+     * volume becomes read-only until the next mount replays the journal
+     */
+    fn transaction<T>(&mut self,
+                      operation: impl FnOnce(&mut Self) -> MxfsResult<T>)
+                      -> MxfsResult<T> {
+        if self.m_is_read_only {
+            return Err(MxfsError::ReadOnly);
+        }
+
+        let result = match operation(self) {
+            Ok(result) => result,
+            Err(error) => {
+                self.abort();
+                return Err(error);
+            }
+        };
+
+        if let Err(error) = self.commit() {
+            self.abort();
+            if error != MxfsError::TransactionTooBig {
+                self.m_is_read_only = true;
+            }
+            return Err(error);
+        }
+        Ok(result)
+    }
+
+    /**
+     * Commits the running transaction through the `Journal`
+     */
+    fn commit(&mut self) -> MxfsResult<()> {
+        for freed_blocks in core::mem::take(&mut self.m_pending_frees).into_iter() {
+            self.m_free_blocks += freed_blocks.end - freed_blocks.start;
+            self.set_blocks_state(freed_blocks, false)?;
+        }
+
+        if self.m_cache.dirty_count() > 0 {
+            if self.m_cache.dirty_count() > self.m_journal.capacity() {
+                return Err(MxfsError::TransactionTooBig);
+            }
+
+            /* the data extents must be persistent before the metadata */
+            self.m_device.flush()?;
+
+            let dirty_blocks = self.m_cache.take_dirty();
+            self.m_journal.write(&self.m_device,
+                                  self.m_super_block.journal_sequence(),
+                                  &dirty_blocks)?;
+            Self::checkpoint(&self.m_device,
+                             &mut self.m_super_block,
+                             dirty_blocks.iter().map(|(block, raw_block)| {
+                                                    (*block, raw_block.as_slice())
+                                                }))?;
+            self.m_cache.commit_done(dirty_blocks);
+        }
+
+        self.m_committed_free_blocks = self.m_free_blocks;
+        Ok(())
+    }
+
+    /**
+     * Throws away the modifications of the running transaction
+     */
+    fn abort(&mut self) {
+        self.m_cache.discard();
+        self.m_pending_frees.clear();
+        self.m_free_blocks = self.m_committed_free_blocks;
+    }
+
+    /**
+     * Creates a new inode of the given type linked into the given
+     * directory with the given name, into the running transaction
+     */
+    fn create_inode(&mut self,
+                    dir_id: InodeId,
+                    name: &str,
+                    inode_type: InodeType)
+                    -> MxfsResult<(InodeId, Inode)> {
+        check_name(name)?;
+
+        let mut dir_inode = self.load_user_dir(dir_id)?;
+        if self.find_entry(&dir_inode, name)?.is_some() {
+            return Err(MxfsError::AlreadyExists);
+        }
+
+        let inode_id = self.alloc_inode()?;
+        let inode =
+            Inode::new(inode_type, self.m_super_block.compression(), dir_id, self.now());
+        self.store_inode(inode_id, &inode)?;
+
+        self.insert_entry(dir_id,
+                          &mut dir_inode,
+                          DirRecord::new(name, inode_id, inode_type))?;
+        Ok((inode_id, inode))
+    }
+
+    /**
+     * Loads, updates and stores the given inode into a transaction
+     */
+    fn update_inode(&mut self,
+                    inode_id: InodeId,
+                    updater: impl FnOnce(&mut Inode) -> MxfsResult<()>)
+                    -> MxfsResult<()> {
+        self.transaction(|volume| {
+                let mut inode = volume.inode(inode_id)?;
+
+                updater(&mut inode)?;
+                inode.touch_info(volume.now());
+                volume.store_inode(inode_id, &inode)
+            })
+    }
+
+    /**
+     * Lists the given inode into the orphans directory, which keeps the
+     * inodes with data extents to release after a crash
+     */
+    fn add_orphan(&mut self, inode_id: InodeId, inode_type: InodeType) -> MxfsResult<()> {
+        let orphans_id = self.m_super_block.orphans_inode();
+        let orphan_name = format!("{}", inode_id);
+
+        let mut orphans_inode = self.load_typed(orphans_id, InodeType::Dir)?;
+        if self.find_entry(&orphans_inode, &orphan_name)?.is_some() {
+            return Ok(());
+        }
+        self.insert_entry(orphans_id,
+                          &mut orphans_inode,
+                          DirRecord::new(&orphan_name, inode_id, inode_type))
+    }
+
+    /**
+     * Releases the data extents of the given orphan inode after its end of
+     * data, or all of them with the inode itself when it has no more links
+     */
+    fn release_orphan(&mut self, inode_id: InodeId) -> MxfsResult<()> {
+        let inode = self.inode(inode_id)?;
+
+        let chunk_span = self.m_super_block.chunk_span();
+        let first_chunk = if inode.links() == 0 {
+            0
+        } else {
+            inode.size().div_ceil(chunk_span)
+        };
+        self.release_chunks_from(inode_id, first_chunk)?;
+
+        self.transaction(|volume| {
+                let orphans_id = volume.m_super_block.orphans_inode();
+                let mut orphans_inode = volume.load_typed(orphans_id, InodeType::Dir)?;
+                volume.remove_entry(orphans_id,
+                                    &mut orphans_inode,
+                                    &format!("{}", inode_id))?;
+
+                if volume.inode(inode_id)?.links() == 0 {
+                    volume.store_inode(inode_id, &Inode::new_free())?;
+                }
+                Ok(())
+            })
+    }
+
+    /**
+     * Releases all the inodes listed into the orphans directory
+     */
+    fn release_orphans(&mut self) -> MxfsResult<()> {
+        let mut orphan_ids = Vec::new();
+        self.for_each_entry(self.m_super_block.orphans_inode(), &mut |dir_record| {
+                orphan_ids.push(dir_record.inode());
+                Ok(true)
+            })?;
+
+        for orphan_id in orphan_ids.into_iter() {
+            self.release_orphan(orphan_id)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the directory with the given `InodeId` which can be modified
+     * by the users, so any directory except the orphans one
+     */
+    fn load_user_dir(&mut self, dir_id: InodeId) -> MxfsResult<Inode> {
+        if dir_id == self.m_super_block.orphans_inode() {
+            return Err(MxfsError::NotFound);
+        }
+        self.load_typed(dir_id, InodeType::Dir)
+    }
+
+    /**
+     * Returns the `DirRecord` with the given name of the given directory
+     * `Inode`
+     */
+    fn find_entry(&mut self,
+                  dir_inode: &Inode,
+                  name: &str)
+                  -> MxfsResult<Option<DirRecord>> {
+        let dir_key: DirKey = (name_hash(name), String::from(name));
+        BTree::<DirRecord>::new(dir_inode.tree_root()).find(self, &dir_key)
+    }
+
+    /**
+     * Inserts the given `DirRecord` into the given directory
+     */
+    fn insert_entry(&mut self,
+                    dir_id: InodeId,
+                    dir_inode: &mut Inode,
+                    dir_record: DirRecord)
+                    -> MxfsResult<()> {
+        self.m_alloc_pool = self.pool_of_inode(dir_id);
+        self.update_tree(dir_inode, |volume, tree: &mut BTree<DirRecord>| {
+                tree.insert(volume, dir_record)?;
+                Ok(())
+            })?;
+
+        dir_inode.set_size(dir_inode.size() + 1);
+        dir_inode.touch_data(self.now());
+        self.store_inode(dir_id, dir_inode)
+    }
+
+    /**
+     * Removes the entry with the given name from the given directory
+     */
+    fn remove_entry(&mut self,
+                    dir_id: InodeId,
+                    dir_inode: &mut Inode,
+                    name: &str)
+                    -> MxfsResult<()> {
+        let dir_key: DirKey = (name_hash(name), String::from(name));
+        let removed_record = self.update_tree(dir_inode,
+                                              |volume, tree: &mut BTree<DirRecord>| {
+                                                  tree.remove(volume, &dir_key)
+                                              })?;
+        if removed_record.is_none() {
+            return Err(MxfsError::NotFound);
+        }
+
+        dir_inode.set_size(dir_inode.size().saturating_sub(1));
+        dir_inode.touch_data(self.now());
+        self.store_inode(dir_id, dir_inode)
+    }
+
+    /**
+     * Returns whether the given directory is the given inode or one of its
+     * ancestors
+     */
+    fn is_ancestor_of(&mut self, dir_id: InodeId, inode_id: InodeId) -> MxfsResult<bool> {
+        let root_id = self.m_super_block.root_inode();
+
+        let mut ancestor_id = inode_id;
+        for _ in 0..self.m_super_block.inodes_count() {
+            if ancestor_id == dir_id {
+                return Ok(true);
+            }
+            if ancestor_id == root_id {
+                return Ok(false);
+            }
+            ancestor_id = self.load_typed(ancestor_id, InodeType::Dir)?.parent();
+        }
+
+        /* the parents form a cycle */
+        Err(MxfsError::Corrupted)
+    }
+
+    /**
+     * Runs the given operation on the tree of the given `Inode`, updating
+     * its root and its used blocks
+     */
+    fn update_tree<R: TTreeRecord, T>(&mut self,
+                                      inode: &mut Inode,
+                                      operation: impl FnOnce(&mut Self,
+                                             &mut BTree<R>)
+                                             -> MxfsResult<T>)
+                                      -> MxfsResult<T> {
+        let mut tree = BTree::new(inode.tree_root());
+        let tree_blocks_delta = self.m_tree_blocks_delta;
+
+        let result = operation(self, &mut tree)?;
+
+        let blocks_delta = self.m_tree_blocks_delta - tree_blocks_delta;
+        inode.set_tree_root(tree.root());
+        inode.set_blocks_used((inode.blocks_used() as i64 + blocks_delta) as u64);
+        Ok(result)
+    }
+
+    /**
+     * Reads the data of the given file or symbolic link from the given
+     * offset into the given buffer
+     */
+    fn read_data(&mut self,
+                 inode: &Inode,
+                 offset: u64,
+                 buffer: &mut [u8])
+                 -> MxfsResult<usize> {
+        if offset >= inode.size() {
+            return Ok(0);
+        }
+
+        let read_len = (inode.size() - offset).min(buffer.len() as u64) as usize;
+        let chunk_span = self.m_super_block.chunk_span();
+        let first_chunk = offset / chunk_span;
+        let last_chunk = (offset + read_len as u64 - 1) / chunk_span;
+
+        /* collect the extents of the range, the missing ones are holes */
+        let mut extent_records = Vec::new();
+        let extent_tree = BTree::<ExtentRecord>::new(inode.tree_root());
+        extent_tree.for_each_from(self, Some(&first_chunk), &mut |record| {
+                       if record.chunk_index() > last_chunk {
+                           return Ok(false);
+                       }
+
+                       extent_records.push(record);
+                       Ok(true)
+                   })?;
+
+        buffer[..read_len].iter_mut().for_each(|byte| *byte = 0);
+        for extent_record in extent_records.into_iter() {
+            let chunk_data = self.read_chunk(extent_record.address())?;
+            let chunk_start = extent_record.chunk_index() * chunk_span;
+
+            /* intersect the chunk with the read range */
+            let copy_start = offset.max(chunk_start);
+            let copy_end =
+                (offset + read_len as u64).min(chunk_start + chunk_data.len() as u64);
+            if copy_start >= copy_end {
+                continue;
+            }
+
+            let buffer_range =
+                (copy_start - offset) as usize..(copy_end - offset) as usize;
+            let chunk_range =
+                (copy_start - chunk_start) as usize..(copy_end - chunk_start) as usize;
+            buffer[buffer_range].copy_from_slice(&chunk_data[chunk_range]);
+        }
+        Ok(read_len)
+    }
+
+    /**
+     * Stores the given data as the given span of the given `Inode`,
+     * replacing the given extent when any.
+     *
+     * The extent is rewritten in place only when the inode has the
+     * `C_INODE_FLAG_NO_COW` and the extent is not shared, otherwise the data
+     * goes into a new extent
+     */
+    fn replace_chunk(&mut self,
+                     inode_id: InodeId,
+                     inode: &mut Inode,
+                     chunk_index: u64,
+                     old_record: Option<ExtentRecord>,
+                     chunk_data: &[u8])
+                     -> MxfsResult<()> {
+        self.m_alloc_pool = self.pool_of_inode(inode_id);
+
+        let old_extent = match old_record {
+            Some(old_record) => Some((old_record.address(),
+                                      self.read_chunk_header(old_record.address())?)),
+            None => None
+        };
+        let in_place_extent = old_extent.filter(|(_, old_header)| {
+                                            inode.has_flag(C_INODE_FLAG_NO_COW)
+                                            && old_header.ref_count() == 1
+                                        });
+
+        let (address, blocks_count) =
+            self.write_chunk(inode, chunk_data, in_place_extent)?;
+        if let Some((old_address, _)) = old_extent {
+            if old_address == address {
+                return Ok(());
+            }
+        }
+
+        self.update_tree(inode, |volume, tree: &mut BTree<ExtentRecord>| {
+                tree.insert(volume, ExtentRecord::new(chunk_index, address))?;
+                Ok(())
+            })?;
+
+        let mut blocks_used = inode.blocks_used() + blocks_count;
+        if let Some((old_address, _)) = old_extent {
+            blocks_used -= self.release_chunk(old_address)?;
+        }
+        inode.set_blocks_used(blocks_used);
+        Ok(())
+    }
+
+    /**
+     * Removes the extents of the given inode starting from the given span,
+     * in batches of `C_RELEASE_BATCH` for each transaction
+     */
+    fn release_chunks_from(&mut self,
+                           inode_id: InodeId,
+                           first_chunk: u64)
+                           -> MxfsResult<()> {
+        loop {
+            let is_done = self.transaction(|volume| {
+                                  let mut inode = volume.inode(inode_id)?;
+
+                                  let mut extent_records =
+                                      Vec::with_capacity(C_RELEASE_BATCH);
+                                  BTree::<ExtentRecord>::new(inode.tree_root())
+                        .for_each_from(volume, Some(&first_chunk), &mut |record| {
+                            extent_records.push(record);
+                            Ok(extent_records.len() < C_RELEASE_BATCH)
+                        })?;
+                                  if extent_records.is_empty() {
+                                      return Ok(true);
+                                  }
+
+                                  for extent_record in extent_records.iter() {
+                                      volume.update_tree(&mut inode,
+                                           |volume, tree: &mut BTree<ExtentRecord>| {
+                                               tree.remove(volume,
+                                                           &extent_record.chunk_index())
+                                           })?;
+
+                                      let released_blocks =
+                                          volume.release_chunk(extent_record.address())?;
+                                      inode.set_blocks_used(inode.blocks_used()
+                                                   .saturating_sub(released_blocks));
+                                  }
+
+                                  volume.store_inode(inode_id, &inode)?;
+                                  Ok(false)
+                              })?;
+
+            if is_done {
+                return Ok(());
+            }
+        }
+    }
+
+    /**
+     * Reads and decompresses the data extent at the given block
+     */
+    fn read_chunk(&mut self, address: u64) -> MxfsResult<Vec<u8>> {
+        let block_size = self.m_super_block.block_size() as usize;
+        let first_block = self.read_chunk_block(address)?;
+        let chunk_header = self.decode_chunk_header(address, &first_block)?;
+
+        let mut stored_data = vec![0; chunk_header.stored_len()];
+        let first_block_len = stored_data.len().min(block_size - C_CHUNK_HEADER_SIZE);
+        stored_data[..first_block_len].copy_from_slice(&first_block[C_CHUNK_HEADER_SIZE
+                                                                    ..C_CHUNK_HEADER_SIZE
+                                                                      + first_block_len]);
+        if stored_data.len() > first_block_len {
+            self.m_device.read_at((address + 1) * block_size as u64,
+                                   &mut stored_data[first_block_len..])?;
+        }
+
+        if let Some(crc) = chunk_header.crc() {
+            if crc32(&stored_data) != crc {
+                return Err(MxfsError::Corrupted);
+            }
+        }
+
+        let mut chunk_data = vec![0; chunk_header.raw_len()];
+        chunk_header.compression()
+                    .compressor()
+                    .decompress(&stored_data, &mut chunk_data)?;
+        Ok(chunk_data)
+    }
+
+    /**
+     * Compresses and writes the given data into a new data extent, or into
+     * the given one when the data fits it.
+     *
+     * Returns the first block and the amount of blocks of the extent
+     */
+    fn write_chunk(&mut self,
+                   inode: &Inode,
+                   chunk_data: &[u8],
+                   in_place_extent: Option<(u64, ChunkHeader)>)
+                   -> MxfsResult<(u64, u64)> {
+        let block_size = self.m_super_block.block_size();
+
+        /* the data which doesn't shrink is stored as is */
+        let mut compression = inode.compression();
+        let mut stored_data = Vec::new();
+        if !compression.compressor().compress(chunk_data, &mut stored_data) {
+            compression = Compression::None;
+            stored_data.clear();
+            stored_data.extend_from_slice(chunk_data);
+        }
+
+        let crc = if inode.has_flag(C_INODE_FLAG_NO_CRC) {
+            None
+        } else {
+            Some(crc32(&stored_data))
+        };
+        let blocks_exp = blocks_exp_for(stored_data.len(), block_size);
+        let chunk_header = ChunkHeader::new(blocks_exp,
+                                            compression,
+                                            crc,
+                                            stored_data.len() as u32,
+                                            chunk_data.len() as u32);
+
+        let address = match in_place_extent {
+            Some((address, old_header)) if old_header.blocks_exp() == blocks_exp => {
+                address
+            },
+            _ => self.alloc_blocks(chunk_header.blocks_count())?
+        };
+
+        /* write only the blocks which contain the header and the data */
+        let written_len = C_CHUNK_HEADER_SIZE + stored_data.len();
+        let written_blocks = (written_len as u64).div_ceil(block_size);
+        let mut raw_chunk = vec![0; (written_blocks * block_size) as usize];
+        chunk_header.encode(&mut raw_chunk);
+        raw_chunk[C_CHUNK_HEADER_SIZE..written_len].copy_from_slice(&stored_data);
+
+        self.m_device.write_at(address * block_size, &raw_chunk)?;
+        for block in address..address + chunk_header.blocks_count() {
+            self.m_cache.invalidate(block);
+        }
+        Ok((address, chunk_header.blocks_count()))
+    }
+
+    /**
+     * Drops a reference to the data extent at the given block, releasing
+     * it with the last one.
+     *
+     * Returns the amount of blocks of the extent
+     */
+    fn release_chunk(&mut self, address: u64) -> MxfsResult<u64> {
+        let mut first_block = self.read_chunk_block(address)?;
+        let mut chunk_header = self.decode_chunk_header(address, &first_block)?;
+
+        /* the header of the shared extents is journaled like the metadata */
+        if chunk_header.ref_count() > 1 {
+            chunk_header.set_ref_count(chunk_header.ref_count() - 1);
+            chunk_header.encode(&mut first_block);
+            self.m_cache.write(address, first_block);
+        } else {
+            self.release_blocks(address..address + chunk_header.blocks_count());
+        }
+        Ok(chunk_header.blocks_count())
+    }
+
+    /**
+     * Reads the `ChunkHeader` of the data extent at the given block
+     */
+    fn read_chunk_header(&mut self, address: u64) -> MxfsResult<ChunkHeader> {
+        let first_block = self.read_chunk_block(address)?;
+        self.decode_chunk_header(address, &first_block)
+    }
+
+    /**
+     * Decodes and validates the `ChunkHeader` of the data extent at the
+     * given block
+     */
+    fn decode_chunk_header(&self,
+                           address: u64,
+                           first_block: &[u8])
+                           -> MxfsResult<ChunkHeader> {
+        let chunk_header = ChunkHeader::decode(first_block)?;
+
+        let extent_size = chunk_header.blocks_count() * self.m_super_block.block_size();
+        if chunk_header.blocks_exp() > self.m_super_block.chunk_exp()
+           || (chunk_header.stored_len() + C_CHUNK_HEADER_SIZE) as u64 > extent_size
+           || chunk_header.raw_len() as u64 > self.m_super_block.chunk_span()
+        {
+            return Err(MxfsError::Corrupted);
+        }
+
+        self.check_data_blocks(address, chunk_header.blocks_count())?;
+        Ok(chunk_header)
+    }
+
+    /**
+     * Reads the first block of the data extent at the given block, which
+     * may have a journaled header into the running transaction
+     */
+    fn read_chunk_block(&mut self, address: u64) -> MxfsResult<Vec<u8>> {
+        self.check_data_blocks(address, 1)?;
+
+        if self.m_cache.is_dirty(address) {
+            return self.m_cache.read(&self.m_device, address);
+        }
+
+        let block_size = self.m_super_block.block_size();
+        let mut first_block = vec![0; block_size as usize];
+        self.m_device.read_at(address * block_size, &mut first_block)?;
+        Ok(first_block)
+    }
+
+    /**
+     * Checks whether the given blocks are into the data area of a pool
+     */
+    fn check_data_blocks(&self, first_block: u64, blocks_count: u64) -> MxfsResult<()> {
+        let pool = self.pool_of_block(first_block).ok_or(MxfsError::Corrupted)?;
+        if first_block < pool.data_start()
+           || first_block + blocks_count > pool.blocks().end
+        {
+            return Err(MxfsError::Corrupted);
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the `Inode` with the given `InodeId`, which must be of the
+     * given type
+     */
+    fn load_typed(&mut self,
+                  inode_id: InodeId,
+                  inode_type: InodeType)
+                  -> MxfsResult<Inode> {
+        let inode = self.inode(inode_id)?;
+        if inode.inode_type() != inode_type {
+            return Err(MxfsError::TypesNotMatch);
+        }
+        Ok(inode)
+    }
+
+    /**
+     * Returns the `Inode` with the given `InodeId`, even when free
+     */
+    fn load_inode(&mut self, inode_id: InodeId) -> MxfsResult<Inode> {
+        let (block, offset) = self.inode_position(inode_id)?;
+        let raw_block = self.m_cache.read(&self.m_device, block)?;
+
+        Inode::decode(&raw_block[offset..offset + C_INODE_SIZE])
+    }
+
+    /**
+     * Stores the given `Inode` into the running transaction
+     */
+    fn store_inode(&mut self, inode_id: InodeId, inode: &Inode) -> MxfsResult<()> {
+        let (block, offset) = self.inode_position(inode_id)?;
+        let mut raw_block = self.m_cache.read(&self.m_device, block)?;
+
+        inode.encode(&mut raw_block[offset..offset + C_INODE_SIZE]);
+        self.m_cache.write(block, raw_block);
+        Ok(())
+    }
+
+    /**
+     * Returns the first free `InodeId`, searching from the last allocated
+     */
+    fn alloc_inode(&mut self) -> MxfsResult<InodeId> {
+        let inodes_count = self.m_super_block.inodes_count();
+
+        for i in 0..inodes_count {
+            let inode_id = (self.m_inode_hint - 1 + i) % inodes_count + 1;
+            let (block, offset) = self.inode_position(inode_id)?;
+
+            if self.m_cache.read(&self.m_device, block)?[offset] == InodeType::Free as u8
+            {
+                self.m_inode_hint = inode_id % inodes_count + 1;
+                return Ok(inode_id);
+            }
+        }
+        Err(MxfsError::NoInodes)
+    }
+
+    /**
+     * Returns the block and the offset into it of the given inode
+     */
+    fn inode_position(&self, inode_id: InodeId) -> MxfsResult<(u64, usize)> {
+        if inode_id == 0 || inode_id > self.m_super_block.inodes_count() {
+            return Err(MxfsError::NotFound);
+        }
+
+        let inode_offset = (inode_id - 1) * C_INODE_SIZE as u64;
+        let block_size = self.m_super_block.block_size();
+        Ok((self.m_super_block.inode_table().start + inode_offset / block_size,
+            (inode_offset % block_size) as usize))
+    }
+
+    /**
+     * Allocates the given amount of contiguous blocks, starting from the
+     * pool selected for the running operation
+     */
+    fn alloc_blocks(&mut self, blocks_count: u64) -> MxfsResult<u64> {
+        for i in 0..self.m_pools.len() {
+            let pool_index = (self.m_alloc_pool + i) % self.m_pools.len();
+
+            if let Some(first_block) = self.find_free_run(pool_index, blocks_count)? {
+                let blocks = first_block..first_block + blocks_count;
+
+                self.set_blocks_state(blocks, true)?;
+                self.m_free_blocks -= blocks_count;
+                return Ok(first_block);
+            }
+        }
+        Err(MxfsError::NoSpace)
+    }
+
+    /**
+     * Releases the given blocks with the commit of the running
+     * transaction, so they can't be reused before
+     */
+    fn release_blocks(&mut self, blocks: Range<u64>) {
+        self.m_pending_frees.push(blocks);
+    }
+
+    /**
+     * Returns the first block of a run of the given amount of free blocks
+     * into the given pool
+     */
+    fn find_free_run(&mut self,
+                     pool_index: usize,
+                     blocks_count: u64)
+                     -> MxfsResult<Option<u64>> {
+        let bits_per_block = self.m_super_block.block_size() * 8;
+        let pool_blocks = self.m_pools[pool_index].blocks();
+        let pool_bitmap = self.m_pools[pool_index].bitmap();
+
+        let mut bitmap_block = None;
+        let mut run_start = self.m_pools[pool_index].data_start();
+        let mut block = run_start;
+        while block < pool_blocks.end {
+            let bit_index = block - pool_blocks.start;
+            let bitmap_block_index = pool_bitmap.start + bit_index / bits_per_block;
+
+            if bitmap_block.as_ref().is_none_or(|(index, _)| *index != bitmap_block_index)
+            {
+                let raw_bitmap = self.m_cache.read(&self.m_device, bitmap_block_index)?;
+                bitmap_block = Some((bitmap_block_index, raw_bitmap));
+            }
+
+            let raw_bitmap = &bitmap_block.as_ref().unwrap().1;
+            let bit_offset = (bit_index % bits_per_block) as usize;
+
+            /* skip the bytes of used blocks */
+            if bit_offset.is_multiple_of(8) && raw_bitmap[bit_offset / 8] == 0xFF {
+                block += 8;
+                run_start = block;
+                continue;
+            }
+
+            if raw_bitmap[bit_offset / 8] & (1 << (bit_offset % 8)) != 0 {
+                run_start = block + 1;
+            } else if block + 1 - run_start == blocks_count {
+                return Ok(Some(run_start));
+            }
+            block += 1;
+        }
+        Ok(None)
+    }
+
+    /**
+     * Marks the given blocks as used or as free into the bitmaps
+     */
+    fn set_blocks_state(&mut self, blocks: Range<u64>, is_used: bool) -> MxfsResult<()> {
+        let bits_per_block = self.m_super_block.block_size() * 8;
+
+        let mut bitmap_block: Option<(u64, Vec<u8>)> = None;
+        for block in blocks {
+            let pool = self.pool_of_block(block).ok_or(MxfsError::Corrupted)?;
+            let bit_index = block - pool.blocks().start;
+            let bitmap_block_index = pool.bitmap().start + bit_index / bits_per_block;
+
+            if bitmap_block.as_ref().is_none_or(|(index, _)| *index != bitmap_block_index)
+            {
+                if let Some((index, raw_bitmap)) = bitmap_block.take() {
+                    self.m_cache.write(index, raw_bitmap);
+                }
+
+                let raw_bitmap = self.m_cache.read(&self.m_device, bitmap_block_index)?;
+                bitmap_block = Some((bitmap_block_index, raw_bitmap));
+            }
+
+            let raw_bitmap = &mut bitmap_block.as_mut().unwrap().1;
+            let bit_offset = (bit_index % bits_per_block) as usize;
+            if is_used {
+                raw_bitmap[bit_offset / 8] |= 1 << (bit_offset % 8);
+            } else {
+                raw_bitmap[bit_offset / 8] &= !(1 << (bit_offset % 8));
+            }
+        }
+
+        if let Some((index, raw_bitmap)) = bitmap_block {
+            self.m_cache.write(index, raw_bitmap);
+        }
+        Ok(())
+    }
+
+    /**
+     * Counts the free blocks of all the pools
+     */
+    fn count_free_blocks(&mut self) -> MxfsResult<u64> {
+        let block_size = self.m_super_block.block_size();
+
+        let mut free_blocks = 0;
+        for pool_index in 0..self.m_pools.len() {
+            let pool_blocks = self.m_pools[pool_index].blocks();
+            let pool_bitmap = self.m_pools[pool_index].bitmap();
+
+            for bitmap_block in pool_bitmap.clone() {
+                let raw_bitmap = self.m_cache.read(&self.m_device, bitmap_block)?;
+                let first_bit = (bitmap_block - pool_bitmap.start) * block_size * 8;
+                let bits_count =
+                    (pool_blocks.end - pool_blocks.start - first_bit).min(block_size * 8);
+
+                free_blocks += count_zero_bits(&raw_bitmap, bits_count as usize);
+            }
+        }
+        Ok(free_blocks)
+    }
+
+    /**
+     * Returns the `PoolHeader` which contains the given block
+     */
+    fn pool_of_block(&self, block: u64) -> Option<&PoolHeader> {
+        let first_pool = self.m_super_block.first_pool();
+        if block < first_pool {
+            return None;
+        }
+
+        let pool_index = (block - first_pool) / self.m_super_block.pool_blocks();
+        self.m_pools
+            .get(pool_index as usize)
+            .filter(|pool| pool.blocks().contains(&block))
+    }
+
+    /**
+     * Returns the index of the pool which stores the blocks of the given
+     * inode
+     */
+    fn pool_of_inode(&self, inode_id: InodeId) -> usize {
+        (inode_id % self.m_pools.len() as u64) as usize
+    }
+
+    /**
+     * Returns the current time in nanoseconds since the Unix epoch
+     */
+    fn now(&self) -> u64 {
+        (self.m_clock)()
+    }
+}
+
+impl<D: TMxfsDevice> Volume<D> /* Static Functions */ {
+    /**
+     * Copies the given journaled blocks to their home, then makes the
+     * transaction stale by advancing the sequence of the `SuperBlock`
+     */
+    fn checkpoint<'a>(device: &dyn TMxfsDevice,
+                      super_block: &mut SuperBlock,
+                      blocks: impl Iterator<Item = (u64, &'a [u8])>)
+                      -> MxfsResult<()> {
+        let block_size = super_block.block_size();
+        for (block, raw_block) in blocks {
+            device.write_at(block * block_size, raw_block)?;
+        }
+        device.flush()?;
+
+        super_block.set_journal_sequence(super_block.journal_sequence() + 1);
+        super_block.write(device)?;
+        device.flush()
+    }
+
+    /**
+     * Loads the chain of the extent pools, which must cover the blocks
+     * after the first pool in order
+     */
+    fn load_pools(device: &dyn TMxfsDevice,
+                  super_block: &SuperBlock)
+                  -> MxfsResult<Vec<PoolHeader>> {
+        let block_size = super_block.block_size();
+
+        let mut pools = Vec::new();
+        let mut pool_block = super_block.first_pool();
+        while pool_block != 0 {
+            let expected_block =
+                super_block.first_pool() + pools.len() as u64 * super_block.pool_blocks();
+            if pool_block != expected_block || pool_block >= super_block.blocks_count() {
+                return Err(MxfsError::Corrupted);
+            }
+
+            let mut raw_header = vec![0; block_size as usize];
+            device.read_at(pool_block * block_size, &mut raw_header)?;
+
+            let pool = PoolHeader::decode(&raw_header, pool_block)?;
+            if pool.blocks().end > super_block.blocks_count()
+               || pool.blocks().end - pool.blocks().start > super_block.pool_blocks()
+            {
+                return Err(MxfsError::Corrupted);
+            }
+
+            pool_block = pool.next_pool();
+            pools.push(pool);
+        }
+        Ok(pools)
+    }
+}
+
+impl<D: TMxfsDevice> TTreeStore for Volume<D> {
+    fn node_size(&self) -> usize {
+        self.m_super_block.block_size() as usize
+    }
+
+    fn read_node(&mut self, block: u64) -> MxfsResult<Vec<u8>> {
+        self.check_data_blocks(block, 1)?;
+        self.m_cache.read(&self.m_device, block)
+    }
+
+    fn write_node(&mut self, block: u64, raw_node: Vec<u8>) -> MxfsResult<()> {
+        self.m_cache.write(block, raw_node);
+        Ok(())
+    }
+
+    fn alloc_node(&mut self) -> MxfsResult<u64> {
+        let block = self.alloc_blocks(1)?;
+
+        self.m_tree_blocks_delta += 1;
+        Ok(block)
+    }
+
+    fn free_node(&mut self, block: u64) -> MxfsResult<()> {
+        self.release_blocks(block..block + 1);
+
+        self.m_tree_blocks_delta -= 1;
+        Ok(())
+    }
+}
+
+/**
+ * Counts the zero bits between the first given bits of the given bitmap
+ */
+pub fn count_zero_bits(raw_bitmap: &[u8], bits_count: usize) -> u64 {
+    let full_bytes = bits_count / 8;
+
+    let mut zero_bits: u64 =
+        raw_bitmap[..full_bytes].iter().map(|byte| byte.count_zeros() as u64).sum();
+    for bit in 0..bits_count % 8 {
+        if raw_bitmap[full_bytes] & (1 << bit) == 0 {
+            zero_bits += 1;
+        }
+    }
+    zero_bits
+}
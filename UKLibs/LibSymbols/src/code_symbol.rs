@@ -51,6 +51,16 @@ impl<'a> CodeSymbol<'a> /* Constructors */ {
     }
 }
 
+impl<'a> CodeSymbol<'a> /* Methods */ {
+    /**
+     * Returns this `CodeSymbol` moved by the given slide
+     */
+    pub fn slid_by(self, slide: usize) -> Self {
+        Self { m_virt_addr: self.m_virt_addr + slide,
+               ..self }
+    }
+}
+
 impl<'a> CodeSymbol<'a> /* Getters */ {
     /**
      * Returns the virtual address on which this symbol starts
@@ -42,21 +42,24 @@ pub struct CodeSymbols {
 
 impl CodeSymbols /* Constructors */ {
     /**
-     * Constructs the global `SM_CODE_SYMBOLS` instance
+     * Constructs the global `SM_CODE_SYMBOLS` instance.
+     *
+     * The symbols are written by the build with their link addresses, so
+     * they are moved by the given `text_slide` of the executable
      */
-    pub fn init_instance() {
+    pub fn init_instance(text_slide: usize) {
         unsafe {
             assert!(SM_CODE_SYMBOLS.is_none(),
                     "Tried to re-initialize global CodeSymbols instance");
 
-            SM_CODE_SYMBOLS = Some(Self::new());
+            SM_CODE_SYMBOLS = Some(Self::new(text_slide));
         }
     }
 
     /**
      * Constructs a filled `CodeSymbols`
      */
-    fn new() -> Self {
+    fn new(text_slide: usize) -> Self {
         /* calculate the length of the symbols */
         let symbols_len = str_len(&S_EXE_SYMBOLS_STORAGE);
         assert_ne!(symbols_len, 0,
@@ -75,6 +78,7 @@ impl CodeSymbols /* Constructors */ {
                    symbols_str_slice.split("\n")
                                     .map(CodeSymbol::from_raw_line)
                                     .filter_map(|opt_code_symbol| opt_code_symbol)
+                                    .map(|code_symbol| code_symbol.slid_by(text_slide))
                                     .collect() }
     }
 }
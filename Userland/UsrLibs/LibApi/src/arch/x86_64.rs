@@ -7,11 +7,18 @@ use api_data::sys::{
 
 /**
  * Performs the `syscall` instruction to switch to the kernel with the given
- * payload
+ * payload.
+ *
+ * The CPU overwrites `rcx` and `r11` with the return address and the flags,
+ * the kernel preserves all the other registers
  */
 #[inline(always)]
 pub(crate) fn do_syscall(syscall_payload: &mut SysCallPayload) {
     unsafe {
-        asm!("syscall", in("rax") syscall_payload.as_syscall_ptr(), options(nostack));
+        asm!("syscall",
+             in("rax") syscall_payload.as_syscall_ptr_mut(),
+             out("rcx") _,
+             out("r11") _,
+             options(nostack));
     }
 }
@@ -69,6 +69,8 @@ MV      ?= $(shell which mv)
 MKDIR   ?= $(shell which mkdir)
 RFILT   ?= $(shell which rustfilt)
 OBJCOPY ?= $(shell which objcopy)
+FIND    ?= $(shell which find)
+CPIO    ?= $(shell which cpio)
 
 ifeq ($(ARCH), x86_64)
     MAKE_RESCUE ?= $(shell which grub-mkrescue)